@@ -0,0 +1,228 @@
+use static_init::dynamic;
+
+use crate::bits::bb_classical::ClassicalBitboard;
+use crate::bits::bitboard::Bitboard;
+use crate::bits::square::Square;
+
+#[dynamic]
+static STATIC_INSTANCE: Box<Magic> = Magic::new_box();
+
+impl Magic {
+    // doesnt impl Default as too large to copy by value
+    #[inline]
+    pub fn instance() -> &'static Self {
+        &STATIC_INSTANCE
+    }
+}
+
+/// the relevant occupancy of a slider on a square, and where its attacks start in the table
+#[derive(Copy, Clone, Debug, Default)]
+struct MagicEntry {
+    mask:   Bitboard,
+    magic:  u64,
+    shift:  u32,
+    offset: usize,
+}
+
+impl MagicEntry {
+    /// with bmi2 the occupancy bits are extracted by pext, and the magic is unused
+    #[inline]
+    fn index(&self, occ: Bitboard) -> usize {
+        #[cfg(all(target_arch = "x86_64", target_feature = "bmi2"))]
+        #[allow(unused_unsafe)]
+        // safe as compiled for bmi2
+        let i = unsafe { std::arch::x86_64::_pext_u64(occ.bits(), self.mask.bits()) };
+        #[cfg(not(all(target_arch = "x86_64", target_feature = "bmi2")))]
+        let i = (occ & self.mask).bits().wrapping_mul(self.magic) >> self.shift;
+        self.offset + i as usize
+    }
+}
+
+/// Fancy magic bitboards: the attacks of a slider are looked up by its square and the
+/// occupancy of the squares it could move through, hashed by a magic multiply (or by pext
+/// when compiled for bmi2). The tables (~840KB) are built at startup from magics found
+/// offline, each checked for harmful collisions as its attacks are added.
+#[derive(Clone, Debug)]
+pub struct Magic {
+    rook:    [MagicEntry; 64],
+    bishop:  [MagicEntry; 64],
+    attacks: Vec<Bitboard>,
+}
+
+impl Magic {
+    #[rustfmt::skip]
+    const ROOK_MAGICS: [u64; 64] = [
+        0x0a80004000801220, 0x8040004010002008, 0x2080200010008008, 0x1100100008210004,
+        0xc200209084020008, 0x2100010004000208, 0x0400081000822421, 0x0200010422048844,
+        0x0800800080400024, 0x0001402000401000, 0x3000801000802001, 0x4400800800100083,
+        0x0904802402480080, 0x4040800400020080, 0x0018808042000100, 0x4040800080004100,
+        0x0040048001458024, 0x00a0004000205000, 0x3100808010002000, 0x4825010010000820,
+        0x5004808008000401, 0x2024818004000a00, 0x0005808002000100, 0x2100060004806104,
+        0x0080400880008421, 0x4062220600410280, 0x010a004a00108022, 0x0000100080080080,
+        0x0021000500080010, 0x0044000202001008, 0x0000100400080102, 0xc020128200040545,
+        0x0080002000400040, 0x0000804000802004, 0x0000120022004080, 0x010a386103001001,
+        0x9010080080800400, 0x8440020080800400, 0x0004228824001001, 0x000000490a000084,
+        0x0080002000504000, 0x200020005000c000, 0x0012088020420010, 0x0010010080080800,
+        0x0085001008010004, 0x0002000204008080, 0x0040413002040008, 0x0000304081020004,
+        0x0080204000800080, 0x3008804000290100, 0x1010100080200080, 0x2008100208028080,
+        0x5000850800910100, 0x8402019004680200, 0x0120911028020400, 0x0000008044010200,
+        0x0020850200244012, 0x0020850200244012, 0x0000102001040841, 0x140900040a100021,
+        0x000200282410a102, 0x000200282410a102, 0x000200282410a102, 0x4048240043802106,
+    ];
+
+    #[rustfmt::skip]
+    const BISHOP_MAGICS: [u64; 64] = [
+        0x40106000a1160020, 0x0020010250810120, 0x2010010220280081, 0x002806004050c040,
+        0x0002021018000000, 0x2001112010000400, 0x0881010120218080, 0x1030820110010500,
+        0x0000120222042400, 0x2000020404040044, 0x8000480094208000, 0x0003422a02000001,
+        0x000a220210100040, 0x8004820202226000, 0x0018234854100800, 0x0100004042101040,
+        0x0004001004082820, 0x0010000810010048, 0x1014004208081300, 0x2080818802044202,
+        0x0040880c00a00100, 0x0080400200522010, 0x0001000188180b04, 0x0080249202020204,
+        0x1004400004100410, 0x00013100a0022206, 0x2148500001040080, 0x4241080011004300,
+        0x4020848004002000, 0x10101380d1004100, 0x0008004422020284, 0x01010a1041008080,
+        0x0808080400082121, 0x0808080400082121, 0x0091128200100c00, 0x0202200802010104,
+        0x8c0a020200440085, 0x01a0008080b10040, 0x0889520080122800, 0x100902022202010a,
+        0x04081a0816002000, 0x0000681208005000, 0x8170840041008802, 0x0a00004200810805,
+        0x0830404408210100, 0x2602208106006102, 0x1048300680802628, 0x2602208106006102,
+        0x0602010120110040, 0x0941010801043000, 0x000040440a210428, 0x0008240020880021,
+        0x0400002012048200, 0x00ac102001210220, 0x0220021002009900, 0x84440c080a013080,
+        0x0001008044200440, 0x0004c04410841000, 0x2000500104011130, 0x1a0c010011c20229,
+        0x0044800112202200, 0x0434804908100424, 0x0300404822c08200, 0x48081010008a2a80,
+    ];
+
+    fn new_box() -> Box<Self> {
+        let cb = ClassicalBitboard::instance();
+        let mut me = Box::new(Self {
+            rook:    [MagicEntry::default(); 64],
+            bishop:  [MagicEntry::default(); 64],
+            attacks: Vec::with_capacity(107_648),
+        });
+        for sq in Bitboard::all().squares() {
+            // rays end at the edge anyway, so only the edges along the slider's own rank or file matter
+            let edges = (Bitboard::RANKS_18 - sq.rank_bitboard()) | (Bitboard::RIM - sq.file_bitboard());
+            let mask = cb.rook_attacks(Bitboard::EMPTY, sq) - edges;
+            me.rook[sq] = me.add_square(mask, Self::ROOK_MAGICS[sq], |occ| cb.rook_attacks(occ, sq));
+            let mask = cb.bishop_attacks(Bitboard::EMPTY, sq) - edges;
+            me.bishop[sq] = me.add_square(mask, Self::BISHOP_MAGICS[sq], |occ| cb.bishop_attacks(occ, sq));
+        }
+        me
+    }
+
+    /// appends the attacks of a square to the table
+    fn add_square(&mut self, mask: Bitboard, magic: u64, slider: impl Fn(Bitboard) -> Bitboard) -> MagicEntry {
+        let entry = MagicEntry {
+            mask,
+            magic,
+            shift: 64 - mask.popcount() as u32,
+            offset: self.attacks.len(),
+        };
+        let mut table: Vec<Option<Bitboard>> = vec![None; 1 << mask.popcount()];
+        for occ in mask.power_set_iter() {
+            let attacks = slider(occ);
+            let slot = &mut table[entry.index(occ) - entry.offset];
+            assert!(
+                slot.is_none() || *slot == Some(attacks),
+                "harmful collision for magic {magic:#018x} with occupancy {occ:?}"
+            );
+            *slot = Some(attacks);
+        }
+        self.attacks.extend(table.into_iter().map(Option::unwrap_or_default));
+        entry
+    }
+
+    #[inline]
+    pub fn rook_attacks(&self, occ: Bitboard, from: Square) -> Bitboard {
+        self.attacks[self.rook[from].index(occ)]
+    }
+
+    #[inline]
+    pub fn bishop_attacks(&self, occ: Bitboard, from: Square) -> Bitboard {
+        self.attacks[self.bishop[from].index(occ)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hint::black_box;
+
+    use Square::*;
+
+    use super::*;
+    use crate::bits::bb_hyperbola::Hyperbola;
+    use crate::catalog::Catalog;
+    use crate::infra::profiler::PerfProfiler;
+    use crate::other::Perft;
+    use crate::test_log::test;
+
+    #[test]
+    fn test_magic_attacks() {
+        let m = Magic::instance();
+        assert_eq!(m.attacks.len(), 107_648);
+        let occ = A1 | A2 | A7 | C3 | C6;
+        assert_eq!(m.rook_attacks(occ, A6), (Bitboard::FILE_A - (A1 | A6 | A8)) | B6 | C6);
+        assert_eq!(m.bishop_attacks(occ, A6), F1 | E2 | D3 | C4 | B5 | B7 | C8);
+        assert_eq!(m.bishop_attacks(Bitboard::B2, C1), B2 | D2 | E3 | F4 | G5 | H6);
+    }
+
+    #[test]
+    fn test_magic_vs_classical() {
+        let cb = ClassicalBitboard::instance();
+        let m = Magic::instance();
+        for sq in Bitboard::all().squares() {
+            for occ in m.rook[sq].mask.power_set_iter() {
+                assert_eq!(m.rook_attacks(occ, sq), cb.rook_attacks(occ, sq), "{sq} {occ:?}");
+                // squares outside the mask (including the slider's own) are ignored
+                let occ = occ | !m.rook[sq].mask;
+                assert_eq!(m.rook_attacks(occ, sq), cb.rook_attacks(occ, sq), "{sq} {occ:?}");
+            }
+            for occ in m.bishop[sq].mask.power_set_iter() {
+                assert_eq!(m.bishop_attacks(occ, sq), cb.bishop_attacks(occ, sq), "{sq} {occ:?}");
+                let occ = occ | !m.bishop[sq].mask;
+                assert_eq!(m.bishop_attacks(occ, sq), cb.bishop_attacks(occ, sq), "{sq} {occ:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_magic_perft() {
+        let hq = Hyperbola::instance();
+        let m = Magic::instance();
+        for (board, perfts) in Catalog::perfts() {
+            let occ = board.occupied();
+            for sq in Bitboard::all().squares() {
+                assert_eq!(m.rook_attacks(occ, sq), hq.rook_attacks(occ, sq), "{sq} {board}");
+                assert_eq!(m.bishop_attacks(occ, sq), hq.bishop_attacks(occ, sq), "{sq} {board}");
+            }
+            for (depth, &expected) in perfts.iter().enumerate().take(4) {
+                let count = Perft::<true>::count(&board, depth as u32);
+                assert_eq!(count, expected, "{board} perft({depth})");
+            }
+        }
+    }
+
+    #[test]
+    fn bench_sliders() {
+        let mut prof_classical = PerfProfiler::new("sliders classical");
+        let mut prof_hyperbola = PerfProfiler::new("sliders hyperbola");
+        let mut prof_magic = PerfProfiler::new("sliders magic");
+        let (cb, hq, m) = (ClassicalBitboard::instance(), Hyperbola::instance(), Magic::instance());
+        for (board, _) in Catalog::perfts() {
+            let occ = black_box(board.occupied());
+            prof_classical.bench(|| {
+                Bitboard::all().squares().fold(Bitboard::EMPTY, |acc, sq| {
+                    acc ^ cb.rook_attacks(occ, sq) ^ cb.bishop_attacks(occ, sq)
+                })
+            });
+            prof_hyperbola.bench(|| {
+                Bitboard::all().squares().fold(Bitboard::EMPTY, |acc, sq| {
+                    acc ^ hq.rook_attacks(occ, sq) ^ hq.bishop_attacks(occ, sq)
+                })
+            });
+            prof_magic.bench(|| {
+                Bitboard::all().squares().fold(Bitboard::EMPTY, |acc, sq| {
+                    acc ^ m.rook_attacks(occ, sq) ^ m.bishop_attacks(occ, sq)
+                })
+            });
+        }
+    }
+}
@@ -198,6 +198,11 @@ pub struct Bitboard(u64);
 //     }
 // }
 
+/// A lazily computed bitboard, using the NICHE value to mean "not yet calculated".
+///
+/// Backed by a (lock free) atomic rather than a Cell, so holders remain Send + Sync without
+/// unsafe impls. Concurrent initialisation is benign as the calculation is deterministic -
+/// every thread stores the same value.
 #[derive(Serialize, Deserialize)]
 pub struct LazyBitboard<const NICHE: u64> {
     #[serde(skip)]
@@ -1,9 +1,9 @@
 use std::fmt;
+use std::str::FromStr;
 
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::bits::bitboard::Bitboard;
 use crate::bits::square::Square;
 use crate::piece::Color;
 
@@ -66,51 +66,41 @@ impl CastlingRights {
         self.intersects(Self::WHITE_KING.or(Self::BLACK_KING))
     }
 
+    /// the color of a single castling right
     #[inline]
-    pub fn king_side_move_squares(c: Color) -> Bitboard {
-        c.chooser_wb(Bitboard::F1.or(Bitboard::G1), Bitboard::F8.or(Bitboard::G8))
-    }
-
-    #[inline]
-    pub fn queen_side_move_squares(c: Color) -> Bitboard {
-        c.chooser_wb(
-            Bitboard::D1.or(Bitboard::C1).or(Bitboard::B1),
-            Bitboard::D8.or(Bitboard::C8).or(Bitboard::B8),
-        )
-    }
-
-    #[inline]
-    pub fn is_castling(from: Square, to: Square) -> bool {
-        from == Square::E1 && (to == Square::C1 || to == Square::G1)
-            || from == Square::E8 && (to == Square::C8 || to == Square::G8)
+    pub fn color(self) -> Color {
+        match self.intersects(Self::WHITE_KING.or(Self::WHITE_QUEEN)) {
+            true => Color::White,
+            false => Color::Black,
+        }
     }
 
+    /// the king and rook destinations of a single castling right, which are the same in Chess960
     #[inline]
-    pub fn from_king_move(to: Square) -> CastlingRights {
-        match to {
-            Square::G1 => CastlingRights::WHITE_KING,
-            Square::C1 => CastlingRights::WHITE_QUEEN,
-            Square::G8 => CastlingRights::BLACK_KING,
-            Square::C8 => CastlingRights::BLACK_QUEEN,
-            _ => CastlingRights::NONE,
+    pub fn king_and_rook_to(self) -> (Square, Square) {
+        match self {
+            Self::WHITE_KING => (Square::G1, Square::F1),
+            Self::WHITE_QUEEN => (Square::C1, Square::D1),
+            Self::BLACK_KING => (Square::G8, Square::F8),
+            Self::BLACK_QUEEN => (Square::C8, Square::D8),
+            _ => panic!("'{self}' is not a single castling right"),
         }
     }
 
-    pub fn rights_lost(from: Square, to: Square) -> CastlingRights {
-        const fn pop_castling_rights() -> [CastlingRights; 64] {
-            let mut cr = [CastlingRights::NONE; 64];
-            cr[Square::A1.index()] = CastlingRights::WHITE_QUEEN;
-            cr[Square::A8.index()] = CastlingRights::BLACK_QUEEN;
-            cr[Square::H1.index()] = CastlingRights::WHITE_KING;
-            cr[Square::H8.index()] = CastlingRights::BLACK_KING;
-            cr[Square::E1.index()] = CastlingRights::WHITE_KING.or(CastlingRights::WHITE_QUEEN);
-            cr[Square::E8.index()] = CastlingRights::BLACK_KING.or(CastlingRights::BLACK_QUEEN);
-            cr
+    /// castling is encoded as the king "capturing" its own rook, so the rook's side of the king
+    /// determines the castling side
+    #[inline]
+    pub fn from_king_and_rook(king: Square, rook: Square) -> CastlingRights {
+        let c = match king.rank_index() {
+            0 => Color::White,
+            7 => Color::Black,
+            _ => return CastlingRights::NONE,
+        };
+        if rook.file_index() > king.file_index() {
+            Self::king_side_right(c)
+        } else {
+            Self::queen_side_right(c)
         }
-
-        const RIGHTS_LOST: [CastlingRights; Square::len()] = pop_castling_rights();
-        // null move has from == to giving no loss
-        RIGHTS_LOST[from] ^ RIGHTS_LOST[to]
     }
 
     #[inline]
@@ -132,6 +122,68 @@ impl CastlingRights {
     }
 }
 
+/// The files of the castling rooks, by castling right. In standard chess the rooks castle from
+/// the a and h files, but in Chess960 the king and rooks can start on any back rank files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CastlingFiles([u8; 4]); // indexed by the bit of the castling right
+
+impl Default for CastlingFiles {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl CastlingFiles {
+    pub const STANDARD: Self = Self::new(7, 0);
+
+    /// the same files for both colors, as in any Chess960 starting position
+    pub const fn new(king_side: u8, queen_side: u8) -> Self {
+        Self([queen_side, king_side, queen_side, king_side])
+    }
+
+    #[inline]
+    fn index(right: CastlingRights) -> usize {
+        debug_assert!(
+            right.bits().count_ones() == 1,
+            "'{right}' is not a single castling right"
+        );
+        right.bits().trailing_zeros() as usize
+    }
+
+    #[inline]
+    pub fn file(&self, right: CastlingRights) -> usize {
+        self.0[Self::index(right)] as usize
+    }
+
+    pub fn set_file(&mut self, right: CastlingRights, file: usize) {
+        self.0[Self::index(right)] = file as u8;
+    }
+
+    /// the starting square of the rook for a single castling right
+    #[inline]
+    pub fn rook_square(&self, right: CastlingRights) -> Square {
+        let rank = right.color().chooser_wb(0, 7);
+        Square::from_xy(self.file(right) as u32, rank)
+    }
+
+    pub fn is_standard(&self) -> bool {
+        *self == Self::STANDARD
+    }
+
+    pub fn color_flip(&self) -> CastlingFiles {
+        let [bq, bk, wq, wk] = self.0;
+        Self([wq, wk, bq, bk])
+    }
+}
+
+impl FromStr for CastlingRights {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
 impl fmt::Display for CastlingRights {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.is_empty() {
@@ -152,3 +204,44 @@ impl fmt::Display for CastlingRights {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_castling_rights_fmt() {
+        for s in ["KQkq", "Kk", "Qq", "q", "-"] {
+            let cr: CastlingRights = s.parse().unwrap();
+            assert_eq!(cr.to_string(), s);
+            let json = serde_json::to_string(&cr).unwrap();
+            assert_eq!(serde_json::from_str::<CastlingRights>(&json).unwrap(), cr, "{json}");
+        }
+        assert_eq!("KQkq".parse::<CastlingRights>().unwrap(), CastlingRights::ALL);
+        assert!("KX".parse::<CastlingRights>().is_err());
+    }
+
+    #[test]
+    fn test_castling_files() {
+        let files = CastlingFiles::STANDARD;
+        assert_eq!(files.rook_square(CastlingRights::WHITE_KING), Square::H1);
+        assert_eq!(files.rook_square(CastlingRights::BLACK_QUEEN), Square::A8);
+        let mut files = CastlingFiles::new(6, 1);
+        assert_eq!(files.rook_square(CastlingRights::WHITE_QUEEN), Square::B1);
+        assert_eq!(files.rook_square(CastlingRights::BLACK_KING), Square::G8);
+        files.set_file(CastlingRights::WHITE_KING, 5);
+        assert_eq!(files.color_flip().rook_square(CastlingRights::BLACK_KING), Square::F8);
+        assert_eq!(files.color_flip().color_flip(), files);
+        assert!(!files.is_standard());
+
+        assert_eq!(
+            CastlingRights::from_king_and_rook(Square::B1, Square::A1),
+            CastlingRights::WHITE_QUEEN
+        );
+        assert_eq!(
+            CastlingRights::from_king_and_rook(Square::F8, Square::G8),
+            CastlingRights::BLACK_KING
+        );
+        assert_eq!(CastlingRights::WHITE_QUEEN.king_and_rook_to(), (Square::C1, Square::D1));
+    }
+}
@@ -1,11 +1,12 @@
 pub mod bb_classical;
 pub mod bb_hyperbola;
+pub mod bb_magic;
 pub mod bitboard;
 pub mod castling;
 pub mod precalc;
 pub mod square;
 
 pub use crate::bits::bitboard::Bitboard;
-pub use crate::bits::castling::CastlingRights;
+pub use crate::bits::castling::{CastlingFiles, CastlingRights};
 pub use crate::bits::precalc::PreCalc;
 pub use crate::bits::square::Square;
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use tabled::builder::Builder;
 
 use crate::bits::bb_hyperbola::Hyperbola;
+use crate::bits::bb_magic::Magic;
 use crate::bits::bitboard::{Bitboard, Dir};
 use crate::bits::square::Square;
 use crate::piece::{Color, FlipSide, Piece};
@@ -135,14 +136,23 @@ impl PreCalc {
         bbs
     }
 
+    /// by magic bitboards, or hyperbola quintessence with the `hyperbola` feature
     #[inline]
     pub fn bishop_attacks(&self, occupied: Bitboard, from: Square) -> Bitboard {
-        self.sliding_piece_attacks.bishop_attacks(occupied, from)
+        if cfg!(feature = "hyperbola") {
+            self.sliding_piece_attacks.bishop_attacks(occupied, from)
+        } else {
+            Magic::instance().bishop_attacks(occupied, from)
+        }
     }
 
     #[inline]
     pub fn rook_attacks(&self, occupied: Bitboard, from: Square) -> Bitboard {
-        self.sliding_piece_attacks.rook_attacks(occupied, from)
+        if cfg!(feature = "hyperbola") {
+            self.sliding_piece_attacks.rook_attacks(occupied, from)
+        } else {
+            Magic::instance().rook_attacks(occupied, from)
+        }
     }
 
     // inclusive of end points
@@ -263,6 +273,35 @@ impl PreCalc {
         }
     }
 
+    /// squares attacked by a piece on `from`, with sliders blocked by `occupied` (which need not
+    /// be the board's occupancy). Pawn attacks are the capture squares, whether occupied or
+    /// not, and are the only attacks depending on color `c`
+    #[inline]
+    pub fn attacks_from(&self, c: Color, p: Piece, from: Square, occupied: Bitboard) -> Bitboard {
+        match p {
+            Piece::Bishop => self.bishop_attacks(occupied, from),
+            Piece::Rook => self.rook_attacks(occupied, from),
+            Piece::Queen => self.rook_attacks(occupied, from) | self.bishop_attacks(occupied, from),
+            Piece::King => self.king_attacks(from),
+            Piece::Knight => self.knight_attacks(from),
+            Piece::Pawn => self.pawn_capture_attacks_from_sq(c, from),
+        }
+    }
+
+    /// the further squares attacked by a slider on `from` once those of `blockers` that it
+    /// attacks directly are removed from `occupied` - see https://www.chessprogramming.org/X-ray_Attacks_(Bitboards)
+    ///
+    /// Empty for non-sliders
+    #[inline]
+    pub fn xray_attacks(&self, c: Color, p: Piece, from: Square, occupied: Bitboard, blockers: Bitboard) -> Bitboard {
+        if !matches!(p, Piece::Bishop | Piece::Rook | Piece::Queen) {
+            return Bitboard::EMPTY;
+        }
+        let attacks = self.attacks_from(c, p, from, occupied);
+        let blockers = blockers & attacks;
+        attacks ^ self.attacks_from(c, p, from, occupied ^ blockers)
+    }
+
     #[inline]
     pub fn pawn_attacks_ext(&self, c: Color, us: Bitboard, them: Bitboard, fr: Square) -> Bitboard {
         let empty = !(us | them);
@@ -386,6 +425,39 @@ impl PreCalc {
         ((side.shift(Dir::E) & open).shift(Dir::E) | (side.shift(Dir::W) & open).shift(Dir::W)) & side
     }
 
+    /// backward pawns of color c have no pawns of their own on a neighbouring file level with
+    /// or behind them (so can never be pawn defended), and a stop square attacked or blocked by
+    /// an enemy pawn. Isolated pawns are not backward
+    #[inline]
+    pub fn pawn_side_backward(&self, c: Color, ours: Bitboard, theirs: Bitboard) -> Bitboard {
+        let our_attacks = self.pawn_attacks_from(c, ours);
+        let supportable = c.chooser_wb(our_attacks.fill_north(), our_attacks.fill_south());
+        let unsafe_stops = self.pawn_attacks_from(c.flip_side(), theirs) | theirs;
+        (ours & (unsafe_stops - supportable).shift(c.backward())) - self.pawn_side_isolated(ours)
+    }
+
+    /// pawns of color c with no enemy pawn in front of them on the same file
+    #[inline]
+    pub fn pawn_side_unopposed(&self, c: Color, ours: Bitboard, theirs: Bitboard) -> Bitboard {
+        ours - c.chooser_wb(theirs.fill_south(), theirs.fill_north())
+    }
+
+    /// levers are pawns of color c attacking (and so attacked by) an enemy pawn
+    #[inline]
+    pub fn pawn_side_levers(&self, c: Color, ours: Bitboard, theirs: Bitboard) -> Bitboard {
+        ours & self.pawn_attacks_from(c.flip_side(), theirs)
+    }
+
+    /// pawn breaks are pawns of color c that can advance (by a single or double push) to
+    /// create a lever
+    #[inline]
+    pub fn pawn_side_breaks(&self, c: Color, ours: Bitboard, theirs: Bitboard, occupied: Bitboard) -> Bitboard {
+        let lever_squares = self.pawn_attacks_from(c.flip_side(), theirs) - occupied;
+        let single = lever_squares.shift(c.backward());
+        let double = ((lever_squares & c.double_push_dest_rank()).shift(c.backward()) - occupied).shift(c.backward());
+        ours & (single | double)
+    }
+
     #[inline]
     pub fn adjacent_and_nearby_pawn_shield(&self, king_color: Color, king_sq: Square) -> (Bitboard, Bitboard) {
         // take the sheild sq as  away from the side if the king is on the side
@@ -434,6 +506,8 @@ pub struct Pawns {
     pub duos:                 Bitboard,
     pub distant_neighbours:   Bitboard,
     pub backward:             Bitboard,
+    pub unopposed:            Bitboard, // no enemy pawn in front on the file
+    pub levers:               Bitboard, // attacking an enemy pawn
     pub doubled:              Bitboard,
 }
 
@@ -526,10 +600,12 @@ impl Pawns {
         let distant_neighbours = precalc.pawn_side_distant_neighbours(wp) | precalc.pawn_side_distant_neighbours(bp);
 
         // backward pawns - cannot be defended by other pawns and cannot move fwd because of a pawn attack
-        // and sq in front not defended (ie not a duo)
-        let backward = (wp & black_outposts & black_attacks.shift(Dir::S)
-            | bp & white_outposts & white_attacks.shift(Dir::N))
-            - duos;
+        // (or block) and sq in front not defended (ie not a duo)
+        let backward =
+            precalc.pawn_side_backward(Color::White, wp, bp) | precalc.pawn_side_backward(Color::Black, bp, wp);
+        let unopposed =
+            precalc.pawn_side_unopposed(Color::White, wp, bp) | precalc.pawn_side_unopposed(Color::Black, bp, wp);
+        let levers = precalc.pawn_side_levers(Color::White, wp, bp) | precalc.pawn_side_levers(Color::Black, bp, wp);
 
         // One possibility is to define a pawn as a candidate, if no square on its path is controlled
         // by more enemy pawns than own pawns.
@@ -573,6 +649,8 @@ impl Pawns {
             duos,
             distant_neighbours,
             backward,
+            unopposed,
+            levers,
             doubled,
         }
     }
@@ -655,6 +733,44 @@ mod tests {
         assert_eq!(atts, B2 | C3 | D4 | E5 | F6 | G7 | H8);
     }
 
+    #[test]
+    fn test_attacks_from() {
+        let bb = PreCalc::instance();
+        let occ = A3 | C1 | C3;
+        assert_eq!(bb.attacks_from(Color::White, Piece::Rook, A1, occ), A2 | A3 | B1 | C1);
+        assert_eq!(
+            bb.attacks_from(Color::White, Piece::Queen, A1, occ),
+            A2 | A3 | B1 | C1 | B2 | C3
+        );
+        assert_eq!(bb.attacks_from(Color::Black, Piece::Pawn, B2, occ), A1 | C1);
+        assert_eq!(bb.attacks_from(Color::White, Piece::Knight, A1, occ), B3 | C2);
+
+        // through the first blocker only, and only if it is one of the blockers
+        let occ = A3 | A5 | C1;
+        assert_eq!(bb.xray_attacks(Color::White, Piece::Rook, A1, occ, A3 | A5), A4 | A5);
+        assert_eq!(
+            bb.xray_attacks(Color::White, Piece::Rook, A1, occ, A5 | C1),
+            D1 | E1 | F1 | G1 | H1
+        );
+        assert_eq!(
+            bb.xray_attacks(Color::White, Piece::Bishop, A1, occ, A3.as_bb()),
+            Bitboard::EMPTY
+        );
+        assert_eq!(
+            bb.xray_attacks(Color::White, Piece::Knight, A1, occ, A3.as_bb()),
+            Bitboard::EMPTY
+        );
+
+        let b = Board::parse_fen("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        assert_eq!(b.attacks_from(E2, b.occupied()).popcount(), 7 + 7);
+        assert_eq!(
+            b.attacks_from(E2, E1.as_bb()),
+            (Bitboard::FILE_E | Bitboard::RANK_2) - E2.as_bb()
+        );
+        assert_eq!(b.xray_attacks(E1, b.occupied(), E2.as_bb()), Bitboard::EMPTY);
+        assert_eq!(b.attacks_from(E4, b.occupied()), Bitboard::EMPTY);
+    }
+
     #[test]
     fn test_pawns() {
         let b = Board::parse_diagram(
@@ -7,10 +7,11 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use super::hasher::Hasher;
+use super::hasher::{HashKeys, Hasher};
 use super::BoardCalcs;
 use crate::bits::bitboard::LazyBitboard;
-use crate::bits::castling::CastlingRights;
+use crate::bits::castling::{CastlingFiles, CastlingRights};
+use crate::bits::precalc::PreCalc;
 use crate::catalog::Catalog;
 use crate::domain::Material;
 use crate::infra::utils::ToStringOr;
@@ -79,9 +80,10 @@ pub struct Board {
     pub(super) colors:          [Bitboard; Color::len()],
     pub(super) fullmove_number: u16,
     pub(super) turn:            Color,
-    pub(super) hash:            Hash,
+    pub(super) keys:            HashKeys,
     pub(super) ply:             Ply,
     pub(super) castling:        CastlingRights,
+    pub(super) castling_files:  CastlingFiles,
     pub(super) en_passant:      Option<Square>,
     pub(super) halfmove_clock:  u16,
     pub(super) threats_to:      [LazyBitboard<{ Bitboard::ALL.bits() }>; Color::len()],
@@ -95,6 +97,12 @@ pub struct BoardBuilder(Board);
 
 // const ASSERT2: () = assert!(std::mem::size_of::<Board>() == 152);
 
+// boards are shared between search threads, so must be Send + Sync without unsafe impls
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Board>();
+};
+
 impl PartialEq for Board {
     fn eq(&self, other: &Self) -> bool {
         self.pieces == other.pieces
@@ -104,6 +112,7 @@ impl PartialEq for Board {
             // && self.hash == other.hash
             && self.ply == other.ply
             && self.castling == other.castling
+            && self.castling_files == other.castling_files
             && self.en_passant == other.en_passant
             && self.halfmove_clock == other.halfmove_clock
         // && self.threats_to == other.threats_to
@@ -128,6 +137,7 @@ impl Default for Board {
             pieces:          Default::default(),
             colors:          Default::default(),
             castling:        CastlingRights::NONE,
+            castling_files:  CastlingFiles::STANDARD,
             en_passant:      None,
             turn:            Default::default(),
             ply:             0,
@@ -137,7 +147,7 @@ impl Default for Board {
             checkers_of:     Default::default(),
             pinned:          Default::default(),
             discoverer:      Default::default(),
-            hash:            0,
+            keys:            HashKeys::default(),
             // moves: MoveList,
         }
         // b.hash = Hasher::default().hash_board(&b);
@@ -176,6 +186,62 @@ impl BoardBuilder {
         self.0.castling = castling;
     }
 
+    pub fn set_castling_files(&mut self, files: CastlingFiles) {
+        self.0.castling_files = files;
+    }
+
+    /// castling rights from a FEN castling field, set after the piece placement. Accepts KQkq
+    /// (the outermost rooks), and in Chess960 Shredder-FEN or X-FEN rook files such as HAha or Bb
+    pub fn set_castling_fen(&mut self, s: &str) -> Result<()> {
+        let b = &self.0;
+        let mut castling = CastlingRights::NONE;
+        let mut files = CastlingFiles::STANDARD;
+        for ch in s.chars() {
+            if ch == '-' {
+                break;
+            }
+            let c = if ch.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king = (b.kings() & b.color(c) & c.back_rank()).find_first_square();
+            let rooks = b.rooks() & b.color(c) & c.back_rank();
+            let (right, file) = match ch.to_ascii_lowercase() {
+                'k' => {
+                    let outermost = rooks.squares().filter(|&r| king.map_or(true, |k| r > k)).last();
+                    (
+                        CastlingRights::king_side_right(c),
+                        outermost.map_or(7, Square::file_index),
+                    )
+                }
+                'q' => {
+                    let outermost = rooks.squares().find(|&r| king.map_or(true, |k| r < k));
+                    (
+                        CastlingRights::queen_side_right(c),
+                        outermost.map_or(0, Square::file_index),
+                    )
+                }
+                f @ 'a'..='h' => {
+                    let Some(king) = king else {
+                        bail!("castling file '{ch}' in castling rights '{s}' needs a king on the back rank");
+                    };
+                    let file = (f as u8 - b'a') as usize;
+                    match file > king.file_index() {
+                        true => (CastlingRights::king_side_right(c), file),
+                        false => (CastlingRights::queen_side_right(c), file),
+                    }
+                }
+                _ => bail!("invalid character '{}' in castling rights '{}'", ch, s),
+            };
+            castling |= right;
+            files.set_file(right, file);
+        }
+        self.0.castling = castling;
+        self.0.castling_files = files;
+        Ok(())
+    }
+
     pub fn set_ep_square(&mut self, ep: Option<Square>) {
         self.0.en_passant = ep;
     }
@@ -483,7 +549,7 @@ impl Board {
 
     #[inline]
     pub fn calculate_internals(&mut self) {
-        self.hash = Hasher::instance().hash_board(self);
+        self.keys = Hasher::instance().hash_keys(self);
         // self.material.set(Material::niche());
         self.pinned = Default::default();
         self.discoverer = Default::default();
@@ -493,7 +559,37 @@ impl Board {
 
     #[inline]
     pub fn hash(&self) -> Hash {
-        self.hash
+        self.keys.board
+    }
+
+    /// the hash of the board after the move, calculated incrementally without making it
+    #[inline]
+    pub fn hash_after(&self, mv: Move) -> Hash {
+        self.keys.board ^ Hasher::instance().hash_move(mv, self)
+    }
+
+    /// pawns of both colors (zero if no pawns)
+    #[inline]
+    pub fn pawn_hash(&self) -> Hash {
+        self.keys.pawns
+    }
+
+    /// king and pawns of color c
+    #[inline]
+    pub fn king_pawn_hash(&self, c: Color) -> Hash {
+        self.keys.king_pawns[c]
+    }
+
+    #[inline]
+    pub fn hash_keys(&self) -> &HashKeys {
+        &self.keys
+    }
+
+    /// the same board when colors are swapped and ranks mirrored, but for the side to
+    /// move (eg the starting position). Cheap, as the flipped hash is kept incrementally
+    #[inline]
+    pub fn is_color_symmetric(&self) -> bool {
+        Hasher::instance().is_color_symmetric(&self.keys)
     }
 
     #[inline]
@@ -501,6 +597,65 @@ impl Board {
         self.castling
     }
 
+    #[inline]
+    pub fn castling_files(&self) -> CastlingFiles {
+        self.castling_files
+    }
+
+    /// the starting square of the rook for a single castling right
+    #[inline]
+    pub fn castling_rook_square(&self, right: CastlingRights) -> Square {
+        self.castling_files.rook_square(right)
+    }
+
+    /// rights are lost if a piece moves from (or is captured on) a castling rook square, or the king
+    /// moves. A rook capturing a rook can lose castling rights for both sides.
+    #[inline]
+    pub fn castling_rights_lost(&self, from: Square, to: Square) -> CastlingRights {
+        let mut lost = CastlingRights::NONE;
+        if self.castling.is_empty() {
+            return lost;
+        }
+        for &right in CastlingRights::values_array() {
+            if self.castling.contains(right) {
+                let rook_sq = self.castling_rook_square(right);
+                let king = self.kings() & self.color(right.color());
+                if from == rook_sq || to == rook_sq || from.is_in(king) {
+                    lost |= right;
+                }
+            }
+        }
+        lost
+    }
+
+    /// the FEN castling field. The outermost rooks are written as KQkq, so standard chess is
+    /// unaffected, with any inner castling rook in Chess960 written as its file (X-FEN)
+    pub fn castling_fen(&self) -> String {
+        if self.castling.is_empty() {
+            return "-".to_string();
+        }
+        let mut s = String::new();
+        for &right in CastlingRights::values_array() {
+            if !self.castling.contains(right) {
+                continue;
+            }
+            let c = right.color();
+            let rook_sq = self.castling_rook_square(right);
+            let rooks = self.rooks() & self.color(c) & c.back_rank();
+            let outer_rook = match right.is_king_side() {
+                true => rooks.squares().any(|r| r > rook_sq),
+                false => rooks.squares().any(|r| r < rook_sq),
+            };
+            let ch = match (outer_rook, right.is_king_side()) {
+                (true, _) => rook_sq.file_char(),
+                (false, true) => 'k',
+                (false, false) => 'q',
+            };
+            s.push(c.chooser_wb(ch.to_ascii_uppercase(), ch));
+        }
+        s
+    }
+
     #[inline]
     pub fn color_us(&self) -> Color {
         self.turn
@@ -621,11 +776,48 @@ impl Board {
             b.en_passant = Some(sq.flip_vertical());
         }
         b.castling = self.castling.color_flip();
+        b.castling_files = self.castling_files.color_flip();
+        b.calculate_internals();
+        debug_assert!(b.validate().is_ok());
+        b
+    }
+
+    /// mirrors files a-h, so castling rights (which are not symmetric) are lost
+    pub fn mirror_horizontal(&self) -> Board {
+        let mut b = self.clone();
+        b.colors = [self.colors[0].flip_horizontal(), self.colors[1].flip_horizontal()];
+        b.pieces.iter_mut().for_each(|bb| *bb = bb.flip_horizontal());
+        if let Some(sq) = b.en_passant {
+            b.en_passant = Some(Square::from_u8(sq.index() as u8 ^ 7));
+        }
+        b.castling = CastlingRights::NONE;
+        b.castling_files = CastlingFiles::STANDARD;
         b.calculate_internals();
         debug_assert!(b.validate().is_ok());
         b
     }
 
+    /// halfmove clock of 0 and fullmove number of 1, for comparing positions by content alone
+    pub fn strip_move_counters(&self) -> Board {
+        let mut b = self.clone();
+        b.halfmove_clock = 0;
+        b.fullmove_number = 1;
+        b.calculate_internals();
+        b
+    }
+
+    /// the e/p square is kept only if an e/p capture is legal, so that positions that differ
+    /// only in how a redundant e/p square was recorded hash identically
+    pub fn canonical_ep(&self) -> Board {
+        if self.en_passant.is_none() || self.legal_moves().iter().any(|mv| mv.is_ep_capture(self)) {
+            return self.clone();
+        }
+        let mut b = self.clone();
+        b.en_passant = None;
+        b.calculate_internals();
+        b
+    }
+
     pub fn to_fen(&self) -> String {
         let b = self.clone();
 
@@ -644,7 +836,7 @@ impl Board {
             "{fen} {turn} {castle} {ep} {fifty} {count}",
             fen = fen,
             turn = self.color_us(),
-            castle = self.castling(),
+            castle = self.castling_fen(),
             ep = if let Some(sq) = self.en_passant_square() {
                 sq.uci().to_string()
             } else {
@@ -663,6 +855,25 @@ impl Board {
         BoardCalcs::attacked_by(targets, self.occupied(), self)
     }
 
+    /// squares attacked by the piece on `sq` (empty if there is none), with sliders blocked by
+    /// `occupied` rather than by the board's own pieces
+    #[inline]
+    pub fn attacks_from(&self, sq: Square, occupied: Bitboard) -> Bitboard {
+        match (self.piece(sq), self.color_of(sq)) {
+            (Some(p), Some(c)) => PreCalc::instance().attacks_from(c, p, sq, occupied),
+            _ => Bitboard::EMPTY,
+        }
+    }
+
+    /// squares attacked by the slider on `sq` through the first of `blockers` on each line
+    #[inline]
+    pub fn xray_attacks(&self, sq: Square, occupied: Bitboard, blockers: Bitboard) -> Bitboard {
+        match (self.piece(sq), self.color_of(sq)) {
+            (Some(p), Some(c)) => PreCalc::instance().xray_attacks(c, p, sq, occupied, blockers),
+            _ => Bitboard::EMPTY,
+        }
+    }
+
     #[inline]
     pub fn pinned(&self, king_color: Color) -> Bitboard {
         let mut pi = self.pinned[king_color].get();
@@ -694,10 +905,30 @@ impl Board {
         mv.from().is_in(self.discoverer(their_king_color))
     }
 
+    /// squares from which a piece `p` of the side to move would check their king, with sliders
+    /// blocked by `occupied`
+    #[inline]
+    pub fn check_squares(&self, p: Piece, occupied: Bitboard) -> Bitboard {
+        let their_king_color = self.color_them();
+        PreCalc::instance().attacks_from(their_king_color, p, self.king(their_king_color), occupied)
+    }
+
+    /// without making the move: a direct check by the moved (or promoted) piece from its
+    /// check squares, or a discoverer leaving the line to their king. Castling and en passant
+    /// move or remove a second piece, so are made in full
     pub fn gives_check(&self, mv: Move) -> bool {
         debug_assert!(mv.is_valid(self));
         let their_king_color = self.color_them();
-        self.make_move(mv).is_in_check(their_king_color)
+        if mv.is_castle(self) || mv.is_ep_capture(self) {
+            return self.make_move(mv).is_in_check(their_king_color);
+        }
+        let occupied = (self.occupied() - mv.from().as_bb()) | mv.to().as_bb();
+        let p = mv.promo_piece().unwrap_or(mv.mover_piece(self));
+        if mv.to().is_in(self.check_squares(p, occupied)) {
+            return true;
+        }
+        let line = PreCalc::instance().line_through(mv.from(), self.king(their_king_color));
+        mv.from().is_in(self.discoverer(their_king_color)) && !mv.to().is_in(line)
     }
 
     #[inline]
@@ -729,6 +960,31 @@ impl Board {
         self.checkers_of(king_color).intersects(them)
     }
 
+    /// pawns of color `c` that can never be defended by their own pawns, and whose stop square
+    /// is attacked or blocked by an enemy pawn
+    pub fn backward_pawns(&self, c: Color) -> Bitboard {
+        let (ours, theirs) = (self.pawns() & self.color(c), self.pawns() & self.color(c.flip_side()));
+        PreCalc::instance().pawn_side_backward(c, ours, theirs)
+    }
+
+    /// pawns of color `c` with no enemy pawn in front, so on a half-open file for the opponent
+    pub fn unopposed_pawns(&self, c: Color) -> Bitboard {
+        let (ours, theirs) = (self.pawns() & self.color(c), self.pawns() & self.color(c.flip_side()));
+        PreCalc::instance().pawn_side_unopposed(c, ours, theirs)
+    }
+
+    /// pawns of color `c` attacking an enemy pawn (which necessarily attacks them too)
+    pub fn pawn_levers(&self, c: Color) -> Bitboard {
+        let (ours, theirs) = (self.pawns() & self.color(c), self.pawns() & self.color(c.flip_side()));
+        PreCalc::instance().pawn_side_levers(c, ours, theirs)
+    }
+
+    /// pawns of color `c` that can push (one or two squares) to create a lever
+    pub fn pawn_breaks(&self, c: Color) -> Bitboard {
+        let (ours, theirs) = (self.pawns() & self.color(c), self.pawns() & self.color(c.flip_side()));
+        PreCalc::instance().pawn_side_breaks(c, ours, theirs, self.occupied())
+    }
+
     pub fn to_diagram(&self) -> String {
         let mut f = String::new();
         for &r in Bitboard::RANKS.iter().rev() {
@@ -886,6 +1142,9 @@ impl Board {
         if self.hash() != Hasher::instance().hash_board(self) {
             bail!("Hash is incorrect");
         }
+        if self.keys != Hasher::instance().hash_keys(self) {
+            bail!("Pawn or king-pawn hashes are incorrect");
+        }
         Ok(())
     }
 
@@ -902,7 +1161,7 @@ impl Board {
         }
         let mut bb = BoardBuilder::parse_piece_placement(words[0])?;
         bb.set_turn(Color::parse(words[1])?);
-        bb.set_castling(CastlingRights::parse(words[2])?);
+        bb.set_castling_fen(words[2])?;
         bb.set_ep_square(if words[3] == "-" {
             None
         } else {
@@ -947,6 +1206,39 @@ mod tests {
         assert_eq!(board1, board2);
     }
 
+    #[test]
+    fn test_board_shared_across_threads() {
+        for epd in Catalog::bratko_kopec() {
+            let shared = epd.board();
+            let expected = epd.board();
+            let results = std::thread::scope(|s| {
+                let handles = (0..4)
+                    .map(|_| {
+                        s.spawn(|| {
+                            Color::ALL.map(|c| {
+                                [
+                                    shared.pinned(c),
+                                    shared.discoverer(c),
+                                    shared.checkers_of(c),
+                                    shared.all_attacks_on(c),
+                                ]
+                            })
+                        })
+                    })
+                    .collect_vec();
+                handles.into_iter().map(|h| h.join().unwrap()).collect_vec()
+            });
+            for r in results {
+                for c in Color::ALL {
+                    assert_eq!(r[c][0], expected.pinned(c));
+                    assert_eq!(r[c][1], expected.discoverer(c));
+                    assert_eq!(r[c][2], expected.checkers_of(c));
+                    assert_eq!(r[c][3], expected.all_attacks_on(c));
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_color_flip() {
         let board1 = Board::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
@@ -972,6 +1264,93 @@ mod tests {
         assert_eq!(board2.color_flip().to_fen(), board1.to_fen());
     }
 
+    #[test]
+    fn test_gives_check() {
+        let mut checks = 0;
+        for (mut board, _perfts) in Catalog::perfts() {
+            Perft::<false>::perft_with(&mut board, 2, &mut |bd, _mv| {
+                for &mv in bd.legal_moves().iter() {
+                    let expected = bd.make_move(mv).is_in_check(bd.color_them());
+                    assert_eq!(bd.gives_check(mv), expected, "{bd} {mv}");
+                    checks += expected as u32;
+                }
+            });
+        }
+        assert!(checks > 0);
+
+        // discovered by the moving pawn, promotion and castling
+        let b = Board::parse_fen("4k3/8/8/8/4P3/8/8/4R1K1 w - - 0 1").unwrap();
+        assert!(!b.gives_check(b.parse_san_move("e5").unwrap()));
+        let b = Board::parse_fen("8/7k/8/8/8/3P4/8/1B3K2 w - - 0 1").unwrap();
+        assert!(b.gives_check(b.parse_san_move("d4").unwrap()));
+        let b = Board::parse_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(b.gives_check(b.parse_san_move("b8=Q").unwrap()));
+        assert!(!b.gives_check(b.parse_san_move("b8=N").unwrap()));
+        let b = Board::parse_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(b.gives_check(b.parse_san_move("O-O").unwrap()));
+    }
+
+    #[test]
+    fn test_pawn_structure_queries() {
+        // the catalog tags a feature (c0) with white (c1) and black (c2) counts
+        for epd in Catalog::pawn_structure() {
+            let b = epd.board();
+            let counts = Color::ALL.map(|c| match epd.tag("c0") {
+                Some("backward") => Some(b.backward_pawns(c) - b.unopposed_pawns(c)),
+                Some("backward_half_open") => Some(b.backward_pawns(c) & b.unopposed_pawns(c)),
+                Some("lever") => Some(b.pawn_levers(c)),
+                _ => None,
+            });
+            if let [Some(w), Some(bl)] = counts {
+                let expected = (epd.int("c1").unwrap() as i32, epd.int("c2").unwrap() as i32);
+                assert_eq!((w.popcount(), bl.popcount()), expected, "{epd}");
+            }
+            // symmetric by color
+            let flipped = b.color_flip();
+            for c in Color::ALL {
+                let f = c.flip_side();
+                assert_eq!(flipped.backward_pawns(f), b.backward_pawns(c).flip_vertical(), "{epd}");
+                assert_eq!(flipped.pawn_levers(f), b.pawn_levers(c).flip_vertical(), "{epd}");
+                assert_eq!(flipped.pawn_breaks(f), b.pawn_breaks(c).flip_vertical(), "{epd}");
+            }
+        }
+
+        let b = Board::parse_fen("k7/8/8/2p1p3/3P1p2/4PP2/8/K7 w - - 0 1").unwrap();
+        assert_eq!(b.backward_pawns(Color::Black), Bitboard::E5);
+        assert_eq!(b.pawn_levers(Color::White), Bitboard::D4 | Bitboard::E3);
+        assert_eq!(b.pawn_breaks(Color::White), Bitboard::EMPTY);
+        assert_eq!(b.pawn_breaks(Color::Black), Bitboard::E5);
+
+        // double push breaks, but not through a piece
+        let b = Board::parse_fen("k7/8/8/2p5/8/8/1P6/K7 w - - 0 1").unwrap();
+        assert_eq!(b.pawn_breaks(Color::White), Bitboard::B2);
+        let b = Board::parse_fen("k7/8/8/2p5/8/1N6/1P6/K7 w - - 0 1").unwrap();
+        assert_eq!(b.pawn_breaks(Color::White), Bitboard::EMPTY);
+    }
+
+    #[test]
+    fn test_mirror_and_canonical_ep() {
+        let b = Board::parse_fen("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 5 3").unwrap();
+        let m = b.mirror_horizontal();
+        assert_eq!(m.to_fen(), "rnbkqbnr/pppp1ppp/8/3Pp3/8/8/PPP1PPPP/RNBKQBNR w - e6 5 3");
+        let mut no_castling = b.clone();
+        no_castling.set_castling(CastlingRights::NONE);
+        assert_eq!(m.mirror_horizontal(), no_castling);
+        assert_eq!(b.strip_move_counters().to_fen(), b.to_fen().replace("5 3", "0 1"));
+        assert_eq!(b.strip_move_counters().hash(), b.hash());
+
+        // exd6 is possible so the e/p square is kept
+        assert_eq!(b.canonical_ep(), b);
+
+        // a double push with no pawn alongside leaves no e/p square, matching the canonical fen
+        let start = Board::starting_pos();
+        let b = start.make_move(start.parse_uci_move("e2e4").unwrap());
+        assert_eq!(b.en_passant_square(), None);
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_ne!(Board::parse_fen(fen).unwrap().hash(), b.hash());
+        assert_eq!(Board::parse_fen(fen).unwrap().canonical_ep().hash(), b.hash());
+    }
+
     #[test]
     fn to_fen() {
         for &fen in &[
@@ -1,6 +1,6 @@
 use crate::bits::bitboard::Bitboard;
 use crate::bits::precalc::PreCalc;
-use crate::piece::{Color, FlipSide};
+use crate::piece::{Color, FlipSide, Piece};
 use crate::prelude::Board;
 
 pub struct BoardCalcs {}
@@ -30,23 +30,22 @@ impl BoardCalcs {
         let color_us = bd.color(king_color);
         let color_them = bd.color(king_color.flip_side());
         let king_sq = bd.king(king_color);
+        let occ = color_us | color_them;
 
+        // their sliders attacking the king through exactly one blocker
         let pc = PreCalc::instance();
-        // let xray_sliding_checkers = Self::attacked_by(kings, Bitboard::EMPTY, bd) &
-        // color_them;
-
-        let xray_sliding_checkers = color_them
-            & (pc.bishop_xray_attacks(king_sq) & bd.bishops_or_queens()
-                | pc.rook_xray_attacks(king_sq) & bd.rooks_or_queens());
+        let xray_checkers = |blockers: Bitboard| {
+            color_them
+                & (pc.xray_attacks(king_color, Piece::Bishop, king_sq, occ, blockers) & bd.bishops_or_queens()
+                    | pc.xray_attacks(king_color, Piece::Rook, king_sq, occ, blockers) & bd.rooks_or_queens())
+        };
         let mut pinned = Bitboard::empty();
         let mut discoverers = Bitboard::empty();
-        for checker in xray_sliding_checkers.squares() {
-            let ray = pc.strictly_between(checker, king_sq);
-            if ray.disjoint(color_them) && (ray & color_us).exactly_one() {
-                pinned |= ray & color_us;
-            } else if ray.disjoint(color_us) && (ray & color_them).exactly_one() {
-                discoverers |= ray & color_them;
-            }
+        for pinner in xray_checkers(color_us).squares() {
+            pinned |= pc.strictly_between(pinner, king_sq) & color_us;
+        }
+        for checker in xray_checkers(color_them).squares() {
+            discoverers |= pc.strictly_between(checker, king_sq) & color_them;
         }
         (pinned, discoverers)
     }
@@ -55,29 +54,13 @@ impl BoardCalcs {
     #[inline]
     pub fn all_attacks_on(bd: &Board, us: Color, occ: Bitboard) -> Bitboard {
         let opponent = us.flip_side();
-        let pawns = bd.pawns() & bd.color(opponent);
-        let knights = bd.knights() & bd.color(opponent);
-        let bishops_or_queens = bd.bishops_or_queens() & bd.color(opponent);
-        let rooks_or_queens = bd.rooks_or_queens() & bd.color(opponent);
-        let kings = bd.kings() & bd.color(opponent);
-
         let attack_gen = PreCalc::instance();
-        let (east, west) = attack_gen.pawn_attacks_ew(pawns, opponent);
+        let (east, west) = attack_gen.pawn_attacks_ew(bd.pawns() & bd.color(opponent), opponent);
         let mut threats = east | west;
-
-        for sq in knights.squares() {
-            threats |= attack_gen.knight_attacks(sq);
-        }
-        for sq in (bishops_or_queens).squares() {
-            threats |= attack_gen.bishop_attacks(occ, sq);
-        }
-
-        for sq in (rooks_or_queens).squares() {
-            threats |= attack_gen.rook_attacks(occ, sq);
-        }
-
-        for sq in kings.squares() {
-            threats |= attack_gen.king_attacks(sq);
+        for p in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+            for sq in (bd.pieces(p) & bd.color(opponent)).squares() {
+                threats |= attack_gen.attacks_from(opponent, p, sq, occ);
+            }
         }
         threats
     }
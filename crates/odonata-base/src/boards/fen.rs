@@ -0,0 +1,54 @@
+use anyhow::Result;
+
+use crate::prelude::*;
+
+/// A canonical fen for a position, so that the same position always maps to the same key
+/// (eg when deduplicating suites or keying positions by fen).
+///
+/// Whitespace is normalised, an e/p square with no legal e/p capture is dropped and the
+/// move counters are reset. A 4-field epd style fen (without counters) is also accepted.
+pub fn normalize(fen: &str) -> Result<String> {
+    let words = fen.split_whitespace().collect_vec();
+    let fen = match words.len() {
+        4 => format!("{} 0 1", words.join(" ")),
+        _ => words.join(" "),
+    };
+    let b = Board::parse_fen(&fen).with_context(|| format!("normalizing fen '{fen}'"))?;
+    Ok(b.canonical_ep().strip_move_counters().to_fen())
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_fen_normalize() {
+        let start = Board::starting_pos().to_fen();
+        assert_eq!(normalize(&start).unwrap(), start);
+
+        // no black pawn can capture on e3
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR  b KQkq e3 0 1";
+        assert_eq!(
+            normalize(fen).unwrap(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+
+        // exd6 is legal, so the e/p square is kept
+        let fen = "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+        assert_eq!(
+            normalize(fen).unwrap(),
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1"
+        );
+        assert_eq!(
+            normalize("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6").unwrap(),
+            normalize(fen).unwrap()
+        );
+
+        // fxe3 would expose the black king to the rook
+        let fen = "8/2p5/3p4/KP5r/1R2Pp1k/8/6P1/8 b - e3 7 40";
+        assert_eq!(normalize(fen).unwrap(), "8/2p5/3p4/KP5r/1R2Pp1k/8/6P1/8 b - - 0 1");
+        assert!(normalize("8/8/8 w - -").is_err());
+    }
+}
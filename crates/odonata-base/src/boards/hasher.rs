@@ -1,7 +1,9 @@
 use std::fmt;
+use std::ops::BitXorAssign;
 
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
+use serde::{Deserialize, Serialize};
 use static_init::dynamic;
 
 use crate::bits::castling::CastlingRights;
@@ -9,7 +11,7 @@ use crate::bits::square::Square;
 use crate::domain::node::Counter;
 use crate::infra::metric::*;
 use crate::mv::Move;
-use crate::piece::{Color, Hash, Piece};
+use crate::piece::{Color, FlipSide, FlipVertical, Hash, Piece};
 use crate::prelude::Board;
 
 // CPW:
@@ -157,12 +159,21 @@ impl Hasher {
 
     pub fn hash_pawns(&self, b: &Board) -> Hash {
         Metrics::incr(Counter::CalcHashPawns);
-        let mut hash = 0; // b.color_us().chooser_wb(0, self.side);
+        let mut hash = 0;
         for sq in b.pawns().squares() {
             hash ^= self.get(b.color_of(sq).unwrap(), Piece::Pawn, sq);
         }
-        if hash == 0 {
-            hash = self.side;
+        hash
+    }
+
+    /// king and pawns of a single color
+    pub fn hash_king_pawns(&self, b: &Board, c: Color) -> Hash {
+        let mut hash = 0;
+        for sq in (b.kings() & b.color(c)).squares() {
+            hash ^= self.get(c, Piece::King, sq);
+        }
+        for sq in (b.pawns() & b.color(c)).squares() {
+            hash ^= self.get(c, Piece::Pawn, sq);
         }
         hash
     }
@@ -188,32 +199,91 @@ impl Hasher {
         hash
     }
 
+    /// the hash of `b.color_flip()`, without flipping the board
+    pub fn hash_flipped(&self, b: &Board) -> Hash {
+        let mut hash = b.color_us().chooser_wb(self.side, 0);
+
+        hash ^= self.castling_by_bitflag[b.castling().color_flip().bits() as usize];
+
+        // the ep square is flipped within its file
+        if let Some(ep) = b.en_passant_square() {
+            hash ^= self.ep[ep.index() & 7];
+        }
+        for &p in &Piece::ALL {
+            for sq in (b.pieces(p) & b.white()).squares() {
+                hash ^= self.get(Color::Black, p, sq.flip_vertical());
+            }
+            for sq in (b.pieces(p) & b.black()).squares() {
+                hash ^= self.get(Color::White, p, sq.flip_vertical());
+            }
+        }
+        hash
+    }
+
+    /// the hash of a board and its color flip differ only by the side to move
+    pub fn is_color_symmetric(&self, keys: &HashKeys) -> bool {
+        keys.board ^ keys.flipped == self.side
+    }
+
+    /// all keys calculated from scratch
+    pub fn hash_keys(&self, b: &Board) -> HashKeys {
+        HashKeys {
+            board:      self.hash_board(b),
+            pawns:      self.hash_pawns(b),
+            king_pawns: [
+                self.hash_king_pawns(b, Color::White),
+                self.hash_king_pawns(b, Color::Black),
+            ],
+            flipped:    self.hash_flipped(b),
+        }
+    }
+
     pub fn hash_move(&self, m: Move, pre_move: &Board) -> Hash {
+        self.hash_move_keys(m, pre_move).board
+    }
+
+    /// the xor-delta of all keys, in a single pass over the move
+    pub fn hash_move_keys(&self, m: Move, pre_move: &Board) -> HashKeys {
         Metrics::incr(Counter::CalcHashMove);
-        let mut hash = self.side;
+        let mut keys = HashKeys {
+            board: self.side,
+            flipped: self.side,
+            ..HashKeys::default()
+        };
 
         // for null move we still need to remove the e/p square
         if let Some(ep) = pre_move.en_passant_square() {
-            hash ^= self.ep[ep.file_index()];
+            keys.board ^= self.ep[ep.file_index()];
+            keys.flipped ^= self.ep[ep.file_index()];
         }
 
         if m.is_null() {
-            return hash;
+            return keys;
         }
 
         // either we're moving to an empty square or its a capture
         let us = pre_move.color_us();
 
         let mover = m.mover_piece(pre_move);
-        hash ^= self.get(us, mover, m.from());
-        hash ^= self.get(us, mover, m.to());
+        let castling_rights_change = pre_move.castling_rights_lost(m.from(), m.to());
+        keys.board ^= self.castling_by_bitflag[castling_rights_change.bits() as usize];
+        keys.flipped ^= self.castling_by_bitflag[castling_rights_change.color_flip().bits() as usize];
 
-        let castling_rights_change = pre_move.castling() & CastlingRights::rights_lost(m.from(), m.to());
-        hash ^= self.castling_by_bitflag[castling_rights_change.bits() as usize];
+        // castling *moves* are king-takes-rook
+        if m.is_castle(pre_move) {
+            let (rook_from, rook_to) = m.rook_move_from_to();
+            keys.toggle(self, us, Piece::King, m.from());
+            keys.toggle(self, us, Piece::King, m.castling_king_to());
+            keys.toggle(self, us, Piece::Rook, rook_from);
+            keys.toggle(self, us, Piece::Rook, rook_to);
+            return keys;
+        }
 
+        keys.toggle(self, us, mover, m.from());
+        keys.toggle(self, us, mover, m.to());
         if let Some(promo) = m.promo_piece() {
-            hash ^= self.get(us, Piece::Pawn, m.to());
-            hash ^= self.get(us, promo, m.to());
+            keys.toggle(self, us, Piece::Pawn, m.to());
+            keys.toggle(self, us, promo, m.to());
         }
 
         // we return early if a capture as double_push and castle are non-captures
@@ -221,26 +291,59 @@ impl Hasher {
             let them = pre_move.color_them();
             if m.is_ep_capture(pre_move) {
                 // ep capture is like capture but with capture piece on *ep* square not *dest*
-                hash ^= self.get(them, cap, m.capture_square(pre_move));
-                return hash;
+                keys.toggle(self, them, cap, m.capture_square(pre_move));
+                return keys;
             } else {
                 // regular capture
-                hash ^= self.get(them, cap, m.to());
-                return hash;
+                keys.toggle(self, them, cap, m.to());
+                return keys;
             }
         }
 
-        if m.is_pawn_double_push(pre_move) {
-            hash ^= self.ep[m.double_push_en_passant_square().file_index()];
+        if m.is_pawn_double_push(pre_move) && m.is_ep_capturable(pre_move) {
+            keys.board ^= self.ep[m.double_push_en_passant_square().file_index()];
+            keys.flipped ^= self.ep[m.double_push_en_passant_square().file_index()];
         }
+        keys
+    }
+}
 
-        // castling *moves*
-        if m.is_castle(pre_move) {
-            let (rook_from, rook_to) = m.rook_move_from_to();
-            hash ^= self.get(us, Piece::Rook, rook_from);
-            hash ^= self.get(us, Piece::Rook, rook_to);
+/// The board hash, together with the pawn-structure and king-position
+/// sub-hashes, and the hash of the color flipped board. All are maintained
+/// incrementally by make_move.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashKeys {
+    pub board:      Hash,
+    pub pawns:      Hash,
+    pub king_pawns: [Hash; Color::len()],
+    pub flipped:    Hash,
+}
+
+impl BitXorAssign for HashKeys {
+    #[inline]
+    fn bitxor_assign(&mut self, rhs: Self) {
+        self.board ^= rhs.board;
+        self.pawns ^= rhs.pawns;
+        self.king_pawns[0] ^= rhs.king_pawns[0];
+        self.king_pawns[1] ^= rhs.king_pawns[1];
+        self.flipped ^= rhs.flipped;
+    }
+}
+
+impl HashKeys {
+    #[inline(always)]
+    fn toggle(&mut self, h: &Hasher, c: Color, p: Piece, sq: Square) {
+        let key = h.get(c, p, sq);
+        self.board ^= key;
+        self.flipped ^= h.get(c.flip_side(), p, sq.flip_vertical());
+        match p {
+            Piece::Pawn => {
+                self.pawns ^= key;
+                self.king_pawns[c] ^= key;
+            }
+            Piece::King => self.king_pawns[c] ^= key,
+            _ => {}
         }
-        hash
     }
 }
 
@@ -329,6 +432,60 @@ mod tests {
         bd1_plus_nulls.set_ply(3);
         let bd2 = bd1.make_move(Move::new_null()).make_move(Move::new_null());
         assert_eq!(bd2, bd1_plus_nulls, "e4 + double null move {bd2:#} {bd1_plus_nulls:#}");
+
+        // the child's hash without making the move (ep, castling and promos included)
+        for (b, _) in Catalog::perfts() {
+            for &mv in b.legal_moves().iter() {
+                assert_eq!(b.hash_after(mv), b.make_move(mv).hash(), "{mv} on {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_move_keys() {
+        let hasher = Hasher::new(1);
+        // castling, ep, promos and captures of pawns
+        let bd1 = Board::parse_fen("r3k2r/1P3p2/8/3pP3/8/8/6p1/R3K2R w KQkq d6 0 1").unwrap();
+        let keys_bd1 = hasher.hash_keys(&bd1);
+        for &mv in bd1.legal_moves().iter() {
+            let mut keys = keys_bd1;
+            keys ^= hasher.hash_move_keys(mv, &bd1);
+            assert_eq!(keys, hasher.hash_keys(&bd1.make_move(mv)), "{mv}");
+        }
+
+        // piece moves leave the sub-hashes unchanged, and color sub-hashes are independent
+        let bd2 = bd1.make_move_str("Ra2").unwrap();
+        assert_eq!(bd2.pawn_hash(), bd1.pawn_hash());
+        assert_eq!(bd2.king_pawn_hash(Color::White), bd1.king_pawn_hash(Color::White));
+        let bd2 = bd1.make_move_str("Kd2").unwrap();
+        assert_eq!(bd2.pawn_hash(), bd1.pawn_hash());
+        assert_ne!(bd2.king_pawn_hash(Color::White), bd1.king_pawn_hash(Color::White));
+        assert_eq!(bd2.king_pawn_hash(Color::Black), bd1.king_pawn_hash(Color::Black));
+        let b = Catalog::starting_board();
+        assert_eq!(b.pawn_hash(), Hasher::instance().hash_pawns(&b));
+    }
+
+    #[test]
+    fn test_hash_flipped() {
+        let hasher = Hasher::instance();
+        for (b, _perfts) in Catalog::perfts() {
+            assert_eq!(hasher.hash_flipped(&b), b.color_flip().hash(), "{b}");
+            assert_eq!(b.hash_keys().flipped, b.color_flip().hash(), "{b}");
+            assert_eq!(b.color_flip().hash_keys().flipped, b.hash(), "{b}");
+        }
+        let b = Catalog::starting_board();
+        assert!(b.is_color_symmetric());
+        let b = b.make_move_str("e4").unwrap();
+        assert!(!b.is_color_symmetric());
+        let b = b.make_move_str("e5").unwrap();
+        assert!(b.is_color_symmetric());
+        assert!(b.make_moves_str("Nf3 Nf6").unwrap().is_color_symmetric());
+        assert!(!b.make_moves_str("Nf3 Nc6").unwrap().is_color_symmetric());
+        // castling rights count
+        let b = Board::parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(b.is_color_symmetric());
+        let b = Board::parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1").unwrap();
+        assert!(!b.is_color_symmetric());
     }
 
     #[test]
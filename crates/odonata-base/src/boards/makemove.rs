@@ -1,4 +1,5 @@
-use crate::boards::hasher::Hasher;
+use crate::bits::{CastlingRights, Square};
+use crate::boards::hasher::{HashKeys, Hasher};
 use crate::domain::node::{Counter, Timing};
 use crate::infra::metric::Metrics;
 use crate::mv::Move;
@@ -6,6 +7,17 @@ use crate::piece::{FlipSide, Piece};
 use crate::prelude::Board;
 use crate::variation::Variation;
 
+/// The board state a move destroys, so that [`Board::undo_move`] can restore it in place
+/// rather than keeping a copy of the whole board
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Undo {
+    captured:       Option<Piece>,
+    castling:       CastlingRights,
+    en_passant:     Option<Square>,
+    halfmove_clock: u16,
+    keys:           HashKeys,
+}
+
 impl Board {
     pub fn make_moves_old(&self, var: &Variation) -> Board {
         let mut b = self.clone();
@@ -55,7 +67,7 @@ impl Board {
             en_passant:      self.en_passant_square(),
             turn:            self.turn,
             fullmove_number: self.fullmove_number,
-            halfmove_clock: self.halfmove_clock,
+            halfmove_clock:  self.halfmove_clock,
             threats_to:      Default::default(),
             checkers_of:     Default::default(),
             pinned:          Default::default(),
@@ -65,7 +77,8 @@ impl Board {
             pieces:          self.pieces,
             colors:          self.colors,
             castling:        self.castling,
-            hash:            self.hash,
+            castling_files:  self.castling_files,
+            keys:            self.keys,
             ply:             self.ply,
         };
         self.make_move_into(m, &mut b);
@@ -86,7 +99,8 @@ impl Board {
         self.pieces = b.pieces;
         self.colors = b.colors;
         self.castling = b.castling;
-        self.hash = b.hash;
+        self.castling_files = b.castling_files;
+        self.keys = b.keys;
         self.ply = b.ply;
     }
 
@@ -104,7 +118,8 @@ impl Board {
         bds[i].pieces = bds[j].pieces;
         bds[i].colors = bds[j].colors;
         bds[i].castling = bds[j].castling;
-        bds[i].hash = bds[j].hash;
+        bds[i].castling_files = bds[j].castling_files;
+        bds[i].keys = bds[j].keys;
         bds[i].ply = bds[j].ply;
     }
 
@@ -141,21 +156,85 @@ impl Board {
         Metrics::profile(t, Timing::TimingMakeMove);
 
         debug_assert!(
-            b.hash == Hasher::instance().hash_board(b),
-            "\n{self}.make_move({m}) = \n{b} inconsistent incremental hash {:x} (should be {:x})",
-            b.hash,
-            Hasher::instance().hash_board(b),
+            b.keys == Hasher::instance().hash_keys(b),
+            "\n{self}.make_move({m}) = \n{b} inconsistent incremental hash {:x?} (should be {:x?})",
+            b.keys,
+            Hasher::instance().hash_keys(b),
         );
     }
 
+    /// make the move in place, returning the state needed to undo it
+    #[inline]
+    pub fn do_move(&mut self, m: Move) -> Undo {
+        Metrics::incr(Counter::MakeMove);
+        let undo = Undo {
+            captured:       m.capture_piece(self),
+            castling:       self.castling,
+            en_passant:     self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            keys:           self.keys,
+        };
+        self.apply_move(m);
+        self.clear_lazy_bitboards();
+        undo
+    }
+
+    /// reverses [`Board::do_move`] - the move must be the last one made
+    #[inline]
+    pub fn undo_move(&mut self, m: Move, undo: Undo) {
+        let b = self;
+        let c = b.turn.flip_side(); // the side that made the move
+        b.turn = c;
+        b.fullmove_number -= c.chooser_wb(0, 1);
+        b.ply -= 1;
+        b.halfmove_clock = undo.halfmove_clock;
+        b.castling = undo.castling;
+        b.en_passant = undo.en_passant;
+        b.keys = undo.keys;
+        b.clear_lazy_bitboards();
+        if m.is_null() {
+            return;
+        }
+
+        if let Some(promo) = m.promo_piece() {
+            b.change_piece(m.to().as_bb(), promo, Piece::Pawn);
+        }
+        if m.is_castle(b) {
+            // toggling squares is its own inverse
+            let (rook_from, rook_to) = m.rook_move_from_to();
+            b.toggle_piece(rook_from.as_bb(), Piece::Rook, c);
+            b.toggle_piece(rook_to.as_bb(), Piece::Rook, c);
+            b.toggle_piece(m.from().as_bb(), Piece::King, c);
+            b.toggle_piece(m.castling_king_to().as_bb(), Piece::King, c);
+        } else {
+            b.move_piece(m.to().as_bb(), m.from().as_bb(), m.mover_piece(b), c);
+        }
+        if let Some(captured) = undo.captured {
+            b.toggle_piece(m.capture_square(b).as_bb(), captured, c.flip_side());
+        }
+        debug_assert!(
+            b.keys == Hasher::instance().hash_keys(b),
+            "{b}.undo_move({m}) has inconsistent hash {:x?}",
+            b.keys
+        );
+    }
+
+    fn clear_lazy_bitboards(&mut self) {
+        self.threats_to = Default::default();
+        self.checkers_of = Default::default();
+        self.pinned = Default::default();
+        self.discoverer = Default::default();
+    }
+
     #[inline]
     pub fn apply_move(&mut self, m: Move) {
         let b = self;
         b.fullmove_number += b.turn.chooser_wb(0, 1);
         b.halfmove_clock += 1;
         b.ply += 1;
-        let move_hash = Hasher::instance().hash_move(m, b);
-        b.hash ^= move_hash;
+        let move_keys = Hasher::instance().hash_move_keys(m, b);
+        b.keys ^= move_keys;
+        let castling_rights_lost = b.castling_rights_lost(m.from(), m.to());
         // now hash calculated - we can adjust these
         b.turn = b.turn.flip_side();
         if m.is_null() {
@@ -168,7 +247,7 @@ impl Board {
         let mut en_passant = None;
         if mover == Piece::Pawn {
             b.halfmove_clock = 0;
-            if m.is_pawn_double_push(b) {
+            if m.is_pawn_double_push(b) && m.is_ep_capturable(b) {
                 en_passant = Some(m.double_push_en_passant_square());
             }
         }
@@ -197,17 +276,19 @@ impl Board {
         // castling *moves*
         if m.is_castle(b) {
             // rules say no reset of fifty clock
-            // king move already handled, castling rights handled below, just the rook move
-
+            // the move is king-takes-rook, and in Chess960 the king or rook may not move at all,
+            // or swap squares, so toggle each square rather than moving from-to
+            let c = b.turn.flip_side();
             let (rook_from, rook_to) = m.rook_move_from_to();
-            // let rook_from_to = rook_from.as_bb() ^ rook_to.as_bb();
-            b.move_piece(rook_from.as_bb(), rook_to.as_bb(), Piece::Rook, b.turn.flip_side())
+            b.toggle_piece(rook_from.as_bb(), Piece::Rook, c);
+            b.toggle_piece(rook_to.as_bb(), Piece::Rook, c);
+            b.toggle_piece(m.from().as_bb(), Piece::King, c);
+            b.toggle_piece(m.castling_king_to().as_bb(), Piece::King, c);
+        } else {
+            // clear one bit and set another for the move using xor
+            b.move_piece(m.from().as_bb(), m.to().as_bb(), mover, b.turn.flip_side());
         }
 
-        // clear one bit and set another for the move using xor
-        // let from_to_bits = m.from().as_bb() | m.to().as_bb();
-        b.move_piece(m.from().as_bb(), m.to().as_bb(), mover, b.turn.flip_side());
-
         if let Some(promo) = m.promo_piece() {
             // fifty clock handled by pawn move above;
             b.change_piece(m.to().as_bb(), Piece::Pawn, promo);
@@ -218,7 +299,7 @@ impl Board {
         //  if a piece moves TO (=capture) or FROM the rook squares - appropriate castling rights are lost
         //  if a piece moves FROM the kings squares, both castling rights are lost
         //  possible with a rook x rook capture that both sides lose castling rights
-        b.castling -= m.castling_rights_lost();
+        b.castling -= castling_rights_lost;
         // b.castling ^= m.castling_side();
     }
 }
@@ -231,6 +312,7 @@ mod tests {
     use anyhow::Result;
 
     use super::*;
+    use crate::bits::{CastlingRights, Square};
     use crate::catalog::*;
     use crate::infra::profiler::PerfProfiler;
     use crate::Bitboard;
@@ -245,7 +327,7 @@ mod tests {
         let board = board.make_move(mov);
         assert_eq!(
             board.to_fen(),
-            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
         );
         assert_eq!(board.total_halfmove_ply(), 1);
 
@@ -325,6 +407,31 @@ mod tests {
         assert_eq!(board.to_fen(), "1r2k2r/pppppppp/8/8/8/8/PPPPPPPP/1R2K2R w Kk - 2 2");
     }
 
+    #[test]
+    fn test_castling_chess960() {
+        // king on c1, so castling queen side moves only the rook, and king side only the king
+        let board = Board::parse_fen("1rk2r2/pppppppp/8/8/8/8/PPPPPPPP/1RK2R2 w FBfb - 0 1").unwrap();
+        board.validate().unwrap();
+        assert_eq!(board.to_fen(), "1rk2r2/pppppppp/8/8/8/8/PPPPPPPP/1RK2R2 w KQkq - 0 1");
+        assert_eq!(board.legal_moves().len(), 16 + 5 + 1 + 1 + 1); // 16P, 5R, 1K, OO, OOO
+
+        let board = board.make_move(board.parse_uci_move("c1b1").unwrap());
+        board.validate().unwrap();
+        assert_eq!(board.to_fen(), "1rk2r2/pppppppp/8/8/8/8/PPPPPPPP/2KR1R2 b kq - 1 1");
+
+        let board = board.make_move(board.parse_uci_move("c8f8").unwrap());
+        board.validate().unwrap();
+        assert_eq!(board.to_fen(), "1r3rk1/pppppppp/8/8/8/8/PPPPPPPP/2KR1R2 w - - 2 2");
+        assert_eq!(board.hash(), Board::parse_fen(&board.to_fen()).unwrap().hash());
+
+        // an inner rook needs its file in the fen, as an outer rook would be assumed by KQkq
+        let board = Board::parse_fen("rr1k4/8/8/8/8/8/8/RR1K4 w Bb - 0 1").unwrap();
+        assert_eq!(board.castling_rook_square(CastlingRights::WHITE_QUEEN), Square::B1);
+        assert_eq!(board.to_fen(), "rr1k4/8/8/8/8/8/8/RR1K4 w Bb - 0 1");
+        let board = Board::parse_fen("rr1k4/8/8/8/8/8/8/RR1K4 w Qq - 0 1").unwrap();
+        assert_eq!(board.castling_rook_square(CastlingRights::WHITE_QUEEN), Square::A1);
+    }
+
     #[test]
     fn bench_make_move() {
         let mut b = Catalog::starting_board();
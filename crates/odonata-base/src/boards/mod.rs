@@ -1,5 +1,6 @@
 pub mod board;
 pub mod boardcalcs;
+pub mod fen;
 pub mod hasher;
 pub mod makemove;
 pub mod movegen;
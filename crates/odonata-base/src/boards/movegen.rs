@@ -1,7 +1,11 @@
+use std::cell::Cell;
+
 use anyhow::anyhow;
 use itertools::Itertools;
+use rand::SeedableRng as _;
+use rand_chacha::ChaChaRng;
 
-use crate::bits::bitboard::{Bitboard, Dir};
+use crate::bits::bitboard::Bitboard;
 use crate::bits::castling::CastlingRights;
 use crate::bits::precalc::PreCalc;
 use crate::bits::square::Square;
@@ -13,6 +17,36 @@ use crate::movelist::MoveList;
 use crate::mv::Move;
 use crate::piece::Piece;
 
+thread_local!(static SHUFFLE_SEED: Cell<Option<u64>> = const { Cell::new(None) });
+
+/// A debug aid to shake out code that depends on move generation order - say search
+/// results that change when scores tie, or perft helpers assuming a stable order. When
+/// seeded (per thread), legal moves are generated in a random order, which for a given
+/// seed and position is always the same.
+pub struct MoveGenOrder;
+
+impl MoveGenOrder {
+    pub fn set_shuffle_seed(seed: Option<u64>) {
+        SHUFFLE_SEED.with(|s| s.set(seed));
+    }
+
+    #[inline]
+    pub fn shuffle_seed() -> Option<u64> {
+        SHUFFLE_SEED.with(|s| s.get())
+    }
+
+    fn shuffled(bd: &Board, seed: u64, to: Bitboard, pseudo: bool, mut f: impl FnMut(Move)) {
+        let mut moves = MoveList::new();
+        if pseudo {
+            LegalMoves::new_pseudo(bd, to, |mv| moves.push(mv));
+        } else {
+            LegalMoves::new(bd, to, |mv| moves.push(mv));
+        }
+        moves.shuffle(&mut ChaChaRng::seed_from_u64(seed ^ bd.hash()));
+        moves.iter().for_each(|&mv| f(mv));
+    }
+}
+
 #[derive(Debug)]
 pub struct LegalMoves<'a, F: FnMut(Move)> {
     board:        &'a Board,
@@ -36,22 +70,95 @@ impl Board {
     }
 
     #[inline]
-    pub fn legal_moves_to_with(&self, to: Bitboard, f: impl FnMut(Move)) {
+    pub fn legal_moves_to_with(&self, to: Bitboard, mut f: impl FnMut(Move)) {
         Metrics::incr(Counter::MoveGen);
-        LegalMoves::new(self, to, f);
+        let f = |mv: Move| {
+            crate::paranoid_assert!(
+                mv.to_inner().validate(self) == Ok(mv),
+                "paranoid: generated move {mv} fails validation on {self}"
+            );
+            f(mv)
+        };
+        match MoveGenOrder::shuffle_seed() {
+            Some(seed) => MoveGenOrder::shuffled(self, seed, to, false, f),
+            None => {
+                LegalMoves::new(self, to, f);
+            }
+        }
     }
 
+    /// pseudo-legal moves, to be checked with `is_legal` as each is tried. Pins, the squares
+    /// the king steps onto and en passant discovered checks are left unchecked, so a node cut
+    /// by its first move never computes them. Castles are generated only when legal
     #[inline]
-    pub fn legal_moves_with(&self, f: impl FnMut(Move)) {
+    pub fn pseudo_legal_moves_to_with(&self, to: Bitboard, f: impl FnMut(Move)) {
         Metrics::incr(Counter::MoveGen);
-        LegalMoves::new(self, Bitboard::all(), f);
+        match MoveGenOrder::shuffle_seed() {
+            Some(seed) => MoveGenOrder::shuffled(self, seed, to, true, f),
+            None => {
+                LegalMoves::new_pseudo(self, to, f);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn pseudo_legal_moves_with(&self, f: impl FnMut(Move)) {
+        self.pseudo_legal_moves_to_with(Bitboard::all(), f);
+    }
+
+    /// whether a pseudo-legal move leaves our king safe. The checkers and pins are cached on
+    /// the board, and a king move tests only the square it lands on
+    pub fn is_legal(&self, mv: Move) -> bool {
+        let Some(king_sq) = (self.kings() & self.us()).find_first_square() else {
+            // allow no kings
+            return true;
+        };
+        if mv.is_castle(self) {
+            return true;
+        }
+        if mv.is_ep_capture(self) {
+            return !self.make_move(mv).is_in_check(self.color_us());
+        }
+        if mv.from() == king_sq {
+            let occ = self.occupied() - king_sq.as_bb();
+            return BoardCalcs::attacked_by(mv.to().as_bb(), occ, self).disjoint(self.them());
+        }
+        let gen = PreCalc::instance();
+        let checkers = self.checkers_of(self.color_us());
+        match checkers.popcount() {
+            0 => {}
+            1 => {
+                let checker = checkers.find_first_square().expect("single checker");
+                if !mv.to().is_in(gen.between(king_sq, checker) | checkers) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+        !mv.from().is_in(self.pinned(self.color_us())) || mv.to().is_in(gen.line_through(mv.from(), king_sq))
+    }
+
+    /// captures, including en passant and capture-promotions
+    #[inline]
+    pub fn legal_captures_with(&self, f: impl FnMut(Move)) {
+        self.legal_moves_to_with(self.them(), f);
+    }
+
+    /// the non-captures, including castling and quiet promotions
+    #[inline]
+    pub fn legal_quiets_with(&self, f: impl FnMut(Move)) {
+        self.legal_moves_to_with(!self.them(), f);
+    }
+
+    #[inline]
+    pub fn legal_moves_with(&self, f: impl FnMut(Move)) {
+        self.legal_moves_to_with(Bitboard::all(), f);
     }
 
     #[inline]
     pub fn legal_moves(&self) -> MoveList {
-        Metrics::incr(Counter::MoveGen);
         let mut moves = MoveList::new();
-        LegalMoves::new(self, Bitboard::all(), |mv| moves.push(mv));
+        self.legal_moves_to_with(Bitboard::all(), |mv| moves.push(mv));
         moves
     }
 }
@@ -70,6 +177,17 @@ where
         me
     }
 
+    /// see `Board::pseudo_legal_moves_to_with`
+    pub fn new_pseudo(board: &'a Board, capture_mask: Bitboard, callback: F) -> Self {
+        let mut me = Self {
+            board,
+            capture_mask,
+            callback,
+        };
+        me.generate_pseudo();
+        me
+    }
+
     pub fn king_legal(&mut self) {
         let bd = self.board;
         let us = bd.us();
@@ -170,6 +288,38 @@ where
         self.castles();
     }
 
+    fn generate_pseudo(&mut self) {
+        let t = Metrics::timing_start();
+        let b = self.board;
+        let gen = PreCalc::instance();
+        let us = b.us();
+        let them = b.them();
+        for &p in Piece::ALL_BAR_KING.iter() {
+            for fr in (b.pieces(p) & us).squares() {
+                let attacks = self.capture_mask & gen.attacks(b.color_us(), p, us, them, fr) & !us;
+                self.add_moves(attacks, p, fr, b);
+            }
+        }
+        if let Some(to) = b.en_passant_square() {
+            let to_bb = to.as_bb();
+            let c = b.color_them();
+            if to_bb.shift(c.forward()).intersects(self.capture_mask) {
+                let fr = to_bb.shift(c.pawn_capture_west()) | to_bb.shift(c.pawn_capture_east());
+                for fr in (fr & b.pawns() & us).squares() {
+                    (self.callback)(Move::new_ep_capture(fr, to, b));
+                }
+            }
+        }
+        if let Some(king_sq) = (b.kings() & us).find_first_square() {
+            let attacks = gen.king_attacks(king_sq) & !us & self.capture_mask;
+            self.add_moves(attacks, Piece::King, king_sq, b);
+            if !b.castling().is_empty() && !b.is_in_check(b.color_us()) {
+                self.castles();
+            }
+        }
+        Metrics::profile(t, Timing::TimingMoveGen);
+    }
+
     pub fn add_moves_en_passant(&mut self) {
         let bd = self.board;
         let Some(to) = bd.en_passant_square() else {
@@ -257,6 +407,9 @@ where
         }
     }
 
+    /// castles are king-takes-rook. The squares the king and rook pass through or land on must be
+    /// empty (bar the two castling pieces) and the king must not pass through check. In Chess960 the
+    /// castling rook can shield the king along the back rank, so it is removed before testing attacks.
     pub fn castles(&mut self) {
         let b = self.board;
         let c = b.color_us();
@@ -270,28 +423,25 @@ where
         }
         let king_sq = b.our_king();
         let rights = b.castling();
+        let pc = PreCalc::instance();
 
-        let right = CastlingRights::king_side_right(c);
-        if rights.contains(right) && !CastlingRights::king_side_move_squares(c).intersects(occ) {
-            let rook_to = king.shift(Dir::E);
-            let king_to = rook_to.shift(Dir::E);
-            let king_moves = king | rook_to | king_to;
-            if BoardCalcs::attacked_by(king_moves, occ, b).disjoint(them) && king_to.intersects(self.capture_mask) {
-                let m = Move::new_castle(king_sq, king_to.find_first_square().expect("king to (short)"), b);
-                (self.callback)(m);
+        for right in [CastlingRights::king_side_right(c), CastlingRights::queen_side_right(c)] {
+            if !rights.contains(right) {
+                continue;
             }
-        }
-
-        let right = CastlingRights::queen_side_right(c);
-        if rights.contains(right) && !CastlingRights::queen_side_move_squares(c).intersects(occ) {
-            let rook_to = king.shift(Dir::W);
-            let king_to = rook_to.shift(Dir::W);
-            let king_moves = king | rook_to | king_to;
-            if BoardCalcs::attacked_by(king_moves, occ, b).disjoint(them) && king_to.intersects(self.capture_mask) {
-                let king_to = king_to.find_first_square().expect("king to (long)");
-                // let rook_from = king_to.shift(Dir::W).shift(Dir::W);
-                let m = Move::new_castle(king_sq, king_to, b);
-                (self.callback)(m);
+            let rook_sq = b.castling_rook_square(right);
+            if !rook_sq.is_in(b.rooks() & us) || !rook_sq.is_in(self.capture_mask) {
+                continue;
+            }
+            let (king_to, rook_to) = right.king_and_rook_to();
+            let king_moves = pc.between(king_sq, king_to);
+            let rook_moves = pc.between(rook_sq, rook_to);
+            let castlers = king | rook_sq.as_bb();
+            if ((king_moves | rook_moves) - castlers).intersects(occ) {
+                continue;
+            }
+            if BoardCalcs::attacked_by(king_moves, occ - rook_sq.as_bb(), b).disjoint(them) {
+                (self.callback)(Move::new_castle(king_sq, rook_sq, b));
             }
         }
     }
@@ -314,19 +464,37 @@ mod tests {
 
     #[test]
     fn test_king_legal() {
-        let b = BoardBuilder::parse_piece_placement("rk6/8/8/8/8/8/8/1K6").unwrap().build();
+        let b = BoardBuilder::parse_piece_placement("rk6/8/8/8/8/8/8/1K6")
+            .unwrap()
+            .build();
         let mut moves = MoveList::new();
         let _lm = LegalMoves::new(&b, Bitboard::all(), |mv| moves.push(mv));
         moves.retain(|m| b.piece(m.from()) == Some(Piece::King));
         assert_eq!(moves.sort().to_string(), "b1b2, b1c1, b1c2");
 
-        let b = BoardBuilder::parse_piece_placement("rk6/8/8/8/8/8/K7/8").unwrap().build();
+        let b = BoardBuilder::parse_piece_placement("rk6/8/8/8/8/8/K7/8")
+            .unwrap()
+            .build();
         let mut moves = MoveList::new();
         let _lm = LegalMoves::new(&b, Bitboard::all(), |mv| moves.push(mv));
         // xray prevents a2a1
         assert_eq!(moves.sort().to_string(), "a2b1, a2b2, a2b3");
     }
 
+    #[test]
+    fn test_move_gen_order() {
+        let (bd, counts) = Catalog::perft_kiwipete();
+        let natural = bd.legal_moves();
+        MoveGenOrder::set_shuffle_seed(Some(42));
+        let shuffled = bd.legal_moves();
+        assert_ne!(shuffled, natural);
+        assert_eq!(shuffled, bd.legal_moves(), "same seed, same order");
+        assert_eq!(shuffled.clone().sort(), natural.clone().sort());
+        assert_eq!(Perft::<true>::count(&bd, 3), counts[3]);
+        MoveGenOrder::set_shuffle_seed(None);
+        assert_eq!(bd.legal_moves(), natural);
+    }
+
     #[test]
     fn test_is_pseudo_legal_move() {
         let bd = Board::parse_fen("8/k7/8/8/4Q3/8/8/1K6 w - - 9 4").unwrap();
@@ -514,6 +682,39 @@ mod tests {
         assert_eq!(bd.is_in_check(Color::White), true);
     }
 
+    #[test]
+    fn test_legal_captures_and_quiets() {
+        for (bd, _) in Catalog::perfts() {
+            let mut f = |bd: &Board, _mv: Move| {
+                let (mut captures, mut quiets) = (MoveList::new(), MoveList::new());
+                bd.legal_captures_with(|mv| captures.push(mv));
+                bd.legal_quiets_with(|mv| quiets.push(mv));
+                assert!(captures.iter().all(|mv| mv.is_capture()), "{bd}");
+                assert!(quiets.iter().all(|mv| !mv.is_capture()), "{bd}");
+                let mut both = captures;
+                both.extend(quiets.iter().cloned());
+                assert_eq!(both.sort(), bd.legal_moves().sort(), "{bd}");
+            };
+            Perft::<false>::perft_with(&mut bd.clone(), 2, &mut f);
+        }
+    }
+
+    #[test]
+    fn test_pseudo_legal_and_is_legal() {
+        for (bd, _) in Catalog::perfts() {
+            let mut f = |bd: &Board, _mv: Move| {
+                let (mut legal, mut illegal) = (MoveList::new(), MoveList::new());
+                bd.pseudo_legal_moves_with(|mv| match bd.is_legal(mv) {
+                    true => legal.push(mv),
+                    false => illegal.push(mv),
+                });
+                assert_eq!(legal.sort(), bd.legal_moves().sort(), "{bd}");
+                assert!(illegal.iter().all(|mv| mv.to_inner().validate(bd).is_err()), "{bd}");
+            };
+            Perft::<false>::perft_with(&mut bd.clone(), 2, &mut f);
+        }
+    }
+
     #[test]
     fn test_legal_move_perft() {
         let mut starting_pos = Catalog::perft_kiwipete().0;
@@ -1,5 +1,6 @@
 use std::fmt::{Debug, Display};
 
+use crate::boards::makemove::Undo;
 use crate::other::outcome::Outcome;
 use crate::other::tags::EpdOps;
 use crate::piece::{FlipSide, Ply, Repeats};
@@ -9,11 +10,13 @@ use crate::Epd;
 /// initial board >---(starting-moves)--> root_board >---(search-variation)--> board
 #[derive(Clone, Default, PartialEq, Eq)]
 pub struct Position {
-    pub board: Board,
-    hash:      Hash,
-    history:   Vec<(Board, Hash, Move)>, // preboard
-    ply:       usize,
-    sel_ply:   usize,
+    pub board:  Board,
+    hash:       Hash,
+    history:    Vec<(Board, Hash, Move)>, // preboard
+    undos:      Vec<(Undo, Hash, Move)>,  // moves made in place, after those in history
+    ply:        usize,
+    sel_ply:    usize,
+    setup_reps: u16, // occurrences of the setup board before it, eg in game history prior to a fen
 }
 
 impl Display for Position {
@@ -77,9 +80,11 @@ impl Debug for Position {
             f.debug_struct("Position")
                 .field("board", &self.board)
                 .field("history", &self.history)
+                .field("undos", &self.undos)
                 .field("hash", &self.hash)
                 .field("ply", &self.ply)
                 .field("sel_ply", &self.sel_ply)
+                .field("setup_reps", &self.setup_reps)
                 .finish()
         }
     }
@@ -92,11 +97,19 @@ impl Position {
 
     pub fn from_board(board: Board) -> Self {
         Self {
+            hash: board.hash(),
             board,
             ..Position::default()
         }
     }
 
+    /// repetitions straddling the setup board (eg a `position fen ... moves ...` from mid-game)
+    /// need the count of its earlier occurrences, which are not in the history
+    pub fn with_setup_reps(mut self, prior_reps: u16) -> Self {
+        self.setup_reps = prior_reps;
+        self
+    }
+
     pub fn from_played_moves(starting_board: Board, setup_moves: Variation) -> Self {
         let mut pos = Position::from_board(starting_board);
         pos.push_moves(setup_moves);
@@ -106,6 +119,7 @@ impl Position {
 
     pub fn has_null_move(&self) -> bool {
         self.search_history().iter().any(|&(_, _, mv)| mv.is_null())
+            || self.undos.iter().any(|&(_, _, mv)| mv.is_null())
     }
 
     #[inline]
@@ -142,10 +156,27 @@ impl Position {
 
     /// reassigns search moves to played moves
     pub fn play_search_moves(&mut self) {
+        debug_assert!(self.undos.is_empty(), "cannot play moves made in place");
         self.ply = 0;
         self.sel_ply = 0;
     }
 
+    /// the number of search moves, including those made in place
+    pub fn search_ply(&self) -> usize {
+        self.ply + self.undos.len()
+    }
+
+    /// the hash of the board `ply` search moves from the root, if on the path to the current board
+    pub fn search_hash(&self, ply: usize) -> Option<Hash> {
+        let search_history = self.search_history();
+        match ply.checked_sub(search_history.len()) {
+            None => Some(search_history[ply].1),
+            Some(i) if i < self.undos.len() => Some(self.undos[i].1),
+            Some(i) if i == self.undos.len() => Some(self.hash),
+            Some(_) => None,
+        }
+    }
+
     pub fn setup_board(&self) -> Board {
         self.get_board(0).clone()
     }
@@ -156,7 +187,12 @@ impl Position {
 
     // excludes starting moves
     pub fn search_variation(&self) -> Variation {
-        self.search_history().iter().map(|(_b, _h, m)| *m).collect()
+        let undos = self.undos.iter().map(|(_u, _h, m)| *m);
+        self.search_history()
+            .iter()
+            .map(|(_b, _h, m)| *m)
+            .chain(undos)
+            .collect()
     }
 
     pub fn push_moves_str(&mut self, var: &str) -> Result<()> {
@@ -171,6 +207,7 @@ impl Position {
 
     #[inline]
     pub fn push_move(&mut self, mv: Move) {
+        debug_assert!(self.undos.is_empty(), "push_move {mv} after a move made in place");
         self.history.push((self.board.make_move(mv), self.hash, mv));
         let (old_mut, ..) = self.history.last_mut().unwrap();
         std::mem::swap(&mut self.board, old_mut);
@@ -179,22 +216,45 @@ impl Position {
     }
 
     pub fn prior_move(&self) -> Option<Move> {
-        self.history.last().map(|(_b, _h, mv)| *mv)
+        match self.undos.last() {
+            Some((_u, _h, mv)) => Some(*mv),
+            None => self.history.last().map(|(_b, _h, mv)| *mv),
+        }
     }
 
+    /// unavailable for moves made in place
     pub fn prior_board(&self) -> Option<&Board> {
+        debug_assert!(self.undos.is_empty(), "no prior board for a move made in place");
         self.history.last().map(|(b, _h, _mv)| b)
     }
 
     #[inline]
     pub fn pop_move(&mut self) -> Move {
-        debug_assert!(self.ply > 0);
+        debug_assert!(self.ply > 0 && self.undos.is_empty());
         let mv;
         (self.board, self.hash, mv) = self.history.pop().unwrap();
         self.ply -= 1;
         mv
     }
 
+    /// Makes the move on the board in place, saving the cost of the board copy made by
+    /// [`Position::push_move`]. The prior board is not kept, so until the move is undone
+    /// there is no `prior_board`, and moves may not be pushed.
+    #[inline]
+    pub fn make_move(&mut self, mv: Move) {
+        let undo = self.board.do_move(mv);
+        self.undos.push((undo, self.hash, mv));
+        self.hash = self.board.hash();
+    }
+
+    #[inline]
+    pub fn undo_move(&mut self) -> Move {
+        let (undo, hash, mv) = self.undos.pop().expect("undo_move without a move made in place");
+        self.board.undo_move(mv, undo);
+        self.hash = hash;
+        mv
+    }
+
     /// current position counts as 1
     pub fn played_reps(&self) -> usize {
         let hash = self.board().hash();
@@ -205,6 +265,11 @@ impl Position {
             .filter(|&(_, h, _)| *h == hash)
             .count()
             + 1
+            + match self.setup_reps {
+                0 => 0,
+                n if hash == self.get_board(0).hash() => n as usize,
+                _ => 0,
+            }
     }
 
     pub fn repetition_counts(&self) -> Repeats {
@@ -219,16 +284,43 @@ impl Position {
     pub fn search_reps(&self) -> usize {
         let include_prior_to_null_moves = true;
         let hash = self.board().hash();
+        let undos = self.undos.iter().map(|&(_, h, mv)| (h, mv));
         self.search_history()
             .iter()
+            .map(|&(_, h, mv)| (h, mv))
+            .chain(undos)
             .rev()
-            .take_while(|&(_, _, mv)| include_prior_to_null_moves || !mv.is_null())
+            .take_while(|&(_, mv)| include_prior_to_null_moves || !mv.is_null())
             .skip(1)
             .step_by(2)
-            .filter(|&(_, h, _)| *h == hash)
+            .filter(|&(h, _)| h == hash)
             .count()
             + 1
     }
+
+    /// A repeat of an earlier position (in the game or the search) with every move of the side
+    /// that just moved, since the earlier occurrence, giving check: a perpetual, which that side
+    /// could force again. Unavailable (false) for moves made in place.
+    pub fn is_perpetual_check(&self) -> bool {
+        let us = self.board().color_us();
+        if !self.undos.is_empty() || !self.board().is_in_check(us) {
+            return false;
+        }
+        let hash = self.board().hash();
+        // every other board back is ours to move, and the checking side has just moved into it
+        for (b, h, mv) in self.history.iter().rev().skip(1).step_by(2) {
+            if mv.is_null() {
+                return false;
+            }
+            if *h == hash {
+                return true;
+            }
+            if !b.is_in_check(us) {
+                return false;
+            }
+        }
+        false
+    }
 }
 
 impl Position {
@@ -277,8 +369,10 @@ impl Position {
             (uci, None)
         };
 
-        let board = match fen {
+        let board = match fen.strip_prefix("fen").map_or(fen, str::trim_start) {
             "startpos" => Board::starting_pos(),
+            // some guis omit the halfmove clock and fullmove number
+            fen if fen.split_whitespace().count() == 4 => Board::parse_fen(&format!("{fen} 0 1"))?,
             fen => Board::parse_fen(fen)?,
         };
 
@@ -290,8 +384,8 @@ impl Position {
     }
 
     pub fn from_epd(epd: Epd) -> Position {
-        let mut pos = Self::from_played_moves(epd.setup_board(), epd.played().clone());
         let pv = epd.var("pv").unwrap_or_default();
+        let mut pos = epd.into_pos();
         pos.push_moves(pv);
         pos
     }
@@ -328,6 +422,64 @@ mod tests {
         assert_eq!(start, clone);
     }
 
+    #[test]
+    fn test_position_make_undo() -> Result<()> {
+        let mut pos = Position::starting_pos();
+        pos.push_moves_str("e4 d5")?;
+        let start = pos.clone();
+        let var = pos
+            .board()
+            .parse_san_variation("exd5 Qxd5 Nc3 Qe4+ Be2 Qxe2+ Ngxe2 Bg4 O-O Nc6")?;
+        let mut copied = pos.clone();
+        for mv in var.moves() {
+            pos.make_move(mv);
+            copied.push_move(mv);
+            assert_eq!(pos.board(), copied.board());
+            assert_eq!(pos.hash(), copied.hash());
+            assert_eq!(pos.prior_move(), Some(mv));
+            assert_eq!(pos.repetition_counts(), copied.repetition_counts());
+        }
+        assert_eq!(pos.search_variation(), copied.search_variation());
+        assert_eq!(pos.search_ply(), var.len() + 2);
+        for ply in 0..=pos.search_ply() + 1 {
+            assert_eq!(pos.search_hash(ply), copied.search_hash(ply), "ply {ply}");
+        }
+        assert_eq!(pos.search_hash(0), Some(Board::starting_pos().hash()));
+        assert_eq!(pos.search_hash(pos.search_ply()), Some(pos.hash()));
+        assert_eq!(pos.search_hash(pos.search_ply() + 1), None);
+        for mv in var.moves().rev() {
+            assert_eq!(pos.undo_move(), mv);
+        }
+        assert_eq!(pos, start);
+        assert_eq!(pos.board().to_fen(), start.board().to_fen());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_perpetual_check() -> Result<()> {
+        let b = Board::parse_fen("6k1/6p1/8/6KQ/1r6/q2b4/8/8 w - - 2 1")?;
+        let mut pos = Position::from_board(b);
+        pos.push_moves_str("Qe8+ Kh7 Qh5+")?;
+        assert!(!pos.is_perpetual_check());
+        pos.push_moves_str("Kg8 Qe8+")?;
+        assert!(pos.is_perpetual_check());
+        pos.play_search_moves();
+        assert!(pos.is_perpetual_check(), "repeats a played position");
+
+        // a repeat without checks
+        let mut pos = Position::starting_pos();
+        pos.push_moves_str("Nf3 Nf6 Ng1 Ng8")?;
+        assert_eq!(pos.search_reps(), 2);
+        assert!(!pos.is_perpetual_check());
+
+        // checks that do not repeat
+        let b = Board::parse_fen("6k1/6p1/8/6KQ/1r6/q2b4/8/8 w - - 2 1")?;
+        let mut pos = Position::from_board(b);
+        pos.push_moves_str("Qe8+ Kh7 Qh5+ Kg8 Qe6+")?;
+        assert!(!pos.is_perpetual_check());
+        Ok(())
+    }
+
     #[test]
     fn test_position_parse_uci() {
         let start = Position::from_board(Board::starting_pos());
@@ -375,6 +527,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_position_partial_game() -> Result<()> {
+        // mid-game fen, the move tail returns to it twice => threefold
+        let fen = "8/1k6/8/8/8/2K4R/8/8 w - - 10 40";
+        let tail = "h3h1 b7a8 h1h3 a8b7 h3h1 b7a8 h1h3 a8b7";
+        let pos = Position::parse_uci(&format!("position fen {fen} moves {tail}"))?;
+        assert_eq!(pos.played_reps(), 3);
+        assert_eq!(pos.outcome(), Outcome::DrawRepetition3);
+        assert_eq!(pos.board().halfmove_clock(), 18);
+
+        // prior occurrences of the fen (from earlier in the game) straddle it
+        let pos = Position::parse_uci(&format!("position fen {fen} moves h3h1 b7a8 h1h3 a8b7"))?;
+        assert_eq!(pos.played_reps(), 2);
+        let pos = pos.with_setup_reps(1);
+        assert_eq!(pos.played_reps(), 3);
+        let epd = Epd::parse_epd(&format!("{fen} moves Rh1 Ka8 Rh3 Kb7; Rcs 2;"))?;
+        assert_eq!(Position::from_epd(epd).outcome(), Outcome::DrawRepetition3);
+
+        // only counted when back at the fen position
+        let epd = Epd::parse_epd(&format!("{fen} moves Rh1; Rcs 2;"))?;
+        assert_eq!(Position::from_epd(epd).played_reps(), 1);
+
+        // counters default when omitted
+        let pos = Position::parse_uci("position fen 8/1k6/8/8/8/2K4R/8/8 w - - moves h3h1")?;
+        assert_eq!(pos.board().halfmove_clock(), 1);
+
+        // 50-move state from the fen counters
+        let fen = "8/1k6/8/8/8/2K4R/8/8 w - - 98 80";
+        let pos = Position::parse_uci(&format!("position fen {fen} moves h3h1"))?;
+        assert_eq!(pos.outcome(), Outcome::Unterminated);
+        let pos = Position::parse_uci(&format!("position fen {fen} moves h3h1 b7a8"))?;
+        assert_eq!(pos.outcome(), Outcome::DrawRule50);
+        Ok(())
+    }
+
     #[test]
     fn test_position_checkmate() {
         assert_eq!(
@@ -389,7 +576,7 @@ mod tests {
 
     #[test]
     fn test_position_size() {
-        assert_eq!(size_of::<Position>(), 200);
+        assert_eq!(size_of::<Position>(), 256);
     }
 
     #[test]
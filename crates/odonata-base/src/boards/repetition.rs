@@ -16,6 +16,7 @@ pub struct Repetition {
     include_prior_to_null_moves: bool,
     in_played_threshold:         usize,
     in_search_threshold:         usize,
+    perpetual_check:             bool,
 
     #[serde(skip)]
     prior_positions: Vec<Hash>,
@@ -30,6 +31,8 @@ pub struct Repetition {
 ///
 /// we may however need to play a repeat of a played position to get out of a bad position
 /// so dont terminate search on these. 3 matches means the move is the third time in this position => draw
+///
+/// unless the repeat of a played position is a perpetual check, which the checking side can force again
 impl Default for Repetition {
     fn default() -> Self {
         Self {
@@ -38,6 +41,7 @@ impl Default for Repetition {
             include_prior_to_null_moves: true, //  +19 elo
             in_played_threshold:         3,
             in_search_threshold:         2,
+            perpetual_check:             true,
             root_index:                  0,
             prior_positions:             Vec::new(),
         }
@@ -52,6 +56,7 @@ impl Configurable for Repetition {
             .set(p.get("include_prior_to_null_moves"))?;
         self.in_played_threshold.set(p.get("in_played_threshold"))?;
         self.in_search_threshold.set(p.get("in_search_threshold"))?;
+        self.perpetual_check.set(p.get("perpetual_check"))?;
         Ok(p.is_modified())
     }
 }
@@ -122,7 +127,9 @@ impl Repetition {
         if self.never_with_null_moves && p.has_null_move() {
             return false;
         }
-        p.search_reps() >= self.in_search_threshold || p.played_reps() >= self.in_played_threshold
+        p.search_reps() >= self.in_search_threshold
+            || p.played_reps() >= self.in_played_threshold
+            || self.perpetual_check && p.played_reps() >= 2 && p.is_perpetual_check()
     }
 
     // pub fn count(&self, p: &Position) -> Repeats {
@@ -1,18 +1,25 @@
 use std::fmt;
+use std::path::Path;
 
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
 
 use crate::bits::bitboard::Bitboard;
-use crate::bits::castling::CastlingRights;
+use crate::bits::castling::{CastlingFiles, CastlingRights};
 use crate::epd::Epd;
 use crate::infra::resources::read_resource_file;
+use crate::infra::utils::read_file;
+use crate::other::tags::{TagOps, Tags};
+use crate::other::{EpdFilter, PerftPosition};
 use crate::prelude::Board;
 
 pub struct Catalog;
 
 // http://computer-chess.org/doku.php?id=computer_chess:wiki:download:epd_contents
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
 pub enum CatalogSuite {
     Empty,
     BratkoKopec,
@@ -21,6 +28,7 @@ pub enum CatalogSuite {
     Tricky,
     Checkmate,
     Zugzwang,
+    Fortress,
     Repetitions,
     Famous,
     EndGame,
@@ -55,6 +63,7 @@ impl Catalog {
             CatalogSuite::Checkmate => Self::checkmates(),
             CatalogSuite::Repetitions => Self::repetitions(),
             CatalogSuite::Zugzwang => Self::zugzwangs(),
+            CatalogSuite::Fortress => Self::fortresses(),
             CatalogSuite::Famous => Self::famous(),
             CatalogSuite::EndGame => Self::end_games(),
             CatalogSuite::Pin => Self::pins(),
@@ -72,6 +81,116 @@ impl Catalog {
         }
     }
 
+    /// a built-in suite name (eg "BratkoKopec"), an EPD file, or a directory of EPD files.
+    /// Positions are tagged with their source (tag "Src") unless already tagged
+    pub fn load(source: &str) -> Result<Vec<Epd>> {
+        let path = Path::new(source);
+        if !path.exists() {
+            if let Ok(suite) = source.parse::<CatalogSuite>() {
+                if suite == CatalogSuite::Perft {
+                    bail!("suite {suite} is not a suite of EPDs");
+                }
+                return Ok(Self::tag_source(Self::positions(suite), &suite.to_string()));
+            }
+        }
+        let files = if path.is_dir() {
+            let mut files = std::fs::read_dir(path)
+                .with_context(|| format!("reading directory '{source}'"))?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            files.retain(|f| f.is_file() && Epd::has_extension(f));
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+        let mut epds = vec![];
+        for file in files {
+            let lines = read_file(&file).with_context(|| format!("reading suite '{source}'"))?;
+            let positions =
+                Epd::parse_many_epd(lines).with_context(|| format!("parsing EPDs in '{f}'", f = file.display()))?;
+            let name = file.file_stem().unwrap_or_default().to_string_lossy();
+            epds.extend(Self::tag_source(positions, &name));
+        }
+        Ok(epds)
+    }
+
+    /// merges suites, files and directories, retaining those EPDs matching the filter
+    pub fn load_many(sources: &[impl AsRef<str>], filter: Option<&EpdFilter>) -> Result<Vec<Epd>> {
+        let mut epds = vec![];
+        for source in sources {
+            let positions = Self::load(source.as_ref())?;
+            match filter {
+                Some(filter) => epds.extend(filter.select(positions)),
+                None => epds.extend(positions),
+            }
+        }
+        Ok(epds)
+    }
+
+    fn tag_source(mut epds: Vec<Epd>, source: &str) -> Vec<Epd> {
+        for epd in epds.iter_mut().filter(|e| e.get(Tags::SRC).is_none()) {
+            epd.set_tag(Tags::SRC, source);
+        }
+        epds
+    }
+
+    /// a position by name, case insensitive: "startpos", "kiwipete", "cpw3" or the id of a
+    /// catalog position (eg "WAC.001")
+    pub fn named(name: &str) -> Result<Epd> {
+        Self::named_positions()
+            .into_iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name.trim()))
+            .map(|(_, epd)| epd)
+            .with_context(|| format!("no catalog position named '{name}'"))
+    }
+
+    /// the names recognised by `named` that start with `prefix` (case insensitive)
+    pub fn names(prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_ascii_lowercase();
+        Self::named_positions()
+            .into_iter()
+            .map(|(n, _)| n)
+            .filter(|n| n.to_ascii_lowercase().starts_with(&prefix))
+            .collect()
+    }
+
+    fn named_positions() -> Vec<(String, Epd)> {
+        let mut named = vec![
+            ("startpos".to_string(), Self::starting_position()),
+            ("kiwipete".to_string(), Epd::from_board(Self::perft_kiwipete().0)),
+            ("cpw3".to_string(), Epd::from_board(Self::perft_cpw_number3().0)),
+        ];
+        use CatalogSuite::*;
+        for suite in [
+            BratkoKopec,
+            WinAtChess,
+            Iq81,
+            Tricky,
+            Checkmate,
+            Zugzwang,
+            Fortress,
+            Repetitions,
+            Famous,
+            EndGame,
+            Pin,
+            Recogs,
+            Move,
+            Quiesce,
+            See,
+            MateIn2,
+            MateIn3,
+            MateIn4,
+        ] {
+            for epd in Self::positions(suite) {
+                if let Some(id) = epd.tag(Tags::ID) {
+                    named.push((id.to_string(), epd.clone()));
+                }
+            }
+        }
+        named
+    }
+
     pub const STARTING_POSITION_FEN: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
     pub fn starting_board() -> Board {
@@ -234,16 +353,41 @@ r1r5/2N1n1kb/2nB1b1p/3p2p1/6q1/P2pQ3/3N1PPP/1RR3K1 b - - 11 37 acd 6;
         bb.build()
     }
 
+    /// positions with an `acn` (node budget) are expected to be solved with null move pruning enabled
     pub fn zugzwangs() -> Vec<Epd> {
         let str = r#"
         #
         ## https://www.chessprogramming.org/Null_Move_Test-Positions
         #
-        8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1 bm Rf1; id "zugzwang.001";
-        1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1 bm Kh6;  id "zugzwang.002";
+        8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1 bm Rf1; id "zugzwang.001"; acn 200000;
+        1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1 bm Kh6;  id "zugzwang.002"; acn 200000;
         7k/5K2/5P1p/3p4/6P1/3p4/8/8 w - - 0 1 bm g5; id "zugzwang.003";
         8/6B1/p5p1/Pp4kp/1P5r/5P1Q/4q1PK/8 w - - 0 32 bm Qxh4; id "zugzwang.004";
         8/8/1p1r1k2/p1pPN1p1/P3KnP1/1P6/8/3R4 b - - 0 1 bm Nxd5; id "zugzwang.005";
+        #
+        ## mutual zugzwang (trebuchet) and opposition
+        #
+        8/8/8/2p1K3/1kP5/8/8/8 w - - 0 1 bm Kd5; id "zugzwang.006"; acn 200000; c0 "trebuchet";
+        8/8/8/1Kp5/2P1k3/8/8/8 b - - 0 1 bm Kd4; id "zugzwang.007"; acn 200000; c0 "trebuchet";
+        3k4/8/3K4/2P5/8/8/8/8 w - - 0 1 bm c6; id "zugzwang.008"; acn 200000; c0 "opposition";
+"#;
+        Epd::parse_many_epd(str.lines()).unwrap()
+    }
+
+    pub fn fortresses() -> Vec<Epd> {
+        // c0 'fortress' or the reason it is not
+        let str = r#"
+        8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/5B2 w - - 0 1 id 'FORT.01'; c0 'fortress'; c1 'bishop cannot attack pawns';
+        4k3/8/8/1p1p1p1p/1P1P1P1P/8/8/4K1B1 b - - 0 1 id 'FORT.02'; c0 'fortress'; c1 'bishop cannot attack pawns';
+        8/4k3/8/2p1p1p1/p1P1P1P1/P3P3/4K3/8 w - - 0 1 id 'FORT.03'; c0 'fortress'; c1 'extra doubled pawn';
+        8/2k5/8/p1p1p1p1/P1P1P1P1/8/3K4/2b5 b - - 0 1 id 'FORT.04'; c0 'fortress'; c1 'black bishop cannot attack pawns';
+        8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/2B5 w - - 0 1 id 'FORT.05'; c0 'bishop attacks pawns';
+        8/8/1k6/p1p1p3/P1P1P1p1/7P/3K4/5B2 w - - 0 1 id 'FORT.06'; c0 'pawn break';
+        8/8/1k6/p1p1p3/P1P1P3/8/3K4/5B2 w - - 0 1 id 'FORT.07'; c0 'entry squares';
+        8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/6N1 w - - 0 1 id 'FORT.08'; c0 'knight';
+        8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/7R w - - 0 1 id 'FORT.09'; c0 'rook';
+        8/8/1k6/p1p1p1p1/P1P1P1P1/7P/3K4/8 w - - 0 1 id 'FORT.10'; c0 'free pawn';
+        8/8/1k6/8/8/8/3K4/5B2 w - - 0 1 id 'FORT.11'; c0 'no pawns';
 "#;
         Epd::parse_many_epd(str.lines()).unwrap()
     }
@@ -257,6 +401,9 @@ r1r5/2N1n1kb/2nB1b1p/3p2p1/6q1/P2pQ3/3N1PPP/1RR3K1 b - - 11 37 acd 6;
         startpos moves; pv Nf3 Nf6 Ng1 Ng8 Nf3 Nf6; rc 1;c0 'search_reps:2';
         startpos moves Nf3 Nf6 Ng1 Ng8 Nf3; pv Nf6; rc 2; c1 'pv counts as played'; c0 'search_reps:1';
         startpos moves Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8 Nf3 Nf6; rc 3;c0 'search_reps:1';
+        startpos moves Nf3 Nf6 Ng1 Ng8; rc 2; c1 'setup position counts'; c0 'search_reps:1';
+        startpos moves Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1 Ng8; rc 3; c0 'search_reps:1';
+        startpos moves Nf3 Nf6 Ng1 Ng8; Rcs 2; rc 3; c1 'prior to setup'; c0 'search_reps:1';
         "#;
         Epd::parse_many_epd(str.lines()).unwrap()
     }
@@ -285,7 +432,7 @@ r1r5/2N1n1kb/2nB1b1p/3p2p1/6q1/P2pQ3/3N1PPP/1RR3K1 b - - 11 37 acd 6;
         2k5/ppp2ppp/8/4P3/P7/3p4/PP5P/K7 b - - 0 1 id 'PAWN.04'; c0 'passed_r6';   c1 '0'; c2 '1';
         2k5/ppp2ppp/8/4P3/P7/3p4/PP5P/K7 b - - 0 1 id 'PAWN.04'; c0 'doubled';  c1 '1'; c2 '0';
         2k5/ppp2p1p/5p2/5p1P/3P3P/3P3P/P2P3P/K7 w - - 0 1 id 'PAWN.05'; c0 'isolated'; c1 '8'; c2 '4'; 
-        2k5/ppp2p1p/5p2/5p1P/3P3P/3P3P/P2P3P/K7 w - - 0 1 id 'PAWN.05'; c0 'passed';   c1 '0'; c2 '2'; 
+        2k5/ppp2p1p/5p2/5p1P/3P3P/3P3P/P2P3P/K7 w - - 0 1 id 'PAWN.05'; c0 'passed';   c1 '0'; c2 '3'; c3 'tripled passers all count';
         2k5/ppp2p1p/5p2/5p1P/3P3P/3P3P/P2P3P/K7 w - - 0 1 id 'PAWN.05'; c0 'passed_r4';   c1 '0'; c2 '1'; 
         2k5/ppp2p1p/5p2/5p1P/3P3P/3P3P/P2P3P/K7 w - - 0 1 id 'PAWN.05'; c0 'doubled';  c1 '0'; c2 '0'; 
         k7/p2p3p/3p3p/3p3p/5P1p/5P2/PPP2P1P/2K5 b - - 0 1 id 'PAWN.06'; c0 'isolated'; c1 '4'; c2 '8';
@@ -298,6 +445,11 @@ r1r5/2N1n1kb/2nB1b1p/3p2p1/6q1/P2pQ3/3N1PPP/1RR3K1 b - - 11 37 acd 6;
         k7/3p4/PPp3P1/1p3P2/4P3/8/6pp/K7 w - - 0 1 id 'PAWN.08'; c0 'connected_r345';  c1 '1'; c2 '2';
         k7/3p4/PPp3P1/1p3P2/4P3/8/6pp/K7 w - - 0 1 id 'PAWN.08'; c0 'backward_half_open';  c1 '0'; c2 '0';
         k7/8/8/5p2/6p1/6Pp/7P/K7 w - - 0 1 id 'PAWN.09'; c0 'backward_half_open';  c1 '0'; c2 '1';
+        k7/8/8/5p2/6p1/6Pp/7P/K7 w - - 0 1 id 'PAWN.09'; c0 'backward';  c1 '1'; c2 '0'; c3 'h2 blocked by h3';
+        k7/8/8/5p2/6p1/6Pp/7P/K7 w - - 0 1 id 'PAWN.09'; c0 'lever';     c1 '0'; c2 '0';
+        k7/8/8/2p1p3/3P1p2/4PP2/8/K7 w - - 0 1 id 'PAWN.10'; c0 'lever';     c1 '2'; c2 '3';
+        k7/8/8/2p1p3/3P1p2/4PP2/8/K7 w - - 0 1 id 'PAWN.10'; c0 'backward';  c1 '0'; c2 '1'; c3 'isolated c5 not backward';
+        k7/8/8/2p1p3/3P1p2/4PP2/8/K7 w - - 0 1 id 'PAWN.10'; c0 'backward_half_open';  c1 '0'; c2 '0';
 "#;
         Epd::parse_many_epd(str.lines()).unwrap()
     }
@@ -370,6 +522,8 @@ K7/7r/8/8/8/8/8/rr5k w - - 0 1
             k7/8/K1p5/8/3N4/8/6N1/7B w - - 5 1; id 'RECOG.03'; am Nxc6; bm Kb6; c0 'white shouldnt take pawn with knight as it triggers stalemate';
             k1K5/8/8/2p5/8/6B1/5B2/8 w - - 0 1;  id 'RECOG.04'; bm Bxc5; c0 'white should force stalemate by capturing pawn on c5 as black could win';
             6k1/7n/8/8/8/8/1N6/B5KR w - - 26 1; id 'RECOG.05'; bm Rxh7; acd 7; c0 'winning position despite king recapture';
+            7k/5K2/6P1/8/1p6/3N4/8/8 w - - 0 1; id 'RECOG.06'; am Nxb4; c0 'white shouldnt take the last pawn as it stalemates';
+            8/8/3n4/1P6/8/6p1/5k2/7K b - - 0 1; id 'RECOG.07'; am Nxb5; c0 'black shouldnt take the last pawn as it stalemates';
             "#;
         Epd::parse_many_epd(str.lines()).unwrap()
     }
@@ -460,6 +614,7 @@ h4f2 h4f4 h4f6 h4g3 h4g4 h4g5 h4h1 h4h2 h4h3 h4h5 h4h6 h4h7 h8b8 h8c8 h8d8 h8e8
 bb3rkr/pp2nppp/4pn2/2qp4/2P5/3RNN2/PP2PPPP/BBQ3KR w - - 0 8 sm c4d5; ce 0; id 'SEE.07'; c0 'shortcoming in SEE, as PxP exposes queen to capture';
 k7/5n2/3p4/4p3/4K1N1/8/8/8 w - - 0 8 sm g4e5; ce -225; id 'SEE.08'; c0 'loses knight for pawn, as king cannot recapture because of check';
 k7/5n2/8/4p3/4K1N1/8/8/8 w - - 0 8  sm g4e5; ce 100; id 'SEE.09'; c0 'king can capture as wont be in check';
+4k3/8/8/3pP3/8/8/K7/3r4 w - d6 0 1 sm e5d6; ce 0; id 'SEE.10'; c0 'xray through the pawn captured en passant';
 "#;
         let positions = Epd::parse_many_epd(str.lines()).unwrap();
         positions
@@ -635,6 +790,11 @@ r3k2r/ppp2Npp/1b5n/4p2b/2B1P2q/BQP2P2/P5PP/RN5K w kq - 1 1 dm 3; pv 1. Bb5+ c6 2
         vec
     }
 
+    /// the built-in perft suite, with expected counts to depth 6 (where known)
+    pub fn perft_suite() -> Vec<PerftPosition> {
+        PerftPosition::parse_many(read_resource_file("perftsuite.epd")).unwrap()
+    }
+
     pub fn bench() -> Vec<Epd> {
         Self::bratko_kopec()
     }
@@ -699,6 +859,7 @@ r3k2r/ppp2Npp/1b5n/4p2b/2B1P2q/BQP2P2/P5PP/RN5K w kq - 1 1 dm 3; pv 1. Bb5+ c6 2
         bb.set(Bitboard::RANK_8, &chars.iter().collect::<String>().to_lowercase())
             .unwrap();
         bb.set_castling(CastlingRights::ALL);
+        bb.set_castling_files(CastlingFiles::new(r2 as u8, r1 as u8));
         let mut pos = Epd::from_board(bb.build());
         pos.set_tag("id", &format!("Chess960(SP{id})"));
         pos
@@ -711,6 +872,45 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_catalog_named() {
+        assert_eq!(Catalog::named("startpos").unwrap().board(), Catalog::starting_board());
+        assert_eq!(Catalog::named("KiwiPete").unwrap().board(), Catalog::perft_kiwipete().0);
+        let wac = Catalog::named("wac.001").unwrap();
+        assert_eq!(wac.tag(Tags::ID), Some("WAC.001"));
+        assert_eq!(wac.board(), Catalog::win_at_chess()[0].board());
+        assert!(Catalog::named("no such position").is_err());
+
+        let names = Catalog::names("WAC.00");
+        assert_eq!(names.len(), 9);
+        assert!(names.iter().all(|n| n.starts_with("WAC.00")));
+        assert!(Catalog::names("kiwi").contains(&"kiwipete".to_string()));
+    }
+
+    #[test]
+    fn test_catalog_load() {
+        let dir = std::env::temp_dir().join("odonata_test_catalog_load");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.epd"), "8/8/8/8/8/8/8/K6k w - - 0 1 id 'A.1'; acd 5;\n").unwrap();
+        std::fs::write(dir.join("b.epd"), "8/8/8/8/8/8/8/K6k w - - 0 1 id 'B.1'; acd 15;\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not an epd").unwrap();
+
+        let epds = Catalog::load(dir.to_str().unwrap()).unwrap();
+        assert_eq!(epds.len(), 2);
+        assert_eq!(epds[0].tag("Src"), Some("a"));
+        assert_eq!(epds[1].tag("Src"), Some("b"));
+
+        let bk = Catalog::load("bratkokopec").unwrap();
+        assert_eq!(bk.len(), Catalog::bratko_kopec().len());
+        assert_eq!(bk[0].tag("Src"), Some("BratkoKopec"));
+
+        let filter = EpdFilter::parse("acd>=10 || Src~Bratko").unwrap();
+        let merged = Catalog::load_many(&[dir.to_str().unwrap(), "BratkoKopec"], Some(&filter)).unwrap();
+        assert_eq!(merged.len(), bk.len() + 1);
+        assert!(Catalog::load("no_such_suite_or_file").is_err());
+        assert!(Catalog::load("Perft").is_err());
+    }
+
     #[test]
     fn test_serde() {
         assert_eq!(
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 use super::wdl::ScoreWdl;
 use crate::infra::utils::{KeywordIter, Uci};
 use crate::prelude::*;
@@ -65,7 +67,7 @@ use crate::prelude::*;
 //     }
 // }
 
-#[derive(Clone, Default, Debug, PartialEq, Eq)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InfoKind {
     #[default]
     Pv,
@@ -74,7 +76,7 @@ pub enum InfoKind {
     NodeCounts,
 }
 
-#[derive(Clone, Default, Debug, PartialEq)]
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Info {
     pub kind:                  InfoKind,
     pub depth:                 Option<Ply>,
@@ -156,16 +158,21 @@ impl fmt::Display for Info {
 
 impl Info {
     pub fn to_uci(&self) -> String {
-        Displayable(|fmt| self.fmt_uci(fmt)).to_string()
+        self.to_uci_with(false)
+    }
+
+    /// with castles written king-takes-rook when `chess960`
+    pub fn to_uci_with(&self, chess960: bool) -> String {
+        Displayable(|fmt| self.fmt_uci(fmt, chess960)).to_string()
     }
 
-    fn fmt_uci(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn fmt_uci(&self, f: &mut fmt::Formatter, chess960: bool) -> fmt::Result {
         let mut v = vec![];
         #[rustfmt::skip] {
         self.depth.iter().for_each(|x| v.push(format!("depth {x}")));
         self.seldepth.iter().for_each(|x| v.push(format!("seldepth {x}")));
         self.multi_pv.iter().for_each(|x| v.push(format!("multipv {}", x)));
-        self.currmove.iter().for_each(|x| v.push(format!("currmove {}", x.to_uci())));
+        self.currmove.iter().for_each(|x| v.push(format!("currmove {}", x.to_uci_with(chess960))));
         self.currmovenumber_from_1.iter().for_each(|x| v.push(format!("currmovenumber {x}")));
         self.score.iter().for_each(|x| v.push(format!("score {}", x.to_uci())));
         self.wdl.iter().for_each(|x| v.push(format!("wdl {} {} {}", x.w, x.d, x.l)));
@@ -175,8 +182,8 @@ impl Info {
         self.tbhits.iter().for_each(|x| v.push(format!("tbhits {x}")));
         self.cpuload_per_mille.iter().for_each(|x| v.push(format!("cpuload {x}")));
         self.time_millis.iter().for_each(|x| v.push(format!("time {x}")));
-        self.pv.iter().for_each(|x| v.push(format!("pv {}", x.to_uci())));
-        self.refutation.iter().for_each(|x| v.push(format!("refutation {} {}", x.0.to_uci(), x.1.to_uci())));
+        self.pv.iter().for_each(|x| v.push(format!("pv {}", x.to_uci_with(chess960))));
+        self.refutation.iter().for_each(|x| v.push(format!("refutation {} {}", x.0.to_uci_with(chess960), x.1.to_uci_with(chess960))));
         self.string_text.iter().for_each(|x| v.push(format!("string {x}")));
         };
         write!(f, "{}", v.join(" "))?;
@@ -266,6 +273,8 @@ mod tests {
         assert_eq!(info.time_millis, Some(22));
         assert_eq!(info.multi_pv, Some(2));
         assert_eq!(info.string_text, Some("Hello World".into()));
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<Info>(&json).unwrap(), info);
         assert_eq!(info.pv.unwrap().to_uci(), "e2e4 c7c5 g1f3 d7d6");
         assert_eq!(info.cpuload_per_mille, None);
 
@@ -1,30 +1,46 @@
+use std::str::FromStr;
 use std::{cmp, fmt, ops};
 
 use anyhow::Result;
 use itertools::Itertools;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 
 use crate::piece::{Color, Piece};
 use crate::prelude::Board;
 
 type MaterialCount = u16;
 
-#[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
+/// serialized as its piece string, eg "KQkr"
+#[derive(Copy, Clone, Default, Debug, Eq, PartialEq, SerializeDisplay, DeserializeFromStr)]
 pub struct Material {
     counts: [[MaterialCount; Piece::len()]; Color::len()],
 }
 
+/// canonical piece string "KQRRkqr", or with the alternate format {:#} the
+/// tablebase (and EPD) style "KQRRvKQR"
 impl fmt::Display for Material {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for &c in &Color::ALL {
-            // write!(f, "{}: ", c)?;
+            if f.alternate() && c == Color::Black {
+                f.write_str("v")?;
+            }
+            let case = if f.alternate() { Color::White } else { c };
             for &p in Piece::ALL.iter().rev() {
-                write!(f, "{}", p.to_char(c).to_string().repeat(self.count(c, p) as usize))?;
+                write!(f, "{}", p.to_char(case).to_string().repeat(self.count(c, p) as usize))?;
             }
         }
         Ok(())
     }
 }
 
+impl FromStr for Material {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_piece_str(s)
+    }
+}
+
 impl ops::Neg for Material {
     type Output = Material;
 
@@ -48,10 +64,33 @@ impl ops::Neg for &Material {
     }
 }
 
-impl<'a, 'b> ops::Sub<&'b Material> for &'a Material {
+impl ops::Add for Material {
     type Output = Material;
 
-    fn sub(self, other: &'b Material) -> Self::Output {
+    fn add(self, other: Material) -> Self::Output {
+        let mut m = Material::new();
+
+        for &c in &Color::ALL {
+            for &p in &Piece::ALL {
+                m.set_count(c, p, self.count(c, p) + other.count(c, p));
+            }
+        }
+        m
+    }
+}
+
+impl ops::Add<&Material> for &Material {
+    type Output = Material;
+
+    fn add(self, other: &Material) -> Self::Output {
+        *self + *other
+    }
+}
+
+impl ops::Sub for Material {
+    type Output = Material;
+
+    fn sub(self, other: Material) -> Self::Output {
         let mut m = Material::new();
 
         for &c in &Color::ALL {
@@ -63,6 +102,14 @@ impl<'a, 'b> ops::Sub<&'b Material> for &'a Material {
     }
 }
 
+impl ops::Sub<&Material> for &Material {
+    type Output = Material;
+
+    fn sub(self, other: &Material) -> Self::Output {
+        *self - *other
+    }
+}
+
 impl cmp::PartialOrd for Material {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
         if self == other {
@@ -130,12 +177,17 @@ impl Material {
         }
     }
 
-    /// Material.from_piece_str("PPPBNRQKppbbqk")
+    /// Material.from_piece_str("PPPBNRQKppbbqk"), also accepting the tablebase style "KQRvKR"
+    /// (white pieces 'v' black pieces, in either case) and fen piece placement "4k3/8/8/3QK3"
     #[inline]
     pub fn from_piece_str(s: &str) -> Result<Material> {
+        let s = s.split_whitespace().next().unwrap_or_default();
+        if let Some((w, b)) = s.split_once('v') {
+            return Ok(Self::from_piece_str(&w.to_uppercase())? + Self::from_piece_str(&b.to_lowercase())?);
+        }
         let mut m: Material = Material::new();
 
-        for ch in s.chars() {
+        for ch in s.chars().filter(|ch| !ch.is_ascii_digit() && *ch != '/') {
             let p = Piece::from_char(ch)?;
             let c = Color::from_piece_char(ch)?;
             m.set_count(c, p, m.count(c, p) + 1);
@@ -143,6 +195,14 @@ impl Material {
         Ok(m)
     }
 
+    /// few enough men (kings included) to be in a `max_men` tablebase, so worth probing
+    #[inline]
+    pub fn is_tb_position(&self, max_men: i32) -> bool {
+        self.total_count() <= max_men
+            && self.count(Color::White, Piece::King) == 1
+            && self.count(Color::Black, Piece::King) == 1
+    }
+
     #[inline]
     pub fn total_count(&self) -> i32 {
         Piece::ALL.iter().map(|&p| self.count_piece(p)).sum::<i32>()
@@ -402,6 +462,24 @@ mod tests {
         let mat_KBk = Material::from_piece_str("KBk").unwrap();
         assert_ne!(mat_full2, mat_KBk);
         assert_eq!(mat_KBk.flip().to_string(), "Kkb");
+        assert_eq!(mat_full1.to_string().parse::<Material>().unwrap(), mat_full1);
+        assert_eq!(serde_json::to_string(&mat_KBk).unwrap(), r#""KBk""#);
+        assert_eq!(serde_json::from_str::<Material>(r#""KBk""#).unwrap(), mat_KBk);
+        assert!("KXk".parse::<Material>().is_err());
+
+        // tablebase and fen forms
+        let krr_kq = Material::from_piece_str("KRRkq").unwrap();
+        assert_eq!(format!("{krr_kq:#}"), "KRRvKQ");
+        assert_eq!(Material::from_piece_str("KRRvKQ").unwrap(), krr_kq);
+        assert_eq!(Material::from_piece_str("krrvkq").unwrap(), krr_kq);
+        assert_eq!(Material::from_piece_str("3qk3/8/8/8/8/8/8/R3K2R").unwrap(), krr_kq);
+        assert_eq!(Material::from_piece_str(&board.to_fen()).unwrap(), mat_full1);
+        assert_eq!(format!("{:#}", Material::from_piece_str("Kk").unwrap()), "KvK");
+        assert_eq!(krr_kq - krr_kq.black() + krr_kq.black(), krr_kq);
+        assert!(krr_kq.is_tb_position(5));
+        assert!(!krr_kq.is_tb_position(4));
+        assert!(!Material::from_piece_str("KQQ").unwrap().is_tb_position(5));
+        assert!(!mat_full1.is_tb_position(7));
 
         assert!(mat_KBk.is_insufficient());
         assert!(!mat_full2.is_insufficient());
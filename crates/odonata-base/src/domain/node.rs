@@ -1,5 +1,6 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
 use strum::{EnumCount, IntoEnumIterator};
 use strum_macros::{AsRefStr, Display, EnumCount, EnumIter, EnumMessage};
 
@@ -56,6 +57,8 @@ impl Timing {
 pub enum Counter {
     SearchTimeUp,
     SearchComplete,
+    FastReturnSingleMove,
+    FastReturnRecapture,
 
     MoveBestPiecePawn,
     MoveBestPieceBishop,
@@ -92,7 +95,21 @@ impl Counter {
     }
 }
 
-#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Display, EnumCount, EnumMessage, EnumIter, AsRefStr)]
+#[derive(
+    Copy,
+    Clone,
+    Default,
+    Debug,
+    PartialEq,
+    Eq,
+    Display,
+    EnumCount,
+    EnumMessage,
+    EnumIter,
+    AsRefStr,
+    Serialize,
+    Deserialize,
+)]
 #[strum(serialize_all = "title_case")]
 pub enum Event {
     #[default]
@@ -121,6 +138,7 @@ pub enum Event {
     NodeLeafDraw,
     NodeLeafWinLoss,
     NodeLeafStalemate,
+    NodeLeafTablebase,
     NodeInterior,
     NodeInteriorAll,
     NodeInteriorCut,
@@ -139,6 +157,8 @@ pub enum Event {
     RecogImmediateDraw,
     RecogMaybeWin,
     RecogHelpmateOrDraw,
+    PawnSolverProven,
+    PawnSolverUnresolved,
 
     #[strum(message = "Moves")]
     MoveCount,
@@ -161,6 +181,9 @@ pub enum Event {
     // MoveGoodCaptureUpfrontSorted,
     MoveKiller,
     MoveSortCounterMove,
+    CaptureCutFirst,
+    CaptureCutLater,
+    CaptureCutFirstPerc,
     MovePromo,
     MoveQuiet,
     MoveOther,
@@ -186,6 +209,9 @@ pub enum Event {
     RazorDeclineMinOpponents,
     RazorDeclineMinPieces,
     RazorDeclineMargin,
+    RazorGuardPawnEnding,
+    RazorGuardKingPawns,
+    RazorGuardFewPieces,
     RazorSuccess,
     RazorD1Success,
     RazorD2Success,
@@ -205,6 +231,9 @@ pub enum Event {
     NmpDeclineInCheck,
     NmpDeclineSuccessive,
     NmpDeclineRecursive,
+    NmpGuardPawnEnding,
+    NmpGuardKingPawns,
+    NmpGuardFewPieces,
     NmpAttempt,
     NmpSuccess,
     NmpFail,
@@ -243,6 +272,9 @@ pub enum Event {
     FutilityDeclineMaxDepth,
     FutilityDeclineFwWindow,
     FutilityDeclineMinPieces,
+    FutilityGuardPawnEnding,
+    FutilityGuardKingPawns,
+    FutilityGuardFewPieces,
     FutilityFail,
     FutilitySuccess,
     FutilitySuccessRemaining,
@@ -352,6 +384,7 @@ pub enum Event {
     QsMovePruneDelta,
     QsCatAllDeltaPruneNode,
     QsCatInsufficientMaterial,
+    QsCatStalemate,
 }
 
 impl Event {
@@ -50,11 +50,19 @@ impl fmt::Display for Score {
     }
 }
 
+/// parses the display format ("23cp", "W(3)", "-inf") as well as pgn pawn values
 impl FromStr for Score {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::parse_pgn_pawn_value(s)
+        let ply = |n: &str| n.strip_suffix(')').unwrap_or_default().parse::<Ply>();
+        match s {
+            "+inf" => Ok(Score::INFINITY),
+            "-inf" => Ok(-Score::INFINITY),
+            _ if s.starts_with("W(") => Ok(Score::we_win_in(ply(&s[2..])?)),
+            _ if s.starts_with("L(") => Ok(Score::we_lose_in(ply(&s[2..])?)),
+            _ => Self::parse_pgn_pawn_value(s),
+        }
     }
 }
 
@@ -287,6 +295,33 @@ impl Score {
     //     *self
     // }
 
+    /// Tablebase wins (and losses) are a band of centipawn scores, above any eval but below
+    /// any mate, less the ply from the root at which the win was proven. They are numeric
+    /// (reported to uci as centipawns, never as a mate) but are ply-relative as mates are.
+    pub const TB_WIN_CP: i32 = 20_000;
+
+    #[inline]
+    pub fn tb_win_in(ply: Ply) -> Score {
+        Score::from_cp(Self::TB_WIN_CP - ply)
+    }
+
+    #[inline]
+    pub fn tb_lose_in(ply: Ply) -> Score {
+        Score::from_cp(-Self::TB_WIN_CP + ply)
+    }
+
+    #[inline]
+    pub const fn is_tb(&self) -> bool {
+        let cp = self.cp.unsigned_abs() as i32;
+        self.is_numeric() && cp <= Self::TB_WIN_CP && cp > Self::TB_WIN_CP - MAX_PLY
+    }
+
+    /// the ply from the root at which a tablebase win or loss was proven
+    #[inline]
+    pub fn tb_ply(&self) -> Option<Ply> {
+        self.is_tb().then(|| Self::TB_WIN_CP - self.cp.unsigned_abs() as i32)
+    }
+
     #[inline]
     pub const fn is_mate(&self) -> bool {
         !(self.cp == -Self::INF || self.cp == Self::INF || self.is_numeric())
@@ -504,6 +539,20 @@ mod tests {
         assert_eq!(Score::from_mate_in_moves(-2).mate_in(), Some(-2));
         assert_eq!(Score::from_mate_in_moves(-3).mate_in(), Some(-3));
     }
+
+    #[test]
+    fn test_tb_score() {
+        let win = Score::tb_win_in(5);
+        assert!(win.is_tb() && win.is_numeric() && !win.is_mate());
+        assert_eq!(win.tb_ply(), Some(5));
+        assert_eq!(Score::tb_lose_in(5), -win);
+        assert_eq!((-win).tb_ply(), Some(5));
+        assert!(Score::tb_win_in(3) > win);
+        assert!(win > Score::from_cp(5000) && win < Score::we_win_in(100));
+        assert!(!Score::from_cp(5000).is_tb());
+        assert_eq!(win.to_uci(), "cp 19995");
+        assert_eq!(Score::parse_uci("cp 19995").unwrap(), win);
+    }
     #[test]
     fn test_uci_score() -> anyhow::Result<()> {
         assert_eq!(Score::from_cp(100).to_uci(), "cp 100");
@@ -525,6 +574,18 @@ mod tests {
         assert_eq!(serde_json::to_string(&s).unwrap(), "123");
         assert_eq!(s, serde_json::from_str("123").unwrap());
         // assert_eq!(s, serde_json::from_str("123").unwrap());
+
+        for s in [
+            Score::from_cp(-45),
+            Score::INFINITY,
+            -Score::INFINITY,
+            Score::we_win_in(3),
+            Score::we_lose_in(4),
+        ] {
+            assert_eq!(s.to_string().parse::<Score>()?, s, "{s}");
+            assert_eq!(serde_json::from_str::<Score>(&serde_json::to_string(&s)?)?, s);
+        }
+        assert!("W(x)".parse::<Score>().is_err());
         Ok(())
     }
 
@@ -69,6 +69,7 @@ pub trait StaticEval: Debug {
     fn static_eval_explain(&self, pos: &Position) -> EvalExplain;
     fn move_material_eval(&self, mv: Move, b: &Board) -> Score;
     fn eval_move_see(&self, mv: Move, b: &Board) -> Score;
+    fn eval_move_swap(&self, mv: Move, b: &Board) -> Score;
     fn piece_material_eval(&self, piece: Piece, b: &Board) -> f64;
     fn eval_draw(&self, b: &Board, ply: Ply) -> Score;
 }
@@ -407,6 +407,10 @@ impl TimeControl {
             let n = tc.parse::<u64>()?;
             return Ok(TimeControl::Instructions(n));
         }
+        // as displayed, a pgn time control has no "tc=" prefix
+        if tc.starts_with(|c: char| c.is_ascii_digit()) {
+            return Self::parse_pgn(&tc);
+        }
         anyhow::bail!("Unable to parse time control '{}'", tc);
     }
 
@@ -488,6 +492,19 @@ mod tests {
 
         assert_eq!(T::parse_uci("nodes 10 depth 5")?, T::DepthNodeCount(5, 10));
 
+        for s in [
+            "depth=5",
+            "nodes=1000",
+            "depth/nodes=5/10000",
+            "st=11ms",
+            "mate=3",
+            "tc=5/60",
+            "inf",
+        ] {
+            let tc = T::parse_cli_option(s)?;
+            assert_eq!(tc.to_string().parse::<T>()?, tc, "{s}");
+            assert_eq!(serde_json::from_str::<T>(&serde_json::to_string(&tc)?)?, tc, "{s}");
+        }
         Ok(())
     }
 }
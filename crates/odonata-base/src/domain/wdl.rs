@@ -2,7 +2,9 @@ use std::fmt::{self, Display};
 use std::str::FromStr;
 
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
+use crate::domain::score::Score;
 use crate::other::outcome::Outcome;
 use crate::prelude::Math;
 use crate::Color;
@@ -79,7 +81,7 @@ impl FromStr for WdlOutcome {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Default, PartialOrd, Ord, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ScoreWdl {
     pub w: i32,
     pub d: i32,
@@ -92,11 +94,13 @@ impl fmt::Display for ScoreWdl {
     }
 }
 
+/// parses "w d l" (as uci) or the display format "W: w  D: d  L: l"
 impl FromStr for ScoreWdl {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some((w, d, l)) = s.split_whitespace().collect_tuple() else {
+        let words = s.split_whitespace().filter(|w| !matches!(*w, "W:" | "D:" | "L:"));
+        let Some((w, d, l)) = words.collect_tuple() else {
             return Err(anyhow::anyhow!("unable to parse {s} into a WDL score"));
         };
         Ok(ScoreWdl::new(w.parse()?, d.parse()?, l.parse()?))
@@ -113,6 +117,27 @@ impl ScoreWdl {
         ScoreWdl { w, d, l }
     }
 
+    /// win, draw and loss per mille (from the side to move's point of view) expected of a
+    /// score, as shown by GUIs when UCI_ShowWDL is set. Each of win and loss is logistic in the
+    /// centipawns, reaching 50% at +/- `Self::WDL_MODEL_A` and the draws make up the rest.
+    /// Mates (and tablebase results) are certain.
+    pub fn from_score(score: Score) -> Self {
+        let Some(cp) = score.cp() else {
+            return if score > Score::zero() {
+                ScoreWdl::new(1000, 0, 0)
+            } else {
+                ScoreWdl::new(0, 0, 1000)
+            };
+        };
+        let per_mille =
+            |cp: f64| (1000.0 / (1.0 + ((Self::WDL_MODEL_A - cp) / Self::WDL_MODEL_B).exp())).round() as i32;
+        let (w, l) = (per_mille(cp as f64), per_mille(-cp as f64));
+        ScoreWdl::new(w, 1000 - w - l, l)
+    }
+
+    const WDL_MODEL_A: f64 = 120.0;
+    const WDL_MODEL_B: f64 = 60.0;
+
     pub fn from_outcome(outcome: &Outcome) -> Self {
         if outcome.is_draw() {
             return ScoreWdl::new(0, 1, 0);
@@ -248,12 +273,14 @@ impl std::iter::Sum for ScoreWdl {
 mod tests {
     use test_log::test;
 
+    use crate::domain::score::Score;
     use crate::domain::wdl::ScoreWdl;
 
     #[test]
     fn test_score_wdl() {
         let mut wdl138 = ScoreWdl::new(1, 3, 8);
         assert_eq!(wdl138, "1 3 8".parse().unwrap());
+        assert_eq!(wdl138, wdl138.to_string().parse().unwrap());
         let wdl567 = ScoreWdl::new(5, 6, 7);
         assert_eq!(wdl138 + wdl567, ScoreWdl::new(6, 9, 15));
         assert_eq!(wdl138 - wdl567, ScoreWdl::new(8, 9, 13));
@@ -265,4 +292,23 @@ mod tests {
         // checked by https://www.3dkingdoms.com/chess/elo.htm
         assert_eq!(format!("{:.02}", ScoreWdl::new(217, 77, 184).elo()), "24.02");
     }
+
+    #[test]
+    fn test_wdl_from_score() {
+        assert_eq!(ScoreWdl::from_score(Score::from_cp(0)), ScoreWdl::new(119, 762, 119));
+        assert_eq!(ScoreWdl::from_score(Score::from_cp(120)), ScoreWdl::new(500, 482, 18));
+        assert_eq!(ScoreWdl::from_score(Score::from_cp(-120)), ScoreWdl::new(18, 482, 500));
+        assert_eq!(
+            ScoreWdl::from_score(Score::from_mate_in_moves(3)),
+            ScoreWdl::new(1000, 0, 0)
+        );
+        assert_eq!(
+            ScoreWdl::from_score(Score::from_mate_in_moves(-3)),
+            ScoreWdl::new(0, 0, 1000)
+        );
+        for cp in (-2000..2000).step_by(50) {
+            let wdl = ScoreWdl::from_score(Score::from_cp(cp));
+            assert!(wdl.n() == 1000 && wdl.d >= 0, "{cp} {wdl}");
+        }
+    }
 }
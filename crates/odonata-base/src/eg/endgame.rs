@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumCount, IntoStaticStr};
 
+use crate::eg::fortress::Fortress;
 use crate::prelude::*;
 use crate::trace::stat::{SliceStat, Stat};
 use crate::PreCalc;
@@ -29,6 +30,7 @@ pub struct EndGameScoring {
     certain_win_bonus: Score,
     likely_draw_scale: f32,
     scale_by_hmvc:     bool,
+    fortress_scale:    f32,
 }
 
 impl Default for EndGameScoring {
@@ -39,6 +41,7 @@ impl Default for EndGameScoring {
             certain_win_bonus: 1000.cp(),
             likely_draw_scale: 1.0,
             scale_by_hmvc:     true,
+            fortress_scale:    0.25,
         }
     }
 }
@@ -50,6 +53,7 @@ impl Configurable for EndGameScoring {
         self.certain_win_bonus.set(p.get("certain_win_bonus"))?;
         self.likely_draw_scale.set(p.get("likely_draw_scale"))?;
         self.scale_by_hmvc.set(p.get("scale_by_hmvc"))?;
+        self.fortress_scale.set(p.get("fortress_scale"))?;
         Ok(p.is_modified())
     }
 }
@@ -308,6 +312,10 @@ impl EndGame {
             _ => {}
         };
 
+        // engines can overpress a blocked position for hundreds of moves
+        if es.fortress_scale < 1.0 && Fortress::is_blockade(b) {
+            return Score::from_f32(es.fortress_scale * pov.as_i16() as f32);
+        }
         pov
     }
 
@@ -691,6 +699,10 @@ mod tests {
         let es = EndGameScoring::default();
         let _sc = eg.endgame_score_adjust(&b, Score::zero(), &es);
         // assert_eq!(sc, Score::from_cp(0 - 50));
+
+        let b = Board::parse_fen("8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/5B2 w - - 0 1").unwrap();
+        let eg = EndGame::from_board(&b);
+        assert_eq!(eg.endgame_score_adjust(&b, 200.cp(), &es), 50.cp());
     }
 
     #[test]
@@ -0,0 +1,87 @@
+use crate::bits::bitboard::Dir;
+use crate::prelude::*;
+use crate::PreCalc;
+
+/// Heuristic (and deliberately conservative) detection of blockade fortresses.
+///
+/// A position is a fortress when
+/// (a) every pawn is blocked by a pawn directly in front of it, and no pawn can capture
+///     (so the pawn structure can never change - no pawn breaks)
+/// (b) neither king can find a path through the pawn chain to an enemy pawn that is not
+///     pawn-defended (no entry squares)
+/// (c) any remaining pieces are bishops which can never attack an enemy pawn
+///
+/// Knights, rooks and queens (which can sacrifice or infiltrate) mean no fortress.
+pub struct Fortress;
+
+impl Fortress {
+    pub fn is_blockade(b: &Board) -> bool {
+        let pawns = b.pawns();
+        if pawns.is_empty() {
+            return false;
+        }
+        if (b.knights() | b.rooks_or_queens()).any() {
+            return false;
+        }
+        Color::ALL.iter().all(|&c| Self::is_blocked_for(b, c))
+    }
+
+    fn is_blocked_for(b: &Board, c: Color) -> bool {
+        let bb = PreCalc::instance();
+        let our_pawns = b.pawns() & b.color(c);
+        let their_pawns = b.pawns() & b.color(c.flip_side());
+
+        // (a) all our pawns blocked, and no levers (so no captures) or pawn breaks
+        if (our_pawns.shift(c.forward()) - b.pawns()).any() {
+            return false;
+        }
+        if (b.pawn_levers(c) | b.pawn_breaks(c)).any() {
+            return false;
+        }
+
+        // (c) our bishops must be unable to ever attack their pawns
+        let our_bishops = b.bishops() & b.color(c);
+        if our_bishops.any() && our_bishops.color_flood().intersects(their_pawns) {
+            return false;
+        }
+
+        // (b) flood fill our king's reachable squares, avoiding pawns and squares they attack
+        let their_pawn_attacks = bb.pawn_attacks_from(c.flip_side(), their_pawns);
+        let passable = !(b.pawns() | their_pawn_attacks);
+        let mut reach = b.king(c).as_bb();
+        loop {
+            let around = Dir::ALL
+                .iter()
+                .fold(Bitboard::empty(), |acc, &dir| acc | reach.shift(dir));
+            let next = reach | (around & passable);
+            if next == reach {
+                break;
+            }
+            reach = next;
+        }
+        let undefended = their_pawns - their_pawn_attacks;
+        !undefended.squares().any(|sq| bb.king_attacks(sq).intersects(reach))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+    use crate::catalog::Catalog;
+
+    #[test]
+    fn test_fortress() {
+        for epd in Catalog::fortresses() {
+            let expected = epd.tag("c0") == Some("fortress");
+            assert_eq!(
+                Fortress::is_blockade(&epd.board()),
+                expected,
+                "{id}\n{b:L>}",
+                id = epd.tag("id").unwrap_or_default(),
+                b = epd.board()
+            );
+        }
+    }
+}
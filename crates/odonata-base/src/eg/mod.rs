@@ -1,5 +1,7 @@
 pub mod endgame;
+pub mod fortress;
 pub mod zugzwang;
 
 pub use crate::eg::endgame::{EndGame, LikelyOutcome};
+pub use crate::eg::fortress::Fortress;
 pub use crate::eg::zugzwang::Zugzwang;
@@ -1,4 +1,6 @@
 use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
 use std::ops::Index;
 use std::path::Path;
 
@@ -9,7 +11,6 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::bits::bitboard::Bitboard;
-use crate::bits::castling::CastlingRights;
 use crate::bits::Square;
 use crate::boards::board::BoardBuilder;
 use crate::boards::Position;
@@ -94,8 +95,10 @@ impl Epd {
         self.played.clone()
     }
 
+    /// with any prior occurrences of the setup position (tag `Rcs`) carried to the position
     pub fn into_pos(self) -> Position {
-        Position::from_played_moves(self.starting, self.played)
+        let prior_reps = self.int(Tags::RCS).map_or(0, |rcs| rcs.max(1) - 1) as u16;
+        Position::from_played_moves(self.starting, self.played).with_setup_reps(prior_reps)
     }
 
     pub fn validate(&self) -> anyhow::Result<()> {
@@ -241,7 +244,7 @@ impl Epd {
             }
             let mut bb = BoardBuilder::parse_piece_placement(words[0])?;
             bb.set_turn(Color::parse(words[1])?);
-            bb.set_castling(CastlingRights::parse(words[2])?);
+            bb.set_castling_fen(words[2])?;
             if words[3] == "-" {
                 bb.set_ep_square(None);
             } else {
@@ -312,6 +315,363 @@ impl Epd {
     }
 }
 
+/// An operand of an [`EpdOperation`], remembering whether it was quoted in the source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdOperand {
+    Word(String),
+    Quoted(String),
+}
+
+impl EpdOperand {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Word(s) | Self::Quoted(s) => s,
+        }
+    }
+
+    fn needs_quotes(s: &str) -> bool {
+        s.is_empty() || s.contains(|c: char| c.is_whitespace() || matches!(c, ';' | '"' | '\'' | '\\'))
+    }
+}
+
+impl fmt::Display for EpdOperand {
+    // canonical form uses double quotes (with `"` and `\` escaped) for quoted operands
+    // and for any word that would not survive being re-parsed unquoted
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Word(s) if !Self::needs_quotes(s) => write!(f, "{s}"),
+            _ => {
+                write!(f, "\"")?;
+                for c in self.as_str().chars() {
+                    if c == '"' || c == '\\' {
+                        write!(f, "\\")?;
+                    }
+                    write!(f, "{c}")?;
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+/// An opcode and its operands, eg `bm e4 d4;`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpdOperation {
+    pub opcode:   String,
+    pub operands: Vec<EpdOperand>,
+}
+
+impl fmt::Display for EpdOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        for operand in &self.operands {
+            write!(f, " {operand}")?;
+        }
+        write!(f, ";")
+    }
+}
+
+impl EpdOperation {
+    /// operands joined by a single space, as stored in [`Tags`]
+    pub fn value(&self) -> String {
+        self.operands.iter().map(EpdOperand::as_str).join(" ")
+    }
+}
+
+/// An EPD record as written: the position fields, then the operations in source order
+/// (duplicate opcodes are kept) and any trailing `#` comment.
+///
+/// Unlike [`Epd`] no board is built, so records can be filtered or rewritten cheaply, and
+/// the [`fmt::Display`] output is the canonical form of the record.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EpdRecord {
+    pub position:   String,
+    pub operations: Vec<EpdOperation>,
+    pub comment:    Option<String>,
+}
+
+impl fmt::Display for EpdRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.position)?;
+        for op in &self.operations {
+            write!(f, " {op}")?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, " ")?;
+            write_comment(f, comment)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_comment(f: &mut fmt::Formatter, comment: &str) -> fmt::Result {
+    if comment.is_empty() {
+        write!(f, "#")
+    } else {
+        write!(f, "# {comment}")
+    }
+}
+
+impl From<&Epd> for EpdRecord {
+    fn from(epd: &Epd) -> Self {
+        let mut record = EpdRecord {
+            position: epd.starting.to_fen(),
+            ..EpdRecord::default()
+        };
+        if !epd.played.is_empty() {
+            let moves = epd.played.to_san(&epd.starting);
+            record.push(
+                "moves",
+                moves
+                    .split_whitespace()
+                    .map(|w| EpdOperand::Word(w.to_string()))
+                    .collect(),
+            );
+        }
+        for (k, v) in epd.tags.iter().sorted() {
+            let operands = match v {
+                "" => vec![],
+                v if v.contains(char::is_whitespace) => vec![EpdOperand::Quoted(v.to_string())],
+                v => vec![EpdOperand::Word(v.to_string())],
+            };
+            record.push(k, operands);
+        }
+        record
+    }
+}
+
+impl EpdRecord {
+    /// a single line record, `fen [hmvc fmvn] op1 ...; op2 ...; # comment` or `startpos op1 ...;`
+    pub fn parse(s: &str) -> Result<Self> {
+        let (position, rest) = Self::split_position(s.trim())?;
+        let mut record = EpdRecord {
+            position,
+            ..EpdRecord::default()
+        };
+        let mut chars = rest.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.peek() {
+                None => break,
+                Some(';') => {
+                    chars.next();
+                    continue;
+                }
+                Some('#') => {
+                    chars.next();
+                    record.comment = Some(chars.collect::<String>().trim().to_string());
+                    break;
+                }
+                Some(_) => {}
+            }
+            let mut opcode = String::new();
+            while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != ';') {
+                opcode.push(c);
+            }
+            let mut operands = vec![];
+            loop {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                match chars.next() {
+                    None | Some(';') => break,
+                    Some(q @ ('"' | '\'')) => {
+                        let quoted = Self::parse_quoted(&mut chars, q).with_context(|| format!("opcode '{opcode}'"))?;
+                        operands.push(EpdOperand::Quoted(quoted));
+                    }
+                    Some(c) => {
+                        let mut word = String::from(c);
+                        while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != ';') {
+                            word.push(c);
+                        }
+                        operands.push(EpdOperand::Word(word));
+                    }
+                }
+            }
+            record.operations.push(EpdOperation { opcode, operands });
+        }
+        Ok(record)
+    }
+
+    fn split_position(s: &str) -> Result<(String, &str)> {
+        if let Some(rest) = s.strip_prefix("startpos") {
+            if rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == ';') {
+                return Ok(("startpos".to_string(), rest));
+            }
+        }
+        let words = s.split_whitespace().take(6).collect_vec();
+        if words.len() < 4 {
+            bail!("must specify at least 4 parts in EPD '{s}'");
+        }
+        let n = if words.len() >= 6 && words[4].parse::<u16>().is_ok() && words[5].parse::<u16>().is_ok() {
+            6
+        } else {
+            4
+        };
+        Ok((words[..n].join(" "), s.trim_first_n_words(n as u16)))
+    }
+
+    fn parse_quoted(chars: &mut impl Iterator<Item = char>, quote: char) -> Result<String> {
+        let mut s = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => s.push(chars.next().context("trailing escape in quoted string")?),
+                c if c == quote => return Ok(s),
+                c => s.push(c),
+            }
+        }
+        bail!("unterminated quoted string '{quote}{s}'")
+    }
+
+    /// the first operation with this opcode
+    pub fn get(&self, opcode: &str) -> Option<&EpdOperation> {
+        self.operations.iter().find(|op| op.opcode == opcode)
+    }
+
+    /// every operation with this opcode, in source order
+    pub fn get_all<'a>(&'a self, opcode: &'a str) -> impl Iterator<Item = &'a EpdOperation> + 'a {
+        self.operations.iter().filter(move |op| op.opcode == opcode)
+    }
+
+    pub fn push(&mut self, opcode: &str, operands: Vec<EpdOperand>) {
+        self.operations.push(EpdOperation {
+            opcode: opcode.to_string(),
+            operands,
+        });
+    }
+
+    /// builds the board, with a `moves` operation becoming the played moves. As [`Tags`] hold
+    /// one value per opcode, the last of any duplicate opcodes wins.
+    pub fn to_epd(&self) -> Result<Epd> {
+        let starting = Epd::parse_epd(&self.position)?.setup_board();
+        let mut played = Variation::new();
+        let mut tags = Tags::new();
+        for op in &self.operations {
+            if op.opcode == "moves" {
+                played = Variation::parse_san(&op.value(), &starting)?;
+            } else {
+                tags.insert(&op.opcode, &op.value());
+            }
+        }
+        Ok(Epd { starting, played, tags })
+    }
+}
+
+/// A line of an EPD file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdLine {
+    Blank,
+    Comment(String),
+    Record(EpdRecord),
+}
+
+impl fmt::Display for EpdLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Blank => Ok(()),
+            Self::Comment(comment) => write_comment(f, comment),
+            Self::Record(record) => write!(f, "{record}"),
+        }
+    }
+}
+
+impl EpdLine {
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            Ok(Self::Blank)
+        } else if let Some(comment) = s.strip_prefix('#') {
+            Ok(Self::Comment(comment.trim().to_string()))
+        } else {
+            Ok(Self::Record(EpdRecord::parse(s)?))
+        }
+    }
+
+    pub fn into_record(self) -> Option<EpdRecord> {
+        match self {
+            Self::Record(record) => Some(record),
+            _ => None,
+        }
+    }
+}
+
+/// Streams [`EpdLine`]s one line at a time, so that files too large to hold in memory can be
+/// filtered or converted. Each record must be on a single line.
+pub struct EpdReader<R> {
+    reader:      R,
+    buf:         String,
+    line_number: usize,
+}
+
+impl EpdReader<BufReader<File>> {
+    pub fn open(file: impl AsRef<Path>) -> Result<Self> {
+        let f = File::open(&file).with_context(|| format!("opening EPD file '{}'", file.as_ref().display()))?;
+        Ok(Self::new(BufReader::new(f)))
+    }
+}
+
+impl<R: BufRead> EpdReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: String::new(),
+            line_number: 0,
+        }
+    }
+
+    /// skips blank and comment lines
+    pub fn records(self) -> impl Iterator<Item = Result<EpdRecord>> {
+        self.filter_map(|line| line.map(EpdLine::into_record).transpose())
+    }
+
+    pub fn epds(self) -> impl Iterator<Item = Result<Epd>> {
+        self.records().map(|record| record?.to_epd())
+    }
+}
+
+impl<R: BufRead> Iterator for EpdReader<R> {
+    type Item = Result<EpdLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.buf.clear();
+        self.line_number += 1;
+        match self.reader.read_line(&mut self.buf) {
+            Ok(0) => None,
+            Ok(_) => Some(EpdLine::parse(&self.buf).with_context(|| format!("line {n} of EPD", n = self.line_number))),
+            Err(e) => Some(Err(e).with_context(|| format!("reading line {n} of EPD", n = self.line_number))),
+        }
+    }
+}
+
+/// Writes lines in canonical form: single spaces between fields, every operation terminated
+/// by `;`, and quoted operands in double quotes.
+pub struct EpdWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> EpdWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write_line(&mut self, line: &EpdLine) -> Result<()> {
+        writeln!(self.writer, "{line}")?;
+        Ok(())
+    }
+
+    pub fn write_record(&mut self, record: &EpdRecord) -> Result<()> {
+        writeln!(self.writer, "{record}")?;
+        Ok(())
+    }
+
+    pub fn write_epd(&mut self, epd: &Epd) -> Result<()> {
+        self.write_record(&EpdRecord::from(epd))
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rand::SeedableRng as _;
@@ -451,6 +811,76 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_epd_record() -> Result<()> {
+        let s = r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR  w KQkq -   c0 'Fischer; "Bobby"'; bm e4 d4; c0 x\y;noop; # from a file"#;
+        let record = EpdRecord::parse(s)?;
+        assert_eq!(record.position, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
+        assert_eq!(record.operations.len(), 4);
+        let c0 = record.get_all("c0").map(EpdOperation::value).collect_vec();
+        assert_eq!(c0, vec![r#"Fischer; "Bobby""#, r"x\y"]);
+        assert_eq!(record.get("bm").unwrap().operands.len(), 2);
+        assert!(record.get("noop").unwrap().operands.is_empty());
+        assert_eq!(record.comment.as_deref(), Some("from a file"));
+
+        // canonical form is stable and round trips
+        let canonical = record.to_string();
+        assert_eq!(
+            canonical,
+            r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - c0 "Fischer; \"Bobby\""; bm e4 d4; c0 "x\\y"; noop; # from a file"#
+        );
+        assert_eq!(EpdRecord::parse(&canonical)?.to_string(), canonical);
+
+        // last duplicate wins when converted to tags
+        let epd = record.to_epd()?;
+        assert_eq!(epd.board(), Board::starting_pos());
+        assert_eq!(epd.tag("c0"), Some(r"x\y"));
+        assert_eq!(epd.moves("bm").unwrap().len(), 2);
+
+        let epd = EpdRecord::parse("startpos moves e4 e5; id 'test 1'; pv Nf3 Nc6")?.to_epd()?;
+        assert_eq!(epd.played().len(), 2);
+        assert_eq!(
+            EpdRecord::from(&epd).to_string(),
+            r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e4 e5; id "test 1"; pv "Nf3 Nc6";"#
+        );
+        assert_eq!(EpdRecord::from(&epd).to_epd()?, epd);
+
+        assert!(EpdRecord::parse("startpos id 'unterminated;").is_err());
+        assert!(EpdRecord::parse("8/8/8 w").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_epd_reader_writer() -> Result<()> {
+        let file = "# a suite\n\nstartpos bm e4;\r\n  8/8/8/8/8/8/8/K6k w - - 10 20 id  \"kk\" ;\n";
+        let lines = EpdReader::new(file.as_bytes()).collect::<Result<Vec<_>>>()?;
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], EpdLine::Comment("a suite".to_string()));
+        assert_eq!(lines[1], EpdLine::Blank);
+
+        let mut writer = EpdWriter::new(Vec::new());
+        for line in &lines {
+            writer.write_line(line)?;
+        }
+        let written = String::from_utf8(writer.into_inner()?)?;
+        assert_eq!(
+            written,
+            "# a suite\n\nstartpos bm e4;\n8/8/8/8/8/8/8/K6k w - - 10 20 id kk;\n"
+        );
+
+        let epds = EpdReader::new(file.as_bytes()).epds().collect::<Result<Vec<_>>>()?;
+        assert_eq!(epds.len(), 2);
+        assert_eq!(epds[1].board().halfmove_clock(), 10);
+        assert_eq!(epds[1].tag("id"), Some("kk"));
+
+        let err = EpdReader::new("startpos\nnot an epd\n".as_bytes())
+            .records()
+            .nth(1)
+            .unwrap();
+        assert!(format!("{:#}", err.unwrap_err()).contains("line 2"));
+        Ok(())
+    }
+
     #[test]
     fn test_random_epd() {
         let mut rand = ChaChaRng::seed_from_u64(1);
@@ -1,7 +1,3 @@
-use std::sync::atomic::AtomicBool;
-
-pub static FEATURE: AtomicBool = AtomicBool::new(false);
-
 #[derive(Copy, Clone, Debug)]
 pub enum State {
     NewGame,
@@ -108,6 +108,23 @@ impl<T: Default> AlignedVec<T> {
         &self.vec[self.index(h)..(self.index(h) + self.bucket_size)]
     }
 
+    /// a hint to load the bucket's cache line, so that a probe shortly after need not wait
+    /// on memory. A no-op other than on x86_64
+    #[inline]
+    pub fn prefetch(&self, h: Hash) {
+        if self.vec.is_empty() {
+            return;
+        }
+        #[cfg(target_arch = "x86_64")]
+        // SAFETY: the index is within the vec, and prefetching never faults
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch::<_MM_HINT_T0>(self.vec.as_ptr().add(self.index(h)) as *const i8);
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = h;
+    }
+
     pub fn bucket_count(&self) -> usize {
         self.capacity() / self.bucket_size.max(1)
     }
@@ -304,10 +321,15 @@ impl<T: Copy + Default> UnsharedTable<T> {
     }
 
     pub fn with_size_bytes(bytes: usize) -> Self {
-        let capacity = bytes / std::mem::size_of::<(Cell<Hash>, Cell<T>)>();
+        let capacity = bytes / Self::entry_bytes();
         Self::with_size(capacity)
     }
 
+    /// the memory taken by each entry of the table
+    pub const fn entry_bytes() -> usize {
+        std::mem::size_of::<(Cell<Hash>, Cell<T>)>()
+    }
+
     #[inline]
     pub fn probe(&self, hash: Hash) -> Option<T> {
         let key = hash as usize % self.capacity();
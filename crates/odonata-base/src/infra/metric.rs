@@ -1095,6 +1095,11 @@ impl fmt::Display for Metrics {
                             self.nodes[Event::TtNodeHit.index()].for_ply(ply),
                             self.nodes[Event::TtNodeMiss.index()].for_ply(ply),
                         ),
+                        Event::CaptureCutFirstPerc => perc(
+                            self.nodes[Event::CaptureCutFirst.index()].for_ply(ply),
+                            self.nodes[Event::CaptureCutFirst.index()].for_ply(ply)
+                                + self.nodes[Event::CaptureCutLater.index()].for_ply(ply),
+                        ),
                         Event::NodeNmpPerc => perc(
                             self.nodes[Event::NmpSuccess.index()].for_ply(ply),
                             self.nodes[Event::NodeInterior.index()].for_ply(ply),
@@ -3,10 +3,15 @@ pub mod lockless_hashmap;
 pub mod math;
 pub mod metric;
 pub mod param;
+pub mod paranoid;
 pub mod resources;
 pub mod utils;
 pub mod value;
 pub mod version;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "profiler"))]
+pub mod profiler;
+
+#[cfg(not(all(target_os = "linux", feature = "profiler")))]
+#[path = "profiler_stub.rs"]
 pub mod profiler;
@@ -0,0 +1,100 @@
+use crate::boards::hasher::Hasher;
+use crate::domain::node::Node;
+use crate::piece::MAX_PLY;
+use crate::prelude::*;
+use crate::variation::Variation;
+
+/// Heavyweight runtime checks for CI and fuzz runs, compiled in only with the `paranoid`
+/// feature. Unlike `debug_assert!` they are too slow for everyday debug builds.
+pub const ENABLED: bool = cfg!(feature = "paranoid");
+
+/// the incremental hashes are recomputed in full every this many nodes
+pub const HASH_CHECK_NODES: u64 = 1 << 10;
+
+/// `assert!` under the `paranoid` feature, otherwise compiled out
+#[macro_export]
+macro_rules! paranoid_assert {
+    ($($arg:tt)+) => {
+        if $crate::infra::paranoid::ENABLED {
+            assert!($($arg)+);
+        }
+    };
+}
+
+/// board consistency, and incremental hashes equal to a full recompute
+pub fn check_board(b: &Board) {
+    if !ENABLED {
+        return;
+    }
+    if let Err(e) = b.validate() {
+        panic!("paranoid: board {b} is invalid: {e}");
+    }
+    let keys = Hasher::instance().hash_keys(b);
+    assert!(
+        *b.hash_keys() == keys,
+        "paranoid: board {b} has incremental hashes {:x?} but recomputed {keys:x?}",
+        b.hash_keys()
+    );
+}
+
+/// alpha below beta, both within the score range, and the ply within bounds
+pub fn check_node(n: &Node) {
+    if !ENABLED {
+        return;
+    }
+    assert!(n.alpha < n.beta, "paranoid: node {n} has alpha >= beta");
+    assert!(
+        n.alpha >= -Score::INFINITY && n.beta <= Score::INFINITY,
+        "paranoid: node {n} bounds out of range"
+    );
+    assert!(n.ply >= 0 && n.ply < MAX_PLY, "paranoid: node {n} ply out of range");
+}
+
+pub fn check_score(s: Score, n: &Node) {
+    if !ENABLED {
+        return;
+    }
+    assert!(
+        s >= -Score::INFINITY && s <= Score::INFINITY,
+        "paranoid: score {s} out of range at node {n}"
+    );
+}
+
+pub fn check_pv(b: &Board, pv: &Variation) {
+    if !ENABLED {
+        return;
+    }
+    if let Some(ply) = pv.first_illegal_ply(b) {
+        panic!("paranoid: pv {pv} illegal at ply {ply} on {b}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_paranoid_checks() {
+        let b = Board::starting_pos();
+        check_board(&b);
+        check_pv(&b, &b.parse_san_variation("e4 e5 Nf3").unwrap());
+        let n = Node::root(3);
+        check_node(&n);
+        check_score(Score::zero(), &n);
+        paranoid_assert!(n.ply == 0, "ply");
+    }
+
+    #[test]
+    #[cfg(feature = "paranoid")]
+    #[should_panic(expected = "alpha >= beta")]
+    fn test_paranoid_bounds() {
+        let n = Node {
+            alpha: Score::zero(),
+            beta: Score::zero(),
+            ..Node::root(3)
+        };
+        check_node(&n);
+    }
+}
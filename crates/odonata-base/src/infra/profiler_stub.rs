@@ -0,0 +1,93 @@
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Stands in for the perf counter profiler when the `profiler` feature is off (or off linux),
+/// so the bench tests still build and run, reporting wall-clock time only.
+pub struct PerfProfiler {
+    benchmark_iters: u64,
+    start_time:      Instant,
+    elapsed:         Duration,
+    name:            String,
+    iters:           u64,
+}
+
+impl fmt::Display for PerfProfiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl PerfProfiler {
+    pub fn new(name: &str) -> Self {
+        let benchmark_iters = if let Ok(s) = std::env::var("RUST_BENCH") {
+            s.parse().unwrap_or_else(|_| panic!("RUST_BENCH not an integer: {s}"))
+        } else {
+            1
+        };
+        Self {
+            benchmark_iters,
+            start_time: Instant::now(),
+            elapsed: Duration::ZERO,
+            name: name.to_string(),
+            iters: 0,
+        }
+    }
+
+    pub fn bench<R>(&mut self, mut f: impl FnMut() -> R) -> R {
+        self.start();
+        for _iter in 1..self.benchmark_iters {
+            let _ret = black_box(f());
+            self.iters += 1;
+        }
+        let ret = black_box(f());
+        self.stop();
+        self.iters += 1;
+        ret
+    }
+
+    pub fn start(&mut self) {
+        self.start_time = Instant::now();
+    }
+
+    pub fn stop(&mut self) {
+        self.elapsed += self.start_time.elapsed();
+    }
+
+    /// no hardware counters, so always zero
+    pub fn cycles(&mut self) -> u64 {
+        0
+    }
+
+    /// no hardware counters, so always zero
+    pub fn instructions(&mut self) -> u64 {
+        0
+    }
+
+    pub fn set_iters(&mut self, iters: u64) {
+        self.iters = iters
+    }
+
+    pub fn write<W: std::io::Write>(&mut self, mut w: W) -> anyhow::Result<()> {
+        self.iters = std::cmp::max(1, self.iters);
+        writeln!(w, "PROFH: {:<30}\t{:>10}\t{:>13}", "name", "iters", "realtime_ms")?;
+        writeln!(
+            w,
+            "PROFD: {:<30}\t{:>10}\t{:>13.3}\n",
+            self.name,
+            self.iters,
+            self.elapsed.as_micros() as f64 / 1000.0
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for PerfProfiler {
+    fn drop(&mut self) {
+        if std::env::var("RUST_BENCH").is_ok() {
+            let mut buf = Vec::new();
+            self.write(&mut buf).unwrap();
+            println!("{}", String::from_utf8(buf).unwrap());
+        }
+    }
+}
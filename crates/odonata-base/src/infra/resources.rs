@@ -6,6 +6,7 @@ use anyhow::Context as _;
 use include_dir::{include_dir, Dir};
 
 use crate::infra::version::Version;
+use crate::Epd;
 
 pub static RESOURCE_DIR: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/resources");
 
@@ -40,12 +41,28 @@ pub fn read_resource_file(path: impl AsRef<Path>) -> &'static str {
         .unwrap()
 }
 
+/// checks every embedded resource is readable (and epd files parse), listing what was checked
+pub fn verify_embedded() -> anyhow::Result<Vec<String>> {
+    let mut checked = vec![];
+    for file in RESOURCE_DIR.files() {
+        let path = file.path().display();
+        let text = file
+            .contents_utf8()
+            .with_context(|| format!("{path} not a text file"))?;
+        if file.path().extension().is_some_and(|ext| ext == "epd") {
+            let epds = Epd::parse_many_epd(text.lines()).with_context(|| format!("parsing {path}"))?;
+            checked.push(format!("resource {path} ({n} positions)", n = epds.len()));
+        } else {
+            checked.push(format!("resource {path}"));
+        }
+    }
+    Ok(checked)
+}
 
 pub fn relative_path(path: impl AsRef<Path>) -> PathBuf {
     workspace_dir().join(path)
 }
 
-
 /// if run from cargo
 pub fn workspace_dir() -> PathBuf {
     let Ok(cargo) = std::env::var("CARGO") else {
@@ -117,6 +134,12 @@ mod tests {
         assert!(read_resource_file("iq.epd").contains("IQ test suite"));
     }
 
+    #[test]
+    fn test_verify_embedded() {
+        let checked = verify_embedded().unwrap();
+        assert!(checked.iter().any(|s| s.starts_with("resource bk.epd")), "{checked:?}");
+    }
+
     #[test]
     fn test_resource_dir() {
         let files = RESOURCE_DIR.files().map(|f| f.path().display()).join(";");
@@ -52,6 +52,20 @@ impl Version {
         )
     }
 
+    /// the speed-critical cpu features compiled for, eg "AVX2 BMI2"
+    pub fn cpu_features() -> String {
+        let features = [
+            ("AVX2", cfg!(target_feature = "avx2")),
+            ("BMI2", cfg!(target_feature = "bmi2")),
+        ];
+        features
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     thread_local! { static METRICS_ENABLED: std::cell::Cell<bool>  = cfg!(any(feature = "metrics", debug_assertions)).into(); }
 
     pub fn compiled_profile_name() -> &'static str {
@@ -1,7 +1,6 @@
 #![cfg_attr(debug_assertions, allow(dead_code))]
 #![cfg_attr(not(debug_assertions), allow(dead_code))]
 
-
 extern crate test_log;
 
 extern crate include_dir;
@@ -34,6 +33,7 @@ pub mod infra;
 pub mod movelist;
 pub mod mv;
 pub mod other;
+pub mod pgn;
 pub mod piece;
 pub mod prelude;
 pub mod trace;
@@ -43,6 +43,7 @@ pub use crate::bits::bitboard::Bitboard;
 pub use crate::bits::precalc::PreCalc;
 pub use crate::epd::Epd;
 pub use crate::movelist::MoveList;
+pub use crate::pgn::Pgn;
 pub use crate::piece::FlipVertical;
 // pub use crate::logger::LogInit;
 pub use crate::piece::{Color, Piece};
@@ -3,10 +3,13 @@ use std::fmt;
 use anyhow::anyhow;
 use arrayvec::ArrayVec;
 use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
+use rand::Rng;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::domain::score::ToScore;
+use crate::mv::{AutoQueen, PromotionError};
 use crate::other::Parse;
 use crate::piece::MAX_LEGAL_MOVES;
 use crate::prelude::*;
@@ -380,6 +383,14 @@ impl MoveList {
         self.moves.contains(m)
     }
 
+    /// a move of the same piece to the same square (and same promotion) as `m`,
+    /// so "Nbd2" matches "Nfd2"
+    pub fn contains_same_destination(&self, m: &Move) -> bool {
+        self.moves
+            .iter()
+            .any(|x| x.to() == m.to() && x.mover() == m.mover() && x.promo_piece() == m.promo_piece())
+    }
+
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = &Move> + '_ {
         self.moves.iter()
@@ -430,13 +441,19 @@ impl MoveList {
         self.moves.sort_unstable_by_key(f)
     }
 
+    /// a stable sort calling the key function once per move. Unlike the slice version,
+    /// the keys are held on the stack so no heap allocation is made during search
     #[inline]
-    pub fn sort_by_cached_key<K, F>(&mut self, f: F)
+    pub fn sort_by_cached_key<K, F>(&mut self, mut f: F)
     where
         F: FnMut(&Move) -> K,
         K: Ord,
     {
-        self.moves.sort_by_cached_key(f)
+        let mut keys: ArrayVec<(K, usize), MAX_LEGAL_MOVES> =
+            self.moves.iter().enumerate().map(|(i, mv)| (f(mv), i)).collect();
+        keys.sort_unstable();
+        let sorted: ArrayVec<Move, MAX_LEGAL_MOVES> = keys.iter().map(|&(_, i)| self.moves[i]).collect();
+        self.moves = sorted;
     }
 
     #[inline]
@@ -444,6 +461,11 @@ impl MoveList {
         self.moves.reverse();
     }
 
+    #[inline]
+    pub fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.moves.shuffle(rng);
+    }
+
     #[inline]
     pub fn extend<T: IntoIterator<Item = Move>>(&mut self, iter: T) {
         self.moves.extend(iter);
@@ -547,12 +569,30 @@ impl fmt::Display for MoveList {
 
 impl Board {
     pub fn parse_uci_move(&self, mv: &str) -> Result<Move> {
+        self.parse_uci_move_with(mv, AutoQueen::Strict)
+    }
+
+    /// as parse_uci_move, with a pawn move to the back rank lacking a promotion piece handled by `auto_queen`
+    pub fn parse_uci_move_with(&self, mv: &str, auto_queen: AutoQueen) -> Result<Move> {
+        if let Some(promo @ (Piece::Pawn | Piece::King)) = mv.chars().nth(4).and_then(|ch| Piece::from_char(ch).ok()) {
+            return Err(PromotionError::InvalidPiece(promo)).with_context(|| format!("Move '{mv}' for board {self}"));
+        }
         let moves = self.legal_moves();
         for &m in moves.iter() {
-            if m.to_uci() == mv {
+            if m.matches_uci(mv) {
                 return Ok(m);
             }
         }
+        if mv.len() == 4 {
+            if let Some(&m) = moves.iter().find(|m| m.is_promo() && m.to_uci().starts_with(mv)) {
+                return match auto_queen {
+                    AutoQueen::Queen => Ok(m.with_promotion(Piece::Queen)?),
+                    AutoQueen::Strict => {
+                        Err(PromotionError::MissingPiece).with_context(|| format!("Move '{mv}' for board {self}"))
+                    }
+                };
+            }
+        }
         Err(anyhow!("Move '{mv}' is not legal for board {self}",))
     }
 
@@ -573,6 +613,11 @@ impl Board {
         Parse::move_san(mv, self)
     }
 
+    /// as parse_san_move, with a pawn move to the back rank lacking a promotion piece handled by `auto_queen`
+    pub fn parse_san_move_with(&self, mv: &str, auto_queen: AutoQueen) -> Result<Move> {
+        Parse::move_san_with(mv, self, auto_queen)
+    }
+
     pub fn parse_san_movelist(&self, s: &str) -> Result<MoveList> {
         let mut moves = MoveList::new();
         let s = s.replace(',', " ");
@@ -651,7 +696,10 @@ impl Board {
             s.push(promo.to_upper_char());
         }
         if self.gives_check(mv) {
-            s.push('+');
+            match self.make_move(mv).has_legal_moves() {
+                true => s.push('+'),
+                false => s.push('#'),
+            }
         }
         s
     }
@@ -725,6 +773,17 @@ mod tests {
 
         assert_eq!(moves.to_string(), "c1d3, a7a8q");
 
+        let b = Board::parse_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+        let bm = b.parse_san_movelist("Nbd2").unwrap();
+        assert!(bm.contains_same_destination(&b.parse_san_move("Nfd2")?));
+        assert!(!bm.contains(&b.parse_san_move("Nfd2")?));
+        assert!(!bm.contains_same_destination(&b.parse_san_move("Kd2")?));
+        assert!(!bm.contains_same_destination(&b.parse_san_move("Nc3")?));
+        assert_eq!(
+            serde_json::from_str::<MoveList>(&serde_json::to_string(&moves)?)?,
+            moves
+        );
+
         let mut moves = Variation::new();
         moves.set_last_move(1, move_c1d3);
         assert_eq!(moves.to_string(), "c1d3");
@@ -4,6 +4,7 @@ use std::str::FromStr;
 use anyhow::{anyhow, bail, Result};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
 
 use crate::bits::bitboard::{Bitboard, Dir};
 use crate::bits::castling::CastlingRights;
@@ -403,6 +404,36 @@ use crate::{FlipVertical, PreCalc};
 //     }
 // }
 
+/// why a promotion was rejected. Returned within the anyhow errors of move parsing,
+/// so callers can `downcast_ref::<PromotionError>()`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PromotionError {
+    InvalidPiece(Piece), // promotion to a king or a pawn
+    MissingPiece,        // pawn move to the back rank without a piece, and not auto-queening
+    NotAPromotion,       // promotion piece given, but not a pawn move to the back rank
+}
+
+impl fmt::Display for PromotionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidPiece(p) => write!(f, "cannot promote to a {}", p.name()),
+            Self::MissingPiece => write!(f, "pawn move to back rank must specify a promotion piece"),
+            Self::NotAPromotion => write!(f, "must be a pawn move to the back rank to be a promotion"),
+        }
+    }
+}
+
+impl std::error::Error for PromotionError {}
+
+/// handling of pawn moves to the back rank given without a promotion piece (eg "e7e8" or "e8"),
+/// as sent by some GUIs and bridges
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumString)]
+pub enum AutoQueen {
+    Strict, // reject with PromotionError::MissingPiece
+    #[default]
+    Queen,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BareMove {
     pub mover: Option<Piece>,
@@ -420,7 +451,7 @@ impl Debug for BareMove {
 impl FromStr for BareMove {
     type Err = anyhow::Error;
 
-    /// either uci or lan acceptable eg e4e5, e4xd5, e7e8p, Ra1a8, Bc1xd2
+    /// either uci or lan acceptable eg e4e5, e4xd5, e7e8n, Ra1a8, Bc1xd2
     fn from_str(s: &str) -> Result<Self> {
         Self::parse_uci(s).or_else(|_| Self::parse_lan(s))
     }
@@ -455,7 +486,36 @@ impl BareMove {
     }
 
     pub fn is_castle(&self, b: &Board) -> bool {
-        self.from.is_in(b.kings()) && CastlingRights::is_castling(self.from, self.to)
+        self.castling_rook(b).is_some()
+    }
+
+    /// the rook square of a castle, which is given either as king-takes-rook (as in Chess960) or
+    /// in standard notation as the king's two-square step from the e-file
+    fn castling_rook(&self, b: &Board) -> Option<Square> {
+        if self.promo.is_some()
+            || !self.from.is_in(b.kings() & b.us())
+            || self.from.rank_index() != self.to.rank_index()
+        {
+            return None;
+        }
+        if self.to.is_in(b.rooks() & b.us()) {
+            return Some(self.to);
+        }
+        let right = CastlingRights::from_king_and_rook(self.from, self.to);
+        match (self.from.file_index(), self.to.file_index()) {
+            (4, 2 | 6) if !right.is_empty() => Some(b.castling_rook_square(right)),
+            _ => None,
+        }
+    }
+
+    pub fn with_promotion(self, promo: Piece) -> Result<Self, PromotionError> {
+        if promo == Piece::Pawn || promo == Piece::King {
+            return Err(PromotionError::InvalidPiece(promo));
+        }
+        Ok(Self {
+            promo: Some(promo),
+            ..self
+        })
     }
 
     fn parse_uci(s: &str) -> anyhow::Result<Self> {
@@ -464,19 +524,17 @@ impl BareMove {
         }
         let from = Square::parse(s.take_slice(0..2))?;
         let to = Square::parse(s.take_slice(2..4))?;
-        let promo = if let Some(ch) = s.take_char_at(4) {
-            Some(Piece::from_char(ch)?)
-        } else {
-            None
-        };
-        Ok(BareMove {
+        let bm = BareMove {
             mover: None,
             from,
             to,
-            promo,
-        })
+            promo: None,
+        };
+        match s.take_char_at(4) {
+            Some(ch) => Ok(bm.with_promotion(Piece::from_char(ch)?)?),
+            None => Ok(bm),
+        }
     }
-
     // lan = Ra1a4 Ra1-a4 Ra1xa4 e4e5 e4-e5 e4xd5 e7e8Q
     fn parse_lan(s: &str) -> anyhow::Result<Self> {
         if s.trim() == "0000" {
@@ -525,6 +583,29 @@ impl BareMove {
             }
         }
 
+        let pc = PreCalc::instance();
+
+        // castling is validated in full here, as king-takes-rook
+        if let Some(rook_sq) = mv.castling_rook(bd) {
+            let right = CastlingRights::from_king_and_rook(from, rook_sq);
+            if !bd.castling().contains(right) || bd.castling_rook_square(right) != rook_sq {
+                return Err("no castling rights");
+            }
+            if bd.is_in_check(color_us) {
+                return Err("cannot castle when in check");
+            }
+            let (king_to, rook_to) = right.king_and_rook_to();
+            let king_moves = pc.between(from, king_to);
+            let castlers = from.as_bb() | rook_sq.as_bb();
+            if ((king_moves | pc.between(rook_sq, rook_to)) - castlers).intersects(us | them) {
+                return Err("cannot castle when king or rook squares are occupied");
+            }
+            if BoardCalcs::attacked_by(king_moves, (us | them) - rook_sq.as_bb(), bd).intersects(them) {
+                return Err("cannot castle when king moves through check");
+            }
+            return Ok(Move::new_castle(from, rook_sq, bd));
+        }
+
         if to.is_in(us) {
             return Err("to-sq is our color");
         }
@@ -540,8 +621,6 @@ impl BareMove {
             }
         }
 
-        let pc = PreCalc::instance();
-
         // check pawn movement
         if mover == Piece::Pawn {
             if (pc.strictly_between(from, to) & bd.occupied()).any() {
@@ -567,43 +646,18 @@ impl BareMove {
             }
         }
 
-        if mv.is_castle(bd) {
-            let rights = CastlingRights::from_king_move(to);
-            if !bd.castling().contains(rights) {
-                return Err("no castling rights");
-            }
-            if bd.is_in_check(color_us) {
-                return Err("cannot castle when in check");
-            }
-            let king_in_between = unsafe { Square::from_u8_unchecked((from.index() + to.index()) as u8 / 2) };
-            if king_in_between.is_in(us | them) || to.is_in(them) {
-                return Err("cannot castle when king step/to square is occupied");
-            }
-            if king_in_between.is_in(us | them) || to.is_in(them) {
-                return Err("cannot castle when king-step/to square is occupied");
-            }
-            // west of king-to is either the king-step square (king side) or the rook step-over sq (queens)
-            // in either case it must be empty, but the step-sq situation is checked above hence the specific error
-            if to.shift(Dir::W).is_in(us | them) {
-                return Err("rook step-square must be empty");
-            }
-            if BoardCalcs::attacked_by(king_in_between.as_bb(), us | them, bd).intersects(them) {
-                return Err("cannot castle when king moves through check");
-            }
-        } else {
-            let atts = pc.attacks(color_us, mover, us, them, from);
-            #[allow(clippy::nonminimal_bool)]
-            if !to.is_in(atts)
-                && !(mover == Piece::Pawn
-                    && bd.is_en_passant_square(to)
-                    && to.is_in(pc.pawn_capture_attacks_from_sq(color_us, from)))
-            {
-                trace!(
-                    "{mover} {from} -> {to}\n{atts} {bd} {ep}",
-                    ep = bd.is_en_passant_square(to)
-                );
-                return Err("to-square is not a valid move-to square for the piece");
-            }
+        let atts = pc.attacks(color_us, mover, us, them, from);
+        #[allow(clippy::nonminimal_bool)]
+        if !to.is_in(atts)
+            && !(mover == Piece::Pawn
+                && bd.is_en_passant_square(to)
+                && to.is_in(pc.pawn_capture_attacks_from_sq(color_us, from)))
+        {
+            trace!(
+                "{mover} {from} -> {to}\n{atts} {bd} {ep}",
+                ep = bd.is_en_passant_square(to)
+            );
+            return Err("to-square is not a valid move-to square for the piece");
         }
 
         // pseudo-legal move - test for in check - castling already handled at this point
@@ -725,6 +779,9 @@ impl BareMove {
 
 type UMove = u32;
 
+/// Displays as uci. Parsing needs the board (mover, capture, castling and ep flags),
+/// so there is no FromStr - use `Move::parse_uci` / `Board::parse_uci_move`, or `BareMove`
+/// which parses without one. Serde stores the packed bits.
 #[derive(Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Move {
     bits: UMove,
@@ -921,7 +978,7 @@ impl Move {
             }
         } else if mover == Piece::Pawn {
             Self::new_pawn_move(from, to, bd)
-        } else if mover == Piece::King && CastlingRights::is_castling(from, to) {
+        } else if mover == Piece::King && to.is_in(bd.rooks() & bd.us()) {
             Self::new_castle(from, to, bd)
         } else if to.is_in(bd.occupied()) {
             Self::new_capture(mover, from, to, bd)
@@ -1010,9 +1067,24 @@ impl Move {
         m
     }
 
+    /// the pawn move to the back rank, promoting to `promo` (replacing any existing promotion)
+    pub fn with_promotion(self, promo: Piece) -> Result<Move, PromotionError> {
+        if promo == Piece::Pawn || promo == Piece::King {
+            return Err(PromotionError::InvalidPiece(promo));
+        }
+        if self.is_null() || self.mover() != Piece::Pawn || !self.to().is_in(Bitboard::RANKS_18) {
+            return Err(PromotionError::NotAPromotion);
+        }
+        let mut m = self;
+        m.set_promo(promo);
+        Ok(m)
+    }
+
+    /// castles are encoded as king-takes-rook, so that Chess960 castles where the king does not
+    /// move remain distinct from the null move
     #[inline]
-    pub fn new_castle(king_from: Square, king_to: Square, bd: &Board) -> Move {
-        let mut m = Self::new_quiet(Piece::King, king_from, king_to, bd);
+    pub fn new_castle(king_from: Square, rook_from: Square, bd: &Board) -> Move {
+        let mut m = Self::new_quiet(Piece::King, king_from, rook_from, bd);
         m.set_castling();
         m
     }
@@ -1149,7 +1221,10 @@ impl Move {
         let from = Square::parse(s.take_slice(0..2))?;
         let to = Square::parse(s.take_slice(2..4))?;
         if let Some(ch) = s.take_char_at(4) {
-            let _promo = Piece::from_char(ch)?;
+            let promo = Piece::from_char(ch)?;
+            if promo == Piece::Pawn || promo == Piece::King {
+                return Err(PromotionError::InvalidPiece(promo).into());
+            }
             if from.rank_number_as_white(b.color_us()) != 7 {
                 bail!("move {s} from {from} sq is not rank 2/7 for board {b}");
             }
@@ -1166,7 +1241,7 @@ impl Move {
         }
         b.legal_moves()
             .iter()
-            .find(|mv| mv.to_uci() == s)
+            .find(|mv| mv.matches_uci(s))
             .ok_or_else(|| anyhow!("move {s} is not a legal move for board {b}"))
             .cloned()
     }
@@ -1195,6 +1270,19 @@ impl Move {
         unsafe { Square::from_u8_unchecked((self.from().index() + self.to().index()) as u8 / 2) }
     }
 
+    /// a double push only sets the e/p square when an enemy pawn alongside could capture it,
+    /// so that otherwise identical positions hash (and print) identically
+    #[inline]
+    pub fn is_ep_capturable(&self, pre_move: &Board) -> bool {
+        let enemy = if self.from().is_in(pre_move.white()) {
+            pre_move.black()
+        } else {
+            pre_move.white()
+        };
+        let alongside = self.to().as_bb().shift(Dir::E) | self.to().as_bb().shift(Dir::W);
+        alongside.intersects(pre_move.pawns() & enemy)
+    }
+
     #[inline]
     pub fn is_ep_capture(&self, _b: &Board) -> bool {
         self.flag().is_en_passant_capture()
@@ -1296,7 +1384,7 @@ impl Move {
     pub fn castling_side(&self, b: &Board) -> CastlingRights {
         // CastlingRights::from_bits_truncate((self.bits >> Self::OFFSET_CASTLE) as u8)
         if self.is_castle(b) {
-            CastlingRights::from_king_move(self.to())
+            CastlingRights::from_king_and_rook(self.from(), self.to())
         } else {
             CastlingRights::NONE
         }
@@ -1320,25 +1408,19 @@ impl Move {
     //     }
     // }
 
+    /// the rook's from and to squares of a castle
     #[inline]
-    pub const fn rook_move_from_to(&self) -> (Square, Square) {
-        match self.to() {
-            Square::C1 => (Square::A1, Square::D1),
-            Square::G1 => (Square::H1, Square::F1),
-            Square::C8 => (Square::A8, Square::D8),
-            Square::G8 => (Square::H8, Square::F8),
-            _ => panic!("not a valid castling king-to square"),
-        }
+    pub fn rook_move_from_to(&self) -> (Square, Square) {
+        let right = CastlingRights::from_king_and_rook(self.from(), self.to());
+        (self.to(), right.king_and_rook_to().1)
     }
 
+    /// the king's destination of a castle (the move itself being king-takes-rook)
     #[inline]
-    pub fn castling_rights_lost(&self) -> CastlingRights {
-        if self.is_null() {
-            CastlingRights::NONE
-        } else {
-            // let squares_changing = self.to().as_bb() | self.from().as_bb();
-            CastlingRights::rights_lost(self.from(), self.to())
-        }
+    pub fn castling_king_to(&self) -> Square {
+        CastlingRights::from_king_and_rook(self.from(), self.to())
+            .king_and_rook_to()
+            .0
     }
 
     #[inline]
@@ -1354,8 +1436,27 @@ impl Move {
         score
     }
 
+    /// standard notation, as a uci server writes unless UCI_Chess960 is set
     pub fn to_uci(&self) -> String {
-        self.to_inner().to_string()
+        self.to_uci_with(false)
+    }
+
+    /// castles are written king-takes-rook with `chess960`, otherwise (where the king starts on the
+    /// e-file and the rook on the a or h file) as the king's two-square step
+    pub fn to_uci_with(&self, chess960: bool) -> String {
+        match self.is_standard_castle() && !chess960 {
+            true => format!("{}{}", self.from().uci(), self.castling_king_to().uci()),
+            false => self.to_inner().to_string(),
+        }
+    }
+
+    /// matches the uci notation, with castles matching either notation
+    pub fn matches_uci(&self, s: &str) -> bool {
+        self.to_uci_with(false) == s || self.flag().is_castling() && self.to_uci_with(true) == s
+    }
+
+    fn is_standard_castle(&self) -> bool {
+        self.flag().is_castling() && self.from().file_index() == 4 && matches!(self.to().file_index(), 0 | 7)
     }
 }
 
@@ -1507,6 +1608,34 @@ mod tests {
         )
         .unwrap();
         assert_eq!(Move::parse_uci("a7a8q", &b)?.to_uci(), "a7a8q");
+
+        let mv = Move::parse_uci("a7a8q", &b)?;
+        assert_eq!(Move::parse_uci(&mv.to_string(), &b)?, mv);
+        assert_eq!(serde_json::from_str::<Move>(&serde_json::to_string(&mv)?)?, mv);
+        Ok(())
+    }
+
+    #[test]
+    fn test_castle_uci() -> anyhow::Result<()> {
+        let b = Board::parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")?;
+        let mv = Move::parse_uci("e1g1", &b)?;
+        assert_eq!(Move::parse_uci("e1h1", &b)?, mv);
+        assert_eq!(mv.to(), Square::H1);
+        assert_eq!(mv.castling_king_to(), Square::G1);
+        assert_eq!(mv.to_uci_with(false), "e1g1");
+        assert_eq!(mv.to_uci_with(true), "e1h1");
+        assert_eq!(
+            BareMove::parse_uci("e1c1")?.validate(&b).unwrap(),
+            Move::parse_uci("e1a1", &b)?
+        );
+
+        // a chess960 castle is always king-takes-rook
+        let b = Board::parse_fen("1rk2r2/8/8/8/8/8/8/1RK2R2 w FBfb - 0 1")?;
+        let mv = Move::parse_uci("c1f1", &b)?;
+        assert_eq!(mv.to_uci_with(false), "c1f1");
+        assert_eq!(mv.castling_king_to(), Square::G1);
+        assert_eq!(mv.rook_move_from_to(), (Square::F1, Square::F1));
+        assert!(Move::parse_uci("c1g1", &b).is_err());
         Ok(())
     }
 
@@ -1712,8 +1841,9 @@ mod tests {
         let move_e2e4 = "e2e4".parse::<BareMove>().unwrap();
         assert_eq!(move_e2e4.to_string(), "e2e4");
 
-        let move_e7e8 = BareMove::from_str("e7e8p").unwrap();
-        assert_eq!(move_e7e8.to_string(), "e7e8p");
+        let move_e7e8 = BareMove::from_str("e7e8n").unwrap();
+        assert_eq!(move_e7e8.to_string(), "e7e8n");
+        assert!(BareMove::from_str("e7e8p").is_err());
 
         let board = Catalog::starting_board();
         assert_eq!(board.parse_san_move("Nc3").unwrap().to_string(), "b1c3");
@@ -1724,6 +1854,33 @@ mod tests {
         assert_eq!(board.parse_san_move("Pc2c4").unwrap().to_string(), "c2c4");
     }
 
+    #[test]
+    fn test_with_promotion() {
+        let bd = Board::parse_fen("4k1n1/7P/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let h8q = bd.parse_uci_move("h7h8q").unwrap();
+        let g8q = bd.parse_uci_move("h7g8q").unwrap();
+        assert_eq!(h8q.with_promotion(Piece::Knight).unwrap().to_uci(), "h7h8n");
+        assert_eq!(g8q.with_promotion(Piece::Rook).unwrap().to_uci(), "h7g8r");
+        assert_eq!(g8q.with_promotion(Piece::Rook).unwrap().is_capture(), true);
+        assert_eq!(
+            h8q.with_promotion(Piece::King),
+            Err(PromotionError::InvalidPiece(Piece::King))
+        );
+        assert_eq!(
+            h8q.with_promotion(Piece::Pawn),
+            Err(PromotionError::InvalidPiece(Piece::Pawn))
+        );
+        let ke2 = bd.parse_uci_move("e1e2").unwrap();
+        assert_eq!(ke2.with_promotion(Piece::Queen), Err(PromotionError::NotAPromotion));
+
+        let bm = BareMove::from_str("h7h8").unwrap();
+        assert_eq!(bm.with_promotion(Piece::Queen).unwrap().to_string(), "h7h8q");
+        assert_eq!(
+            bm.with_promotion(Piece::King),
+            Err(PromotionError::InvalidPiece(Piece::King))
+        );
+    }
+
     #[test]
     fn test_to_and_from_detail() {
         let positions = Catalog::win_at_chess();
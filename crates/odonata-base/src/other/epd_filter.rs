@@ -0,0 +1,232 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::other::tags::TagOps;
+use crate::Epd;
+
+/// Selects EPDs by tag expression, such as `id~'WAC' && acd>=10`
+///
+/// Terms are `tag`, `!tag` (presence/absence) or `tag op value` with op one of
+/// `~` `!~` (regex match), `==` `!=` `>=` `<=` `>` `<` (numeric if both sides are numbers).
+/// Terms are combined with `&&`, which binds tighter than `||`. There are no parentheses.
+/// Quoted values may contain `&&` and `||`.
+/// A missing tag only satisfies `!tag`, `!=` and `!~`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdFilter {
+    text:  String,
+    terms: Vec<Vec<Term>>, // or of ands
+}
+
+#[derive(Clone, Debug)]
+struct Term {
+    negated: bool,
+    tag:     String,
+    cmp:     Option<(Op, String)>,
+    regex:   Option<Regex>,
+}
+
+impl PartialEq for Term {
+    fn eq(&self, other: &Self) -> bool {
+        self.negated == other.negated && self.tag == other.tag && self.cmp == other.cmp
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Match,
+    NotMatch,
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+static REGEX_TERM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?x)
+    ^\s*
+    (!)?\s*                                 # negation grp(1)
+    ([A-Za-z_][A-Za-z0-9_]*)\s*             # tag grp(2)
+    (?:
+        (!~|==|!=|>=|<=|~|=|>|<)\s*         # op grp(3)
+        (?:'([^']*)'|"([^"]*)"|(\S+))       # value grp(4,5,6)
+    )?
+    \s*$
+    "#,
+    )
+    .unwrap()
+});
+
+impl fmt::Display for EpdFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl FromStr for EpdFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl EpdFilter {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut terms = vec![];
+        for conjunction in split_unquoted(s, "||") {
+            let ands = split_unquoted(conjunction, "&&")
+                .into_iter()
+                .map(Term::parse)
+                .collect::<Result<Vec<_>>>()
+                .map_err(|e| anyhow!("{e} in filter '{s}'"))?;
+            terms.push(ands);
+        }
+        Ok(Self {
+            text: s.trim().to_string(),
+            terms,
+        })
+    }
+
+    pub fn matches(&self, epd: &Epd) -> bool {
+        self.terms.iter().any(|ands| ands.iter().all(|t| t.matches(epd)))
+    }
+
+    pub fn select(&self, epds: Vec<Epd>) -> Vec<Epd> {
+        epds.into_iter().filter(|epd| self.matches(epd)).collect()
+    }
+}
+
+/// splits on `sep` except within single or double quoted values
+fn split_unquoted<'a>(s: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut parts = vec![];
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if i >= start && s[i..].starts_with(sep) => {
+                parts.push(&s[start..i]);
+                start = i + sep.len();
+            }
+            None => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+impl Term {
+    fn parse(s: &str) -> Result<Self> {
+        let Some(caps) = REGEX_TERM.captures(s) else {
+            bail!("unable to parse term '{s}'");
+        };
+        let negated = caps.get(1).is_some();
+        let tag = caps[2].to_string();
+        let op = match caps.get(3).map(|m| m.as_str()) {
+            None => None,
+            Some("~") => Some(Op::Match),
+            Some("!~") => Some(Op::NotMatch),
+            Some("==" | "=") => Some(Op::Eq),
+            Some("!=") => Some(Op::Ne),
+            Some(">=") => Some(Op::Ge),
+            Some("<=") => Some(Op::Le),
+            Some(">") => Some(Op::Gt),
+            Some("<") => Some(Op::Lt),
+            Some(op) => bail!("unknown operator '{op}' in term '{s}'"),
+        };
+        if negated && op.is_some() {
+            bail!("negation only applies to a tag on its own, not '{s}'");
+        }
+        let value = (4..=6)
+            .find_map(|i| caps.get(i))
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        let regex = match op {
+            Some(Op::Match | Op::NotMatch) => Some(Regex::new(&value)?),
+            _ => None,
+        };
+        Ok(Term {
+            negated,
+            tag,
+            cmp: op.map(|op| (op, value)),
+            regex,
+        })
+    }
+
+    fn matches(&self, epd: &Epd) -> bool {
+        let Some(actual) = epd.get(&self.tag) else {
+            // a missing tag is "not equal" to anything
+            return self.negated || matches!(self.cmp, Some((Op::Ne | Op::NotMatch, _)));
+        };
+        let Some((op, value)) = &self.cmp else {
+            return !self.negated;
+        };
+        let ord = match (actual.parse::<f64>(), value.parse::<f64>()) {
+            (Ok(a), Ok(v)) => a.partial_cmp(&v),
+            _ => Some(actual.cmp(value.as_str())),
+        };
+        use std::cmp::Ordering::*;
+        match op {
+            Op::Match => self.regex.as_ref().is_some_and(|re| re.is_match(actual)),
+            Op::NotMatch => !self.regex.as_ref().is_some_and(|re| re.is_match(actual)),
+            Op::Eq => ord == Some(Equal),
+            Op::Ne => ord != Some(Equal),
+            Op::Ge => matches!(ord, Some(Greater | Equal)),
+            Op::Le => matches!(ord, Some(Less | Equal)),
+            Op::Gt => ord == Some(Greater),
+            Op::Lt => ord == Some(Less),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_epd_filter() {
+        let epds = Epd::parse_many_epd([
+            "8/8/8/8/8/8/8/K6k w - - 0 1 id 'WAC.001'; acd 12;",
+            "8/8/8/8/8/8/8/K6k w - - 0 1 id 'WAC.002'; acd 8;",
+            "8/8/8/8/8/8/8/K6k w - - 0 1 id 'BK.01'; acd 10.0; c0 'endgame';",
+            "8/8/8/8/8/8/8/K6k w - - 0 1 id 'BK.02'; c1 'a || b && c';",
+        ])
+        .unwrap();
+        let ids = |f: &str| {
+            let f = EpdFilter::parse(f).unwrap();
+            epds.iter()
+                .filter(|e| f.matches(e))
+                .map(|e| e["id"].to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("id~'WAC' && acd>=10"), ["WAC.001"]);
+        assert_eq!(ids("id ~ ^BK"), ["BK.01", "BK.02"]);
+        assert_eq!(ids("acd>=10"), ["WAC.001", "BK.01"]);
+        assert_eq!(ids("acd == 10"), ["BK.01"]);
+        assert_eq!(ids("acd<10 || c0"), ["WAC.002", "BK.01"]);
+        assert_eq!(ids("!acd"), ["BK.02"]);
+        assert_eq!(ids("id !~ \"\\.00\""), ["BK.01", "BK.02"]);
+        assert_eq!(ids("id = 'BK.02'"), ["BK.02"]);
+        assert_eq!(ids("id != 'BK.02' && c0 != endgame"), ["WAC.001", "WAC.002"]);
+        assert_eq!(ids("c1 == 'a || b && c'"), ["BK.02"]);
+        assert_eq!(ids("c1 ~ \"&& c\" || id == 'WAC.002'"), ["WAC.002", "BK.02"]);
+        assert_eq!(EpdFilter::parse("c1 ~ 'a||b' && acd").unwrap().terms[0].len(), 2);
+
+        assert!(EpdFilter::parse("id ~").is_err());
+        assert!(EpdFilter::parse("!id == 3").is_err());
+        assert!(EpdFilter::parse("id ~ '('").is_err());
+        assert!(EpdFilter::parse("acd >= 1 &&").is_err());
+        assert_eq!(EpdFilter::parse(" acd>1 ").unwrap().to_string(), "acd>1");
+    }
+}
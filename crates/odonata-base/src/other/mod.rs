@@ -1,10 +1,14 @@
+pub mod epd_filter;
 pub mod outcome;
 pub mod parse;
 pub mod perft;
 pub mod phaser;
+pub mod shard;
 pub mod tags;
 
+pub use epd_filter::EpdFilter;
 pub use parse::Parse;
-pub use perft::Perft;
+pub use perft::{Perft, PerftPosition, PerftSuite, PerftSuiteResults, PerftTable};
 pub use phaser::{Phase, Phaser};
+pub use shard::Shard;
 pub use tags::Tags;
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::str::FromStr;
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
@@ -31,6 +32,7 @@ pub enum Outcome {
 }
 
 /// formatting with sign_minus, eg {outcome:-}, shows loss rather than win
+/// alternate format, eg {outcome:#}, prefixes the pgn result "1-0 win by checkmate" (and parses)
 impl fmt::Display for Outcome {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = match (self, f.sign_minus()) {
@@ -61,12 +63,59 @@ impl fmt::Display for Outcome {
             (Self::DrawOther, _) => "draw (other)",
             (Self::Abandoned, _) => "abandoned",
         };
+        if f.alternate() {
+            write!(f, "{} ", self.as_pgn())?;
+        }
         f.write_str(s)?;
         Ok(())
     }
 }
 
+/// parses the alternate display format "0-1 win on time", or just a pgn result "0-1"
+impl FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (pgn, text) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+        let outcome = Self::try_from_pgn(pgn)?;
+        if text.is_empty() {
+            return Ok(outcome);
+        }
+        Self::all()
+            .find(|o| o.as_pgn() == pgn && o.to_string() == text)
+            .ok_or_else(|| anyhow!("Unknown outcome '{s}'"))
+    }
+}
+
 impl Outcome {
+    pub fn all() -> impl Iterator<Item = Outcome> {
+        use Outcome::*;
+        let wins = Color::ALL.into_iter().flat_map(|c| {
+            [
+                WinOther(c),
+                WinByCheckmate(c),
+                WinOnTime(c),
+                WinByAdjudication(c),
+                WinByResignation(c),
+                WinByRulesInfraction(c),
+            ]
+        });
+        let others = [
+            Unterminated,
+            DrawByAdjudication,
+            DrawByAgreement,
+            DrawStalemate,
+            DrawRepetition3,
+            DrawRepetition5,
+            DrawInsufficientMaterial,
+            DrawRule50,
+            DrawRule75,
+            DrawOther,
+            Abandoned,
+        ];
+        wins.chain(others)
+    }
+
     /// from Color's point of view
     pub fn to_string_pov(&self, c: Color) -> String {
         match (self, self.winning_color().map(|wc| wc == c)) {
@@ -217,4 +266,19 @@ mod tests {
             WinByCheckmate(Color::White)
         );
     }
+
+    #[test]
+    fn test_outcome_fmt() {
+        use Outcome::*;
+        assert_eq!(Outcome::all().count(), 23);
+        assert_eq!(format!("{:#}", WinOnTime(Color::Black)), "0-1 win on time");
+        assert_eq!(format!("{:#}", DrawRule50), "1/2-1/2 draw rule 50");
+        assert_eq!("1-0".parse::<Outcome>().unwrap(), WinOther(Color::White));
+        assert!("1-0 draw rule 50".parse::<Outcome>().is_err());
+        for o in Outcome::all() {
+            assert_eq!(format!("{o:#}").parse::<Outcome>().unwrap(), o, "{o:#}");
+            let json = serde_json::to_string(&o).unwrap();
+            assert_eq!(serde_json::from_str::<Outcome>(&json).unwrap(), o, "{json}");
+        }
+    }
 }
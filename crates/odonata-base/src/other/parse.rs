@@ -3,11 +3,11 @@ use regex::Regex;
 
 use crate::infra::utils::StringUtils;
 use crate::movelist::MoveList;
-use crate::mv::Move;
-use crate::piece::Color;
+use crate::mv::{AutoQueen, Move, PromotionError};
+use crate::piece::{Color, Piece};
 use crate::prelude::Board;
 pub struct Parse;
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
 // regex from https://stackoverflow.com/questions/40007937/regex-help-for-chess-moves-san
 // /^([NBRQK])?([a-h])?([1-8])?(x)?([a-h][1-8])(=[NBRQK])?(\+|#)?$|^O-O(-O)?$/
@@ -29,14 +29,14 @@ static REGEX_SAN: Lazy<Regex> = Lazy::new(|| {
     ([1-8])?        # src square rank grp(3)
     (\-|x)?         # move or capture grp(4)
     ([a-h][1-8])?   # square - both rank and file grp(5)
-    (=[NBRQ])?      # promo grp(6) 
+    (=[NBRQKP])?    # promo grp(6) (king and pawn matched to give a better error)
     (\+|\#)?        # check or checkmate grp(7)
     \z
     |               # OR
     ^O-O(-O)?(?:[\+\#]?) # castling can cause check(mate)s too! not captured
     \z     #   or castling king (or queens) side and eol
     |
-    ^([a-h][1-8][a-h][1-8][nbrq])\z  # uci promo grp(9)
+    ^([a-h][1-8][a-h][1-8][nbrqkp])\z  # uci promo grp(9)
     ",
     )
     .unwrap()
@@ -44,6 +44,10 @@ static REGEX_SAN: Lazy<Regex> = Lazy::new(|| {
 
 impl Parse {
     pub fn move_san(s: &str, board: &Board) -> Result<Move> {
+        Self::move_san_with(s, board, AutoQueen::Strict)
+    }
+
+    pub fn move_san_with(s: &str, board: &Board, auto_queen: AutoQueen) -> Result<Move> {
         let orig = s; // save original string
                       //  convert 0's to O's
                       //  Wikipedia:
@@ -89,7 +93,13 @@ impl Parse {
         let promo = caps.get(6).map_or("", |m| m.as_str());
         let _checks = caps.get(7).map_or("", |m| m.as_str());
         let _q_side_castle = caps.get(8).map_or("", |m| m.as_str());
-        let _uci_promo = caps.get(9).map_or("", |m| m.as_str());
+        let uci_promo = caps.get(9).map_or("", |m| m.as_str());
+        if let Some(ch) = promo.chars().nth(1).or(uci_promo.chars().nth(4)) {
+            let p = Piece::from_char(ch)?;
+            if p == Piece::Pawn || p == Piece::King {
+                return Err(PromotionError::InvalidPiece(p)).with_context(|| format!("Move {orig} for board {board}"));
+            }
+        }
         // println!("Parsed p={} f={} r={} to={}", piece, src_file, src_rank, dst_square);
 
         // if one square is given, its the destination not the source
@@ -109,7 +119,7 @@ impl Parse {
         let mut matching_moves = MoveList::new();
         for lm in legal_moves.iter() {
             // allow UCI moves as well as SAN (we test on length to try and prevent a uci format)
-            if (s.len() == 4 || s.len() == 5 && lm.is_promo()) && lm.to_uci() == s {
+            if (s.len() == 4 || s.len() == 5 && lm.is_promo()) && lm.matches_uci(s) {
                 matching_moves.clear();
                 matching_moves.push(*lm);
                 break;
@@ -152,6 +162,19 @@ impl Parse {
         if matching_moves.is_empty() {
             bail!("Move {orig} is invalid - not a legal move for board {board}",);
         }
+        // "e8" matches every promotion
+        let from = matching_moves.first().map(|mv| mv.from());
+        if matching_moves.len() > 1
+            && promo.is_empty()
+            && matching_moves.iter().all(|mv| mv.is_promo() && Some(mv.from()) == from)
+        {
+            return match auto_queen {
+                AutoQueen::Queen => Ok(matching_moves[0].with_promotion(Piece::Queen)?),
+                AutoQueen::Strict => {
+                    Err(PromotionError::MissingPiece).with_context(|| format!("Move {orig} for board {board}"))
+                }
+            };
+        }
         if matching_moves.len() > 1 {
             bail!(
                 "Move {} is ambiguous - moves {} match. For board {}",
@@ -220,6 +243,33 @@ mod tests {
         do_test_and_make_move(&bd, "a1b1", "a1b1");
     }
 
+    #[test]
+    fn test_parse_promotions() {
+        let bd = Board::parse_fen("4k1n1/1p1p3P/8/8/pPp1p3/3P1P2/P1P1P1P1/R3K3 w Q - 0 1").unwrap();
+        let is_promo_err = |res: Result<Move>, e: PromotionError| {
+            let err = res.unwrap_err();
+            assert_eq!(err.downcast_ref::<PromotionError>(), Some(&e), "{err:#}");
+        };
+        is_promo_err(bd.parse_san_move("h8=K"), PromotionError::InvalidPiece(Piece::King));
+        is_promo_err(bd.parse_san_move("h7h8p"), PromotionError::InvalidPiece(Piece::Pawn));
+        is_promo_err(bd.parse_uci_move("h7h8k"), PromotionError::InvalidPiece(Piece::King));
+        is_promo_err(bd.parse_san_move("h8"), PromotionError::MissingPiece);
+        is_promo_err(bd.parse_uci_move("h7h8"), PromotionError::MissingPiece);
+
+        let queen = bd.parse_uci_move("h7h8q").unwrap();
+        assert_eq!(bd.parse_san_move_with("h8", AutoQueen::Queen).unwrap(), queen);
+        assert_eq!(bd.parse_uci_move_with("h7h8", AutoQueen::Queen).unwrap(), queen);
+        assert_eq!(
+            bd.parse_uci_move_with("h7g8", AutoQueen::Queen).unwrap(),
+            bd.parse_san_move("hxg8=Q").unwrap()
+        );
+        // a piece given is never overridden
+        assert_eq!(
+            bd.parse_san_move_with("h8=N", AutoQueen::Queen).unwrap().to_uci(),
+            "h7h8n"
+        );
+    }
+
     fn do_test_and_make_move(bd: &Board, san: &str, uci: &str) -> Board {
         let mv = Parse::move_san(san, bd);
         assert!(
@@ -1,7 +1,53 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
 use crate::boards::board::Var;
 use crate::boards::{Board, Position};
+use crate::infra::utils::Formatting;
 use crate::mv::Move;
 
+#[derive(Copy, Clone, Debug, Default)]
+struct PerftEntry {
+    hash:  u64,
+    depth: u32,
+    count: u64,
+}
+
+/// Subtree counts by position hash and depth, so that transpositions (common from depth
+/// 4) are counted once. Always replaces, and the full hash is compared on probing.
+#[derive(Clone, Debug, Default)]
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    /// zero mb for no table
+    pub fn new(mb: usize) -> Self {
+        let len = mb * 1_000_000 / std::mem::size_of::<PerftEntry>();
+        Self {
+            entries: vec![PerftEntry::default(); len],
+        }
+    }
+
+    fn probe(&self, hash: u64, depth: u32) -> Option<u64> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let e = &self.entries[(hash % self.entries.len() as u64) as usize];
+        (e.hash == hash && e.depth == depth).then_some(e.count)
+    }
+
+    fn store(&mut self, hash: u64, depth: u32, count: u64) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let len = self.entries.len() as u64;
+        self.entries[(hash % len) as usize] = PerftEntry { hash, depth, count };
+    }
+}
+
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Perft<const BULK_COUNT: bool = true> {
     pub captures:         u64,
@@ -28,6 +74,24 @@ impl<const BULK_COUNT: bool> Perft<BULK_COUNT> {
         }
     }
 
+    /// as `perft_with` but generating pseudo-legal moves, each checked as it is made
+    pub fn perft_pseudo_legal_with(board: &mut Board, depth: u32, f: &mut impl FnMut(&Board, Move)) {
+        if depth == 0 {
+            f(board, Move::new_null());
+            return;
+        }
+        board.pseudo_legal_moves_with(|mv| {
+            if !board.is_legal(mv) {
+                return;
+            }
+            if BULK_COUNT && depth == 1 {
+                f(board, mv)
+            } else {
+                Self::perft_pseudo_legal_with(&mut board.make_move(mv), depth - 1, f)
+            }
+        });
+    }
+
     fn perft_with_var(var: &mut Var, depth: u32, f: &mut impl FnMut(&Board, Move)) {
         let board = var.board();
         if depth == 0 {
@@ -63,6 +127,24 @@ impl<const BULK_COUNT: bool> Perft<BULK_COUNT> {
         }
     }
 
+    /// as `perft_with_position` but making and undoing moves in place
+    fn perft_with_undo(pos: &mut Position, depth: u32, f: &mut impl FnMut(&Position, Move)) {
+        if depth == 0 {
+            f(pos, Move::new_null());
+            return;
+        }
+        if BULK_COUNT && depth == 1 {
+            pos.board().legal_moves_with(|mv| f(pos, mv))
+        } else {
+            let moves = pos.board().legal_moves();
+            for &m in moves.iter() {
+                pos.make_move(m);
+                Self::perft_with_undo(pos, depth - 1, f);
+                pos.undo_move();
+            }
+        }
+    }
+
     pub fn count(board: &Board, depth: u32) -> u64 {
         Self::count_using_position(board, depth)
     }
@@ -80,6 +162,41 @@ impl<const BULK_COUNT: bool> Perft<BULK_COUNT> {
         count.max(1) // we count perft(0) as 1
     }
 
+    /// as `count`, but with subtree counts looked up and stored in `table`
+    pub fn count_hashed(board: &Board, depth: u32, table: &mut PerftTable) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if BULK_COUNT && depth == 1 {
+            let mut count = 0;
+            board.legal_moves_with(|_mv| count += 1);
+            return count;
+        }
+        if let Some(count) = table.probe(board.hash(), depth) {
+            return count;
+        }
+        let mut count = 0;
+        board.legal_moves_with(|mv| count += Self::count_hashed(&board.make_move(mv), depth - 1, table));
+        table.store(board.hash(), depth, count);
+        count
+    }
+
+    /// the count below each root move, so that a wrong total can be traced to a move
+    pub fn divide(board: &Board, depth: u32, table: &mut PerftTable) -> Vec<(Move, u64)> {
+        let mut counts = vec![];
+        board.legal_moves_with(|mv| {
+            let count = Self::count_hashed(&board.make_move(mv), depth.saturating_sub(1), table);
+            counts.push((mv, count));
+        });
+        counts
+    }
+
+    pub fn count_pseudo_legal(board: &Board, depth: u32) -> u64 {
+        let mut count = 0;
+        Self::perft_pseudo_legal_with(&mut board.clone(), depth, &mut |_b, _mv| count += 1);
+        count.max(1) // we count perft(0) as 1
+    }
+
     fn count_using_position(board: &Board, depth: u32) -> u64 {
         let mut count = 0;
         Self::perft_with_position(&mut Position::from_board(board.clone()), depth, &mut |_b, _mv| {
@@ -88,6 +205,14 @@ impl<const BULK_COUNT: bool> Perft<BULK_COUNT> {
         count.max(1) // we count perft(0) as 1
     }
 
+    pub fn count_using_undo(board: &Board, depth: u32) -> u64 {
+        let mut count = 0;
+        Self::perft_with_undo(&mut Position::from_board(board.clone()), depth, &mut |_b, _mv| {
+            count += 1
+        });
+        count.max(1) // we count perft(0) as 1
+    }
+
     pub fn perft_cat(&mut self, board: &mut Board, depth: u32) -> u64 {
         let mut count = 0;
         const BULK_COUNT: bool = true;
@@ -110,6 +235,136 @@ impl<const BULK_COUNT: bool> Perft<BULK_COUNT> {
     }
 }
 
+/// a position of a perft suite, with its expected counts by depth
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftPosition {
+    pub board:    Board,
+    pub expected: Vec<(u32, u64)>, // depth and count
+}
+
+impl PerftPosition {
+    /// a line of a perft suite: the fen followed by counts as ";D1 20 ;D2 400" (the
+    /// standard format) or as epd operations "D1 20; D2 400;". Other operations are ignored
+    pub fn parse(line: &str) -> Result<Self> {
+        let words = line
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>();
+        let depth = |i: usize| -> Option<(u32, u64)> {
+            let depth = words.get(i)?.strip_prefix('D')?.parse().ok()?;
+            Some((depth, words.get(i + 1)?.parse().ok()?))
+        };
+        let fen_len = (0..words.len())
+            .find(|&i| depth(i).is_some())
+            .with_context(|| format!("no D1..D6 counts in perft suite line '{line}'"))?;
+        let mut fen = words[..fen_len].join(" ");
+        if fen_len == 4 {
+            fen += " 0 1";
+        }
+        Ok(Self {
+            board:    Board::parse_fen(&fen).with_context(|| format!("in perft suite line '{line}'"))?,
+            expected: (fen_len..words.len()).filter_map(depth).collect(),
+        })
+    }
+
+    /// skipping blank lines and # comments
+    pub fn parse_many(text: &str) -> Result<Vec<Self>> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse)
+            .collect()
+    }
+}
+
+/// a count differing from that expected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PerftMismatch {
+    pub fen:      String,
+    pub depth:    u32,
+    pub expected: u64,
+    pub count:    u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PerftSuiteResults {
+    pub positions:  usize,
+    pub counts:     usize, // compared
+    pub nodes:      u64,
+    pub elapsed:    Duration,
+    pub mismatches: Vec<PerftMismatch>,
+}
+
+impl fmt::Display for PerftSuiteResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for m in &self.mismatches {
+            writeln!(
+                f,
+                "mismatch      : perft({}) = {} expected {} for {}",
+                m.depth, m.count, m.expected, m.fen
+            )?;
+        }
+        writeln!(f, "positions     : {}", self.positions)?;
+        writeln!(f, "counts        : {}", self.counts)?;
+        writeln!(f, "mismatches    : {}", self.mismatches.len())?;
+        writeln!(f, "nodes         : {}", Formatting::u64(self.nodes))?;
+        writeln!(f, "elapsed       : {}", Formatting::duration(self.elapsed))?;
+        writeln!(
+            f,
+            "nodes/sec     : {}",
+            Formatting::f64(self.nodes as f64 / self.elapsed.as_secs_f64().max(1e-9))
+        )
+    }
+}
+
+/// Runs a perft suite, comparing counts to depth `max_depth` with those expected
+#[derive(Clone, Debug)]
+pub struct PerftSuite {
+    pub max_depth: u32,
+    pub hash_mb:   usize,
+}
+
+impl Default for PerftSuite {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            hash_mb:   0,
+        }
+    }
+}
+
+impl PerftSuite {
+    /// `f` is called with each position, depth, expected count and count
+    pub fn run(
+        &self,
+        positions: &[PerftPosition],
+        f: &mut impl FnMut(&PerftPosition, u32, u64, u64),
+    ) -> PerftSuiteResults {
+        let mut table = PerftTable::new(self.hash_mb);
+        let mut results = PerftSuiteResults::default();
+        let t = Instant::now();
+        for pos in positions {
+            results.positions += 1;
+            for &(depth, expected) in pos.expected.iter().filter(|(d, _)| *d <= self.max_depth) {
+                let count = Perft::<true>::count_hashed(&pos.board, depth, &mut table);
+                f(pos, depth, expected, count);
+                results.counts += 1;
+                results.nodes += count;
+                if count != expected {
+                    results.mismatches.push(PerftMismatch {
+                        fen: pos.board.to_fen(),
+                        depth,
+                        expected,
+                        count,
+                    });
+                }
+            }
+        }
+        results.elapsed = t.elapsed();
+        results
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::hint::black_box;
@@ -143,6 +398,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_perft_chess960() {
+        let positions = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [
+                1, 21, 528, 12189,
+            ]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [
+                1, 21, 807, 18002,
+            ]),
+        ];
+        for (fen, perfts) in positions {
+            let board = Board::parse_fen(fen).unwrap();
+            for (depth, &expected) in perfts.iter().enumerate() {
+                let count = Perft::<true>::count(&board, depth as u32);
+                assert_eq!(count, expected, "fen {} perft({})", fen, depth);
+            }
+        }
+        // scharnagl 518 is the standard starting position
+        let board = Catalog::chess960(518).board();
+        assert_eq!(Perft::<true>::count(&board, 3), 8902);
+    }
+
     #[test]
     fn test_perft_1() {
         let (board, perfts) = &Catalog::perfts()[1];
@@ -157,6 +434,10 @@ mod tests {
                     let now = Instant::now();
                     let count = Perft::<true>::count(&board, depth as u32);
                     assert_eq!(count, expected, "fen {} perft({})", board.to_fen(), depth);
+                    let count = Perft::<true>::count_using_undo(&board, depth as u32);
+                    assert_eq!(count, expected, "undo: fen {} perft({})", board.to_fen(), depth);
+                    let count = Perft::<true>::count_pseudo_legal(&board, depth as u32);
+                    assert_eq!(count, expected, "pseudo: fen {} perft({})", board.to_fen(), depth);
                     println!(
                         "perft({depth})={count} in {time} millis",
                         depth = depth,
@@ -169,6 +450,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_perft_suite() {
+        let pos = PerftPosition::parse("4k3/8/8/8/8/8/8/4K2R w K - 0 1 ;D1 15 ;D2 66 ;D3 1197").unwrap();
+        assert_eq!(pos.board.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert_eq!(pos.expected, vec![(1, 15), (2, 66), (3, 1197)]);
+        let epd = PerftPosition::parse("4k3/8/8/8/8/8/8/4K2R w K - D1 15; D2 66; id \"x\";").unwrap();
+        assert_eq!(epd.board, pos.board);
+        assert_eq!(epd.expected, vec![(1, 15), (2, 66)]);
+        assert!(PerftPosition::parse("4k3/8/8/8/8/8/8/4K2R w K - 0 1").is_err());
+
+        let suite = Catalog::perft_suite();
+        assert!(suite.len() >= 9);
+        let mut lines = 0;
+        let ps = PerftSuite {
+            max_depth: 3,
+            hash_mb:   1,
+        };
+        let results = ps.run(&suite, &mut |_pos, _depth, _expected, _count| lines += 1);
+        println!("{results}");
+        assert_eq!(results.positions, suite.len());
+        assert_eq!(results.counts, 3 * suite.len());
+        assert_eq!(lines, results.counts);
+        assert!(results.mismatches.is_empty());
+
+        // a wrong count is reported rather than failing
+        let wrong = PerftPosition::parse("4k3/8/8/8/8/8/8/4K2R w K - 0 1 ;D1 15 ;D2 67").unwrap();
+        let results = ps.run(&[wrong], &mut |_, _, _, _| {});
+        assert_eq!(results.mismatches.len(), 1);
+        assert_eq!(results.mismatches[0].count, 66);
+    }
+
+    #[test]
+    fn test_perft_hashed_and_divide() {
+        let mut table = PerftTable::new(1);
+        for (board, perfts) in Catalog::perfts() {
+            for (depth, &expected) in perfts.iter().enumerate().take(5) {
+                let count = Perft::<true>::count_hashed(&board, depth as u32, &mut table);
+                assert_eq!(count, expected, "hashed: fen {} perft({})", board.to_fen(), depth);
+                let count = Perft::<true>::count_hashed(&board, depth as u32, &mut PerftTable::new(0));
+                assert_eq!(count, expected, "no table: fen {} perft({})", board.to_fen(), depth);
+            }
+            let divide = Perft::<true>::divide(&board, 3, &mut table);
+            assert_eq!(divide.len(), board.legal_moves().len());
+            assert_eq!(divide.iter().map(|(_mv, count)| count).sum::<u64>(), perfts[3]);
+            for (mv, count) in divide {
+                assert_eq!(count, Perft::<true>::count(&board.make_move(mv), 2), "{mv}");
+            }
+        }
+    }
+
     #[test]
     fn bench_perft() {
         let mut p1 = PerfProfiler::new("perft var");
@@ -176,6 +507,7 @@ mod tests {
         let mut p3 = PerfProfiler::new("perft position");
         let mut p4 = PerfProfiler::new("perft without bulk count (raw b)");
         let mut p5 = PerfProfiler::new("perft without bulk count (pos)");
+        let mut p6 = PerfProfiler::new("perft position make/undo");
         let board = Board::starting_pos();
         let depth = 5;
         let c1 = p1.bench(|| Perft::<true>::count_using_var(black_box(&board), depth));
@@ -183,13 +515,15 @@ mod tests {
         let c3 = p3.bench(|| Perft::<true>::count_using_position(black_box(&board), depth));
         let c4 = p4.bench(|| Perft::<false>::count_using_board(black_box(&board), depth));
         let c5 = p5.bench(|| Perft::<false>::count_using_position(black_box(&board), depth));
+        let c6 = p6.bench(|| Perft::<true>::count_using_undo(black_box(&board), depth));
         assert_eq!(c1, c2);
         assert_eq!(c1, c3);
         assert_eq!(c1, c4);
         assert_eq!(c1, c5);
+        assert_eq!(c1, c6);
         info!("perft({depth})={c1}");
         let iters = Perft::<true>::count(&board, depth - 1);
-        for p in &mut [p1, p2, p3, p4, p5] {
+        for p in &mut [p1, p2, p3, p4, p5, p6] {
             p.set_iters(iters);
         }
     }
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+/// A deterministic slice `i/N` (1 <= i <= N) of a suite, so that independent machines can
+/// each process a disjoint part of the same suite, with the results merged afterwards.
+///
+/// Items are dealt round-robin by their position in the suite, so every shard gets a
+/// similar mix of easy and hard positions. `1/1` (the default) is the whole suite.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self { index: 1, count: 1 }
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (i, n) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("shard '{s}' should be of the form i/N"))?;
+        let shard = Shard {
+            index: i.trim().parse()?,
+            count: n.trim().parse()?,
+        };
+        if shard.index == 0 || shard.index > shard.count {
+            bail!("shard '{s}' must have 1 <= i <= N");
+        }
+        Ok(shard)
+    }
+}
+
+impl Shard {
+    pub fn is_whole(&self) -> bool {
+        self.count == 1
+    }
+
+    /// whether the item at (0-based) position `i` of the suite belongs to this shard
+    pub fn contains(&self, i: usize) -> bool {
+        i % self.count == self.index - 1
+    }
+
+    pub fn select<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.contains(*i))
+            .map(|(_, t)| t)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_shard() {
+        let shard: Shard = "2/3".parse().unwrap();
+        assert_eq!(shard, Shard { index: 2, count: 3 });
+        assert_eq!(shard.to_string(), "2/3");
+        assert_eq!(shard.select((0..8).collect()), vec![1, 4, 7]);
+        assert!(Shard::default().is_whole());
+        assert!("0/3".parse::<Shard>().is_err());
+        assert!("4/3".parse::<Shard>().is_err());
+        assert!("3".parse::<Shard>().is_err());
+
+        // the shards partition the suite
+        let mut all = (1..=3)
+            .flat_map(|i| Shard { index: i, count: 3 }.select((0..10).collect()))
+            .collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -196,11 +196,13 @@ impl Tags {
     pub const HMVC: &'static str = "hmvc";
     pub const PM: &'static str = "pm";
     pub const RC: &'static str = "rc"; // repetition count
+    pub const RCS: &'static str = "Rcs"; // repetition count of the setup position (eg from game history prior to a fen)
     pub const NOOP: &'static str = "noop";
     pub const SM: &'static str = "sm";
     pub const SV: &'static str = "sv";
     pub const GM: &'static str = "Gm"; // Game move
     pub const SQ: &'static str = "Sq";
+    pub const SRC: &'static str = "Src"; // source suite or file of the position
     pub const RES: &'static str = "Res";
     pub const TS: &'static str = "ts";
     pub const COMMENTS: [&'static str; 10] = ["c0", "c1", "c2", "c3", "c4", "c5", "c6", "c7", "c8", "c9"];
@@ -0,0 +1,456 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::boards::Position;
+use crate::epd::Epd;
+use crate::other::outcome::Outcome;
+use crate::prelude::*;
+
+// http://www.saremba.de/chessgml/standards/pgn/pgn-complete.htm
+
+/// A move of a pgn game, with its annotations and the alternatives to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    pub mv:             Move,
+    pub comment_before: Option<String>, // only where the move starts a line
+    pub nags:           Vec<u8>,        // "!" is $1, "?" is $2, ... "?!" is $6
+    pub comment:        Option<String>,
+    pub variations:     Vec<Vec<PgnMove>>, // each played instead of this move
+}
+
+impl PgnMove {
+    pub fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            comment_before: None,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// A full pgn game: tags, starting position, and the movetext as a tree of moves with
+/// comments, NAGs and (recursive) variations.
+///
+/// Parses and writes the export format, so a game read and written again differs only in
+/// layout. Move number indications and `%` escape lines are not kept, and `;` comments are
+/// written as brace comments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pgn {
+    pub tags:     Vec<(String, String)>, // in the order read
+    pub starting: Board,
+    pub moves:    Vec<PgnMove>,
+    pub result:   String, // "1-0", "0-1", "1/2-1/2" or "*"
+}
+
+impl Default for Pgn {
+    fn default() -> Self {
+        Self {
+            tags:     Vec::new(),
+            starting: Board::starting_pos(),
+            moves:    Vec::new(),
+            result:   "*".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    San(String),
+    Nag(u8),
+    Comment(String),
+    Open,
+    Close,
+    Result(String),
+}
+
+impl Pgn {
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, key: &str, value: &str) {
+        match self.tags.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /// the game played in a position (from its setup board), with the result if it is over
+    pub fn from_position(pos: &Position) -> Pgn {
+        let mut pgn = Pgn {
+            starting: pos.setup_board(),
+            moves: pos.played_moves().moves().map(PgnMove::new).collect(),
+            ..Pgn::default()
+        };
+        for key in ["Event", "Site", "Date", "Round", "White", "Black"] {
+            pgn.set_tag(key, "?");
+        }
+        if pos.outcome().is_game_over() {
+            pgn.result = pos.outcome().as_pgn();
+        }
+        pgn.set_tag("Result", &pgn.result.clone());
+        if pgn.starting != Board::starting_pos() {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", &pgn.starting.to_fen());
+        }
+        pgn
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        Outcome::try_from_pgn(&self.result).unwrap_or(Outcome::Unterminated)
+    }
+
+    pub fn main_line(&self) -> Variation {
+        self.moves.iter().map(|m| m.mv).collect()
+    }
+
+    /// the positions along the main line, from the starting position to the final one
+    pub fn positions(&self) -> Vec<Position> {
+        let main_line = self.main_line();
+        (0..=main_line.len())
+            .map(|ply| Position::from_played_moves(self.starting.clone(), main_line.take(ply)))
+            .collect()
+    }
+
+    /// the final position of the main line, with the moves played to reach it
+    pub fn to_epd(&self) -> Epd {
+        Epd::from_var(self.starting.clone(), self.main_line())
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Pgn>> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).with_context(|| format!("reading pgn {}", path.display()))?;
+        Self::parse_many(&s).with_context(|| format!("parsing pgn {}", path.display()))
+    }
+
+    /// games are separated by the tag pairs of the next game
+    pub fn parse_many(s: &str) -> Result<Vec<Pgn>> {
+        let mut games = vec![];
+        let mut text = String::new();
+        let mut in_movetext = false;
+        for line in s.lines() {
+            let is_tag = line.trim_start().starts_with('[');
+            if is_tag && in_movetext {
+                games.push(Self::parse(&text)?);
+                text.clear();
+                in_movetext = false;
+            }
+            if !is_tag && !line.trim().is_empty() {
+                in_movetext = true;
+            }
+            text += line;
+            text += "\n";
+        }
+        if !text.trim().is_empty() {
+            games.push(Self::parse(&text)?);
+        }
+        Ok(games)
+    }
+
+    pub fn parse(s: &str) -> Result<Pgn> {
+        let mut pgn = Pgn::default();
+        let mut movetext = String::new();
+        for line in s.lines() {
+            if line.starts_with('%') {
+                continue;
+            }
+            match line.trim().strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(tag) => {
+                    let (k, v) = tag
+                        .split_once(' ')
+                        .with_context(|| format!("invalid pgn tag '{line}'"))?;
+                    let v = v.trim();
+                    let v = v.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(v);
+                    let v = v.replace("\\\"", "\"").replace("\\\\", "\\");
+                    pgn.tags.push((k.to_string(), v));
+                }
+                None => {
+                    movetext += line;
+                    movetext += "\n";
+                }
+            }
+        }
+        if let Some(fen) = pgn.tag("FEN") {
+            pgn.starting = Board::parse_fen(fen).with_context(|| format!("pgn FEN tag '{fen}'"))?;
+        }
+        if let Some(result) = pgn.tag("Result") {
+            pgn.result = result.to_string();
+        }
+        let mut tokens = Self::tokenize(&movetext)?.into_iter();
+        pgn.moves = Self::parse_line(&mut tokens, &pgn.starting, &mut pgn.result, false)?;
+        Ok(pgn)
+    }
+
+    fn tokenize(movetext: &str) -> Result<Vec<Token>> {
+        let mut tokens = vec![];
+        let mut rest = movetext;
+        loop {
+            rest = rest.trim_start();
+            let Some(c) = rest.chars().next() else {
+                break;
+            };
+            let end = match c {
+                '{' => {
+                    let end = rest
+                        .find('}')
+                        .with_context(|| format!("unclosed comment in pgn movetext '{movetext}'"))?;
+                    tokens.push(Token::Comment(rest[1..end].trim().to_string()));
+                    end + 1
+                }
+                ';' => {
+                    let end = rest.find('\n').unwrap_or(rest.len());
+                    tokens.push(Token::Comment(rest[1..end].trim().to_string()));
+                    end
+                }
+                '(' => {
+                    tokens.push(Token::Open);
+                    1
+                }
+                ')' => {
+                    tokens.push(Token::Close);
+                    1
+                }
+                _ => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "{}();".contains(c))
+                        .unwrap_or(rest.len());
+                    Self::tokenize_word(&rest[..end], &mut tokens)?;
+                    end
+                }
+            };
+            rest = &rest[end..];
+        }
+        Ok(tokens)
+    }
+
+    /// a word of movetext: "1-0", "$6", "12.", "12...", "12.e4", "Nf3!?"
+    fn tokenize_word(word: &str, tokens: &mut Vec<Token>) -> Result<()> {
+        if let "1-0" | "0-1" | "1/2-1/2" | "*" = word {
+            tokens.push(Token::Result(word.to_string()));
+            return Ok(());
+        }
+        if let Some(nag) = word.strip_prefix('$') {
+            tokens.push(Token::Nag(
+                nag.parse().with_context(|| format!("invalid pgn nag '{word}'"))?,
+            ));
+            return Ok(());
+        }
+        let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let suffix = san.trim_start_matches(|c: char| c != '!' && c != '?');
+        let san = &san[..san.len() - suffix.len()];
+        if !san.is_empty() {
+            tokens.push(Token::San(san.to_string()));
+        }
+        match suffix {
+            "" => {}
+            "!" => tokens.push(Token::Nag(1)),
+            "?" => tokens.push(Token::Nag(2)),
+            "!!" => tokens.push(Token::Nag(3)),
+            "??" => tokens.push(Token::Nag(4)),
+            "!?" => tokens.push(Token::Nag(5)),
+            "?!" => tokens.push(Token::Nag(6)),
+            _ => bail!("invalid pgn move suffix in '{word}'"),
+        }
+        Ok(())
+    }
+
+    /// the moves of a line from board `b`, up to its closing ")" if it is a variation
+    fn parse_line(
+        tokens: &mut impl Iterator<Item = Token>,
+        b: &Board,
+        result: &mut String,
+        is_variation: bool,
+    ) -> Result<Vec<PgnMove>> {
+        let mut line: Vec<PgnMove> = vec![];
+        let mut comment_before = None;
+        let mut before = b.clone(); // the board before the last move, where its variations start
+        let mut b = b.clone();
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::San(san) => {
+                    let mv = b
+                        .parse_san_move(&san)
+                        .with_context(|| format!("parsing pgn move '{san}' in {}", b.to_fen()))?;
+                    line.push(PgnMove {
+                        comment_before: comment_before.take(),
+                        ..PgnMove::new(mv)
+                    });
+                    before = b.clone();
+                    b = b.make_move(mv);
+                }
+                Token::Nag(nag) => line.last_mut().context("pgn nag before any move")?.nags.push(nag),
+                Token::Comment(c) => {
+                    let comment = match line.last_mut() {
+                        Some(m) => &mut m.comment,
+                        None => &mut comment_before,
+                    };
+                    *comment = Some(match comment.take() {
+                        Some(earlier) => earlier + " " + &c,
+                        None => c,
+                    });
+                }
+                Token::Open => {
+                    let var = Self::parse_line(tokens, &before, result, true)?;
+                    let m = line.last_mut().context("pgn variation before any move")?;
+                    if !var.is_empty() {
+                        m.variations.push(var);
+                    }
+                }
+                Token::Close if is_variation => return Ok(line),
+                Token::Close => bail!("unbalanced ')' in pgn movetext"),
+                Token::Result(r) => *result = r,
+            }
+        }
+        if is_variation {
+            bail!("unclosed variation in pgn movetext");
+        }
+        Ok(line)
+    }
+
+    /// the movetext of a line from board `b` as tokens ("1. e4", "$1", "{comment}", ...)
+    fn write_line(b: &Board, line: &[PgnMove], tokens: &mut Vec<String>) {
+        let mut b = b.clone();
+        let mut numbered = true;
+        for m in line {
+            if let Some(c) = &m.comment_before {
+                tokens.push(format!("{{{c}}}"));
+                numbered = true;
+            }
+            let san = b.to_san(m.mv);
+            tokens.push(match (b.color_us(), numbered) {
+                (Color::White, _) => format!("{}. {san}", b.fullmove_number()),
+                (Color::Black, true) => format!("{}... {san}", b.fullmove_number()),
+                (Color::Black, false) => san,
+            });
+            numbered = false;
+            for nag in &m.nags {
+                tokens.push(format!("${nag}"));
+            }
+            if let Some(c) = &m.comment {
+                tokens.push(format!("{{{c}}}"));
+                numbered = true;
+            }
+            for var in &m.variations {
+                let start = tokens.len();
+                Self::write_line(&b, var, tokens);
+                if tokens.len() > start {
+                    tokens[start].insert(0, '(');
+                    tokens.last_mut().unwrap().push(')');
+                }
+                numbered = true;
+            }
+            b = b.make_move(m.mv);
+        }
+    }
+}
+
+/// the export format, with movetext lines of at most 80 characters
+impl fmt::Display for Pgn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (k, v) in &self.tags {
+            let v = v.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{k} \"{v}\"]")?;
+        }
+        writeln!(f)?;
+        let mut tokens = vec![];
+        Self::write_line(&self.starting, &self.moves, &mut tokens);
+        tokens.push(self.result.clone());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_pgn_round_trip() -> Result<()> {
+        let s = r#"
+[Event "Test"]
+[White "A \"the first\""]
+[Black "B"]
+[Result "0-1"]
+
+{Opening} 1. e4 e5 {a comment} 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 3. Bc4?! $13 Nd4
+; a rest of line comment
+4. Nxe5 Qg5 5. Nxf7 Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 0-1
+
+[Event "Test2"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40"]
+
+40... Kd7 41. e4 *
+"#;
+        let games = Pgn::parse_many(s)?;
+        assert_eq!(games.len(), 2);
+        let g = &games[0];
+        assert_eq!(g.tag("White"), Some("A \"the first\""));
+        assert_eq!(g.result, "0-1");
+        assert_eq!(g.outcome(), Outcome::WinOther(Color::Black));
+        assert_eq!(g.moves.len(), 14);
+        assert_eq!(g.moves[0].comment_before.as_deref(), Some("Opening"));
+        assert_eq!(g.moves[1].comment.as_deref(), Some("a comment"));
+        assert_eq!(g.moves[4].nags, vec![6, 13]);
+        assert_eq!(g.moves[5].comment.as_deref(), Some("a rest of line comment"));
+
+        // variations start from the board before the move they replace, and nest
+        let f4 = &g.moves[2].variations[0];
+        assert_eq!(Variation::from_iter(f4.iter().map(|m| m.mv)).to_uci(), "f2f4 e5f4 g1f3");
+        assert_eq!(f4[1].variations[0][0].mv.to_uci(), "d7d5");
+
+        let written = g.to_string();
+        println!("{written}");
+        assert!(written
+            .replace('\n', " ")
+            .contains("2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 3. Bc4 $6 $13"));
+        assert!(written.lines().all(|l| l.len() <= 80));
+        assert_eq!(&Pgn::parse(&written)?, g);
+
+        let g = &games[1];
+        assert_eq!(g.starting.color_us(), Color::Black);
+        assert_eq!(g.main_line().to_uci(), "e8d7 e2e4");
+        assert_eq!(Pgn::parse(&g.to_string())?, *g);
+        assert!(g.to_string().contains("40... Kd7 41. e4 *"));
+
+        assert!(Pgn::parse("1. e4 {unclosed").is_err());
+        assert!(Pgn::parse("1. e4 (1. d4").is_err());
+        assert!(Pgn::parse("1. e5").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pgn_positions() -> Result<()> {
+        let mut pos = Position::starting_pos();
+        pos.push_moves_str("f3 e5 g4 Qh4")?;
+        pos.play_search_moves();
+        let pgn = Pgn::from_position(&pos);
+        assert_eq!(pgn.result, "0-1");
+        assert_eq!(pgn.tag("Result"), Some("0-1"));
+        assert_eq!(pgn.tag("FEN"), None);
+
+        let positions = Pgn::parse(&pgn.to_string())?.positions();
+        assert_eq!(positions.len(), 5);
+        assert_eq!(positions[0].board(), &Board::starting_pos());
+        assert_eq!(positions[4].board(), pos.board());
+        assert_eq!(positions[4].played_moves(), pos.played_moves());
+        assert_eq!(pgn.to_epd().board(), *pos.board());
+        Ok(())
+    }
+}
@@ -5,18 +5,32 @@ use anyhow::Context;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use crate::boards::Position;
 use crate::domain::score::Score;
 use crate::movelist::ScoredMoveList;
 use crate::mv::Move;
 use crate::piece::Ply;
-use crate::prelude::Board;
+use crate::prelude::{Board, Color};
 
-#[derive(Clone, PartialEq, Hash, Eq, Serialize, Deserialize)]
+#[derive(PartialEq, Hash, Eq, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct Variation {
     moves: Vec<Move>,
 }
 
+impl Clone for Variation {
+    fn clone(&self) -> Self {
+        Self {
+            moves: self.moves.clone(),
+        }
+    }
+
+    /// reuses the existing allocation, so copying pvs during search does not allocate
+    fn clone_from(&mut self, source: &Self) {
+        self.moves.clone_from(&source.moves)
+    }
+}
+
 #[derive(Clone, Default, PartialEq, Debug, Eq, Serialize, Deserialize)]
 pub struct ScoredVariation {
     pub var:   Variation,
@@ -153,7 +167,7 @@ impl Variation {
 
     #[inline]
     pub fn validate(&self, bd: &Board) -> anyhow::Result<()> {
-        bd.validate_moves(&self.moves().collect_vec())
+        bd.validate_moves(&self.moves)
     }
 
     // truncate the variation to length ply
@@ -170,7 +184,15 @@ impl Variation {
     }
 
     pub fn to_uci(&self) -> String {
-        self.moves().map(|mv| mv.to_uci()).collect::<Vec<String>>().join(" ")
+        self.to_uci_with(false)
+    }
+
+    /// with castles written king-takes-rook when `chess960`
+    pub fn to_uci_with(&self, chess960: bool) -> String {
+        self.moves()
+            .map(|mv| mv.to_uci_with(chess960))
+            .collect::<Vec<String>>()
+            .join(" ")
     }
 
     pub fn parse_uci(s: &str, bd: &Board) -> anyhow::Result<Variation> {
@@ -205,6 +227,10 @@ impl Variation {
         self.len() >= var.len() && &self.take(var.len()) == var
     }
 
+    pub fn as_slice(&self) -> &[Move] {
+        &self.moves
+    }
+
     /// variation tail
     pub fn skip(&self, ply: usize) -> Variation {
         Variation {
@@ -250,22 +276,60 @@ impl Variation {
         s.join(" ")
     }
 
-    /// wont panic
+    /// the index of the first move that is not legal in sequence from board `b`
+    /// (null moves are permitted)
+    pub fn first_illegal_ply(&self, b: &Board) -> Option<usize> {
+        let mut b2 = b.clone();
+        for (i, mv) in self.moves().enumerate() {
+            if !mv.is_null() && !b2.legal_moves().contains(&mv) {
+                return Some(i);
+            }
+            b2 = b2.make_move(mv);
+        }
+        None
+    }
+
+    /// the search moves of `pos` reach the same position as the same number of
+    /// moves of this variation, though perhaps by a different move order
+    pub fn matches_prefix_with_transpositions(&self, pos: &Position) -> bool {
+        let played = pos.search_variation();
+        if played.len() > self.len() {
+            return false;
+        }
+        let prefix = self.take(played.len());
+        let root = pos.root_board();
+        prefix.first_illegal_ply(root).is_none() && root.make_moves(&prefix).hash() == pos.board().hash()
+    }
+
+    /// SAN with check and mate suffixes, validated against successive positions. Wont panic.
+    ///
+    /// An illegal tail (from TT corruption say) is truncated and marked as `{illegal: e2e4 ...}`
     pub fn display_san(&self, b: &Board) -> String {
+        self.fmt_verified_san(b, false)
+    }
+
+    /// As `display_san` but with PGN move numbers, eg "12... Nc6 13. Bb5+"
+    pub fn to_pgn(&self, b: &Board) -> String {
+        self.fmt_verified_san(b, true)
+    }
+
+    fn fmt_verified_san(&self, b: &Board, numbered: bool) -> String {
         let mut b2 = b.clone();
         let mut s = vec![];
-        let mut errors = false;
-        for mv in self.moves() {
-            if !mv.is_null() && mv.to_inner().validate(&b2).is_err() {
-                errors = true;
-            }
-            match errors {
-                false => {
-                    s.push(b2.to_san(mv).to_string());
-                    b2 = b2.make_move(mv);
-                }
-                true => s.push(format!("[{}]", mv.to_uci())),
+        let legal = self.first_illegal_ply(b).unwrap_or(self.len());
+        for (i, mv) in self.moves().take(legal).enumerate() {
+            let mut token = String::new();
+            if numbered && b2.color_us() == Color::White {
+                token += &format!("{}. ", b2.fullmove_number());
+            } else if numbered && i == 0 {
+                token += &format!("{}... ", b2.fullmove_number());
             }
+            token += &b2.to_san(mv);
+            s.push(token);
+            b2 = b2.make_move(mv);
+        }
+        if legal < self.len() {
+            s.push(format!("{{illegal: {}}}", self.skip(legal).to_uci()));
         }
         s.join(" ")
     }
@@ -477,7 +541,7 @@ impl MultiVariation {
     pub fn to_san(&self, bd: &Board) -> String {
         self.vars_and_scores
             .iter()
-            .map(|vs| format!("{sc}:{s}", sc = vs.score.to_pgn(), s = vs.var.to_san(bd)))
+            .map(|vs| format!("{sc}:{s}", sc = vs.score.to_pgn(), s = vs.var.display_san(bd)))
             .join(", ")
     }
 
@@ -527,9 +591,32 @@ mod tests {
         assert_eq!(var[1], a6);
         assert_eq!(b.make_moves(&var.take(0)), *b);
         assert_eq!(b.make_moves(&var.take(2)), b.make_move(a3).make_move(a6));
+
+        assert_eq!(Variation::parse_uci(&var.to_uci(), b)?, var);
+        assert_eq!(serde_json::from_str::<Variation>(&serde_json::to_string(&var)?)?, var);
         Ok(())
     }
 
+    #[test]
+    fn test_variation_san() {
+        let b = &Catalog::starting_board();
+        let fools_mate = "f3 e5 g4 Qh4".var(b);
+        assert_eq!(fools_mate.first_illegal_ply(b), None);
+        assert_eq!(fools_mate.display_san(b), "f3 e5 g4 Qh4#");
+        assert_eq!(fools_mate.to_pgn(b), "1. f3 e5 2. g4 Qh4#");
+        let b2 = b.make_move(fools_mate[0]);
+        assert_eq!(fools_mate.skip(1).to_pgn(&b2), "1... e5 2. g4 Qh4#");
+
+        // Nf3 twice is an illegal tail
+        let mut var = "Nf3 d5 Nc3 Bg4".var(b);
+        assert_eq!(var.to_pgn(b), "1. Nf3 d5 2. Nc3 Bg4");
+        var.push(var[0]);
+        var.push(var[1]);
+        assert_eq!(var.first_illegal_ply(b), Some(4));
+        assert_eq!(var.display_san(b), "Nf3 d5 Nc3 Bg4 {illegal: g1f3 d7d5}");
+        assert_eq!(var.to_pgn(b), "1. Nf3 d5 2. Nc3 Bg4 {illegal: g1f3 d7d5}");
+    }
+
     #[test]
     fn test_variation_ordering() {
         let b = &Board::starting_pos();
@@ -539,6 +626,26 @@ mod tests {
         assert!("a4 a5".var(b).partial_cmp(&"a4 c5".var(b)).is_none());
     }
 
+    #[test]
+    fn test_variation_transpositions() {
+        let b = Board::starting_pos();
+        let solution = "e4 e5 Nf3 Nc6".var(&b);
+        let mut pos = Position::from_board(b.clone());
+        assert!(solution.matches_prefix_with_transpositions(&pos));
+        pos.push_moves("Nf3 Nc6 e4".var(&b));
+        assert!(!solution.matches_prefix_with_transpositions(&pos));
+        pos.push_moves("e5".var(pos.board()));
+        assert!(solution.matches_prefix_with_transpositions(&pos));
+        pos.push_moves("Bc4".var(pos.board()));
+        assert!(!solution.matches_prefix_with_transpositions(&pos));
+
+        // played moves are not part of the match
+        pos.play_search_moves();
+        let solution = "Nf6 d3".var(pos.board());
+        pos.push_moves("Nf6".var(pos.board()));
+        assert!(solution.matches_prefix_with_transpositions(&pos));
+    }
+
     #[test]
     fn test_multi_variation() {
         let bd = Board::starting_pos();
@@ -563,5 +670,7 @@ mod tests {
             MultiVariation::parse_san("+M2:a3 a6, -M2:a4", &bd).unwrap().to_uci(),
             "+M2:a2a3 a7a6, -M2:a2a4"
         );
+        let json = serde_json::to_string(&mvar).unwrap();
+        assert_eq!(serde_json::from_str::<MultiVariation>(&json).unwrap(), mvar);
     }
 }
@@ -1,15 +1,42 @@
 use std::backtrace::Backtrace;
-use std::collections::HashMap;
 use std::panic;
 
 use anyhow::Context;
 use clap::{Parser, Subcommand};
+#[cfg(feature = "tools")]
+use odonata_base::catalog::Catalog;
 use odonata_base::domain::timecontrol::TimeControl;
 use odonata_base::infra::utils::ToStringOr;
+#[cfg(feature = "tools")]
+use odonata_base::other::EpdFilter;
+use odonata_base::other::{PerftPosition, PerftSuite, Shard};
 use odonata_base::trace::logger::LoggingSystem;
-use odonata_engine::comms::bench::Bench;
-use odonata_engine::comms::uci_server::UciServer;
-use tracing::{debug, error};
+#[cfg(feature = "tools")]
+use odonata_engine::book::analysis::BookAnalysis;
+use odonata_engine::book::learning::Learning;
+#[cfg(feature = "tools")]
+use odonata_engine::book::polyglot::Polyglot;
+#[cfg(feature = "tools")]
+use odonata_engine::book::prep::Prep;
+#[cfg(feature = "tools")]
+use odonata_engine::comms::annotate::Annotator;
+use odonata_engine::comms::bench::{Bench, BenchResults};
+#[cfg(feature = "tools")]
+use odonata_engine::comms::exam::{Exam, ExamResults};
+#[cfg(feature = "tools")]
+use odonata_engine::comms::handicap::{Handicap, HandicapMatch};
+use odonata_engine::comms::health::HealthCheck;
+use odonata_engine::comms::protocol::Protocol;
+#[cfg(feature = "tools")]
+use odonata_engine::comms::report::Report;
+#[cfg(feature = "tools")]
+use odonata_engine::comms::selfplay::{DataFormat, SelfPlay};
+#[cfg(feature = "tools")]
+use odonata_engine::comms::spsa::{Spsa, SpsaParam};
+#[cfg(feature = "tools")]
+use odonata_engine::eval::tune::{Optimizer, Tuner};
+use odonata_engine::{Engine, EngineConfig};
+use tracing::{debug, error, info, warn};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -34,6 +61,18 @@ struct OdonataCli {
     #[arg(short, long)]
     strict: bool,
 
+    /// Verify embedded and external resources (networks, eval weights, epd suites) then exit
+    #[arg(long)]
+    verify_resources: bool,
+
+    /// Run a quick self-benchmark at startup, warning if nodes/sec is well below that expected
+    #[arg(long)]
+    health_check: bool,
+
+    /// Protocol on stdin (uci, json-rpc or console), otherwise detected from the first line
+    #[arg(long, value_name = "PROTOCOL", value_parser = Protocol::parse_forced)]
+    protocol: Option<Protocol>,
+
     #[command(subcommand)]
     command: Option<Cmd>,
 }
@@ -56,15 +95,325 @@ enum Cmd {
     Profile,
 
     /// Runs perft from standard chess opening position
-    Perft { depth: u32 },
+    Perft {
+        depth: u32,
+
+        /// Size in mb of a table of subtree counts, to speed up large depths
+        #[arg(long, default_value_t = 0)]
+        hash: usize,
+    },
+
+    /// Runs perft from standard chess opening position, giving the count for each move
+    Divide {
+        depth: u32,
+
+        /// Size in mb of a table of subtree counts, to speed up large depths
+        #[arg(long, default_value_t = 0)]
+        hash: usize,
+    },
+
+    /// Runs perft over the positions of a suite, reporting counts differing from those expected
+    PerftSuite {
+        /// Perft suite file, each line a fen with expected counts as ";D1 20 ;D2 400" or as
+        /// epd operations "D1 20; D2 400;" (the built-in suite if not given)
+        file: Option<String>,
+
+        /// Counts beyond this depth are not checked
+        #[arg(long, default_value_t = 5)]
+        max_depth: u32,
+
+        /// Size in mb of a table of subtree counts, to speed up large depths
+        #[arg(long, default_value_t = 0)]
+        hash: usize,
+    },
 
     /// Runs perft with category breakdown from standard chess opening position
     PerftCat { depth: u32 },
 
+    /// Annotate the games in a PGN file with inaccuracies, mistakes and blunders
+    #[cfg(feature = "tools")]
+    Annotate {
+        /// PGN file of games to annotate
+        pgn: String,
+
+        /// Search depth used to evaluate each move
+        #[arg(long, default_value_t = 10)]
+        depth: i32,
+
+        /// Write annotated PGN to this file rather than stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Run an exam over EPD suites, files or directories, reporting pass/fail by tag
+    #[cfg(feature = "tools")]
+    Exam {
+        /// Built-in suite names (eg BratkoKopec), EPD files or directories of EPD files
+        #[arg(required = true)]
+        sources: Vec<String>,
+
+        /// Select positions by tag expression, eg "id~'WAC' && acd>=10"
+        #[arg(short, long, value_name = "EXPR")]
+        filter: Option<EpdFilter>,
+
+        #[arg(short = 't', long, value_name = "TIME_CONTROL", default_value = "depth=8")]
+        time_control: TimeControl,
+
+        /// Tag whose values group the pass/fail statistics, or Theme for the STS theme
+        #[arg(short, long, value_name = "TAG", default_value = "Src")]
+        group_by: String,
+
+        /// Examine only slice i of N of the positions, for running across several machines
+        #[arg(long, value_name = "i/N", default_value = "1/1")]
+        shard: Shard,
+
+        /// Suite (Src tag) whose bm also accepts moves to the same square or transpositions of the pv
+        #[arg(long, value_name = "SUITE")]
+        lenient: Vec<String>,
+
+        /// Also write the results (with a record per position) as json to this file, for merge-results or bench-diff
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Write a regression report (bench signature, exam solve rates, nodes/sec, tt hit rate and
+    /// eval symmetry) as json, for archiving per commit and comparing over time
+    #[cfg(feature = "tools")]
+    Report {
+        /// Json file to write the report to
+        output: String,
+
+        /// Built-in suite names, EPD files or directories of EPD files for the exam
+        #[arg(short, long, value_name = "SUITE", default_values = ["BratkoKopec", "WinAtChess"])]
+        suites: Vec<String>,
+
+        #[arg(short, long, value_name = "TIME_CONTROL", default_value = "nodes=100000")]
+        bench_tc: TimeControl,
+
+        #[arg(short = 't', long, value_name = "TIME_CONTROL", default_value = "depth=8")]
+        exam_tc: TimeControl,
+    },
+
+    /// Play handicap games (material and/or time odds) against a second engine config
+    #[cfg(feature = "tools")]
+    Handicap {
+        /// Handicap conditions as odds,giver_tc,taker_tc, eg "knight,depth=8,depth=4"
+        #[arg(required = true)]
+        handicaps: Vec<Handicap>,
+
+        /// Defines a parameter of the engine taking the odds, which otherwise has the defaults
+        #[arg(long, value_name = "VAR=VALUE")]
+        taker_define: Vec<String>,
+
+        /// Built-in suites, EPD files or directories of opening positions, each played with
+        /// colors swapped (the starting position if not given)
+        #[arg(long, value_name = "SOURCE")]
+        openings: Vec<String>,
+
+        /// Games still in progress after this many moves are adjudicated drawn
+        #[arg(long, default_value_t = 150)]
+        max_moves: usize,
+
+        /// Also write the results as json to this file
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Report an opponent's repertoire, typical mistakes and the lines scoring well against them
+    #[cfg(feature = "tools")]
+    Prep {
+        /// PGN file of the opponent's games
+        pgn: String,
+
+        /// Opponent name (matched case-insensitively against the White and Black tags)
+        #[arg(short, long)]
+        opponent: String,
+
+        /// Number of opening plies considered
+        #[arg(long, default_value_t = 16)]
+        max_ply: i32,
+    },
+
+    /// Report the coverage, diversity and holes (positions whose book moves all score as
+    /// losing on a shallow search) of a polyglot book
+    #[cfg(feature = "tools")]
+    BookAnalyse {
+        /// Polyglot book file
+        book: String,
+
+        /// Plies walked from the starting position
+        #[arg(long, default_value_t = 12)]
+        max_ply: i32,
+
+        /// Book moves with less than this percentage of a position's weight are not followed
+        #[arg(long, default_value_t = 5.0)]
+        min_weight_perc: f64,
+
+        /// Search checking the book moves of each position
+        #[arg(short = 't', long, value_name = "TIME_CONTROL", default_value = "depth=6")]
+        time_control: TimeControl,
+
+        /// Book moves all scoring below minus this many centipawns make a hole
+        #[arg(long, default_value_t = 150)]
+        hole_cp: i32,
+
+        /// Also write the report as json to this file
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Merge learning files into one, keeping the largest score drops
+    LearnMerge {
+        /// Learning files to merge
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Merged learning file to write (may be one of the inputs)
+        #[arg(short, long, value_name = "FILE")]
+        output: String,
+
+        /// Maximum number of lessons kept
+        #[arg(long, default_value_t = 10_000)]
+        max_entries: usize,
+    },
+
     /// Analyse a series of test positions with using a given depth, nodes, st or tc. Use odonata_var_eval__eval_weights_kind=SoftcodedF64 etc
     Search {
         #[arg(short = 't', long, value_name = "TIME_CONTROL")]
         time_control: TimeControl,
+
+        /// Search only slice i of N of the positions, for running across several machines
+        #[arg(long, value_name = "i/N", default_value = "1/1")]
+        shard: Shard,
+
+        /// Also write the results (with a record per position) as json to this file, for merge-results or bench-diff
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+
+        /// Print a json record per line (fen, depth, score, pv, nodes, nps, elapsed and tt stats for
+        /// each position, then a summary) rather than the text table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Merge the json results of sharded search or exam runs, and report the combined results
+    MergeResults {
+        /// Results files (all from search, or all from exam)
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+
+    /// Export the hand-crafted eval weights, as csv and as a heatmap of the piece square tables
+    WeightsExport {
+        /// Csv file of all the feature weights (written to stdout if no files are given)
+        #[arg(long, value_name = "FILE")]
+        csv: Option<String>,
+
+        /// PPM image file of the piece square tables, start game left and end game right
+        #[arg(long, value_name = "FILE")]
+        heatmap: Option<String>,
+    },
+
+    /// Texel tune the hand-crafted eval weights to the game results of EPD or PGN files
+    #[cfg(feature = "tools")]
+    Tune {
+        /// EPD files with results tagged c9 "1-0" (etc), or PGN files of finished games
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Toml file for the tuned weights (loadable as eval.hce.hce_file), or a .rs file for a hardcoded array
+        #[arg(short, long, value_name = "FILE", default_value = "tuned.hce.toml")]
+        output: String,
+
+        #[arg(long, default_value = "Adam")]
+        optimizer: Optimizer,
+
+        #[arg(long, default_value_t = 1.0)]
+        learning_rate: f64,
+
+        #[arg(long, default_value_t = 1000)]
+        epochs: usize,
+
+        /// Sigmoid scaling, otherwise searched for to best fit the starting weights
+        #[arg(short, long)]
+        k: Option<f64>,
+    },
+
+    /// Play the engine against itself, writing quiet positions with their search scores and
+    /// game results as nnue training data
+    #[cfg(feature = "tools")]
+    Selfplay {
+        /// File of training data to write
+        output: String,
+
+        #[arg(short = 't', long, value_name = "TIME_CONTROL", default_value = "nodes=5000")]
+        time_control: TimeControl,
+
+        #[arg(short, long, default_value_t = 100)]
+        games: usize,
+
+        /// Epd (fen, ce and c9 result tags) or Binary (32-byte records)
+        #[arg(short, long, default_value = "Epd")]
+        format: DataFormat,
+
+        /// Built-in suites, EPD files or directories of opening positions, played in turn
+        /// (the starting position if not given)
+        #[arg(long, value_name = "SOURCE")]
+        openings: Vec<String>,
+
+        /// Random moves played from the opening, so that games differ
+        #[arg(long, default_value_t = 8)]
+        random_plies: usize,
+
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+
+    /// Tune engine settings by SPSA, each iteration playing game pairs between engines with the
+    /// settings perturbed up and down, and moving the settings towards the winner
+    #[cfg(feature = "tools")]
+    Spsa {
+        /// File of settings to tune, a line each of "name, int|float|cp, value, min, max, c_end, r_end"
+        params: String,
+
+        /// File for the tuned settings, in the same format, rewritten after each iteration
+        #[arg(short, long, value_name = "FILE", default_value = "spsa.tuned.txt")]
+        output: String,
+
+        #[arg(short, long, default_value_t = 1000)]
+        iterations: usize,
+
+        /// Game pairs (an opening played with each side) per iteration
+        #[arg(long, default_value_t = 8)]
+        pairs: usize,
+
+        #[arg(short = 't', long, value_name = "TIME_CONTROL", default_value = "nodes=5000")]
+        time_control: TimeControl,
+
+        /// Built-in suites, EPD files or directories of opening positions, chosen at random
+        /// (the starting position if not given)
+        #[arg(long, value_name = "SOURCE")]
+        openings: Vec<String>,
+
+        /// Random moves played from the opening, so that games differ
+        #[arg(long, default_value_t = 8)]
+        random_plies: usize,
+
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+
+    /// Compare two json results of search, listing positions whose best move or node count changed
+    BenchDiff {
+        /// Results file of the earlier run
+        old: String,
+
+        /// Results file of the later run
+        new: String,
+
+        /// Node count changes (up or down) beyond this percentage are listed
+        #[arg(long, default_value_t = 10.0)]
+        nodes_perc: f64,
     },
 }
 
@@ -90,25 +439,343 @@ pub fn main() -> anyhow::Result<()> {
     //     let config = ParsedConfig::read_from_file(filename)?;
     //     ParsedConfig::set_global(config);
     // }
-    let mut settings = HashMap::new();
-    for kv in &cli.define {
-        let (k, v) = kv.split_once('=').context("unable to find '=' in -D/--define '{kv}'")?;
-        settings.insert(k.to_string(), v.to_string());
+    let mut config = EngineConfig::from_defines(&cli.define).context("parsing -D/--define")?;
+    config.strict_error_handling = cli.strict;
+    if let Some(threads) = cli.threads {
+        config.threads = threads;
+    }
+    let uci = || {
+        let mut uci = Engine::with_config(config.clone())?.into_uci_server();
+        uci.protocol = cli.protocol;
+        anyhow::Ok(uci)
+    };
+
+    if cli.verify_resources {
+        for checked in Engine::with_config(config)?.verify_resources()? {
+            println!("ok  {checked}");
+        }
+        return Ok(());
+    }
+
+    if cli.health_check {
+        let report = HealthCheck::default().run(&mut Engine::with_config(config.clone())?)?;
+        info!("health check\n{report}");
+        for w in &report.warnings {
+            warn!("health check: {w}");
+            eprintln!("warning: {w}");
+        }
     }
-    let mut uci = UciServer::configure(settings.clone())?;
-    uci.strict_error_handling = cli.strict;
 
     match cli.command.unwrap_or(Cmd::Engine) {
-        Cmd::ShowConfig => uci.add_prelude("uci; show_config; quit").run(),
-        Cmd::Bench => uci
+        Cmd::ShowConfig => uci()?.add_prelude("uci; show_config; quit").run(),
+        Cmd::Bench => uci()?
             .add_prelude("position startpos; go depth 11; isready; bench; quit")
             .run(),
         Cmd::Profile => Bench::profile_me(),
-        Cmd::Perft { depth } => uci.add_prelude(&format!("perft {depth}; quit")).run(),
-        Cmd::PerftCat { depth } => uci.add_prelude(&format!("perft_cat {depth}; quit")).run(),
-        Cmd::Search { time_control } => drop(Bench::search(time_control, cli.threads, settings)?),
-        Cmd::Uci { command } => uci.add_prelude(&(command + "; isready; quit")).run(),
-        Cmd::Engine => uci.add_prelude("compiler").run(),
+        Cmd::Perft { depth, hash } => uci()?.add_prelude(&format!("perft {depth} {hash}; quit")).run(),
+        Cmd::Divide { depth, hash } => uci()?.add_prelude(&format!("divide {depth} {hash}; quit")).run(),
+        Cmd::PerftSuite { file, max_depth, hash } => {
+            let positions = match file {
+                Some(file) => PerftPosition::parse_many(&fs_err::read_to_string(file)?)?,
+                None => Catalog::perft_suite(),
+            };
+            let suite = PerftSuite {
+                max_depth,
+                hash_mb: hash,
+            };
+            let results = suite.run(&positions, &mut |pos, depth, expected, count| {
+                let status = if count == expected { "ok" } else { "MISMATCH" };
+                println!(
+                    "{status:<8} perft({depth}) = {count:>12} expected {expected:>12} {}",
+                    pos.board.to_fen()
+                );
+            });
+            print!("{results}");
+            if !results.mismatches.is_empty() {
+                anyhow::bail!("{} perft counts differ from those expected", results.mismatches.len());
+            }
+        }
+        Cmd::PerftCat { depth } => uci()?.add_prelude(&format!("perft_cat {depth}; quit")).run(),
+        Cmd::Search {
+            time_control,
+            shard,
+            output,
+            json,
+        } => {
+            let results = Bench::search(time_control, config, shard, json)?;
+            if let Some(file) = output {
+                fs_err::write(file, serde_json::to_string_pretty(&results)?)?;
+            }
+        }
+        Cmd::MergeResults { files } => print!("{}", merge_results(&files)?),
+        Cmd::WeightsExport { csv, heatmap } => {
+            let engine = Engine::with_config(config)?.into_threaded_search();
+            let hce = &engine.search.eval.hce;
+            if let Some(file) = &csv {
+                hce.write_weights_csv(fs_err::File::create(file)?)?;
+            }
+            if let Some(file) = &heatmap {
+                hce.write_pst_heatmap(std::io::BufWriter::new(fs_err::File::create(file)?))?;
+            }
+            if csv.is_none() && heatmap.is_none() {
+                hce.write_weights_csv(std::io::stdout())?;
+            }
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Tune {
+            files,
+            output,
+            optimizer,
+            learning_rate,
+            epochs,
+            k,
+        } => {
+            let engine = Engine::with_config(config)?.into_threaded_search();
+            let hce = &engine.search.eval.hce;
+            let mut tuner = Tuner {
+                optimizer,
+                learning_rate,
+                epochs,
+                k,
+                ..Tuner::default()
+            };
+            for file in &files {
+                let n = match file.ends_with(".pgn") {
+                    true => tuner.load_pgn(file, &hce.phaser)?,
+                    false => tuner.load_epd(file, &hce.phaser)?,
+                };
+                info!("loaded {n} positions from {file}");
+            }
+            let tuned = tuner.tune(hce.soft_coded_f64())?;
+            Tuner::write_weights(&tuned, &output)?;
+            println!("tuned weights written to {output}");
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Selfplay {
+            output,
+            time_control,
+            games,
+            format,
+            openings,
+            random_plies,
+            seed,
+        } => {
+            let mut selfplay = SelfPlay {
+                tc: time_control,
+                games,
+                format,
+                random_plies,
+                seed,
+                ..SelfPlay::default()
+            };
+            if !openings.is_empty() {
+                selfplay.openings = Catalog::load_many(&openings, None)?;
+            }
+            let w = std::io::BufWriter::new(fs_err::File::create(&output)?);
+            let results = selfplay.run(&mut Engine::with_config(config)?, w)?;
+            print!("{results}");
+            println!("training data written to {output}");
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Spsa {
+            params,
+            output,
+            iterations,
+            pairs,
+            time_control,
+            openings,
+            random_plies,
+            seed,
+        } => {
+            let mut spsa = Spsa {
+                params: SpsaParam::parse_many(&fs_err::read_to_string(&params)?)
+                    .with_context(|| format!("reading {params}"))?,
+                iterations,
+                pairs,
+                tc: time_control,
+                random_plies,
+                seed,
+                ..Spsa::default()
+            };
+            if !openings.is_empty() {
+                spsa.openings = Catalog::load_many(&openings, None)?;
+            }
+            let tuned = spsa.run(&config, &mut |it| {
+                println!("{it}");
+                fs_err::write(&output, it.params.iter().map(|p| format!("{p}\n")).collect::<String>())?;
+                Ok(())
+            })?;
+            println!("tuned settings written to {output}");
+            for p in &tuned {
+                println!("-D {}={}", p.name, p.setting(p.value));
+            }
+        }
+        Cmd::BenchDiff { old, new, nodes_perc } => {
+            let read = |file: &String| -> anyhow::Result<BenchResults> {
+                let text = fs_err::read_to_string(file)?;
+                serde_json::from_str(&text).with_context(|| format!("reading results file {file}"))
+            };
+            print!("{}", BenchResults::diff(&read(&old)?, &read(&new)?, nodes_perc));
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Annotate { pgn, depth, output } => {
+            let annotator = Annotator {
+                depth,
+                ..Annotator::default()
+            };
+            let mut engine = Engine::with_config(config)?;
+            let pgn = fs_err::read_to_string(pgn)?;
+            let annotated = annotator.annotate_pgn(&mut engine, &pgn)?;
+            match output {
+                Some(file) => fs_err::write(file, annotated)?,
+                None => print!("{annotated}"),
+            }
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Exam {
+            sources,
+            filter,
+            time_control,
+            group_by,
+            shard,
+            lenient,
+            output,
+        } => {
+            let epds = Catalog::load_many(&sources, filter.as_ref())?;
+            let exam = Exam {
+                tc: time_control,
+                group_by,
+                shard,
+                lenient,
+            };
+            let mut engine = Engine::with_config(config)?;
+            let results = exam.run(&mut engine, &epds)?;
+            println!("{results:#}");
+            if let Some(file) = output {
+                fs_err::write(file, serde_json::to_string_pretty(&results)?)?;
+            }
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Report {
+            output,
+            suites,
+            bench_tc,
+            exam_tc,
+        } => {
+            let report = Report {
+                bench_tc,
+                exam_tc,
+                suites,
+            };
+            let results = report.run(&mut Engine::with_config(config)?)?;
+            println!("{results}");
+            fs_err::write(output, serde_json::to_string_pretty(&results)?)?;
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Handicap {
+            handicaps,
+            taker_define,
+            openings,
+            max_moves,
+            output,
+        } => {
+            let mut hm = HandicapMatch {
+                handicaps,
+                max_moves,
+                ..HandicapMatch::default()
+            };
+            if !openings.is_empty() {
+                hm.openings = Catalog::load_many(&openings, None)?;
+            }
+            let mut giver = Engine::with_config(config)?;
+            let mut taker = Engine::with_config(EngineConfig::from_defines(&taker_define)?)?;
+            let results = hm.run(&mut giver, &mut taker)?;
+            println!("{results:#}");
+            if let Some(file) = output {
+                fs_err::write(file, serde_json::to_string_pretty(&results)?)?;
+            }
+        }
+        #[cfg(feature = "tools")]
+        Cmd::Prep { pgn, opponent, max_ply } => {
+            let mut prep = Prep {
+                prep_file: pgn.into(),
+                opponent,
+                max_ply,
+                ..Prep::default()
+            };
+            prep.reload()?;
+            print!("{prep}");
+        }
+        #[cfg(feature = "tools")]
+        Cmd::BookAnalyse {
+            book,
+            max_ply,
+            min_weight_perc,
+            time_control,
+            hole_cp,
+            output,
+        } => {
+            let analysis = BookAnalysis {
+                max_ply,
+                min_weight_perc,
+                hole_tc: time_control,
+                hole_cp,
+            };
+            let mut polyglot = Polyglot::new();
+            polyglot.load(&book)?;
+            let report = analysis.run(&polyglot, &mut Engine::with_config(config)?)?;
+            print!("{report}");
+            if let Some(file) = output {
+                fs_err::write(file, serde_json::to_string_pretty(&report)?)?;
+            }
+        }
+        Cmd::LearnMerge {
+            files,
+            output,
+            max_entries,
+        } => {
+            let mut learning = Learning::default();
+            learning.learning_file = output.into();
+            learning.max_entries = max_entries;
+            for file in files {
+                learning.merge(Learning::read(file.as_ref())?);
+            }
+            learning.save()?;
+            println!("{} lessons", learning.lessons().len());
+        }
+        Cmd::Uci { command } => uci()?.add_prelude(&(command + "; isready; quit")).run(),
+        Cmd::Engine => uci()?.add_prelude("compiler").run(),
     };
     Ok(())
 }
+
+/// merged results of sharded runs, as a printable report
+fn merge_results(files: &[String]) -> anyhow::Result<String> {
+    let texts = files
+        .iter()
+        .map(fs_err::read_to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    #[cfg(feature = "tools")]
+    if let Ok(shards) = texts
+        .iter()
+        .map(|t| serde_json::from_str::<ExamResults>(t))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        let mut merged = ExamResults {
+            group_by: shards[0].group_by.clone(),
+            ..ExamResults::default()
+        };
+        for shard in shards {
+            merged.merge(shard)?;
+        }
+        return Ok(format!("{merged:#}"));
+    }
+
+    let mut merged = BenchResults::default();
+    for (text, file) in texts.iter().zip(files) {
+        let shard = serde_json::from_str(text).with_context(|| format!("reading results file {file}"))?;
+        merged.merge(shard)?;
+    }
+    Ok(merged.to_string())
+}
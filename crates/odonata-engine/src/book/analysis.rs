@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use odonata_base::epd::Epd;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+use tabled::builder::Builder;
+
+use super::polyglot::Polyglot;
+use crate::search::restrictions::Restrictions;
+use crate::Engine;
+
+/// Reports on a polyglot book for its maintainers: how deep each first move's main line
+/// runs, how varied the choices are in each position (the entropy of the weights), and the
+/// holes - positions whose book moves all score as losing on a shallow engine search.
+///
+/// The book is walked from the starting position to `max_ply` plies, following moves with
+/// at least `min_weight_perc` of a position's weight, and visiting each position (matched
+/// by polyglot key, so transpositions are merged) once. Zero weight moves (by polyglot
+/// convention, not to be played) are ignored throughout.
+#[derive(Clone, Debug)]
+pub struct BookAnalysis {
+    pub max_ply:         Ply,
+    pub min_weight_perc: f64,
+    pub hole_tc:         TimeControl,
+    pub hole_cp:         i32, // book moves all scoring below -hole_cp make a hole
+}
+
+impl Default for BookAnalysis {
+    fn default() -> Self {
+        Self {
+            max_ply:         12,
+            min_weight_perc: 5.0,
+            hole_tc:         TimeControl::Depth(6),
+            hole_cp:         150,
+        }
+    }
+}
+
+/// the main line (most weighted moves) after a first move, and its length in plies
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineCoverage {
+    pub line:        String, // san
+    pub weight_perc: f64,    // of the first move, at the starting position
+    pub plies:       usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PositionDiversity {
+    pub line:    String, // san moves from the start, reaching the position
+    pub moves:   usize,
+    pub entropy: f64, // in bits, zero for a single move, log2(moves) for equal weights
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BookHole {
+    pub line:  String,
+    pub fen:   String,
+    pub score: Score, // of the best book move, from the point of view of the side to move
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BookReport {
+    pub entries:   usize,
+    pub positions: usize, // visited by the walk
+    pub coverage:  Vec<LineCoverage>,
+    pub diversity: Vec<PositionDiversity>,
+    pub holes:     Vec<BookHole>,
+}
+
+impl BookReport {
+    /// mean entropy of the positions visited
+    pub fn mean_entropy(&self) -> f64 {
+        self.diversity.iter().map(|d| d.entropy).sum::<f64>() / self.diversity.len().max(1) as f64
+    }
+}
+
+impl fmt::Display for BookReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "entries       : {}", self.entries)?;
+        writeln!(f, "positions     : {}", self.positions)?;
+        writeln!(f, "mean entropy  : {:.2} bits", self.mean_entropy())?;
+        writeln!(f, "holes         : {}", self.holes.len())?;
+
+        writeln!(f, "\ncoverage by first move")?;
+        let mut bu = Builder::new();
+        bu.push_record(["weight %", "plies", "main line"]);
+        for c in &self.coverage {
+            bu.push_record([format!("{:.1}", c.weight_perc), c.plies.to_string(), c.line.clone()]);
+        }
+        writeln!(f, "{}", bu.build())?;
+
+        writeln!(f, "\nleast diverse positions")?;
+        let mut bu = Builder::new();
+        bu.push_record(["moves", "entropy", "line"]);
+        let mut diversity = self.diversity.iter().collect_vec();
+        diversity.sort_by(|a, b| a.entropy.total_cmp(&b.entropy));
+        for d in diversity.iter().take(20) {
+            bu.push_record([d.moves.to_string(), format!("{:.2}", d.entropy), d.line.clone()]);
+        }
+        writeln!(f, "{}", bu.build())?;
+
+        writeln!(f, "\nholes")?;
+        let mut bu = Builder::new();
+        bu.push_record(["score", "line", "fen"]);
+        for h in &self.holes {
+            bu.push_record([h.score.to_string(), h.line.clone(), h.fen.clone()]);
+        }
+        writeln!(f, "{}", bu.build())
+    }
+}
+
+impl BookAnalysis {
+    pub fn run(&self, book: &Polyglot, engine: &mut Engine) -> Result<BookReport> {
+        let start = Board::starting_pos();
+        let mut report = BookReport {
+            entries: book.find_all().count(),
+            coverage: self.coverage(book, &start),
+            ..BookReport::default()
+        };
+        let mut visited = HashSet::new();
+        self.walk(book, engine, &start, &Variation::new(), &mut visited, &mut report)?;
+        report.positions = visited.len();
+        Ok(report)
+    }
+
+    /// the book moves of a position with a non-zero weight, most weighted first
+    fn moves(book: &Polyglot, b: &Board) -> Vec<(Move, u16)> {
+        let mut moves = book
+            .find_matching(b, &Restrictions::none())
+            .filter(|e| e.weight > 0)
+            .map(|e| (e.calc_move(b), e.weight))
+            .collect_vec();
+        moves.sort_by_key(|&(_mv, weight)| std::cmp::Reverse(weight));
+        moves
+    }
+
+    /// entropy in bits of the choice of move, were moves chosen in proportion to weight
+    pub fn entropy(weights: &[u16]) -> f64 {
+        let total = weights.iter().map(|&w| w as f64).sum::<f64>();
+        weights
+            .iter()
+            .filter(|&&w| w > 0)
+            .map(|&w| w as f64 / total)
+            .map(|p| -p * p.log2())
+            .sum()
+    }
+
+    fn coverage(&self, book: &Polyglot, start: &Board) -> Vec<LineCoverage> {
+        let first_moves = Self::moves(book, start);
+        let total = first_moves.iter().map(|&(_mv, w)| w as f64).sum::<f64>();
+        let mut coverage = vec![];
+        for &(mv, weight) in &first_moves {
+            let mut var = Variation::new();
+            let mut b = start.clone();
+            let mut seen = HashSet::new();
+            let mut next = Some(mv);
+            // ends out of book, or should the main line cycle
+            while let Some(mv) = next.filter(|_| seen.insert(book.hash_board(&b))) {
+                var.push(mv);
+                b = b.make_move(mv);
+                next = Self::moves(book, &b).first().map(|&(mv, _)| mv);
+            }
+            coverage.push(LineCoverage {
+                line:        start.to_san_variation(&var),
+                weight_perc: 100.0 * weight as f64 / total,
+                plies:       var.len(),
+            });
+        }
+        coverage
+    }
+
+    fn walk(
+        &self,
+        book: &Polyglot,
+        engine: &mut Engine,
+        b: &Board,
+        var: &Variation,
+        visited: &mut HashSet<Hash>,
+        report: &mut BookReport,
+    ) -> Result<()> {
+        let moves = Self::moves(book, b);
+        if moves.is_empty() || !visited.insert(book.hash_board(b)) {
+            return Ok(());
+        }
+        let start = Board::starting_pos();
+        let line = start.to_san_variation(var);
+        let weights = moves.iter().map(|&(_mv, w)| w).collect_vec();
+        report.diversity.push(PositionDiversity {
+            line:    line.clone(),
+            moves:   moves.len(),
+            entropy: Self::entropy(&weights),
+        });
+
+        let opts = SearchOptions {
+            root_moves: moves.iter().map(|&(mv, _)| mv).collect(),
+        };
+        let score = engine
+            .search_with_options(Epd::from_var(start, var.clone()), self.hole_tc.clone(), opts)?
+            .score()
+            .with_context(|| format!("no score from search of book moves after {line}"))?;
+        if score < Score::from_cp(-self.hole_cp) {
+            report.holes.push(BookHole {
+                line,
+                fen: b.to_fen(),
+                score,
+            });
+        }
+
+        if var.len() < self.max_ply as usize {
+            let total = weights.iter().map(|&w| w as f64).sum::<f64>();
+            for &(mv, weight) in &moves {
+                if 100.0 * weight as f64 / total >= self.min_weight_perc {
+                    self.walk(book, engine, &b.make_move(mv), &var.append(mv), visited, report)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_book_entropy() {
+        assert_eq!(BookAnalysis::entropy(&[10]), 0.0);
+        assert_eq!(BookAnalysis::entropy(&[5, 5]), 1.0);
+        assert_eq!(BookAnalysis::entropy(&[3, 3, 3, 3]), 2.0);
+        assert_eq!(BookAnalysis::entropy(&[3, 3, 0]), 1.0);
+        assert!(BookAnalysis::entropy(&[90, 10]) < 0.5);
+    }
+
+    #[test]
+    fn test_book_analysis() {
+        let mut book = Polyglot::new();
+        book.load([env!("CARGO_MANIFEST_DIR"), "/../../ext/books/gm2001.bin"].concat())
+            .unwrap();
+        let analysis = BookAnalysis {
+            max_ply: 3,
+            min_weight_perc: 20.0,
+            hole_tc: TimeControl::Depth(2),
+            ..BookAnalysis::default()
+        };
+        let report = analysis.run(&book, &mut Engine::new()).unwrap();
+        println!("{report}");
+        assert_eq!(report.entries, book.find_all().count());
+        assert!(report.positions > 1);
+        assert_eq!(report.positions, report.diversity.len());
+        assert!(report.coverage.iter().any(|c| c.line.starts_with("e4") && c.plies > 3));
+        let weights = report.coverage.iter().map(|c| c.weight_perc).sum::<f64>();
+        assert!((weights - 100.0).abs() < 0.01, "{weights}");
+        assert!(report.diversity[0].entropy > 1.0, "{:?}", report.diversity[0]);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<BookReport>(&json).unwrap(), report);
+    }
+}
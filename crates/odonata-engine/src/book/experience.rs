@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use odonata_base::domain::wdl::ScoreWdl;
+use odonata_base::infra::component::{Component, State};
+use odonata_base::other::outcome::Outcome;
+use odonata_base::prelude::*;
+
+use crate::search::restrictions::Restrictions;
+
+/// A persistent (opt-in) book of the results of the engine's own games, keyed by position.
+///
+/// The positions reached by our moves in the first `max_ply` plies of a game are noted, and
+/// when the game ends its result is added to each position's win/draw/loss tally (from
+/// white's point of view). Uci has no notion of a result, so unless told the outcome the
+/// result is inferred from our last search score: a mate or a score beyond `decisive_score`
+/// is a win or loss, and otherwise the game is not recorded.
+///
+/// At the root, moves leading to positions that have scored below `min_score` for us over
+/// `min_games` or more games are avoided, provided an alternative remains. Unlike the
+/// learning of individual mistakes, this catches lines that go wrong well after the move.
+///
+/// The file is plain text, one position per line "hash wins draws losses", saved after each
+/// game and bounded to the `max_entries` most played positions.
+#[derive(Clone, Debug)]
+pub struct ExperienceBook {
+    pub experience_file: PathBuf,
+    pub max_entries:     usize,
+    pub max_ply:         Ply,
+    pub min_games:       i32,
+    pub min_score:       f64,
+    pub decisive_score:  i32, // cp
+
+    stats: BTreeMap<Hash, ScoreWdl>,
+    game:  Vec<Hash>,
+    last:  Option<(Color, Score)>,
+    dirty: bool,
+}
+
+impl Default for ExperienceBook {
+    fn default() -> Self {
+        Self {
+            experience_file: PathBuf::new(),
+            max_entries:     100_000,
+            max_ply:         30,
+            min_games:       3,
+            min_score:       0.3,
+            decisive_score:  800,
+            stats:           BTreeMap::new(),
+            game:            Vec::new(),
+            last:            None,
+            dirty:           false,
+        }
+    }
+}
+
+impl Configurable for ExperienceBook {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.experience_file.set(p.get("experience_file"))?;
+        self.max_entries.set(p.get("max_entries"))?;
+        self.max_ply.set(p.get("max_ply"))?;
+        self.min_games.set(p.get("min_games"))?;
+        self.min_score.set(p.get("min_score"))?;
+        self.decisive_score.set(p.get("decisive_score"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for ExperienceBook {
+    fn set_state(&mut self, s: State) {
+        use State::*;
+        match s {
+            NewGame | Shutdown => {
+                self.finish_game(None);
+                if let Err(e) = self.save() {
+                    warn!("failed to save experience file: {e:#}");
+                }
+            }
+            SetPosition => {}
+            StartSearch => {}
+            EndSearch => {}
+            StartDepthIteration(_) => {}
+        }
+    }
+
+    fn new_game(&mut self) {}
+
+    fn new_position(&mut self) {}
+}
+
+impl ExperienceBook {
+    pub fn is_enabled(&self) -> bool {
+        !self.experience_file.as_os_str().is_empty()
+    }
+
+    pub fn stats(&self) -> &BTreeMap<Hash, ScoreWdl> {
+        &self.stats
+    }
+
+    /// (re)reads the experience file, a missing file is an empty one
+    pub fn reload(&mut self) -> Result<()> {
+        self.stats.clear();
+        self.game.clear();
+        self.dirty = false;
+        if self.is_enabled() && self.experience_file.exists() {
+            self.stats = Self::read(&self.experience_file)?;
+        }
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<BTreeMap<Hash, ScoreWdl>> {
+        let text = fs_err::read_to_string(path)?;
+        let mut stats = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse = || -> Result<(Hash, ScoreWdl)> {
+                let Some((hash, wdl)) = line.split_once(' ') else {
+                    anyhow::bail!("expected 'hash wins draws losses'");
+                };
+                Ok((Hash::from_str_radix(hash, 16)?, wdl.parse()?))
+            };
+            let (hash, wdl) = parse().with_context(|| format!("line {} of {}: '{line}'", i + 1, path.display()))?;
+            stats.insert(hash, wdl);
+        }
+        Ok(stats)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut text = String::new();
+        for (hash, wdl) in &self.stats {
+            writeln!(text, "{hash:016x} {} {} {}", wdl.w, wdl.d, wdl.l)?;
+        }
+        fs_err::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        if self.is_enabled() && self.dirty {
+            self.truncate();
+            self.write(&self.experience_file.clone())?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// keep the most played positions
+    pub fn truncate(&mut self) {
+        if self.stats.len() > self.max_entries {
+            let mut by_games = self.stats.iter().map(|(&h, wdl)| (wdl.n(), h)).collect_vec();
+            by_games.sort_by(|a, b| b.cmp(a));
+            for (_, hash) in by_games.into_iter().skip(self.max_entries) {
+                self.stats.remove(&hash);
+            }
+        }
+    }
+
+    /// notes the position reached by our move, and our score
+    pub fn record(&mut self, bd: &Board, mv: Move, score: Score) {
+        if !self.is_enabled() || mv.is_null() {
+            return;
+        }
+        if bd.total_halfmove_ply() < self.max_ply {
+            self.game.push(bd.make_move(mv).hash());
+        }
+        self.last = Some((bd.turn(), score));
+    }
+
+    /// adds the game's result (if known or inferred) to the tallies of the positions reached
+    pub fn finish_game(&mut self, outcome: Option<Outcome>) {
+        let last = self.last.take();
+        let game = std::mem::take(&mut self.game);
+        let wdl = match (outcome, last) {
+            (Some(outcome), _) => ScoreWdl::from_outcome(&outcome),
+            (None, Some((us, score))) => {
+                // mate scores lie beyond every centipawn score
+                let decisive = Score::from_cp(self.decisive_score);
+                let winner = match score {
+                    s if !s.is_finite() => return,
+                    s if s >= decisive => us,
+                    s if s <= -decisive => us.flip_side(),
+                    _ => return,
+                };
+                winner.chooser_wb(ScoreWdl::new(1, 0, 0), ScoreWdl::new(0, 0, 1))
+            }
+            (None, None) => return,
+        };
+        if wdl.n() == 0 || game.is_empty() {
+            return;
+        }
+        for hash in game {
+            *self.stats.entry(hash).or_default() += wdl;
+        }
+        self.dirty = true;
+    }
+
+    /// the expected score for `c` from the position, once played in `min_games` games
+    pub fn score_for(&self, hash: Hash, c: Color) -> Option<f64> {
+        let wdl = self.stats.get(&hash)?;
+        if wdl.n() < self.min_games.max(1) {
+            return None;
+        }
+        let white = wdl.points() / wdl.n() as f64;
+        Some(c.chooser_wb(white, 1.0 - white))
+    }
+
+    /// restricts the root moves to avoid those that have scored badly, returning the moves avoided
+    pub fn avoid(&self, bd: &Board, res: &mut Restrictions) -> MoveList {
+        if !self.is_enabled() || self.stats.is_empty() {
+            return MoveList::new();
+        }
+        let poor = |mv: &Move| {
+            self.score_for(bd.make_move(*mv).hash(), bd.turn())
+                .is_some_and(|score| score < self.min_score)
+        };
+        let candidates = match res.search_moves.is_empty() {
+            true => bd.legal_moves(),
+            false => res.search_moves.clone(),
+        };
+        let (avoided, remaining): (Vec<Move>, Vec<Move>) = candidates.iter().partition(|&mv| poor(mv));
+        if avoided.is_empty() || remaining.is_empty() {
+            return MoveList::new();
+        }
+        res.search_moves = remaining.into();
+        avoided.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_experience_book() {
+        let file = std::env::temp_dir().join(format!("odonata-experience-{}.txt", std::process::id()));
+        let mut book = ExperienceBook {
+            experience_file: file.clone(),
+            min_games: 2,
+            ..ExperienceBook::default()
+        };
+        book.reload().unwrap();
+        let b = Board::starting_pos();
+        let f3 = b.parse_uci_move("f2f3").unwrap();
+        let after_f3 = b.make_move(f3).hash();
+
+        // two games lost after f3, with the result inferred from our last (mate) score
+        for _ in 0..2 {
+            book.record(&b, f3, Score::from_cp(-20));
+            let later = b.make_moves_str("f3 e5").unwrap();
+            book.record(&later, later.parse_uci_move("g2g4").unwrap(), -Score::we_win_in(2));
+            book.set_state(State::NewGame);
+        }
+        assert_eq!(book.stats()[&after_f3], ScoreWdl::new(0, 0, 2));
+        assert_eq!(book.score_for(after_f3, Color::White), Some(0.0));
+
+        // an undecided game is not recorded, a known outcome is
+        book.record(&b, f3, Score::from_cp(-20));
+        book.finish_game(None);
+        assert_eq!(book.stats()[&after_f3].n(), 2);
+        book.record(&b, f3, Score::from_cp(-20));
+        book.finish_game(Some(Outcome::DrawRepetition3));
+        assert_eq!(book.stats()[&after_f3], ScoreWdl::new(0, 1, 2));
+        book.save().unwrap();
+
+        // avoided when next at the starting position, unless the only move
+        let mut reread = ExperienceBook {
+            experience_file: file.clone(),
+            min_games: 2,
+            ..ExperienceBook::default()
+        };
+        reread.reload().unwrap();
+        assert_eq!(reread.stats(), book.stats());
+        let mut res = Restrictions::default();
+        assert_eq!(reread.avoid(&b, &mut res).to_uci(), "f2f3");
+        assert_eq!(res.search_moves.len(), 19);
+        let mut res = Restrictions {
+            search_moves: [f3].into(),
+            ..Restrictions::default()
+        };
+        assert!(reread.avoid(&b, &mut res).is_empty());
+
+        reread.max_entries = 1;
+        reread.truncate();
+        assert_eq!(reread.stats().len(), 1);
+        fs_err::remove_file(file).unwrap();
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use odonata_base::boards::Position;
+use odonata_base::infra::component::{Component, State};
+use odonata_base::prelude::*;
+use rand::Rng;
+
+use crate::search::restrictions::Restrictions;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Lesson {
+    pub drop:  i32, // largest score drop (cp) seen after playing the move
+    pub count: u32, // times the mistake was recorded
+}
+
+/// A persistent (opt-in) record of the engine's own mistakes. After each root search the
+/// score is compared with that of our previous move in the same game - a sharp drop marks
+/// the previous choice (keyed by position hash and move) as a lesson. In later games each
+/// learned move is removed from the root move list at random, more often the larger its
+/// drop and count, so a mistake is played less often but can still be retried. A move is
+/// never removed if no alternative would remain.
+///
+/// The file is plain text, one lesson per line "hash move drop count", saved after each
+/// game and bounded to the `max_entries` largest drops.
+#[derive(Clone, Debug)]
+pub struct Learning {
+    pub learning_file: PathBuf,
+    pub max_entries:   usize,
+    pub score_drop:    i32,
+
+    lessons: BTreeMap<(Hash, String), Lesson>,
+    last:    Option<(Hash, Move, Score)>,
+    dirty:   bool,
+}
+
+impl Default for Learning {
+    fn default() -> Self {
+        Self {
+            learning_file: PathBuf::new(),
+            max_entries:   10_000,
+            score_drop:    150,
+            lessons:       BTreeMap::new(),
+            last:          None,
+            dirty:         false,
+        }
+    }
+}
+
+impl Configurable for Learning {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.learning_file.set(p.get("learning_file"))?;
+        self.max_entries.set(p.get("max_entries"))?;
+        self.score_drop.set(p.get("score_drop"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for Learning {
+    fn set_state(&mut self, s: State) {
+        use State::*;
+        match s {
+            NewGame | Shutdown => {
+                if let Err(e) = self.save() {
+                    warn!("failed to save learning file: {e:#}");
+                }
+                self.last = None;
+            }
+            SetPosition => {}
+            StartSearch => {}
+            EndSearch => {}
+            StartDepthIteration(_) => {}
+        }
+    }
+
+    fn new_game(&mut self) {}
+
+    fn new_position(&mut self) {}
+}
+
+impl fmt::Display for Learning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+impl Learning {
+    pub fn is_enabled(&self) -> bool {
+        !self.learning_file.as_os_str().is_empty()
+    }
+
+    pub fn lessons(&self) -> &BTreeMap<(Hash, String), Lesson> {
+        &self.lessons
+    }
+
+    /// (re)reads the learning file, a missing file is an empty one
+    pub fn reload(&mut self) -> Result<()> {
+        self.lessons.clear();
+        self.dirty = false;
+        if self.is_enabled() && self.learning_file.exists() {
+            self.lessons = Self::read(&self.learning_file)?;
+        }
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<BTreeMap<(Hash, String), Lesson>> {
+        let text = fs_err::read_to_string(path)?;
+        let mut lessons = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse = || -> Result<((Hash, String), Lesson)> {
+                let [hash, mv, drop, count] = line.split_whitespace().collect_vec()[..] else {
+                    anyhow::bail!("expected 'hash move drop count'");
+                };
+                let hash = Hash::from_str_radix(hash, 16)?;
+                let lesson = Lesson {
+                    drop:  drop.parse()?,
+                    count: count.parse()?,
+                };
+                Ok(((hash, mv.to_string()), lesson))
+            };
+            let (key, lesson) = parse().with_context(|| format!("line {} of {}: '{line}'", i + 1, path.display()))?;
+            lessons.insert(key, lesson);
+        }
+        Ok(lessons)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut text = String::new();
+        for ((hash, mv), l) in &self.lessons {
+            writeln!(text, "{hash:016x} {mv} {drop} {count}", drop = l.drop, count = l.count)?;
+        }
+        fs_err::write(path, text)?;
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        if self.is_enabled() && self.dirty {
+            self.truncate();
+            self.write(&self.learning_file.clone())?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// combines lessons (typically read from another engine's learning file)
+    pub fn merge(&mut self, lessons: BTreeMap<(Hash, String), Lesson>) {
+        for (key, l) in lessons {
+            let e = self.lessons.entry(key).or_insert(Lesson { drop: 0, count: 0 });
+            e.drop = e.drop.max(l.drop);
+            e.count += l.count;
+        }
+        self.truncate();
+        self.dirty = true;
+    }
+
+    /// keep the largest drops
+    pub fn truncate(&mut self) {
+        if self.lessons.len() > self.max_entries {
+            let mut by_drop = self
+                .lessons
+                .iter()
+                .map(|(k, l)| (l.drop, l.count, k.clone()))
+                .collect_vec();
+            by_drop.sort_by(|a, b| b.cmp(a));
+            for (.., key) in by_drop.into_iter().skip(self.max_entries) {
+                self.lessons.remove(&key);
+            }
+        }
+    }
+
+    /// compares the root score with that of our previous move this game
+    pub fn record(&mut self, pos: &Position, mv: Move, score: Score) {
+        if !self.is_enabled() || mv.is_null() {
+            return;
+        }
+        if let Some((hash, prev_mv, prev)) = self.last.take() {
+            // our previous root is one or two plies back (unless the gui changed games)
+            let continues = pos
+                .played_history()
+                .iter()
+                .rev()
+                .take(2)
+                .any(|(b, _, m)| b.hash() == hash && *m == prev_mv);
+            if let (true, Some(prev), Some(cp)) = (continues, prev.cp(), score.cp()) {
+                let drop = prev as i32 - cp as i32;
+                if drop >= self.score_drop {
+                    debug!("learning: {prev_mv} at {hash:016x} dropped {drop}cp");
+                    self.merge([((hash, prev_mv.to_uci()), Lesson { drop, count: 1 })].into());
+                }
+            }
+        }
+        self.last = Some((pos.board().hash(), mv, score));
+    }
+
+    /// the chance of avoiding a learned move, weighting its drop by the times it was recorded
+    pub fn avoid_chance(&self, l: &Lesson) -> f64 {
+        let weight = l.drop.max(0) as f64 * l.count as f64;
+        weight / (weight + self.score_drop.max(1) as f64)
+    }
+
+    /// restricts the root moves to avoid learned mistakes (by chance), returning the moves avoided
+    pub fn avoid(&self, bd: &Board, res: &mut Restrictions, rng: &mut impl Rng) -> MoveList {
+        if !self.is_enabled() {
+            return MoveList::new();
+        }
+        let hash = bd.hash();
+        let mut learned = |mv: &Move| {
+            self.lessons
+                .get(&(hash, mv.to_uci()))
+                .is_some_and(|l| rng.gen_bool(self.avoid_chance(l)))
+        };
+        let candidates = match res.search_moves.is_empty() {
+            true => bd.legal_moves(),
+            false => res.search_moves.clone(),
+        };
+        let (avoided, remaining): (Vec<Move>, Vec<Move>) = candidates.iter().partition(|&&mv| learned(&mv));
+        if avoided.is_empty() || remaining.is_empty() {
+            return MoveList::new();
+        }
+        res.search_moves = remaining.into();
+        avoided.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng as _;
+    use rand_chacha::ChaChaRng;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_learning() {
+        let file = std::env::temp_dir().join(format!("odonata-learning-{}.txt", std::process::id()));
+        let mut learn = Learning {
+            learning_file: file.clone(),
+            ..Learning::default()
+        };
+        learn.reload().unwrap();
+        assert!(learn.lessons().is_empty());
+
+        // we play e4, then after d5 the score collapses
+        let mut pos = Position::starting_pos();
+        let e4 = pos.board().parse_uci_move("e2e4").unwrap();
+        learn.record(&pos, e4, Score::from_cp(30));
+        pos.push_moves_str("e2e4 d7d5").unwrap();
+        pos.play_search_moves();
+        let exd5 = pos.board().parse_uci_move("e4d5").unwrap();
+        learn.record(&pos, exd5, Score::from_cp(-200));
+        assert_eq!(learn.lessons().len(), 1);
+
+        // saved on new game, and avoided when next at the starting position
+        learn.set_state(State::NewGame);
+        let mut reread = Learning {
+            learning_file: file.clone(),
+            ..Learning::default()
+        };
+        reread.reload().unwrap();
+        assert_eq!(reread.lessons(), learn.lessons());
+        let mut rng = ChaChaRng::seed_from_u64(1);
+        let mut avoided_count = 0;
+        for _ in 0..1000 {
+            let mut res = Restrictions::default();
+            let avoided = reread.avoid(&Board::starting_pos(), &mut res, &mut rng);
+            if !avoided.is_empty() {
+                assert_eq!(avoided.to_uci(), "e2e4");
+                assert_eq!(res.search_moves.len(), 19);
+                avoided_count += 1;
+            }
+        }
+        // a 230cp drop is avoided 230 / (230 + 150) of the time, so e4 is still sometimes played
+        assert!((500..700).contains(&avoided_count), "{avoided_count}");
+
+        // repeated mistakes are avoided more often
+        let lesson = *reread.lessons().values().next().unwrap();
+        let repeated = Lesson { count: 3, ..lesson };
+        assert!(reread.avoid_chance(&repeated) > reread.avoid_chance(&lesson));
+
+        // never avoid the only move
+        let mut res = Restrictions {
+            search_moves: [e4].into(),
+            ..Restrictions::default()
+        };
+        assert!(reread.avoid(&Board::starting_pos(), &mut res, &mut rng).is_empty());
+        assert_eq!(res.search_moves.len(), 1);
+
+        // merge sums counts, and the file is bounded
+        let lessons = reread.lessons().clone();
+        reread.merge(lessons);
+        assert_eq!(reread.lessons().values().next().unwrap().count, 2);
+        reread.max_entries = 0;
+        reread.truncate();
+        assert!(reread.lessons().is_empty());
+        fs_err::remove_file(file).unwrap();
+    }
+}
@@ -1,2 +1,6 @@
+pub mod analysis;
+pub mod experience;
+pub mod learning;
 pub mod opening_book;
 pub mod polyglot;
+pub mod prep;
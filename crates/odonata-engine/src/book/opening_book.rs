@@ -3,19 +3,32 @@ use std::path::PathBuf;
 
 use odonata_base::infra::component::{Component, State};
 use odonata_base::prelude::*;
-use rand::seq::SliceRandom;
 use rand::thread_rng;
 use serde::{Deserialize, Serialize};
 
 use super::polyglot::Polyglot;
 use crate::search::restrictions::Restrictions;
 
+/// Book usage adapts to the time control. At fast time controls the book is followed as
+/// deep as it goes, but is abandoned once the static eval strays more than `exit_margin`
+/// from the (level) book expectation. At long time controls book depth is capped at
+/// `max_ply`, and each book move must survive a shallow search of `verify_nodes` before
+/// being played.
+///
+/// Book moves are probed from a polyglot (.bin) `book_file` by the position's polyglot key.
+/// With `best_book_line` the most heavily weighted move is played, otherwise a move is
+/// chosen at random with chances in proportion to the book weights.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct OpeningBook {
     pub own_book:       bool,
     pub book_file:      PathBuf,
     pub best_book_line: bool,
+    pub max_ply:        Ply,
+    pub verify_nodes:   u64,
+    pub verify_margin:  i32, // cp a book move may trail the searched best move by
+    pub exit_margin:    i32, // cp the static eval may stray from the book expectation
+    pub fast_tc_secs:   f32, // estimated time per move, below which the time control is fast
 
     #[serde(skip)]
     book_exhausted: Cell<bool>,
@@ -30,6 +43,11 @@ impl Default for OpeningBook {
             own_book:       false,
             book_file:      PathBuf::new(),
             best_book_line: true,
+            max_ply:        24,
+            verify_nodes:   20_000,
+            verify_margin:  50,
+            exit_margin:    150,
+            fast_tc_secs:   2.0,
             book_exhausted: Cell::new(false),
             polyglot:       Polyglot::new(),
         }
@@ -41,6 +59,11 @@ impl Configurable for OpeningBook {
         self.own_book.set(p.get("own_book"))?;
         self.book_file.set(p.get("book_file"))?;
         self.best_book_line.set(p.get("best_book_line"))?;
+        self.max_ply.set(p.get("max_ply"))?;
+        self.verify_nodes.set(p.get("verify_nodes"))?;
+        self.verify_margin.set(p.get("verify_margin"))?;
+        self.exit_margin.set(p.get("exit_margin"))?;
+        self.fast_tc_secs.set(p.get("fast_tc_secs"))?;
         Ok(p.is_modified())
     }
 }
@@ -67,34 +90,132 @@ impl Component for OpeningBook {
 
 impl OpeningBook {
     pub fn reload(&mut self) -> anyhow::Result<()> {
+        self.polyglot.clear();
         if self.own_book && !self.book_file.as_os_str().is_empty() {
             self.polyglot.load(&self.book_file)?;
         }
         Ok(())
     }
 
-    pub fn lookup(&self, board: &Board, res: &Restrictions) -> Option<Move> {
+    /// the memory held by the loaded book, none if not in use
+    pub fn size_in_bytes(&self) -> usize {
+        self.polyglot.size_in_bytes()
+    }
+
+    /// clock based time controls with little time per move (assuming 30 moves to go)
+    pub fn is_fast(&self, tc: &TimeControl) -> bool {
+        let secs = match tc {
+            TimeControl::SearchTime(d) => d.as_secs_f32(),
+            TimeControl::UciFischer(rt) => {
+                let (time, inc) = rt.our_time_and_inc();
+                let moves = match rt.moves_to_go {
+                    0 => 30,
+                    n => n.min(30),
+                };
+                time.as_secs_f32() / moves as f32 + inc.as_secs_f32()
+            }
+            _ => return false,
+        };
+        secs < self.fast_tc_secs
+    }
+
+    /// at fast time controls, a position the book expects to be level but which evaluates
+    /// as sharply unbalanced means we have left the book's main lines, so we exit the book
+    pub fn exit_on_eval(&self, eval: Score) -> bool {
+        let surprised = eval.cp().map_or(true, |cp| (cp as i32).abs() > self.exit_margin);
+        if surprised {
+            self.book_exhausted.set(true);
+        }
+        surprised
+    }
+
+    /// the book move is played if it scores within `verify_margin` of the searched best move
+    pub fn is_verified(&self, book_score: Score, best_score: Score) -> bool {
+        book_score.is_finite()
+            && best_score.is_finite()
+            && book_score >= best_score - Score::from_cp(self.verify_margin)
+    }
+
+    pub fn lookup(&self, board: &Board, res: &Restrictions, fast: bool) -> Option<Move> {
         if !self.own_book || self.book_file.as_os_str().is_empty() || self.book_exhausted.get() {
             return None;
         }
-        let entries = self.polyglot.find_best_matching(board, res).collect_vec();
+        if !fast && board.total_halfmove_ply() >= self.max_ply {
+            self.book_exhausted.set(true);
+            return None;
+        }
+        let entry = if self.best_book_line {
+            self.polyglot.find_best_matching(board, res).next()
+        } else {
+            self.polyglot.choose_weighted(board, res, &mut thread_rng())
+        };
 
-        if entries.is_empty() {
+        let Some(entry) = entry else {
             if res.is_none() {
                 // as long as multi-pv not being applied,
                 // we can flag book as exhaused
                 self.book_exhausted.set(true);
             }
             return None;
-        }
+        };
+        Some(entry.calc_move(board))
+    }
+}
 
-        if self.best_book_line {
-            // take the first
-            Some(entries.first().unwrap().calc_move(board))
-        } else {
-            // randomly select one of the best
-            let mut rng = thread_rng();
-            Some(entries.choose(&mut rng).unwrap().calc_move(board))
-        }
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_opening_book_policy() {
+        let mut book = OpeningBook {
+            own_book: true,
+            book_file: [env!("CARGO_MANIFEST_DIR"), "/../../ext/books/gm2001.bin"]
+                .concat()
+                .into(),
+            ..OpeningBook::default()
+        };
+        book.reload().unwrap();
+
+        assert!(book.is_fast(&TimeControl::from_move_time_millis(500)));
+        assert!(!book.is_fast(&TimeControl::from_move_time_millis(10_000)));
+        assert!(book.is_fast(&TimeControl::from_game_time_secs(30)));
+        assert!(!book.is_fast(&TimeControl::from_game_time_secs(600)));
+        assert!(!book.is_fast(&TimeControl::Depth(5)));
+        assert!(!book.is_fast(&TimeControl::SearchTime(Duration::from_secs(5))));
+
+        let res = Restrictions::default();
+        let board = Board::starting_pos();
+        assert!(book.lookup(&board, &res, false).is_some());
+        book.best_book_line = false;
+        assert!(book.lookup(&board, &res, false).is_some());
+        book.best_book_line = true;
+
+        // reloading replaces rather than adds to the entries
+        book.reload().unwrap();
+        let n = book.polyglot.find_all().count();
+        book.reload().unwrap();
+        assert_eq!(book.polyglot.find_all().count(), n);
+
+        // at long time controls the book depth is capped
+        book.max_ply = 0;
+        assert!(book.lookup(&board, &res, true).is_some());
+        assert!(book.lookup(&board, &res, false).is_none());
+        assert!(book.lookup(&board, &res, true).is_none(), "book exhausted");
+
+        // a surprising eval exits the book
+        book.set_state(State::NewGame);
+        assert!(!book.exit_on_eval(Score::from_cp(40)));
+        assert!(book.lookup(&board, &res, true).is_some());
+        assert!(book.exit_on_eval(Score::from_cp(-400)));
+        assert!(book.lookup(&board, &res, true).is_none());
+
+        assert!(book.is_verified(Score::from_cp(10), Score::from_cp(50)));
+        assert!(!book.is_verified(Score::from_cp(-100), Score::from_cp(50)));
+        assert!(!book.is_verified(Score::INFINITY, Score::from_cp(50)));
     }
 }
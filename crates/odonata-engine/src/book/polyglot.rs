@@ -11,6 +11,8 @@ use odonata_base::piece::{Hash, Ply};
 use odonata_base::prelude::Board;
 use odonata_base::variation::Variation;
 use odonata_base::{Color, Piece};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
 use serde::Deserializer;
 
 use crate::search::restrictions::Restrictions;
@@ -147,19 +149,9 @@ impl PolyglotEntry {
         }
     }
 
+    /// polyglot castles are king-takes-rook, as are our own
     pub fn validate_move(&self, bd: &Board) -> anyhow::Result<Move> {
-        let mut bm = self.bare_move();
-        if bm.from.is_in(bd.kings()) && bm.promo.is_none() {
-            let to = match (bm.from, bm.to) {
-                (Square::E1, Square::H1) => Square::G1,
-                (Square::E1, Square::A1) => Square::C1,
-                (Square::E8, Square::H8) => Square::G8,
-                (Square::E8, Square::A8) => Square::C8,
-                _ => bm.to,
-            };
-            bm.to = to;
-        }
-        match bm.validate(bd) {
+        match self.bare_move().validate(bd) {
             Err(s) => Err(anyhow::anyhow!(s)),
             Ok(mv) => Ok(mv),
         }
@@ -208,25 +200,33 @@ impl Polyglot {
         Ok(())
     }
 
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.entries.len() * std::mem::size_of::<PolyglotEntry>()
+    }
+
     pub fn find_all(&self) -> impl Iterator<Item = &PolyglotEntry> + '_ {
         self.entries.iter()
     }
 
+    /// the entries for a polyglot key (see [`Polyglot::hash_board`]), by binary search
+    pub fn probe(&self, key: Hash) -> &[PolyglotEntry] {
+        let start = self.entries.partition_point(|entry| entry.hash < key);
+        let len = self.entries[start..].partition_point(|entry| entry.hash == key);
+        &self.entries[start..start + len]
+    }
+
     pub fn find_matching<'a>(
         &'a self,
         bd: &'a Board,
         res: &'a Restrictions,
     ) -> impl Iterator<Item = &PolyglotEntry> + '_ {
-        // the elements are ordered so we can optimise better than a linear filter
-        // such as .filter(move |entry| entry.hash == board_hash)
-        let board_hash = self.hash_board(bd);
-        let iter = self
-            .entries
+        self.probe(self.hash_board(bd))
             .iter()
-            .skip_while(move |entry| entry.hash != board_hash)
-            .take_while(move |entry| entry.hash == board_hash)
-            .filter(move |entry| !res.skip_move(0, entry.calc_move(bd)));
-        iter
+            .filter(move |entry| !res.skip_move(0, entry.calc_move(bd)))
     }
 
     pub fn find_best_matching<'a>(
@@ -237,6 +237,14 @@ impl Polyglot {
         self.find_matching(b, res).max_set_by_key(|ent| ent.weight).into_iter()
     }
 
+    /// a random entry, with chances in proportion to the weights. Zero weight entries (by
+    /// polyglot convention, moves not to be played) are never chosen.
+    pub fn choose_weighted(&self, b: &Board, res: &Restrictions, rng: &mut impl Rng) -> Option<&PolyglotEntry> {
+        let entries = self.find_matching(b, res).collect_vec();
+        let dist = WeightedIndex::new(entries.iter().map(|e| e.weight)).ok()?;
+        Some(entries[dist.sample(rng)])
+    }
+
     pub fn perft(&self, init: &Board, res: &Restrictions, d: Ply, f: &mut impl FnMut(&Variation)) {
         self.perft_var(init, res, &Variation::new(), d, f);
     }
@@ -631,6 +639,38 @@ mod tests {
         println!("Moves from {b} {}", mvs.format(", "));
     }
 
+    #[test]
+    fn test_polyglot_weighted() {
+        use rand::SeedableRng as _;
+        use rand_chacha::ChaChaRng;
+
+        let mut polyglot = Polyglot::new();
+        polyglot
+            .load([env!("CARGO_MANIFEST_DIR"), "/../../ext/books/gm2001.bin"].concat())
+            .unwrap();
+        let b = Board::starting_pos();
+        let res = Restrictions::none();
+        let key = polyglot.hash_board(&b);
+        assert_eq!(polyglot.probe(key).len(), polyglot.find_matching(&b, &res).count());
+        assert!(polyglot.probe(key).iter().all(|e| e.hash == key));
+        assert!(polyglot.probe(key ^ 1).is_empty());
+
+        let mut rng = ChaChaRng::seed_from_u64(1);
+        let mut counts = std::collections::HashMap::new();
+        for _ in 0..1000 {
+            let e = polyglot.choose_weighted(&b, &res, &mut rng).unwrap();
+            assert!(e.weight > 0);
+            *counts.entry(e.mv).or_insert(0) += 1;
+        }
+        let best = polyglot.find_best_matching(&b, &res).next().unwrap();
+        let most_chosen = counts.iter().max_by_key(|(_, &n)| n).unwrap().0;
+        assert_eq!(*most_chosen, best.mv);
+        assert!(counts.len() > 1);
+
+        let empty = Board::parse_fen("k7/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(polyglot.choose_weighted(&empty, &res, &mut rng).is_none());
+    }
+
     #[test]
     fn test_polyglot_read() {
         let bytes1 = [
@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use odonata_base::infra::component::{Component, State};
+use odonata_base::prelude::*;
+
+use crate::comms::pgn::PgnGame;
+use crate::search::restrictions::Restrictions;
+
+/// game results from the opponent's point of view
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins:   u32,
+    pub draws:  u32,
+    pub losses: u32,
+}
+
+impl Tally {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// the opponent's score, 1.0 for all wins through 0.0 for all losses
+    pub fn score(&self) -> f32 {
+        match self.games() {
+            0 => 0.5,
+            n => (self.wins as f32 + 0.5 * self.draws as f32) / n as f32,
+        }
+    }
+
+    fn add(&mut self, result: &str, opponent: Color) {
+        match (result, opponent) {
+            ("1-0", Color::White) | ("0-1", Color::Black) => self.wins += 1,
+            ("1-0", Color::Black) | ("0-1", Color::White) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrepEntry {
+    pub tally:            Tally,
+    pub line:             String, // san moves from the start, up to and including this move
+    pub opponent_to_move: bool,
+}
+
+/// Opponent-specific preparation (opt-in).
+///
+/// Games of a named opponent are read from a PGN archive, and the moves played in the first
+/// `max_ply` plies tallied (keyed by position hash and move) with the opponent's results.
+/// The opponent's own moves form their repertoire, and those they score badly with are their
+/// typical mistakes. At our turn, within the first `max_ply` plies, the root moves are limited
+/// to those that have held the opponent to at most `max_score` over `min_games` or more games.
+/// As the book honours the root restrictions, book selection is biased in the same way.
+#[derive(Clone, Debug)]
+pub struct Prep {
+    pub prep_file: PathBuf,
+    pub opponent:  String,
+    pub max_ply:   Ply,
+    pub min_games: u32,
+    pub max_score: f32,
+
+    entries: BTreeMap<(Hash, String), PrepEntry>,
+    games:   [u32; 2],
+}
+
+impl Default for Prep {
+    fn default() -> Self {
+        Self {
+            prep_file: PathBuf::new(),
+            opponent:  String::new(),
+            max_ply:   16,
+            min_games: 2,
+            max_score: 0.4,
+            entries:   BTreeMap::new(),
+            games:     [0; 2],
+        }
+    }
+}
+
+impl Configurable for Prep {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.prep_file.set(p.get("prep_file"))?;
+        self.opponent.set(p.get("opponent"))?;
+        self.max_ply.set(p.get("max_ply"))?;
+        self.min_games.set(p.get("min_games"))?;
+        self.max_score.set(p.get("max_score"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for Prep {
+    fn set_state(&mut self, s: State) {
+        use State::*;
+        match s {
+            NewGame => {}
+            SetPosition => {}
+            StartSearch => {}
+            EndSearch => {}
+            StartDepthIteration(_) => {}
+            Shutdown => {}
+        }
+    }
+
+    fn new_game(&mut self) {}
+
+    fn new_position(&mut self) {}
+}
+
+/// the prep report
+impl fmt::Display for Prep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let games = self.games[Color::White] + self.games[Color::Black];
+        writeln!(
+            f,
+            "prep vs '{opp}': {games} games ({w} as white, {b} as black)",
+            opp = self.opponent,
+            w = self.games[Color::White],
+            b = self.games[Color::Black]
+        )?;
+        let row = |f: &mut fmt::Formatter, e: &PrepEntry| {
+            writeln!(
+                f,
+                "  {games:>5} {freq:>5.1}% {score:>5.2}  {line}",
+                games = e.tally.games(),
+                freq = 100.0 * e.tally.games() as f32 / games.max(1) as f32,
+                score = e.tally.score(),
+                line = e.line
+            )
+        };
+
+        writeln!(f, "\nrepertoire (games, frequency, opponent score, line)")?;
+        let mut repertoire = self.entries.values().filter(|e| e.opponent_to_move).collect_vec();
+        repertoire.sort_by_key(|e| (std::cmp::Reverse(e.tally.games()), e.line.len()));
+        for e in repertoire.iter().take(20) {
+            row(f, e)?;
+        }
+
+        writeln!(f, "\ntypical mistakes")?;
+        for e in self.mistakes() {
+            row(f, e)?;
+        }
+
+        writeln!(f, "\nprepared lines")?;
+        let mut prepared = self
+            .entries
+            .values()
+            .filter(|e| !e.opponent_to_move && self.is_good(e))
+            .collect_vec();
+        prepared.sort_by(|a, b| a.line.cmp(&b.line));
+        for e in prepared {
+            row(f, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Prep {
+    pub fn is_enabled(&self) -> bool {
+        !self.prep_file.as_os_str().is_empty() && !self.opponent.is_empty()
+    }
+
+    pub fn entries(&self) -> &BTreeMap<(Hash, String), PrepEntry> {
+        &self.entries
+    }
+
+    /// the name from a uci "UCI_Opponent" value such as "GM 2800 human Gary Kasparov"
+    pub fn parse_uci_opponent(value: &str) -> String {
+        value.splitn(4, ' ').nth(3).unwrap_or_default().trim().to_string()
+    }
+
+    /// (re)reads the opponent's games from the prep file
+    pub fn reload(&mut self) -> Result<()> {
+        self.entries.clear();
+        self.games = [0; 2];
+        if self.is_enabled() {
+            let pgn = fs_err::read_to_string(&self.prep_file)?;
+            let games = PgnGame::parse_many(&pgn).with_context(|| format!("reading {}", self.prep_file.display()))?;
+            self.add_games(&games);
+            let n = self.games[Color::White] + self.games[Color::Black];
+            info!(target: "eng", "prep: {n} games of '{opp}'", opp = self.opponent);
+        }
+        Ok(())
+    }
+
+    /// adds those games where the opponent played (matched case-insensitively on name)
+    pub fn add_games(&mut self, games: &[PgnGame]) {
+        let name = self.opponent.to_lowercase();
+        let plays = |g: &PgnGame, tag: &str| g.header(tag).is_some_and(|n| n.to_lowercase().contains(&name));
+        for g in games {
+            let opponent = match (plays(g, "White"), plays(g, "Black")) {
+                (true, false) => Color::White,
+                (false, true) => Color::Black,
+                _ => continue,
+            };
+            self.games[opponent] += 1;
+            let mut b = g.starting.clone();
+            for (i, mv) in g.moves.moves().enumerate() {
+                if b.total_halfmove_ply() >= self.max_ply {
+                    break;
+                }
+                let e = self
+                    .entries
+                    .entry((b.hash(), mv.to_uci()))
+                    .or_insert_with(|| PrepEntry {
+                        tally:            Tally::default(),
+                        line:             g.moves.take(i + 1).to_san(&g.starting),
+                        opponent_to_move: b.color_us() == opponent,
+                    });
+                e.tally.add(&g.result, opponent);
+                b = b.make_move(mv);
+            }
+        }
+    }
+
+    fn is_good(&self, e: &PrepEntry) -> bool {
+        e.tally.games() >= self.min_games && e.tally.score() <= self.max_score
+    }
+
+    /// opponent moves which have scored badly for them, worst first
+    pub fn mistakes(&self) -> Vec<&PrepEntry> {
+        let mut mistakes = self
+            .entries
+            .values()
+            .filter(|e| e.opponent_to_move && self.is_good(e))
+            .collect_vec();
+        mistakes.sort_by(|a, b| {
+            a.tally
+                .score()
+                .total_cmp(&b.tally.score())
+                .then_with(|| a.line.cmp(&b.line))
+        });
+        mistakes
+    }
+
+    /// restricts the root moves to those scoring well against the opponent, best first,
+    /// returning the moves chosen (empty if the position is unprepared)
+    pub fn bias(&self, bd: &Board, res: &mut Restrictions) -> MoveList {
+        if !self.is_enabled() || bd.total_halfmove_ply() >= self.max_ply {
+            return MoveList::new();
+        }
+        let candidates = match res.search_moves.is_empty() {
+            true => bd.legal_moves(),
+            false => res.search_moves.clone(),
+        };
+        let mut prepared = candidates
+            .iter()
+            .filter_map(|&mv| {
+                let e = self.entries.get(&(bd.hash(), mv.to_uci()))?;
+                (!e.opponent_to_move && self.is_good(e)).then_some((e.tally.score(), mv))
+            })
+            .collect_vec();
+        if prepared.is_empty() {
+            return MoveList::new();
+        }
+        prepared.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let chosen: MoveList = prepared.into_iter().map(|(_, mv)| mv).collect_vec().into();
+        res.search_moves = chosen.clone();
+        chosen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_prep() -> Result<()> {
+        let pgn = r#"
+[White "Kasparov, G"]
+[Black "X"]
+[Result "0-1"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+
+[White "Y"]
+[Black "Kasparov, G"]
+[Result "1-0"]
+
+1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0
+
+[White "Kasparov, G"]
+[Black "Z"]
+[Result "0-1"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+
+[White "Y"]
+[Black "Z"]
+[Result "1-0"]
+
+1. d4 d5 1-0
+"#;
+        let mut prep = Prep {
+            opponent: "kasparov".to_string(),
+            ..Prep::default()
+        };
+        prep.add_games(&PgnGame::parse_many(pgn)?);
+        assert_eq!(prep.games, [2, 1]);
+        assert_eq!(prep.mistakes().len(), 2, "{prep}");
+        assert_eq!(prep.mistakes()[0].line, "f3");
+        println!("{prep}");
+
+        // enabled by a prep file, even if unread
+        prep.prep_file = PathBuf::from("unused.pgn");
+        let b = Board::starting_pos().make_move(Board::starting_pos().parse_uci_move("f2f3")?);
+        let mut res = Restrictions::default();
+        assert_eq!(prep.bias(&b, &mut res).to_uci(), "e7e5");
+        assert_eq!(res.search_moves.to_uci(), "e7e5");
+
+        // not below min games, and never beyond max ply
+        prep.min_games = 3;
+        assert!(prep.bias(&b, &mut Restrictions::default()).is_empty());
+        prep.min_games = 2;
+        prep.max_ply = 1;
+        assert!(prep.bias(&b, &mut Restrictions::default()).is_empty());
+
+        assert_eq!(Prep::parse_uci_opponent("GM 2800 human Gary Kasparov"), "Gary Kasparov");
+        assert_eq!(Prep::parse_uci_opponent("none"), "");
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+
+use odonata_base::prelude::*;
+
+use crate::cache::tt2::TranspositionTable2;
+
+const MB: usize = 1_000_000;
+
+/// The memory of each component, in bytes. The tt is shared, but each search (the main
+/// one, plus one per thread) holds its own eval cache and copy of the book.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemorySplit {
+    pub searches:    usize,
+    pub tt:          usize,
+    pub eval_caches: usize, // all searches
+    pub book:        usize, // all searches
+}
+
+impl MemorySplit {
+    pub fn total(&self) -> usize {
+        self.tt + self.eval_caches + self.book
+    }
+
+    /// the eval cache of each search
+    pub fn eval_cache(&self) -> usize {
+        self.eval_caches / self.searches.max(1)
+    }
+}
+
+impl fmt::Display for MemorySplit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mb = |bytes: usize| bytes as f64 / MB as f64;
+        write!(
+            f,
+            "tt {:.1}MB, eval caches {} x {:.1}MB, book {:.1}MB, tablebases memory mapped",
+            mb(self.tt),
+            self.searches,
+            mb(self.eval_cache()),
+            mb(self.book),
+        )
+    }
+}
+
+/// A cap on the memory of the tt, eval caches and opening book together (the
+/// `MemoryLimitMB` uci option), so that an engine in a memory-limited container isn't
+/// killed for a large Hash setting. Zero for no limit.
+///
+/// The book can't be resized, so is taken first. The tt and eval caches keep their
+/// configured sizes if they fit in what is left, and are otherwise scaled down in
+/// proportion. A limit too small for the book plus a minimal tt is refused. Syzygy tables
+/// are memory mapped from file, so are left to the operating system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub limit_mb: i64,
+}
+
+impl MemoryBudget {
+    const MIN_TT_MB: i64 = 2;
+
+    /// the split within the limit, given the split as configured
+    pub fn allocate(&self, configured: &MemorySplit) -> Result<MemorySplit> {
+        if self.limit_mb <= 0 {
+            return Ok(configured.clone());
+        }
+        let limit = self.limit_mb as usize * MB;
+        let min_tt = TranspositionTable2::bytes_for_mb(Self::MIN_TT_MB);
+        anyhow::ensure!(
+            configured.book + min_tt <= limit,
+            "MemoryLimitMB {} is below the {:.1}MB of book (over {} searches) plus a {}MB tt",
+            self.limit_mb,
+            configured.book as f64 / MB as f64,
+            configured.searches,
+            Self::MIN_TT_MB
+        );
+        if configured.total() <= limit {
+            return Ok(configured.clone());
+        }
+        let scale = (limit - configured.book) as f64 / (configured.tt + configured.eval_caches) as f64;
+        let tt = TranspositionTable2::mb_within((configured.tt as f64 * scale) as usize).max(Self::MIN_TT_MB);
+        let tt = TranspositionTable2::bytes_for_mb(tt);
+        // a tt held up at its minimum leaves the eval caches less than their scaled share
+        let eval_caches = ((configured.eval_caches as f64 * scale) as usize).min(limit - configured.book - tt);
+        Ok(MemorySplit {
+            tt,
+            eval_caches,
+            ..configured.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let configured = MemorySplit {
+            searches:    4,
+            tt:          TranspositionTable2::bytes_for_mb(1000),
+            eval_caches: 4 * 50 * MB,
+            book:        4 * 5 * MB,
+        };
+        println!("{configured}");
+        assert_eq!(MemoryBudget::default().allocate(&configured).unwrap(), configured);
+
+        // room enough
+        let budget = MemoryBudget { limit_mb: 10_000 };
+        assert_eq!(budget.allocate(&configured).unwrap(), configured);
+
+        // scaled down, keeping the book
+        let budget = MemoryBudget { limit_mb: 500 };
+        let split = budget.allocate(&configured).unwrap();
+        println!("{split}");
+        assert!(split.total() <= 500 * MB, "{split}");
+        assert_eq!(split.book, configured.book);
+        assert!(split.tt < configured.tt && split.tt > 100 * MB, "{split}");
+        assert!(split.eval_cache() < 50 * MB && split.eval_cache() > 5 * MB, "{split}");
+        assert!(split.to_string().contains("eval caches 4 x"));
+
+        // just room for the book and a minimal tt, the tt held at its minimum above its scaled share
+        let large_caches = MemorySplit {
+            tt: TranspositionTable2::bytes_for_mb(16),
+            eval_caches: 4 * 100 * MB,
+            ..configured.clone()
+        };
+        let budget = MemoryBudget { limit_mb: 23 };
+        let split = budget.allocate(&large_caches).unwrap();
+        assert!(split.total() <= 23 * MB, "{split}");
+        assert_eq!(split.tt, TranspositionTable2::bytes_for_mb(MemoryBudget::MIN_TT_MB));
+        assert_eq!(split.book, configured.book);
+
+        // refused when the book alone won't fit
+        let budget = MemoryBudget { limit_mb: 20 };
+        assert!(budget.allocate(&configured).is_err());
+    }
+}
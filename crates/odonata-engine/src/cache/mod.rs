@@ -1 +1,2 @@
+pub mod memory_budget;
 pub mod tt2;
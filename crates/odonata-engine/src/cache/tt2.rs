@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt;
 use std::sync::Arc;
 
@@ -6,7 +7,9 @@ use odonata_base::domain::BoundType;
 use odonata_base::infra::component::{Component, State};
 use odonata_base::infra::lockless_hashmap::HashEntry;
 use odonata_base::infra::metric::Metrics;
+use odonata_base::infra::paranoid;
 use odonata_base::mv::BareMove;
+use odonata_base::piece::MAX_PLY;
 use odonata_base::prelude::*;
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumString;
@@ -19,28 +22,37 @@ pub struct TtNode {
     pub bt:    BoundType,
     pub bm:    BareMove,
     pub eval:  Score,
+    pub ghi:   bool, // score depended on a path-specific draw (repetition or 50-move rule)
 }
 
-/// TtScore has mate scores relative to current ply, NOT to root board
+/// TtScore has mate (and tablebase) scores relative to current ply, NOT to root board
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct TtScore(Score);
 
 impl TtScore {
     #[inline]
     pub fn new(s: Score, ply: Ply) -> TtScore {
-        TtScore(match s {
-            s if s >= Score::we_win_in(0) => Score::we_win_in(s.ply_win() - ply),
-            s if s <= Score::we_lose_in(0) => Score::we_lose_in(s.ply_loss() - ply),
-            _ => s,
-        })
+        TtScore(Self::adjust(s, -ply))
     }
 
     #[inline]
     pub fn as_score(&self, ply: Ply) -> Score {
-        match self.0 {
-            s if s >= Score::we_win_in(0) => Score::we_win_in(s.ply_win() + ply),
-            s if s <= Score::we_lose_in(0) => Score::we_lose_in(s.ply_loss() + ply),
-            _ => self.0,
+        Self::adjust(self.0, ply)
+    }
+
+    /// moves a mate (or tablebase) score `plies` further from the root. The distance is kept
+    /// within 0..MAX_PLY, as beyond that a mate score would read as a (huge) centipawn score
+    /// and a tablebase score as an eval. Infinities are left alone.
+    #[inline]
+    fn adjust(s: Score, plies: Ply) -> Score {
+        let distance = |d: Ply| (d + plies).clamp(0, MAX_PLY - 1);
+        match s {
+            s if !s.is_finite() => s,
+            s if s >= Score::we_win_in(MAX_PLY - 1) => Score::we_win_in(distance(s.ply_win())),
+            s if s <= Score::we_lose_in(MAX_PLY - 1) => Score::we_lose_in(distance(s.ply_loss())),
+            s if s.is_tb() && s > Score::zero() => Score::tb_win_in(distance(s.tb_ply().unwrap_or_default())),
+            s if s.is_tb() => Score::tb_lose_in(distance(s.tb_ply().unwrap_or_default())),
+            _ => s,
         }
     }
     pub fn pack_16bits(&self) -> u64 {
@@ -99,7 +111,8 @@ impl TtNode {
 
     pub fn pack(node: &TtNode, age: u8) -> u64 {
         let mut bits = age as u64; // age in bits 0-7
-        bits |= ((node.depth & 255) as u64) << 8; // bits 8-15
+        bits |= ((node.depth.min(127) & 127) as u64) << 8; // bits 8-14
+        bits |= (node.ghi as u64) << 15; // bit 15
         bits |= (node.bt as u64 & 3) << 16; // bits 16 and 17
         bits |= (node.score.pack_16bits()) << 18; // bits 18-33
         bits |= (node.bm.pack_14bits()) << 34; // bits 34-47
@@ -109,7 +122,8 @@ impl TtNode {
 
     pub fn unpack(bits: u64) -> (TtNode, u8) {
         // age
-        let draft = (bits >> 8) & 255;
+        let draft = (bits >> 8) & 127;
+        let ghi = (bits >> 15) & 1 == 1;
         let node_type = BoundType::unpack_2bits((bits >> 16) & 3);
         let score = TtScore::unpack_16bits((bits >> 18) & ((2 << 16) - 1));
         let bm = BareMove::unpack_14bits(bits >> 34);
@@ -121,6 +135,7 @@ impl TtNode {
                 bm,
                 score,
                 eval,
+                ghi,
             },
             (bits & 255) as u8,
         )
@@ -151,13 +166,27 @@ impl fmt::Display for TtNode {
         } else {
             write!(
                 f,
-                "{} scoring {} draft {} type {}",
-                self.bm, self.score.0, self.depth, self.bt
+                "{} scoring {} draft {} type {}{}",
+                self.bm,
+                self.score.0,
+                self.depth,
+                self.bt,
+                if self.ghi { " (ghi)" } else { "" }
             )
         }
     }
 }
 
+/// Handling of entries whose score depended on a path-specific draw (the graph-history
+/// interaction problem). A repetition draw found along one path is stored under the hash of
+/// a position which, reached by another path, may be winning - showing up as inexplicable
+/// 0.00 scores.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumString)]
+pub enum Ghi {
+    Ignore,   // flagged entries are used as any other
+    MoveOnly, // flagged entries guide move ordering, but their scores are never used
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, EnumString)]
 enum Replacement {
     Always,
@@ -201,9 +230,13 @@ pub struct TranspositionTable2 {
     freshen_on_fetch:       bool,
     replacement:            Replacement,
     preserve_bm:            bool,
+    pub ghi:                Ghi,
 
     #[serde(skip)]
     pub current_age: u8,
+
+    #[serde(skip)]
+    budget_mb: Option<i64>, // when a memory limit holds the table below mb
 }
 
 impl Default for TranspositionTable2 {
@@ -233,6 +266,8 @@ impl Default for TranspositionTable2 {
             freshen_on_fetch:      true,
             replacement:           Replacement::AgeTypeDepth,
             preserve_bm:           false,
+            ghi:                   Ghi::MoveOnly,
+            budget_mb:             None,
             // deletes: Stat::new("deletes"),
             // fail_priority: Stat::new("ins fail priority"),
             // fail_ownership: Stat::new("ins fail owner"),
@@ -265,6 +300,7 @@ impl Configurable for TranspositionTable2 {
         self.freshen_on_fetch.set(p.get("freshen_on_fetch"))?;
         self.replacement.set(p.get("replacement"))?;
         self.preserve_bm.set(p.get("preserve_bm"))?;
+        self.ghi.set(p.get("ghi"))?;
         Ok(p.is_modified())
     }
 }
@@ -296,11 +332,7 @@ impl Component for TranspositionTable2 {
     fn set_state(&mut self, s: State) {
         use State::*;
         match s {
-            NewGame => {
-                self.resize_if_required();
-                self.current_age = 10;
-                self.clear()
-            }
+            NewGame => self.clear_hash(),
             SetPosition => self.next_generation(),
             StartSearch => self.resize_if_required(),
             EndSearch => {}
@@ -323,7 +355,7 @@ impl Component for TranspositionTable2 {
 impl TranspositionTable2 {
     fn resize_if_required(&mut self) {
         if self.requires_resize() {
-            let capacity = Table::convert_mb_to_capacity(self.mb);
+            let capacity = Table::convert_mb_to_capacity(self.allocated_mb());
             debug!(
                 "tt resized so capacity is now {} with {} buckets",
                 capacity, self.buckets
@@ -347,6 +379,13 @@ impl TranspositionTable2 {
         self.table.iter().for_each(|he| he.set_empty());
     }
 
+    /// empties the table (the uci "Clear Hash" button)
+    pub fn clear_hash(&mut self) {
+        self.resize_if_required();
+        self.current_age = 10;
+        self.clear()
+    }
+
     pub fn rewrite_pv(&mut self, base: &Board, pv: &Variation) {
         if self.rewrite_pv {
             for i in 0..pv.len() {
@@ -382,8 +421,34 @@ impl TranspositionTable2 {
         // }
     }
 
+    /// the memory of a table of `mb`, whose capacity rounds up to a power of two
+    pub fn bytes_for_mb(mb: i64) -> usize {
+        Table::convert_mb_to_capacity(mb) * std::mem::size_of::<HashEntry>()
+    }
+
+    /// the largest `mb` whose table fits within `bytes`
+    pub fn mb_within(bytes: usize) -> i64 {
+        let entries = (bytes / std::mem::size_of::<HashEntry>()).max(1);
+        let capacity = if entries.is_power_of_two() {
+            entries
+        } else {
+            entries.next_power_of_two() / 2
+        };
+        (capacity * std::mem::size_of::<HashEntry>() / 1_000_000).max(1) as i64
+    }
+
+    /// holds the table to `mb` (or lifts the hold if none), taking effect at the next search
+    pub fn set_budget_mb(&mut self, mb: Option<i64>) {
+        self.budget_mb = mb;
+    }
+
+    /// `mb`, unless a memory limit holds the table below it
+    pub fn allocated_mb(&self) -> i64 {
+        self.budget_mb.map_or(self.mb, |budget| budget.min(self.mb))
+    }
+
     pub fn requires_resize(&self) -> bool {
-        let capacity = Table::convert_mb_to_capacity(self.mb);
+        let capacity = Table::convert_mb_to_capacity(self.allocated_mb());
         debug!(
             "tt current capacity {} and {} mb implies capacity of {}",
             self.table.capacity(),
@@ -422,6 +487,12 @@ impl TranspositionTable2 {
         self.enabled
     }
 
+    /// whether the entry's score can be used for this position regardless of the path to it
+    #[inline]
+    pub fn is_path_independent(&self, entry: &TtNode) -> bool {
+        !entry.ghi || self.ghi == Ghi::Ignore
+    }
+
     #[inline]
     pub fn capacity(&self) -> usize {
         self.table.capacity()
@@ -460,6 +531,19 @@ impl TranspositionTable2 {
             new_node
         );
 
+        if paranoid::ENABLED {
+            // the packed move drops the mover, so is compared by squares
+            let (unpacked, age) = TtNode::unpack(TtNode::pack(&new_node, self.current_age));
+            let (mv, new_mv) = (unpacked.bm, new_node.bm);
+            let same_move = (mv.from, mv.to, mv.promo) == (new_mv.from, new_mv.to, new_mv.promo);
+            let same_bounds =
+                (unpacked.depth, unpacked.bt, unpacked.score) == (new_node.depth, new_node.bt, new_node.score);
+            assert!(
+                same_move && same_bounds && unpacked.ghi == new_node.ghi && age == self.current_age,
+                "paranoid: tt entry {new_node} packs as {unpacked}"
+            );
+        }
+
         // probe by hash not board so any "conditions" are bypassed
         let mut bucket_to_overwrite = None;
         let buckets = self.table.bucket(h);
@@ -604,6 +688,19 @@ impl TranspositionTable2 {
         None
     }
 
+    /// the entry and its age, for debugging. Unlike a probe, the entry is not freshened
+    pub fn probe_entry(&self, h: Hash) -> Option<(TtNode, u8)> {
+        self.probe_raw(h).map(|(data, _entry)| TtNode::unpack(data))
+    }
+
+    /// starts loading the entry's cache line, ahead of a probe
+    #[inline]
+    pub fn prefetch(&self, h: Hash) {
+        if self.enabled && self.capacity() > 0 {
+            self.table.prefetch(h);
+        }
+    }
+
     pub fn probe_by_hash(&self, h: Hash) -> Option<TtNode> {
         // debug!("Probe by hash");
         // if !self.enabled || self.capacity() == 0 {
@@ -622,50 +719,154 @@ impl TranspositionTable2 {
     }
 
     pub fn extract_pv_and_score(&self, b: &Board) -> (Variation, Option<Score>) {
-        let mut board = b.clone();
         let mut var = Variation::new();
         let mut score = None;
-        // board = board.make_move(&first);
-        // moves.push(*first);
-        while var.len() < 50 {
-            // probe by hash to avoid all the board filters (ply etc)
-            let entry = self.probe_by_hash(board.hash());
-            if let Some(entry) = entry {
-                if entry.bt == BoundType::ExactPv {
-                    let mv = entry.validate_move(&board);
-                    if !mv.is_null() {
-                        board = board.make_move(mv);
-                        var.push(mv);
-                        if score.is_none() {
-                            score = Some(entry.score.0); // score at root is same as WrtRoot
-                        }
-                        continue;
-                    } else {
-                        debug_assert!(
-                            false,
-                            "Invalid move {} in tt for board position {} from starting {}",
-                            mv,
-                            board.to_fen(),
-                            b.to_fen()
-                        );
-                        error!(
-                            "Invalid move {} for nt {} in tt for board position {}",
-                            mv,
-                            entry.bt,
-                            board.to_fen()
-                        );
-                        break;
-                    }
-                }
+        for e in self.subtree(b, 50, true).take(50) {
+            if e.node.bt != BoundType::ExactPv {
                 if var.is_empty() {
-                    info!("root node is {:?}", entry.bt);
+                    info!("root node is {:?}", e.node.bt);
                 }
+                break;
             }
-            // println!("Unable to find hash {} after move {}", board.hash(), mv) ;
-            break;
+            if e.bm.is_null() {
+                error!(
+                    "Invalid move {} for nt {} in tt for board position {}",
+                    e.node.bm,
+                    e.node.bt,
+                    b.make_moves(&e.var).to_fen()
+                );
+                break;
+            }
+            var.push(e.bm);
+            // score at root is same as WrtRoot
+            score.get_or_insert(e.node.score.0);
         }
         (var, score)
     }
+
+    /// The entries reachable from `root` within `max_ply` plies, following every legal move
+    /// (or with `best_moves_only`, just the stored best moves), depth first with the best
+    /// move first. Transpositions are visited once, except along a line of best moves.
+    ///
+    /// The walk is read-only, entries are never freshened. The table is lockless so may be
+    /// written by a search as it is walked: each entry is read once, and a torn read fails
+    /// the key/data check and is skipped; moves are only followed once legal in the
+    /// position. Each entry is therefore a consistent snapshot, but for a consistent view of
+    /// the whole subtree walk it between searches (or collect it whilst the search is
+    /// stopped).
+    pub fn subtree(&self, root: &Board, max_ply: Ply, best_moves_only: bool) -> TtSubtree<'_> {
+        TtSubtree {
+            tt: self,
+            max_ply,
+            best_moves_only,
+            stack: vec![(root.clone(), Variation::new())],
+            visited: HashSet::new(),
+        }
+    }
+}
+
+/// A table entry as read during a walk of the table from a root position
+#[derive(Clone, Debug)]
+pub struct TtSubtreeEntry {
+    pub var:  Variation, // from the root
+    pub node: TtNode,
+    pub age:  u8,
+    pub bm:   Move, // the stored best move, or null if none or not legal in the position
+}
+
+/// Iterator over the entries below a root position, see [`TranspositionTable2::subtree`]
+pub struct TtSubtree<'a> {
+    tt:              &'a TranspositionTable2,
+    max_ply:         Ply,
+    best_moves_only: bool,
+    stack:           Vec<(Board, Variation)>,
+    visited:         HashSet<Hash>,
+}
+
+impl Iterator for TtSubtree<'_> {
+    type Item = TtSubtreeEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((b, var)) = self.stack.pop() {
+            // a line of best moves ends (by max_ply), so revisiting along it is fine
+            if !self.best_moves_only && !self.visited.insert(b.hash()) {
+                continue;
+            }
+            let Some((node, age)) = self.tt.probe_entry(b.hash()) else {
+                continue;
+            };
+            let bm = node.bm.validate(&b).unwrap_or(Move::new_null());
+            if (var.len() as Ply) < self.max_ply {
+                let mut push = |mv: Move| {
+                    let mut child_var = var.clone();
+                    child_var.push(mv);
+                    self.stack.push((b.make_move(mv), child_var));
+                };
+                if !self.best_moves_only {
+                    b.legal_moves().iter().filter(|&&mv| mv != bm).for_each(|&mv| push(mv));
+                }
+                if !bm.is_null() {
+                    push(bm);
+                }
+            }
+            return Some(TtSubtreeEntry { var, node, age, bm });
+        }
+        None
+    }
+}
+
+/// A summary of the entries below a position, as shown by `tt stats subtree`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TtSubtreeStats {
+    pub entries:     usize,
+    pub exact:       usize,
+    pub lower:       usize,
+    pub upper:       usize,
+    pub ghi:         usize,
+    pub current_age: usize,
+    pub max_ply:     usize,
+    pub max_depth:   Ply,
+    depth_total:     i64,
+}
+
+impl TtSubtreeStats {
+    pub fn new(entries: impl IntoIterator<Item = TtSubtreeEntry>, current_age: u8) -> Self {
+        let mut stats = Self::default();
+        for e in entries {
+            stats.entries += 1;
+            match e.node.bt {
+                BoundType::ExactPv => stats.exact += 1,
+                BoundType::LowerCut => stats.lower += 1,
+                BoundType::UpperAll => stats.upper += 1,
+                BoundType::Unused => {}
+            }
+            stats.ghi += e.node.ghi as usize;
+            stats.current_age += (e.age == current_age) as usize;
+            stats.max_ply = stats.max_ply.max(e.var.len());
+            stats.max_depth = stats.max_depth.max(e.node.depth);
+            stats.depth_total += e.node.depth as i64;
+        }
+        stats
+    }
+
+    pub fn mean_depth(&self) -> f64 {
+        self.depth_total as f64 / self.entries.max(1) as f64
+    }
+}
+
+impl fmt::Display for TtSubtreeStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "entries         : {}", self.entries)?;
+        writeln!(f, "exact           : {}", self.exact)?;
+        writeln!(f, "lower bound     : {}", self.lower)?;
+        writeln!(f, "upper bound     : {}", self.upper)?;
+        writeln!(f, "ghi             : {}", self.ghi)?;
+        writeln!(f, "current age     : {}", self.current_age)?;
+        writeln!(f, "max ply         : {}", self.max_ply)?;
+        writeln!(f, "max depth       : {}", self.max_depth)?;
+        writeln!(f, "mean depth      : {:.2}", self.mean_depth())?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -685,6 +886,7 @@ mod tests {
             bt:    BoundType::ExactPv,
             bm:    Move::new_quiet(Piece::Pawn, Square::B7, Square::B6, &Board::starting_pos()).to_inner(),
             eval:  123.cp(),
+            ghi:   false,
         }
     }
 
@@ -695,6 +897,7 @@ mod tests {
             bt:    BoundType::ExactPv,
             bm:    Move::new_quiet(Piece::Pawn, Square::A2, Square::A3, &Board::starting_pos()).to_inner(),
             eval:  456.cp(),
+            ghi:   false,
         }
     }
 
@@ -705,6 +908,7 @@ mod tests {
             bt:    BoundType::ExactPv,
             bm:    Move::new_quiet(Piece::Rook, Square::A1, Square::A2, &Board::starting_pos()).to_inner(),
             eval:  Score::zero(),
+            ghi:   true,
         }
     }
 
@@ -712,6 +916,45 @@ mod tests {
     fn test_tt_score() {
         assert_eq!(TtScore::new(Score::we_lose_in(5), 3).as_score(3), Score::we_lose_in(5));
         assert_eq!(TtScore::new(Score::we_win_in(5), 3).as_score(3), Score::we_win_in(5));
+
+        // a tablebase win proven 5 ply from the root, stored at ply 3 and reached at ply 1
+        assert_eq!(TtScore::new(Score::tb_win_in(5), 3).as_score(3), Score::tb_win_in(5));
+        assert_eq!(TtScore::new(Score::tb_win_in(5), 3).as_score(1), Score::tb_win_in(3));
+        assert_eq!(TtScore::new(Score::tb_lose_in(5), 3).as_score(1), Score::tb_lose_in(3));
+        assert_eq!(TtScore::new(Score::from_cp(300), 3).as_score(1), Score::from_cp(300));
+
+        // a mate 5 ply from the root found at ply 3 is a mate in 2 ply, and 4 ply from ply 2
+        assert_eq!(TtScore::new(Score::we_win_in(5), 3), TtScore(Score::we_win_in(2)));
+        assert_eq!(TtScore::new(Score::we_win_in(5), 3).as_score(2), Score::we_win_in(4));
+        assert_eq!(TtScore::new(Score::we_lose_in(6), 3).as_score(7), Score::we_lose_in(10));
+
+        // mates too distant from the root remain mates
+        let far = TtScore::new(Score::we_win_in(100), 90).as_score(120);
+        assert_eq!(far, Score::we_win_in(MAX_PLY - 1));
+        assert!(far.is_mate());
+        assert!(TtScore::new(Score::we_lose_in(100), 90).as_score(120).is_mate());
+        assert!(TtScore::new(Score::tb_win_in(100), 90).as_score(120).is_tb());
+
+        // and infinities (as stored for an unknown eval) are not taken as mates
+        assert_eq!(TtScore::new(Score::INFINITY, 3).as_score(5), Score::INFINITY);
+        assert_eq!(TtScore::new(-Score::INFINITY, 3).as_score(5), -Score::INFINITY);
+    }
+
+    #[test]
+    fn test_tt_pack_ghi() {
+        for ghi in [false, true] {
+            let node = TtNode {
+                depth: 100,
+                ghi,
+                ..entry456b()
+            };
+            assert_eq!(TtNode::unpack(TtNode::pack(&node, 17)), (node, 17));
+        }
+        let mut tt = TranspositionTable2::default();
+        assert!(tt.is_path_independent(&entry123()));
+        assert!(!tt.is_path_independent(&entry456b()));
+        tt.ghi = Ghi::Ignore;
+        assert!(tt.is_path_independent(&entry456b()));
     }
 
     #[test]
@@ -842,6 +1085,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_tt_subtree() {
+        let mut eng = ThreadedSearch::new();
+        let epd = Catalog::starting_position();
+        eng.search(epd.clone(), TimeControl::Depth(5)).unwrap();
+        let tt = &eng.search.tt;
+        let b = epd.board();
+
+        // the best move line is the tt pv
+        let line = tt.subtree(&b, 50, true).collect_vec();
+        let (pv, _) = tt.extract_pv_and_score(&b);
+        assert!(!pv.is_empty());
+        assert!(line.len() >= pv.len());
+        assert_eq!(line[pv.len() - 1].var.to_uci(), pv.take(pv.len() - 1).to_uci());
+
+        // every entry is legally reached, once, and within the plies
+        let entries = tt.subtree(&b, 2, false).collect_vec();
+        assert!(entries.len() >= 20, "{}", entries.len());
+        assert!(entries.iter().all(|e| e.var.len() <= 2));
+        assert_eq!(entries[0].var.len(), 0);
+        assert_eq!(entries[1].var.first(), Some(entries[0].bm));
+        let hashes = entries
+            .iter()
+            .map(|e| b.make_moves(&e.var).hash())
+            .collect::<HashSet<_>>();
+        assert_eq!(hashes.len(), entries.len());
+
+        let stats = TtSubtreeStats::new(entries, tt.current_age);
+        println!("{stats}");
+        assert_eq!(stats.entries, stats.exact + stats.lower + stats.upper);
+        assert!(stats.max_ply <= 2 && stats.max_depth >= 4);
+    }
+
+    #[test]
+    fn tt2_test_probe_entry() {
+        let mut eng = ThreadedSearch::new();
+        let bd = Catalog::starting_board();
+        eng.search(Catalog::starting_position(), TimeControl::Depth(5)).unwrap();
+        let (node, age) = eng.search.tt.probe_entry(bd.hash()).unwrap();
+        assert_eq!(node.bt, BoundType::ExactPv);
+        assert!(node.depth >= 5, "{node}");
+        assert_eq!(age, eng.search.tt.current_age);
+        eng.search.set_option("Clear Hash", "").unwrap();
+        assert_eq!(eng.search.tt.probe_entry(bd.hash()), None);
+    }
+
     #[test]
     fn tt2_test_new_game() {
         let mut eng = ThreadedSearch::new();
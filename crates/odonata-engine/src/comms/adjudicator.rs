@@ -0,0 +1,182 @@
+use odonata_base::other::outcome::Outcome;
+use odonata_base::prelude::*;
+
+/// Resignation and draw offer policies, for play against humans and other engines
+/// (uci has no notion of either, so these are applied by whatever is running the game).
+///
+/// Scores are recorded per side, from that side's point of view, after each of its searches.
+/// A side resigns once its last `resign_moves` scores are all below `resign_score`. With
+/// fewer than `resign_min_pieces` on the board (tablebase territory, where a static score
+/// says little about the result) only a mate score is trusted.
+///
+/// A side offers a draw once its last `draw_moves` scores are all within `draw_score` of zero,
+/// on a board of at most `draw_max_pieces` pieces and no earlier than `draw_min_ply`. An offer
+/// is accepted unless the side offered it is the better by more than `draw_score`.
+#[derive(Clone, Debug)]
+pub struct Adjudicator {
+    pub resign_enabled:    bool,
+    pub resign_score:      Score,
+    pub resign_moves:      usize,
+    pub resign_min_pieces: i32,
+
+    pub draw_enabled:    bool,
+    pub draw_score:      Score,
+    pub draw_moves:      usize,
+    pub draw_min_ply:    Ply,
+    pub draw_max_pieces: i32,
+
+    scores: [Vec<Score>; 2],
+}
+
+impl Default for Adjudicator {
+    fn default() -> Self {
+        Self {
+            resign_enabled:    true,
+            resign_score:      (-700).cp(),
+            resign_moves:      4,
+            resign_min_pieces: 8,
+
+            draw_enabled:    true,
+            draw_score:      15.cp(),
+            draw_moves:      8,
+            draw_min_ply:    80,
+            draw_max_pieces: 12,
+
+            scores: Default::default(),
+        }
+    }
+}
+
+impl Configurable for Adjudicator {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.resign_enabled.set(p.get("resign_enabled"))?;
+        self.resign_score.set(p.get("resign_score"))?;
+        self.resign_moves.set(p.get("resign_moves"))?;
+        self.resign_min_pieces.set(p.get("resign_min_pieces"))?;
+        self.draw_enabled.set(p.get("draw_enabled"))?;
+        self.draw_score.set(p.get("draw_score"))?;
+        self.draw_moves.set(p.get("draw_moves"))?;
+        self.draw_min_ply.set(p.get("draw_min_ply"))?;
+        self.draw_max_pieces.set(p.get("draw_max_pieces"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Adjudicator {
+    pub fn new_game(&mut self) {
+        self.scores = Default::default();
+    }
+
+    /// the score of a search by side `c`, from its point of view
+    pub fn record(&mut self, c: Color, score: Score) {
+        self.scores[c].push(score);
+    }
+
+    /// the last `n` scores of side `c`, or None if it has made fewer than `n` searches
+    fn last(&self, c: Color, n: usize) -> Option<&[Score]> {
+        let scores = &self.scores[c];
+        (n > 0 && scores.len() >= n).then(|| &scores[scores.len() - n..])
+    }
+
+    pub fn should_resign(&self, c: Color, b: &Board) -> bool {
+        if !self.resign_enabled {
+            return false;
+        }
+        let Some(scores) = self.last(c, self.resign_moves) else {
+            return false;
+        };
+        let few_pieces = b.occupied().popcount() < self.resign_min_pieces;
+        scores
+            .iter()
+            .all(|&s| s < self.resign_score && (!few_pieces || s.is_mate()))
+    }
+
+    pub fn should_offer_draw(&self, c: Color, b: &Board) -> bool {
+        if !self.draw_enabled
+            || b.total_halfmove_ply() < self.draw_min_ply
+            || b.occupied().popcount() > self.draw_max_pieces
+        {
+            return false;
+        }
+        let Some(scores) = self.last(c, self.draw_moves) else {
+            return false;
+        };
+        scores.iter().all(|s| s.in_window(-self.draw_score, self.draw_score))
+    }
+
+    /// whether side `c` accepts a draw offered by its opponent
+    pub fn accepts_draw(&self, c: Color, b: &Board) -> bool {
+        if !self.draw_enabled || b.total_halfmove_ply() < self.draw_min_ply {
+            return false;
+        }
+        self.last(c, 1).is_some_and(|s| s[0] <= self.draw_score)
+    }
+
+    /// the outcome, if side `c` (having just searched) resigns or agrees a draw with its opponent
+    pub fn adjudicate(&self, c: Color, b: &Board) -> Option<Outcome> {
+        if self.should_resign(c, b) {
+            Some(Outcome::WinByResignation(c.flip_side()))
+        } else if self.should_offer_draw(c, b) && self.accepts_draw(c.flip_side(), b) {
+            Some(Outcome::DrawByAgreement)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_adjudicator() {
+        let mut adj = Adjudicator::default();
+        let b = Board::starting_pos();
+        for _ in 0..3 {
+            adj.record(Color::White, (-800).cp());
+        }
+        assert!(!adj.should_resign(Color::White, &b));
+        adj.record(Color::White, (-800).cp());
+        assert!(adj.should_resign(Color::White, &b));
+        assert!(!adj.should_resign(Color::Black, &b));
+        assert_eq!(
+            adj.adjudicate(Color::White, &b),
+            Some(Outcome::WinByResignation(Color::Black))
+        );
+
+        // with few pieces only a mate score is trusted
+        let kqk = Board::parse_fen("8/8/8/3k4/8/8/8/KQ6 b - - 0 60").unwrap();
+        for _ in 0..4 {
+            adj.record(Color::Black, (-900).cp());
+        }
+        assert!(!adj.should_resign(Color::Black, &kqk));
+        adj.record(Color::Black, Score::we_lose_in(9));
+        assert!(!adj.should_resign(Color::Black, &kqk));
+        adj.new_game();
+        for _ in 0..4 {
+            adj.record(Color::Black, Score::we_lose_in(9));
+        }
+        assert!(adj.should_resign(Color::Black, &kqk));
+
+        // a draw offer needs level scores, low material and a late enough ply
+        adj.new_game();
+        let krkr = Board::parse_fen("8/3k1r2/8/8/8/8/3K1R2/8 w - - 0 60").unwrap();
+        for _ in 0..8 {
+            adj.record(Color::White, 5.cp());
+        }
+        assert!(adj.should_offer_draw(Color::White, &krkr));
+        assert!(!adj.should_offer_draw(Color::White, &b));
+        assert!(!adj.accepts_draw(Color::Black, &krkr));
+        adj.record(Color::Black, 50.cp());
+        assert!(!adj.accepts_draw(Color::Black, &krkr));
+        adj.record(Color::Black, (-5).cp());
+        assert!(adj.accepts_draw(Color::Black, &krkr));
+        assert_eq!(adj.adjudicate(Color::White, &krkr), Some(Outcome::DrawByAgreement));
+
+        adj.resign_enabled = false;
+        adj.draw_enabled = false;
+        assert_eq!(adj.adjudicate(Color::White, &krkr), None);
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt::{self, Write as _};
+
+use anyhow::Context;
+use odonata_base::domain::SearchOptions;
+use odonata_base::epd::Epd;
+use odonata_base::prelude::*;
+use strum_macros::Display;
+
+pub use crate::comms::pgn::PgnGame;
+use crate::search::best_move_change::BestMoveChange;
+use crate::Engine;
+
+/// Centipawn value used in place of a mate score when measuring centipawn loss
+const MATE_CP: i32 = 2000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum MoveClass {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    /// numeric annotation glyph: $6 = ?!, $2 = ?, $4 = ??
+    pub fn nag(self) -> Option<u8> {
+        match self {
+            MoveClass::Good => None,
+            MoveClass::Inaccuracy => Some(6),
+            MoveClass::Mistake => Some(2),
+            MoveClass::Blunder => Some(4),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnnotatedMove {
+    pub board:         Board,
+    pub played:        Move,
+    pub played_score:  Score,
+    pub best_score:    Score,
+    pub best_pv:       Variation,
+    pub class:         MoveClass,
+    pub missed_tactic: bool,
+    pub bm_change:     Option<BestMoveChange>,
+}
+
+impl AnnotatedMove {
+    pub fn cp_loss(&self) -> i32 {
+        (centipawns(self.best_score) - centipawns(self.played_score)).max(0)
+    }
+
+    pub fn win_probability_loss(&self) -> f32 {
+        (self.best_score.win_probability() - self.played_score.win_probability()).max(0.0)
+    }
+
+    fn comment(&self) -> Option<String> {
+        if self.class == MoveClass::Good {
+            return None;
+        }
+        let b = &self.board;
+        let mut s = format!(
+            "{class} ({played}). Best is {best} ({sc})",
+            class = self.class,
+            played = self.played_score.to_pgn(),
+            best = self.best_pv.display_san(b),
+            sc = self.best_score.to_pgn(),
+        );
+        if self.missed_tactic {
+            s += ", missed tactic";
+        }
+        if let Some(c) = &self.bm_change {
+            write!(
+                s,
+                ", best move changed from {old} at depth {d} ({mt})",
+                old = c.old_move.to_san(b),
+                d = c.depth,
+                mt = c.move_type,
+            )
+            .unwrap();
+        }
+        Some(s)
+    }
+}
+
+/// Annotates games, classifying each played move by its loss against the engine's best move.
+///
+/// A move is only classified as an inaccuracy, mistake or blunder if both the centipawn
+/// loss and the loss in win probability reach the threshold. The win probability test
+/// stops moves in already won (or lost) positions being flagged for losing a few pawns.
+#[derive(Clone, Debug)]
+pub struct Annotator {
+    pub depth:         Ply,
+    pub inaccuracy_cp: i32,
+    pub mistake_cp:    i32,
+    pub blunder_cp:    i32,
+    pub inaccuracy_wp: f32,
+    pub mistake_wp:    f32,
+    pub blunder_wp:    f32,
+}
+
+impl Default for Annotator {
+    fn default() -> Self {
+        Self {
+            depth:         10,
+            inaccuracy_cp: 50,
+            mistake_cp:    100,
+            blunder_cp:    250,
+            inaccuracy_wp: 0.05,
+            mistake_wp:    0.10,
+            blunder_wp:    0.20,
+        }
+    }
+}
+
+impl Configurable for Annotator {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.depth.set(p.get("depth"))?;
+        self.inaccuracy_cp.set(p.get("inaccuracy_cp"))?;
+        self.mistake_cp.set(p.get("mistake_cp"))?;
+        self.blunder_cp.set(p.get("blunder_cp"))?;
+        self.inaccuracy_wp.set(p.get("inaccuracy_wp"))?;
+        self.mistake_wp.set(p.get("mistake_wp"))?;
+        self.blunder_wp.set(p.get("blunder_wp"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl fmt::Display for Annotator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+fn centipawns(s: Score) -> i32 {
+    match s.cp() {
+        Some(cp) => (cp as i32).clamp(-MATE_CP, MATE_CP),
+        None if s > Score::zero() => MATE_CP,
+        None => -MATE_CP,
+    }
+}
+
+impl Annotator {
+    pub fn classify(&self, best: Score, played: Score) -> MoveClass {
+        let cp_loss = centipawns(best) - centipawns(played);
+        let wp_loss = best.win_probability() - played.win_probability();
+        match (cp_loss, wp_loss) {
+            (cp, wp) if cp >= self.blunder_cp && wp >= self.blunder_wp => MoveClass::Blunder,
+            (cp, wp) if cp >= self.mistake_cp && wp >= self.mistake_wp => MoveClass::Mistake,
+            (cp, wp) if cp >= self.inaccuracy_cp && wp >= self.inaccuracy_wp => MoveClass::Inaccuracy,
+            _ => MoveClass::Good,
+        }
+    }
+
+    /// evaluates `played` from position `before` against the best move found by search
+    pub fn annotate_move(&self, engine: &mut Engine, before: Epd, played: Move) -> Result<AnnotatedMove> {
+        let tc = TimeControl::Depth(self.depth);
+        let board = before.board();
+        let best = engine.search(before.clone(), tc.clone())?;
+        let best_score = best.score().context("no score from search")?;
+        let best_pv = best.pv();
+        let bm_change = best.bm_change.clone();
+        let played_score = if best_pv.first() == Some(played) {
+            best_score
+        } else {
+            let opts = SearchOptions {
+                root_moves: [played].into_iter().collect(),
+            };
+            engine
+                .search_with_options(before, tc, opts)?
+                .score()
+                .context("no score from search of played move")?
+        };
+        // a search may find a deeper resource in the played move than the full width search
+        let best_score = std::cmp::max(best_score, played_score);
+        let class = self.classify(best_score, played_score);
+        let missed_tactic = class >= MoveClass::Mistake
+            && best_pv
+                .first()
+                .is_some_and(|mv| mv.is_capture() || mv.promo_piece().is_some() || board.gives_check(mv));
+        Ok(AnnotatedMove {
+            board,
+            played,
+            played_score,
+            best_score,
+            best_pv,
+            class,
+            missed_tactic,
+            bm_change,
+        })
+    }
+
+    pub fn annotate_game(&self, engine: &mut Engine, game: &PgnGame) -> Result<Vec<AnnotatedMove>> {
+        engine.new_game();
+        let mut annotations = Vec::with_capacity(game.moves.len());
+        for (i, mv) in game.moves.moves().enumerate() {
+            let before = Epd::from_var(game.starting.clone(), game.moves.take(i));
+            annotations.push(self.annotate_move(engine, before, mv)?);
+        }
+        Ok(annotations)
+    }
+
+    /// annotates every game in the pgn text, returning annotated pgn
+    pub fn annotate_pgn(&self, engine: &mut Engine, pgn: &str) -> Result<String> {
+        let mut out = String::new();
+        for game in PgnGame::parse_many(pgn)? {
+            let annotations = self.annotate_game(engine, &game)?;
+            out += &game.to_annotated_pgn(&annotations, &engine.name());
+            out += "\n";
+        }
+        Ok(out)
+    }
+}
+
+impl PgnGame {
+    pub fn to_annotated_pgn(&self, annotations: &[AnnotatedMove], annotator: &str) -> String {
+        let mut s = String::new();
+        for (k, v) in &self.headers {
+            if k != "Annotator" {
+                writeln!(s, "[{k} \"{v}\"]").unwrap();
+            }
+        }
+        writeln!(s, "[Annotator \"{annotator}\"]").unwrap();
+        writeln!(s).unwrap();
+
+        let mut b = self.starting.clone();
+        let mut needs_number = true;
+        let mut line = String::new();
+        for (i, mv) in self.moves.moves().enumerate() {
+            let mut token = String::new();
+            if b.color_us() == Color::White {
+                write!(token, "{}. ", b.fullmove_number()).unwrap();
+            } else if needs_number {
+                write!(token, "{}... ", b.fullmove_number()).unwrap();
+            }
+            token += &b.to_san(mv);
+            needs_number = false;
+            if let Some(a) = annotations.get(i) {
+                if let Some(nag) = a.class.nag() {
+                    write!(token, " ${nag}").unwrap();
+                }
+                if let Some(comment) = a.comment() {
+                    write!(token, " {{ {comment} }}").unwrap();
+                    needs_number = true;
+                }
+            }
+            b = b.make_move(mv);
+            for word in token.split_inclusive(' ') {
+                if line.len() + word.len() > 80 {
+                    writeln!(s, "{}", line.trim_end()).unwrap();
+                    line.clear();
+                }
+                line += word;
+            }
+            line.push(' ');
+        }
+        line += &self.result;
+        writeln!(s, "{}", line.trim_end()).unwrap();
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    fn test_classify() {
+        let a = Annotator::default();
+        assert_eq!(a.classify(Score::from_cp(30), Score::from_cp(20)), MoveClass::Good);
+        assert_eq!(
+            a.classify(Score::from_cp(30), Score::from_cp(-40)),
+            MoveClass::Inaccuracy
+        );
+        assert_eq!(a.classify(Score::from_cp(30), Score::from_cp(-100)), MoveClass::Mistake);
+        assert_eq!(a.classify(Score::from_cp(30), Score::from_cp(-300)), MoveClass::Blunder);
+        // already winning: losing 3 pawns barely changes the expected result
+        assert_eq!(a.classify(Score::from_cp(1500), Score::from_cp(1200)), MoveClass::Good);
+        assert_eq!(
+            a.classify(Score::from_mate_in_moves(2), Score::from_cp(-200)),
+            MoveClass::Blunder
+        );
+    }
+
+    #[test]
+    fn test_annotate_pgn() -> Result<()> {
+        let pgn = "[Event \"Scholar\"]\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n";
+        let annotator = Annotator {
+            depth: 4,
+            ..Annotator::default()
+        };
+        let mut engine = Engine::new();
+        let game = &PgnGame::parse_many(pgn)?[0];
+        let annotations = annotator.annotate_game(&mut engine, game)?;
+        assert_eq!(annotations.len(), 7);
+        // 3... Nf6 allows mate in one
+        assert_eq!(annotations[5].class, MoveClass::Blunder);
+
+        let out = annotator.annotate_pgn(&mut engine, pgn)?;
+        println!("{out}");
+        assert!(out.contains("[Annotator "));
+        assert!(out.contains("Nf6 $4"));
+        assert!(out.trim_end().ends_with("1-0"));
+        Ok(())
+    }
+}
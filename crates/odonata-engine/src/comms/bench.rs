@@ -1,15 +1,24 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use itertools::Itertools;
 use odonata_base::catalog::*;
+use odonata_base::domain::score::Score;
 use odonata_base::domain::timecontrol::TimeControl;
 use odonata_base::infra::component::{Component, State};
 use odonata_base::infra::utils::Formatting;
 use odonata_base::other::tags::EpdOps as _;
+use odonata_base::other::Shard;
+use odonata_base::piece::Ply;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tabled::builder::Builder;
 
+use crate::comms::health::CpuClass;
 use crate::search::engine::ThreadedSearch;
+use crate::{Engine, EngineConfig};
 pub struct Bench;
 
 impl Bench {
@@ -17,21 +26,32 @@ impl Bench {
         let _engine = ThreadedSearch::new();
     }
 
-    pub fn search(tc: TimeControl, threads: Option<u32>, settings: HashMap<String, String>) -> Result<u64> {
-        let mut engine = ThreadedSearch::with_threads(threads.unwrap_or(1));
-        engine.configure(settings)?;
+    /// searches the bench positions of `shard`, printing a line per position, or with `json` a
+    /// json record per line (see `BenchPosition::to_json`) followed by a summary record
+    pub fn search(tc: TimeControl, config: EngineConfig, shard: Shard, json: bool) -> Result<BenchResults> {
+        let mut engine = Engine::with_config(config)?.into_threaded_search();
+        Self::search_with(&mut engine, tc, shard, json)
+    }
+
+    /// as `search` but with an already configured engine
+    pub fn search_with(engine: &mut ThreadedSearch, tc: TimeControl, shard: Shard, json: bool) -> Result<BenchResults> {
         let epds = &Catalog::bench();
 
-        println!(
-            "{:>3} {:<6} {:>8} {:>2} {:>13} {:>7} {:>6} {:>5}  {:<85}",
-            "#", "bm", "ce", "?", "nodes", "nps", "depth", "bf", "fen"
-        );
-        let mut total_time = Duration::from_millis(0);
-        let mut total_nodes = 0;
-        let mut total_depth = 0;
-        let mut total_bf = 0.0;
-        let mut score = 0;
-        for (i, epd) in epds.iter().enumerate() {
+        if !json {
+            println!("engine        : {}", engine.identity());
+            println!(
+                "{:>3} {:<6} {:>8} {:>2} {:>13} {:>7} {:>6} {:>5}  {:<85}",
+                "#", "bm", "ce", "?", "nodes", "nps", "depth", "bf", "fen"
+            );
+        }
+        let mut results = BenchResults {
+            engine: engine.identity(),
+            eval: engine.search.eval.eval_kind.to_string(),
+            time_control: tc.to_string(),
+            threads: engine.thread_count,
+            ..BenchResults::default()
+        };
+        for (i, epd) in epds.iter().enumerate().filter(|(i, _)| shard.contains(*i)) {
             let t = Instant::now();
 
             engine.set_state(State::NewGame);
@@ -39,7 +59,7 @@ impl Bench {
             let elapsed = t.elapsed();
             let sm = res.supplied_move().unwrap();
             let correct = if epd.moves("bm").unwrap().iter().contains(&sm) {
-                score += 1;
+                results.correct += 1;
                 "✔ " // '✅'
             } else {
                 " x" //'❌'
@@ -51,39 +71,319 @@ impl Bench {
             let bf = res.bf;
             let bf_string = Formatting::decimal(2, bf);
             let fen = res.to_results_epd().board().to_fen();
-            total_bf += bf;
-            total_time += elapsed;
-            total_nodes += res.nodes;
-            total_depth += depth;
-            let nodes = Formatting::u64(res.nodes);
-            println!(
-                "{:>3} {:<6} {:>8} {:>2} {:>13} {:>7} {:>3}/{:<2} {:>5}  {:<85}",
-                i + 1,
-                sm.to_string(),
-                cp.to_string(),
-                correct,
-                nodes,
-                nps,
+            results.positions += 1;
+            results.total_bf += bf;
+            results.total_time += elapsed;
+            results.total_nodes += res.nodes;
+            results.total_depth += depth as u64;
+            results.tt_probes += engine.search.tt_probes;
+            results.tt_hits += engine.search.tt_hits;
+            let record = BenchPosition {
+                index: i + 1,
+                fen: fen.clone(),
+                best_move: sm.to_uci(),
+                pv: res.pv().to_uci(),
+                score: cp,
+                nodes: res.nodes,
                 depth,
-                sel_depth,
-                bf_string,
-                fen
-            );
+                seldepth: sel_depth,
+                time: elapsed,
+                tt_probes: engine.search.tt_probes,
+                tt_hits: engine.search.tt_hits,
+            };
+            if json {
+                println!("{}", record.to_json());
+            } else {
+                println!(
+                    "{:>3} {:<6} {:>8} {:>2} {:>13} {:>7} {:>3}/{:<2} {:>5}  {:<85}",
+                    i + 1,
+                    sm.to_string(),
+                    cp.to_string(),
+                    correct,
+                    Formatting::u64(res.nodes),
+                    nps,
+                    depth,
+                    sel_depth,
+                    bf_string,
+                    fen
+                );
+            }
+            results.records.push(record);
+        }
+        if json {
+            println!("{}", results.to_json());
+            return Ok(results);
         }
-        let average_depth = total_depth as f64 / epds.len() as f64;
-        let average_bf = total_bf / epds.len() as f64;
-        let nps = total_nodes as f64 / total_time.as_secs_f64();
         println!();
-        println!("eval          : {}", engine.search.eval.eval_kind);
-        println!("time control  : {}", tc);
-        println!("threads       : {}", engine.thread_count);
-        println!("nodes/sec     : {}", Formatting::f64(nps));
-        println!("average depth : {}", Formatting::decimal(2, average_depth));
-        println!("average bf    : {}", Formatting::decimal(2, average_bf));
-        println!("total nodes   : {}", Formatting::u64(total_nodes));
-        println!("total time    : {}", Formatting::duration(total_time));
-        println!("score         : {}", score);
-        Ok(total_nodes)
+        print!("{results}");
+        if engine.search.pruning_monitor.enabled {
+            println!(
+                "\npruning verdicts re-searched at full depth\n{}",
+                engine.search.pruning_monitor
+            );
+        }
+        Ok(results)
+    }
+}
+
+/// the search of a single bench position
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchPosition {
+    pub index:     usize, // 1-based, in the bench catalog
+    pub fen:       String,
+    pub best_move: String,
+    #[serde(default)]
+    pub pv:        String, // uci
+    pub score:     Score,
+    pub nodes:     u64,
+    pub depth:     Ply,
+    pub seldepth:  Ply,
+    pub time:      Duration,
+    #[serde(default)]
+    pub tt_probes: u64,
+    #[serde(default)]
+    pub tt_hits:   u64,
+}
+
+impl BenchPosition {
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(1e-9)) as u64
+    }
+
+    /// a flat record for external tooling, with the time in milliseconds
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "type": "position",
+            "index": self.index,
+            "fen": self.fen,
+            "depth": self.depth,
+            "seldepth": self.seldepth,
+            "score": self.score,
+            "best_move": self.best_move,
+            "pv": self.pv,
+            "nodes": self.nodes,
+            "nps": self.nps(),
+            "elapsed_ms": self.time.as_millis() as u64,
+            "tt_probes": self.tt_probes,
+            "tt_hits": self.tt_hits,
+        })
+    }
+}
+
+/// totals over the bench positions searched, which can be merged across shards
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchResults {
+    #[serde(default)]
+    pub engine:       String,
+    pub eval:         String,
+    pub time_control: String,
+    pub threads:      u32,
+    pub positions:    usize,
+    pub correct:      usize,
+    pub total_nodes:  u64,
+    pub total_time:   Duration,
+    pub total_depth:  u64,
+    pub total_bf:     f64,
+
+    #[serde(default)]
+    pub tt_probes: u64, // by the main thread
+    #[serde(default)]
+    pub tt_hits:   u64,
+    #[serde(default)]
+    pub records:   Vec<BenchPosition>,
+}
+
+impl fmt::Display for BenchResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let n = self.positions.max(1) as f64;
+        let nps = self.total_nodes as f64 / self.total_time.as_secs_f64();
+        writeln!(f, "engine        : {}", self.engine)?;
+        writeln!(f, "eval          : {}", self.eval)?;
+        writeln!(f, "time control  : {}", self.time_control)?;
+        writeln!(f, "threads       : {}", self.threads)?;
+        writeln!(f, "nodes/sec     : {}", Formatting::f64(nps))?;
+        writeln!(
+            f,
+            "average depth : {}",
+            Formatting::decimal(2, self.total_depth as f64 / n)
+        )?;
+        writeln!(f, "average bf    : {}", Formatting::decimal(2, self.total_bf / n))?;
+        writeln!(f, "total nodes   : {}", Formatting::u64(self.total_nodes))?;
+        writeln!(f, "total time    : {}", Formatting::duration(self.total_time))?;
+        writeln!(f, "tt hit rate   : {}%", Formatting::decimal(1, self.tt_hit_perc()))?;
+        writeln!(f, "score         : {}", self.correct)
+    }
+}
+
+impl BenchResults {
+    /// percentage of transposition table probes that found an entry
+    pub fn tt_hit_perc(&self) -> f64 {
+        100.0 * self.tt_hits as f64 / self.tt_probes.max(1) as f64
+    }
+
+    /// the totals as a flat record, following the position records of a json bench
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "type": "summary",
+            "engine": self.engine,
+            "eval": self.eval,
+            "time_control": self.time_control,
+            "threads": self.threads,
+            "positions": self.positions,
+            "correct": self.correct,
+            "nodes": self.total_nodes,
+            "nps": (self.total_nodes as f64 / self.total_time.as_secs_f64().max(1e-9)) as u64,
+            "elapsed_ms": self.total_time.as_millis() as u64,
+            "tt_probes": self.tt_probes,
+            "tt_hits": self.tt_hits,
+        })
+    }
+
+    /// combines the results of another shard, which must have used the same settings
+    pub fn merge(&mut self, other: BenchResults) -> Result<()> {
+        let settings = |r: &BenchResults| (r.engine.clone(), r.eval.clone(), r.time_control.clone(), r.threads);
+        if self.positions > 0 && settings(self) != settings(&other) {
+            bail!("cannot merge bench results with different engine, eval, time control or threads");
+        }
+        if self.positions == 0 {
+            (self.engine, self.eval, self.time_control, self.threads) = settings(&other);
+        }
+        self.positions += other.positions;
+        self.correct += other.correct;
+        self.total_nodes += other.total_nodes;
+        self.total_time += other.total_time;
+        self.total_depth += other.total_depth;
+        self.total_bf += other.total_bf;
+        self.tt_probes += other.tt_probes;
+        self.tt_hits += other.tt_hits;
+        self.records.extend(other.records);
+        self.records.sort_by_key(|r| r.index);
+        Ok(())
+    }
+
+    /// the positions (matched by fen) whose best move changed, or whose node count changed
+    /// by more than `nodes_perc` percent, between an `old` and a `new` run
+    pub fn diff(old: &BenchResults, new: &BenchResults, nodes_perc: f64) -> BenchDiff {
+        let mut diff = BenchDiff::default();
+        for n in &new.records {
+            let Some(o) = old.records.iter().find(|o| o.fen == n.fen) else {
+                diff.unmatched += 1;
+                continue;
+            };
+            diff.compared += 1;
+            let change_perc = 100.0 * (n.nodes as f64 - o.nodes as f64) / o.nodes.max(1) as f64;
+            if o.best_move != n.best_move || change_perc.abs() > nodes_perc {
+                diff.changes.push((o.clone(), n.clone()));
+            }
+        }
+        diff
+    }
+}
+
+/// positions that changed between two bench runs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BenchDiff {
+    pub compared:  usize,
+    pub unmatched: usize, // positions in the new run but not the old
+    pub changes:   Vec<(BenchPosition, BenchPosition)>,
+}
+
+impl fmt::Display for BenchDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bu = Builder::new();
+        bu.push_record([
+            "#",
+            "old bm",
+            "new bm",
+            "old ce",
+            "new ce",
+            "old nodes",
+            "new nodes",
+            "%",
+            "fen",
+        ]);
+        for (o, n) in &self.changes {
+            let change_perc = 100.0 * (n.nodes as f64 - o.nodes as f64) / o.nodes.max(1) as f64;
+            let bm_changed = if o.best_move != n.best_move { " *" } else { "" };
+            bu.push_record([
+                n.index.to_string(),
+                o.best_move.clone(),
+                format!("{}{bm_changed}", n.best_move),
+                o.score.to_string(),
+                n.score.to_string(),
+                Formatting::u64(o.nodes),
+                Formatting::u64(n.nodes),
+                format!("{change_perc:+.1}"),
+                n.fen.clone(),
+            ]);
+        }
+        writeln!(f, "{}", bu.build())?;
+        writeln!(f, "compared      : {}", self.compared)?;
+        writeln!(f, "unmatched     : {}", self.unmatched)?;
+        writeln!(f, "changed       : {}", self.changes.len())
+    }
+}
+
+/// The nodes/sec expected of a small fixed workload (a deterministic, single threaded
+/// search of a few bench positions) by cpu class, checked by the `perf_guard` test so that
+/// a change which tanks search speed fails loudly rather than showing up as lost elo.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NpsBaseline {
+    pub tolerance_perc: u64,
+    pub nps:            HashMap<String, u64>, // by cpu class, for a release build
+}
+
+impl NpsBaseline {
+    pub const FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/nps-baseline.toml");
+
+    pub fn load() -> Result<Self> {
+        let s = fs_err::read_to_string(Self::FILE)?;
+        Ok(toml::from_str(&s)?)
+    }
+
+    /// nodes/sec searching the first `positions` bench positions to `nodes` each
+    pub fn measure(positions: usize, nodes: u64) -> Result<u64> {
+        let mut engine = ThreadedSearch::new();
+        engine.configure([("mte.deterministic".to_string(), "true".to_string())].into())?;
+        let tc = TimeControl::NodeCount(nodes);
+        engine.search(Catalog::starting_position(), tc.clone())?; // warm up
+        let (mut total_nodes, mut total_time) = (0, Duration::ZERO);
+        for epd in Catalog::bench().into_iter().take(positions) {
+            engine.set_state(State::NewGame);
+            let t = Instant::now();
+            let res = engine.search(epd, tc.clone())?;
+            total_time += t.elapsed();
+            total_nodes += res.nodes;
+        }
+        Ok((total_nodes as f64 / total_time.as_secs_f64()) as u64)
+    }
+
+    /// an error unless `nps` is within the tolerance of the baseline for the cpu class
+    pub fn check(&self, cpu_class: CpuClass, nps: u64) -> Result<()> {
+        let Some(&baseline) = self.nps.get(&cpu_class.to_string()) else {
+            bail!("no nps baseline for cpu class {cpu_class} in {}", Self::FILE);
+        };
+        let lo = baseline * (100 - self.tolerance_perc.min(100)) / 100;
+        let hi = baseline * (100 + self.tolerance_perc) / 100;
+        if nps < lo {
+            bail!(
+                "nodes/sec {} is below the baseline {} for {cpu_class} by over {}%",
+                Formatting::u64(nps),
+                Formatting::u64(baseline),
+                self.tolerance_perc
+            );
+        }
+        if nps > hi {
+            bail!(
+                "nodes/sec {} is above the baseline {} for {cpu_class} by over {}% - update {}",
+                Formatting::u64(nps),
+                Formatting::u64(baseline),
+                self.tolerance_perc,
+                Self::FILE
+            );
+        }
+        Ok(())
     }
 }
 
@@ -107,7 +407,11 @@ mod tests {
         };
 
         let mut prof = PerfProfiler::new("bench_bratko_approx");
-        prof.bench(|| total_nodes += Bench::search(tc.clone(), None, HashMap::new()).unwrap());
+        prof.bench(|| {
+            total_nodes += Bench::search(tc.clone(), EngineConfig::default(), Shard::default(), false)
+                .unwrap()
+                .total_nodes
+        });
         prof.set_iters(total_nodes / 1000); // total number of searches
 
         let mut prof_accurate = PerfProfiler::new("bench.bratko");
@@ -126,6 +430,82 @@ mod tests {
         prof.set_iters(Catalog::bench().len() as u64);
     }
 
+    #[test]
+    fn test_nps_baseline() {
+        let baseline = NpsBaseline::load().unwrap();
+        assert!(baseline.nps.contains_key(&CpuClass::detect().to_string()));
+
+        let baseline = NpsBaseline {
+            tolerance_perc: 20,
+            nps:            [("X86Modern".to_string(), 1_000_000)].into(),
+        };
+        assert!(baseline.check(CpuClass::X86Modern, 1_000_000).is_ok());
+        assert!(baseline.check(CpuClass::X86Modern, 850_000).is_ok());
+        assert!(baseline.check(CpuClass::X86Modern, 750_000).is_err());
+        assert!(baseline.check(CpuClass::X86Modern, 1_250_000).is_err());
+        assert!(baseline.check(CpuClass::Arm, 1_000_000).is_err());
+    }
+
+    /// cargo test --release -p odonata-engine --features perf_guard test_nps_guard
+    #[cfg(feature = "perf_guard")]
+    #[test]
+    fn test_nps_guard() {
+        assert!(!cfg!(debug_assertions), "the nps guard needs a release build");
+        let baseline = NpsBaseline::load().unwrap();
+        // best of three, as a busy machine only ever slows the search
+        let nps = (0..3).map(|_| NpsBaseline::measure(8, 100_000).unwrap()).max().unwrap();
+        let cpu_class = CpuClass::detect();
+        println!("nodes/sec {} for {cpu_class}", Formatting::u64(nps));
+        baseline.check(cpu_class, nps).unwrap();
+    }
+
+    #[test]
+    fn test_bench_diff() {
+        let tc = TimeControl::NodeCount(1000);
+        let old = Bench::search(tc, EngineConfig::default(), "1/8".parse().unwrap(), false).unwrap();
+        assert_eq!(old.records.len(), old.positions);
+        assert!(old.records.iter().all(|r| r.nodes > 0 && !r.best_move.is_empty()));
+        assert!(old.tt_probes > 0 && old.tt_hits <= old.tt_probes);
+
+        let json = serde_json::to_string(&old).unwrap();
+        let reread: BenchResults = serde_json::from_str(&json).unwrap();
+        assert_eq!(reread, old);
+        assert!(BenchResults::diff(&old, &reread, 0.0).changes.is_empty());
+
+        let mut new = old.clone();
+        new.records[0].nodes *= 2;
+        new.records[1].best_move = "a1a2".to_string();
+        new.records.push(BenchPosition {
+            fen: "k7/8/8/8/8/8/8/K7 w - - 0 1".to_string(),
+            ..new.records[2].clone()
+        });
+        let diff = BenchResults::diff(&old, &new, 50.0);
+        println!("{diff}");
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(diff.compared, old.records.len());
+        assert_eq!(diff.unmatched, 1);
+        assert_eq!(BenchResults::diff(&old, &new, 200.0).changes.len(), 1);
+    }
+
+    #[test]
+    fn test_bench_json() {
+        let tc = TimeControl::NodeCount(1000);
+        let results = Bench::search(tc, EngineConfig::default(), "1/16".parse().unwrap(), true).unwrap();
+        let record = results.records[0].to_json();
+        assert_eq!(record["type"], "position");
+        assert_eq!(record["fen"], results.records[0].fen.as_str());
+        assert!(record["pv"]
+            .as_str()
+            .unwrap()
+            .starts_with(&results.records[0].best_move));
+        assert!(record["nodes"].as_u64().unwrap() > 0);
+        assert!(record["tt_probes"].as_u64().unwrap() >= record["tt_hits"].as_u64().unwrap());
+        let summary = results.to_json();
+        assert_eq!(summary["type"], "summary");
+        assert_eq!(summary["positions"], results.records.len());
+        assert_eq!(summary["nodes"], results.records.iter().map(|r| r.nodes).sum::<u64>());
+    }
+
     #[test]
     fn bench_search() {
         let pos = Catalog::test_position();
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use odonata_base::boards::Position;
+use odonata_base::domain::timecontrol::RemainingTime;
+use odonata_base::infra::utils::Formatting;
+use odonata_base::other::outcome::Outcome;
+use odonata_base::prelude::*;
+
+use crate::Engine;
+
+/// Plays a game (engine as white) against a scripted opponent on a simulated chess clock,
+/// so time management can be exercised end-to-end without a gui or server.
+///
+/// The engine is charged its measured search time plus `lag` (gui/network latency) per move,
+/// the opponent a fixed `opponent_think`. With `moves_to_go` > 0 the clock is topped up by
+/// `initial` every `moves_to_go` moves (repeating time control), otherwise it is sudden death.
+#[derive(Clone, Debug)]
+pub struct ClockSim {
+    pub initial:        Duration,
+    pub increment:      Duration,
+    pub moves_to_go:    u16,
+    pub lag:            Duration,
+    pub opponent_depth: Ply,
+    pub opponent_think: Duration,
+    pub moves:          usize,
+}
+
+impl Default for ClockSim {
+    fn default() -> Self {
+        Self {
+            initial:        Duration::from_millis(1000),
+            increment:      Duration::from_millis(10),
+            moves_to_go:    0,
+            lag:            Duration::from_millis(2),
+            opponent_depth: 3,
+            opponent_think: Duration::from_millis(10),
+            moves:          25,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ClockSimResult {
+    pub moves_played:  usize,
+    pub flagged:       bool,
+    pub outcome:       Outcome,
+    pub remaining:     Duration,
+    pub min_remaining: Duration,
+    pub max_move_time: Duration,
+    pub granted:       Duration, // initial time plus increments and top-ups
+    pub unused_perc:   f64,      // worst case over time controls, of time left unused
+    pub move_times:    Vec<Duration>,
+}
+
+/// percentage of the time available in a time control still on the clock at its end
+fn unused_perc(remaining: Duration, available: Duration) -> f64 {
+    100.0 * remaining.as_secs_f64() / available.as_secs_f64().max(f64::EPSILON)
+}
+
+impl fmt::Display for ClockSimResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "moves played     : {}", self.moves_played)?;
+        writeln!(f, "flagged          : {}", self.flagged)?;
+        writeln!(f, "outcome          : {}", self.outcome)?;
+        writeln!(f, "remaining        : {}", Formatting::duration(self.remaining))?;
+        writeln!(f, "min remaining    : {}", Formatting::duration(self.min_remaining))?;
+        writeln!(f, "max move time    : {}", Formatting::duration(self.max_move_time))?;
+        writeln!(f, "granted          : {}", Formatting::duration(self.granted))?;
+        writeln!(f, "unused %         : {:.1}", self.unused_perc)?;
+        if f.alternate() {
+            for (i, t) in self.move_times.iter().enumerate() {
+                writeln!(f, "move {:>3}         : {}", i + 1, Formatting::duration(*t))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ClockSim {
+    pub fn run(&self, engine: &mut Engine) -> Result<ClockSimResult> {
+        let mut opponent = Engine::new();
+        let mut pos = Position::starting_pos();
+        let (mut wtime, mut btime) = (self.initial, self.initial);
+        let (mut available, mut period_moves) = (self.initial, 0);
+        let mut res = ClockSimResult {
+            granted: self.initial,
+            min_remaining: self.initial,
+            ..ClockSimResult::default()
+        };
+        engine.new_game();
+        opponent.new_game();
+
+        while res.moves_played < self.moves {
+            let played = res.moves_played as u16;
+            let moves_to_go = match self.moves_to_go {
+                0 => 0,
+                mtg => mtg - played % mtg,
+            };
+            let tc = TimeControl::UciFischer(RemainingTime {
+                our_color: Color::White,
+                wtime,
+                btime,
+                winc: self.increment,
+                binc: self.increment,
+                moves_to_go,
+            });
+            let start = Instant::now();
+            let resp = engine.search(pos.to_epd(), tc)?;
+            let elapsed = start.elapsed() + self.lag;
+            let mv = resp
+                .supplied_move()
+                .map_err(|o| anyhow!("no move ({o}) at move {}", played + 1))?;
+            res.move_times.push(elapsed);
+            res.max_move_time = res.max_move_time.max(elapsed);
+            if elapsed > wtime {
+                res.flagged = true;
+                res.remaining = Duration::ZERO;
+                return Ok(res);
+            }
+            wtime = wtime - elapsed + self.increment;
+            available += self.increment;
+            res.granted += self.increment;
+            res.moves_played += 1;
+            period_moves += 1;
+            if period_moves == self.moves_to_go && res.moves_played < self.moves {
+                res.unused_perc = res.unused_perc.max(unused_perc(wtime, available));
+                wtime += self.initial;
+                btime += self.initial;
+                res.granted += self.initial;
+                (available, period_moves) = (wtime, 0);
+            }
+            res.min_remaining = res.min_remaining.min(wtime);
+            pos.push_move(mv);
+            pos.play_search_moves();
+            if pos.outcome().is_game_over() {
+                break;
+            }
+
+            let resp = opponent.search(pos.to_epd(), TimeControl::Depth(self.opponent_depth))?;
+            let mv = resp
+                .supplied_move()
+                .map_err(|o| anyhow!("opponent has no move ({o})"))?;
+            btime = btime.saturating_sub(self.opponent_think) + self.increment;
+            pos.push_move(mv);
+            pos.play_search_moves();
+            if pos.outcome().is_game_over() {
+                break;
+            }
+        }
+        res.outcome = pos.outcome();
+        res.remaining = wtime;
+        if period_moves > 0 {
+            res.unused_perc = res.unused_perc.max(unused_perc(wtime, available));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    #[ignore = "charges wall-clock search time, so fails on a loaded machine"]
+    fn test_clock_sim() {
+        let mut engine = Engine::new();
+        // (simulation, max % of clock left unused)
+        for (sim, max_unused) in [
+            (ClockSim::default(), 50.0),
+            (
+                ClockSim {
+                    initial: Duration::from_millis(300),
+                    increment: Duration::ZERO,
+                    moves: 30,
+                    ..ClockSim::default()
+                },
+                25.0,
+            ),
+            (
+                ClockSim {
+                    initial: Duration::from_millis(500),
+                    moves_to_go: 10,
+                    lag: Duration::from_millis(5),
+                    moves: 20,
+                    ..ClockSim::default()
+                },
+                50.0,
+            ),
+        ] {
+            let res = sim.run(&mut engine).unwrap();
+            println!("{sim:?}\n{res}");
+            assert!(!res.flagged, "{sim:?}\n{res:#}");
+            if res.moves_played == sim.moves {
+                assert!(res.unused_perc < max_unused, "{sim:?}\n{res:#}");
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use odonata_base::catalog::Catalog;
+use odonata_base::prelude::*;
+
+#[cfg(feature = "line_editor")]
+pub use self::line_editor::LineEditor;
+
+/// Tab-completion of console input. The first word completes to a command, and later words
+/// by command: catalog position names after "position name" (or "positions"), uci option
+/// names after "setoption name", and legal san moves after "moves" or "go searchmoves".
+#[derive(Clone, Debug, Default)]
+pub struct Completions {
+    pub board:   Board,       // the current position
+    pub options: Vec<String>, // uci option names
+}
+
+impl Completions {
+    pub const COMMANDS: &'static [&'static str] = &[
+        "uci",
+        "isready",
+        "debug",
+        "setoption",
+        "ucinewgame",
+        "position",
+        "go",
+        "stop",
+        "quit",
+        "ponderhit",
+        "sleep",
+        "perft",
+        "perft_cat",
+        "divide",
+        "board",
+        "ttentry",
+        "tt",
+        "positions",
+        "tree",
+        "mark",
+        "marks",
+        "config",
+        "bench",
+        "compiler",
+        "show_options",
+        "metrics",
+        "show_config",
+        "eval",
+        "explain_last_search",
+    ];
+
+    /// where in `line` the word being completed starts, and the candidates for it
+    pub fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let (done, partial) = line.split_at(start);
+        let words = done.split_whitespace().collect_vec();
+        let candidates = match words.as_slice() {
+            [] => Self::matching(Self::COMMANDS.iter().copied(), partial),
+            ["position"] => Self::matching(["startpos", "fen", "name"], partial),
+            ["position", "name"] | ["positions"] => Catalog::names(partial),
+            ["setoption"] => Self::matching(["name"], partial),
+            ["setoption", "name"] => Self::matching(self.options.iter().map(String::as_str), partial),
+            ["debug"] => Self::matching(["on", "off"], partial),
+            ["tree"] => Self::matching(["show", "export", "clear"], partial),
+            ["config"] => Self::matching(["snapshot", "rollback"], partial),
+            ["position", ..] if words.contains(&"moves") => match Self::position_board(&words) {
+                Some(b) => Self::san_moves(&b, partial),
+                None => vec![],
+            },
+            ["position", _, ..] => Self::matching(["moves"], partial),
+            ["go", .., "searchmoves"] => Self::san_moves(&self.board, partial),
+            _ => vec![],
+        };
+        (start, candidates)
+    }
+
+    fn matching<'a>(words: impl IntoIterator<Item = &'a str>, partial: &str) -> Vec<String> {
+        words
+            .into_iter()
+            .filter(|w| w.starts_with(partial))
+            .map(String::from)
+            .collect()
+    }
+
+    fn san_moves(b: &Board, partial: &str) -> Vec<String> {
+        let sans = b.legal_moves().iter().map(|&mv| b.to_san(mv)).collect_vec();
+        let mut sans = Self::matching(sans.iter().map(String::as_str), partial);
+        sans.sort();
+        sans
+    }
+
+    /// the board after the moves of a (partly typed) "position ... moves ..." command
+    fn position_board(words: &[&str]) -> Option<Board> {
+        let moves = words.iter().position(|&w| w == "moves")?;
+        let mut b = match &words[1..moves] {
+            ["startpos"] => Catalog::starting_board(),
+            ["name", name @ ..] => Catalog::named(&name.join(" ")).ok()?.board(),
+            ["fen", fen @ ..] if fen.len() == 4 => Board::parse_fen(&(fen.join(" ") + " 0 1")).ok()?,
+            ["fen", fen @ ..] => Board::parse_fen(&fen.join(" ")).ok()?,
+            _ => return None,
+        };
+        for mv in &words[moves + 1..] {
+            let mv = b.parse_uci_move(mv).or_else(|_| b.parse_san_move(mv)).ok()?;
+            b = b.make_move(mv);
+        }
+        Some(b)
+    }
+}
+
+#[cfg(feature = "line_editor")]
+mod line_editor {
+    use std::fmt;
+    use std::path::PathBuf;
+
+    use rustyline::completion::Completer;
+    use rustyline::highlight::Highlighter;
+    use rustyline::hint::Hinter;
+    use rustyline::history::FileHistory;
+    use rustyline::validate::Validator;
+    use rustyline::{Context, Editor, Helper};
+
+    use super::Completions;
+
+    impl Completer for Completions {
+        type Candidate = String;
+
+        fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+            Ok(Completions::complete(self, &line[..pos]))
+        }
+    }
+
+    impl Hinter for Completions {
+        type Hint = String;
+    }
+
+    impl Highlighter for Completions {}
+
+    impl Validator for Completions {}
+
+    impl Helper for Completions {}
+
+    /// Console input at a terminal, with tab-completion and a history (kept across runs in
+    /// ~/.odonata_history) recalled by the arrow keys or searched with ctrl-r
+    pub struct LineEditor {
+        editor:       Editor<Completions, FileHistory>,
+        history_file: Option<PathBuf>,
+    }
+
+    impl fmt::Debug for LineEditor {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("LineEditor")
+                .field("history_file", &self.history_file)
+                .finish()
+        }
+    }
+
+    impl LineEditor {
+        pub fn new() -> anyhow::Result<Self> {
+            let mut editor = Editor::new()?;
+            let history_file = std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".odonata_history"));
+            if let Some(file) = &history_file {
+                editor.load_history(file).ok(); // no history file on first use
+            }
+            Ok(Self { editor, history_file })
+        }
+
+        /// a line completed from `completions`, or None at the end of input (ctrl-d or ctrl-c)
+        pub fn readline(&mut self, completions: Completions) -> Option<String> {
+            self.editor.set_helper(Some(completions));
+            let line = self.editor.readline("> ").ok()?;
+            if !line.trim().is_empty() {
+                self.editor.add_history_entry(line.as_str()).ok();
+                if let Some(file) = &self.history_file {
+                    self.editor.save_history(file).ok();
+                }
+            }
+            Some(line)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        let c = Completions {
+            board:   Board::starting_pos(),
+            options: vec!["Hash".to_string(), "Threads".to_string(), "MultiPV".to_string()],
+        };
+        assert_eq!(
+            c.complete("pe"),
+            (0, vec!["perft".to_string(), "perft_cat".to_string()])
+        );
+        assert_eq!(c.complete("position s"), (9, vec!["startpos".to_string()]));
+        assert_eq!(c.complete("position startpos m"), (18, vec!["moves".to_string()]));
+        assert_eq!(c.complete("setoption name Th"), (15, vec!["Threads".to_string()]));
+        assert!(c.complete("position name kiwi").1.contains(&"kiwipete".to_string()));
+        assert!(c.complete("position name WAC.00").1.len() >= 9);
+
+        // san moves, after those typed so far
+        let (_, moves) = c.complete("position startpos moves ");
+        assert_eq!(moves.len(), 20);
+        assert!(moves.contains(&"Nf3".to_string()));
+        assert_eq!(c.complete("position startpos moves e4 e5 N").1, [
+            "Na3", "Nc3", "Ne2", "Nf3", "Nh3"
+        ]);
+        assert_eq!(c.complete("position startpos moves e4 e5 Kxx ").1, Vec::<String>::new());
+        assert_eq!(c.complete("go searchmoves e").1, ["e3", "e4"]);
+        assert_eq!(c.complete("bench x").1, Vec::<String>::new());
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use odonata_base::domain::info::{Info, InfoKind};
+use odonata_base::domain::score::WhiteScore;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The engine's view of the position when it chose one of its moves
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EvalPoint {
+    pub ply:         Ply, // game ply of the position searched
+    pub best_move:   String,
+    pub score:       Option<Score>, // from the engine's point of view
+    pub white_score: Option<WhiteScore>,
+    pub depth:       Option<Ply>,
+    pub time_millis: Option<u64>,
+}
+
+/// Eval history of the current game, so that front ends can plot an eval graph live
+/// without re-analysing.
+///
+/// Fed from the uci `go` (which position is being searched) and the search callback (the
+/// last principal variation and the bestmove), and cleared by `ucinewgame`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EvalHistory {
+    points: Vec<EvalPoint>,
+
+    #[serde(skip)]
+    searching: Option<Board>,
+
+    #[serde(skip)]
+    last_pv: Option<Info>,
+}
+
+impl fmt::Display for EvalHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for p in &self.points {
+            writeln!(
+                f,
+                "{ply:>4} {mv:<6} {score:>8} {depth:>3} {time:>8}",
+                ply = p.ply,
+                mv = p.best_move,
+                score = p.white_score.map_or("-".to_string(), |ws| ws.0.to_pgn()),
+                depth = p.depth.map_or("-".to_string(), |d| d.to_string()),
+                time = p.time_millis.map_or("-".to_string(), |t| format!("{t}ms")),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl EvalHistory {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn points(&self) -> &[EvalPoint] {
+        &self.points
+    }
+
+    /// any points at or beyond this position's ply are from an abandoned line (a takeback or
+    /// a position set up afresh) and are dropped
+    pub fn start_search(&mut self, board: Board) {
+        self.points.retain(|p| p.ply < board.total_halfmove_ply());
+        self.last_pv = None;
+        self.searching = Some(board);
+    }
+
+    pub fn record(&mut self, info: &Info) {
+        match info.kind {
+            InfoKind::Pv if info.multi_pv.unwrap_or(1) == 1 => self.last_pv = Some(info.clone()),
+            InfoKind::BestMove => {
+                let Some(board) = self.searching.take() else {
+                    return;
+                };
+                let Some(mv) = info.pv.as_ref().and_then(|pv| pv.first()) else {
+                    return;
+                };
+                let last_pv = self.last_pv.take().unwrap_or_default();
+                self.points.push(EvalPoint {
+                    ply:         board.total_halfmove_ply(),
+                    best_move:   mv.to_uci(),
+                    score:       last_pv.score,
+                    white_score: last_pv.score.map(|s| s.as_white(board.color_us())),
+                    depth:       last_pv.depth,
+                    time_millis: last_pv.time_millis,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_eval_history() {
+        let mut history = EvalHistory::default();
+        let b = Board::starting_pos();
+        let pv = |s: i32, mpv: usize| Info {
+            kind: InfoKind::Pv,
+            score: Some(Score::from_cp(s)),
+            depth: Some(10),
+            multi_pv: Some(mpv),
+            ..Info::default()
+        };
+        let best_move = Info {
+            kind: InfoKind::BestMove,
+            pv: Some(Variation::from_move(b.parse_uci_move("e2e4").unwrap())),
+            ..Info::default()
+        };
+
+        // bestmove without a go is ignored
+        history.record(&best_move);
+        assert!(history.points().is_empty());
+
+        history.start_search(b.clone());
+        history.record(&pv(30, 1));
+        history.record(&pv(10, 2));
+        history.record(&best_move);
+        assert_eq!(history.points().len(), 1);
+        assert_eq!(history.points()[0].best_move, "e2e4");
+        assert_eq!(history.points()[0].score, Some(Score::from_cp(30)));
+        assert_eq!(history.points()[0].depth, Some(10));
+
+        // black to move, so the white score is negated
+        let b2 = b.make_move(b.parse_uci_move("e2e4").unwrap());
+        history.start_search(b2.clone());
+        history.record(&pv(20, 1));
+        history.record(&best_move);
+        assert_eq!(history.points()[1].white_score, Some(WhiteScore(Score::from_cp(-20))));
+
+        // a takeback drops the later points
+        history.start_search(b2);
+        assert_eq!(history.points().len(), 1);
+        assert!(history.to_string().contains("e2e4"));
+
+        history.clear();
+        assert!(history.points().is_empty());
+    }
+}
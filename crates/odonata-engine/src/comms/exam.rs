@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::{anyhow, bail};
+use odonata_base::boards::Position;
+use odonata_base::epd::Epd;
+use odonata_base::other::tags::{TagOps as _, Tags};
+use odonata_base::other::Shard;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+use tabled::builder::Builder;
+
+use crate::Engine;
+
+/// Runs the engine over a set of EPDs (typically merged from several suites, files
+/// and directories via `Catalog::load_many`), grading the engine's move against
+/// the `bm` and `am` tags. Statistics are grouped by the value of tag `group_by`.
+///
+/// Only the positions of `shard` are examined, and the results of all shards can be
+/// combined with [`ExamResults::merge`].
+///
+/// Suites (by `Src` tag) listed in `lenient` also accept a `bm` reached by a different
+/// piece of the same kind, or a pv transposing into the tagged `pv` solution.
+///
+/// Positions of the Strategic Test Suite (STS) also score points for the move played, from
+/// the `c9` (uci moves) or `c7` (san moves) tag with the `c8` tag of points, or failing
+/// those from the `c0` tag ("Nf3=10, e4=3"). Grouped by [`Exam::THEME`], the points are
+/// summarised per STS theme.
+#[derive(Clone, Debug)]
+pub struct Exam {
+    pub tc:       TimeControl,
+    pub group_by: String,
+    pub shard:    Shard,
+    pub lenient:  Vec<String>,
+}
+
+impl Default for Exam {
+    fn default() -> Self {
+        Self {
+            tc:       TimeControl::Depth(8),
+            group_by: Tags::SRC.to_string(),
+            shard:    Shard::default(),
+            lenient:  Vec::new(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tally {
+    pub pass:       u32,
+    pub fail:       u32,
+    pub ungraded:   u32,
+    #[serde(default)]
+    pub points:     u32, // sts points scored
+    #[serde(default)]
+    pub max_points: u32, // of positions with sts points
+}
+
+impl Tally {
+    pub fn total(&self) -> u32 {
+        self.pass + self.fail + self.ungraded
+    }
+
+    pub fn perc(&self) -> f64 {
+        match self.pass + self.fail {
+            0 => 0.0,
+            graded => 100.0 * self.pass as f64 / graded as f64,
+        }
+    }
+
+    pub fn points_perc(&self) -> f64 {
+        match self.max_points {
+            0 => 0.0,
+            max => 100.0 * self.points as f64 / max as f64,
+        }
+    }
+
+    fn merge(&self, t: &Tally) -> Tally {
+        Tally {
+            pass:       self.pass + t.pass,
+            fail:       self.fail + t.fail,
+            ungraded:   self.ungraded + t.ungraded,
+            points:     self.points + t.points,
+            max_points: self.max_points + t.max_points,
+        }
+    }
+
+    fn add(&mut self, grade: Option<bool>) {
+        match grade {
+            Some(true) => self.pass += 1,
+            Some(false) => self.fail += 1,
+            None => self.ungraded += 1,
+        }
+    }
+
+    fn add_points(&mut self, points: Option<(u32, u32)>) {
+        if let Some((points, max)) = points {
+            self.points += points;
+            self.max_points += max;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExamResults {
+    pub group_by: String,
+    pub groups:   BTreeMap<String, Tally>,
+    pub failures: Vec<(String, Move, Epd)>,
+}
+
+impl ExamResults {
+    pub fn total(&self) -> Tally {
+        self.groups.values().fold(Tally::default(), |acc, t| acc.merge(t))
+    }
+
+    /// combines the results of another shard
+    pub fn merge(&mut self, other: ExamResults) -> Result<()> {
+        if self.group_by != other.group_by {
+            bail!(
+                "cannot merge results grouped by '{}' and '{}'",
+                self.group_by,
+                other.group_by
+            );
+        }
+        for (group, t) in other.groups {
+            let tally = self.groups.entry(group).or_default();
+            *tally = tally.merge(&t);
+        }
+        self.failures.extend(other.failures);
+        Ok(())
+    }
+}
+
+/// alternate format also lists the failed positions
+impl fmt::Display for ExamResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bu = Builder::new();
+        let total = self.total();
+        let sts = total.max_points > 0;
+        let mut header = vec![self.group_by.as_str(), "positions", "pass", "fail", "ungraded", "%"];
+        if sts {
+            header.extend(["points", "max", "points %"]);
+        }
+        bu.push_record(header);
+        for (group, t) in self.groups.iter().chain([(&"total".to_string(), &total)]) {
+            let mut row = vec![
+                group.to_string(),
+                t.total().to_string(),
+                t.pass.to_string(),
+                t.fail.to_string(),
+                t.ungraded.to_string(),
+                format!("{:.1}", t.perc()),
+            ];
+            if sts {
+                row.extend([
+                    t.points.to_string(),
+                    t.max_points.to_string(),
+                    format!("{:.1}", t.points_perc()),
+                ]);
+            }
+            bu.push_record(row);
+        }
+        writeln!(f, "{}", bu.build())?;
+        if f.alternate() {
+            for (id, mv, epd) in &self.failures {
+                writeln!(f, "failed {id:<20} played {mv:<6} {epd}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Exam {
+    /// groups by STS theme, taken from an id such as "STS(v1.0) Undermine.001"
+    pub const THEME: &'static str = "Theme";
+
+    /// the group of the position, the value of tag `group_by` (or for [`Exam::THEME`], the
+    /// STS theme)
+    pub fn group(epd: &Epd, group_by: &str) -> String {
+        if let Some(value) = epd.get(group_by) {
+            return value.to_string();
+        }
+        let theme = || {
+            let id = epd.get(Tags::ID)?;
+            let id = id.split_once(") ").map_or(id, |(_version, rest)| rest);
+            Some(id.rsplit_once('.').map_or(id, |(theme, _n)| theme).trim().to_string())
+        };
+        match group_by {
+            Self::THEME => theme().unwrap_or_else(|| "-".to_string()),
+            _ => "-".to_string(),
+        }
+    }
+
+    /// the STS points for `mv` and the most points available, None if the position has no
+    /// STS points. Moves not listed score zero
+    pub fn sts_points(epd: &Epd, mv: Move) -> Result<Option<(u32, u32)>> {
+        let b = epd.board();
+        let scored: Vec<(Move, u32)> = if let Some(points) = epd.get(Tags::COMMENTS[8]) {
+            // alternative moves, not a variation
+            let moves = match (epd.get(Tags::COMMENTS[9]), epd.get(Tags::COMMENTS[7])) {
+                (Some(uci), _) => uci
+                    .split_whitespace()
+                    .map(|mv| b.parse_uci_move(mv))
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("c9 in {epd}"))?,
+                (None, Some(san)) => {
+                    let moves = b.parse_san_movelist(san).with_context(|| format!("c7 in {epd}"))?;
+                    moves.iter().copied().collect_vec()
+                }
+                (None, None) => bail!("c8 points without c9 or c7 moves in {epd}"),
+            };
+            let points = points
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<Vec<u32>, _>>()
+                .with_context(|| format!("c8 in {epd}"))?;
+            if points.len() != moves.len() {
+                bail!("c8 has {} points for {} moves in {epd}", points.len(), moves.len());
+            }
+            moves.into_iter().zip(points).collect()
+        } else if let Some(c0) = epd.get(Tags::COMMENTS[0]).filter(|c0| c0.contains('=')) {
+            c0.split(',')
+                .map(|scored| {
+                    let (san, points) = scored.split_once('=').context("expected move=points")?;
+                    Ok((b.parse_san_move(san.trim())?, points.trim().parse()?))
+                })
+                .collect::<Result<_>>()
+                .with_context(|| format!("c0 in {epd}"))?
+        } else {
+            return Ok(None);
+        };
+        let max = scored.iter().map(|&(_mv, points)| points).max().unwrap_or_default();
+        let points = scored.iter().find(|&&(m, _)| m == mv).map_or(0, |&(_m, points)| points);
+        Ok(Some((points, max)))
+    }
+
+    /// pass if the first move of the pv is a `bm` (or not an `am`), None if there is nothing
+    /// to grade against. If `lenient`, moves reaching the `bm` square or solution position pass too
+    pub fn grade(epd: &Epd, pv: &Variation, lenient: bool) -> Result<Option<bool>> {
+        let b = epd.board();
+        let mv = pv.first().ok_or_else(|| anyhow!("empty pv for {epd}"))?;
+        if let Some(bm) = epd.get(Tags::BM) {
+            let bm = b.parse_san_movelist(bm).with_context(|| format!("bm in {epd}"))?;
+            if bm.contains(&mv) || !lenient {
+                return Ok(Some(bm.contains(&mv)));
+            }
+            if bm.contains_same_destination(&mv) {
+                return Ok(Some(true));
+            }
+            let Some(solution) = epd.get(Tags::PV) else {
+                return Ok(Some(false));
+            };
+            let solution = b
+                .parse_san_variation(solution)
+                .with_context(|| format!("pv in {epd}"))?;
+            if solution.len() < 2 || pv.len() < solution.len() {
+                return Ok(Some(false));
+            }
+            let mut pos = Position::from_board(b);
+            pos.push_moves(pv.take(solution.len()));
+            return Ok(Some(solution.matches_prefix_with_transpositions(&pos)));
+        }
+        if let Some(am) = epd.get(Tags::AM) {
+            let am = b.parse_san_movelist(am).with_context(|| format!("am in {epd}"))?;
+            return Ok(Some(!am.contains(&mv)));
+        }
+        Ok(None)
+    }
+
+    pub fn run(&self, engine: &mut Engine, epds: &[Epd]) -> Result<ExamResults> {
+        let mut results = ExamResults {
+            group_by: self.group_by.clone(),
+            ..ExamResults::default()
+        };
+        // ids are by position in the whole suite, so are unchanged by sharding
+        for (i, epd) in epds.iter().enumerate().filter(|(i, _)| self.shard.contains(*i)) {
+            engine.new_game();
+            let res = engine.search(epd.clone(), self.tc.clone())?;
+            let mv = res.supplied_move().map_err(|o| anyhow!("no move ({o}) for {epd}"))?;
+            let pv = match res.pv() {
+                pv if pv.first() == Some(mv) => pv,
+                _ => Variation::from_move(mv),
+            };
+            let lenient = epd
+                .get(Tags::SRC)
+                .is_some_and(|src| self.lenient.iter().any(|s| s == src));
+            let grade = Self::grade(epd, &pv, lenient)?;
+            let tally = results.groups.entry(Self::group(epd, &self.group_by)).or_default();
+            tally.add(grade);
+            tally.add_points(Self::sts_points(epd, mv)?);
+            if grade == Some(false) {
+                let id = epd.get(Tags::ID).map_or_else(|| format!("#{}", i + 1), str::to_string);
+                results.failures.push((id, mv, epd.clone()));
+            }
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::catalog::Catalog;
+    use odonata_base::other::EpdFilter;
+    use odonata_base::prelude::testing::Testing;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_exam_grade() {
+        let epd = Epd::parse_epd("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1 bm Nbd2;").unwrap();
+        let b = epd.board();
+        let grade = |san: &str, lenient| Exam::grade(&epd, &san.var(&b), lenient).unwrap();
+        assert_eq!(grade("Nbd2", false), Some(true));
+        assert_eq!(grade("Nfd2", false), Some(false));
+        assert_eq!(grade("Nfd2", true), Some(true));
+        assert_eq!(grade("Kd2", true), Some(false));
+
+        let epd = Epd::parse_epd(r#"8/8/8/8/8/8/8/K6k w - - 0 1 am Kb1; pv "Ka2";"#).unwrap();
+        let b = epd.board();
+        assert_eq!(Exam::grade(&epd, &"Kb1".var(&b), true).unwrap(), Some(false));
+        assert_eq!(Exam::grade(&epd, &"Kb2".var(&b), true).unwrap(), Some(true));
+
+        // a pv transposing into the solution line
+        let epd = Epd::parse_epd(r#"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 bm e4; pv "e4 e5 Nf3";"#)
+            .unwrap();
+        let b = epd.board();
+        let grade = |san: &str, lenient| Exam::grade(&epd, &san.var(&b), lenient).unwrap();
+        assert_eq!(grade("Nf3 e5 e4 Nc6", false), Some(false));
+        assert_eq!(grade("Nf3 e5 e4 Nc6", true), Some(true));
+        assert_eq!(grade("Nf3 e6 e4", true), Some(false));
+        assert_eq!(grade("Nf3", true), Some(false));
+        assert!(Exam::grade(&Epd::starting_pos(), &"e4".var(&b), true)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_exam_sts() {
+        let fen = "1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - 0 1";
+        let epd = Epd::parse_epd(&format!(
+            r#"{fen} bm f5; id "STS(v1.0) Undermine.001"; c0 "f5=10, Be5+=2, Bf2=3, Bg4=2"; c7 "f5 Bf2 Be5+ Bg4"; c8 "10 3 2 2"; c9 "f4f5 d4f2 d4e5 f3g4";"#
+        ))
+        .unwrap();
+        let b = epd.board();
+        assert_eq!(Exam::sts_points(&epd, "f5".mv(&b)).unwrap(), Some((10, 10)));
+        assert_eq!(Exam::sts_points(&epd, "Bf2".mv(&b)).unwrap(), Some((3, 10)));
+        assert_eq!(Exam::sts_points(&epd, "Kg2".mv(&b)).unwrap(), Some((0, 10)));
+        assert_eq!(Exam::group(&epd, Exam::THEME), "Undermine");
+        assert_eq!(Exam::group(&epd, Tags::BM), "f5");
+        assert_eq!(Exam::group(&epd, "Src"), "-");
+
+        // from c7 (san) or c0 alone
+        let epd = Epd::parse_epd(&format!(r#"{fen} c7 "f5 Bf2"; c8 "10 3";"#)).unwrap();
+        assert_eq!(Exam::sts_points(&epd, "Bf2".mv(&b)).unwrap(), Some((3, 10)));
+        let epd = Epd::parse_epd(&format!(r#"{fen} c0 "f5=10, Be5+=2";"#)).unwrap();
+        assert_eq!(Exam::sts_points(&epd, "Be5".mv(&b)).unwrap(), Some((2, 10)));
+        let epd = Epd::parse_epd(&format!(r#"{fen} c0 "a comment";"#)).unwrap();
+        assert_eq!(Exam::sts_points(&epd, "f5".mv(&b)).unwrap(), None);
+        let epd = Epd::parse_epd(&format!(r#"{fen} c7 "f5 Bf2"; c8 "10";"#)).unwrap();
+        assert!(Exam::sts_points(&epd, "f5".mv(&b)).is_err());
+
+        let mut tally = Tally::default();
+        tally.add_points(Some((3, 10)));
+        tally.add_points(Some((10, 10)));
+        tally.add_points(None);
+        assert_eq!((tally.points, tally.max_points), (13, 20));
+        assert_eq!(tally.points_perc(), 65.0);
+    }
+
+    #[test]
+    fn test_exam() {
+        let filter = EpdFilter::parse("id ~ '^(BK.0[1-3]|WAC.00[12])$'").unwrap();
+        let epds = Catalog::load_many(&["BratkoKopec", "WinAtChess"], Some(&filter)).unwrap();
+        assert_eq!(epds.len(), 5);
+        let exam = Exam {
+            tc: TimeControl::Depth(4),
+            ..Exam::default()
+        };
+        let results = exam.run(&mut Engine::new(), &epds).unwrap();
+        assert_eq!(results.groups["BratkoKopec"].total(), 3);
+        assert_eq!(results.groups["WinAtChess"].total(), 2);
+        assert_eq!(results.total().total(), 5);
+        assert_eq!(results.total().ungraded, 0);
+        assert_eq!(results.failures.len() as u32, results.total().fail);
+        println!("{results:#}");
+
+        // shards examined separately and merged give the same results
+        let mut merged = ExamResults {
+            group_by: exam.group_by.clone(),
+            ..ExamResults::default()
+        };
+        for shard in ["1/2", "2/2"] {
+            let exam = Exam {
+                shard: shard.parse().unwrap(),
+                ..exam.clone()
+            };
+            let results = exam.run(&mut Engine::new(), &epds).unwrap();
+            let json = serde_json::to_string(&results).unwrap();
+            merged.merge(serde_json::from_str(&json).unwrap()).unwrap();
+        }
+        assert_eq!(merged.groups, results.groups);
+        assert_eq!(merged.failures.len(), results.failures.len());
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::anyhow;
+use odonata_base::boards::Position;
+use odonata_base::epd::Epd;
+use odonata_base::other::outcome::Outcome;
+use odonata_base::prelude::*;
+use rand::Rng;
+
+use super::adjudicator::Adjudicator;
+use crate::search::search_results::Response;
+
+/// the `i`th opening (cycling), or the starting position if there are none
+pub fn opening(openings: &[Epd], i: usize) -> Board {
+    match openings.len() {
+        0 => Board::starting_pos(),
+        n => openings[i % n].board(),
+    }
+}
+
+/// random legal moves from the opening, stopping short of the game ending
+pub fn randomize(mut b: Board, plies: usize, rng: &mut impl Rng) -> Board {
+    for _ in 0..plies {
+        let moves = b.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let next = b.make_move(moves[rng.gen_range(0..moves.len())]);
+        if next.legal_moves().is_empty() {
+            break;
+        }
+        b = next;
+    }
+    b
+}
+
+/// Plays a game from `start`, calling `search` for the move of whichever side is to play,
+/// returning the outcome and the final position.
+///
+/// The sides resign or agree draws as the `adjudicator` policies decide, and a game still in
+/// progress after `max_moves` moves is adjudicated drawn. The engines (and their new game
+/// state) are left to the caller.
+pub fn play_game(
+    start: Board,
+    max_moves: usize,
+    adjudicator: &Adjudicator,
+    mut search: impl FnMut(&Position) -> Result<Response>,
+) -> Result<(Outcome, Position)> {
+    let mut pos = Position::from_board(start);
+    let mut adjudicator = adjudicator.clone();
+    adjudicator.new_game();
+    let mut outcome = Outcome::Unterminated;
+    while !pos.outcome().is_game_over() && pos.played_moves().len() < 2 * max_moves {
+        let us = pos.board().color_us();
+        let resp = search(&pos)?;
+        let mv = resp
+            .supplied_move()
+            .map_err(|o| anyhow!("no move ({o}) in {}", pos.board().to_fen()))?;
+        if let Some(score) = resp.score() {
+            adjudicator.record(us, score);
+        }
+        if let Some(adjudicated) = adjudicator.adjudicate(us, pos.board()) {
+            outcome = adjudicated;
+            break;
+        }
+        pos.push_move(mv);
+        pos.play_search_moves();
+    }
+    let outcome = match (outcome, pos.outcome()) {
+        (Outcome::Unterminated, Outcome::Unterminated) => Outcome::DrawByAdjudication,
+        (Outcome::Unterminated, outcome) => outcome,
+        (adjudicated, _) => adjudicated,
+    };
+    Ok((outcome, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_opening() {
+        assert_eq!(opening(&[], 3), Board::starting_pos());
+        let openings = [
+            Epd::starting_pos(),
+            Epd::parse_epd("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap(),
+        ];
+        assert_eq!(opening(&openings, 3), openings[1].board());
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use odonata_base::domain::wdl::ScoreWdl;
+use odonata_base::epd::Epd;
+use odonata_base::other::outcome::Outcome;
+use odonata_base::other::tags::Tags;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use tabled::builder::Builder;
+
+use super::adjudicator::Adjudicator;
+use super::game;
+use crate::Engine;
+
+/// Material removed from the odds giver's side of the starting position
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum MaterialOdds {
+    #[default]
+    None,
+    Pawn,   // f-pawn
+    Knight, // queen's knight
+    Rook,   // queen's rook (and so queen side castling)
+    Queen,
+}
+
+impl MaterialOdds {
+    /// the piece removed, and its square from white's point of view
+    fn removed(self) -> Option<(Piece, Square)> {
+        match self {
+            MaterialOdds::None => None,
+            MaterialOdds::Pawn => Some((Piece::Pawn, Square::F2)),
+            MaterialOdds::Knight => Some((Piece::Knight, Square::B1)),
+            MaterialOdds::Rook => Some((Piece::Rook, Square::A1)),
+            MaterialOdds::Queen => Some((Piece::Queen, Square::D1)),
+        }
+    }
+
+    pub fn apply(self, b: &Board, giver: Color) -> Result<Board> {
+        let Some((p, sq)) = self.removed() else {
+            return Ok(b.clone());
+        };
+        let sq = giver.chooser_wb(sq, sq.flip_vertical());
+        if b.piece(sq) != Some(p) || b.color_of(sq) != Some(giver) {
+            bail!("{self} odds for {giver} needs a {p} on {sq} in {}", b.to_fen());
+        }
+        let castling = b.castling() - b.castling_rights_lost(sq, sq);
+        let mut bb = b.clone().into_builder();
+        bb.set_piece(sq, None);
+        bb.set_castling(castling);
+        bb.try_build()
+    }
+}
+
+/// A handicap condition: material odds and/or time odds given by one engine (the giver) to
+/// the other (the taker). Parsed from "odds,giver_tc,taker_tc", eg "knight,depth=8,depth=4".
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handicap {
+    pub name:     String,
+    pub odds:     MaterialOdds,
+    pub giver_tc: TimeControl,
+    pub taker_tc: TimeControl,
+}
+
+impl FromStr for Handicap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((odds, giver_tc, taker_tc)) = s.split(',').map(str::trim).collect_tuple() else {
+            bail!("handicap '{s}' should be of the form odds,giver_tc,taker_tc");
+        };
+        Ok(Handicap {
+            name:     s.to_string(),
+            odds:     odds.parse().with_context(|| format!("material odds '{odds}'"))?,
+            giver_tc: giver_tc.parse()?,
+            taker_tc: taker_tc.parse()?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HandicapGame {
+    pub handicap:    String,
+    pub opening:     String,
+    pub giver_color: Color,
+    pub outcome:     Outcome,
+    pub moves:       String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HandicapResults {
+    pub conditions: Vec<(String, ScoreWdl)>, // from the giver's point of view
+    pub games:      Vec<HandicapGame>,
+}
+
+/// alternate format also lists the games
+impl fmt::Display for HandicapResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bu = Builder::new();
+        bu.push_record(["handicap", "games", "giver wins", "draws", "giver losses", "giver %"]);
+        for (name, wdl) in &self.conditions {
+            bu.push_record([
+                name.to_string(),
+                wdl.n().to_string(),
+                wdl.w.to_string(),
+                wdl.d.to_string(),
+                wdl.l.to_string(),
+                format!("{:.1}", 100.0 * wdl.points() / wdl.n().max(1) as f64),
+            ]);
+        }
+        writeln!(f, "{}", bu.build())?;
+        if f.alternate() {
+            for g in &self.games {
+                writeln!(
+                    f,
+                    "{handicap:<24} {opening:<12} giver {color:<5} {outcome:<8} {moves}",
+                    handicap = g.handicap,
+                    opening = g.opening,
+                    color = g.giver_color,
+                    outcome = g.outcome.as_pgn(),
+                    moves = g.moves,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plays handicap games between two engines, for training games at odds.
+///
+/// Every opening is played twice per handicap condition, with the giver taking each color
+/// in turn, and the results are reported by condition from the giver's point of view.
+/// Engines resign or agree draws as the `adjudicator` policies decide, and games still in
+/// progress after `max_moves` moves are adjudicated drawn.
+#[derive(Clone, Debug)]
+pub struct HandicapMatch {
+    pub handicaps:   Vec<Handicap>,
+    pub openings:    Vec<Epd>,
+    pub max_moves:   usize,
+    pub adjudicator: Adjudicator,
+}
+
+impl Default for HandicapMatch {
+    fn default() -> Self {
+        Self {
+            handicaps:   Vec::new(),
+            openings:    vec![Epd::starting_pos()],
+            max_moves:   150,
+            adjudicator: Adjudicator::default(),
+        }
+    }
+}
+
+impl HandicapMatch {
+    pub fn run(&self, giver: &mut Engine, taker: &mut Engine) -> Result<HandicapResults> {
+        let mut results = HandicapResults::default();
+        for h in &self.handicaps {
+            let mut wdl = ScoreWdl::default();
+            for (i, opening) in self.openings.iter().enumerate() {
+                let id = opening
+                    .get(Tags::ID)
+                    .map_or_else(|| format!("#{}", i + 1), str::to_string);
+                for giver_color in Color::ALL {
+                    let start = h.odds.apply(&opening.board(), giver_color)?;
+                    let game = self.play_game(h, start, giver_color, giver, taker)?;
+                    let w = ScoreWdl::from_outcome(&game.outcome);
+                    wdl += giver_color.chooser_wb(w, ScoreWdl::new(w.l, w.d, w.w));
+                    results.games.push(HandicapGame {
+                        handicap: h.name.clone(),
+                        opening: id.clone(),
+                        giver_color,
+                        ..game
+                    });
+                }
+            }
+            results.conditions.push((h.name.clone(), wdl));
+        }
+        Ok(results)
+    }
+
+    fn play_game(
+        &self,
+        h: &Handicap,
+        start: Board,
+        giver_color: Color,
+        giver: &mut Engine,
+        taker: &mut Engine,
+    ) -> Result<HandicapGame> {
+        giver.new_game();
+        taker.new_game();
+        let (outcome, pos) = game::play_game(start, self.max_moves, &self.adjudicator, |pos| {
+            let (engine, tc) = if pos.board().color_us() == giver_color {
+                (&mut *giver, &h.giver_tc)
+            } else {
+                (&mut *taker, &h.taker_tc)
+            };
+            engine.search(pos.to_epd(), tc.clone())
+        })?;
+        Ok(HandicapGame {
+            handicap: String::new(),
+            opening: String::new(),
+            giver_color,
+            outcome,
+            moves: pos.played_moves().to_uci(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_material_odds() {
+        let b = Board::starting_pos();
+        let knight = MaterialOdds::Knight.apply(&b, Color::White).unwrap();
+        assert_eq!(
+            knight.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1"
+        );
+        let rook = MaterialOdds::Rook.apply(&b, Color::Black).unwrap();
+        assert_eq!(rook.to_fen(), "1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1");
+        assert_eq!(MaterialOdds::None.apply(&b, Color::White).unwrap(), b);
+        assert!(MaterialOdds::Queen.apply(&knight.color_flip(), Color::White).is_ok());
+        assert!(MaterialOdds::Knight.apply(&knight, Color::White).is_err());
+    }
+
+    #[test]
+    fn test_handicap_match() {
+        let h: Handicap = "queen, depth=3, depth=1".parse().unwrap();
+        assert_eq!(h.odds, MaterialOdds::Queen);
+        assert_eq!(h.giver_tc, TimeControl::Depth(3));
+        assert!("queen,depth=3".parse::<Handicap>().is_err());
+        assert!("bishop,depth=3,depth=1".parse::<Handicap>().is_err());
+
+        let hm = HandicapMatch {
+            handicaps: vec![h, "none,depth=1,depth=3".parse().unwrap()],
+            max_moves: 20,
+            adjudicator: Adjudicator {
+                resign_moves: 2,
+                ..Adjudicator::default()
+            },
+            ..HandicapMatch::default()
+        };
+        let results = hm.run(&mut Engine::new(), &mut Engine::new()).unwrap();
+        println!("{results:#}");
+        assert_eq!(results.games.len(), 4);
+        assert_eq!(results.conditions.len(), 2);
+        assert!(results.conditions.iter().all(|(_, wdl)| wdl.n() == 2));
+
+        // colors are swapped for the rematch of each opening
+        assert_eq!(results.games[0].giver_color, Color::White);
+        assert_eq!(results.games[1].giver_color, Color::Black);
+        assert!(results.games.iter().all(|g| g.outcome.is_game_over()));
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use odonata_base::domain::BoundType;
+use odonata_base::epd::Epd;
+use odonata_base::infra::component::{Component, State};
+use odonata_base::infra::utils::Formatting;
+use odonata_base::mv::BareMove;
+use odonata_base::prelude::*;
+
+use crate::cache::tt2::{TranspositionTable2, TtNode, TtScore};
+use crate::eval::EvalBackend;
+use crate::Engine;
+
+/// Broad class of cpu, from the instruction set extensions detected at runtime.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CpuClass {
+    X86Modern,   // avx2 and bmi2
+    X86Baseline, // x86-64 without avx2
+    Arm,
+    Other,
+}
+
+impl fmt::Display for CpuClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl CpuClass {
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("bmi2") {
+                return Self::X86Modern;
+            }
+            Self::X86Baseline
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            Self::Arm
+        }
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            Self::Other
+        }
+    }
+
+    /// single threaded nodes/sec (low, high) expected of a release build
+    pub fn expected_nps(self) -> (u64, u64) {
+        match self {
+            Self::X86Modern => (500_000, 3_000_000),
+            Self::X86Baseline => (300_000, 2_000_000),
+            Self::Arm => (400_000, 3_000_000),
+            Self::Other => (100_000, 3_000_000),
+        }
+    }
+
+    /// transposition table stores plus probes per second (low, high) expected of a release build
+    pub fn expected_tt_ops(self) -> (u64, u64) {
+        match self {
+            Self::X86Modern => (10_000_000, 100_000_000),
+            Self::X86Baseline => (5_000_000, 100_000_000),
+            Self::Arm => (5_000_000, 100_000_000),
+            Self::Other => (2_000_000, 100_000_000),
+        }
+    }
+}
+
+/// A quick (a few hundred ms) self-benchmark of search speed and transposition table
+/// throughput, compared against the range expected for the detected cpu class. A debug
+/// build, a power-saving cpu governor or a heavily loaded machine show up as warnings,
+/// rather than as an engine that mysteriously plays weakly.
+#[derive(Clone, Debug)]
+pub struct HealthCheck {
+    pub duration:  Duration, // split between the search and the tt benchmarks
+    pub warn_perc: u64,      // warn if below this % of the low end of the expected range
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            duration:  Duration::from_millis(300),
+            warn_perc: 50,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    pub debug_build:     bool,
+    pub cpu_class:       CpuClass,
+    pub eval_backend:    EvalBackend,
+    pub nps:             u64,
+    pub tt_ops_per_sec:  u64,
+    pub expected_nps:    (u64, u64),
+    pub expected_tt_ops: (u64, u64),
+    pub warnings:        Vec<String>,
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let range = |(lo, hi): (u64, u64)| format!("{}-{}", Formatting::u64(lo), Formatting::u64(hi));
+        writeln!(
+            f,
+            "build            : {}",
+            if self.debug_build { "debug" } else { "release" }
+        )?;
+        writeln!(f, "cpu class        : {}", self.cpu_class)?;
+        writeln!(f, "eval backend     : {}", self.eval_backend)?;
+        writeln!(f, "nodes/sec        : {}", Formatting::u64(self.nps))?;
+        writeln!(f, "expected         : {}", range(self.expected_nps))?;
+        writeln!(f, "tt ops/sec       : {}", Formatting::u64(self.tt_ops_per_sec))?;
+        writeln!(f, "expected         : {}", range(self.expected_tt_ops))?;
+        for w in &self.warnings {
+            writeln!(f, "warning          : {w}")?;
+        }
+        Ok(())
+    }
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+impl HealthCheck {
+    pub fn run(&self, engine: &mut Engine) -> Result<HealthReport> {
+        let cpu_class = CpuClass::detect();
+        let mut report = HealthReport {
+            debug_build: cfg!(debug_assertions),
+            cpu_class,
+            eval_backend: engine.threaded_search().search.eval.backend,
+            nps: Self::search_nps(engine, self.duration / 2)?,
+            tt_ops_per_sec: Self::tt_ops_per_sec(self.duration / 2),
+            expected_nps: cpu_class.expected_nps(),
+            expected_tt_ops: cpu_class.expected_tt_ops(),
+            warnings: Vec::new(),
+        };
+        if report.debug_build {
+            report
+                .warnings
+                .push("debug build - search is several times slower than a release build".to_string());
+        }
+        let is_slow = |value: u64, (lo, _hi): (u64, u64)| value < lo * self.warn_perc / 100;
+        if is_slow(report.nps, report.expected_nps) {
+            report.warnings.push(format!(
+                "nodes/sec {} is severely below that expected for cpu class {cpu_class} \
+                (debug build, power-saving cpu governor or a busy machine?)",
+                Formatting::u64(report.nps)
+            ));
+        }
+        if is_slow(report.tt_ops_per_sec, report.expected_tt_ops) {
+            report.warnings.push(format!(
+                "tt ops/sec {} is severely below that expected for cpu class {cpu_class}",
+                Formatting::u64(report.tt_ops_per_sec)
+            ));
+        }
+        Ok(report)
+    }
+
+    fn search_nps(engine: &mut Engine, duration: Duration) -> Result<u64> {
+        engine.new_game();
+        let t = Instant::now();
+        let res = engine.search(Epd::starting_pos(), TimeControl::SearchTime(duration))?;
+        let elapsed = t.elapsed();
+        engine.new_game();
+        Ok((res.nodes as f64 / elapsed.as_secs_f64()) as u64)
+    }
+
+    fn tt_ops_per_sec(duration: Duration) -> u64 {
+        let mut tt = TranspositionTable2::default();
+        tt.set_state(State::NewGame);
+        let node = TtNode {
+            score: TtScore::new(Score::from_cp(10), 1),
+            depth: 1,
+            bt:    BoundType::LowerCut,
+            bm:    BareMove::null(),
+            eval:  Score::from_cp(10),
+            ghi:   false,
+        };
+        let t = Instant::now();
+        let mut hash: Hash = 0x9e37_79b9_7f4a_7c15;
+        let mut ops = 0;
+        while t.elapsed() < duration {
+            for _ in 0..1000 {
+                // xorshift spreads the probes across the table
+                hash ^= hash << 13;
+                hash ^= hash >> 7;
+                hash ^= hash << 17;
+                tt.store(hash, node);
+                black_box(tt.probe_by_hash(hash));
+            }
+            ops += 2000;
+        }
+        (ops as f64 / t.elapsed().as_secs_f64()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_health_check() {
+        let hc = HealthCheck {
+            duration: Duration::from_millis(100),
+            ..HealthCheck::default()
+        };
+        let report = hc.run(&mut Engine::new()).unwrap();
+        println!("{report}");
+        assert!(report.nps > 0);
+        assert!(report.tt_ops_per_sec > 0);
+        assert_eq!(report.debug_build, cfg!(debug_assertions));
+        assert_eq!(report.is_healthy(), report.warnings.is_empty());
+        if report.debug_build {
+            assert!(report.warnings[0].contains("debug build"));
+        }
+    }
+}
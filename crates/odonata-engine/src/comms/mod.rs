@@ -1,2 +1,25 @@
+pub mod adjudicator;
+#[cfg(feature = "tools")]
+pub mod annotate;
 pub mod bench;
+#[cfg(feature = "tools")]
+pub mod clock_sim;
+pub mod console;
+pub mod eval_history;
+#[cfg(feature = "tools")]
+pub mod exam;
+#[cfg(feature = "tools")]
+pub mod game;
+#[cfg(feature = "tools")]
+pub mod handicap;
+pub mod health;
+pub mod pgn;
+pub mod protocol;
+#[cfg(feature = "tools")]
+pub mod report;
+#[cfg(feature = "tools")]
+pub mod selfplay;
+pub mod session;
+#[cfg(feature = "tools")]
+pub mod spsa;
 pub mod uci_server;
@@ -0,0 +1,71 @@
+use odonata_base::prelude::*;
+use odonata_base::Pgn;
+
+/// A single game read from PGN: headers, starting position and the main line.
+///
+/// Comments, NAGs and recursive variations in the input are skipped (see [`Pgn`] for
+/// the full game).
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub headers:  Vec<(String, String)>,
+    pub starting: Board,
+    pub moves:    Variation,
+    pub result:   String,
+}
+
+impl From<Pgn> for PgnGame {
+    fn from(pgn: Pgn) -> Self {
+        PgnGame {
+            moves:    pgn.main_line(),
+            headers:  pgn.tags,
+            starting: pgn.starting,
+            result:   pgn.result,
+        }
+    }
+}
+
+impl PgnGame {
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn parse_many(s: &str) -> Result<Vec<PgnGame>> {
+        Ok(Pgn::parse_many(s)?.into_iter().map(PgnGame::from).collect())
+    }
+
+    pub fn parse(s: &str) -> Result<PgnGame> {
+        Ok(Pgn::parse(s)?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_pgn_game_parse() -> Result<()> {
+        let pgn = r#"
+[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 {a comment} 2. Nf3 (2. f4 exf4) 2... Nc6 3. Bc4?! $6 Nd4 4. Nxe5 Qg5
+5. Nxf7 Qxg2 6. Rf1 Qxe4+ 7. Be2 Nf3# 0-1
+
+[Event "Test2"]
+
+1. d4 d5 *
+"#;
+        let games = PgnGame::parse_many(pgn)?;
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].header("White"), Some("A"));
+        assert_eq!(games[0].moves.len(), 14);
+        assert_eq!(games[0].result, "0-1");
+        assert_eq!(games[1].moves.to_uci(), "d2d4 d7d5");
+        assert!(PgnGame::parse("1. e4 {unclosed").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal as _};
+
+use strum_macros::{Display, EnumString};
+
+/// The protocol spoken on stdin. Unless forced (by `--protocol`), it is detected from the
+/// first non-blank line: "uci" from a uci gui, "xboard" from an xboard/winboard gui, a
+/// json-rpc request from a script, and anything else is a user at the console.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum Protocol {
+    Uci,
+    JsonRpc,
+    Xboard,
+    Console,
+}
+
+impl Protocol {
+    /// None for a blank line
+    pub fn detect(line: &str) -> Option<Protocol> {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        match line.split_whitespace().next()? {
+            "uci" => Some(Protocol::Uci),
+            "xboard" => Some(Protocol::Xboard),
+            _ if line.starts_with('{') && line.contains(r#""jsonrpc""#) => Some(Protocol::JsonRpc),
+            _ => Some(Protocol::Console),
+        }
+    }
+
+    /// a protocol to force on stdin. Xboard is only detected, so as to decline it
+    pub fn parse_forced(s: &str) -> anyhow::Result<Protocol> {
+        match s.parse()? {
+            Protocol::Xboard => anyhow::bail!("protocol xboard is not supported, odonata is a uci engine"),
+            p => Ok(p),
+        }
+    }
+}
+
+/// Lines of input, from stdin or (for tests) any reader. Invalid utf-8 (eg from a gui using
+/// a local code page) is replaced rather than fatal
+pub struct InputReader {
+    reader: Box<dyn BufRead + Send>,
+    stdin:  bool,
+}
+
+impl fmt::Debug for InputReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InputReader")
+            .field("stdin", &self.stdin)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for InputReader {
+    fn default() -> Self {
+        Self {
+            reader: Box::new(io::BufReader::new(io::stdin())),
+            stdin:  true,
+        }
+    }
+}
+
+impl InputReader {
+    pub fn from_reader(reader: impl BufRead + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            stdin:  false,
+        }
+    }
+
+    /// stdin at a terminal, so a user at the console rather than a gui or script
+    pub fn is_terminal(&self) -> bool {
+        self.stdin && io::stdin().is_terminal()
+    }
+
+    /// None at the end of input (or on a read error, as the pipe is then broken)
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut bytes = Vec::new();
+        match self.reader.read_until(b'\n', &mut bytes) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_protocol_detect() {
+        assert_eq!(Protocol::detect("uci\n"), Some(Protocol::Uci));
+        assert_eq!(Protocol::detect("\u{feff}uci\r\n"), Some(Protocol::Uci));
+        assert_eq!(Protocol::detect("xboard"), Some(Protocol::Xboard));
+        assert_eq!(
+            Protocol::detect(r#"{"jsonrpc":"2.0","id":1,"method":"open"}"#),
+            Some(Protocol::JsonRpc)
+        );
+        assert_eq!(
+            Protocol::detect(r#" { "jsonrpc": "2.0", "method": "open" }"#),
+            Some(Protocol::JsonRpc)
+        );
+        assert_eq!(Protocol::detect("position startpos"), Some(Protocol::Console));
+        assert_eq!(Protocol::detect("ucinewgame"), Some(Protocol::Console));
+        assert_eq!(Protocol::detect("  \n"), None);
+        assert_eq!("json-rpc".parse::<Protocol>().unwrap(), Protocol::JsonRpc);
+        assert_eq!("UCI".parse::<Protocol>().unwrap(), Protocol::Uci);
+        assert_eq!(Protocol::JsonRpc.to_string(), "json-rpc");
+        assert_eq!(Protocol::parse_forced("console").unwrap(), Protocol::Console);
+        assert!(Protocol::parse_forced("xboard").is_err());
+    }
+
+    #[test]
+    fn test_input_reader() {
+        let mut input = InputReader::from_reader(Cursor::new(b"uci\nbad \xff utf8\nlast".to_vec()));
+        assert_eq!(input.read_line().as_deref(), Some("uci\n"));
+        assert_eq!(input.read_line().as_deref(), Some("bad \u{fffd} utf8\n"));
+        assert_eq!(input.read_line().as_deref(), Some("last"));
+        assert_eq!(input.read_line(), None);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use odonata_base::boards::Position;
+use odonata_base::catalog::Catalog;
+use odonata_base::domain::staticeval::StaticEval as _;
+use odonata_base::epd::Epd;
+use odonata_base::infra::utils::Formatting;
+use odonata_base::other::tags::Tags;
+use odonata_base::other::Shard;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::comms::bench::Bench;
+use crate::comms::exam::{Exam, Tally};
+use crate::Engine;
+
+/// Bundles the bench signature, exam solve rates, nodes/sec, transposition table hit rate
+/// and an eval symmetry check into a single json document, keyed by the engine identity and
+/// config fingerprint. Archived per commit, reports can be compared over time to spot search
+/// or eval regressions.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub bench_tc: TimeControl,
+    pub exam_tc:  TimeControl,
+    pub suites:   Vec<String>, // built-in suites, EPD files or directories for the exam
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self {
+            bench_tc: TimeControl::NodeCount(100_000),
+            exam_tc:  TimeControl::Depth(8),
+            suites:   vec!["BratkoKopec".to_string(), "WinAtChess".to_string()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReportResults {
+    pub engine:          String, // eg "Odonata 0.9 NN-ab12cd34 AVX2"
+    pub fingerprint:     String, // of the uci options and config settings
+    pub debug_build:     bool,
+    pub bench_tc:        String,
+    pub bench_signature: u64, // total bench nodes, which changes only if search behaviour changes
+    pub nps:             u64,
+    pub tt_hit_perc:     f64,
+    pub exam_tc:         String,
+    pub exam:            BTreeMap<String, Tally>, // by suite, with pass % derived from the tally
+    pub symmetry_checks: usize,
+    pub asymmetries:     Vec<(String, Score, Score)>, // fen, eval and eval of the color flipped board
+}
+
+impl fmt::Display for ReportResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "engine          : {}", self.engine)?;
+        writeln!(f, "fingerprint     : {}", self.fingerprint)?;
+        writeln!(
+            f,
+            "build           : {}",
+            if self.debug_build { "debug" } else { "release" }
+        )?;
+        writeln!(f, "bench tc        : {}", self.bench_tc)?;
+        writeln!(f, "bench signature : {}", self.bench_signature)?;
+        writeln!(f, "nodes/sec       : {}", Formatting::u64(self.nps))?;
+        writeln!(f, "tt hit rate     : {}%", Formatting::decimal(1, self.tt_hit_perc))?;
+        writeln!(f, "exam tc         : {}", self.exam_tc)?;
+        for (suite, t) in &self.exam {
+            writeln!(f, "exam {suite:<10} : {}/{} ({:.1}%)", t.pass, t.total(), t.perc())?;
+        }
+        writeln!(
+            f,
+            "eval symmetry   : {} of {} positions asymmetric",
+            self.asymmetries.len(),
+            self.symmetry_checks
+        )?;
+        for (fen, eval, flipped) in &self.asymmetries {
+            writeln!(f, "asymmetric      : {eval} vs {flipped} {fen}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Report {
+    pub fn run(&self, engine: &mut Engine) -> Result<ReportResults> {
+        let ts = engine.threaded_search_mut();
+        let bench = Bench::search_with(ts, self.bench_tc.clone(), Shard::default(), false)?;
+
+        let epds = Catalog::load_many(&self.suites, None)?;
+        let exam = Exam {
+            tc:       self.exam_tc.clone(),
+            group_by: Tags::SRC.to_string(),
+            shard:    Shard::default(),
+            lenient:  Vec::new(),
+        };
+        let exam_results = exam.run(engine, &epds)?;
+
+        let ts = engine.threaded_search_mut();
+        let boards = Catalog::bench().iter().chain(&epds).map(Epd::board).collect_vec();
+        let asymmetries = boards
+            .iter()
+            .filter_map(|b| {
+                let eval = &ts.search.eval;
+                let score = eval.static_eval(&Position::from_board(b.clone()));
+                let flipped = eval.static_eval(&Position::from_board(b.color_flip()));
+                // a color symmetric position is worth just the tempo
+                let expected = if b.is_color_symmetric() { eval.tempo } else { score };
+                (score != flipped || score != expected).then(|| (b.to_fen(), score, flipped))
+            })
+            .collect_vec();
+        engine.new_game();
+
+        let ts = engine.threaded_search();
+        Ok(ReportResults {
+            engine: ts.identity(),
+            fingerprint: ts.config_fingerprint(),
+            debug_build: cfg!(debug_assertions),
+            bench_tc: self.bench_tc.to_string(),
+            bench_signature: bench.total_nodes,
+            nps: (bench.total_nodes as f64 / bench.total_time.as_secs_f64()) as u64,
+            tt_hit_perc: bench.tt_hit_perc(),
+            exam_tc: self.exam_tc.to_string(),
+            exam: exam_results.groups,
+            symmetry_checks: boards.len(),
+            asymmetries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let report = Report {
+            bench_tc: TimeControl::NodeCount(1000),
+            exam_tc:  TimeControl::Depth(2),
+            suites:   vec!["BratkoKopec".to_string()],
+        };
+        let mut engine = Engine::new();
+        let results = report.run(&mut engine).unwrap();
+        println!("{results}");
+        assert_eq!(results.engine, engine.threaded_search().identity());
+        assert_eq!(results.fingerprint.len(), 16);
+        assert!(results.bench_signature > 0);
+        assert!(results.tt_hit_perc > 0.0 && results.tt_hit_perc <= 100.0);
+        let positions = Catalog::bratko_kopec().len();
+        assert_eq!(results.exam["BratkoKopec"].total() as usize, positions);
+        assert_eq!(results.symmetry_checks, 2 * positions);
+        let tempo = engine.threaded_search().search.eval.tempo;
+        assert!(results
+            .asymmetries
+            .iter()
+            .all(|(_fen, eval, flipped)| eval != flipped || *eval != tempo));
+
+        // the signature is reproducible, and the json round trips
+        let again = report.run(&mut Engine::new()).unwrap();
+        assert_eq!(again.bench_signature, results.bench_signature);
+        let json = serde_json::to_string_pretty(&results).unwrap();
+        let reread: ReportResults = serde_json::from_str(&json).unwrap();
+        assert_eq!(reread.fingerprint, results.fingerprint);
+        assert_eq!(reread.exam, results.exam);
+        assert_eq!(reread.asymmetries, results.asymmetries);
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::io::Write;
+
+use odonata_base::domain::wdl::ScoreWdl;
+use odonata_base::epd::Epd;
+use odonata_base::other::outcome::Outcome;
+use odonata_base::other::tags::Tags;
+use odonata_base::prelude::*;
+use rand::SeedableRng as _;
+use rand_chacha::ChaChaRng;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+use super::adjudicator::Adjudicator;
+use super::game;
+use crate::Engine;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum DataFormat {
+    /// a line per position: the fen, `ce` (side to move's point of view) and `c9` the result
+    #[default]
+    Epd,
+
+    /// [`Sample::RECORD_LEN`] byte records, see [`Sample::to_bytes`]
+    Binary,
+}
+
+/// A training sample: a quiet position from a self-play game, its search score and the
+/// game's result, both from white's point of view
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub board:  Board,
+    pub score:  i16, // centipawns
+    pub result: f64, // 1, 0.5 or 0
+}
+
+impl Sample {
+    pub const RECORD_LEN: usize = 32;
+
+    /// loadable by the tuner, which reads the result from `c9`
+    pub fn to_epd(&self) -> String {
+        let mut epd = Epd::from_board(self.board.clone());
+        let pov = self.board.turn().chooser_wb(1, -1);
+        epd.set_tag(Tags::CE, &(pov * self.score).to_string());
+        epd.set_tag(Tags::C9, &self.result_pgn());
+        epd.to_epd()
+    }
+
+    fn result_pgn(&self) -> String {
+        match self.result {
+            r if r > 0.75 => "1-0",
+            r if r < 0.25 => "0-1",
+            _ => "1/2-1/2",
+        }
+        .to_string()
+    }
+
+    /// Little endian, from white's point of view throughout:
+    ///
+    /// | bytes  | field                                                                  |
+    /// |--------|------------------------------------------------------------------------|
+    /// | 0..8   | occupancy bitboard (a1 = bit 0)                                        |
+    /// | 8..24  | a 4-bit piece per occupied square in square order, low nibble first:   |
+    /// |        | 0..=5 pawn, knight, bishop, rook, queen, king, plus 8 if black         |
+    /// | 24..26 | score, centipawns                                                      |
+    /// | 26     | result: 0 black win, 1 draw, 2 white win                               |
+    /// | 27     | side to move: 0 white, 1 black                                         |
+    /// | 28..32 | zero                                                                   |
+    pub fn to_bytes(&self) -> [u8; Self::RECORD_LEN] {
+        let b = &self.board;
+        let mut bytes = [0; Self::RECORD_LEN];
+        bytes[0..8].copy_from_slice(&b.occupied().bits().to_le_bytes());
+        for (i, sq) in b.occupied().squares().enumerate() {
+            let p = b.piece(sq).unwrap_or_default().index() as u8;
+            let nibble = p + b.color_of(sq).map_or(0, |c| c.chooser_wb(0, 8));
+            bytes[8 + i / 2] |= nibble << (4 * (i % 2));
+        }
+        bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+        bytes[26] = (2.0 * self.result).round() as u8;
+        bytes[27] = b.turn().chooser_wb(0, 1);
+        bytes
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SelfPlayResults {
+    pub wdl:     ScoreWdl,
+    pub samples: usize,
+}
+
+impl fmt::Display for SelfPlayResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "games {n} (white wins {w}, draws {d}, black wins {l}) samples {samples}",
+            n = self.wdl.n(),
+            w = self.wdl.w,
+            d = self.wdl.d,
+            l = self.wdl.l,
+            samples = self.samples
+        )
+    }
+}
+
+/// Plays the engine against itself to generate nnue training data.
+///
+/// Each game starts from an opening (in turn) followed by `random_plies` random moves, so
+/// that games differ, and is played at the time control `tc` (typically nodes or depth).
+/// Positions are sampled from `min_ply` onwards unless in check, or the best move is a
+/// capture or promotion, or the score is a mate or beyond `max_score`: positions that are
+/// not quiet say little about a static evaluation.
+///
+/// Samples are written as each game finishes, once its result is known.
+#[derive(Clone, Debug)]
+pub struct SelfPlay {
+    pub tc:           TimeControl,
+    pub games:        usize,
+    pub openings:     Vec<Epd>,
+    pub random_plies: usize,
+    pub seed:         u64,
+    pub min_ply:      usize,
+    pub max_score:    i16,
+    pub max_moves:    usize,
+    pub format:       DataFormat,
+    pub adjudicator:  Adjudicator,
+}
+
+impl Default for SelfPlay {
+    fn default() -> Self {
+        Self {
+            tc:           TimeControl::NodeCount(5000),
+            games:        100,
+            openings:     vec![Epd::starting_pos()],
+            random_plies: 8,
+            seed:         1,
+            min_ply:      16,
+            max_score:    3000,
+            max_moves:    200,
+            format:       DataFormat::Epd,
+            adjudicator:  Adjudicator::default(),
+        }
+    }
+}
+
+impl SelfPlay {
+    pub fn run(&self, engine: &mut Engine, mut w: impl Write) -> Result<SelfPlayResults> {
+        let mut results = SelfPlayResults::default();
+        let mut rng = ChaChaRng::seed_from_u64(self.seed);
+        for i in 0..self.games {
+            let start = game::randomize(game::opening(&self.openings, i), self.random_plies, &mut rng);
+            let (outcome, samples) = self.play_game(start, engine)?;
+            results.wdl += ScoreWdl::from_outcome(&outcome);
+            results.samples += samples.len();
+            for s in &samples {
+                match self.format {
+                    DataFormat::Epd => writeln!(w, "{}", s.to_epd())?,
+                    DataFormat::Binary => w.write_all(&s.to_bytes())?,
+                }
+            }
+            info!(target: "selfplay", "game {} {} samples {}", i + 1, outcome.as_pgn(), samples.len());
+        }
+        w.flush()?;
+        Ok(results)
+    }
+
+    fn play_game(&self, start: Board, engine: &mut Engine) -> Result<(Outcome, Vec<Sample>)> {
+        engine.new_game();
+        let mut candidates = vec![];
+        let (outcome, _pos) = game::play_game(start, self.max_moves, &self.adjudicator, |pos| {
+            let resp = engine.search(pos.to_epd(), self.tc.clone())?;
+            if let (Some(score), Ok(mv)) = (resp.score(), resp.supplied_move()) {
+                let b = pos.board();
+                let quiet = !b.is_in_check(b.turn()) && !mv.is_capture() && !mv.is_promo();
+                let ply = pos.played_moves().len();
+                if quiet && ply >= self.min_ply && score.is_numeric() && score.as_i16().abs() <= self.max_score {
+                    let score = b.turn().chooser_wb(1, -1) * score.as_i16();
+                    candidates.push((b.clone(), score));
+                }
+            }
+            Ok(resp)
+        })?;
+        let result = ScoreWdl::from_outcome(&outcome).points();
+        let samples = candidates
+            .into_iter()
+            .map(|(board, score)| Sample { board, score, result })
+            .collect();
+        Ok((outcome, samples))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let b = Board::parse_fen("4k3/8/8/8/8/8/4P3/4K2R b K - 0 1").unwrap();
+        let s = Sample {
+            board:  b.clone(),
+            score:  250,
+            result: 1.0,
+        };
+        let epd = Epd::parse_epd(&s.to_epd()).unwrap();
+        assert_eq!(epd.board(), b);
+        assert_eq!(epd.get(Tags::CE), Some("-250"));
+        assert_eq!(epd.get(Tags::C9), Some("1-0"));
+
+        let bytes = s.to_bytes();
+        assert_eq!(u64::from_le_bytes(bytes[0..8].try_into().unwrap()), b.occupied().bits());
+        // e1 white king, h1 white rook, e2 white pawn, e8 black king
+        assert_eq!(&bytes[8..10], &[0x35, 0xd0]);
+        assert_eq!(i16::from_le_bytes([bytes[24], bytes[25]]), 250);
+        assert_eq!(&bytes[26..28], &[2, 1]);
+    }
+
+    #[test]
+    fn test_selfplay() {
+        let sp = SelfPlay {
+            tc: TimeControl::Depth(2),
+            games: 2,
+            min_ply: 4,
+            max_moves: 30,
+            ..SelfPlay::default()
+        };
+        let mut epds = vec![];
+        let results = sp.run(&mut Engine::new(), &mut epds).unwrap();
+        println!("{results}");
+        assert_eq!(results.wdl.n(), 2);
+        let epds = Epd::parse_many_epd(String::from_utf8(epds).unwrap().lines()).unwrap();
+        assert_eq!(epds.len(), results.samples);
+        assert!(epds
+            .iter()
+            .all(|e| e.get(Tags::C9).is_some() && e.get(Tags::CE).is_some()));
+        assert!(epds.iter().all(|e| !e.board().is_in_check(e.board().turn())));
+
+        let mut bin = vec![];
+        let binary = SelfPlay {
+            format: DataFormat::Binary,
+            ..sp
+        };
+        let results = binary.run(&mut Engine::new(), &mut bin).unwrap();
+        assert_eq!(bin.len(), results.samples * Sample::RECORD_LEN);
+
+        // no openings, so the starting position
+        let no_openings = SelfPlay {
+            openings: vec![],
+            games: 1,
+            ..binary
+        };
+        assert_eq!(no_openings.run(&mut Engine::new(), std::io::sink()).unwrap().wdl.n(), 1);
+    }
+}
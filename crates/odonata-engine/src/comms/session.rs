@@ -0,0 +1,125 @@
+use std::fmt;
+
+use odonata_base::epd::Epd;
+use odonata_base::other::Tags;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// a position bookmarked during analysis, with the user's note
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub epd:  Epd,
+    pub note: String,
+}
+
+/// State of an interactive analysis session (the console, or a json-rpc session) kept
+/// alongside its engine: the position being analysed, and the positions bookmarked with
+/// notes. The searches' analysis tree is the engine's own, so jumping back to a bookmark
+/// and running `tree show` continues from what was found there.
+#[derive(Clone, Debug, Default)]
+pub struct SessionState {
+    pub position: Epd,
+    bookmarks:    Vec<Bookmark>,
+}
+
+impl fmt::Display for SessionState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, bm) in self.bookmarks.iter().enumerate() {
+            writeln!(
+                f,
+                "{n:>3}: {fen} \"{note}\"",
+                n = i + 1,
+                fen = bm.epd.board().to_fen(),
+                note = bm.note
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SessionState {
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// bookmarks the current position, returning its number (from 1)
+    pub fn mark(&mut self, note: &str) -> usize {
+        self.mark_epd(self.position.clone(), note)
+    }
+
+    pub fn mark_epd(&mut self, epd: Epd, note: &str) -> usize {
+        let note = note.trim().trim_matches('"').to_string();
+        self.bookmarks.push(Bookmark { epd, note });
+        self.bookmarks.len()
+    }
+
+    pub fn bookmark(&self, n: usize) -> Result<&Bookmark> {
+        n.checked_sub(1)
+            .and_then(|i| self.bookmarks.get(i))
+            .with_context(|| format!("no bookmark {n} (of {})", self.bookmarks.len()))
+    }
+
+    /// the bookmarked position becomes the current one
+    pub fn jump(&mut self, n: usize) -> Result<Epd> {
+        self.position = self.bookmark(n)?.epd.clone();
+        Ok(self.position.clone())
+    }
+
+    pub fn unmark(&mut self, n: usize) -> Result<Bookmark> {
+        self.bookmark(n)?;
+        Ok(self.bookmarks.remove(n - 1))
+    }
+
+    pub fn clear(&mut self) {
+        self.bookmarks.clear();
+    }
+
+    /// one line per bookmark, numbered by `id` and with the note as comment `c0`
+    pub fn to_epd(&self) -> String {
+        let mut s = String::new();
+        for (i, bm) in self.bookmarks.iter().enumerate() {
+            let mut epd = Epd::from_board(bm.epd.board());
+            epd.set_tag(Tags::ID, &format!("bookmark {}", i + 1));
+            epd.set_tag("c0", &bm.note);
+            s += &epd.to_epd();
+            s += "\n";
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::catalog::Catalog;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_session_bookmarks() {
+        let mut session = SessionState::default();
+        let wac = Catalog::win_at_chess();
+        session.position = wac[0].clone();
+        assert_eq!(session.mark("\"critical zugzwang\""), 1);
+        session.position = wac[1].clone();
+        assert_eq!(session.mark("sac?"), 2);
+        assert_eq!(session.bookmark(1).unwrap().note, "critical zugzwang");
+        assert!(session.bookmark(3).is_err());
+        assert!(session.bookmark(0).is_err());
+        assert!(session.to_string().contains("sac?"));
+
+        assert_eq!(session.jump(1).unwrap().board(), wac[0].board());
+        assert_eq!(session.position.board(), wac[0].board());
+
+        let epds = Epd::parse_many_epd(session.to_epd().lines()).unwrap();
+        assert_eq!(epds.len(), 2);
+        assert_eq!(epds[0].tag("c0"), Some("critical zugzwang"));
+        assert_eq!(epds[1].tag(Tags::ID), Some("bookmark 2"));
+        assert_eq!(epds[1].board(), wac[1].board());
+
+        assert_eq!(session.unmark(1).unwrap().note, "critical zugzwang");
+        assert_eq!(session.bookmarks().len(), 1);
+        session.clear();
+        assert!(session.bookmarks().is_empty());
+    }
+}
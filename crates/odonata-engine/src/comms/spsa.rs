@@ -0,0 +1,312 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use odonata_base::domain::wdl::ScoreWdl;
+use odonata_base::epd::Epd;
+use odonata_base::other::outcome::Outcome;
+use odonata_base::prelude::*;
+use rand::{Rng as _, SeedableRng as _};
+use rand_chacha::ChaChaRng;
+use strum_macros::{Display, EnumString};
+
+use super::adjudicator::Adjudicator;
+use super::game;
+use crate::{Engine, EngineConfig};
+
+/// how a parameter's value is written as a setting
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum SpsaKind {
+    Int,
+    Float,
+    Cp, // a score, such as aspiration.window
+}
+
+/// A tunable parameter, as a line "name, kind, value, min, max, c_end, r_end" in the style of
+/// OpenBench. `c_end` is the size of the perturbation, and `r_end` the learning rate, by the
+/// last iteration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpsaParam {
+    pub name:  String, // a setting such as "aspiration.window"
+    pub kind:  SpsaKind,
+    pub value: f64,
+    pub min:   f64,
+    pub max:   f64,
+    pub c_end: f64,
+    pub r_end: f64,
+}
+
+impl FromStr for SpsaParam {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((name, kind, value, min, max, c_end, r_end)) = s.split(',').map(str::trim).collect_tuple() else {
+            bail!("spsa parameter '{s}' should be of the form name, kind, value, min, max, c_end, r_end");
+        };
+        let num = |v: &str| {
+            v.parse::<f64>()
+                .with_context(|| format!("'{v}' in spsa parameter '{s}'"))
+        };
+        let param = SpsaParam {
+            name:  name.to_string(),
+            kind:  kind
+                .parse()
+                .with_context(|| format!("kind '{kind}' is not int, float or cp"))?,
+            value: num(value)?,
+            min:   num(min)?,
+            max:   num(max)?,
+            c_end: num(c_end)?,
+            r_end: num(r_end)?,
+        };
+        if !(param.min..=param.max).contains(&param.value) || param.c_end <= 0.0 {
+            bail!("spsa parameter '{s}' needs min <= value <= max and c_end > 0");
+        }
+        Ok(param)
+    }
+}
+
+impl fmt::Display for SpsaParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}, {}, {}, {}, {}",
+            self.name, self.kind, self.value, self.min, self.max, self.c_end, self.r_end
+        )
+    }
+}
+
+impl SpsaParam {
+    /// one per line, ignoring blank lines and # comments
+    pub fn parse_many(s: &str) -> Result<Vec<SpsaParam>> {
+        s.lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    /// the value (clamped to the range) as a setting value
+    pub fn setting(&self, value: f64) -> String {
+        let value = value.clamp(self.min, self.max);
+        match self.kind {
+            SpsaKind::Int => format!("{}", value.round() as i64),
+            SpsaKind::Float => format!("{value}"),
+            SpsaKind::Cp => format!("{}cp", value.round() as i64),
+        }
+    }
+}
+
+/// an iteration's games, from the point of view of the engine with the parameters raised by
+/// the perturbation, and the parameters after the update
+#[derive(Clone, Debug)]
+pub struct SpsaIteration {
+    pub iteration: usize, // from 1
+    pub wdl:       ScoreWdl,
+    pub params:    Vec<SpsaParam>,
+}
+
+impl fmt::Display for SpsaIteration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "iteration {:>5} +{} ={} -{} ",
+            self.iteration, self.wdl.w, self.wdl.d, self.wdl.l
+        )?;
+        let values = self.params.iter().map(|p| format!("{}={}", p.name, p.setting(p.value)));
+        write!(f, "{}", values.format(" "))
+    }
+}
+
+/// Tunes search (or eval) settings by simultaneous perturbation stochastic approximation, as
+/// OpenBench does but locally: each iteration perturbs every parameter up or down at random,
+/// plays `pairs` game pairs (an opening with each side) between an engine with the raised
+/// values and one with the lowered, and moves each parameter towards the winner.
+///
+/// The gains follow the usual schedule, decaying from the first iteration (by `alpha` and
+/// `gamma`, with a stability constant of `a_ratio` times the iterations) so that by the last
+/// iteration each parameter is perturbed by its `c_end` and learns at its `r_end`.
+#[derive(Clone, Debug)]
+pub struct Spsa {
+    pub params:       Vec<SpsaParam>,
+    pub iterations:   usize,
+    pub pairs:        usize,
+    pub tc:           TimeControl,
+    pub openings:     Vec<Epd>,
+    pub random_plies: usize,
+    pub max_moves:    usize,
+    pub seed:         u64,
+    pub alpha:        f64,
+    pub gamma:        f64,
+    pub a_ratio:      f64,
+    pub adjudicator:  Adjudicator,
+}
+
+impl Default for Spsa {
+    fn default() -> Self {
+        Self {
+            params:       vec![],
+            iterations:   1000,
+            pairs:        8,
+            tc:           TimeControl::NodeCount(5000),
+            openings:     vec![Epd::starting_pos()],
+            random_plies: 8,
+            max_moves:    150,
+            seed:         1,
+            alpha:        0.602,
+            gamma:        0.101,
+            a_ratio:      0.1,
+            adjudicator:  Adjudicator::default(),
+        }
+    }
+}
+
+impl Spsa {
+    /// the perturbation and learning rate of a parameter at iteration k (from 0)
+    pub fn gains(&self, p: &SpsaParam, k: usize) -> (f64, f64) {
+        let n = self.iterations as f64;
+        let big_a = self.a_ratio * n;
+        let c = p.c_end * n.powf(self.gamma);
+        let a = p.r_end * p.c_end * p.c_end * (big_a + n).powf(self.alpha);
+        let c_k = c / (k as f64 + 1.0).powf(self.gamma);
+        let a_k = a / (big_a + k as f64 + 1.0).powf(self.alpha);
+        (c_k, a_k / (c_k * c_k))
+    }
+
+    /// the tuned parameters, calling `f` after each iteration
+    pub fn run(
+        &self,
+        config: &EngineConfig,
+        f: &mut impl FnMut(&SpsaIteration) -> Result<()>,
+    ) -> Result<Vec<SpsaParam>> {
+        let mut params = self.params.clone();
+        let mut rng = ChaChaRng::seed_from_u64(self.seed);
+        for k in 0..self.iterations {
+            let deltas = params
+                .iter()
+                .map(|_| if rng.gen_bool(0.5) { 1.0 } else { -1.0 })
+                .collect_vec();
+            let gains = params.iter().map(|p| self.gains(p, k)).collect_vec();
+            let engine = |sign: f64| -> Result<Engine> {
+                let mut config = config.clone();
+                for ((p, &delta), &(c_k, _)) in params.iter().zip(&deltas).zip(&gains) {
+                    config
+                        .settings
+                        .insert(p.name.clone(), p.setting(p.value + sign * c_k * delta));
+                }
+                Engine::with_config(config).context("configuring an engine with the spsa parameters")
+            };
+            let (mut plus, mut minus) = (engine(1.0)?, engine(-1.0)?);
+
+            let mut wdl = ScoreWdl::default();
+            for _ in 0..self.pairs {
+                let opening = game::opening(&self.openings, rng.gen_range(0..self.openings.len().max(1)));
+                let start = game::randomize(opening, self.random_plies, &mut rng);
+                for plus_color in Color::ALL {
+                    let outcome = self.play_game(start.clone(), plus_color, &mut plus, &mut minus)?;
+                    let w = ScoreWdl::from_outcome(&outcome);
+                    wdl += plus_color.chooser_wb(w, ScoreWdl::new(w.l, w.d, w.w));
+                }
+            }
+
+            let result = (wdl.w - wdl.l) as f64;
+            for ((p, delta), (c_k, r_k)) in params.iter_mut().zip(deltas).zip(gains) {
+                p.value = (p.value + r_k * c_k * result * delta).clamp(p.min, p.max);
+            }
+            f(&SpsaIteration {
+                iteration: k + 1,
+                wdl,
+                params: params.clone(),
+            })?;
+        }
+        Ok(params)
+    }
+
+    fn play_game(&self, start: Board, plus_color: Color, plus: &mut Engine, minus: &mut Engine) -> Result<Outcome> {
+        plus.new_game();
+        minus.new_game();
+        let (outcome, _pos) = game::play_game(start, self.max_moves, &self.adjudicator, |pos| {
+            let engine = if pos.board().color_us() == plus_color {
+                &mut *plus
+            } else {
+                &mut *minus
+            };
+            engine.search(pos.to_epd(), self.tc.clone())
+        })?;
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_spsa_params() {
+        let text = "
+            # search
+            aspiration.window, cp, 100, 10, 300, 20, 0.002
+            aspiration.fail_high_growth, Float, 4, 1.5, 8, 0.5, 0.002  # per fail high
+        ";
+        let params = SpsaParam::parse_many(text).unwrap();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].kind, SpsaKind::Cp);
+        assert_eq!(params[0].setting(123.4), "123cp");
+        assert_eq!(params[0].setting(1000.0), "300cp");
+        assert_eq!(params[1].setting(4.25), "4.25");
+        assert_eq!(params[1].to_string().parse::<SpsaParam>().unwrap(), params[1]);
+        assert!("x, int, 5, 0, 3, 1, 0.002".parse::<SpsaParam>().is_err());
+        assert!("x, bool, 1, 0, 3, 1, 0.002".parse::<SpsaParam>().is_err());
+        assert!("x, int, 1, 0, 3".parse::<SpsaParam>().is_err());
+
+        // by the last iteration, the gains are as given
+        let spsa = Spsa {
+            iterations: 500,
+            ..Spsa::default()
+        };
+        let (c_k, r_k) = spsa.gains(&params[0], 499);
+        assert!((c_k - 20.0).abs() < 1e-6 && (r_k - 0.002).abs() < 1e-6, "{c_k} {r_k}");
+        let (c_0, r_0) = spsa.gains(&params[0], 0);
+        assert!(c_0 > c_k && r_0 > r_k);
+    }
+
+    #[test]
+    fn test_spsa_run() {
+        let spsa = Spsa {
+            params: SpsaParam::parse_many("aspiration.fail_high_growth, float, 4, 1.5, 8, 0.5, 0.002").unwrap(),
+            iterations: 2,
+            pairs: 1,
+            tc: TimeControl::Depth(1),
+            max_moves: 10,
+            ..Spsa::default()
+        };
+        let mut iterations = vec![];
+        let tuned = spsa
+            .run(&EngineConfig::default(), &mut |it| {
+                println!("{it}");
+                iterations.push(it.clone());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(iterations.len(), 2);
+        assert!(iterations.iter().all(|it| it.wdl.n() == 2));
+        assert_eq!(tuned, iterations[1].params);
+        assert!((1.5..=8.0).contains(&tuned[0].value));
+
+        let unknown = Spsa {
+            params: SpsaParam::parse_many("no.such.setting, int, 4, 1, 8, 1, 0.002").unwrap(),
+            ..spsa.clone()
+        };
+        assert!(unknown.run(&EngineConfig::default(), &mut |_| Ok(())).is_err());
+
+        // no openings, so the starting position
+        let no_openings = Spsa {
+            openings: vec![],
+            iterations: 1,
+            ..spsa
+        };
+        assert!(no_openings.run(&EngineConfig::default(), &mut |_| Ok(())).is_ok());
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -13,10 +14,19 @@ use odonata_base::infra::component::{Component, State};
 use odonata_base::infra::utils::{Formatting, Uci};
 use odonata_base::infra::value::Stats;
 use odonata_base::infra::version::Version;
-use odonata_base::other::Perft;
+use odonata_base::mv::AutoQueen;
+use odonata_base::other::{Perft, PerftTable};
 use odonata_base::prelude::*;
 
+use crate::cache::tt2::TtSubtreeStats;
+use crate::comms::console::Completions;
+#[cfg(feature = "line_editor")]
+use crate::comms::console::LineEditor;
+use crate::comms::eval_history::EvalHistory;
+use crate::comms::protocol::{InputReader, Protocol};
+use crate::comms::session::SessionState;
 use crate::search::engine::ThreadedSearch;
+use crate::{Engine, EngineConfig, EnginePool};
 
 //  see https://www.chessprogramming.org/CPW-Engine_com
 //
@@ -33,7 +43,7 @@ use crate::search::engine::ThreadedSearch;
 //  type 'bench'
 //
 //  PERFT
-//  type 'go perft [ply/depth]'
+//  type 'perft [ply/depth] [hash mb]', or 'divide [ply/depth] [hash mb]' for counts by move
 //
 //  Move format:
 //  ------------
@@ -80,19 +90,36 @@ use crate::search::engine::ThreadedSearch;
 // gui -> engine: position p1 moves a2a3 b7b6... [- because engine2 played a different move]
 // gui -> engine: go...
 //
+/// longest input line accepted, a "position ... moves ..." of a long game being a few thousand
+const MAX_LINE_LEN: usize = 64 * 1024;
+
 #[derive(Debug)]
 pub struct UciServer {
     pub prelude:               Vec<String>,
     pub strict_error_handling: bool,
+    pub auto_queen:            AutoQueen, // for promotions sent without a piece
+    pub named_positions:       bool,      // "position name kiwipete" once in uci mode
+    pub rerun_go:              bool,      // re-run an interrupted "go" after an EvalFile swap
+    chess960:                  Arc<AtomicBool>, // UCI_Chess960 castling notation, shared with the info callback
+    pub protocol:              Option<Protocol>, // forced, else detected from the first line of input
     running:                   bool,
+    uci_mode:                  bool, // "uci" received, else a console
     board:                     Board,
+    session:                   SessionState, // the position as an epd, and bookmarks
+    last_go:                   Option<String>,
     engine:                    Arc<Mutex<ThreadedSearch>>,
+    pool:                      EnginePool, // json-rpc sessions, independent of the uci engine
+    history:                   Arc<Mutex<EvalHistory>>,
     debug:                     bool,
+    input:                     InputReader,
+    #[cfg(feature = "line_editor")]
+    line_editor:               Option<LineEditor>, // created on first console input at a terminal
 }
 
 impl Component for UciServer {
     fn new_game(&mut self) {
         self.engine.lock().unwrap().set_state(State::NewGame);
+        self.history.lock().unwrap().clear();
     }
 
     fn new_position(&mut self) {}
@@ -101,31 +128,68 @@ impl Component for UciServer {
 #[allow(clippy::useless_format)]
 impl UciServer {
     pub fn configure(settings: HashMap<String, String>) -> anyhow::Result<UciServer> {
-        let mut eng = ThreadedSearch::with_threads(1);
-        eng.configure(settings)?;
-        let engine = Arc::new(Mutex::new(eng));
+        let engine = Engine::with_config(EngineConfig {
+            settings,
+            ..EngineConfig::default()
+        })?;
+        Ok(Self::from_engine(engine))
+    }
+
+    pub fn from_engine(engine: Engine) -> UciServer {
+        let strict_error_handling = engine.strict_error_handling;
+        let history = Arc::new(Mutex::new(EvalHistory::default()));
+        let chess960 = Arc::new(AtomicBool::new(false));
         let uci = UciServer {
-            board:                 Catalog::starting_board(),
-            engine:                Arc::clone(&engine),
-            debug:                 false,
-            running:               false,
-            prelude:               Vec::default(),
-            strict_error_handling: false,
+            board: Catalog::starting_board(),
+            session: SessionState {
+                position: Epd::starting_pos(),
+                ..SessionState::default()
+            },
+            engine: Arc::new(Mutex::new(engine.into_threaded_search())),
+            pool: EnginePool::default().with_eval_history(Arc::clone(&history)),
+            history: Arc::clone(&history),
+            debug: false,
+            input: InputReader::default(),
+            protocol: None,
+            #[cfg(feature = "line_editor")]
+            line_editor: None,
+            running: false,
+            uci_mode: false,
+            named_positions: false,
+            rerun_go: false,
+            chess960: Arc::clone(&chess960),
+            last_go: None,
+            prelude: Vec::default(),
+            strict_error_handling,
+            auto_queen: match strict_error_handling {
+                true => AutoQueen::Strict,
+                false => AutoQueen::default(),
+            },
         };
         {
             let mut eng = uci.engine.lock().unwrap();
             eng.set_position(Epd::from_board(uci.board.clone()));
-            eng.set_callback(Self::uci_info);
+            eng.set_callback(move |info| {
+                history.lock().unwrap().record(info);
+                Self::uci_info_with(info, chess960.load(Ordering::Relaxed));
+            });
         }
-        Ok(uci)
+        uci
     }
 
     pub fn new() -> Result<UciServer> {
         Self::configure(HashMap::new())
     }
 
+    /// input read from `input` rather than stdin
+    pub fn with_input(mut self, input: InputReader) -> Self {
+        self.input = input;
+        self
+    }
+
     pub fn add_prelude(mut self, s: &str) -> Self {
-        self.prelude.extend(Itertools::intersperse(s.split(';'), "isready").map(String::from));
+        self.prelude
+            .extend(Itertools::intersperse(s.split(';'), "isready").map(String::from));
         self
     }
 
@@ -136,19 +200,20 @@ impl UciServer {
     }
 
     fn print_info_string(msg: &str) {
-        let send = format!("info string {}", msg.replace('\n', "\ninfo string "));
-        info!("<< {send}");
-        println!("{send}");
-        debug!(target: "uci", "<< {send}");
+        Self::print(&format!("info string {}", msg.replace('\n', "\ninfo string ")));
     }
 
+    /// writes and flushes under a single lock of stdout, so lines from the search thread and
+    /// the uci thread never interleave, and are not held back in a pipe's buffer (eg on Windows)
     fn print(send: &str) {
+        let mut stdout = io::stdout().lock();
         for line in send.lines() {
-            println!("{}", line);
+            writeln!(stdout, "{}", line).ok();
             info!("<< {}", line);
             debug!(target: "uci", "<< {line}");
             log::logger().flush();
         }
+        stdout.flush().ok();
     }
 
     pub fn uci_compiler(&self) -> Result<()> {
@@ -161,6 +226,9 @@ impl UciServer {
         Self::print("releases and licence details.");
         Self::print("Commands...");
         Self::print(&format!("{:<10} enter uci protocol mode", "uci"));
+        Self::print(&format!("{:<10} eg 'position name kiwipete'", "position"));
+        Self::print(&format!("{:<10} list catalog position names", "positions"));
+        Self::print(&format!("{:<10} 'config snapshot' or 'config rollback'", "config"));
         Self::print(&format!("{:<10} quit the program", "quit"));
         Ok(())
     }
@@ -179,24 +247,46 @@ impl UciServer {
     }
 
     fn readline_and_execute(&mut self) {
-        let mut input = String::new();
-        if !self.prelude.is_empty() {
-            input = self.prelude.remove(0);
+        let input = if !self.prelude.is_empty() {
+            self.prelude.remove(0)
+        } else if let Some(input) = self.read_input() {
+            if self.protocol.is_none() {
+                self.protocol = Protocol::detect(&input);
+            }
+            input
         } else {
-            let bytes_read = io::stdin().read_line(&mut input).unwrap();
-            if bytes_read == 0 {
-                // exit without printing as the pipe is broken
-                // self.uci_quit().unwrap();
-                self.engine.lock().unwrap().search_stop();
-                self.running = false;
+            // exit without printing as the pipe is broken
+            // self.uci_quit().unwrap();
+            self.engine.lock().unwrap().search_stop();
+            self.running = false;
+            return;
+        };
+        let input = match self.sanitize_input(&input) {
+            Ok(input) => input,
+            Err(e) => {
+                warn!(target: "uci", "!! uci error '{e:#}'");
+                Self::print_info_string(&format!("error '{e:#}'"));
                 return;
             }
-        }
+        };
         let words: Vec<&str> = input.split_whitespace().collect();
         if words.is_empty() {
             return;
         }
         Self::recv(&input);
+        if self.protocol == Some(Protocol::JsonRpc) || self.is_json_request(input.trim_start()) {
+            Self::print(&self.pool.handle_json_rpc(&input));
+            io::stdout().flush().ok();
+            return;
+        }
+        if self.protocol == Some(Protocol::Xboard) {
+            Self::print(
+                "tellusererror Odonata speaks uci (and json-rpc) but not xboard, please add it as a uci engine",
+            );
+            self.engine.lock().unwrap().search_stop();
+            self.running = false;
+            return;
+        }
         let res = match words[0] {
             "uci" => self.uci_uci(),
             "isready" => self.uci_isready(),
@@ -214,11 +304,20 @@ impl UciServer {
             "ext:legal_moves" => self.ext_uci_legal_moves(&Args::parse(&input)),
             "ext:make_moves" => self.ext_uci_make_moves(&Args::parse(&input)),
             "ext:version" => self.ext_uci_version(&Args::parse(&input)),
+            "ext:eval_history" => self.ext_uci_eval_history(&Args::parse(&input)),
             // "ext:move_attributes" => self.ext_uci_move_attributes(&Args::parse(&input)),
             "sleep" => self.uci_sleep(&words[1..]),
             "perft" => self.uci_perft(&words[1..]),
             "perft_cat" => self.uci_perft_cat(&words[1..]),
+            "divide" => self.uci_divide(&words[1..]),
             "board" | "d" => self.uci_board(),
+            "ttentry" => self.uci_tt_entry(&words[1..]),
+            "tt" => self.uci_tt(&words[1..]),
+            "positions" => self.uci_positions(&words[1..]),
+            "tree" => self.uci_tree(&words[1..]),
+            "mark" => self.uci_mark(&input),
+            "marks" => self.uci_marks(&words[1..]),
+            "config" => self.uci_config(&words[1..]),
             "bench" => self.ext_uci_bench(),
             "compiler" => self.uci_compiler(),
             "show_options" => self.uci_show_options(),
@@ -240,6 +339,60 @@ impl UciServer {
         io::stdout().flush().ok();
     }
 
+    /// a line of input, or None at the end of input. In the console at a terminal, lines are
+    /// edited with tab-completion and history (with feature `line_editor`)
+    fn read_input(&mut self) -> Option<String> {
+        #[cfg(feature = "line_editor")]
+        if !self.uci_mode && self.input.is_terminal() {
+            let completions = self.completions();
+            if self.line_editor.is_none() {
+                self.line_editor = LineEditor::new().ok();
+            }
+            if let Some(editor) = &mut self.line_editor {
+                return editor.readline(completions);
+            }
+        }
+        self.input.read_line()
+    }
+
+    /// completions of console input in the current position
+    pub fn completions(&self) -> Completions {
+        let mut options = self.engine.lock().unwrap().options().into_keys().collect_vec();
+        options.extend(["Named Positions".to_string(), "Rerun Go On EvalFile".to_string()]);
+        Completions {
+            board: self.board.clone(),
+            options,
+        }
+    }
+
+    /// cleans up input from Windows guis or pasted from web pages: byte order marks, carriage
+    /// returns and other control or zero width characters. In the console, unicode chess
+    /// pieces are read as piece letters, so "♞f6" is "Nf6"
+    fn sanitize_input(&self, input: &str) -> Result<String> {
+        if input.len() > MAX_LINE_LEN {
+            bail!(
+                "input line of {} bytes exceeds the maximum of {MAX_LINE_LEN}",
+                input.len()
+            );
+        }
+        let console = !self.uci_mode;
+        let mut s = String::with_capacity(input.len());
+        for ch in input.chars() {
+            match ch {
+                '\u{feff}' | '\u{200b}'..='\u{200d}' | '\u{2060}' => {}
+                '♔' | '♚' if console => s.push('K'),
+                '♕' | '♛' if console => s.push('Q'),
+                '♖' | '♜' if console => s.push('R'),
+                '♗' | '♝' if console => s.push('B'),
+                '♘' | '♞' if console => s.push('N'),
+                '♙' | '♟' if console => {}
+                c if c.is_control() => s.push(' '),
+                c => s.push(c),
+            }
+        }
+        Ok(s)
+    }
+
     fn uci_unknown(&mut self, words: &[&str]) -> Result<()> {
         bail!("unknown command {:?}", words)
     }
@@ -288,8 +441,16 @@ impl UciServer {
     }
 
     fn uci_uci(&mut self) -> Result<()> {
-        Self::print(&format!("id name {}", Version::name_and_version()));
+        self.uci_mode = true;
+        Self::print(&format!("id name {}", self.engine.lock().unwrap().identity()));
         Self::print(&format!("id author {}", Version::AUTHORS));
+        let memory = self.engine.lock().unwrap().memory_report()?;
+        let eval = &self.engine.lock().unwrap().search.eval;
+        if let Some(warning) = &eval.warning {
+            Self::print_info_string(warning);
+        }
+        Self::print_info_string(&format!("eval backend {}", eval.backend));
+        Self::print_info_string(&memory);
         self.uci_show_options()?;
         Self::print("uciok");
         Ok(())
@@ -305,15 +466,31 @@ impl UciServer {
         Ok(())
     }
 
-    fn uci_perft(&mut self, words: &[&str]) -> Result<()> {
-        self.engine.lock().unwrap().search_stop();
+    /// depth and optional hash table size in mb
+    fn perft_args(words: &[&str]) -> Result<(u32, usize)> {
         let depth = words.first().ok_or(anyhow!("Must specify a depth"))?;
         let depth = depth
             .parse::<u32>()
             .map_err(|_| anyhow!("Depth {} must be numeric", depth))?;
+        let hash_mb = match words.get(1) {
+            Some(mb) => mb
+                .parse::<usize>()
+                .map_err(|_| anyhow!("Hash {} must be numeric", mb))?,
+            None => 0,
+        };
+        Ok((depth, hash_mb))
+    }
+
+    fn uci_perft(&mut self, words: &[&str]) -> Result<()> {
+        self.engine.lock().unwrap().search_stop();
+        let (depth, hash_mb) = Self::perft_args(words)?;
+        let mut table = PerftTable::new(hash_mb);
         for d in 1..=depth {
             let t = Instant::now();
-            let p = Perft::<true>::count(&self.board, d);
+            let p = match hash_mb {
+                0 => Perft::<true>::count(&self.board, d),
+                _ => Perft::<true>::count_hashed(&self.board, d, &mut table),
+            };
             let elapsed = t.elapsed();
             Self::print(&format!(
                 "perft({}) = {:<12} in {}",
@@ -331,6 +508,23 @@ impl UciServer {
         Ok(())
     }
 
+    fn uci_divide(&mut self, words: &[&str]) -> Result<()> {
+        self.engine.lock().unwrap().search_stop();
+        let (depth, hash_mb) = Self::perft_args(words)?;
+        let t = Instant::now();
+        let counts = Perft::<true>::divide(&self.board, depth, &mut PerftTable::new(hash_mb));
+        for (mv, count) in &counts {
+            Self::print(&format!("{}: {count}", mv.to_uci()));
+        }
+        let total: u64 = counts.iter().map(|(_mv, count)| count).sum();
+        Self::print(&format!(
+            "\nmoves {}\nnodes {total} in {}",
+            counts.len(),
+            Formatting::duration(t.elapsed())
+        ));
+        Ok(())
+    }
+
     pub fn uci_perft_cat(&mut self, words: &[&str]) -> Result<()> {
         self.engine.lock().unwrap().search_stop();
         let depth = words.first().ok_or(anyhow!("Must specify a depth"))?;
@@ -407,17 +601,28 @@ impl UciServer {
         Ok(())
     }
 
+    /// `ext:eval_history` as json, or `ext:eval_history table` as text
+    fn ext_uci_eval_history(&mut self, arg: &Args) -> Result<()> {
+        let history = self.history.lock().unwrap();
+        if arg.contain("table") {
+            Self::print(&history.to_string());
+        } else {
+            Self::print(&format!("result:{}", serde_json::to_string(history.points())?));
+        }
+        Ok(())
+    }
+
     fn ext_uci_make_moves(&mut self, arg: &Args) -> Result<()> {
         let mut b = Board::new_empty();
-        Self::parse_fen(arg, &mut b)?;
-        let var = Self::parse_moves(arg, &b)?;
+        self.parse_fen(arg, &mut b)?;
+        let var = Self::parse_moves(arg, &b, self.auto_queen, false)?;
         Self::print(&format!("result:{}", b.make_moves_old(&var).to_fen()));
         Ok(())
     }
 
     fn ext_uci_legal_moves(&mut self, arg: &Args) -> Result<()> {
         let mut b = Board::new_empty();
-        Self::parse_fen(arg, &mut b)?;
+        self.parse_fen(arg, &mut b)?;
         let moves = b.legal_moves();
         Self::print(&format!("result:{}", moves.to_uci()));
         Ok(())
@@ -435,26 +640,34 @@ impl UciServer {
     fn uci_position(&mut self, arg: &Args) -> Result<()> {
         self.engine.lock().unwrap().search_stop();
         let mut origin = Board::default();
-        Self::parse_fen(arg, &mut origin)?;
-        let variation = Self::parse_moves(arg, &origin)?;
+        self.parse_fen(arg, &mut origin)?;
+        let variation = Self::parse_moves(arg, &origin, self.auto_queen, !self.uci_mode)?;
         let epd = Epd::from_var(origin, variation);
         self.board = epd.board();
+        self.session.position = epd.clone();
         self.engine.lock().unwrap().set_position(epd);
         Ok(())
     }
 
-    fn parse_fen(arg: &Args, b: &mut Board) -> Result<()> {
+    fn parse_fen(&self, arg: &Args, b: &mut Board) -> Result<()> {
         let fen = arg.words.get(1);
         if let Some(fen) = fen {
             if fen == "startpos" {
                 *b = Catalog::starting_board();
+            } else if fen == "name" {
+                if self.uci_mode && !self.named_positions {
+                    bail!("position name requires setoption name Named Positions value true");
+                }
+                let end = arg.index_of("moves").unwrap_or(arg.words.len());
+                let name = arg.words.get(2..end).unwrap_or_default().join(" ");
+                *b = Catalog::named(&name)?.board();
             } else if fen == "fen" {
-                // expect pos, b/w, castling, ep and 2 x counts
-                let fen = arg.words.get(2..8);
-                if let Some(fen) = fen {
-                    *b = Board::parse_fen(&fen.join(" "))?;
-                } else {
-                    bail!("Fen or parts of fen are missing");
+                // expect pos, b/w, castling, ep and 2 x counts (counts default to "0 1" if omitted)
+                let end = arg.index_of("moves").unwrap_or(arg.words.len());
+                match arg.words.get(2..end) {
+                    Some(fen) if fen.len() == 6 => *b = Board::parse_fen(&fen.join(" "))?,
+                    Some(fen) if fen.len() == 4 => *b = Board::parse_fen(&(fen.join(" ") + " 0 1"))?,
+                    _ => bail!("Fen or parts of fen are missing"),
                 }
             } else {
                 bail!("must specify fen or startpos after position command");
@@ -465,13 +678,21 @@ impl UciServer {
         Ok(())
     }
 
-    fn parse_moves(args: &Args, board: &Board) -> Result<Variation> {
+    /// uci moves, or in the console (`san` true) also san moves such as "Nf6"
+    fn parse_moves(args: &Args, board: &Board, auto_queen: AutoQueen, san: bool) -> Result<Variation> {
         let mut variation = Variation::new();
         let index = args.index_of("moves");
         let mut b = board.clone();
         if let Some(index) = index {
             for mv in args.words[(index + 1)..].iter() {
-                let mv = b.parse_uci_move(mv)?;
+                let mv = match b.parse_uci_move_with(mv, auto_queen) {
+                    Ok(mv) => mv,
+                    Err(_) if san => b.parse_san_move_with(mv, auto_queen)?,
+                    Err(e) => return Err(e),
+                };
+                if mv.is_null() {
+                    bail!("null move in position moves");
+                }
                 b = b.make_move(mv);
                 variation.push(mv)
             }
@@ -515,7 +736,9 @@ impl UciServer {
         // self.log_debug_message(&format!("{}", self.engine.lock().unwrap().algo));
         // self.log_debug_message(&format!("{}", self.board));
         info!("odonata: searching {} on tc {tc}", self.board.to_fen());
+        self.history.lock().unwrap().start_search(self.board.clone());
         self.engine.lock().unwrap().search_start();
+        self.last_go = Some(input.to_string());
         // if !self.prelude.is_empty() {
         //     self.engine.lock().unwrap().wait()
         // }
@@ -542,10 +765,25 @@ impl UciServer {
         //     engine.configment("debug", value)?;
 
         // } else
+        if name == "Named Positions" {
+            self.named_positions = value.parse()?;
+            return Ok(());
+        }
+        if name == "Rerun Go On EvalFile" {
+            self.rerun_go = value.parse()?;
+            return Ok(());
+        }
+        if name == "UCI_Chess960" {
+            self.chess960.store(value.parse()?, Ordering::Relaxed);
+            return Ok(());
+        }
         let mut eng = self.engine.lock().unwrap();
 
         if eng.options().contains_key(name) {
             eng.set_option(name, value)?;
+            if name == "MemoryLimitMB" {
+                Self::print_info_string(&eng.memory_report()?);
+            }
             return Ok(());
         }
         warn!("Unknown action '{}'", name);
@@ -558,6 +796,8 @@ impl UciServer {
     }
 
     fn uci_setoption(&mut self, input: &str) -> Result<()> {
+        // a new network mid-analysis aborts the search, and optionally re-runs it on the new network
+        let interrupted = self.engine.lock().unwrap().is_searching();
         self.engine.lock().unwrap().search_stop();
         let s1 = input
             .trim_start()
@@ -585,6 +825,12 @@ impl UciServer {
                 .lock()
                 .unwrap()
                 .set_position(Epd::from_board(self.board.clone()));
+            if name == "EvalFile" && interrupted && self.rerun_go {
+                if let Some(go) = self.last_go.clone() {
+                    info!("re-running '{go}' on new network");
+                    self.uci_go(&go)?;
+                }
+            }
         } else {
             self.uci_option_name_value(s.trim(), "")?;
         };
@@ -598,6 +844,91 @@ impl UciServer {
         for op in &Self::uci_options(&engine) {
             Self::print(op);
         }
+        Self::print(&format!(
+            "option name Named Positions type check default {}",
+            self.named_positions
+        ));
+        Self::print(&format!(
+            "option name Rerun Go On EvalFile type check default {}",
+            self.rerun_go
+        ));
+        Self::print(&format!(
+            "option name UCI_Chess960 type check default {}",
+            self.chess960.load(Ordering::Relaxed)
+        ));
+        Ok(())
+    }
+
+    /// the analysis tree: `tree show` below the current position, `tree export pgn [file]`
+    /// from the first position analysed, or `tree clear`
+    fn uci_tree(&mut self, words: &[&str]) -> Result<()> {
+        let mut eng = self.engine.lock().unwrap();
+        eng.wait();
+        let tree = &mut eng.search.analysis_tree;
+        let root = tree.root().unwrap_or_else(|| self.board.clone());
+        match words {
+            ["show"] => Self::print(&tree.show(&self.board)),
+            ["export", "pgn"] => Self::print(&tree.to_pgn(&root)),
+            ["export", "pgn", file] => fs_err::write(file, tree.to_pgn(&root))?,
+            ["clear"] => tree.clear(),
+            _ => bail!("expected tree show, tree export pgn [file] or tree clear"),
+        }
+        Ok(())
+    }
+
+    /// bookmarks the current position with a note, eg `mark "critical zugzwang"`
+    fn uci_mark(&mut self, input: &str) -> Result<()> {
+        let note = input.trim_start().strip_prefix("mark").unwrap_or_default();
+        let n = self.session.mark(note);
+        Self::print_info_string(&format!("bookmark {n}"));
+        Ok(())
+    }
+
+    /// the bookmarks: `marks` lists them, `marks goto N` jumps back to one, `marks unmark N`,
+    /// `marks export epd [file]` (with the notes as comments) or `marks clear`
+    fn uci_marks(&mut self, words: &[&str]) -> Result<()> {
+        match words {
+            [] => Self::print(&self.session.to_string()),
+            ["goto", n] => {
+                let epd = self.session.jump(n.parse()?)?;
+                let mut eng = self.engine.lock().unwrap();
+                eng.search_stop();
+                self.board = epd.board();
+                eng.set_position(epd);
+            }
+            ["unmark", n] => {
+                self.session.unmark(n.parse()?)?;
+            }
+            ["export", "epd"] => Self::print(&self.session.to_epd()),
+            ["export", "epd", file] => fs_err::write(file, self.session.to_epd())?,
+            ["clear"] => self.session.clear(),
+            _ => bail!("expected marks, marks goto N, marks unmark N, marks export epd [file] or marks clear"),
+        }
+        Ok(())
+    }
+
+    /// snapshots of the engine configuration, so that trial settings can be reverted
+    fn uci_config(&mut self, words: &[&str]) -> Result<()> {
+        let mut eng = self.engine.lock().unwrap();
+        eng.search_stop();
+        match words {
+            ["snapshot"] => {
+                let n = eng.config_snapshot();
+                Self::print_info_string(&format!("config snapshot {n} taken"));
+            }
+            ["rollback"] => {
+                let n = eng.config_rollback()?;
+                Self::print_info_string(&format!("config rolled back ({n} snapshots left)"));
+            }
+            _ => bail!("expected config snapshot or config rollback"),
+        }
+        Ok(())
+    }
+
+    /// the catalog position names starting with a prefix, for completing "position name"
+    fn uci_positions(&mut self, words: &[&str]) -> Result<()> {
+        let prefix = words.join(" ");
+        Self::print(&Catalog::names(&prefix).join("\n"));
         Ok(())
     }
 
@@ -636,7 +967,7 @@ impl UciServer {
             diag = self.board.to_diagram(),
             fen = self.board.to_fen()
         ));
-        Self::print(&format!("Quiet: {qspv}", qspv = quiet_pv.to_san(&self.board),));
+        Self::print(&format!("Quiet: {qspv}", qspv = quiet_pv.display_san(&self.board),));
         Self::print(&format!("Material advantage: {}", &self.board.material().balance()));
         Self::print(&format!("Static eval:\n{:#}", static_eval));
 
@@ -682,6 +1013,53 @@ impl UciServer {
         Ok(())
     }
 
+    /// the tt entry for the fen (or startpos), by default for the current position
+    fn uci_tt_entry(&mut self, words: &[&str]) -> Result<()> {
+        let mut eng = self.engine.lock().unwrap();
+        eng.search_stop();
+        let bd = match words {
+            [] => self.board.clone(),
+            ["startpos"] => Catalog::starting_board(),
+            [_, _, _, _] => Board::parse_fen(&(words.join(" ") + " 0 1"))?,
+            _ => Board::parse_fen(&words.join(" "))?,
+        };
+        let tt = &eng.search.tt;
+        let hash = bd.hash();
+        match tt.probe_entry(hash) {
+            Some((node, age)) => Self::print(&format!(
+                "ttentry hash {hash:016x} depth {depth} bound {bt} score {sc} move {mv} eval {ev} age {age} (current {cur})",
+                depth = node.depth,
+                bt = node.bt,
+                sc = node.score.as_score(0),
+                mv = match node.bm.validate(&bd) {
+                    Ok(mv) => mv.to_uci(),
+                    Err(_) if node.bm.is_null() => "none".to_string(),
+                    Err(e) => format!("{bm} ({e})", bm = node.bm),
+                },
+                ev = node.eval,
+                cur = tt.current_age,
+            )),
+            None => Self::print(&format!("ttentry hash {hash:016x} not found")),
+        }
+        Ok(())
+    }
+
+    /// `tt stats subtree [plies]` summarises the tt entries reachable from the current
+    /// position (by default within 3 plies), once any search has finished
+    fn uci_tt(&mut self, words: &[&str]) -> Result<()> {
+        let max_ply = match words {
+            ["stats", "subtree"] => 3,
+            ["stats", "subtree", plies] => plies.parse()?,
+            _ => bail!("expected tt stats subtree [plies]"),
+        };
+        let mut eng = self.engine.lock().unwrap();
+        eng.wait();
+        let tt = &eng.search.tt;
+        let stats = TtSubtreeStats::new(tt.subtree(&self.board, max_ply, false), tt.current_age);
+        Self::print(&stats.to_string());
+        Ok(())
+    }
+
     fn uci_stop(&mut self) -> Result<()> {
         self.engine.lock().unwrap().search_stop();
         // Self::print_bm_and_ponder(&self.algo.bm(), &self.algo.pv() );
@@ -694,23 +1072,29 @@ impl UciServer {
     }
 
     pub fn uci_info(info: &Info) {
+        Self::uci_info_with(info, false)
+    }
+
+    /// with castles written king-takes-rook when `chess960`
+    pub fn uci_info_with(info: &Info, chess960: bool) {
         if info.kind == InfoKind::BestMove {
-            Self::print_bm_and_ponder(info.pv.as_ref());
+            Self::print_bm_and_ponder(info.pv.as_ref(), chess960);
         } else {
-            Self::print(&format!("info {}", info.to_uci()));
+            Self::print(&format!("info {}", info.to_uci_with(chess960)));
         }
     }
 
-    fn print_bm_and_ponder(var: Option<&Variation>) {
+    fn print_bm_and_ponder(var: Option<&Variation>, chess960: bool) {
         let bm = if let Some(mv) = var.and_then(|v| v.first()) {
             mv
         } else {
             info!("---> Null  best move");
             Move::new_null()
         };
-        let mut output = format!("bestmove {}", bm.to_uci());
+        let mut output = format!("bestmove {}", bm.to_uci_with(chess960));
         if var.is_some() && var.unwrap().len() > 1 {
-            output = format!("{} ponder {}", output, var.unwrap().second().unwrap().to_uci());
+            let ponder = var.unwrap().second().unwrap();
+            output = format!("{} ponder {}", output, ponder.to_uci_with(chess960));
         }
         Self::print(&output);
     }
@@ -739,6 +1123,10 @@ impl Args {
 
 #[cfg(test)]
 mod tests {
+    use odonata_base::other::outcome::Outcome;
+    use rand::seq::SliceRandom as _;
+    use rand::{Rng as _, SeedableRng as _};
+    use rand_chacha::ChaChaRng;
     use test_log::test;
 
     use super::*;
@@ -755,11 +1143,51 @@ mod tests {
             .run();
     }
 
+    #[test]
+    fn test_uci_protocol_detect() {
+        let input = |s: &'static str| InputReader::from_reader(io::Cursor::new(s));
+        let mut uci = UciServer::new().unwrap().with_input(input("\nuci\nisready\n"));
+        uci.run();
+        assert_eq!((uci.protocol, uci.uci_mode), (Some(Protocol::Uci), true));
+
+        // the position command is answered as json-rpc, and so ignored
+        let json = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"open\"}\nposition startpos moves e2e4\n";
+        let mut uci = UciServer::new().unwrap().with_input(input(json));
+        uci.run();
+        assert_eq!(uci.protocol, Some(Protocol::JsonRpc));
+        assert_eq!(uci.board, Board::starting_pos());
+
+        // xboard is declined, and the following commands unread
+        let mut uci = UciServer::new()
+            .unwrap()
+            .with_input(input("xboard\nposition startpos moves e2e4\n"));
+        uci.run();
+        assert_eq!(uci.protocol, Some(Protocol::Xboard));
+        assert_eq!(uci.board, Board::starting_pos());
+
+        let mut uci = UciServer::new()
+            .unwrap()
+            .with_input(input("position startpos moves e4\nuci\n"));
+        uci.run();
+        assert_eq!(uci.protocol, Some(Protocol::Console));
+        assert_eq!(uci.board, Board::starting_pos().make_moves_str("e4").unwrap());
+
+        // a forced protocol is not redetected
+        let mut uci = UciServer::new()
+            .unwrap()
+            .with_input(input("xboard\nposition startpos moves e2e4\n"));
+        uci.protocol = Some(Protocol::Uci);
+        uci.run();
+        assert_eq!(uci.board, Board::starting_pos().make_moves_str("e4").unwrap());
+    }
+
     #[test]
     fn test_uci_perft() {
         UciServer::new()
             .unwrap()
             .add_prelude("perft 1")
+            .add_prelude("perft 3 1")
+            .add_prelude("divide 2")
             .add_prelude("quit")
             .run();
     }
@@ -775,6 +1203,221 @@ mod tests {
             .run();
     }
 
+    #[test]
+    fn test_uci_tt_entry() {
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("position startpos moves e2e4")
+            .add_prelude("go depth 4")
+            .add_prelude("isready")
+            .add_prelude("ttentry")
+            .add_prelude("ttentry startpos")
+            .add_prelude("ttentry 8/8/8/8/8/8/8/K1k5 w - -")
+            .add_prelude("ttentry junk")
+            .add_prelude("tt stats subtree")
+            .add_prelude("tt stats subtree 1")
+            .add_prelude("tt stats")
+            .add_prelude("setoption name Clear Hash")
+            .add_prelude("quit");
+        uci.run();
+        let hash = uci.board.hash();
+        assert!(uci.engine.lock().unwrap().search.tt.probe_entry(hash).is_none());
+    }
+
+    #[test]
+    fn test_uci_named_positions() {
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("position name kiwipete moves e1g1")
+            .add_prelude("positions WAC.01")
+            .add_prelude("quit");
+        uci.run();
+        let kiwipete = Catalog::perft_kiwipete().0;
+        assert_eq!(uci.board, kiwipete.make_move(kiwipete.parse_uci_move("e1g1").unwrap()));
+
+        // in uci mode, only once enabled
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("uci")
+            .add_prelude("position name WAC.001")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, Catalog::starting_board());
+
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("uci")
+            .add_prelude("setoption name Named Positions value true")
+            .add_prelude("position name WAC.001")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, Catalog::win_at_chess()[0].board());
+    }
+
+    #[test]
+    fn test_uci_tree() {
+        let file = std::env::temp_dir().join(format!("odonata-tree-{}.pgn", std::process::id()));
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("setoption name Analysis Tree value true")
+            .add_prelude("position startpos")
+            .add_prelude("go depth 4")
+            .add_prelude("isready")
+            .add_prelude("position startpos moves d2d4")
+            .add_prelude("go depth 4")
+            .add_prelude("isready")
+            .add_prelude("tree show")
+            .add_prelude(&format!("tree export pgn {}", file.display()))
+            .add_prelude("quit");
+        uci.run();
+        assert!(uci.engine.lock().unwrap().search.analysis_tree.len() >= 4);
+        let pgn = fs_err::read_to_string(&file).unwrap();
+        assert!(pgn.contains("d4"));
+        fs_err::remove_file(file).unwrap();
+
+        uci = uci.add_prelude("tree clear").add_prelude("quit");
+        uci.run();
+        assert!(uci.engine.lock().unwrap().search.analysis_tree.is_empty());
+    }
+
+    #[test]
+    fn test_uci_marks() {
+        let file = std::env::temp_dir().join(format!("odonata-marks-{}.epd", std::process::id()));
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("position startpos moves e2e4 e7e5")
+            .add_prelude("mark \"open game\"")
+            .add_prelude("position startpos moves d2d4")
+            .add_prelude("mark critical zugzwang")
+            .add_prelude("marks")
+            .add_prelude("marks goto 1")
+            .add_prelude(&format!("marks export epd {}", file.display()))
+            .add_prelude("quit");
+        uci.run();
+        let e4e5 = Board::starting_pos().make_moves_str("e2e4 e7e5").unwrap();
+        assert_eq!(uci.board, e4e5);
+        assert_eq!(uci.session.bookmark(2).unwrap().note, "critical zugzwang");
+        let epds = fs_err::read_to_string(&file).unwrap();
+        assert!(epds.contains("c0 \"open game\""), "{epds}");
+        assert_eq!(epds.lines().count(), 2);
+        fs_err::remove_file(file).unwrap();
+
+        uci = uci
+            .add_prelude("marks unmark 1")
+            .add_prelude("marks goto 5")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.session.bookmarks().len(), 1);
+    }
+
+    #[test]
+    fn test_uci_sanitize_input() {
+        let uci = UciServer::new().unwrap();
+        assert_eq!(uci.sanitize_input("\u{feff}isready\r\n").unwrap().trim(), "isready");
+        assert_eq!(
+            uci.sanitize_input("position startpos moves e4 ♞f6").unwrap(),
+            "position startpos moves e4 Nf6"
+        );
+        assert!(uci.sanitize_input(&"x".repeat(MAX_LINE_LEN + 1)).is_err());
+
+        let mut uci = uci
+            .add_prelude("\u{feff}position startpos moves e2e4 e7e5\r\n")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, Board::starting_pos().make_moves_str("e4 e5").unwrap());
+
+        // san and unicode pieces in the console, but not once in uci mode
+        uci = uci
+            .add_prelude("position startpos moves e4 ♞f6 ♘c3")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, Board::starting_pos().make_moves_str("e4 Nf6 Nc3").unwrap());
+        uci.uci_mode = true;
+        uci.strict_error_handling = false;
+        uci = uci.add_prelude("position startpos moves e4").add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, Board::starting_pos().make_moves_str("e4 Nf6 Nc3").unwrap());
+    }
+
+    /// mangled commands are rejected (or survive) without a panic, and the engine stays usable
+    #[test]
+    fn test_uci_fuzz_input() {
+        let commands = [
+            "position startpos moves e2e4 e7e5",
+            "position fen r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3 moves f1b5",
+            "position name kiwipete",
+            "ext:legal_moves fen 8/8/8/8/8/8/8/K6k w - - 0 1",
+            "setoption name MultiPV value 2",
+            "positions kiwi",
+            "debug on",
+            "isready",
+        ];
+        let junk = [
+            '\u{feff}', '\r', '\0', '\u{a0}', '\u{200b}', 'é', '漢', '♞', '🦋', '\u{7f}', ' ',
+        ];
+        let mut rng = ChaChaRng::seed_from_u64(1);
+        let mut uci = UciServer::new().unwrap();
+        uci.strict_error_handling = false;
+        for _ in 0..500 {
+            let mut line = commands.choose(&mut rng).unwrap().chars().collect_vec();
+            for _ in 0..rng.gen_range(0..4) {
+                let i = rng.gen_range(0..=line.len());
+                match rng.gen_range(0..3) {
+                    0 => line.insert(i, *junk.choose(&mut rng).unwrap()),
+                    1 if i < line.len() => {
+                        line.remove(i);
+                    }
+                    _ => line.truncate(i),
+                }
+            }
+            uci.prelude.push(line.into_iter().collect());
+        }
+        uci = uci.add_prelude("position startpos moves e2e4").add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, Board::starting_pos().make_moves_str("e4").unwrap());
+    }
+
+    #[test]
+    fn test_uci_config() {
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("config snapshot")
+            .add_prelude("setoption name MultiPV value 4")
+            .add_prelude("setoption name Init value eval.hce.quantum=200")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.engine.lock().unwrap().search.controller.multi_pv, 4);
+        assert_eq!(uci.engine.lock().unwrap().search.eval.hce.quantum, 200);
+
+        uci = uci.add_prelude("config rollback").add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.engine.lock().unwrap().search.controller.multi_pv, 1);
+        assert_eq!(uci.engine.lock().unwrap().search.eval.hce.quantum, 1);
+    }
+
+    #[test]
+    fn test_uci_eval_file() {
+        let net = [env!("CARGO_MANIFEST_DIR"), "/resources/r61-net.i16.bin"].concat();
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("setoption name Rerun Go On EvalFile value true")
+            .add_prelude("position startpos")
+            .add_prelude("go infinite")
+            .add_prelude(&format!("setoption name EvalFile value {net}"))
+            .add_prelude("quit");
+        uci.run();
+        assert!(uci.rerun_go);
+        assert_eq!(uci.last_go.as_deref(), Some("go infinite"));
+        assert_eq!(uci.engine.lock().unwrap().search.eval.nnue_file.to_string_lossy(), net);
+
+        // a bad network is rejected, and the current one kept
+        uci = uci
+            .add_prelude("setoption name EvalFile value no-such-net.bin")
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.engine.lock().unwrap().search.eval.nnue_file.to_string_lossy(), net);
+    }
+
     // #[test]
     // fn test_uci_config_file() {
     //     let mut uci = UciServer::new().unwrap();
@@ -797,13 +1440,25 @@ mod tests {
             .add_prelude("setoption name MultiPV value 6")
             .add_prelude("setoption name Init value tt.mb=2")
             .add_prelude("setoption name Best Book Move value false")
+            .add_prelude("setoption name Info Interval value 250")
+            .add_prelude("setoption name UCI_ShowWDL value true")
+            .add_prelude("setoption name UCI_Chess960 value true")
+            .add_prelude("setoption name BookDepth value 16")
+            .add_prelude("setoption name BookVerifyNodes value 5000")
+            .add_prelude("setoption name HandicapThreatBlindness value 30")
             .add_prelude("quit");
         uci.run();
         assert_eq!(uci.engine.lock().unwrap().search.opening_book.own_book, true);
+        assert_eq!(uci.engine.lock().unwrap().search.opening_book.max_ply, 16);
+        assert_eq!(uci.engine.lock().unwrap().search.opening_book.verify_nodes, 5000);
+        assert_eq!(uci.engine.lock().unwrap().search.eval.handicap.threat_blindness, 30);
         assert_eq!(uci.engine.lock().unwrap().search.controller.multi_pv, 6);
         assert_eq!(uci.engine.lock().unwrap().search.tt.enabled, false, "tt enabled");
         assert_eq!(uci.engine.lock().unwrap().search.tt.mb, 2);
         assert_eq!(uci.engine.lock().unwrap().search.opening_book.best_book_line, false);
+        assert_eq!(uci.engine.lock().unwrap().search.controller.info_interval_ms, 250);
+        assert_eq!(uci.engine.lock().unwrap().search.controller.show_wdl, true);
+        assert_eq!(uci.chess960.load(Ordering::Relaxed), true);
     }
 
     #[test]
@@ -846,6 +1501,38 @@ mod tests {
                 .unwrap()
                 .to_fen()
         );
+
+        // mid-game fen without counters, the move tail repeating the fen position
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude("position fen 8/1k6/8/8/8/2K4R/8/8 w - - moves h3h1 b7a8 h1h3 a8b7 h3h1 b7a8 h1h3 a8b7")
+            .add_prelude("quit");
+        uci.run();
+        let pos = uci.engine.lock().unwrap().search.position.clone();
+        assert_eq!(uci.board.halfmove_clock(), 8);
+        assert_eq!(pos.played_reps(), 3);
+        assert_eq!(pos.outcome(), Outcome::DrawRepetition3);
+
+        // a promotion without a piece is queened, unless strict
+        let fen = "4k3/7P/8/8/8/8/8/4K3 w - - 0 1";
+        let mut uci = UciServer::new()
+            .unwrap()
+            .add_prelude(&format!("position fen {fen} moves h7h8"))
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(uci.board, "4k2Q/8/8/8/8/8/8/4K3 b - - 0 1".parse().unwrap());
+
+        let mut uci = UciServer::new().unwrap();
+        uci.auto_queen = AutoQueen::Strict;
+        let mut uci = uci
+            .add_prelude(&format!("position fen {fen} moves h7h8"))
+            .add_prelude("quit");
+        uci.run();
+        assert_eq!(
+            uci.board.to_fen(),
+            Catalog::starting_board().to_fen(),
+            "position rejected"
+        );
     }
 
     #[test]
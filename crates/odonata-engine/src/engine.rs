@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Context;
+use odonata_base::boards::Position;
+use odonata_base::domain::info::Info;
+use odonata_base::domain::staticeval::StaticEval as _;
+use odonata_base::epd::Epd;
+use odonata_base::infra::component::{Component, State};
+use odonata_base::infra::resources;
+use odonata_base::other::phaser::Phase;
+use odonata_base::prelude::*;
+
+use crate::comms::uci_server::UciServer;
+use crate::search::engine::ThreadedSearch;
+use crate::search::search_results::Response;
+
+/// Startup configuration for an [`Engine`].
+///
+/// `settings` are dotted config keys such as `eval.eval_kind` or `tt.mb`
+/// (the same keys accepted by `-D` on the command line and `setoption name Init`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineConfig {
+    pub settings:              HashMap<String, String>,
+    pub threads:               u32,
+    pub strict_error_handling: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            settings:              HashMap::new(),
+            threads:               1,
+            strict_error_handling: false,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// parses a list of "key=value" definitions (as supplied by -D/--define)
+    pub fn from_defines(defines: &[String]) -> Result<Self> {
+        let mut cfg = Self::default();
+        for kv in defines {
+            cfg.define(kv)?;
+        }
+        Ok(cfg)
+    }
+
+    pub fn define(&mut self, kv: &str) -> Result<&mut Self> {
+        let (k, v) = kv
+            .split_once('=')
+            .with_context(|| format!("unable to find '=' in definition '{kv}'"))?;
+        self.settings.insert(k.trim().to_string(), v.trim().to_string());
+        Ok(self)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> &mut Self {
+        self.settings.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// A cheap, search-free summary of a position. See [`Engine::quick_assess`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickAssessment {
+    /// static eval from the point of view of the side to move
+    pub static_eval:      Score,
+    /// the capture with the best static exchange evaluation, if any
+    pub best_capture_see: Option<(Move, Score)>,
+    pub in_check:         bool,
+    pub game_phase:       Phase,
+}
+
+impl fmt::Display for QuickAssessment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+/// The single public entry point to the engine.
+///
+/// Wraps eval, search, transposition table and opening book (via [`ThreadedSearch`])
+/// together with the comms configuration, so that binaries and downstream users
+/// need not reach into the internal module layout.
+#[derive(Debug)]
+pub struct Engine {
+    threaded:                  ThreadedSearch,
+    pub strict_error_handling: bool,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::with_config(EngineConfig::default()).expect("default engine config")
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.threaded)
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: EngineConfig) -> Result<Self> {
+        let mut threaded = ThreadedSearch::with_threads(config.threads);
+        threaded.configure(config.settings)?;
+        Ok(Self {
+            threaded,
+            strict_error_handling: config.strict_error_handling,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        self.threaded.name()
+    }
+
+    pub fn threads(&self) -> u32 {
+        self.threaded.thread_count
+    }
+
+    pub fn threaded_search(&self) -> &ThreadedSearch {
+        &self.threaded
+    }
+
+    pub fn threaded_search_mut(&mut self) -> &mut ThreadedSearch {
+        &mut self.threaded
+    }
+
+    pub fn into_threaded_search(self) -> ThreadedSearch {
+        self.threaded
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        self.threaded.set_option(name, value)
+    }
+
+    pub fn set_callback(&mut self, callback: impl Fn(&Info) + Send + Sync + 'static) {
+        self.threaded.set_callback(callback);
+    }
+
+    pub fn new_game(&mut self) {
+        self.threaded.set_state(State::NewGame);
+    }
+
+    pub fn search(&mut self, epd: Epd, tc: TimeControl) -> Result<Response> {
+        self.threaded.search(epd, tc)
+    }
+
+    pub fn search_with_options(&mut self, epd: Epd, tc: TimeControl, opts: SearchOptions) -> Result<Response> {
+        self.threaded.search_with_options(epd, tc, opts)
+    }
+
+    pub fn static_eval(&mut self, epd: Epd) -> Result<Score> {
+        self.threaded.static_eval(epd)
+    }
+
+    /// Eager evaluation for GUI hover tooltips and arrows - no search is performed.
+    ///
+    /// Latency contract: a single static eval, a legal move generation and a SEE per capture.
+    /// No transposition table reads or writes and no effect on subsequent searches, so it may be
+    /// called thousands of times per second (typically a few microseconds per call).
+    /// Each call evaluates the board from scratch, there is no NNUE accumulator kept between calls.
+    pub fn quick_assess(&self, b: &Board) -> QuickAssessment {
+        let eval = &self.threaded.search.eval;
+        let best_capture_see = b
+            .legal_moves()
+            .iter()
+            .filter(|mv| mv.is_capture())
+            .map(|&mv| (mv, eval.eval_move_see(mv, b)))
+            .max_by_key(|&(_mv, see)| see);
+        QuickAssessment {
+            static_eval: eval.static_eval(&Position::from_board(b.clone())),
+            best_capture_see,
+            in_check: b.is_in_check(b.turn()),
+            game_phase: b.phase(&eval.hce.phaser),
+        }
+    }
+
+    /// checks embedded and external resources (networks, eval weights, epd suites)
+    pub fn verify_resources(&self) -> Result<Vec<String>> {
+        let mut checked = resources::verify_embedded()?;
+        checked.extend(self.threaded.search.eval.verify_resources()?);
+        Ok(checked)
+    }
+
+    pub fn show_config(&self) -> Result<String> {
+        self.threaded.show_config()
+    }
+
+    /// a uci server, reading from stdin, driving this engine
+    pub fn into_uci_server(self) -> UciServer {
+        UciServer::from_engine(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hint::black_box;
+
+    use odonata_base::catalog::Catalog;
+    use odonata_base::infra::profiler::PerfProfiler;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_engine_config() {
+        let cfg =
+            EngineConfig::from_defines(&["eval.hce.quantum=300".into(), "controller.multi_pv = 6".into()]).unwrap();
+        assert_eq!(cfg.settings["controller.multi_pv"], "6");
+        assert!(EngineConfig::from_defines(&["no_equals".into()]).is_err());
+
+        let eng = Engine::with_config(cfg).unwrap();
+        assert_eq!(eng.threaded_search().search.eval.hce.quantum, 300);
+        assert_eq!(eng.threaded_search().search.controller.multi_pv, 6);
+        assert_eq!(eng.threads(), 1);
+
+        let mut cfg = EngineConfig::default();
+        cfg.set("no.such.key", "1");
+        assert!(Engine::with_config(cfg).is_err());
+    }
+
+    #[test]
+    fn test_quick_assess() {
+        let eng = Engine::new();
+        let qa = eng.quick_assess(&Catalog::starting_board());
+        assert_eq!(qa.in_check, false);
+        assert_eq!(qa.best_capture_see, None);
+        assert!(qa.static_eval.is_numeric());
+
+        // white queen can take an undefended rook, black is not in check
+        let b = Board::parse_fen("k7/8/8/3r4/8/8/8/K2Q4 w - - 0 1").unwrap();
+        let qa = eng.quick_assess(&b);
+        let (mv, see) = qa.best_capture_see.unwrap();
+        assert_eq!(mv.to_uci(), "d1d5");
+        assert_eq!(see, Score::from_cp(Piece::Rook.centipawns()));
+        println!("{qa}");
+
+        // no state carried between calls
+        eng.quick_assess(&Catalog::starting_board());
+        assert_eq!(eng.quick_assess(&b), qa);
+
+        let b = Board::parse_fen("k7/8/8/8/8/8/8/K2r4 w - - 0 1").unwrap();
+        assert_eq!(eng.quick_assess(&b).in_check, true);
+    }
+
+    #[test]
+    fn bench_quick_assess() {
+        let eng = Engine::new();
+        let epds = Catalog::bratko_kopec();
+        let mut prof = PerfProfiler::new("quick_assess");
+        for epd in &epds {
+            black_box(prof.bench(|| eng.quick_assess(black_box(&epd.board()))));
+        }
+    }
+
+    #[test]
+    fn test_verify_resources() {
+        let eng = Engine::new();
+        assert_eq!(eng.threaded_search().search.eval.warning, None);
+        let checked = eng.verify_resources().unwrap();
+        assert!(checked.iter().any(|s| s.starts_with("embedded nnue")), "{checked:?}");
+
+        let mut cfg = EngineConfig::default();
+        cfg.set("eval.nnue_file", "no-such-file.bin");
+        assert!(Engine::with_config(cfg).is_err());
+    }
+
+    #[test]
+    fn test_engine_search() {
+        let mut eng = Engine::new();
+        let res = eng.search(Catalog::test_position(), TimeControl::Depth(3)).unwrap();
+        assert!(res.supplied_move().is_ok());
+        eng.new_game();
+    }
+}
@@ -9,6 +9,7 @@ use odonata_base::PreCalc;
 
 use super::scoring::Scorer;
 use crate::eval::feature::Feature;
+use crate::eval::influence::Influence;
 
 pub struct Evaluation;
 
@@ -27,8 +28,13 @@ impl Evaluation {
             self.other(b, sc);
             self.king_safety(White, b, sc);
             self.king_safety(Black, b, sc);
-            self.mobility(White, b, sc);
-            self.mobility(Black, b, sc);
+            // the influence tables are only built when scored (or counted)
+            let mut influence = sc.wants_influence().then(Influence::default);
+            self.mobility(White, b, influence.as_mut(), sc);
+            self.mobility(Black, b, influence.as_mut(), sc);
+            if let Some(influence) = &mut influence {
+                self.influence(b, influence, sc);
+            }
         }
         // scorer.set_phase(b.phase(ph));
         // scorer.interpolate_and_scale("interpolate");
@@ -404,18 +410,21 @@ impl Evaluation {
         // TODO:PassedDuoR67 & PassedDuoR2345
         net(s, PassedDuoR67, Bitboard::EMPTY, Bitboard::EMPTY);
         net(s, PassedDuoR2345, Bitboard::EMPTY, Bitboard::EMPTY);
+        net(s, PawnLever, p.levers & w, p.levers & b);
         net(
             s,
             BackwardHalfOpen,
-            p.backward & p.half_open & w,
-            p.backward & p.half_open & b,
+            p.backward & p.unopposed & w,
+            p.backward & p.unopposed & b,
         );
         net(
             s,
             Backward,
-            p.backward & !p.half_open & w,
-            p.backward & !p.half_open & b,
+            p.backward & !p.unopposed & w,
+            p.backward & !p.unopposed & b,
         );
+        s.set_bits(Backward, p.backward);
+        s.set_bits(PawnLever, p.levers);
     }
 
     #[inline(never)]
@@ -555,8 +564,36 @@ impl Evaluation {
         sc.accum2(c, DiscoveredChecks, discovered_checks * mult);
     }
 
+    /// completes the influence tables with pawn and king attacks (pieces are added by mobility)
+    #[inline(never)]
+    fn influence<T>(&mut self, b: &Board, inf: &mut Influence, s: &mut impl Scorer<T>) {
+        use Feature::*;
+        let bb = PreCalc::instance();
+        for c in Color::ALL {
+            let (e, w) = bb.pawn_attacks_ew(b.pawns() & b.color(c), c);
+            inf.add_attacks(c, Piece::Pawn, e);
+            inf.add_attacks(c, Piece::Pawn, w);
+            if let Some(ksq) = (b.kings() & b.color(c)).find_first_square() {
+                inf.add_attacks(c, Piece::King, bb.king_attacks(ksq));
+            }
+        }
+        for c in Color::ALL {
+            let controlled = inf.controlled(c);
+            let center = controlled & Bitboard::CENTER_16_SQ;
+            let king_zone = match (b.kings() & b.color(c.flip_side())).find_first_square() {
+                Some(ksq) => controlled & bb.king_attacks(ksq),
+                None => Bitboard::empty(),
+            };
+            s.accum2(c, InfluenceCenter, center.popcount());
+            s.accum2(c, InfluenceKingZone, king_zone.popcount());
+            s.set_bits(InfluenceCenter, center);
+            s.set_bits(InfluenceKingZone, king_zone);
+        }
+        s.set_influence(inf);
+    }
+
     #[inline(never)]
-    fn mobility<T>(&mut self, c: Color, b: &Board, s: &mut impl Scorer<T>) {
+    fn mobility<T>(&mut self, c: Color, b: &Board, mut inf: Option<&mut Influence>, s: &mut impl Scorer<T>) {
         use Color::*;
         use Feature::*;
         let bb = PreCalc::instance();
@@ -671,6 +708,9 @@ impl Evaluation {
             // empty squares + undefended + defended qrkb (but not defended pawns)
             let our_attacks = our_raw_attacks - (pa & (empty | their_p));
             ni_atts |= our_raw_attacks;
+            if let Some(inf) = inf.as_deref_mut() {
+                inf.add_attacks(c, Piece::Knight, our_raw_attacks);
+            }
             // | (them & !their_attacks_bb) | (bi | r | q | ni));
 
             center_attacks += (our_attacks & Bitboard::CENTER_16_SQ).popcount();
@@ -775,6 +815,9 @@ impl Evaluation {
 
             let our_attacks = our_raw_attacks - (pa & (empty | their_p));
             bi_atts |= our_raw_attacks;
+            if let Some(inf) = inf.as_deref_mut() {
+                inf.add_attacks(c, Piece::Bishop, our_raw_attacks);
+            }
             // | (them & !their_attacks_bb) | (bi | r | q | ni));
 
             center_attacks += (our_attacks & Bitboard::CENTER_16_SQ).popcount();
@@ -841,6 +884,9 @@ impl Evaluation {
             connected_rooks += (our_raw_attacks & b.rooks_or_queens() & us).any() as i32;
             let our_attacks = our_raw_attacks - (pa & (empty | their_p | bi | ni));
             ro_atts |= our_raw_attacks;
+            if let Some(inf) = inf.as_deref_mut() {
+                inf.add_attacks(c, Piece::Rook, our_raw_attacks);
+            }
             //| (them & !their_attacks_bb) | (r | q));
 
             center_attacks += (our_attacks & Bitboard::CENTER_16_SQ).popcount();
@@ -898,6 +944,9 @@ impl Evaluation {
 
             let our_attacks = our_raw_attacks - (pa & (empty | their_p | bi | ni | r));
             qu_atts |= our_raw_attacks;
+            if let Some(inf) = inf.as_deref_mut() {
+                inf.add_attacks(c, Piece::Queen, our_raw_attacks);
+            }
             //  | (them & !their_attacks_bb) | q);
 
             center_attacks += (our_attacks & Bitboard::CENTER_16_SQ).popcount();
@@ -1049,6 +1098,7 @@ impl Evaluation {
 
 #[cfg(test)]
 mod eval_tests {
+    use std::collections::HashMap;
     use std::hint::black_box;
 
     use odonata_base::catalog::Catalog;
@@ -1058,8 +1108,92 @@ mod eval_tests {
     use super::*;
     use crate::eval::hce::Hce;
     use crate::eval::scoring::SummationScorer;
+    use crate::eval::weight::WeightOf;
     use crate::test_log::test;
 
+    /// counts (white, black) per feature, rather than weighting them
+    #[derive(Default)]
+    struct CountingScorer {
+        counts: HashMap<Feature, (i32, i32)>,
+    }
+
+    impl Scorer<i32> for CountingScorer {
+        fn accum(&mut self, f: Feature, w: i32, b: i32) {
+            let e = self.counts.entry(f).or_default();
+            *e = (e.0 + w, e.1 + b);
+        }
+
+        fn total(&self) -> WeightOf<i32> {
+            WeightOf::zero()
+        }
+    }
+
+    /// the pawn structure catalog tags each position with a feature (c0) and the expected
+    /// white (c1) and black (c2) counts
+    #[test]
+    fn test_pawn_structure_catalog() {
+        let mut diffs = vec![];
+        for epd in Catalog::pawn_structure() {
+            let tag = epd.tag("c0").unwrap();
+            let feature = match tag {
+                "isolated" => Feature::PawnIsolated,
+                "doubled" => Feature::PawnDoubled,
+                "passed" => Feature::PawnPassed,
+                "passed_r4" => Feature::PawnPassedR4,
+                "passed_r5" => Feature::PawnPassedR5,
+                "passed_r6" => Feature::PawnPassedR6,
+                "passed_r7" => Feature::PawnPassedR7,
+                "connected_r67" => Feature::PawnConnectedR67,
+                "connected_r345" => Feature::PawnConnectedR345,
+                "backward_half_open" => Feature::BackwardHalfOpen,
+                "backward" => Feature::Backward,
+                "lever" => Feature::PawnLever,
+                _ => panic!("unknown pawn structure tag '{tag}' in {epd}"),
+            };
+            let expected = (epd.int("c1").unwrap() as i32, epd.int("c2").unwrap() as i32);
+            let bd = epd.board();
+            let mut counter = CountingScorer::default();
+            let pawns = Pawns::new(bd.pawns() & bd.white(), bd.pawns() & bd.black());
+            Evaluation.pawns_both(&bd, &pawns, &mut counter);
+            let actual = counter.counts.get(&feature).copied().unwrap_or_default();
+            if actual != expected {
+                diffs.push(format!(
+                    "{id} {tag:<18} expected (w,b) {expected:?} actual {actual:?}  {fen}",
+                    id = epd.tag("id").unwrap_or_default(),
+                    fen = bd.to_fen()
+                ));
+            }
+        }
+        assert!(diffs.is_empty(), "pawn structure mismatches:\n{}", diffs.join("\n"));
+    }
+
+    /// the influence built up during mobility matches a from-scratch calculation
+    #[test]
+    fn test_influence_incremental() {
+        #[derive(Default)]
+        struct InfluenceScorer(Option<Influence>);
+
+        impl Scorer<i32> for InfluenceScorer {
+            fn accum(&mut self, _f: Feature, _w: i32, _b: i32) {}
+
+            fn set_influence(&mut self, influence: &Influence) {
+                self.0 = Some(influence.clone());
+            }
+
+            fn total(&self) -> WeightOf<i32> {
+                WeightOf::zero()
+            }
+        }
+
+        for epd in Catalog::win_at_chess() {
+            let bd = epd.board();
+            let mut sc = InfluenceScorer::default();
+            Evaluation.eval(&bd, &mut sc);
+            let influence = sc.0.expect("influence tables built");
+            assert_eq!(influence, Influence::from_board(&bd), "{epd}");
+        }
+    }
+
     // #[test]
     // fn test_eval_basics() {
     //     let mut sum = SummationScorer::new(|f| Hardcoded::<f32>::WTS[f]);
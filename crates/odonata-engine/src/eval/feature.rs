@@ -36,6 +36,7 @@ pub enum FeatureCategory {
     Threats,
     Passed,
     Space,
+    Influence,
     Winnable,
     Other,
 }
@@ -94,6 +95,7 @@ pub enum Feature {
     PawnDuoR2345,
     PassedDuoR67,
     PassedDuoR2345,
+    PawnLever,
     BackwardHalfOpen,
     Backward,
     PotentialOutpost,
@@ -216,6 +218,9 @@ pub enum Feature {
     NewFeature1,
     NewFeature2,
 
+    InfluenceCenter,
+    InfluenceKingZone,
+
     // block copy from square
     PstP_A1,
     PstP_B1,
@@ -748,6 +753,7 @@ impl Feature {
             x if x >= PstP_A1 && x <= PstK_H8 => "Pst",
             x if x >= MaterialPawn && x <= MaterialQueen => "Material",
             x if x >= ContemptPenalty && x <= WinBonus => "Winnable",
+            x if x >= InfluenceCenter && x <= InfluenceKingZone => "Influence",
             _ => "Tempo",
         }
     }
@@ -770,6 +776,7 @@ impl Feature {
                     x if x >= PstP_A1.index() && x <= PstK_H8.index() => FeatureCategory::Mobility,
                     x if x >= ContemptPenalty.index() && x <= WinBonus.index() => FeatureCategory::Winnable,
                     x if x >= MaterialPawn.index() && x <= MaterialQueen.index() => FeatureCategory::Material,
+                    x if x >= InfluenceCenter.index() && x <= InfluenceKingZone.index() => FeatureCategory::Influence,
                     _ => FeatureCategory::Initiative,
                 };
                 f += 1;
@@ -852,6 +859,8 @@ mod tests_feature {
 
         assert_eq!(KingSafety.name(), "King safety");
         assert_eq!(Initiative.name(), "Initiative");
+        assert_eq!(InfluenceKingZone.category(), FeatureCategory::Influence);
+        assert_eq!(FeatureCategory::Influence.features().count(), 2);
 
         pub fn test_index_pst(base: Feature, p: Piece, sq: Square) -> usize {
             let index = base.index() + p.index() * Square::len() + sq.index();
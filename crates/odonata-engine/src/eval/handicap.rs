@@ -0,0 +1,92 @@
+use odonata_base::boards::Position;
+use odonata_base::prelude::*;
+
+/// Skews the evaluation for the engine's own side (the side to move at the root), for
+/// weaker but plausible play in training games, rather than just a shallower search.
+///
+/// Scores in the engine's favour are exaggerated by `optimism` percent, so it overrates its
+/// own prospects, and scores against it are played down by `threat_blindness` percent, so it
+/// underrates the opponent's threats. A deterministic `noise` of up to that many centipawns
+/// (by board hash) is added too.
+///
+/// All zero (the default) leaves the evaluation untouched. The handicap is suspended whilst
+/// in analysis mode, where an honest evaluation is always wanted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Handicap {
+    pub optimism:         i32, // percent
+    pub threat_blindness: i32, // percent
+    pub noise:            i32, // centipawns
+    pub suspended:        bool,
+}
+
+impl Configurable for Handicap {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.optimism.set(p.get("optimism"))?;
+        self.threat_blindness.set(p.get("threat_blindness"))?;
+        self.noise.set(p.get("noise"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Handicap {
+    pub fn is_active(&self) -> bool {
+        !self.suspended && (self.optimism != 0 || self.threat_blindness != 0 || self.noise != 0)
+    }
+
+    /// `score` is from the point of view of the side to move of `pos`
+    pub fn adjust(&self, pos: &Position, score: Score) -> Score {
+        if !self.is_active() || !score.is_numeric() {
+            return score;
+        }
+        let own = pos.root_board().turn();
+        let pov = pos.board().turn().chooser_wb(1, -1) * own.chooser_wb(1, -1);
+        let cp = pov * score.as_i16() as i32;
+        let cp = if cp > 0 {
+            cp * (100 + self.optimism) / 100
+        } else {
+            cp * (100 - self.threat_blindness.min(100)) / 100
+        };
+        let noise = match self.noise {
+            0 => 0,
+            n => (pos.board().hash() % (2 * n as u64 + 1)) as i32 - n,
+        };
+        Score::from_cp(pov * (cp + noise)).clamp_score()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_handicap() {
+        let mut pos = Position::starting_pos();
+        let mut h = Handicap::default();
+        assert!(!h.is_active());
+        assert_eq!(h.adjust(&pos, Score::from_cp(100)), Score::from_cp(100));
+
+        h.optimism = 50;
+        h.threat_blindness = 20;
+        assert!(h.is_active());
+        assert_eq!(h.adjust(&pos, Score::from_cp(100)), Score::from_cp(150));
+        assert_eq!(h.adjust(&pos, Score::from_cp(-100)), Score::from_cp(-80));
+        assert_eq!(h.adjust(&pos, Score::INFINITY), Score::INFINITY);
+
+        // scores are from the opponent's point of view after a move
+        pos.push_moves_str("e4").unwrap();
+        assert_eq!(h.adjust(&pos, Score::from_cp(100)), Score::from_cp(80));
+        assert_eq!(h.adjust(&pos, Score::from_cp(-100)), Score::from_cp(-150));
+
+        h.optimism = 0;
+        h.threat_blindness = 0;
+        h.noise = 10;
+        let sc = h.adjust(&pos, Score::zero()).as_i16();
+        assert!((-10..=10).contains(&sc));
+        assert_eq!(h.adjust(&pos, Score::zero()).as_i16(), sc);
+
+        h.suspended = true;
+        assert_eq!(h.adjust(&pos, Score::from_cp(100)), Score::from_cp(100));
+    }
+}
@@ -17,6 +17,7 @@ use strum_macros::{Display, EnumString};
 
 use crate::eval::evaluation::Evaluation;
 use crate::eval::feature::{Feature, FeatureCategory};
+use crate::eval::influence::Influence;
 use crate::eval::scoring::{Scorer, Softcoded, SummationScorer, WeightVec};
 use crate::eval::see::See;
 use crate::eval::weight::{Rounding, Weight, WeightOf};
@@ -32,6 +33,7 @@ pub struct Hce {
     mobility_phase_disable: u8,
     pub quantum:            i32,
     cache_size:             usize,
+    cache_budget:           Option<usize>, // entries, when a memory limit holds the cache below cache_size
     draw_scaling:           f32,
     draw_scaling_noisy:     f32,
     see:                    See,
@@ -52,7 +54,7 @@ impl Default for Hce {
             hce_file:               hce_file.into(),
             weights_kind:           WeightsKind::SoftcodedF64,
             rounding:               Rounding::None,
-            weights_raw:            Softcoded::load(hce_file).expect("unable to load default weights"),
+            weights_raw:            Softcoded::load(hce_file).unwrap_or_default(), // see is_loaded
             weights_i32:            Default::default(),
             weights_f32:            Default::default(),
             weights_f64:            Default::default(),
@@ -65,6 +67,7 @@ impl Default for Hce {
             mobility_phase_disable: 101,
             quantum:                1,
             cache_size:             DEFAULT_CACHE_SIZE,
+            cache_budget:           None,
             eval_cache:             UnsharedTable::with_size(DEFAULT_CACHE_SIZE),
         }
     }
@@ -108,6 +111,7 @@ struct ExplainVector {
     board:        Board,
     items:        HashMap<Feature, i16>,
     bitboards:    HashMap<Feature, Bitboard>,
+    influence:    Option<Influence>,
     phase:        Phase,
     draw_scaling: f32,
 }
@@ -156,6 +160,13 @@ impl ExplainVector {
         writeln!(&mut ai, "phase   : {}%", ex.phase).unwrap();
         writeln!(&mut ai, "balance : {}", ex.board.material().balance()).unwrap();
         writeln!(&mut ai, "{:#}", ex.board.to_diagram()).unwrap();
+        if let Some(influence) = &ex.influence {
+            writeln!(&mut ai, "influence (white - black)\n{}", influence.heatmap()).unwrap();
+        }
+        let bitboards = ex.bitboards.iter().filter(|(_, bb)| bb.any());
+        for (f, bb) in bitboards.sorted_by_key(|(f, _)| f.index()) {
+            writeln!(&mut ai, "{f}\n{bb:#}").unwrap();
+        }
         writeln!(&mut ai, "end of hce explain").unwrap();
         e.additional_info = ai;
         e
@@ -180,7 +191,12 @@ impl Scorer<f64> for ExplainVector {
     }
 
     fn set_bits(&mut self, f: Feature, bits: Bitboard) {
-        self.bitboards.insert(f, bits);
+        // called once per color by some features
+        *self.bitboards.entry(f).or_default() |= bits;
+    }
+
+    fn set_influence(&mut self, influence: &Influence) {
+        self.influence = Some(influence.clone());
     }
 
     fn apply_scaling(&mut self, _scaling: f32) {}
@@ -201,6 +217,10 @@ impl StaticEval for Hce {
         Score::from_cp(self.see.eval_move_see(b, mv))
     }
 
+    fn eval_move_swap(&self, mv: Move, b: &Board) -> Score {
+        Score::from_cp(self.see.eval_move_swap(b, mv))
+    }
+
     fn move_material_eval(&self, mv: Move, b: &Board) -> Score {
         // FIXME! far too slow (-7 ELO)
         let mut wt = WeightOf::zero();
@@ -232,7 +252,7 @@ impl StaticEval for Hce {
     }
 
     fn new_game(&mut self) {
-        self.eval_cache = UnsharedTable::with_size(self.cache_size);
+        self.eval_cache = UnsharedTable::with_size(self.cache_entries());
         // self.mb.new_game();
         // self.pawn_cache.clear();
         self.eval_cache.clear();
@@ -248,7 +268,7 @@ impl Component for Hce {
         use State::*;
         match s {
             NewGame => {
-                self.eval_cache = UnsharedTable::with_size(self.cache_size);
+                self.eval_cache = UnsharedTable::with_size(self.cache_entries());
                 // self.mb.new_game();
                 self.phaser.new_game();
                 // self.pawn_cache.clear();
@@ -302,6 +322,30 @@ impl Hce {
         Self::default()
     }
 
+    /// the memory of the eval cache as configured, before any memory limit
+    pub fn cache_bytes(&self) -> usize {
+        self.cache_size * UnsharedTable::<Score>::entry_bytes()
+    }
+
+    fn cache_entries(&self) -> usize {
+        self.cache_budget
+            .map_or(self.cache_size, |entries| entries.min(self.cache_size))
+    }
+
+    /// holds the eval cache within `bytes` (or lifts the hold if none), resizing it now if
+    /// need be rather than at the next new game
+    pub fn set_cache_budget(&mut self, bytes: Option<usize>) {
+        self.cache_budget = bytes.map(|bytes| (bytes / UnsharedTable::<Score>::entry_bytes()).max(1));
+        if self.cache_size > 0 && self.eval_cache.capacity() != self.cache_entries() {
+            self.eval_cache = UnsharedTable::with_size(self.cache_entries());
+        }
+    }
+
+    /// false if the weights could not be loaded, leaving them all zero
+    pub fn is_loaded(&self) -> bool {
+        self.weights_raw != Softcoded::default()
+    }
+
     pub fn reload_weights(&mut self) -> Result<()> {
         self.weights_raw = Softcoded::load(&self.hce_file)
             .context(format!("unable to load weights from {}", self.hce_file.display()))?;
@@ -356,6 +400,14 @@ impl Hce {
         Ok(())
     }
 
+    pub fn write_weights_csv<W: std::io::Write>(&self, w: W) -> anyhow::Result<()> {
+        (self.soft_coded_f64() as &dyn WeightVec<f64>).write_csv(w)
+    }
+
+    pub fn write_pst_heatmap<W: std::io::Write>(&self, w: W) -> anyhow::Result<()> {
+        (self.soft_coded_f64() as &dyn WeightVec<f64>).write_pst_heatmap(w, 16)
+    }
+
     // pub fn w_eval_explain(&self, b: &Board) -> impl fmt::Display {
     //     let ph = b.phase(&self.phaser);
 
@@ -616,6 +668,25 @@ mod tests {
         eval.write_weights(std::io::stdout()).unwrap()
     }
 
+    #[test]
+    fn test_export_weights() {
+        let eval = Hce::new();
+        let mut csv = vec![];
+        eval.write_weights_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), Feature::len() + 1);
+        assert!(csv.lines().nth(1).unwrap().starts_with("0,"));
+
+        let mut ppm = vec![];
+        eval.write_pst_heatmap(&mut ppm).unwrap();
+        let ppm = String::from_utf8(ppm).unwrap();
+        let mut lines = ppm.lines();
+        assert_eq!(lines.next(), Some("P3"));
+        assert_eq!(lines.next(), Some("272 848"));
+        assert_eq!(lines.next(), Some("255"));
+        assert_eq!(lines.count(), 848);
+    }
+
     #[test]
     fn bench_eval() {
         let eval = Hce::default();
@@ -653,6 +724,11 @@ mod tests {
         let explain = eval.static_eval_explain(&pos);
         println!("{explain}");
         println!("{explain:#}");
+
+        // the e4/d5 lever shown as a bitboard
+        let b = Board::starting_pos().make_moves_str("e4 d5").unwrap();
+        let explain = eval.static_eval_explain(&Position::from_board(b));
+        assert!(explain.additional_info.contains("pawn_lever\n"));
     }
 
     #[ignore]
@@ -0,0 +1,177 @@
+use std::fmt::{self, Write};
+
+use odonata_base::prelude::*;
+use odonata_base::PreCalc;
+
+/// weighted attack counts per square for each color (square control)
+///
+/// lower valued attackers exert more influence, so a square attacked by a pawn is better
+/// controlled than one attacked by a queen. Maintained by adding (or removing) each piece's
+/// attacks as they are generated, so the attack tables of mobility are not recalculated.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Influence {
+    counts: [[i16; 64]; 2],
+}
+
+impl Default for Influence {
+    fn default() -> Self {
+        Self { counts: [[0; 64]; 2] }
+    }
+}
+
+impl fmt::Debug for Influence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.heatmap())
+    }
+}
+
+impl Influence {
+    pub const fn attacker_weight(p: Piece) -> i16 {
+        match p {
+            Piece::Pawn => 4,
+            Piece::Knight | Piece::Bishop => 3,
+            Piece::Rook => 2,
+            Piece::Queen | Piece::King => 1,
+        }
+    }
+
+    #[inline]
+    pub fn add_attacks(&mut self, c: Color, p: Piece, attacks: Bitboard) {
+        let wt = Self::attacker_weight(p);
+        for sq in attacks.squares() {
+            self.counts[c][sq] += wt;
+        }
+    }
+
+    #[inline]
+    pub fn remove_attacks(&mut self, c: Color, p: Piece, attacks: Bitboard) {
+        let wt = Self::attacker_weight(p);
+        for sq in attacks.squares() {
+            self.counts[c][sq] -= wt;
+        }
+    }
+
+    /// non-incremental calculation, used to verify the incremental one
+    pub fn from_board(b: &Board) -> Self {
+        let bb = PreCalc::instance();
+        let occ = b.occupied();
+        let mut inf = Self::default();
+        for c in Color::ALL {
+            let us = b.color(c);
+            let (e, w) = bb.pawn_attacks_ew(b.pawns() & us, c);
+            inf.add_attacks(c, Piece::Pawn, e);
+            inf.add_attacks(c, Piece::Pawn, w);
+            for sq in (b.knights() & us).squares() {
+                inf.add_attacks(c, Piece::Knight, bb.knight_attacks(sq));
+            }
+            for sq in (b.bishops() & us).squares() {
+                inf.add_attacks(c, Piece::Bishop, bb.bishop_attacks(occ, sq));
+            }
+            for sq in (b.rooks() & us).squares() {
+                inf.add_attacks(c, Piece::Rook, bb.rook_attacks(occ, sq));
+            }
+            for sq in (b.queens() & us).squares() {
+                inf.add_attacks(c, Piece::Queen, bb.rook_attacks(occ, sq) | bb.bishop_attacks(occ, sq));
+            }
+            for sq in (b.kings() & us).squares() {
+                inf.add_attacks(c, Piece::King, bb.king_attacks(sq));
+            }
+        }
+        inf
+    }
+
+    #[inline]
+    pub fn of(&self, c: Color, sq: Square) -> i16 {
+        self.counts[c][sq]
+    }
+
+    /// white influence less black influence
+    #[inline]
+    pub fn net(&self, sq: Square) -> i16 {
+        self.counts[Color::White][sq] - self.counts[Color::Black][sq]
+    }
+
+    /// squares where color c has strictly more influence than the opponent
+    pub fn controlled(&self, c: Color) -> Bitboard {
+        let mut bb = Bitboard::empty();
+        for sq in Square::all() {
+            if self.counts[c][sq] > self.counts[c.flip_side()][sq] {
+                bb |= sq.as_bb();
+            }
+        }
+        bb
+    }
+
+    /// net influence per square from white's point of view, rank 8 first
+    pub fn heatmap(&self) -> String {
+        let mut s = String::new();
+        for r in (0..8).rev() {
+            write!(s, "{} ", r + 1).unwrap();
+            for f in 0..8 {
+                write!(s, "{:>4}", self.net(Square::from_xy(f, r))).unwrap();
+            }
+            writeln!(s).unwrap();
+        }
+        writeln!(s, "     a   b   c   d   e   f   g   h").unwrap();
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::catalog::Catalog;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_influence() {
+        let b = Board::starting_pos();
+        let inf = Influence::from_board(&b);
+        // d3 is covered by pawns c2 and e2 (but the queen on d1 is blocked)
+        assert_eq!(inf.of(Color::White, Square::D3), 8);
+        assert_eq!(inf.of(Color::Black, Square::D3), 0);
+        assert_eq!(inf.net(Square::D6), -8);
+        let rank1 = Bitboard::RANK_1 - Bitboard::A1 - Bitboard::H1;
+        assert_eq!(
+            inf.controlled(Color::White),
+            rank1 | Bitboard::RANK_2 | Bitboard::RANK_3
+        );
+        println!("{}", inf.heatmap());
+
+        let bb = PreCalc::instance();
+        for epd in Catalog::win_at_chess() {
+            let b = epd.board();
+            let mut inf = Influence::from_board(&b);
+            assert!(
+                inf.controlled(Color::White).disjoint(inf.controlled(Color::Black)),
+                "{epd}"
+            );
+
+            // removing then re-adding the knight attacks restores the full influence
+            let mut knights = Influence::default();
+            for sq in b.knights().squares() {
+                let c = if sq.is_in(b.white()) {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                inf.remove_attacks(c, Piece::Knight, bb.knight_attacks(sq));
+            }
+            for c in Color::ALL {
+                for sq in (b.knights() & b.color(c)).squares() {
+                    knights.add_attacks(c, Piece::Knight, bb.knight_attacks(sq));
+                }
+            }
+            for c in Color::ALL {
+                for sq in Square::all() {
+                    assert_eq!(
+                        inf.of(c, sq) + knights.of(c, sq),
+                        Influence::from_board(&b).of(c, sq),
+                        "{epd}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use odonata_base::boards::Position;
 use odonata_base::domain::staticeval::{EvalExplain, StaticEval};
@@ -9,18 +10,26 @@ use odonata_base::infra::utils::Formatting;
 use odonata_base::prelude::*;
 use strum_macros::{Display, EnumString};
 
+use self::handicap::Handicap;
 use self::hce::Hce;
+use self::network::{checksum, simd_supported, NetworkLoader, EMBEDDED_NNUE_CHECKSUM};
 use self::nnue::Nnue;
+use self::scoring::Softcoded;
 use self::weight::WeightOf;
 
 pub mod evaluation;
 pub mod feature;
+pub mod handicap;
 pub mod hce;
+pub mod influence;
 pub mod network;
 pub mod nnue;
+pub mod pawn_solver;
 pub mod recognizer;
 pub mod scoring;
 pub mod see;
+#[cfg(feature = "tools")]
+pub mod tune;
 pub mod vector;
 pub mod weight;
 
@@ -30,10 +39,25 @@ pub mod weight;
 #[derive(Debug, Clone)]
 pub struct Eval {
     pub eval_kind:   EvalKind,
+    pub backend:     EvalBackend,
     pub hce:         Box<Hce>,
     pub nnue_file:   PathBuf,
     pub nnue:        Box<Nnue>,
     pub incremental: bool,
+    pub symmetry:    bool,  // color symmetric positions are evaluated as just the tempo
+    pub tempo:       Score, // the side to move's advantage in an otherwise balanced position
+    pub handicap:    Handicap,
+    pub warning:     Option<String>,
+    nnue_checksum:   u64, // of the network file, computed when loaded
+    explain_cache:   RefCell<Option<PiecesRemoved>>,
+}
+
+/// nnue evals of a board (by hash) and of the board with each piece removed
+#[derive(Debug, Clone)]
+struct PiecesRemoved {
+    hash:     Hash,
+    base:     i16,
+    removals: Vec<(Square, i16)>,
 }
 
 // impl Clone for Eval {
@@ -56,15 +80,49 @@ pub enum EvalKind {
     Hce,
 }
 
+/// The evaluators in fallback order. The first whose cpu features and files are available
+/// is used (and reported at startup), so a single release binary runs everywhere, down to
+/// old ARM boards without simd or an nnue.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display)]
+pub enum EvalBackend {
+    NnueSimd,
+    NnueScalar,
+    Hce,
+    Material,
+}
+
+impl EvalBackend {
+    pub const FALLBACK_ORDER: [EvalBackend; 4] = [Self::NnueSimd, Self::NnueScalar, Self::Hce, Self::Material];
+
+    pub fn select(simd: bool, nnue_loaded: bool, hce_loaded: bool) -> EvalBackend {
+        let available = |backend: &EvalBackend| match backend {
+            Self::NnueSimd => simd && nnue_loaded,
+            Self::NnueScalar => nnue_loaded,
+            Self::Hce => hce_loaded,
+            Self::Material => true,
+        };
+        *Self::FALLBACK_ORDER
+            .iter()
+            .find(|b| available(b))
+            .expect("material always available")
+    }
+
+    pub fn is_nnue(self) -> bool {
+        matches!(self, Self::NnueSimd | Self::NnueScalar)
+    }
+
+    fn eval_kind(self) -> EvalKind {
+        match self {
+            Self::NnueSimd | Self::NnueScalar => EvalKind::Blend,
+            Self::Hce => EvalKind::Hce,
+            Self::Material => EvalKind::Material,
+        }
+    }
+}
+
 impl Default for Eval {
     fn default() -> Self {
-        Self {
-            eval_kind:   EvalKind::Blend,
-            hce:         Default::default(),
-            nnue:        Box::new(Nnue::from_file("").expect("unable to load")),
-            incremental: true,
-            nnue_file:   PathBuf::new(),
-        }
+        Self::with_nnue(Nnue::from_file(""))
     }
 }
 
@@ -90,18 +148,122 @@ impl Display for Eval {
 }
 
 impl Eval {
+    /// selects the backend in fallback order, with a warning if the network failed to load
+    fn with_nnue(nnue: Result<Nnue>) -> Self {
+        let hce = Box::<Hce>::default();
+        let backend = EvalBackend::select(simd_supported(), nnue.is_ok(), hce.is_loaded());
+        let (mut nnue, warning) = match nnue {
+            Ok(nnue) => (nnue, None),
+            Err(e) => {
+                let msg = format!("unable to load nnue ({e:#}) - falling back to {backend}");
+                warn!("{msg}");
+                (Nnue::fallback(), Some(msg))
+            }
+        };
+        nnue.set_simd(backend == EvalBackend::NnueSimd);
+        info!(target: "eng", "eval backend {backend}");
+        Self {
+            eval_kind: backend.eval_kind(),
+            backend,
+            hce,
+            nnue: Box::new(nnue),
+            incremental: true,
+            symmetry: true,
+            tempo: Score::from_cp(5),
+            handicap: Handicap::default(),
+            nnue_file: PathBuf::new(),
+            nnue_checksum: if warning.is_none() { EMBEDDED_NNUE_CHECKSUM } else { 0 },
+            warning,
+            explain_cache: RefCell::new(None),
+        }
+    }
+
+    /// repeated explains of the same board (as a GUI does) reuse the cached evals
+    fn nnue_pieces_removed(&self, b: &Board) -> (i16, Vec<(Square, i16)>) {
+        let mut cache = self.explain_cache.borrow_mut();
+        match &*cache {
+            Some(pr) if pr.hash == b.hash() => (pr.base, pr.removals.clone()),
+            _ => {
+                let (base, removals) = self.nnue.eval_pieces_removed(b);
+                *cache = Some(PiecesRemoved {
+                    hash: b.hash(),
+                    base,
+                    removals: removals.clone(),
+                });
+                (base, removals)
+            }
+        }
+    }
+
+    /// checks the embedded network and any configured external eval files, listing what was checked
+    pub fn verify_resources(&self) -> Result<Vec<String>> {
+        let mut checked = vec![];
+        if cfg!(feature = "nnue") {
+            NetworkLoader::verify_embedded()?;
+            checked.push(format!("embedded nnue checksum {EMBEDDED_NNUE_CHECKSUM:#x}"));
+        }
+        if !self.nnue_file.as_os_str().is_empty() {
+            Nnue::from_file(&self.nnue_file)?;
+            checked.push(format!("nnue file {}", self.nnue_file.display()));
+        }
+        Softcoded::<f64>::load(&self.hce.hce_file).with_context(|| self.hce.hce_file.display().to_string())?;
+        checked.push(format!("hce weights {}", self.hce.hce_file.display()));
+        Ok(checked)
+    }
+
+    /// identifies the network in use by the leading hex digits of its checksum, eg "NN-739fd35a"
+    pub fn net_id(&self) -> String {
+        match self.eval_kind {
+            EvalKind::Hce => "HCE".to_string(),
+            EvalKind::Material => "MATERIAL".to_string(),
+            _ => format!("NN-{:08x}", self.nnue_checksum >> 32),
+        }
+    }
+
+    fn nnue_checksum(nnue_file: &Path) -> Result<u64> {
+        if nnue_file.as_os_str().is_empty() {
+            return Ok(EMBEDDED_NNUE_CHECKSUM);
+        }
+        Ok(checksum(&fs_err::read(nnue_file)?))
+    }
+
     pub fn reload(&mut self) -> Result<()> {
         self.nnue = Box::new(Nnue::from_file(&self.nnue_file)?);
+        self.nnue.set_simd(self.backend == EvalBackend::NnueSimd);
+        self.nnue_checksum = Self::nnue_checksum(&self.nnue_file)?;
+        self.warning = None;
+        self.explain_cache.take();
         // self.hce.reload_weights()?;
         self.new_game();
         Ok(())
     }
+
+    /// swaps in a different network, keeping the current one if the new one fails its checks.
+    /// If we had fallen back to hce, the network is back in use
+    pub fn swap_nnue_file(&mut self, nnue_file: PathBuf) -> Result<()> {
+        let nnue = Nnue::from_file(&nnue_file).with_context(|| format!("loading nnue {}", nnue_file.display()))?;
+        self.nnue_checksum = Self::nnue_checksum(&nnue_file)?;
+        if self.warning.is_some() && !self.backend.is_nnue() {
+            self.backend = EvalBackend::select(simd_supported(), true, self.hce.is_loaded());
+            self.eval_kind = self.backend.eval_kind();
+        }
+        self.nnue_file = nnue_file;
+        self.nnue = Box::new(nnue);
+        self.nnue.set_simd(self.backend == EvalBackend::NnueSimd);
+        self.warning = None;
+        self.explain_cache.take();
+        self.new_game();
+        Ok(())
+    }
 }
 
 impl Configurable for Eval {
     fn set(&mut self, p: Param) -> Result<bool> {
         self.eval_kind.set(p.get("eval_kind"))?;
         self.incremental.set(p.get("incremental"))?;
+        self.symmetry.set(p.get("symmetry"))?;
+        self.tempo.set(p.get("tempo"))?;
+        self.handicap.set(p.get("handicap"))?;
         self.hce.set(p.get("hce"))?;
         if self.nnue_file.set(p.get("nnue_file"))? {
             self.reload()?;
@@ -110,15 +272,18 @@ impl Configurable for Eval {
     }
 }
 
-impl StaticEval for Eval {
-    fn new_game(&mut self) {
-        self.nnue.new_game();
-    }
-
-    fn static_eval(&self, eval_pos: &Position) -> Score {
+impl Eval {
+    fn unhandicapped_eval(&self, eval_pos: &Position) -> Score {
+        if self.symmetry && eval_pos.board().is_color_symmetric() {
+            return self.tempo;
+        }
         if self.eval_kind == EvalKind::Hce {
             return self.hce.static_eval(eval_pos);
         }
+        if self.eval_kind == EvalKind::Material {
+            let material = eval_pos.board().material().centipawns_as_white();
+            return Score::from_cp(eval_pos.board().turn().chooser_wb(1, -1) * material);
+        }
 
         let cp = match self.incremental {
             true => self.nnue.eval(eval_pos),
@@ -139,19 +304,22 @@ impl StaticEval for Eval {
         eg.endgame_score_adjust(eval_pos.board(), pov, &self.hce.endgame)
     }
 
-    fn static_eval_explain(&self, pos: &Position) -> EvalExplain {
+    fn unhandicapped_eval_explain(&self, pos: &Position) -> EvalExplain {
         if self.eval_kind == EvalKind::Hce {
             return self.hce.static_eval_explain(pos);
         }
+        if self.eval_kind == EvalKind::Material {
+            return EvalExplain {
+                additional_info: format!("material : {}\n", self.unhandicapped_eval(pos)),
+                ..EvalExplain::default()
+            };
+        }
 
         let mut cells = HashMap::new();
-        for sq in pos.board.occupied().squares() {
+        let (score1, removals) = self.nnue_pieces_removed(pos.board());
+        for (sq, score2) in removals {
             let p = pos.board().piece(sq).unwrap();
             let c = pos.board().color_of(sq).unwrap();
-            let score1 = self.nnue.eval_stateless(pos.board());
-            let mut bb = pos.board().clone().into_builder();
-            bb.set_piece(sq, None);
-            let score2 = self.nnue.eval_stateless(&bb.build());
             let cp = score1 - score2;
             let key = (7 - sq.rank_index(), sq.file_index());
             cells.insert(key, format!("\n{p:^9}\n\n{cp:^9}\n", p = p.to_char(c)));
@@ -164,7 +332,7 @@ impl StaticEval for Eval {
 
         let cp = self.nnue.eval(pos) as i32;
         let pov = Score::from_cp(cp);
-        let sc = self.static_eval(pos);
+        let sc = self.unhandicapped_eval(pos);
 
         let eg = EndGame::from_board(pos.board());
 
@@ -188,6 +356,27 @@ impl StaticEval for Eval {
         e.additional_info = ai;
         e
     }
+}
+
+impl StaticEval for Eval {
+    fn new_game(&mut self) {
+        self.nnue.new_game();
+    }
+
+    fn static_eval(&self, eval_pos: &Position) -> Score {
+        let sc = self.unhandicapped_eval(eval_pos);
+        self.handicap.adjust(eval_pos, sc)
+    }
+
+    fn static_eval_explain(&self, pos: &Position) -> EvalExplain {
+        let mut e = self.unhandicapped_eval_explain(pos);
+        if self.handicap.is_active() {
+            use std::fmt::Write;
+            let sc = self.static_eval(pos);
+            writeln!(&mut e.additional_info, "handicapped score {sc} ({:?})", self.handicap).unwrap();
+        }
+        e
+    }
 
     fn move_material_eval(&self, mv: Move, b: &Board) -> Score {
         self.hce.move_material_eval(mv, b)
@@ -197,6 +386,10 @@ impl StaticEval for Eval {
         self.hce.eval_move_see(mv, b)
     }
 
+    fn eval_move_swap(&self, mv: Move, b: &Board) -> Score {
+        self.hce.eval_move_swap(mv, b)
+    }
+
     fn piece_material_eval(&self, piece: Piece, b: &Board) -> f64 {
         self.hce.piece_material_eval(piece, b)
     }
@@ -222,4 +415,68 @@ mod tests {
 
         // println!("{expl}");
     }
+
+    #[test]
+    fn test_eval_nnue_explain_cache() {
+        let eval = Eval::default();
+        let pos = Position::starting_pos();
+        let (base, removals) = eval.nnue_pieces_removed(pos.board());
+        assert_eq!(removals.len(), 32);
+        assert_eq!(eval.explain_cache.borrow().as_ref().unwrap().hash, pos.board().hash());
+        assert_eq!(eval.nnue_pieces_removed(pos.board()), (base, removals));
+        let _expl = eval.static_eval_explain(&pos);
+    }
+
+    #[test]
+    fn test_eval_symmetry() {
+        let mut eval = Eval::default();
+        let mut pos = Position::starting_pos();
+        assert_eq!(eval.static_eval(&pos), eval.tempo);
+        pos.push_moves_str("e4 e5 Nf3 Nf6").unwrap();
+        assert_eq!(eval.static_eval(&pos), eval.tempo);
+        pos.push_moves_str("Nc3").unwrap();
+        assert_ne!(eval.static_eval(&pos), eval.tempo);
+
+        eval.tempo = Score::from_cp(12);
+        assert_eq!(eval.static_eval(&Position::starting_pos()), Score::from_cp(12));
+    }
+
+    #[test]
+    fn test_eval_nnue_fallback() {
+        let eval = Eval::with_nnue(Err(anyhow::anyhow!("corrupt network")));
+        assert_eq!(eval.eval_kind, EvalKind::Hce);
+        assert!(eval.warning.as_ref().unwrap().contains("corrupt network"));
+        let pos = Position::starting_pos();
+        assert!(eval.static_eval(&pos).is_numeric());
+        assert!(Eval::default().warning.is_none());
+        assert_eq!(eval.net_id(), "HCE");
+        assert_eq!(
+            Eval::default().net_id(),
+            format!("NN-{:08x}", EMBEDDED_NNUE_CHECKSUM >> 32)
+        );
+    }
+
+    #[test]
+    fn test_eval_backend() {
+        use EvalBackend::*;
+        assert_eq!(EvalBackend::select(true, true, true), NnueSimd);
+        assert_eq!(EvalBackend::select(false, true, true), NnueScalar);
+        assert_eq!(EvalBackend::select(true, false, true), Hce);
+        assert_eq!(EvalBackend::select(true, false, false), Material);
+        assert_eq!(EvalBackend::select(false, true, false), NnueScalar);
+
+        let eval = Eval::default();
+        assert!(eval.backend.is_nnue());
+        assert_eq!(eval.backend == NnueSimd, simd_supported());
+        assert_eq!(Eval::with_nnue(Err(anyhow::anyhow!("no nnue"))).backend, Hce);
+
+        // material only, from the side to move's point of view
+        let mut eval = eval;
+        eval.eval_kind = EvalKind::Material;
+        let pos = Position::from_board(Board::parse_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap());
+        let flipped = Position::from_board(pos.board().color_flip());
+        assert_eq!(eval.static_eval(&pos), eval.static_eval(&flipped));
+        assert!(eval.static_eval(&pos) < Score::from_cp(-500));
+        assert_eq!(eval.net_id(), "MATERIAL");
+    }
 }
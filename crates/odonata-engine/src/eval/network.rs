@@ -22,8 +22,23 @@ pub trait Network {
     fn new_accumulators(&self) -> Self::Accumulators;
     fn forward1_input(&self, wb: &mut Self::Accumulators, bd1: &Board, bd2: &Board);
     fn forward1(&self, acc: &mut Self::Accumulators, b: &Board);
+    /// a single-feature delta, removing piece p of color c on sq from the accumulators
+    fn forward1_remove(&self, acc: &mut Self::Accumulators, p: Piece, sq: Square, c: Color);
     fn forward2(&self, pov: Color, state: &Self::Accumulators) -> Self::Output;
 
+    /// as `forward2`, but compiled for the simd instructions detected at runtime (if any)
+    fn forward2_simd(&self, pov: Color, state: &Self::Accumulators) -> Self::Output {
+        self.forward2(pov, state)
+    }
+
+    /// input features that depend on king placement are grouped into buckets, and when a
+    /// king changes bucket the accumulators are refreshed rather than updated
+    const KING_BUCKETS: usize = 1;
+
+    fn king_bucket(&self, _king_sq: Square, _king_color: Color) -> usize {
+        0
+    }
+
     fn predict(&self, bd: &Board) -> Self::Output {
         let mut accs = self.new_accumulators();
         self.forward1(&mut accs, bd);
@@ -69,6 +84,40 @@ where
     }
 }
 
+/// the default network, embedded in the binary
+#[cfg(feature = "nnue")]
+pub const EMBEDDED_NNUE: &[u8] = include_bytes!("../../resources/r61-net.i16.bin");
+
+/// built without the `nnue` feature, so no network is embedded
+#[cfg(not(feature = "nnue"))]
+pub const EMBEDDED_NNUE: &[u8] = &[];
+
+/// FNV-1a checksum of [`EMBEDDED_NNUE`], verified before use
+pub const EMBEDDED_NNUE_CHECKSUM: u64 = 0x739f_d35a_63e2_8ff5;
+
+/// simd instructions beyond the target's baseline, worth a vectorised nnue. Neon is baseline on aarch64
+pub fn simd_supported() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        true
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// 64 bit FNV-1a
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 #[inline(always)]
 pub fn crelu<T: RealField>(value: T) -> T {
     T::clamp(value, T::zero(), T::one())
@@ -81,16 +130,19 @@ impl Network768xH2<i16> {
         let mut buf = Vec::new();
         if path.is_empty() {
             debug!(target: "config", "loading nnue from default location");
-            let buf = include_bytes!("../../resources/r61-net.i16.bin");
-            buf.as_slice();
-            NetworkLoader::read_postcard_format(buf)
+            NetworkLoader::verify_embedded()?;
+            NetworkLoader::read_postcard_format(EMBEDDED_NNUE)
+        } else if path.ends_with(".nnue") {
+            debug!(target: "config", "loading external nnue from {}", path);
+            utils::file_open(&path)?.read_to_end(&mut buf).context(path.clone())?;
+            NetworkLoader::read_external_format(&buf, &ExternalFormat::default()).context(path)
         } else if path.ends_with("i16.yaml") {
             let net = serde_yaml::from_reader(file_open(&path)?).context(path)?;
             Ok(Box::new(net))
         } else {
             debug!(target: "config", "loading binary nnue from {}", path);
-            let _bytes = utils::file_open(&path).unwrap().read_to_end(&mut buf).unwrap();
-            NetworkLoader::read_postcard_format(buf.as_slice())
+            utils::file_open(&path)?.read_to_end(&mut buf).context(path.clone())?;
+            NetworkLoader::read_postcard_format(buf.as_slice()).context(path)
         }
     }
 }
@@ -160,6 +212,12 @@ where
         }
     }
 
+    fn forward1_remove(&self, (w, b): &mut (Vector<T>, Vector<T>), p: Piece, sq: Square, c: Color) {
+        let (wi, bi) = feature768(p, sq, c);
+        w.mul_add_assign(-T::one(), &self.wt[wi]);
+        b.mul_add_assign(-T::one(), &self.wt[bi]);
+    }
+
     fn forward1_input(&self, (w, b): &mut (Vector<T>, Vector<T>), bd1: &Board, bd2: &Board) {
         for (p, sq, c) in feature768_diff_iter(bd1, bd2) {
             let (wi, bi) = feature768(p, sq, c);
@@ -232,20 +290,25 @@ impl Network for Network768xH2<Float> {
     fn forward1(&self, acc: &mut Self::Accumulators, b: &Board) {
         self.forward1(acc, b)
     }
+
+    fn forward1_remove(&self, acc: &mut Self::Accumulators, p: Piece, sq: Square, c: Color) {
+        self.forward1_remove(acc, p, sq, c)
+    }
 }
 
+/// quantization of the i16 network: feature layer by QA, output layer by QB, centipawns by SCALE
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
 #[inline(always)]
 fn crelu_i16(x: i16) -> i32 {
-    (x as i32).clamp(0, 255)
+    (x as i32).clamp(0, QA)
 }
 
-impl Network for Network768xH2<i16> {
-    type Accumulators = (Vector<i16>, Vector<i16>); // white, black
-
-    type Input = i16;
-    type Output = i16;
-
-    fn forward2(&self, pov: Color, (w, b): &Self::Accumulators) -> Self::Output {
+impl Network768xH2<i16> {
+    #[inline(always)]
+    fn forward2_scalar(&self, pov: Color, (w, b): &(Vector<i16>, Vector<i16>)) -> i16 {
         let mut output = self.h1_bi.get(0) as i32;
         match pov {
             Color::White => {
@@ -257,11 +320,38 @@ impl Network for Network768xH2<i16> {
                 w.apply_zip(&self.h1_wt[1], |x, y| output += crelu_i16(*x) * *y as i32);
             }
         }
-        output *= 400;
-        output /= 255 * 64;
+        output *= SCALE;
+        output /= QA * QB;
         output as i16
     }
 
+    /// the scalar code, vectorised by the compiler for avx2
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn forward2_avx2(&self, pov: Color, acc: &(Vector<i16>, Vector<i16>)) -> i16 {
+        self.forward2_scalar(pov, acc)
+    }
+}
+
+impl Network for Network768xH2<i16> {
+    type Accumulators = (Vector<i16>, Vector<i16>); // white, black
+
+    type Input = i16;
+    type Output = i16;
+
+    fn forward2(&self, pov: Color, acc: &Self::Accumulators) -> Self::Output {
+        self.forward2_scalar(pov, acc)
+    }
+
+    fn forward2_simd(&self, pov: Color, acc: &Self::Accumulators) -> Self::Output {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            // safe as the cpu supports avx2
+            return unsafe { self.forward2_avx2(pov, acc) };
+        }
+        self.forward2_scalar(pov, acc)
+    }
+
     fn new_accumulators(&self) -> Self::Accumulators {
         self.new_accumulators()
     }
@@ -273,6 +363,10 @@ impl Network for Network768xH2<i16> {
     fn forward1(&self, acc: &mut Self::Accumulators, b: &Board) {
         self.forward1(acc, b)
     }
+
+    fn forward1_remove(&self, acc: &mut Self::Accumulators, p: Piece, sq: Square, c: Color) {
+        self.forward1_remove(acc, p, sq, c)
+    }
 }
 
 impl<T: Default + Copy> Network768xH2<T> {
@@ -301,6 +395,17 @@ impl<T: Copy + Debug + Default> Debug for Network768xH2<T> {
 pub struct NetworkLoader;
 
 impl NetworkLoader {
+    pub fn verify_embedded() -> Result<()> {
+        if EMBEDDED_NNUE.is_empty() {
+            anyhow::bail!("no embedded nnue (built without the nnue feature)");
+        }
+        let sum = checksum(EMBEDDED_NNUE);
+        if sum != EMBEDDED_NNUE_CHECKSUM {
+            anyhow::bail!("embedded nnue checksum {sum:#x} does not match expected {EMBEDDED_NNUE_CHECKSUM:#x}");
+        }
+        Ok(())
+    }
+
     pub fn read_postcard_format(buf: &[u8]) -> Result<Box<Network768xH2<i16>>> {
         let net: Network768xH2<i16> = postcard::from_bytes(buf)?;
         Ok(Box::new(net))
@@ -310,6 +415,86 @@ impl NetworkLoader {
         postcard::to_io(net, w)?;
         Ok(())
     }
+
+    /// a 768→Nx2 network in the raw format of external trainers, converted to our quantization.
+    /// The hidden size is inferred from the length, and stockfish (HalfKA) networks are rejected
+    pub fn read_external_format(buf: &[u8], fmt: &ExternalFormat) -> Result<Box<Network768xH2<i16>>> {
+        if let Some(version) = buf.get(..4) {
+            let version = u32::from_le_bytes(version.try_into()?);
+            if STOCKFISH_VERSIONS.contains(&version) {
+                anyhow::bail!(
+                    "stockfish network (version {version:#x}) has HalfKA features, only 768→Nx2 is supported"
+                );
+            }
+        }
+        anyhow::ensure!(
+            fmt.qa > 0 && fmt.qb > 0 && fmt.scale > 0,
+            "invalid quantization {fmt:?}"
+        );
+        let n_hidden = (buf.len() / 2).saturating_sub(1) / (768 + 3);
+        anyhow::ensure!(
+            n_hidden > 0 && n_hidden <= Vector::<i16>::CAPACITY && buf.len() == ExternalFormat::file_len(n_hidden),
+            "file length {} is not that of a 768→Nx2 network with N <= {}",
+            buf.len(),
+            Vector::<i16>::CAPACITY
+        );
+        let mut vals = buf.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as i64);
+        let mut convert = |num: i64, den: i64| -> Result<i16> {
+            let v = vals.next().context("truncated")?;
+            let q = (v * num + v.signum() * den / 2) / den; // rounded
+            i16::try_from(q).with_context(|| format!("weight {v} out of range once quantization converted"))
+        };
+        let (qa, qb, scale) = (fmt.qa as i64, fmt.qb as i64, fmt.scale as i64);
+        let (our_qa, our_qb, our_scale) = (QA as i64, QB as i64, SCALE as i64);
+        let mut net = Network768xH2::<i16>::new(768, n_hidden);
+        for f in 0..768 {
+            for i in 0..n_hidden {
+                *net.wt[f].set(i) = convert(our_qa, qa)?;
+            }
+        }
+        for i in 0..n_hidden {
+            *net.bi.set(i) = convert(our_qa, qa)?;
+        }
+        for h in 0..2 {
+            for i in 0..n_hidden {
+                *net.h1_wt[h].set(i) = convert(scale * our_qb, qb * our_scale)?;
+            }
+        }
+        *net.h1_bi.set(0) = convert(scale * our_qa * our_qb, qa * qb * our_scale)?;
+        net.description = format!("external 768→{n_hidden}x2 {fmt:?}");
+        Ok(Box::new(net))
+    }
+}
+
+/// stockfish network file versions (first 4 bytes), so they can be told apart from raw networks
+const STOCKFISH_VERSIONS: [u32; 2] = [0x7af3_2f16, 0x7af3_2f20];
+
+/// The raw network format written by external trainers (eg bullet's "simple" 768→Nx2 network):
+/// little endian i16 feature weights [768][N], feature biases [N], output weights [2N] for
+/// the side to move then the opponent, and the output bias, zero padded to a multiple of
+/// 64 bytes. Features are indexed as ours (color * 384 + piece * 64 + square, from the
+/// perspective's side), with the quantization and output scale given here.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExternalFormat {
+    pub qa:    i32,
+    pub qb:    i32,
+    pub scale: i32,
+}
+
+impl Default for ExternalFormat {
+    fn default() -> Self {
+        Self {
+            qa:    255,
+            qb:    64,
+            scale: 400,
+        }
+    }
+}
+
+impl ExternalFormat {
+    fn file_len(n_hidden: usize) -> usize {
+        (2 * (768 * n_hidden + n_hidden + 2 * n_hidden + 1)).next_multiple_of(64)
+    }
 }
 
 #[cfg(test)]
@@ -377,6 +562,49 @@ mod tests {
         prof_fw2.bench(|| net.forward2(bd1.turn(), &acc1));
     }
 
+    /// in the external format, with the (rescaled) weights of net
+    fn external_bytes(net: &Network768xH2<i16>, fmt: &ExternalFormat) -> Vec<u8> {
+        let n = net.n_hidden_layer;
+        let layer0 = net.wt.iter().chain([&net.bi]).flat_map(|v| v.as_slice()[..n].to_vec());
+        let layer1 = net.h1_wt.iter().flat_map(|v| v.as_slice()[..n].to_vec());
+        let mut vals: Vec<i32> = layer0.map(|w| w as i32 * fmt.qa / QA).collect();
+        vals.extend(layer1.map(|w| w as i32 * fmt.qb * SCALE / (QB * fmt.scale)));
+        vals.push(net.h1_bi.get(0) as i32 * fmt.qa * fmt.qb * SCALE / (QA * QB * fmt.scale));
+        let mut bytes: Vec<u8> = vals.iter().flat_map(|&v| (v as i16).to_le_bytes()).collect();
+        bytes.resize(ExternalFormat::file_len(n), 0);
+        bytes
+    }
+
+    #[test]
+    fn test_read_external_format() -> TestResult {
+        let net = network_fixture();
+        let b = Catalog::example_game()[3].board();
+
+        let fmt = ExternalFormat::default();
+        let ext = NetworkLoader::read_external_format(&external_bytes(&net, &fmt), &fmt)?;
+        assert_eq!(ext.wt, net.wt);
+        assert_eq!(ext.h1_wt, net.h1_wt);
+        assert_eq!(ext.predict(&b), net.predict(&b));
+
+        // converted from a different quantization
+        let fmt = ExternalFormat {
+            qa:    510,
+            qb:    32,
+            scale: 200,
+        };
+        let ext = NetworkLoader::read_external_format(&external_bytes(&net, &fmt), &fmt)?;
+        assert_eq!(ext.predict(&b), net.predict(&b));
+
+        // not a multiple of the layer sizes, and a stockfish header
+        let bytes = external_bytes(&net, &fmt);
+        assert!(NetworkLoader::read_external_format(&bytes[..bytes.len() - 64], &fmt).is_err());
+        let mut sf = bytes;
+        sf[..4].copy_from_slice(&0x7af3_2f20_u32.to_le_bytes());
+        let err = NetworkLoader::read_external_format(&sf, &fmt).unwrap_err();
+        assert!(err.to_string().contains("stockfish"));
+        Ok(())
+    }
+
     fn network_fixture() -> Box<Network768xH2<i16>> {
         let file = "../../crates/odonata-engine/resources/r61-net.i16.bin";
         let mut buf = Vec::new();
@@ -33,15 +33,19 @@ impl Nnue {
                 let nnue = NnueMixin::<Network768xH2<i16>> {
                     wb_acc: RefCell::new(net.new_accumulators()),
                     last: RefCell::new(Board::new_empty()),
+                    stack: RefCell::default(),
+                    simd: false,
                     net,
                 };
                 Ok(Nnue::Nnue768H2(nnue))
             }
-            f if f.ends_with(".bin") => {
+            f if f.ends_with(".bin") || f.ends_with(".nnue") => {
                 let net = Network768xH2::<i16>::from_file(nnue_file)?;
                 let nnue = NnueMixin::<Network768xH2<i16>> {
                     wb_acc: RefCell::new(net.new_accumulators()),
                     last: RefCell::new(Board::new_empty()),
+                    stack: RefCell::default(),
+                    simd: false,
                     net,
                 };
                 Ok(Nnue::Nnue768H2(nnue))
@@ -51,6 +55,8 @@ impl Nnue {
                 let nnue = NnueMixin::<Network768xH2<i16>> {
                     wb_acc: RefCell::new(net.new_accumulators()),
                     last: RefCell::new(Board::new_empty()),
+                    stack: RefCell::default(),
+                    simd: false,
                     net,
                 };
                 Ok(Nnue::Nnue768H2(nnue))
@@ -60,6 +66,8 @@ impl Nnue {
                 let nnue = NnueMixin {
                     wb_acc: RefCell::new(net.new_accumulators()),
                     last: RefCell::new(Board::starting_pos()),
+                    stack: RefCell::default(),
+                    simd: false,
                     net,
                 };
                 Ok(Nnue::Nnue(nnue))
@@ -68,6 +76,18 @@ impl Nnue {
         }
     }
 
+    /// an all-zero network, used only when no network can be loaded
+    pub fn fallback() -> Nnue {
+        let net = Box::new(Network768xH2::<i16>::new(768, 16));
+        Nnue::Nnue768H2(NnueMixin {
+            wb_acc: RefCell::new(net.new_accumulators()),
+            last: RefCell::new(Board::new_empty()),
+            stack: RefCell::default(),
+            simd: false,
+            net,
+        })
+    }
+
     pub fn eval(&self, pos: &Position) -> i16 {
         match self {
             Nnue::Nnue(nnue) => nnue.eval(pos) as i16,
@@ -82,6 +102,25 @@ impl Nnue {
         }
     }
 
+    /// see [`NnueMixin::eval_pieces_removed`]
+    pub fn eval_pieces_removed(&self, b: &Board) -> (i16, Vec<(Square, i16)>) {
+        match self {
+            Nnue::Nnue(nnue) => {
+                let (base, evals) = nnue.eval_pieces_removed(b);
+                (base as i16, evals.into_iter().map(|(sq, e)| (sq, e as i16)).collect())
+            }
+            Nnue::Nnue768H2(nnue) => nnue.eval_pieces_removed(b),
+        }
+    }
+
+    /// use the simd forward pass (see [`Network::forward2_simd`])
+    pub fn set_simd(&mut self, simd: bool) {
+        match self {
+            Nnue::Nnue(nnue) => nnue.simd = simd,
+            Nnue::Nnue768H2(nnue) => nnue.simd = simd,
+        }
+    }
+
     pub fn new_game(&mut self) {
         match self {
             Nnue::Nnue(nnue) => nnue.new_game(),
@@ -95,6 +134,37 @@ pub struct NnueMixin<N: Network> {
     wb_acc: RefCell<N::Accumulators>,
     net:    Box<N>,
     last:   RefCell<Board>,
+    stack:  RefCell<AccumulatorStack<N::Accumulators>>,
+    simd:   bool,
+}
+
+/// Accumulators of boards on the path from the search root to the last evaluated board, by
+/// increasing ply. Entries off the path to the board being evaluated are popped, and the new
+/// board is pushed as a delta from the deepest remaining entry, so the stack follows the
+/// search tree. Entries are reused, so pushes do not allocate.
+#[derive(Clone)]
+struct AccumulatorStack<A> {
+    entries:   Vec<StackEntry<A>>,
+    len:       usize,
+    refreshes: u64,
+}
+
+#[derive(Clone)]
+struct StackEntry<A> {
+    ply:   usize,
+    hash:  Hash,
+    board: Board,
+    acc:   A,
+}
+
+impl<A> Default for AccumulatorStack<A> {
+    fn default() -> Self {
+        Self {
+            entries:   Vec::new(),
+            len:       0,
+            refreshes: 0,
+        }
+    }
 }
 
 // impl<N: Network + Default> Default for NnueMixin<N> {
@@ -107,29 +177,87 @@ impl<N: Network + Debug> Debug for NnueMixin<N> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "net         : {:?}", self.net)?;
         writeln!(f, "last eval bd: {}", self.last.borrow().to_fen())?;
+        let stack = self.stack.borrow();
+        writeln!(f, "acc stack   : {} deep {} refreshes", stack.len, stack.refreshes)?;
         Ok(())
     }
 }
 
 impl<N: Network> NnueMixin<N> {
-
     pub fn new_game(&mut self) {
         let mut acc = self.net.new_accumulators();
         let b = Board::starting_pos();
         self.net.forward1(&mut acc, &b);
         self.wb_acc = RefCell::new(acc);
         self.last = RefCell::new(b);
+        self.stack.get_mut().len = 0;
+    }
+
+    #[inline]
+    fn forward2(&self, pov: Color, acc: &N::Accumulators) -> N::Output {
+        match self.simd {
+            true => self.net.forward2_simd(pov, acc),
+            false => self.net.forward2(pov, acc),
+        }
     }
 
     #[cfg(test)]
     pub fn testing_eval_current(&self) -> N::Output {
         let turn = self.last.borrow().turn();
-        self.net.forward2(turn, &self.wb_acc.borrow())
+        self.forward2(turn, &self.wb_acc.borrow())
     }
 
+    /// incremental, using the accumulator stack. Exact, as every delta is taken between
+    /// the boards themselves (and never inferred from the moves or hashes)
     pub fn eval(&self, pos: &Position) -> N::Output {
         let b = pos.board();
-        self.eval_stateless(b)
+        let ply = pos.search_ply();
+        let mut stack = self.stack.borrow_mut();
+        let stack = &mut *stack;
+        while let Some(top) = stack.len.checked_sub(1).map(|i| &stack.entries[i]) {
+            if top.ply <= ply && pos.search_hash(top.ply) == Some(top.hash) {
+                if top.ply == ply {
+                    return self.forward2(b.turn(), &top.acc);
+                }
+                break;
+            }
+            stack.len -= 1;
+        }
+
+        let len = stack.len;
+        if len == stack.entries.len() {
+            stack.entries.push(StackEntry {
+                ply:   0,
+                hash:  0,
+                board: Board::new_empty(),
+                acc:   self.net.new_accumulators(),
+            });
+        }
+        let refresh = len == 0 || self.is_king_bucket_change(&stack.entries[len - 1].board, b);
+        let (parents, children) = stack.entries.split_at_mut(len);
+        let entry = &mut children[0];
+        if refresh {
+            entry.acc = self.net.new_accumulators();
+            self.net.forward1(&mut entry.acc, b);
+        } else {
+            let parent = &parents[len - 1];
+            entry.acc.clone_from(&parent.acc);
+            self.net.forward1_input(&mut entry.acc, &parent.board, b);
+        }
+        entry.ply = ply;
+        entry.hash = pos.hash();
+        entry.board.clone_from(b);
+        let output = self.forward2(b.turn(), &entry.acc);
+        stack.len += 1;
+        stack.refreshes += refresh as u64;
+        output
+    }
+
+    fn is_king_bucket_change(&self, b1: &Board, b2: &Board) -> bool {
+        N::KING_BUCKETS > 1
+            && Color::ALL
+                .iter()
+                .any(|&c| self.net.king_bucket(b1.king(c), c) != self.net.king_bucket(b2.king(c), c))
     }
 
     pub fn eval_stateless(&self, board: &Board) -> N::Output {
@@ -137,12 +265,34 @@ impl<N: Network> NnueMixin<N> {
         let mut acc = self.wb_acc.borrow_mut();
         self.net.forward1_input(&mut acc, &last, board);
         *last = board.clone();
-        self.net.forward2(board.turn(), &acc)
+        self.forward2(board.turn(), &acc)
+    }
+
+    /// the eval of the board, and the eval with each occupied square emptied in turn
+    ///
+    /// the base accumulator is calculated once, and each removal is a single-feature delta
+    /// from it, rather than a full evaluation per square. The incremental state is untouched.
+    pub fn eval_pieces_removed(&self, board: &Board) -> (N::Output, Vec<(Square, N::Output)>) {
+        let mut base = self.net.new_accumulators();
+        self.net.forward1(&mut base, board);
+        let evals = board
+            .occupied()
+            .squares()
+            .map(|sq| {
+                let mut acc = base.clone();
+                let p = board.piece_unchecked(sq);
+                let c = board.color_of(sq).unwrap();
+                self.net.forward1_remove(&mut acc, p, sq, c);
+                (sq, self.forward2(board.turn(), &acc))
+            })
+            .collect();
+        (self.forward2(board.turn(), &base), evals)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use odonata_base::catalog::Catalog;
     use odonata_base::infra::profiler::PerfProfiler;
     use odonata_base::Epd;
     use rand_chacha::rand_core::SeedableRng as _;
@@ -217,6 +367,184 @@ mod tests {
         }
     }
 
+    /// walks the tree of the first few moves, pushing moves then making them in place
+    /// (as quiescence does), checking the incremental eval at every node
+    fn walk(nnue: &Nnue, stateless: &Nnue, pos: &mut Position, depth: u32) -> u64 {
+        assert_eq!(nnue.eval(pos), stateless.eval_stateless(pos.board()), "{pos:#}");
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 1;
+        for &mv in pos.board().legal_moves().iter().take(6) {
+            if depth > 1 {
+                pos.push_move(mv);
+                nodes += walk(nnue, stateless, pos, depth - 1);
+                pos.pop_move();
+            } else {
+                pos.make_move(mv);
+                nodes += walk(nnue, stateless, pos, depth - 1);
+                pos.undo_move();
+            }
+            // re-evaluating the parent pops the child
+            assert_eq!(nnue.eval(pos), stateless.eval_stateless(pos.board()));
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_nn_incremental() {
+        // the simd forward pass must agree exactly with the scalar one
+        let mut nnue = Nnue::from_file("").unwrap();
+        nnue.set_simd(true);
+        let stateless = Nnue::from_file("").unwrap();
+        for epd in Catalog::bratko_kopec() {
+            let mut pos = Position::from_board(epd.board());
+            walk(&nnue, &stateless, &mut pos, 3);
+
+            // a new root after moves are played
+            pos.push_moves(epd.board().legal_moves().iter().take(1).copied());
+            pos.play_search_moves();
+            walk(&nnue, &stateless, &mut pos, 2);
+        }
+        let Nnue::Nnue768H2(mixin) = &nnue else { panic!() };
+        assert!(mixin.stack.borrow().refreshes > 0);
+        assert!(mixin.stack.borrow().entries.len() <= 4);
+    }
+
+    /// a toy network whose features depend on which half of the board (a-d or e-h files)
+    /// each side's king is on, so a king crossing between halves changes bucket
+    #[derive(Clone, Debug)]
+    struct KingBucketNet;
+
+    impl KingBucketNet {
+        fn weight(bucket: usize, p: Piece, c: Color, sq: Square) -> i32 {
+            let feature = bucket * 768 + c.index() * 384 + p.index() * 64 + sq.index();
+            (feature as i32 * 7919) % 101 - 50
+        }
+    }
+
+    impl Network for KingBucketNet {
+        type Accumulators = [(usize, i32); 2]; // (king bucket, sum of weights) by perspective
+        type Input = ();
+        type Output = i32;
+
+        const KING_BUCKETS: usize = 2;
+
+        fn king_bucket(&self, king_sq: Square, _king_color: Color) -> usize {
+            king_sq.file_index() / 4
+        }
+
+        fn new_accumulators(&self) -> Self::Accumulators {
+            [(0, 0); 2]
+        }
+
+        // a delta within the buckets of the accumulators, so stale if a king changed bucket
+        fn forward1_input(&self, acc: &mut Self::Accumulators, bd1: &Board, bd2: &Board) {
+            for sq in (bd1.occupied() | bd2.occupied()).squares() {
+                let (before, after) = ((bd1.piece(sq), bd1.color_of(sq)), (bd2.piece(sq), bd2.color_of(sq)));
+                if before != after {
+                    if let (Some(p), Some(c)) = before {
+                        self.forward1_remove(acc, p, sq, c);
+                    }
+                    if let (Some(p), Some(c)) = after {
+                        for (bucket, sum) in acc.iter_mut() {
+                            *sum += Self::weight(*bucket, p, c, sq);
+                        }
+                    }
+                }
+            }
+        }
+
+        fn forward1(&self, acc: &mut Self::Accumulators, b: &Board) {
+            for pov in Color::ALL {
+                let bucket = self.king_bucket(b.king(pov), pov);
+                let sum = b
+                    .occupied()
+                    .squares()
+                    .map(|sq| Self::weight(bucket, b.piece_unchecked(sq), b.color_of(sq).unwrap(), sq))
+                    .sum();
+                acc[pov.index()] = (bucket, sum);
+            }
+        }
+
+        fn forward1_remove(&self, acc: &mut Self::Accumulators, p: Piece, sq: Square, c: Color) {
+            for (bucket, sum) in acc.iter_mut() {
+                *sum -= Self::weight(*bucket, p, c, sq);
+            }
+        }
+
+        fn forward2(&self, pov: Color, acc: &Self::Accumulators) -> i32 {
+            acc[pov.index()].1 - acc[pov.flip_side().index()].1
+        }
+    }
+
+    #[test]
+    fn test_nn_king_bucket_refresh() {
+        let net = KingBucketNet;
+        let nnue = NnueMixin {
+            wb_acc: RefCell::new(net.new_accumulators()),
+            last:   RefCell::new(Board::new_empty()),
+            stack:  RefCell::default(),
+            simd:   false,
+            net:    Box::new(net),
+        };
+        let mut pos = Position::starting_pos();
+        assert_eq!(nnue.eval(&pos), nnue.net.predict(pos.board()));
+
+        // the kings walk e1-e2-d3-e2 (and e8-e7-d6), changing bucket on the last three moves
+        for mv in "e2e4 e7e5 e1e2 e8e7 e2d3 e7d6 d3e2".split_whitespace() {
+            let mv = pos.board().parse_uci_move(mv).unwrap();
+            pos.push_move(mv);
+            assert_eq!(nnue.eval(&pos), nnue.net.predict(pos.board()), "{pos:#}");
+        }
+        assert_eq!(nnue.stack.borrow().refreshes, 1 + 3);
+    }
+
+    #[test]
+    fn bench_nnue_incremental() {
+        fn walk_with(pos: &mut Position, depth: u32, eval: &dyn Fn(&Position) -> i16) -> i64 {
+            let mut sum = eval(pos) as i64;
+            if depth > 0 {
+                for &mv in pos.board().legal_moves().iter() {
+                    pos.push_move(mv);
+                    sum += walk_with(pos, depth - 1, eval);
+                    pos.pop_move();
+                }
+            }
+            sum
+        }
+        let nnue = Nnue::from_file("").unwrap();
+        let mut pos = Position::starting_pos();
+        pos.push_moves_str("e4 e5 Nf3 Nc6 Bb5").unwrap();
+        pos.play_search_moves();
+        for depth in 1..=3 {
+            let mut prof_inc = PerfProfiler::new(&format!("nnue incremental d{depth}"));
+            let mut prof_stateless = PerfProfiler::new(&format!("nnue stateless d{depth}"));
+            let inc = prof_inc.bench(|| walk_with(&mut pos, depth, &|p| nnue.eval(p)));
+            let stateless = prof_stateless.bench(|| walk_with(&mut pos, depth, &|p| nnue.eval_stateless(p.board())));
+            assert_eq!(inc, stateless);
+        }
+    }
+
+    #[test]
+    fn test_eval_pieces_removed() {
+        let nnue = Nnue::from_file("").unwrap();
+        for epd in Catalog::bratko_kopec() {
+            let b = epd.board();
+            let (base, evals) = nnue.eval_pieces_removed(&b);
+            assert_eq!(base, nnue.eval_stateless(&b), "{epd}");
+            assert_eq!(evals.len(), b.occupied().popcount() as usize);
+            for (sq, eval) in evals {
+                let mut bb = b.clone().into_builder();
+                bb.set_piece(sq, None);
+                assert_eq!(eval, nnue.eval_stateless(&bb.build()), "{epd} {sq}");
+            }
+        }
+        let b = Catalog::starting_board();
+        let mut prof = PerfProfiler::new("nnue eval_pieces_removed");
+        prof.bench(|| nnue.eval_pieces_removed(&b));
+    }
+
     #[test]
     fn bench_nnue() {
         let mut board = Board::starting_pos();
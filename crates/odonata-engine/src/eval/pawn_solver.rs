@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use odonata_base::domain::node::Node;
+use odonata_base::infra::component::Component;
+use odonata_base::piece::Hash;
+use odonata_base::prelude::*;
+
+/// A proven result, from the point of view of the side to move. Plies are the length of the proof.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Proof {
+    Win(Ply),
+    Draw,
+    Loss(Ply),
+}
+
+impl Proof {
+    /// the proof one ply closer to the root, from the other side's point of view
+    fn negate(self) -> Self {
+        match self {
+            Proof::Win(ply) => Proof::Loss(ply + 1),
+            Proof::Draw => Proof::Draw,
+            Proof::Loss(ply) => Proof::Win(ply + 1),
+        }
+    }
+
+    /// quicker wins and slower losses rank higher
+    fn rank(self) -> i32 {
+        match self {
+            Proof::Win(ply) => 1000 - ply,
+            Proof::Draw => 0,
+            Proof::Loss(ply) => -1000 + ply,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Entry {
+    Proven(Proof),
+    Unresolved(Ply), // searched to this depth without a proof
+}
+
+/// Exact solver for king and pawn endings (the material signature of kings and pawns only).
+///
+/// A full-width, iteratively deepened search over win/draw/loss with its own transposition
+/// table. Leaves are only ever exact: mate, stalemate, insufficient material, or a queen or
+/// rook against a bare king with that side to move. Wins are proven by reaching such a leaf
+/// in every line, so a proof is exact, and positions not resolved within `node_budget`
+/// are left to the normal search.
+///
+/// Repetitions are ignored inside a proof, and positions whose proof could run into the
+/// fifty-move rule are not attempted.
+#[derive(Clone)]
+pub struct PawnSolver {
+    pub enabled:    bool,
+    max_pawns:      i32,
+    min_depth:      Ply,
+    max_ply:        Ply,
+    node_budget:    u64,
+    win_score:      Score,
+    max_tt_entries: usize,
+    tt:             HashMap<Hash, Entry>,
+    nodes:          u64,
+}
+
+impl Default for PawnSolver {
+    fn default() -> Self {
+        Self {
+            enabled:        true,
+            max_pawns:      6,
+            min_depth:      3,
+            max_ply:        24,
+            node_budget:    20_000,
+            win_score:      4000.cp(),
+            max_tt_entries: 1 << 20,
+            tt:             HashMap::new(),
+            nodes:          0,
+        }
+    }
+}
+
+impl Configurable for PawnSolver {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.max_pawns.set(p.get("max_pawns"))?;
+        self.min_depth.set(p.get("min_depth"))?;
+        self.max_ply.set(p.get("max_ply"))?;
+        self.node_budget.set(p.get("node_budget"))?;
+        self.win_score.set(p.get("win_score"))?;
+        self.max_tt_entries.set(p.get("max_tt_entries"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for PawnSolver {
+    fn new_game(&mut self) {
+        self.tt.clear();
+    }
+
+    fn new_position(&mut self) {
+        if self.tt.len() > self.max_tt_entries {
+            self.tt.clear();
+        }
+    }
+}
+
+// the transposition table is left out as it can be large
+impl fmt::Debug for PawnSolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PawnSolver")
+            .field("enabled", &self.enabled)
+            .field("max_pawns", &self.max_pawns)
+            .field("min_depth", &self.min_depth)
+            .field("max_ply", &self.max_ply)
+            .field("node_budget", &self.node_budget)
+            .field("win_score", &self.win_score)
+            .field("max_tt_entries", &self.max_tt_entries)
+            .field("tt_entries", &self.tt.len())
+            .finish()
+    }
+}
+
+impl fmt::Display for PawnSolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+impl PawnSolver {
+    pub fn is_applicable(&self, b: &Board, n: &Node) -> bool {
+        self.enabled
+            && n.depth >= self.min_depth
+            && (b.occupied() - b.kings() - b.pawns()).is_empty()
+            && b.pawns().popcount() <= self.max_pawns
+            && b.halfmove_clock() + self.max_ply < 100
+    }
+
+    /// a proven win or loss as a (non-mate) score, preferring the shorter wins
+    pub fn to_score(&self, proof: Proof) -> Option<Score> {
+        match proof {
+            Proof::Win(ply) => Some(Score::from_cp(self.win_score.as_i16() as i32 - ply)),
+            Proof::Loss(ply) => Some(Score::from_cp(-(self.win_score.as_i16() as i32) + ply)),
+            Proof::Draw => None,
+        }
+    }
+
+    /// the proven result for the side to move, or None if unresolved within the node budget
+    pub fn solve(&mut self, b: &Board) -> Option<Proof> {
+        if let Some(&entry) = self.tt.get(&b.hash()) {
+            match entry {
+                Entry::Proven(proof) => return Some(proof),
+                Entry::Unresolved(depth) if depth >= self.max_ply => return None,
+                Entry::Unresolved(_) => {}
+            }
+        }
+        self.nodes = 0;
+        for depth in 1..=self.max_ply {
+            if let Some(proof) = self.search(b, depth) {
+                return Some(proof);
+            }
+            if self.nodes >= self.node_budget {
+                break;
+            }
+        }
+        // dont retry a position we have already given up on
+        self.tt.insert(b.hash(), Entry::Unresolved(self.max_ply));
+        None
+    }
+
+    fn search(&mut self, b: &Board, depth: Ply) -> Option<Proof> {
+        if let Some(&entry) = self.tt.get(&b.hash()) {
+            match entry {
+                Entry::Proven(proof) => return Some(proof),
+                Entry::Unresolved(d) if d >= depth => return None,
+                Entry::Unresolved(_) => {}
+            }
+        }
+        self.nodes += 1;
+
+        let moves = b.legal_moves();
+        let proof = if moves.is_empty() {
+            if b.is_in_check(b.color_us()) {
+                Some(Proof::Loss(0))
+            } else {
+                Some(Proof::Draw)
+            }
+        } else if b.is_draw_insufficient_material() {
+            Some(Proof::Draw)
+        } else if (b.them() - b.kings()).is_empty() && !(b.rooks_or_queens() & b.us()).is_empty() {
+            Some(Proof::Win(0))
+        } else if depth <= 0 || self.nodes >= self.node_budget {
+            return None;
+        } else {
+            // promotions first, as they are most likely to prove a result
+            let ordered = moves
+                .iter()
+                .filter(|mv| mv.is_promo())
+                .chain(moves.iter().filter(|mv| !mv.is_promo()));
+            let mut best: Option<Proof> = None;
+            let mut all_proven = true;
+            for &mv in ordered {
+                match self.search(&b.make_move(mv), depth - 1).map(Proof::negate) {
+                    Some(proof @ Proof::Win(_)) => {
+                        best = Some(proof);
+                        all_proven = true;
+                        break;
+                    }
+                    Some(proof) => {
+                        best = match best {
+                            Some(best) if best.rank() >= proof.rank() => Some(best),
+                            _ => Some(proof),
+                        }
+                    }
+                    None => all_proven = false,
+                }
+                if self.nodes >= self.node_budget {
+                    // an incomplete node proves nothing, and is not recorded
+                    return None;
+                }
+            }
+            best.filter(|_| all_proven)
+        };
+
+        match proof {
+            Some(proof) => self.tt.insert(b.hash(), Entry::Proven(proof)),
+            None => self.tt.insert(b.hash(), Entry::Unresolved(depth)),
+        };
+        proof
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_pawn_solver() {
+        let mut solver = PawnSolver {
+            node_budget: 1_000_000,
+            ..PawnSolver::default()
+        };
+        let solve = |solver: &mut PawnSolver, fen: &str| solver.solve(&Board::parse_fen(fen).unwrap());
+
+        // promotes next move, with the black king far away
+        assert_eq!(
+            solve(&mut solver, "8/5P2/8/8/8/8/k7/2K5 w - - 0 1"),
+            Some(Proof::Win(2))
+        );
+        assert_eq!(solve(&mut solver, "8/8/8/8/8/8/k7/2K5 w - - 0 1"), Some(Proof::Draw));
+
+        // stalemated
+        assert_eq!(solve(&mut solver, "k7/2K5/1P6/8/8/8/8/8 b - - 0 1"), Some(Proof::Draw));
+
+        // king on the sixth in front of the pawn wins whoever is to move
+        assert!(matches!(
+            solve(&mut solver, "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(Proof::Win(_))
+        ));
+        assert!(matches!(
+            solve(&mut solver, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(Proof::Loss(_))
+        ));
+
+        // given up positions are not retried
+        let mut solver = PawnSolver {
+            node_budget: 10,
+            ..PawnSolver::default()
+        };
+        let b = Board::parse_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(solver.solve(&b), None);
+        assert_eq!(solver.solve(&b), None);
+        assert_eq!(solver.nodes, 10);
+
+        let n = Node::root(4);
+        assert!(solver.is_applicable(&b, &n));
+        assert!(!solver.is_applicable(&Board::starting_pos(), &n));
+        assert_eq!(solver.to_score(Proof::Win(3)), Some(3997.cp()));
+        assert_eq!(solver.to_score(Proof::Draw), None);
+    }
+}
@@ -64,13 +64,15 @@ impl Search {
         //     return (score,mv);
         // }
 
+        self.tt_probes += 1;
         if let Some(entry) = self.tt.probe_by_board(b, n.ply, n.depth) {
             let score = entry.score.as_score(n.ply);
             debug_assert!(score.is_finite());
             Metrics::incr_node(n, Event::TtNodeHit);
+            self.tt_hits += 1;
 
             // FIXME! v33
-            if entry.depth >= n.depth {
+            if entry.depth >= n.depth && self.tt.is_path_independent(&entry) {
                 // if entry.draft >= draft  && (ply >= 1 || self.tt.allow_tt_at_root) && !(b.repetition_count().total > 0 && self.repetition.avoid_tt_on_repeats)
 
                 // if n.ply == 0 && self.restrictions.is_none() {
@@ -174,6 +176,20 @@ impl Search {
         if !self.recognizer.enabled || n.depth < self.recognizer.min_depth || n.ply == 0 {
             return (None, None);
         }
+        if self.pawn_solver.is_applicable(b, n) {
+            match self.pawn_solver.solve(b) {
+                Some(proof) => {
+                    Metrics::incr_node(n, Event::PawnSolverProven);
+                    let score = self
+                        .pawn_solver
+                        .to_score(proof)
+                        .unwrap_or_else(|| self.eval.eval_draw(b, n.ply).pov_score());
+                    return (Some(score), None);
+                }
+                None => Metrics::incr_node(n, Event::PawnSolverUnresolved),
+            }
+        }
+
         let endgame = EndGame::from_board(b);
         Metrics::inc_endgame(endgame);
 
@@ -18,6 +18,7 @@ use tabled::settings::object::{Columns, Segment};
 use tabled::settings::{Alignment, Modify, Padding, Style};
 
 use crate::eval::feature::{Feature, FeatureCategory};
+use crate::eval::influence::Influence;
 use crate::eval::weight::{Number, Rounding, Weight, WeightOf};
 
 pub trait WeightVec<T: Number> {
@@ -35,6 +36,11 @@ pub trait Scorer<T> {
 
     fn accum(&mut self, f: Feature, w: i32, b: i32);
     fn set_bits(&mut self, _i: Feature, _bits: Bitboard) {}
+    fn set_influence(&mut self, _influence: &Influence) {}
+    /// whether to build the influence tables, which cost every eval
+    fn wants_influence(&self) -> bool {
+        true
+    }
     fn apply_scaling(&mut self, _scaling: f32) {}
     fn total(&self) -> WeightOf<T>;
 }
@@ -87,6 +93,61 @@ impl<T: Number> dyn WeightVec<T> {
     }
 }
 
+impl<T: Number> dyn WeightVec<T> {
+    /// one row per feature: index, name, category, start and end game weights
+    pub fn write_csv<W: Write>(&self, mut w: W) -> anyhow::Result<()> {
+        writeln!(w, "index,feature,category,s,e")?;
+        for f in Feature::iter() {
+            let (s, e): (f64, f64) = (self.weight(f).s().into(), self.weight(f).e().into());
+            writeln!(w, "{},{f},{},{s},{e}", f.index(), f.category().name())?;
+        }
+        Ok(())
+    }
+
+    /// the piece square tables as a heatmap image in (ascii) PPM format: a row of boards per
+    /// piece (pawn first), start game on the left and end game on the right, each board
+    /// with rank 8 at the top and shaded from blue (lowest) through white to red (highest)
+    pub fn write_pst_heatmap<W: Write>(&self, mut w: W, square_px: usize) -> anyhow::Result<()> {
+        const GAP: usize = 1; // in squares, between boards
+        let (cols, rows) = (2 * (8 + GAP) - GAP, Piece::ALL.len() * (8 + GAP) - GAP);
+        writeln!(w, "P3\n{} {}\n255", cols * square_px, rows * square_px)?;
+        let pst = |p: Piece, sq: Square| self.weight(Feature::index_pst(Feature::PstP_A1, p, sq));
+        let tables = Piece::ALL.map(|p| {
+            let s: Vec<f64> = Square::all().map(|sq| pst(p, sq).s().into()).collect();
+            let e: Vec<f64> = Square::all().map(|sq| pst(p, sq).e().into()).collect();
+            [s, e]
+        });
+        for y in 0..rows * square_px {
+            let mut line = Vec::with_capacity(cols * square_px);
+            for x in 0..cols * square_px {
+                let (row, col) = (y / square_px, x / square_px);
+                let (piece, rank) = (row / (8 + GAP), row % (8 + GAP));
+                let (phase, file) = (col / (8 + GAP), col % (8 + GAP));
+                let rgb = if rank >= 8 || file >= 8 {
+                    [255, 255, 255]
+                } else {
+                    let table = &tables[piece][phase];
+                    heat(table, table[8 * (7 - rank) + file])
+                };
+                line.push(format!("{} {} {}", rgb[0], rgb[1], rgb[2]));
+            }
+            writeln!(w, "{}", line.join("  "))?;
+        }
+        Ok(())
+    }
+}
+
+/// blue through white to red, scaled to the largest magnitude in the table
+fn heat(table: &[f64], v: f64) -> [u8; 3] {
+    let max = table.iter().fold(f64::EPSILON, |m, t| m.max(t.abs()));
+    let shade = (255.0 * (1.0 - v.abs() / max)) as u8;
+    if v >= 0.0 {
+        [255, shade, shade]
+    } else {
+        [shade, shade, 255]
+    }
+}
+
 // pub(crate) struct Hardcoded<T: Number> {
 //     _phantom: PhantomData<T>,
 // }
@@ -523,6 +584,15 @@ where
     }
 }
 
+impl<T: Number, W: Fn(Feature) -> WeightOf<T>> SummationScorer<T, W> {
+    /// the shipped weights leave the influence features unscored
+    fn weights_influence(&self) -> bool {
+        [Feature::InfluenceCenter, Feature::InfluenceKingZone]
+            .into_iter()
+            .any(|f| (self.wts)(f) != WeightOf::zero())
+    }
+}
+
 impl<T: Number, R, W> CategoryScorer<T, W>
 where
     W: Fn(Feature) -> R,
@@ -760,6 +830,9 @@ impl<W: Fn(Feature) -> WeightOf<f64>> Scorer<f64> for SummationScorer<f64, W>
     fn accum(&mut self, f: Feature, w: i32, b: i32) {
         self.total += (w - b) * (self.wts)(f);
     }
+    fn wants_influence(&self) -> bool {
+        self.weights_influence()
+    }
     fn total(&self) -> WeightOf<f64> {
         self.total
     }
@@ -773,6 +846,9 @@ impl<W: Fn(Feature) -> WeightOf<i16>> Scorer<i16> for SummationScorer<i16, W>
     fn accum(&mut self, f: Feature, w: i32, b: i32) {
         self.total += (w - b) * (self.wts)(f);
     }
+    fn wants_influence(&self) -> bool {
+        self.weights_influence()
+    }
     fn total(&self) -> WeightOf<i16> {
         self.total
     }
@@ -786,6 +862,9 @@ impl<W: Fn(Feature) -> WeightOf<f32>> Scorer<f32> for SummationScorer<f32, W>
     fn accum(&mut self, f: Feature, w: i32, b: i32) {
         self.total += (w - b) * (self.wts)(f);
     }
+    fn wants_influence(&self) -> bool {
+        self.weights_influence()
+    }
     fn total(&self) -> WeightOf<f32> {
         self.total
     }
@@ -799,6 +878,9 @@ impl<W: Fn(Feature) -> WeightOf<i32>> Scorer<i32> for SummationScorer<i32, W>
     fn accum(&mut self, f: Feature, w: i32, b: i32) {
         self.total += (w - b) * (self.wts)(f);
     }
+    fn wants_influence(&self) -> bool {
+        self.weights_influence()
+    }
     fn total(&self) -> WeightOf<i32> {
         self.total
     }
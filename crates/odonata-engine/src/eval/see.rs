@@ -54,14 +54,20 @@ impl See {
     //
     // using n=b=325 rather than n=325, b=350 gives +6 Elo
     //
+    // non-captures score zero
     pub fn eval_move_see(&self, board: &Board, mv: Move) -> i32 {
         if !mv.is_capture() {
             return 0;
         }
+        self.eval_move_swap(board, mv)
+    }
+
+    // as eval_move_see, but quiet moves are exchanged too, so a quiet move onto a square
+    // the opponent wins scores the material lost
+    pub fn eval_move_swap(&self, board: &Board, mv: Move) -> i32 {
         debug_assert!(!mv.is_null());
-        debug_assert!(mv.is_capture());
         debug_assert!(board.us().contains(mv.from().as_bb()));
-        debug_assert!(board.them().contains(mv.capture_square(board).as_bb()));
+        debug_assert!(!mv.is_capture() || board.them().contains(mv.capture_square(board).as_bb()));
 
         let bb = PreCalc::instance();
         let mut gain: [i32; 40] = [0; 40];
@@ -69,12 +75,14 @@ impl See {
         let mut attacker = Some(mv.from());
         let to = mv.to().as_bb();
         let mut occ = board.black() | board.white();
+        if mv.is_ep_capture(board) {
+            // the captured pawn is not on the 'to' square, and may have blocked a file onto it
+            occ -= mv.capture_square(board).as_bb();
+        }
         let mut attacker_color = board.color_us();
         let mut attackers_bw = BoardCalcs::attacked_by(to, occ, board); // will include the current 'mv' attacker
-        let mut attackers_xray = BoardCalcs::attacked_by(to, Bitboard::EMPTY, board); // will include the current 'mv' attacker
-        attackers_xray -= board.non_line_pieces() | attackers_bw;
 
-        gain[0] = CLASSICAL_WEIGHTS[mv.capture_piece(board).unwrap()].s() as i32;
+        gain[0] = mv.capture_piece(board).map_or(0, |p| CLASSICAL_WEIGHTS[p].s() as i32);
         while let Some(from) = attacker {
             let mut mover = board.piece_unchecked(from);
             // check for a pawn promo during capture
@@ -87,15 +95,12 @@ impl See {
             occ -= from.as_bb();
             attacker_color = attacker_color.flip_side();
 
-            // xray attackers
-            // we move some pieces from xray into attackers - these are all line pieces
-            // alternatively use attacked by bishops & B&Q and attacked-by-rooks & R & Q and move them into attackers
-            for sq in (attackers_xray & board.color(attacker_color)).squares() {
-                if bb.strictly_between(sq, mv.to()).disjoint(occ) {
-                    attackers_xray -= sq.as_bb();
-                    attackers_bw |= sq.as_bb();
-                }
-            }
+            // xray attackers - line pieces behind the one just moved now attack the square
+            attackers_bw |= occ
+                & (bb.xray_attacks(attacker_color, Piece::Bishop, mv.to(), occ | from.as_bb(), from.as_bb())
+                    & board.bishops_or_queens()
+                    | bb.xray_attacks(attacker_color, Piece::Rook, mv.to(), occ | from.as_bb(), from.as_bb())
+                        & board.rooks_or_queens());
 
             attacker = board.least_valuable_piece(attackers_bw & board.color(attacker_color));
             if mover == Piece::King && attacker.is_some() {
@@ -250,6 +255,25 @@ mod tests {
         let mv = b.parse_san_move("Qxb8")?;
         let see_value = see.eval_move_see(&b, mv);
         assert_eq!(see_value, 2 * Piece::Rook.centipawns() - Piece::Queen.centipawns());
+
+        // quiet moves only lose material, by moving onto an attacked square
+        let b = Board::parse_diagram(
+            r"
+            .......k
+            ........
+            ...p....
+            ........
+            ..N.....
+            ........
+            ........
+            K....... w - - 1 1",
+        )?;
+        let see = See::default();
+        let mv = b.parse_san_move("Ne5")?;
+        assert_eq!(see.eval_move_see(&b, mv), 0);
+        assert_eq!(see.eval_move_swap(&b, mv), -Piece::Knight.centipawns());
+        let mv = b.parse_san_move("Nd2")?;
+        assert_eq!(see.eval_move_swap(&b, mv), 0);
         Ok(())
     }
 
@@ -0,0 +1,292 @@
+use std::io::Write;
+use std::path::Path;
+
+use odonata_base::epd::EpdReader;
+use odonata_base::other::{Outcome, Phase, Phaser};
+use odonata_base::pgn::Pgn;
+use odonata_base::prelude::*;
+use strum_macros::{Display, EnumString};
+
+use crate::eval::evaluation::Evaluation;
+use crate::eval::feature::Feature;
+use crate::eval::scoring::{Scorer, Softcoded};
+use crate::eval::weight::WeightOf;
+
+/// A training position: its hce feature counts (white minus black), its phase, and the
+/// result of the game it came from (1, 0.5 or 0 from white's point of view).
+///
+/// The hce is linear in its weights, so the eval (and its gradient) for any weights follows
+/// directly from the counts, without evaluating the board again.
+#[derive(Clone, Debug)]
+pub struct TuningPosition {
+    counts: Vec<(Feature, i32)>,
+    phase:  Phase,
+    result: f64,
+}
+
+#[derive(Default)]
+struct FeatureCounts(Vec<(Feature, i32)>);
+
+impl Scorer<f64> for FeatureCounts {
+    fn accum(&mut self, f: Feature, w: i32, b: i32) {
+        if w != b {
+            self.0.push((f, w - b));
+        }
+    }
+
+    fn total(&self) -> WeightOf<f64> {
+        unreachable!();
+    }
+}
+
+impl TuningPosition {
+    pub fn new(b: &Board, result: f64, phaser: &Phaser) -> Self {
+        let mut counts = FeatureCounts::default();
+        Evaluation.eval(b, &mut counts);
+        Self {
+            counts: counts.0,
+            phase: b.phase(phaser),
+            result,
+        }
+    }
+
+    /// centipawns from white's point of view, as the hce would score it with weights `wts`
+    pub fn eval(&self, wts: &[WeightOf<f64>]) -> f64 {
+        self.counts
+            .iter()
+            .map(|&(f, n)| n as f64 * wts[f.index()].interpolate(self.phase))
+            .sum()
+    }
+
+    /// 1, 0.5 or 0 from white's point of view, or None if the game was unfinished
+    fn result_of(outcome: Outcome) -> Option<f64> {
+        match outcome.winning_color() {
+            Some(c) => Some(c.chooser_wb(1.0, 0.0)),
+            None if outcome.is_draw() => Some(0.5),
+            None => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString)]
+pub enum Optimizer {
+    Sgd,
+    Adam,
+}
+
+/// Texel tuning of the hce weights: minimise the mean squared error between game results and
+/// the eval mapped to an expected score by `sigmoid(eval, k)`. The scaling `k` is first
+/// fitted to the starting weights (unless given), so that the tuned weights stay in
+/// centipawns, then every weight is fitted by (full batch) gradient descent.
+///
+/// See https://www.chessprogramming.org/Texel%27s_Tuning_Method
+#[derive(Clone, Debug)]
+pub struct Tuner {
+    pub optimizer:     Optimizer,
+    pub learning_rate: f64,
+    pub epochs:        usize,
+    pub k:             Option<f64>,
+    pub skip_plies:    usize, // opening plies of each pgn game, which are mostly book
+    pub positions:     Vec<TuningPosition>,
+}
+
+impl Default for Tuner {
+    fn default() -> Self {
+        Self {
+            optimizer:     Optimizer::Adam,
+            learning_rate: 1.0,
+            epochs:        1000,
+            k:             None,
+            skip_plies:    8,
+            positions:     Vec::new(),
+        }
+    }
+}
+
+impl Tuner {
+    /// positions tagged with the game result, as `c9 "1-0";` or `result "1-0";`
+    pub fn load_epd(&mut self, file: impl AsRef<Path>, phaser: &Phaser) -> Result<usize> {
+        let before = self.positions.len();
+        for epd in EpdReader::open(&file)?.epds() {
+            let epd = epd?;
+            let Some(result) = epd.tag("c9").or(epd.tag("result")) else {
+                anyhow::bail!("no result (c9) tag for {}", epd.to_epd());
+            };
+            let outcome = Outcome::try_from_pgn(result).with_context(|| epd.to_epd())?;
+            if let Some(result) = TuningPosition::result_of(outcome) {
+                self.positions.push(TuningPosition::new(&epd.board(), result, phaser));
+            }
+        }
+        Ok(self.positions.len() - before)
+    }
+
+    /// every quiet position (not in check) of finished games, after the opening plies
+    pub fn load_pgn(&mut self, file: impl AsRef<Path>, phaser: &Phaser) -> Result<usize> {
+        let before = self.positions.len();
+        for pgn in Pgn::from_file(&file)? {
+            let Some(result) = TuningPosition::result_of(pgn.outcome()) else {
+                continue;
+            };
+            for pos in pgn.positions().iter().skip(self.skip_plies) {
+                let b = pos.board();
+                if !b.is_in_check(b.turn()) {
+                    self.positions.push(TuningPosition::new(b, result, phaser));
+                }
+            }
+        }
+        Ok(self.positions.len() - before)
+    }
+
+    #[inline]
+    pub fn sigmoid(cp: f64, k: f64) -> f64 {
+        1.0 / (1.0 + 10_f64.powf(-k * cp / 400.0))
+    }
+
+    pub fn error(&self, wts: &[WeightOf<f64>], k: f64) -> f64 {
+        let sum: f64 = self
+            .positions
+            .iter()
+            .map(|p| (p.result - Self::sigmoid(p.eval(wts), k)).powi(2))
+            .sum();
+        sum / self.positions.len().max(1) as f64
+    }
+
+    /// the k minimising the error for the weights, by golden section search
+    pub fn search_k(&self, wts: &[WeightOf<f64>]) -> f64 {
+        let ratio = (5_f64.sqrt() - 1.0) / 2.0;
+        let (mut lo, mut hi) = (0.01, 4.0);
+        while hi - lo > 1e-4 {
+            let k1 = hi - ratio * (hi - lo);
+            let k2 = lo + ratio * (hi - lo);
+            if self.error(wts, k1) < self.error(wts, k2) {
+                hi = k2;
+            } else {
+                lo = k1;
+            }
+        }
+        (lo + hi) / 2.0
+    }
+
+    /// of the error, for the start and end game weights of every feature
+    pub fn gradient(&self, wts: &[WeightOf<f64>], k: f64) -> Vec<WeightOf<f64>> {
+        let mut grad = vec![WeightOf::<f64>::zero(); wts.len()];
+        let n = self.positions.len().max(1) as f64;
+        for p in &self.positions {
+            let sig = Self::sigmoid(p.eval(wts), k);
+            // d/d(eval) of (result - sig)^2
+            let d = -2.0 * (p.result - sig) * sig * (1.0 - sig) * k * 10_f64.ln() / 400.0 / n;
+            let (s, e) = ((100 - p.phase.0) as f64 / 100.0, p.phase.0 as f64 / 100.0);
+            for &(f, count) in &p.counts {
+                grad[f.index()][0] += d * count as f64 * s;
+                grad[f.index()][1] += d * count as f64 * e;
+            }
+        }
+        grad
+    }
+
+    pub fn tune(&self, initial: &Softcoded<f64>) -> Result<Softcoded<f64>> {
+        anyhow::ensure!(!self.positions.is_empty(), "no positions to tune with");
+        let mut wts = initial.wts.to_vec();
+        let k = self.k.unwrap_or_else(|| self.search_k(&wts));
+        let (n, error) = (self.positions.len(), self.error(&wts, k));
+        info!(target: "tune", "tuning {n} positions with k = {k:.4}, error {error:.6}");
+
+        let (beta1, beta2, eps) = (0.9, 0.999, 1e-8);
+        let mut m = vec![[0.0; 2]; wts.len()];
+        let mut v = vec![[0.0; 2]; wts.len()];
+        for epoch in 1..=self.epochs {
+            let grad = self.gradient(&wts, k);
+            for i in 0..wts.len() {
+                for j in 0..2 {
+                    let g = grad[i][j];
+                    let step = match self.optimizer {
+                        Optimizer::Sgd => g,
+                        Optimizer::Adam => {
+                            m[i][j] = beta1 * m[i][j] + (1.0 - beta1) * g;
+                            v[i][j] = beta2 * v[i][j] + (1.0 - beta2) * g * g;
+                            let m_hat = m[i][j] / (1.0 - beta1.powi(epoch as i32));
+                            let v_hat = v[i][j] / (1.0 - beta2.powi(epoch as i32));
+                            m_hat / (v_hat.sqrt() + eps)
+                        }
+                    };
+                    wts[i][j] -= self.learning_rate * step;
+                }
+            }
+            if epoch % 100 == 0 || epoch == self.epochs {
+                info!(target: "tune", "epoch {epoch} error {:.6}", self.error(&wts, k));
+            }
+        }
+        let mut tuned = Softcoded::default();
+        tuned.wts.copy_from_slice(&wts);
+        Ok(tuned)
+    }
+
+    /// as toml (loadable as an hce_file), or for a `.rs` file as a hardcoded weights array
+    pub fn write_weights(wts: &Softcoded<f64>, file: impl AsRef<Path>) -> Result<()> {
+        let file = file.as_ref();
+        let mut w = std::io::BufWriter::new(fs_err::File::create(file)?);
+        if file.extension().is_some_and(|ext| ext == "rs") {
+            writeln!(w, "// generated by odonata tune\n")?;
+            writeln!(w, "pub const WTS: [WeightOf<f64>; {}] = [", Feature::len())?;
+            for f in Feature::iter() {
+                let wt = wts.wts[f.index()];
+                writeln!(w, "    WeightOf({:.2}, {:.2}), // {f}", wt.s(), wt.e())?;
+            }
+            writeln!(w, "];")?;
+        } else {
+            write!(w, "{}", toml::to_string(wts)?)?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::catalog::Catalog;
+    use test_log::test;
+
+    use super::*;
+    use crate::eval::hce::Hce;
+
+    #[test]
+    fn test_tuner() -> Result<()> {
+        let hce = Hce::default();
+        let wts = hce.soft_coded_f64();
+
+        // the counts reproduce the hce's own eval
+        let b = Catalog::bratko_kopec()[0].board();
+        let p = TuningPosition::new(&b, 1.0, &hce.phaser);
+        let cp = hce.w_eval_no_cache(&b).as_i16() as f64 * b.turn().chooser_wb(1.0, -1.0);
+        assert!((p.eval(&wts.wts) - cp).abs() <= 1.0, "{} {cp}", p.eval(&wts.wts));
+
+        // white wins the white-favoured positions, and a gradient step reduces the error
+        let mut tuner = Tuner {
+            epochs: 5,
+            k: Some(1.0),
+            ..Tuner::default()
+        };
+        for epd in Catalog::bratko_kopec() {
+            let b = epd.board();
+            let pos = TuningPosition::new(&b, 0.5, &hce.phaser);
+            let result = match pos.eval(&wts.wts) {
+                cp if cp > 50.0 => 1.0,
+                cp if cp < -50.0 => 0.0,
+                _ => 0.5,
+            };
+            tuner.positions.push(TuningPosition { result, ..pos });
+        }
+        let tuned = tuner.tune(wts)?;
+        assert!(tuner.error(&tuned.wts, 1.0) < tuner.error(&wts.wts, 1.0));
+        assert!(tuner.search_k(&wts.wts) > 0.01);
+
+        let grad = tuner.gradient(&wts.wts, 1.0);
+        assert_eq!(grad.len(), Feature::len());
+
+        let file = odonata_base::infra::resources::relative_path("ext/output/tmp/tuned.toml");
+        Tuner::write_weights(&tuned, &file)?;
+        let reloaded = Softcoded::<f64>::load(&file)?;
+        assert!((reloaded.wts[0].s() - tuned.wts[0].s()).abs() < 0.01);
+        Ok(())
+    }
+}
@@ -114,6 +114,8 @@ impl<T: Copy> Vector<T> {
 }
 
 impl<T> Vector<T> {
+    pub const CAPACITY: usize = N;
+
     #[allow(clippy::len_without_is_empty)]
     #[inline(always)]
     pub fn len(&self) -> usize {
@@ -1,4 +1,3 @@
-
 #[macro_use]
 extern crate tracing;
 
@@ -11,5 +10,11 @@ extern crate test_log;
 pub mod book;
 pub mod cache;
 pub mod comms;
+pub mod engine;
 pub mod eval;
+pub mod pool;
 pub mod search;
+pub mod tb;
+
+pub use crate::engine::{Engine, EngineConfig, QuickAssessment};
+pub use crate::pool::{EnginePool, SessionId};
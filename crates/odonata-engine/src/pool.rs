@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Context};
+use odonata_base::epd::Epd;
+use odonata_base::prelude::*;
+use serde_json::{json, Value};
+
+use crate::comms::eval_history::EvalHistory;
+use crate::comms::session::SessionState;
+use crate::{Engine, EngineConfig};
+
+/// identifies an engine session within an [`EnginePool`]
+pub type SessionId = u64;
+
+/// Hosts many independent engines in one process, for analysis servers.
+///
+/// Each session owns its own [`Engine`] (transposition table, eval caches and move ordering
+/// heuristics), so searches in different sessions neither share nor disturb each other's state
+/// and may run concurrently. Sessions are addressed by id, either directly or through
+/// [`EnginePool::handle_json_rpc`].
+///
+/// Engines share no mutable state through statics. Those remaining are precomputed tables
+/// (attacks, magics, zobrist and polyglot keys), compiled regexes, thread locals of the
+/// search threads (the movegen shuffle seed), the process-wide logging set by Debug_Log_File,
+/// and the diagnostic metrics totals of `metrics` (or debug) builds. The hce's lazily
+/// converted weights are per engine, and UCI_Chess960 notation is held by the uci server.
+pub struct EnginePool {
+    config:           EngineConfig,
+    pub max_sessions: usize,
+    next_id:          AtomicU64,
+    sessions:         Mutex<BTreeMap<SessionId, Arc<Mutex<Engine>>>>,
+    states:           Mutex<BTreeMap<SessionId, SessionState>>,
+    eval_history:     Option<Arc<Mutex<EvalHistory>>>, // of the uci engine's game, when hosted by one
+}
+
+impl Default for EnginePool {
+    fn default() -> Self {
+        Self::new(EngineConfig::default())
+    }
+}
+
+impl fmt::Debug for EnginePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EnginePool")
+            .field("max_sessions", &self.max_sessions)
+            .field("sessions", &self.session_ids())
+            .finish()
+    }
+}
+
+impl EnginePool {
+    /// every session's engine is created from `config`
+    pub fn new(config: EngineConfig) -> Self {
+        Self {
+            config,
+            max_sessions: 64,
+            next_id: AtomicU64::new(1),
+            sessions: Mutex::default(),
+            states: Mutex::default(),
+            eval_history: None,
+        }
+    }
+
+    pub fn with_eval_history(mut self, eval_history: Arc<Mutex<EvalHistory>>) -> Self {
+        self.eval_history = Some(eval_history);
+        self
+    }
+
+    pub fn open(&self) -> Result<SessionId> {
+        if self.len() >= self.max_sessions {
+            bail!("engine pool full ({} sessions)", self.max_sessions);
+        }
+        let engine = Engine::with_config(self.config.clone())?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.sessions.lock().unwrap().insert(id, Arc::new(Mutex::new(engine)));
+        self.states.lock().unwrap().insert(id, SessionState::default());
+        Ok(id)
+    }
+
+    pub fn close(&self, id: SessionId) -> Result<()> {
+        let engine = self
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .ok_or_else(|| anyhow!("no such session {id}"))?;
+        self.states.lock().unwrap().remove(&id);
+        engine.lock().unwrap().threaded_search_mut().search_stop();
+        Ok(())
+    }
+
+    pub fn with_state<R>(&self, id: SessionId, f: impl FnOnce(&mut SessionState) -> R) -> Result<R> {
+        let mut states = self.states.lock().unwrap();
+        let state = states.get_mut(&id).ok_or_else(|| anyhow!("no such session {id}"))?;
+        Ok(f(state))
+    }
+
+    /// the pool lock is released before the caller locks the engine, so a long search
+    /// in one session does not block other sessions
+    pub fn session(&self, id: SessionId) -> Result<Arc<Mutex<Engine>>> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("no such session {id}"))
+    }
+
+    pub fn session_ids(&self) -> Vec<SessionId> {
+        self.sessions.lock().unwrap().keys().copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// handles a single JSON-RPC 2.0 request, returning the response line
+    ///
+    /// methods are `open`, `close`, `sessions`, `new_game`, `set_option`, `static_eval`, `search`,
+    /// `mark` (bookmarking a position with a `note`), `bookmarks`, `export_bookmarks` (as EPD)
+    /// and `eval_history` (of the hosting uci engine's current game).
+    /// Session methods take a `session` param, and positions are given as a `fen` param
+    /// (defaulting to the starting position). `search` takes a `tc` param such as `depth=10`.
+    pub fn handle_json_rpc(&self, request: &str) -> String {
+        let (id, result) = match serde_json::from_str::<Value>(request) {
+            Ok(req) => (req["id"].clone(), self.dispatch(&req)),
+            Err(e) => (Value::Null, Err(anyhow!(e).context("parse error"))),
+        };
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": -32000, "message": format!("{e:#}")}}),
+        };
+        response.to_string()
+    }
+
+    fn dispatch(&self, req: &Value) -> Result<Value> {
+        let method = req["method"].as_str().context("missing method")?;
+        let params = &req["params"];
+        let session = || -> Result<Arc<Mutex<Engine>>> {
+            let id = params["session"].as_u64().context("missing session")?;
+            self.session(id)
+        };
+        let epd = || -> Result<Epd> {
+            match params["fen"].as_str() {
+                Some(fen) => Ok(Epd::from_board(Board::parse_fen(fen)?)),
+                None => Ok(Epd::from_board(Board::starting_pos())),
+            }
+        };
+        let str_param = |name: &str| params[name].as_str().with_context(|| format!("missing {name}"));
+        let session_id = || params["session"].as_u64().context("missing session");
+
+        let result = match method {
+            "open" => json!(self.open()?),
+            "close" => {
+                let id = params["session"].as_u64().context("missing session")?;
+                self.close(id)?;
+                Value::Null
+            }
+            "sessions" => json!(self.session_ids()),
+            "new_game" => {
+                session()?.lock().unwrap().new_game();
+                Value::Null
+            }
+            "set_option" => {
+                session()?
+                    .lock()
+                    .unwrap()
+                    .set_option(str_param("name")?, str_param("value")?)?;
+                Value::Null
+            }
+            "static_eval" => json!(session()?.lock().unwrap().static_eval(epd()?)?),
+            "search" => {
+                let tc = str_param("tc")?.parse::<TimeControl>()?;
+                let response = session()?.lock().unwrap().search(epd()?, tc)?;
+                json!({
+                    "bestmove": response.supplied_move.to_uci(),
+                    "depth": response.depth,
+                    "nodes": response.nodes,
+                    "pv": response.pv().to_uci(),
+                    "score": response.score(),
+                })
+            }
+            "mark" => {
+                let (epd, note) = (epd()?, str_param("note")?);
+                json!(self.with_state(session_id()?, |state| state.mark_epd(epd, note))?)
+            }
+            "bookmarks" => self.with_state(session_id()?, |state| {
+                let bookmarks = state.bookmarks().iter().map(|bm| {
+                    json!({
+                        "fen": bm.epd.board().to_fen(),
+                        "note": bm.note,
+                    })
+                });
+                json!(bookmarks.collect::<Vec<_>>())
+            })?,
+            "export_bookmarks" => json!(self.with_state(session_id()?, |state| state.to_epd())?),
+            "eval_history" => match &self.eval_history {
+                Some(history) => json!(history.lock().unwrap().points()),
+                None => bail!("no eval history outside of a uci session"),
+            },
+            _ => bail!("unknown method '{method}'"),
+        };
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_engine_pool() {
+        let pool = EnginePool::default();
+        let s1 = pool.open().unwrap();
+        let s2 = pool.open().unwrap();
+        assert_ne!(s1, s2);
+        assert_eq!(pool.session_ids(), vec![s1, s2]);
+
+        // separate engines, so separate transposition tables
+        let e1 = pool.session(s1).unwrap();
+        let e2 = pool.session(s2).unwrap();
+        assert!(!Arc::ptr_eq(&e1, &e2));
+
+        thread::scope(|scope| {
+            for e in [e1, e2] {
+                scope.spawn(move || {
+                    let res = e
+                        .lock()
+                        .unwrap()
+                        .search(Epd::from_board(Board::starting_pos()), TimeControl::Depth(4));
+                    assert!(res.unwrap().supplied_move().is_ok());
+                });
+            }
+        });
+
+        pool.close(s1).unwrap();
+        assert!(pool.close(s1).is_err());
+        assert!(pool.session(s1).is_err());
+        assert_eq!(pool.len(), 1);
+
+        pool.close(s2).unwrap();
+        let mut pool = pool;
+        pool.max_sessions = 1;
+        pool.open().unwrap();
+        assert!(pool.open().is_err());
+    }
+
+    #[test]
+    fn test_concurrent_options() {
+        let pool = EnginePool::default();
+        let (s1, s2) = (pool.open().unwrap(), pool.open().unwrap());
+        let (e1, e2) = (pool.session(s1).unwrap(), pool.session(s2).unwrap());
+        e1.lock().unwrap().set_option("MultiPV", "3").unwrap();
+        e2.lock().unwrap().set_option("Hash", "2").unwrap();
+
+        let responses = thread::scope(|scope| {
+            let searches = [&e1, &e2].map(|engine| {
+                scope.spawn(move || {
+                    let epd = Epd::from_board(Board::starting_pos());
+                    engine.lock().unwrap().search(epd, TimeControl::Depth(5)).unwrap()
+                })
+            });
+            searches.map(|h| h.join().unwrap())
+        });
+        assert_eq!(responses[0].multi_pv.len(), 3);
+        assert_eq!(responses[1].multi_pv.len(), 1);
+
+        let (mut e1, mut e2) = (e1.lock().unwrap(), e2.lock().unwrap());
+        assert_eq!(e1.threaded_search_mut().search.tt.mb, 8);
+        assert_eq!(e2.threaded_search_mut().search.tt.mb, 2);
+        assert_eq!(e2.threaded_search_mut().search.controller.multi_pv, 1);
+    }
+
+    #[test]
+    fn test_json_rpc() {
+        let pool = EnginePool::default();
+        let resp: Value =
+            serde_json::from_str(&pool.handle_json_rpc(r#"{"jsonrpc":"2.0","id":1,"method":"open"}"#)).unwrap();
+        assert_eq!(resp["id"], 1);
+        let session = resp["result"].as_u64().unwrap();
+
+        let req = json!({"jsonrpc":"2.0","id":2,"method":"search","params":{"session":session,"tc":"depth=3"}});
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert_eq!(resp["id"], 2);
+        assert!(resp["result"]["bestmove"].is_string(), "{resp}");
+
+        let req = json!({"jsonrpc":"2.0","id":3,"method":"static_eval","params":{"session":99}});
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert_eq!(resp["error"]["message"], "no such session 99");
+
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc("{not json")).unwrap();
+        assert!(resp["error"].is_object());
+        assert_eq!(resp["id"], Value::Null);
+
+        let req = json!({"jsonrpc":"2.0","id":4,"method":"eval_history"});
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert!(resp["error"].is_object());
+        let pool = pool.with_eval_history(Arc::default());
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert_eq!(resp["result"], json!([]));
+
+        let req =
+            json!({"jsonrpc":"2.0","id":5,"method":"mark","params":{"session":session,"note":"critical zugzwang"}});
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert_eq!(resp["result"], 1);
+        let req = json!({"jsonrpc":"2.0","id":6,"method":"bookmarks","params":{"session":session}});
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert_eq!(resp["result"][0]["note"], "critical zugzwang");
+        let req = json!({"jsonrpc":"2.0","id":7,"method":"export_bookmarks","params":{"session":session}});
+        let resp: Value = serde_json::from_str(&pool.handle_json_rpc(&req.to_string())).unwrap();
+        assert!(
+            resp["result"].as_str().unwrap().contains("c0 \"critical zugzwang\""),
+            "{resp}"
+        );
+    }
+}
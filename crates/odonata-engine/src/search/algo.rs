@@ -17,16 +17,24 @@ use odonata_base::prelude::*;
 use odonata_base::trace::logger::LoggingSystem;
 use regex::Regex;
 
+use super::capture_history::CaptureHistory;
+use super::continuation_history::ContinuationHistory;
 use super::counter_move::CounterMove;
 use super::lmp::Lmp;
 use super::mate_distance::MateDistance;
 use super::qs::Qs;
 use super::reverse_futility::ReverseFutility;
+use crate::book::experience::ExperienceBook;
+use crate::book::learning::Learning;
 use crate::book::opening_book::OpeningBook;
+use crate::book::prep::Prep;
 use crate::cache::tt2::TranspositionTable2;
-use crate::eval::Eval;
+use crate::eval::pawn_solver::PawnSolver;
 use crate::eval::recognizer::Recognizer;
+use crate::eval::Eval;
+use crate::search::analysis_tree::AnalysisTree;
 use crate::search::aspiration::Aspiration;
+use crate::search::best_move_change::RootRaise;
 use crate::search::controller::Controller;
 use crate::search::extensions::Extensions;
 use crate::search::futility::Futility;
@@ -37,11 +45,15 @@ use crate::search::lmr::Lmr;
 use crate::search::move_orderer::MoveOrderer;
 use crate::search::move_time_estimator::MoveTimeEstimator;
 use crate::search::nmp::NullMovePruning;
+use crate::search::pruning_monitor::PruningMonitor;
+use crate::search::pv_cache::PvCache;
 use crate::search::pvs::Pvs;
 use crate::search::razor::Razor;
 use crate::search::restrictions::Restrictions;
 use crate::search::search_results::Response;
 use crate::search::trail::Trail;
+use crate::search::volatility::Volatility;
+use crate::tb::Tablebase;
 
 #[derive(Clone)]
 pub struct Callback(pub Arc<dyn Fn(&Info) + Send + Sync>);
@@ -78,14 +90,25 @@ pub struct Search {
     pub history:      HistoryHeuristic,
     pub razor:        Razor,
     pub recognizer:   Recognizer,
+    pub pawn_solver:  PawnSolver,
     pub aspiration:   Aspiration,
     pub clock:        Clock,
     pub controller:   Controller,
     pub lmp:          Lmp,
     pub qs:           Qs,
     pub counter_move: CounterMove,
+    pub cont_history: ContinuationHistory,
+    pub capt_history: CaptureHistory,
     pub opening_book: OpeningBook,
-
+    pub learning:     Learning,
+    pub experience:   ExperienceBook,
+    pub pv_cache:     PvCache,
+    pub prep:         Prep,
+    pub volatility:   Volatility,
+    pub tablebase:    Tablebase,
+
+    pub pruning_monitor:   PruningMonitor,
+    pub analysis_tree:     AnalysisTree,
     pub callback:          Callback,
     pub eval:              Eval,
     pub position:          Position,
@@ -96,6 +119,10 @@ pub struct Search {
     pub clock_checks:      u64,
     pub current_variation: Variation,
     pub restrictions:      Restrictions,
+    pub root_raise:        Option<RootRaise>,
+    pub path_draws:        u64, // count of path-specific draws (repetition, 50-move) seen by the search
+    pub tt_probes:         u64, // transposition table probes by the search since the new game
+    pub tt_hits:           u64,
 }
 
 pub struct Query {
@@ -117,8 +144,17 @@ impl Configurable for Search {
         self.eval.set(p.get("eval"))?;
         self.tt.set(p.get("tt"))?;
         self.razor.set(p.get("razor"))?;
+        self.volatility.set(p.get("volatility"))?;
+        self.pawn_solver.set(p.get("pawn_solver"))?;
+        self.pruning_monitor.set(p.get("pruning_monitor"))?;
         self.mate_dist.set(p.get("mate_dist"))?;
         self.opening_book.set(p.get("opening_book"))?;
+        self.learning.set(p.get("learning"))?;
+        self.experience.set(p.get("experience"))?;
+        self.pv_cache.set(p.get("pv_cache"))?;
+        self.analysis_tree.set(p.get("analysis_tree"))?;
+        self.prep.set(p.get("prep"))?;
+        self.tablebase.set(p.get("tablebase"))?;
         self.aspiration.set(p.get("aspiration"))?;
         self.ext.set(p.get("ext"))?;
         self.pvs.set(p.get("pvs"))?;
@@ -126,6 +162,8 @@ impl Configurable for Search {
         self.rev_fut.set(p.get("rev_fut"))?;
         self.history.set(p.get("history"))?;
         self.counter_move.set(p.get("counter_move"))?;
+        self.cont_history.set(p.get("cont_history"))?;
+        self.capt_history.set(p.get("capt_history"))?;
         self.repetition.set(p.get("repetition"))?;
         self.clock.set(p.get("clock"))?;
         self.mte.set(p.get("mte"))?;
@@ -190,7 +228,7 @@ impl Search {
         self.set_timing_method(t.clone());
         self.set_position(epd.clone());
         self.restrictions.search_moves = opts.root_moves;
-        let mut pos = Position::from_played_moves(epd.setup_board(), epd.played().clone());
+        let mut pos = epd.into_pos();
         self.run_search(&mut pos);
         info!(target: "eng", " <- results {res}", res = self.response);
         Ok(self.response.clone())
@@ -200,11 +238,43 @@ impl Search {
         let mut map: IndexMap<&str, &str> = IndexMap::new();
         let mb = format!("spin default {} min 0 max 4000", self.tt.mb);
         let mpv = format!("spin default {} min 1 max 64", self.controller.multi_pv);
+        let info_interval = format!("spin default {} min 0 max 10000", self.controller.info_interval_ms);
         let book_file = format!(
             "string default {}",
             UciString::to_uci(&self.opening_book.book_file.to_string_lossy())
         );
+        let book_depth = format!("spin default {} min 0 max 200", self.opening_book.max_ply);
+        let book_verify_nodes = format!("spin default {} min 0 max 10000000", self.opening_book.verify_nodes);
+        let learning_file = format!(
+            "string default {}",
+            UciString::to_uci(&self.learning.learning_file.to_string_lossy())
+        );
+        let experience_file = format!(
+            "string default {}",
+            UciString::to_uci(&self.experience.experience_file.to_string_lossy())
+        );
+        let prep_file = format!(
+            "string default {}",
+            UciString::to_uci(&self.prep.prep_file.to_string_lossy())
+        );
+        let pv_cache_file = format!(
+            "string default {}",
+            UciString::to_uci(&self.pv_cache.pv_file.to_string_lossy())
+        );
+        let analysis_tree = format!("check default {}", self.analysis_tree.enabled);
+        let eval_file = format!(
+            "string default {}",
+            UciString::to_uci(&self.eval.nnue_file.to_string_lossy())
+        );
         let opponent = format!("string default {}", UciString::to_uci(""));
+        let handicap_optimism = format!("spin default {} min 0 max 200", self.eval.handicap.optimism);
+        let handicap_blindness = format!("spin default {} min 0 max 100", self.eval.handicap.threat_blindness);
+        let handicap_noise = format!("spin default {} min 0 max 1000", self.eval.handicap.noise);
+        let syzygy_path = format!("string default {}", UciString::to_uci(&self.tablebase.path));
+        let syzygy_probe_depth = format!("spin default {} min 1 max 100", self.tablebase.probe_depth);
+        let syzygy_probe_limit = format!("spin default {} min 0 max 7", self.tablebase.probe_limit);
+        let syzygy_50_move_rule = format!("check default {}", self.tablebase.use_50_move_rule);
+        let show_wdl = format!("check default {}", self.controller.show_wdl);
         let debug_log_file = format!("string default {}", UciString::to_uci(""));
 
         map.insert("Hash", &mb);
@@ -212,17 +282,39 @@ impl Search {
         map.insert("Debug_Log_File", &debug_log_file);
         map.insert("Ponder", "check default false");
         map.insert("UCI_AnalyseMode", "check default false");
+        map.insert("UCI_ShowWDL", &show_wdl);
         map.insert("Analyse_Mode", "check default false");
         map.insert("UCI_Opponent", &opponent);
         // map.insert("nodestime", "");
         map.insert("OwnBook", "check default false");
-        map.insert("Book File", &book_file);
+        map.insert("BookFile", &book_file);
         map.insert("Best Book Move", "check default true");
+        map.insert("BookDepth", &book_depth);
+        map.insert("BookVerifyNodes", &book_verify_nodes);
+        map.insert("LearningFile", &learning_file);
+        map.insert("ExperienceFile", &experience_file);
+        map.insert("Prep File", &prep_file);
+        map.insert("PV Cache File", &pv_cache_file);
+        map.insert("Analysis Tree", &analysis_tree);
+        map.insert("EvalFile", &eval_file);
+        map.insert("HandicapOptimism", &handicap_optimism);
+        map.insert("HandicapThreatBlindness", &handicap_blindness);
+        map.insert("HandicapNoise", &handicap_noise);
+        map.insert("SyzygyPath", &syzygy_path);
+        map.insert("SyzygyProbeDepth", &syzygy_probe_depth);
+        map.insert("SyzygyProbeLimit", &syzygy_probe_limit);
+        map.insert("Syzygy50MoveRule", &syzygy_50_move_rule);
         map.insert("Clear Hash", "button");
+        map.insert("DebugTree", "check default false");
+        map.insert("Search Summary", "check default false");
+        map.insert("Info Interval", &info_interval);
         if Metrics::metrics_enabled() {
-            map.insert("Features", "string default [perft,eval,board,d,metrics,compiler]");
+            map.insert(
+                "Features",
+                "string default [perft,eval,board,d,ttentry,metrics,compiler]",
+            );
         } else {
-            map.insert("Features", "string default [perft,eval,board,d,compiler]");
+            map.insert("Features", "string default [perft,eval,board,d,ttentry,compiler]");
         }
         // map
 
@@ -233,24 +325,74 @@ impl Search {
         trace!(target: "eng", "-> trying... search.set_option('{key}' = '{value}')");
         match key {
             "Features" => {}
-            "Clear Hash" => self.set_state(State::NewGame),
+            "Clear Hash" => self.tt.clear_hash(),
             "Hash" => self.tt.mb = value.parse()?,
             "MultiPV" => self.controller.multi_pv = value.parse()?,
+            "Info Interval" => self.controller.info_interval_ms = value.parse()?,
             "Debug_Log_File" => LoggingSystem::parse(value)?.apply()?,
             "UCI_AnalyseMode" => self.controller.analyse_mode = value.parse()?,
             "Analyse_Mode" => self.controller.analyse_mode = value.parse()?,
-            "UCI_Opponent" => {}
+            "UCI_ShowWDL" => self.controller.show_wdl = value.parse()?,
+            "UCI_Opponent" => {
+                self.prep.opponent = Prep::parse_uci_opponent(&UciString::parse_uci(value));
+                self.prep.reload()?;
+            }
             "nodestime" => self.mte.set_nodestime(value.parse()?),
             "Ponder" => {} // pondering determined by "go ponder", so no variable to track
             "OwnBook" => {
                 self.opening_book.own_book = value.parse()?;
                 self.opening_book.reload()?;
             }
-            "Book File" => {
+            "BookFile" | "Book File" => {
                 self.opening_book.book_file = PathBuf::from(UciString::parse_uci(value));
                 self.opening_book.reload()?;
             }
             "Best Book Move" => self.opening_book.best_book_line = value.parse()?,
+            "BookDepth" | "BookMaxPly" => self.opening_book.max_ply = value.parse()?,
+            "BookVerifyNodes" => self.opening_book.verify_nodes = value.parse()?,
+            "LearningFile" => {
+                self.learning.save()?;
+                self.learning.learning_file = PathBuf::from(UciString::parse_uci(value));
+                self.learning.reload()?;
+            }
+            "ExperienceFile" => {
+                self.experience.save()?;
+                self.experience.experience_file = PathBuf::from(UciString::parse_uci(value));
+                self.experience.reload()?;
+            }
+            "Prep File" => {
+                self.prep.prep_file = PathBuf::from(UciString::parse_uci(value));
+                self.prep.reload()?;
+            }
+            "PV Cache File" => self.pv_cache.pv_file = PathBuf::from(UciString::parse_uci(value)),
+            "Analysis Tree" => self.analysis_tree.enabled = value.parse()?,
+            "EvalFile" => {
+                // scores in the tt are from the old network
+                self.eval.swap_nnue_file(PathBuf::from(UciString::parse_uci(value)))?;
+                self.tt.clear_hash();
+            }
+            // scores in the tt are from the old evaluation
+            "HandicapOptimism" => {
+                self.eval.handicap.optimism = value.parse()?;
+                self.tt.clear_hash();
+            }
+            "HandicapThreatBlindness" => {
+                self.eval.handicap.threat_blindness = value.parse()?;
+                self.tt.clear_hash();
+            }
+            "HandicapNoise" => {
+                self.eval.handicap.noise = value.parse()?;
+                self.tt.clear_hash();
+            }
+            "SyzygyPath" => {
+                self.tablebase.path = UciString::parse_uci(value);
+                self.tablebase.reload()?;
+            }
+            "SyzygyProbeDepth" => self.tablebase.probe_depth = value.parse()?,
+            "SyzygyProbeLimit" => self.tablebase.probe_limit = value.parse()?,
+            "Syzygy50MoveRule" => self.tablebase.use_50_move_rule = value.parse()?,
+            "DebugTree" => self.controller.debug_tree = value.parse()?,
+            "Search Summary" => self.controller.show_summary = value.parse()?,
             _ => anyhow::bail!("search does not support set option '{key}'"),
         }
 
@@ -295,6 +437,8 @@ impl Component for Search {
                 self.clock_checks = 0;
                 self.max_depth = 0;
                 self.restrictions = Restrictions::default();
+                self.tt_probes = 0;
+                self.tt_hits = 0;
             }
 
             SetPosition => {
@@ -303,7 +447,8 @@ impl Component for Search {
                 self.max_depth = 0;
             }
 
-            StartSearch => {}
+            // an honest eval when analysing
+            StartSearch => self.eval.handicap.suspended = self.controller.analyse_mode,
             EndSearch => {
                 // if self.explainer.show_pv_eval {
                 //     self.response
@@ -334,14 +479,25 @@ impl Component for Search {
         // self.explainer.set_state(s);
 
         self.razor.set_state(s);
+        self.volatility.set_state(s);
         self.recognizer.set_state(s);
+        self.pawn_solver.set_state(s);
+        self.pruning_monitor.set_state(s);
         self.aspiration.set_state(s);
 
         self.controller.set_state(s);
         self.lmp.set_state(s);
         self.counter_move.set_state(s);
+        self.cont_history.set_state(s);
+        self.capt_history.set_state(s);
         self.qs.set_state(s);
         self.opening_book.set_state(s);
+        self.learning.set_state(s);
+        self.experience.set_state(s);
+        self.pv_cache.set_state(s);
+        self.analysis_tree.set_state(s);
+        self.prep.set_state(s);
+        self.tablebase.set_state(s);
     }
 
     fn new_game(&mut self) {
@@ -410,14 +566,17 @@ impl Search {
         &self.current_variation
     }
 
+    /// a node count info, at least each second
     pub fn report_progress(&self) {
-        if self.clock.cumul_nodes_this_thread() % 1_000_000 == 0 && self.clock.cumul_nodes_this_thread() != 0 {
+        let nodes = self.clock.cumul_nodes_this_thread();
+        if nodes % 16_384 == 0 && nodes != 0 && self.controller.is_progress_due() {
             let info = Info {
                 kind: InfoKind::NodeCounts,
                 nodes: Some(self.clock.cumul_nodes_all_threads()),
-                nodes_thread: Some(self.clock.cumul_nodes_this_thread()),
+                nodes_thread: Some(nodes),
                 nps: Some(self.clock.cumul_knps_all_threads() * 1000),
                 hashfull_per_mille: Some(self.tt.hashfull_per_mille()),
+                tbhits: Some(self.tablebase.hits),
                 time_millis: Some(self.clock.elapsed_search().time.as_millis() as u64),
                 ..Default::default()
             };
@@ -444,7 +603,7 @@ impl Search {
         self.repetition.capture_all_prior_positions(&epd);
         self.response.input = epd.clone();
         self.board = epd.board();
-        self.position = Position::from_played_moves(epd.setup_board(), epd.played().clone());
+        self.position = epd.into_pos();
         self
     }
 
@@ -541,6 +700,15 @@ mod tests {
         assert_eq!(search.lmr.cfg.enabled, true);
         search.set(param).unwrap();
         assert_eq!(search.lmr.cfg.enabled, false);
+
+        let param = Param::new("eval.handicap.optimism", "25");
+        search.set(param).unwrap();
+        assert_eq!(search.eval.handicap.optimism, 25);
+        search.set_state(State::StartSearch);
+        assert!(search.eval.handicap.is_active());
+        search.controller.analyse_mode = true;
+        search.set_state(State::StartSearch);
+        assert!(!search.eval.handicap.is_active());
     }
 
     // #[test]
@@ -552,10 +720,10 @@ mod tests {
     #[cfg(feature = "nnue")]
     #[test]
     fn test_search_nnue() {
-        use std::collections::HashMap;
-        let mut map = HashMap::new();
-        map.insert("eval".to_string(), "nnue".to_string());
-        let mut eng = ThreadedSearch::configure(map).unwrap();
+        use crate::search::engine::ThreadedSearch;
+        let mut eng = ThreadedSearch::new();
+        eng.configure([("eval.eval_kind".into(), "Nnue".into())].into())
+            .unwrap();
         let res = eng
             .search(Epd::from_board(Board::starting_pos()), TimeControl::Depth(5))
             .unwrap();
@@ -4,11 +4,14 @@ use odonata_base::domain::score::Score;
 use odonata_base::domain::staticeval::{eval_with_outcome, StaticEval};
 use odonata_base::domain::BoundType;
 use odonata_base::infra::metric::Metrics;
+use odonata_base::infra::paranoid;
 use odonata_base::mv::Move;
-use odonata_base::variation::Variation;
+use odonata_base::paranoid_assert;
 use tracing::{event, Level};
 
-use super::trail::Trail;
+use super::best_move_change::RootRaise;
+use super::pruning_monitor::Pruning;
+use super::trail::{Trail, TreeCrit};
 use crate::cache::tt2::{EvalFromTt, TtNode, TtScore};
 use crate::search::algo::Search;
 use crate::search::qs::RunQs;
@@ -23,11 +26,18 @@ impl Search {
         n: &mut Node,
     ) -> Result<(Score, Event), Event> {
         self.clock_checks = 0;
-        *trail = Trail::new(pos.board().clone());
-        self.current_variation = Variation::new();
+        // seldepth spans the iteration's aspiration re-searches and multi-pv lines
+        let seldepth = trail.selective_depth();
+        trail.reset(pos.board().clone());
+        self.current_variation.clear();
+        self.root_raise = None;
         self.max_depth = 0;
-        trail.clear();
-        // trail.set_tree_crit(self.explainer.tree_crit.clone());
+        trail.set_selective_depth(seldepth);
+        trail.set_tree_crit(TreeCrit {
+            enabled: self.controller.debug_tree,
+            max_ply: self.controller.tree_max_ply,
+            ..TreeCrit::default()
+        });
         debug_assert!(n.alpha < n.beta);
 
         let t = Metrics::timing_start();
@@ -131,6 +141,14 @@ impl Search {
 
         Metrics::incr_node(&n, Event::NodeTotal);
         self.clock.inc_int_nodes();
+        paranoid::check_node(&n);
+        if paranoid::ENABLED && self.clock.cumul_nodes_this_thread() % paranoid::HASH_CHECK_NODES == 0 {
+            paranoid::check_board(pos.board());
+            paranoid_assert!(
+                pos.hash() == pos.board().hash(),
+                "paranoid: position hash stale\n{pos:#?}"
+            );
+        }
         match () {
             _ if n.is_null_move() => Metrics::incr_node(&n, Event::NodeNullMove),
             _ if n.is_razor() => Metrics::incr_node(&n, Event::NodeRazor),
@@ -164,7 +182,14 @@ impl Search {
         let mut bm = None;
         let mut bt = BoundType::UpperAll;
         // we dont draw at root, as otherwise it wont play a move if handed an illegaly (already drawn) position
-        if n.ply > 0 && (self.repetition.is_draw_by_repetition(pos) || pos.board().material().is_insufficient()) {
+        let path_draw = n.ply > 0
+            && (self.repetition.is_draw_by_repetition(pos)
+                || pos.is_draw_rule_fifty() && !pos.board().is_in_check(pos.board().color_us()));
+        if path_draw {
+            // the hash of this position does not capture the draw, so ancestors are flagged for the tt
+            self.path_draws += 1;
+        }
+        if n.ply > 0 && (path_draw || pos.board().material().is_insufficient()) {
             // if n.ply > 0 && pos.board().draw_outcome().is_some() {
             Metrics::incr_node(&n, Event::NodeLeafDraw);
             if n.ply == 0 {
@@ -178,6 +203,13 @@ impl Search {
             // will return a draw score
         }
 
+        if let Some(s) = self.tablebase.probe_node(pos.board(), &n) {
+            Metrics::incr_node(&n, Event::NodeLeafTablebase);
+            trail.terminal(&n, s, Event::NodeLeafTablebase);
+            return Ok((s, Event::NodeLeafTablebase));
+        }
+
+        let path_draws = self.path_draws;
         let mut tt_mv = Move::new_null();
         match self.lookup(trail, pos.board(), &mut n) {
             (Some(ab), None) => {
@@ -195,21 +227,32 @@ impl Search {
             }
             _ => {}
         }
+        // the root isn't probed, but on the first iteration the pv cache may have a hint
+        if n.ply == 0 && tt_mv.is_null() && n.depth <= self.ids.start_ply {
+            tt_mv = self.pv_cache.root_move().unwrap_or_default();
+        }
 
         // static eval
         let eval = self.alphabeta_static_eval(pos, &n);
-
-        if let Some(s) = self.reverse_fut(pos.board(), eval, &n, 0) {
-            trail.prune_node(&n, s, Event::RevFutSuccess);
-            return Ok((s, Event::RevFutSuccess));
-        }
-        if let Some(s) = self.razor_node(trail, last_move, pos, eval, &n)? {
-            trail.prune_node(&n, s, Event::RazorSuccess);
-            return Ok((s, Event::RazorSuccess));
-        }
-        if let Some(s) = self.nmp_node(trail, pos, &n, eval)? {
-            trail.prune_node(&n, s, Event::NmpSuccess);
-            return Ok((s, Event::NmpSuccess));
+        trail.set_eval(&n, eval);
+        let vol = self.volatility.scale_perc(trail.eval_swing(&n), pos.board(), &n);
+
+        if !self.pruning_monitor.is_verifying(n.ply) {
+            if let Some(s) = self.reverse_fut(pos.board(), eval, &n, 0) {
+                self.verify_node_pruning(Pruning::RevFut, trail, pos, &n, last_move, s)?;
+                trail.prune_node(&n, s, Event::RevFutSuccess);
+                return Ok((s, Event::RevFutSuccess));
+            }
+            if let Some(s) = self.razor_node(trail, last_move, pos, eval, vol, &n)? {
+                self.verify_node_pruning(Pruning::Razor, trail, pos, &n, last_move, s)?;
+                trail.prune_node(&n, s, Event::RazorSuccess);
+                return Ok((s, Event::RazorSuccess));
+            }
+            if let Some(s) = self.nmp_node(trail, pos, &n, eval)? {
+                self.verify_node_pruning(Pruning::Nmp, trail, pos, &n, last_move, s)?;
+                trail.prune_node(&n, s, Event::NmpSuccess);
+                return Ok((s, Event::NmpSuccess));
+            }
         }
 
         let mut sorted_moves = self.move_orderer.create_sorted_moves(n, pos.board(), tt_mv, last_move);
@@ -218,6 +261,7 @@ impl Search {
         }
         let mut count = 0;
         let mut quiets = 0;
+        let mut captures = 0;
         while let Some((mt, mv)) = sorted_moves.next_move(pos.board(), self) {
             if self.restrictions.skip_move(n.ply, mv) {
                 continue;
@@ -232,10 +276,16 @@ impl Search {
             if is_quiet {
                 quiets += 1;
             }
+            if mv.is_capture() {
+                captures += 1;
+            }
 
             if bm.is_some() {
-                if let Some(est) = self.can_futility_prune_move(mv, count, mt, before, pos.board(), eval, &n, ext) {
+                if let Some(est) = self.can_futility_prune_move(mv, count, mt, before, pos.board(), eval, vol, &n, ext)
+                {
+                    self.verify_move_pruning(Pruning::Futility, trail, pos, &n, mv, ext)?;
                     // dont actually want to make move - but want to record it
+                    let before = pos.prior_board().unwrap();
                     if self.can_prune_remaining_moves(before, mt, &n) {
                         trail.prune_move(&n, est, mv, Event::FutilitySuccessRemaining);
                         pos.pop_move();
@@ -247,11 +297,14 @@ impl Search {
                         continue;
                     }
                 }
-                if self.can_lmp_move(before, count, is_quiet, quiets, &n, mv) {
+                if self.can_lmp_move(pos.prior_board().unwrap(), count, is_quiet, quiets, &n, mv) {
+                    self.verify_move_pruning(Pruning::Lmp, trail, pos, &n, mv, ext)?;
+                    trail.prune_move(&n, n.alpha, mv, Event::LmpSuccess);
                     pos.pop_move();
                     continue;
                 }
             }
+            let before = pos.prior_board().unwrap(); // re-borrowed, as verifying pruning needs pos mutably
 
             self.repetition.push_move(mv, before);
             self.current_variation.push(mv);
@@ -286,6 +339,7 @@ impl Search {
                     before,
                     pos.board(),
                     eval,
+                    vol,
                     &Node {
                         zw:    SearchType::ZeroWindow,
                         ply:   n.ply + 1,
@@ -310,6 +364,9 @@ impl Search {
                         Metrics::incr_node(&n, Event::SearchZwRd);
                         (s, ev) = self.alphabeta("zwrd", trail, pos, n.new_child().ext(ext - lmr).zw(), mv)?;
                         s = -s;
+                        if s <= n.alpha {
+                            trail.lmr_saved(&n);
+                        }
                         if s > n.alpha {
                             Metrics::incr_node(&n, Event::ReSearchZwFd);
                             (s, ev) = self.alphabeta("zwfd", trail, pos, n.new_child().ext(ext).zw(), mv)?;
@@ -325,6 +382,9 @@ impl Search {
                         Metrics::incr_node(&n, Event::SearchZwRd);
                         (s, ev) = self.alphabeta("fwrd", trail, pos, n.new_child().ext(ext - lmr), mv)?;
                         s = -s;
+                        if s <= n.alpha {
+                            trail.lmr_saved(&n);
+                        }
                         if s > n.alpha {
                             Metrics::incr_node(&n, Event::ReSearchFwFd);
                             (s, ev) = self.alphabeta("fwfd", trail, pos, n.new_child().ext(ext), mv)?;
@@ -365,6 +425,16 @@ impl Search {
                 score = s;
                 category = cat;
                 bm = Some(mv);
+                if n.ply == 0 {
+                    self.root_raise = Some(RootRaise {
+                        mv,
+                        score: s,
+                        move_type: mt,
+                        lmr,
+                        event: cat,
+                        nodes: self.clock.cumul_nodes_this_thread(),
+                    });
+                }
             }
             // if metrics && futility prune with enabled then print.
             if s >= n.beta {
@@ -377,6 +447,9 @@ impl Search {
                     .beta_variation(&n, pos.board(), &self.current_variation, mv, mt);
                 self.counter_move
                     .store(pos.board().color_us(), last_move, mv, &n, pos.board());
+                self.cont_history
+                    .beta_cutoff(&n, pos.board().color_us(), &self.current_variation, mv, mt);
+                self.capt_history.beta_cutoff(&n, pos.board(), mv, captures);
                 self.report_refutation(n.ply);
                 break;
             }
@@ -394,6 +467,9 @@ impl Search {
             } else {
                 trail.ignore_move(&n, s, mv, Event::MoveScoreLow);
                 self.history.duff(&n, pos.board(), mv, mt);
+                self.cont_history
+                    .duff(&n, pos.board().color_us(), &self.current_variation, mv, mt);
+                self.capt_history.duff(&n, pos.board(), mv);
             }
         }
         // if trail.path().len() < 2 {
@@ -433,8 +509,14 @@ impl Search {
                 bt,
                 bm: bm.unwrap_or_default().to_inner(),
                 eval,
+                ghi: self.path_draws != path_draws,
             };
             Metrics::incr_node(&n, Event::TtStoreNode);
+            paranoid_assert!(
+                bm.is_none() || entry.bm.validate(pos.board()).is_ok(),
+                "paranoid: tt entry {entry} has invalid move on {b}",
+                b = pos.board()
+            );
             self.tt.store(pos.board().hash(), entry);
         }
         debug_assert!(
@@ -449,6 +531,7 @@ impl Search {
             bt,
             pos.board(),
         );
+        paranoid::check_score(score, &n);
         Ok((score, category))
     }
 }
@@ -460,7 +543,9 @@ mod tests {
     use odonata_base::catalog::*;
     use odonata_base::domain::staticeval::StaticEval;
     use odonata_base::domain::timecontrol::*;
+    use odonata_base::epd::Epd;
     use odonata_base::other::tags::EpdOps as _;
+    use odonata_base::prelude::*;
     use test_log::test;
 
     use crate::search::engine::ThreadedSearch;
@@ -528,4 +613,56 @@ mod tests {
         }
         Ok(())
     }
+
+    /// mate scores stored in the tt at one ply and probed at another, as when the positions
+    /// along a mating line are searched in turn without clearing the tt
+    #[test]
+    fn test_mate_scores_via_tt() -> Result<()> {
+        let suites = [
+            (Catalog::mate_in_2(), 2, 7),
+            (Catalog::mate_in_3(), 3, 5),
+            (Catalog::mate_in_4(), 4, 13),
+        ];
+        for (positions, mate_in, depth) in suites {
+            for epd in &positions {
+                let mut eng = ThreadedSearch::new();
+                eng.search.tt.allow_truncated_pv = false;
+                let res = eng.search(epd.clone(), TimeControl::Depth(depth))?;
+                assert_eq!(res.score().unwrap().mate_in(), Some(mate_in), "{epd}");
+                let pv = epd.var("pv").unwrap_or_else(|| res.pv());
+
+                // after our move the opponent is mated, and after theirs we mate, a move sooner
+                let b = epd.board().make_move(pv.first().unwrap());
+                let res = eng.search(Epd::from_board(b.clone()), TimeControl::Depth(depth))?;
+                assert_eq!(res.score().unwrap().mate_in(), Some(1 - mate_in), "{epd} then {pv}");
+                let b = b.make_move(pv.second().unwrap());
+                let res = eng.search(Epd::from_board(b), TimeControl::Depth(depth))?;
+                assert_eq!(res.score().unwrap().mate_in(), Some(mate_in - 1), "{epd} then {pv}");
+            }
+        }
+        Ok(())
+    }
+
+    /// winning positions reached by repeating moves, where repetition draws found along one
+    /// path and stored in the tt could be reused along another as 0.00. The positions are
+    /// made up rather than from user reports, so they only guard against the symptom. That
+    /// a flagged draw cuts with `Ghi::Ignore` but not by default is shown by `qs::test_qs_ghi`
+    #[test]
+    fn test_ghi_regressions() -> Result<()> {
+        let cases = [
+            ("8/8/8/4k3/8/8/8/R3K3 w - - 0 1", "Ra2 Kd4 Ra1 Ke5"),
+            ("8/8/8/2k5/8/8/8/3QK3 w - - 0 1", "Qd2 Kc4 Qd1 Kc5"),
+            ("8/5k2/8/8/8/8/1R6/1R2K3 w - - 0 1", "Rb7+ Kf6 Rb2 Kf7"),
+        ];
+        let mut eng = ThreadedSearch::new();
+        for (fen, moves) in cases {
+            let board = Board::parse_fen(fen)?;
+            let epd = Epd::from_var(board.clone(), board.parse_san_variation(moves)?);
+            eng.new_game();
+            let res = eng.search(epd.clone(), TimeControl::Depth(10))?;
+            let score = res.score().unwrap();
+            assert!(score > Score::from_cp(300), "{score} {epd}\n{res:#}");
+        }
+        Ok(())
+    }
 }
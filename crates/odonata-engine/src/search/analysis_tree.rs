@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+
+use odonata_base::domain::BoundType;
+use odonata_base::infra::component::Component;
+use odonata_base::prelude::*;
+
+use crate::cache::tt2::TtSubtreeEntry;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnalysisNode {
+    pub best_move: Move,
+    pub score:     Score, // from the point of view of the side to move
+    pub depth:     Ply,
+    pub searches:  u32, // times the position was the root of a search
+}
+
+/// An analysis session's memory: the best lines and scores of every search, kept by position
+/// hash so that lines reached by different move orders meet.
+///
+/// Each search adds its pv, the positions along it at the search depth less their ply, and
+/// a deeper result replaces a shallower one. Re-visiting a position (directly or via a
+/// transposition) seeds the search with the tree's line, as for the pv cache.
+///
+/// Unlike the pv cache the tree is kept across games, until cleared, and is shared by the
+/// search threads (only the main thread records). Optionally the exact entries of the
+/// transposition table within `tt_ply` plies of the root are kept too, as alternatives to
+/// the pv that would otherwise be overwritten in the table.
+#[derive(Clone, Debug)]
+pub struct AnalysisTree {
+    pub enabled:     bool,
+    pub max_entries: usize,
+    pub max_ply:     Ply, // of lines shown, exported or followed
+    pub tt_ply:      Ply,
+    root:            Arc<Mutex<Option<Board>>>,
+    nodes:           Arc<Mutex<HashMap<Hash, AnalysisNode>>>,
+}
+
+impl Default for AnalysisTree {
+    fn default() -> Self {
+        Self {
+            enabled:     false,
+            max_entries: 100_000,
+            max_ply:     24,
+            tt_ply:      0,
+            root:        Arc::default(),
+            nodes:       Arc::default(),
+        }
+    }
+}
+
+impl Configurable for AnalysisTree {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.max_entries.set(p.get("max_entries"))?;
+        self.max_ply.set(p.get("max_ply"))?;
+        self.tt_ply.set(p.get("tt_ply"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for AnalysisTree {
+    fn new_game(&mut self) {}
+
+    fn new_position(&mut self) {}
+}
+
+impl AnalysisTree {
+    pub fn len(&self) -> usize {
+        self.nodes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.lock().unwrap().clear();
+        *self.root.lock().unwrap() = None;
+    }
+
+    /// the first position searched since the tree was cleared
+    pub fn root(&self) -> Option<Board> {
+        self.root.lock().unwrap().clone()
+    }
+
+    pub fn node(&self, b: &Board) -> Option<AnalysisNode> {
+        self.nodes.lock().unwrap().get(&b.hash()).copied()
+    }
+
+    /// adds the pv (with its score and depth) of a search of board `b`
+    pub fn record(&mut self, b: &Board, pv: &Variation, score: Score, depth: Ply) {
+        if !self.enabled {
+            return;
+        }
+        self.root.lock().unwrap().get_or_insert_with(|| b.clone());
+        let mut b = b.clone();
+        let mut score = score;
+        for (ply, mv) in pv.moves().enumerate() {
+            let depth = depth - ply as Ply;
+            if depth <= 0 {
+                break;
+            }
+            self.update(b.hash(), mv, score, depth, (ply == 0) as u32);
+            b = b.make_move(mv);
+            score = -score;
+        }
+    }
+
+    /// adds the exact (scored) entries of a walk of the transposition table
+    pub fn record_tt(&mut self, root: &Board, entries: impl IntoIterator<Item = TtSubtreeEntry>) {
+        if !self.enabled {
+            return;
+        }
+        for e in entries {
+            if e.node.bt == BoundType::ExactPv && !e.bm.is_null() && e.node.depth > 0 {
+                let hash = root.make_moves(&e.var).hash();
+                self.update(hash, e.bm, e.node.score.as_score(0), e.node.depth, 0);
+            }
+        }
+    }
+
+    // a deeper result replaces a shallower one
+    fn update(&self, hash: Hash, mv: Move, score: Score, depth: Ply, searches: u32) {
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get_mut(&hash) {
+            Some(node) if node.depth > depth => node.searches += searches,
+            Some(node) => {
+                *node = AnalysisNode {
+                    best_move: mv,
+                    score,
+                    depth,
+                    searches: node.searches + searches,
+                }
+            }
+            None if nodes.len() < self.max_entries => {
+                nodes.insert(hash, AnalysisNode {
+                    best_move: mv,
+                    score,
+                    depth,
+                    searches,
+                });
+            }
+            None => {}
+        }
+    }
+
+    /// the line of best moves from this position, if it is in the tree
+    pub fn lookup(&self, b: &Board) -> Option<Variation> {
+        if !self.enabled {
+            return None;
+        }
+        let mut pv = Variation::new();
+        let mut b = b.clone();
+        while let Some(node) = self.node(&b) {
+            if pv.len() >= self.max_ply as usize || !b.legal_moves().contains(&node.best_move) {
+                break;
+            }
+            pv.push(node.best_move);
+            b = b.make_move(node.best_move);
+        }
+        (!pv.is_empty()).then_some(pv)
+    }
+
+    /// the moves from this position leading to positions in the tree, best move first
+    fn children(&self, b: &Board) -> Vec<Move> {
+        let best = self.node(b).map(|n| n.best_move);
+        let nodes = self.nodes.lock().unwrap();
+        let mut moves = b
+            .legal_moves()
+            .iter()
+            .copied()
+            .filter(|&mv| Some(mv) == best || nodes.contains_key(&b.make_move(mv).hash()))
+            .collect_vec();
+        moves.sort_by_key(|&mv| Some(mv) != best);
+        moves
+    }
+
+    /// the tree below this position, a move per line indented by ply
+    pub fn show(&self, b: &Board) -> String {
+        let mut s = String::new();
+        if let Some(node) = self.node(b) {
+            let _ = writeln!(s, "{} depth {} searches {}", node.score, node.depth, node.searches);
+        }
+        self.show_below(b, 0, &mut HashSet::new(), &mut s);
+        s
+    }
+
+    fn show_below(&self, b: &Board, ply: Ply, visited: &mut HashSet<Hash>, s: &mut String) {
+        if ply >= self.max_ply || !visited.insert(b.hash()) {
+            return;
+        }
+        let indent = 2 * ply as usize;
+        for mv in self.children(b) {
+            let child = b.make_move(mv);
+            let san = b.to_san(mv);
+            let _ = match self.node(&child) {
+                // scores shown from the point of view of the side making the move
+                Some(n) => writeln!(s, "{:indent$}{san:<8} {} d{}", "", -n.score, n.depth),
+                None => writeln!(s, "{:indent$}{san}", ""),
+            };
+            self.show_below(&child, ply + 1, visited, s);
+        }
+        visited.remove(&b.hash());
+    }
+
+    /// the tree from this position as a pgn game, with the alternatives as variations and
+    /// scores (from white's point of view) and depths as comments
+    pub fn to_pgn(&self, b: &Board) -> String {
+        let mut s = String::new();
+        s += "[Event \"Analysis\"]\n";
+        s += "[Site \"?\"]\n";
+        s += "[Date \"????.??.??\"]\n";
+        s += "[Round \"-\"]\n";
+        s += "[White \"?\"]\n";
+        s += "[Black \"?\"]\n";
+        s += "[Result \"*\"]\n";
+        if *b != Board::starting_pos() {
+            let _ = writeln!(s, "[SetUp \"1\"]\n[FEN \"{}\"]", b.to_fen());
+        }
+        s += "\n";
+        let mut movetext = vec![];
+        self.movetext(b, 0, true, &mut HashSet::new(), &mut movetext);
+        movetext.push("*".to_string());
+        s + &movetext.join(" ") + "\n"
+    }
+
+    fn movetext(&self, b: &Board, ply: Ply, numbered: bool, visited: &mut HashSet<Hash>, out: &mut Vec<String>) {
+        if ply >= self.max_ply || !visited.insert(b.hash()) {
+            return;
+        }
+        let children = self.children(b);
+        if let Some((&main, alternatives)) = children.split_first() {
+            out.push(self.san_and_comment(b, main, numbered));
+            for &mv in alternatives {
+                out.push("(".to_string() + &self.san_and_comment(b, mv, true));
+                self.movetext(&b.make_move(mv), ply + 1, false, visited, out);
+                out.push(")".to_string());
+            }
+            self.movetext(&b.make_move(main), ply + 1, !alternatives.is_empty(), visited, out);
+        }
+        visited.remove(&b.hash());
+    }
+
+    fn san_and_comment(&self, b: &Board, mv: Move, numbered: bool) -> String {
+        let mut token = match (b.color_us(), numbered) {
+            (Color::White, _) => format!("{}. ", b.fullmove_number()),
+            (Color::Black, true) => format!("{}... ", b.fullmove_number()),
+            (Color::Black, false) => String::new(),
+        };
+        token += &b.to_san(mv);
+        if let Some(n) = self.node(&b.make_move(mv)) {
+            let white_score = n.score.as_white(b.color_them()).0;
+            let _ = write!(token, " {{{}/{}}}", white_score.to_pgn(), n.depth);
+        }
+        token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::Epd;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    #[test]
+    fn test_analysis_tree() {
+        let mut eng = ThreadedSearch::new();
+        eng.search.analysis_tree.enabled = true;
+        let start = Epd::starting_pos();
+        let res = eng.search(start.clone(), TimeControl::Depth(5)).unwrap();
+        let tree = eng.search.analysis_tree.clone();
+        assert_eq!(tree.root(), Some(start.board()));
+        let node = tree.node(&start.board()).unwrap();
+        assert_eq!(node.best_move, res.pv().first().unwrap());
+        assert_eq!(node.depth, 5);
+        assert_eq!(node.searches, 1);
+        assert_eq!(tree.lookup(&start.board()), Some(res.pv().take(5)));
+
+        // a search of an alternative first move adds a branch at the root
+        let alt = start
+            .board()
+            .legal_moves()
+            .iter()
+            .copied()
+            .find(|&mv| mv != node.best_move)
+            .unwrap();
+        let epd = Epd::from_var(start.board(), Variation::from_move(alt));
+        eng.search(epd, TimeControl::Depth(4)).unwrap();
+        let tree = eng.search.analysis_tree.clone();
+        assert_eq!(tree.children(&start.board()), vec![node.best_move, alt]);
+        println!("{}", tree.show(&start.board()));
+
+        let pgn = tree.to_pgn(&start.board());
+        println!("{pgn}");
+        assert!(pgn.contains(&format!("(1. {}", start.board().to_san(alt))));
+        assert!(pgn.trim_end().ends_with('*'));
+
+        // a shallower search does not replace a deeper one
+        eng.search(start.clone(), TimeControl::Depth(2)).unwrap();
+        let node2 = eng.search.analysis_tree.node(&start.board()).unwrap();
+        assert_eq!((node2.depth, node2.searches), (5, 2));
+
+        eng.search.analysis_tree.clear();
+        assert!(eng.search.analysis_tree.is_empty());
+        assert_eq!(eng.search.analysis_tree.lookup(&start.board()), None);
+
+        // the exact entries of the tt near the root are kept as well as the pv
+        eng.search.analysis_tree.tt_ply = 2;
+        eng.search(start.clone(), TimeControl::Depth(5)).unwrap();
+        let tree = eng.search.analysis_tree.clone();
+        let exact = eng
+            .search
+            .tt
+            .subtree(&start.board(), 2, false)
+            .filter(|e| e.node.bt == BoundType::ExactPv && !e.bm.is_null())
+            .count();
+        assert!(tree.len() >= exact, "{} {exact}", tree.len());
+        assert_eq!(tree.node(&start.board()).unwrap().depth, 5);
+    }
+}
@@ -10,6 +10,11 @@ use odonata_base::prelude::*;
 use super::algo::Search;
 use super::trail::Trail;
 
+/// Aspiration windows: each iteration is first searched in a window about the previous
+/// iteration's score. A fail low (or high) widens that side of the window, by `window` times
+/// `fail_low_growth` (or `fail_high_growth`) raised to the number of fails on that side, and
+/// re-searches. With `fail_soft` the widened bound is measured from the score returned rather
+/// than from the bound that failed.
 #[derive(Clone, Debug)]
 pub struct Aspiration {
     enabled:            bool,
@@ -17,14 +22,21 @@ pub struct Aspiration {
     window:             Score,
     max_iter:           i32,
     max_window:         Score,
-    multiplier1:        f32,
-    multiplier2:        f32,
+    fail_low_growth:    f32,
+    fail_high_growth:   f32,
     change_both_bounds: bool,
     fail_soft:          bool,
+
+    searches:   u64, // aspirated iterations since the new game
+    fail_lows:  u64,
+    fail_highs: u64,
 }
 
 impl Component for Aspiration {
     fn new_game(&mut self) {
+        self.searches = 0;
+        self.fail_lows = 0;
+        self.fail_highs = 0;
         self.new_position();
     }
 
@@ -39,10 +51,13 @@ impl Default for Aspiration {
             window:             100.cp(),
             max_iter:           16,
             max_window:         1200.cp(),
-            multiplier1:        4.0,
-            multiplier2:        4.0,
+            fail_low_growth:    4.0,
+            fail_high_growth:   4.0,
             change_both_bounds: false,
             fail_soft:          true,
+            searches:           0,
+            fail_lows:          0,
+            fail_highs:         0,
         }
     }
 }
@@ -54,8 +69,8 @@ impl Configurable for Aspiration {
         self.window.set(p.get("window"))?;
         self.max_iter.set(p.get("max_iter"))?;
         self.max_window.set(p.get("max_window"))?;
-        self.multiplier1.set(p.get("multiplier1"))?;
-        self.multiplier2.set(p.get("multiplier2"))?;
+        self.fail_low_growth.set(p.get("fail_low_growth"))?;
+        self.fail_high_growth.set(p.get("fail_high_growth"))?;
         self.change_both_bounds.set(p.get("change_both_bounds"))?;
         self.fail_soft.set(p.get("fail_soft"))?;
         Ok(p.is_modified())
@@ -68,6 +83,26 @@ impl fmt::Display for Aspiration {
     }
 }
 
+impl Aspiration {
+    /// percentage of aspirated iterations re-searched after failing low and high
+    pub fn fail_percs(&self) -> (f64, f64) {
+        let n = self.searches.max(1) as f64;
+        (100.0 * self.fail_lows as f64 / n, 100.0 * self.fail_highs as f64 / n)
+    }
+
+    /// the aspirated iterations since the new game, and how often they failed
+    pub fn display_stats(&self) -> impl fmt::Display + '_ {
+        Displayable(|f| {
+            let (low, high) = self.fail_percs();
+            write!(
+                f,
+                "{} aspirated, {low:.1}% failed low, {high:.1}% failed high",
+                self.searches
+            )
+        })
+    }
+}
+
 impl Search {
     pub fn aspirated_search(
         &mut self,
@@ -76,18 +111,24 @@ impl Search {
         n: &mut Node,
         score: Score,
     ) -> Result<(Score, Event), Event> {
-        if n.depth <= self.aspiration.min_depth || !self.aspiration.enabled || !score.is_numeric() {
+        if n.depth <= self.aspiration.min_depth
+            || !self.aspiration.enabled
+            || !score.is_numeric()
+            || self.mte.is_low_time()
+        {
             Metrics::incr_node(n, Event::AspirationNone);
             self.alphabeta_root_search(trail, pos, n)
         } else {
+            self.aspiration.searches += 1;
             let mut aspiration_count = 0;
-            // let mut delta = ((4 + n.ply / 4) * self.aspiration.window.as_i16() as i32) as f32 / 6.0;
-            let mut delta = self.aspiration.window.as_i16() as f32;
-            let mut alpha1 = score - Score::from_f32(delta);
-            let mut beta1 = score + Score::from_f32(delta);
+            let window = self.aspiration.window.as_i16() as f32;
+            let max_window = self.aspiration.max_window.as_i16() as f32;
+            let (mut delta_low, mut delta_high) = (window, window);
+            let mut alpha1 = score - Score::from_f32(delta_low);
+            let mut beta1 = score + Score::from_f32(delta_high);
             let ret = loop {
                 aspiration_count += 1;
-                if aspiration_count > self.aspiration.max_iter || delta > self.aspiration.max_window.as_i16() as f32 {
+                if aspiration_count > self.aspiration.max_iter || delta_low.max(delta_high) > max_window {
                     break self.alphabeta_root_search(trail, pos, n);
                 }
                 alpha1 = max(n.alpha, alpha1);
@@ -106,31 +147,26 @@ impl Search {
                 if new_score.is_mate() {
                     break self.alphabeta_root_search(trail, pos, n);
                 }
-                delta *= match aspiration_count {
-                    1 => self.aspiration.multiplier1,
-                    _ => self.aspiration.multiplier2,
-                };
 
                 if new_score <= alpha1 && alpha1 > n.alpha {
-                    if self.aspiration.fail_soft {
-                        alpha1 = new_score - Score::from_f32(delta);
-                    } else {
-                        alpha1 = alpha1 - Score::from_f32(delta);
-                    }
+                    self.aspiration.fail_lows += 1;
+                    Metrics::incr_node(n, Event::AspirationFailLow);
+                    delta_low *= self.aspiration.fail_low_growth;
+                    let from = if self.aspiration.fail_soft { new_score } else { alpha1 };
+                    alpha1 = from - Score::from_f32(delta_low);
                     if self.aspiration.change_both_bounds {
                         beta1 = new_score;
                     }
                 } else if new_score >= beta1 && beta1 < n.beta {
+                    self.aspiration.fail_highs += 1;
+                    Metrics::incr_node(n, Event::AspirationFailHigh);
+                    delta_high *= self.aspiration.fail_high_growth;
                     if self.aspiration.change_both_bounds {
                         alpha1 = new_score;
                     }
-                    if self.aspiration.fail_soft {
-                        beta1 = new_score + Score::from_f32(delta);
-                    } else {
-                        beta1 = beta1 + Score::from_f32(delta);
-                    }
+                    let from = if self.aspiration.fail_soft { new_score } else { beta1 };
+                    beta1 = from + Score::from_f32(delta_high);
                 } else {
-                    // info!("Found {:?} in search window {} {}", new_score, alpha1, beta1);
                     break Ok((new_score, event));
                 }
             };
@@ -147,36 +183,30 @@ impl Search {
 
 #[cfg(test)]
 mod tests {
-
-    // use crate::search::timecontrol::*;
-
-    // #[test]
-    // #[ignore]
-    // fn test_aspiration() {
-    //     let mut engine = ThreadedSearch::new();
-
-    //     let positions = &Catalog::example_game()[101..102];
-    //     let mut node_count = 0;
-    //     for pos in positions {
-    //         engine.new_game();
-    //         let suggested_depth = pos.int("acd").unwrap() as i32;
-    //         engine
-    //             .search
-    //             .set_timing_method(TimeControl::Depth(suggested_depth - 1));
-    //         engine.set_position(pos.clone());
-
-    //         engine.search_sync();
-    //         let mut results = engine.search.results_as_position().clone();
-    //         let nodes = results.int("acn").unwrap();
-    //         node_count += nodes;
-
-    //         // just leave acd
-    //         results.tags_mut().pv = None;
-    //         results.tags_mut().supplied_move = None;
-    //         results.tags_mut().best_moves = None;
-    //         results.tags_mut().centipawn_evaluation = None;
-    //         results.tags_mut().acn = None;
-    //         println!("{nodes:>12} {node_count:>12} {results}");
-    //     }
-    // }
+    use odonata_base::catalog::Catalog;
+    use odonata_base::infra::component::State;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    #[test]
+    fn test_aspiration_fails() {
+        let mut eng = ThreadedSearch::new();
+        eng.search.aspiration.window = 2.cp();
+        eng.search.aspiration.fail_high_growth = 8.0;
+        for epd in Catalog::bench().into_iter().take(8) {
+            eng.search(epd, TimeControl::Depth(8)).unwrap();
+        }
+        let asp = &eng.search.aspiration;
+        println!("{asp}");
+        assert!(asp.searches > 0);
+        assert!(asp.fail_lows > 0 && asp.fail_highs > 0, "{asp}");
+        let (low, high) = asp.fail_percs();
+        assert!(low > 0.0 && high > 0.0);
+        assert!(eng.display_metrics().to_string().contains("[aspiration]\n"));
+
+        eng.search.set_state(State::NewGame);
+        assert_eq!(eng.search.aspiration.searches, 0);
+    }
 }
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use odonata_base::domain::node::Event;
+use odonata_base::piece::MoveType;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::search_results::Response;
+
+/// The root move that last raised the best score, and how it was found. Recorded by the
+/// root node of alpha-beta, and overwritten as each better move is found.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RootRaise {
+    pub mv:        Move,
+    pub score:     Score,
+    pub move_type: MoveType,
+    pub lmr:       Ply,
+    pub event:     Event,
+    pub nodes:     u64,
+}
+
+/// A change of best move between the last two completed iterations. Late changes are
+/// where blunders (and saves) come from, so the record says which move ordering stage
+/// yielded the new move, whether it was reduced, and how deep into the iteration it
+/// was found.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BestMoveChange {
+    pub depth:      Ply,
+    pub old_move:   Move,
+    pub old_score:  Score,
+    pub new_move:   Move,
+    pub new_score:  Score,
+    pub move_type:  MoveType, // move ordering stage that produced the new move
+    pub lmr:        Ply,      // reduction applied before any re-search
+    pub event:      Event,    // outcome of the new move's subtree
+    pub nodes:      u64,      // (this thread) when the new move raised the score
+    pub iter_nodes: u64,      // (this thread) at the end of the iteration
+}
+
+impl fmt::Display for BestMoveChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "depth {d} best move {old} ({old_sc}) -> {new} ({new_sc}) via {mt} lmr {lmr} event '{ev}' at node {n} of {iter_n}",
+            d = self.depth,
+            old = self.old_move.to_uci(),
+            old_sc = self.old_score,
+            new = self.new_move.to_uci(),
+            new_sc = self.new_score,
+            mt = self.move_type,
+            lmr = self.lmr,
+            ev = self.event,
+            n = self.nodes,
+            iter_n = self.iter_nodes,
+        )
+    }
+}
+
+impl BestMoveChange {
+    /// the change (if any) from the previous iteration's results to the latest. The root
+    /// raise is only trusted if it names the new best move
+    pub fn between(depth: Ply, prev: &Response, latest: &Response, raise: Option<RootRaise>) -> Option<Self> {
+        let (Ok(old_move), Ok(new_move)) = (prev.supplied_move(), latest.supplied_move()) else {
+            return None;
+        };
+        if old_move == new_move {
+            return None;
+        }
+        let raise = raise.filter(|r| r.mv == new_move);
+        Some(BestMoveChange {
+            depth,
+            old_move,
+            old_score: prev.score().unwrap_or_default(),
+            new_move,
+            new_score: latest.score().unwrap_or_default(),
+            move_type: raise.map_or(MoveType::empty(), |r| r.move_type),
+            lmr: raise.map_or(0, |r| r.lmr),
+            event: raise.map_or(Event::Unknown, |r| r.event),
+            nodes: raise.map_or(0, |r| r.nodes),
+            iter_nodes: latest.nodes_thread,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::variation::MultiVariation;
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_best_move_change() {
+        let bd = Board::starting_pos();
+        let response = |uci: &str, cp: i32| {
+            let mv = bd.parse_uci_move(uci).unwrap();
+            let mut multi_pv = MultiVariation::new();
+            multi_pv.push(Variation::new().append(mv), Score::from_cp(cp));
+            Response {
+                supplied_move: mv,
+                multi_pv,
+                nodes_thread: 1000,
+                ..Response::default()
+            }
+        };
+        let (e4, d4) = (response("e2e4", 30), response("d2d4", 45));
+        let raise = RootRaise {
+            mv:        d4.supplied_move,
+            score:     Score::from_cp(45),
+            move_type: MoveType::Killer,
+            lmr:       1,
+            event:     Event::NodeInterior,
+            nodes:     600,
+        };
+        assert_eq!(BestMoveChange::between(5, &e4, &e4, Some(raise)), None);
+        assert_eq!(BestMoveChange::between(5, &Response::default(), &e4, Some(raise)), None);
+
+        let change = BestMoveChange::between(5, &e4, &d4, Some(raise)).unwrap();
+        println!("{change}");
+        assert_eq!(change.old_score, Score::from_cp(30));
+        assert_eq!(change.move_type, MoveType::Killer);
+        assert_eq!((change.nodes, change.iter_nodes), (600, 1000));
+        assert!(change.to_string().contains("e2e4"));
+        let json = serde_json::to_string(&change).unwrap();
+        assert_eq!(serde_json::from_str::<BestMoveChange>(&json).unwrap(), change);
+
+        // a raise naming another move is ignored
+        let change = BestMoveChange::between(5, &d4, &e4, Some(raise)).unwrap();
+        assert_eq!((change.move_type, change.event), (MoveType::empty(), Event::Unknown));
+    }
+}
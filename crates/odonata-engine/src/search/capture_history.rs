@@ -0,0 +1,196 @@
+use std::fmt;
+use std::fmt::Debug;
+
+use odonata_base::domain::node::Node;
+use odonata_base::infra::component::Component;
+use odonata_base::infra::metric::{Event, Metrics};
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const TABLE_LEN: usize = 2 * Piece::len() * 64 * Piece::len();
+
+/// History of captures, indexed by (piece, to-square, captured piece), so that captures
+/// that repeatedly fail high are ordered ahead of others of similar MVV-LVA.
+///
+/// Updated with a depth squared bonus when a capture causes a beta cut, and a malus for
+/// the captures tried before it, with "gravity" keeping entries within +/- `max_value`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CaptureHistory {
+    enabled:      bool,
+    min_depth:    Ply,
+    max_value:    i32,
+    max_bonus:    i32,
+    malus_factor: i32,
+    age_factor:   i32,
+
+    #[serde(skip, default = "table_default")]
+    table: Vec<i16>,
+}
+
+impl Default for CaptureHistory {
+    fn default() -> Self {
+        CaptureHistory {
+            enabled:      true,
+            min_depth:    1,
+            max_value:    16_384,
+            max_bonus:    1_200,
+            malus_factor: 1,
+            age_factor:   64,
+            table:        table_default(),
+        }
+    }
+}
+
+fn table_default() -> Vec<i16> {
+    vec![0; TABLE_LEN]
+}
+
+impl Configurable for CaptureHistory {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.min_depth.set(p.get("min_depth"))?;
+        self.max_value.set(p.get("max_value"))?;
+        self.max_bonus.set(p.get("max_bonus"))?;
+        self.malus_factor.set(p.get("malus_factor"))?;
+        self.age_factor.set(p.get("age_factor"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Debug for CaptureHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaptureHistory")
+            .field("enabled", &self.enabled)
+            .field("min_depth", &self.min_depth)
+            .field("max_value", &self.max_value)
+            .field("max_bonus", &self.max_bonus)
+            .field("malus_factor", &self.malus_factor)
+            .field("age_factor", &self.age_factor)
+            .field("table", &"<omitted>")
+            .finish()
+    }
+}
+
+impl Component for CaptureHistory {
+    fn new_game(&mut self) {
+        self.table.fill(0);
+    }
+
+    fn new_position(&mut self) {
+        for v in self.table.iter_mut() {
+            *v = (*v as i32 * self.age_factor / 128) as i16;
+        }
+    }
+}
+
+impl fmt::Display for CaptureHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", toml::to_string_pretty(self).unwrap())?;
+        Ok(())
+    }
+}
+
+impl CaptureHistory {
+    #[inline]
+    fn index(mv: Move, b: &Board) -> Option<usize> {
+        let cap = mv.capture_piece(b)?;
+        let (us, mover) = (b.color_us().index(), mv.mover_piece(b).index());
+        Some(((us * Piece::len() + mover) * 64 + mv.to().index()) * Piece::len() + cap.index())
+    }
+
+    /// within +/- max_value, zero for non-captures
+    #[inline]
+    pub fn score(&self, mv: Move, b: &Board) -> i32 {
+        if !self.enabled {
+            return 0;
+        }
+        Self::index(mv, b).map_or(0, |i| self.table[i] as i32)
+    }
+
+    fn update(&mut self, mv: Move, b: &Board, bonus: i32) {
+        let Some(i) = Self::index(mv, b) else {
+            return;
+        };
+        let max = self.max_value.clamp(1, i16::MAX as i32);
+        let v = self.table[i] as i32;
+        self.table[i] = (v + bonus - v * bonus.abs() / max).clamp(-max, max) as i16;
+    }
+
+    fn bonus(&self, n: &Node) -> i32 {
+        (n.depth * n.depth).min(self.max_bonus)
+    }
+
+    /// the board is before the move, and `nth` is the capture's position (from 1) among
+    /// the captures tried at the node
+    pub fn beta_cutoff(&mut self, n: &Node, b: &Board, mv: Move, nth: u32) {
+        if !mv.is_capture() {
+            return;
+        }
+        match nth {
+            1 => Metrics::incr_node(n, Event::CaptureCutFirst),
+            _ => Metrics::incr_node(n, Event::CaptureCutLater),
+        }
+        if !self.enabled || n.depth < self.min_depth {
+            return;
+        }
+        let bonus = self.bonus(n);
+        self.update(mv, b, bonus);
+    }
+
+    pub fn duff(&mut self, n: &Node, b: &Board, mv: Move) {
+        if !self.enabled || n.depth < self.min_depth || !mv.is_capture() {
+            return;
+        }
+        let malus = -self.bonus(n) / self.malus_factor.max(1);
+        self.update(mv, b, malus);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_capture_history() {
+        let mut ch = CaptureHistory::default();
+        let text = toml::to_string(&ch).unwrap();
+        let ch2: CaptureHistory = toml::from_str(&text).unwrap();
+        assert_eq!(ch2.max_value, ch.max_value);
+
+        let b = Board::parse_fen("4k3/8/8/3p1p2/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let exd5 = b.parse_uci_move("e4d5").unwrap();
+        let exf5 = b.parse_uci_move("e4f5").unwrap();
+        let e1d2 = b.parse_uci_move("e1d2").unwrap();
+        let n = Node {
+            depth: 4,
+            ..Node::default()
+        };
+        ch.duff(&n, &b, exd5);
+        ch.beta_cutoff(&n, &b, exf5, 2);
+        ch.beta_cutoff(&n, &b, e1d2, 1);
+        assert_eq!(ch.score(exd5, &b), -16);
+        assert_eq!(ch.score(exf5, &b), 16);
+        assert_eq!(ch.score(e1d2, &b), 0);
+
+        // by color, piece, to-square and captured piece
+        let b2 = Board::parse_fen("4k3/8/8/3p1p2/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(ch.score(b2.parse_uci_move("d5e4").unwrap(), &b2), 0);
+
+        // gravity bounds the entries
+        let deep = Node {
+            depth: 40,
+            ..Node::default()
+        };
+        for _ in 0..1000 {
+            ch.beta_cutoff(&deep, &b, exf5, 1);
+        }
+        assert_eq!(ch.score(exf5, &b), ch.max_value);
+        ch.new_position();
+        assert_eq!(ch.score(exf5, &b), ch.max_value / 2);
+        ch.new_game();
+        assert_eq!(ch.score(exf5, &b), 0);
+    }
+}
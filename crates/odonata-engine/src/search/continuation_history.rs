@@ -0,0 +1,252 @@
+use std::fmt;
+use std::fmt::Debug;
+
+use odonata_base::domain::node::Node;
+use odonata_base::infra::component::Component;
+use odonata_base::piece::MoveType;
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const TABLE_LEN: usize = 2 * Piece::len() * 64 * Piece::len() * 64;
+
+/// History of quiet moves indexed by the moves that preceded them.
+///
+/// The counter-move history is indexed by the opponent's previous move (1 ply back), and the
+/// follow-up history by our own previous move (2 plies back), each as (piece, to-square) pairs.
+/// Entries are updated with a depth squared bonus (on a beta cut) or malus, with "gravity"
+/// keeping them within +/- `max_value`.
+///
+/// Each search thread has its own instance, so tables are never shared or locked.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContinuationHistory {
+    enabled:      bool,
+    follow_up:    bool,
+    min_depth:    Ply,
+    max_ply:      Ply,
+    max_value:    i32,
+    max_bonus:    i32,
+    malus_factor: i32,
+    age_factor:   i32,
+
+    #[serde(skip, default = "table_default")]
+    counter: Vec<i16>,
+
+    #[serde(skip, default = "table_default")]
+    follow: Vec<i16>,
+}
+
+impl Default for ContinuationHistory {
+    fn default() -> Self {
+        ContinuationHistory {
+            enabled:      true,
+            follow_up:    true,
+            min_depth:    1,
+            max_ply:      128,
+            max_value:    16_384,
+            max_bonus:    1_200,
+            malus_factor: 2,
+            age_factor:   64,
+            counter:      table_default(),
+            follow:       table_default(),
+        }
+    }
+}
+
+fn table_default() -> Vec<i16> {
+    vec![0; TABLE_LEN]
+}
+
+impl Configurable for ContinuationHistory {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.follow_up.set(p.get("follow_up"))?;
+        self.min_depth.set(p.get("min_depth"))?;
+        self.max_ply.set(p.get("max_ply"))?;
+        self.max_value.set(p.get("max_value"))?;
+        self.max_bonus.set(p.get("max_bonus"))?;
+        self.malus_factor.set(p.get("malus_factor"))?;
+        self.age_factor.set(p.get("age_factor"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Debug for ContinuationHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContinuationHistory")
+            .field("enabled", &self.enabled)
+            .field("follow_up", &self.follow_up)
+            .field("min_depth", &self.min_depth)
+            .field("max_ply", &self.max_ply)
+            .field("max_value", &self.max_value)
+            .field("max_bonus", &self.max_bonus)
+            .field("malus_factor", &self.malus_factor)
+            .field("age_factor", &self.age_factor)
+            .field("counter", &"<omitted>")
+            .field("follow", &"<omitted>")
+            .finish()
+    }
+}
+
+impl Component for ContinuationHistory {
+    fn new_game(&mut self) {
+        self.counter.fill(0);
+        self.follow.fill(0);
+    }
+
+    fn new_position(&mut self) {
+        self.adjust_by_factor(self.age_factor);
+    }
+}
+
+impl fmt::Display for ContinuationHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", toml::to_string_pretty(self).unwrap())?;
+        Ok(())
+    }
+}
+
+impl ContinuationHistory {
+    /// the previous move (opponent's) and the one before (ours) of the variation, less the
+    /// last `skip` moves. Null where the variation is too short.
+    pub fn priors(var: &Variation, skip: usize) -> [Move; 2] {
+        let mut moves = var.moves().rev().skip(skip);
+        [moves.next().unwrap_or_default(), moves.next().unwrap_or_default()]
+    }
+
+    pub fn max_value(&self) -> i32 {
+        self.max_value
+    }
+
+    /// scales by age_factor/128
+    pub fn adjust_by_factor(&mut self, age_factor: i32) {
+        for v in self.counter.iter_mut().chain(self.follow.iter_mut()) {
+            *v = (*v as i32 * age_factor / 128) as i16;
+        }
+    }
+
+    #[inline]
+    fn index(c: Color, prior: Move, mv: Move) -> usize {
+        (((c.index() * Piece::len() + prior.mover().index()) * 64 + prior.to().index()) * Piece::len()
+            + mv.mover().index())
+            * 64
+            + mv.to().index()
+    }
+
+    #[inline]
+    fn is_accepted(&self, n: &Node, mv: Move, mt: MoveType) -> bool {
+        self.enabled
+            && n.depth >= self.min_depth
+            && n.ply <= self.max_ply
+            && !mv.is_capture()
+            && !mv.is_promo()
+            && mt != MoveType::GoodCapture
+    }
+
+    /// the counter-move history plus (if enabled) the follow-up history of a quiet move
+    /// for player `c`, within +/- 2 * max_value
+    #[inline]
+    pub fn score(&self, c: Color, priors: [Move; 2], mv: Move) -> i32 {
+        if !self.enabled || mv.is_capture() {
+            return 0;
+        }
+        let [prev, prev2] = priors;
+        let mut score = 0;
+        if !prev.is_null() {
+            score += self.counter[Self::index(c, prev, mv)] as i32;
+        }
+        if self.follow_up && !prev2.is_null() {
+            score += self.follow[Self::index(c, prev2, mv)] as i32;
+        }
+        score
+    }
+
+    #[inline]
+    fn update(&mut self, c: Color, priors: [Move; 2], mv: Move, bonus: i32) {
+        let max = self.max_value.clamp(1, i16::MAX as i32);
+        let gravity = |v: &mut i16| {
+            let new = *v as i32 + bonus - *v as i32 * bonus.abs() / max;
+            *v = new.clamp(-max, max) as i16;
+        };
+        let [prev, prev2] = priors;
+        if !prev.is_null() {
+            gravity(&mut self.counter[Self::index(c, prev, mv)]);
+        }
+        if self.follow_up && !prev2.is_null() {
+            gravity(&mut self.follow[Self::index(c, prev2, mv)]);
+        }
+    }
+
+    fn bonus(&self, n: &Node) -> i32 {
+        (n.depth * n.depth).min(self.max_bonus)
+    }
+
+    /// the variation is the path to the node, and the color the player of the move
+    pub fn beta_cutoff(&mut self, n: &Node, c: Color, var: &Variation, mv: Move, mt: MoveType) {
+        if !self.is_accepted(n, mv, mt) {
+            return;
+        }
+        let bonus = self.bonus(n);
+        self.update(c, Self::priors(var, 0), mv, bonus);
+    }
+
+    pub fn duff(&mut self, n: &Node, c: Color, var: &Variation, mv: Move, mt: MoveType) {
+        if !self.is_accepted(n, mv, mt) {
+            return;
+        }
+        let malus = -self.bonus(n) / self.malus_factor.max(1);
+        self.update(c, Self::priors(var, 0), mv, malus);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_continuation_history() {
+        let mut ch = ContinuationHistory::default();
+        let text = toml::to_string(&ch).unwrap();
+        let ch2: ContinuationHistory = toml::from_str(&text).unwrap();
+        assert_eq!(ch2.max_value, ch.max_value);
+
+        let b0 = Board::starting_pos();
+        let e4 = b0.parse_uci_move("e2e4").unwrap();
+        let b1 = b0.make_move(e4);
+        let e5 = b1.parse_uci_move("e7e5").unwrap();
+        let b2 = b1.make_move(e5);
+        let nf3 = b2.parse_uci_move("g1f3").unwrap();
+        let d3 = b2.parse_uci_move("d2d3").unwrap();
+        let var: Variation = [e4, e5].into_iter().collect();
+        let n = Node {
+            depth: 6,
+            ..Node::default()
+        };
+        let priors = ContinuationHistory::priors(&var, 0);
+        assert_eq!(priors, [e5, e4]);
+
+        ch.beta_cutoff(&n, Color::White, &var, nf3, MoveType::Quiet);
+        ch.duff(&n, Color::White, &var, d3, MoveType::Quiet);
+        assert_eq!(ch.score(Color::White, priors, nf3), 2 * 36);
+        assert_eq!(ch.score(Color::White, priors, d3), 2 * -18);
+        assert_eq!(ch.score(Color::Black, priors, nf3), 0);
+
+        // only the counter-move history after a different move two plies back
+        let d4 = b0.parse_uci_move("d2d4").unwrap();
+        assert_eq!(ch.score(Color::White, [e5, d4], nf3), 36);
+
+        // gravity bounds the entries
+        for _ in 0..1000 {
+            ch.beta_cutoff(&n, Color::White, &var, nf3, MoveType::Quiet);
+        }
+        let s = ch.score(Color::White, priors, nf3);
+        assert!(s > ch.max_value() && s <= 2 * ch.max_value(), "{s}");
+
+        ch.adjust_by_factor(64);
+        assert!(ch.score(Color::White, priors, nf3) <= ch.max_value());
+        ch.new_game();
+        assert_eq!(ch.score(Color::White, priors, nf3), 0);
+    }
+}
@@ -1,9 +1,11 @@
 use std::fmt;
 use std::sync::atomic::{self, AtomicBool};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use odonata_base::domain::info::Info;
+use odonata_base::domain::info::{Info, InfoKind};
 use odonata_base::infra::component::{Component, State};
+use odonata_base::piece::MAX_PLY;
 use odonata_base::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -13,12 +15,21 @@ use super::algo::Callback;
 #[serde(deny_unknown_fields)]
 pub struct Controller {
     pub show_refutations: bool,
+    pub show_summary:     bool, // a plain account of the search after bestmove, as info strings
     pub analyse_mode:     bool, // tries to find full PV etc
     pub multi_pv:         usize,
+    pub debug_tree:       bool, // record the search tree and show a pruning heat table
+    pub tree_max_ply:     Ply,
+    pub shuffle_movegen:  bool, // random move generation order, fixed seed if deterministic
+    pub info_interval_ms: u64,  // min gap between progress infos. pv (depth complete) and bestmove always sent
+    pub show_wdl:         bool, // win/draw/loss per mille alongside scores (UCI_ShowWDL)
 
     #[serde(skip)]
     pub progress_callback: Callback,
 
+    #[serde(skip)]
+    last_info: Arc<Mutex<Option<Instant>>>,
+
     #[serde(skip)]
     kill_switch: Arc<AtomicBool>,
 }
@@ -27,9 +38,16 @@ impl Default for Controller {
     fn default() -> Self {
         Self {
             show_refutations:  false,
+            show_summary:      false,
             analyse_mode:      false,
             multi_pv:          1,
+            debug_tree:        false,
+            tree_max_ply:      MAX_PLY,
+            shuffle_movegen:   false,
+            info_interval_ms:  50,
+            show_wdl:          false,
             progress_callback: Callback::default(),
+            last_info:         Arc::default(),
             kill_switch:       Arc::new(AtomicBool::new(false)),
         }
     }
@@ -38,8 +56,14 @@ impl Default for Controller {
 impl Configurable for Controller {
     fn set(&mut self, p: Param) -> Result<bool> {
         self.show_refutations.set(p.get("show_refutations"))?;
+        self.show_summary.set(p.get("show_summary"))?;
         self.analyse_mode.set(p.get("analyse_mode"))?;
         self.multi_pv.set(p.get("multi_pv"))?;
+        self.debug_tree.set(p.get("debug_tree"))?;
+        self.tree_max_ply.set(p.get("tree_max_ply"))?;
+        self.shuffle_movegen.set(p.get("shuffle_movegen"))?;
+        self.info_interval_ms.set(p.get("info_interval_ms"))?;
+        self.show_wdl.set(p.get("show_wdl"))?;
         Ok(p.is_modified())
     }
 }
@@ -69,7 +93,7 @@ impl Component for Controller {
         match s {
             NewGame => self.new_game(),
             SetPosition => {}
-            StartSearch => {}
+            StartSearch => *self.last_info.lock().unwrap() = None,
             EndSearch => {}
             StartDepthIteration(_) => {}
             Shutdown => {}
@@ -97,11 +121,83 @@ impl Controller {
     }
 
     pub fn invoke_callback(&self, info: &Info) {
+        if self.is_throttled(info) {
+            trace!("throttled callback with {info}");
+            return;
+        }
         trace!("callback with {info}");
         self.progress_callback.0(info);
     }
 
+    /// progress infos (node counts, refutations) are dropped if sent within `info_interval_ms`
+    /// of the previous info, so that GUIs are not flooded
+    fn is_throttled(&self, info: &Info) -> bool {
+        let now = Instant::now();
+        let mut last_info = self.last_info.lock().unwrap();
+        let progress = matches!(info.kind, InfoKind::NodeCounts | InfoKind::Refutation);
+        let interval = Duration::from_millis(self.info_interval_ms);
+        if progress && last_info.is_some_and(|last| now.duration_since(last) < interval) {
+            return true;
+        }
+        *last_info = Some(now);
+        false
+    }
+
+    /// no info has been sent for a second, so a progress info (hashfull, tbhits etc) is due
+    pub fn is_progress_due(&self) -> bool {
+        let last_info = self.last_info.lock().unwrap();
+        last_info.map_or(true, |last| last.elapsed() >= Duration::from_secs(1))
+    }
+
     pub fn register_callback(&mut self, callback: impl Fn(&Info) + Send + Sync + 'static) {
         self.progress_callback = Callback(Arc::new(callback));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_info_throttling() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut controller = Controller {
+            info_interval_ms: 10_000,
+            ..Controller::default()
+        };
+        let counter = Arc::clone(&count);
+        controller.register_callback(move |_| {
+            counter.fetch_add(1, atomic::Ordering::SeqCst);
+        });
+        let progress = Info {
+            kind: InfoKind::NodeCounts,
+            ..Info::default()
+        };
+        let pv = Info {
+            kind: InfoKind::Pv,
+            ..Info::default()
+        };
+        let best_move = Info {
+            kind: InfoKind::BestMove,
+            ..Info::default()
+        };
+        controller.invoke_callback(&progress);
+        controller.invoke_callback(&progress); // throttled
+        controller.invoke_callback(&pv);
+        controller.invoke_callback(&progress); // throttled
+        controller.invoke_callback(&best_move);
+        assert_eq!(count.load(atomic::Ordering::SeqCst), 3);
+
+        controller.set_state(State::StartSearch);
+        controller.invoke_callback(&progress);
+        assert_eq!(count.load(atomic::Ordering::SeqCst), 4);
+
+        controller.info_interval_ms = 0;
+        controller.invoke_callback(&progress);
+        assert_eq!(count.load(atomic::Ordering::SeqCst), 5);
+    }
+}
@@ -16,17 +16,31 @@ use odonata_base::prelude::*;
 
 use super::algo::Callback;
 use super::search_results::Response;
+use crate::cache::memory_budget::{MemoryBudget, MemorySplit};
 use crate::cache::tt2::TranspositionTable2;
+use crate::eval::network::checksum;
 use crate::search::algo::Search;
 
 #[derive(Debug)]
 pub struct ThreadedSearch {
     pub search:       Search,
     pub thread_count: u32,
+    pub memory:       MemoryBudget,
     engine_name:      String,
     thread_handles:   Vec<JoinHandle<Result<Search>>>,
     settings:         HashMap<String, String>,
     options:          Vec<(String, String)>,
+    snapshots:        Vec<ConfigSnapshot>,
+}
+
+/// the full configuration of the engine, for rolling back experimental changes
+#[derive(Clone, Debug)]
+struct ConfigSnapshot {
+    search:       Search,
+    thread_count: u32,
+    memory:       MemoryBudget,
+    settings:     HashMap<String, String>,
+    options:      Vec<(String, String)>,
 }
 
 impl Default for ThreadedSearch {
@@ -34,10 +48,12 @@ impl Default for ThreadedSearch {
         Self {
             search:         Search::default(),
             thread_count:   0,
+            memory:         MemoryBudget::default(),
             engine_name:    Version::name_and_version(),
             settings:       HashMap::new(),
             thread_handles: Vec::new(),
             options:        Vec::new(),
+            snapshots:      Vec::new(),
         }
     }
 }
@@ -60,6 +76,7 @@ impl ThreadedSearch {
             if !modified {
                 anyhow::bail!("setting {k} = {v} failed as no matching keys");
             }
+            self.settings.insert(k.clone(), v.clone());
         }
         Ok(())
     }
@@ -68,14 +85,38 @@ impl ThreadedSearch {
         self.engine_name.clone()
     }
 
+    /// the engine name and version, network and cpu features, eg "Odonata 0.9 NN-ab12cd34 AVX2",
+    /// so that results and reports identify the configuration
+    pub fn identity(&self) -> String {
+        [self.name(), self.search.eval.net_id(), Version::cpu_features()]
+            .iter()
+            .filter(|s| !s.is_empty())
+            .join(" ")
+    }
+
+    /// a checksum of the uci options and config settings, so that results from differently
+    /// configured engines with the same identity can be told apart
+    pub fn config_fingerprint(&self) -> String {
+        let options = self.options().iter().map(|(k, v)| format!("{k}={v}")).join("\n");
+        let settings = self
+            .settings
+            .iter()
+            .sorted()
+            .map(|(k, v)| format!("{k}={v}"))
+            .join("\n");
+        format!("{:016x}", checksum(format!("{options}\n{settings}").as_bytes()))
+    }
+
     pub fn try_clone(&self) -> Result<Self> {
         Ok(Self {
             search:         self.search.clone(),
             thread_count:   self.thread_count,
+            memory:         self.memory.clone(),
             engine_name:    self.engine_name.clone(),
             thread_handles: vec![], // dont clone running threads
             settings:       self.settings.clone(),
             options:        self.options.clone(),
+            snapshots:      self.snapshots.clone(),
         })
     }
 
@@ -142,6 +183,8 @@ impl ThreadedSearch {
         let tc = format!("spin default {} min 1 max 16", self.thread_count);
         map.insert("Ponder", "check default false");
         map.insert("Threads", &tc);
+        let limit = format!("spin default {} min 0 max 1048576", self.memory.limit_mb);
+        map.insert("MemoryLimitMB", &limit);
         let s = format!("string default {}", UciString::to_uci(""));
         map.insert("Init", &s);
 
@@ -152,6 +195,15 @@ impl ThreadedSearch {
 
     pub fn set_option(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
         debug!(target: "eng", "-> trying threaded-search set option('{name}' = '{value}')");
+        // validated against the memory limit before anything is assigned
+        let prospective = match name {
+            "Threads" => Some(self.configured_memory_with(value.parse()?, self.search.tt.mb)),
+            "Hash" => Some(self.configured_memory_with(self.thread_count, value.parse()?)),
+            _ => None,
+        };
+        if let Some(split) = prospective {
+            self.memory.allocate(&split)?;
+        }
         match name {
             "Init" => {
                 let Some((key, value)) = value.split_once('=') else {
@@ -161,24 +213,113 @@ impl ThreadedSearch {
                 anyhow::ensure!(modified, "unable to set {key} = {value}"); // goes to UI
             }
             "Threads" => self.thread_count = value.parse()?,
+            "MemoryLimitMB" => {
+                let memory = MemoryBudget {
+                    limit_mb: value.parse()?,
+                };
+                memory.allocate(&self.configured_memory())?; // refused if the limit is too small
+                self.memory = memory;
+            }
 
             _ => {
                 if self.search.options().contains_key(name) {
-                    self.options.push((name.to_string(), value.to_string()));
                     self.search.set_option(name, value)?;
+                    self.options.push((name.to_string(), value.to_string()));
                 } else {
                     anyhow::bail!("ThreadedSearch does not support set option '{name}'");
                 }
             }
         };
+        if matches!(
+            name,
+            "MemoryLimitMB" | "Threads" | "Hash" | "OwnBook" | "BookFile" | "Book File"
+        ) {
+            self.apply_memory_limit()?;
+        }
         Ok(())
     }
 
+    /// the memory of the tt, eval caches and book as configured, before any memory limit
+    pub fn configured_memory(&self) -> MemorySplit {
+        self.configured_memory_with(self.thread_count, self.search.tt.mb)
+    }
+
+    fn configured_memory_with(&self, thread_count: u32, tt_mb: i64) -> MemorySplit {
+        let searches = 1 + thread_count as usize;
+        MemorySplit {
+            searches,
+            tt: TranspositionTable2::bytes_for_mb(tt_mb),
+            eval_caches: searches * self.search.eval.hce.cache_bytes(),
+            book: searches * self.search.opening_book.size_in_bytes(),
+        }
+    }
+
+    /// holds the tt and eval caches within `MemoryLimitMB`, returning the resulting split
+    pub fn apply_memory_limit(&mut self) -> Result<MemorySplit> {
+        let configured = self.configured_memory();
+        let split = self.memory.allocate(&configured)?;
+        let held = split != configured;
+        self.search
+            .tt
+            .set_budget_mb(held.then(|| TranspositionTable2::mb_within(split.tt)));
+        self.search.eval.hce.set_cache_budget(held.then(|| split.eval_cache()));
+        Ok(split)
+    }
+
+    /// the memory limit and how it is split, eg for the uci startup
+    pub fn memory_report(&mut self) -> Result<String> {
+        let split = self.apply_memory_limit()?;
+        Ok(match self.memory.limit_mb {
+            0 => format!("memory (no limit): {split}"),
+            mb => format!("memory limit {mb}MB: {split}"),
+        })
+    }
+
     pub fn start_game(&mut self) -> anyhow::Result<()> {
         self.set_state(State::NewGame);
         Ok(())
     }
 
+    /// captures the configuration (settings and options) for a later `config_rollback`,
+    /// returning the number of snapshots held
+    pub fn config_snapshot(&mut self) -> usize {
+        self.snapshots.push(ConfigSnapshot {
+            search:       self.search.clone(),
+            thread_count: self.thread_count,
+            memory:       self.memory.clone(),
+            settings:     self.settings.clone(),
+            options:      self.options.clone(),
+        });
+        self.snapshots.len()
+    }
+
+    /// restores the most recent snapshot as a whole, between searches, returning the number
+    /// of snapshots still held.
+    ///
+    /// The position, tt contents, metrics and callback are kept, but other component state
+    /// (eg the pv cache) is as it was at the snapshot
+    pub fn config_rollback(&mut self) -> Result<usize> {
+        anyhow::ensure!(
+            !self.is_searching(),
+            "cannot roll back the configuration during a search"
+        );
+        let snapshot = self
+            .snapshots
+            .pop()
+            .context("no configuration snapshot to roll back to")?;
+        let current = std::mem::replace(&mut self.search, snapshot.search);
+        self.search.callback = current.callback;
+        self.search.position = current.position;
+        self.search.board = current.board;
+        self.search.response = current.response;
+        self.search.game_metrics = current.game_metrics;
+        self.thread_count = snapshot.thread_count;
+        self.memory = snapshot.memory;
+        self.settings = snapshot.settings;
+        self.options = snapshot.options;
+        Ok(self.snapshots.len())
+    }
+
     pub fn metrics(&mut self, filter: &str) -> anyhow::Result<Stats> {
         // TODO! sum metrics accross threads
         self.search.metrics(filter)
@@ -194,6 +335,7 @@ impl fmt::Display for ThreadedSearch {
 impl ThreadedSearch {
     fn fmt_metrics(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "\n[algo]\n{}", self.search)?;
+        writeln!(f, "\n[aspiration]\n{}", self.search.aspiration.display_stats())?;
         Ok(())
     }
 
@@ -299,6 +441,11 @@ impl ThreadedSearch {
         }
     }
 
+    /// whether a search started by `search_start` is still running
+    pub fn is_searching(&self) -> bool {
+        self.thread_handles.iter().any(|t| !t.is_finished())
+    }
+
     pub fn search_stop(&mut self) {
         self.search.controller.cancel();
         self.wait();
@@ -371,8 +518,9 @@ mod tests {
     use std::time::{self, Duration};
 
     use odonata_base::catalog::*;
+    use odonata_base::domain::info::InfoKind;
     use odonata_base::infra::metric::MetricsRegistry;
-    use odonata_base::infra::utils::Formatting;
+    use odonata_base::infra::utils::{Formatting, Uci};
     use pretty_assertions::assert_eq;
     use test_log::test;
 
@@ -415,6 +563,147 @@ mod tests {
         // assert_eq!(engine.algo.eval.quantum, 2);
     }
 
+    #[test]
+    fn test_config_rollback() {
+        let mut eng = ThreadedSearch::new();
+        assert!(eng.config_rollback().is_err());
+        let fingerprint = eng.config_fingerprint();
+        assert_eq!(eng.config_snapshot(), 1);
+        eng.configure([("eval.hce.quantum".into(), "300".into())].into())
+            .unwrap();
+        eng.set_option("MultiPV", "3").unwrap();
+        eng.set_option("Threads", "2").unwrap();
+        assert_ne!(eng.config_fingerprint(), fingerprint);
+        eng.search(Epd::starting_pos(), TimeControl::Depth(3)).unwrap();
+        let board = eng.search.board.clone();
+
+        assert_eq!(eng.config_rollback().unwrap(), 0);
+        assert_eq!(eng.search.eval.hce.quantum, 1);
+        assert_eq!(eng.search.controller.multi_pv, 1);
+        assert_eq!(eng.thread_count, 0);
+        assert_eq!(eng.search.board, board);
+        assert_eq!(eng.config_fingerprint(), fingerprint);
+        assert!(eng.search(Epd::starting_pos(), TimeControl::Depth(3)).is_ok());
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut eng = ThreadedSearch::new();
+        eng.set_option("Hash", "64").unwrap();
+        eng.set_option("Threads", "2").unwrap();
+        let configured = eng.configured_memory();
+        assert_eq!(configured.searches, 3);
+        assert_eq!(eng.search.tt.allocated_mb(), 64);
+
+        eng.set_option("MemoryLimitMB", "20").unwrap();
+        assert!(eng.search.tt.allocated_mb() < 20);
+        assert!(eng.apply_memory_limit().unwrap().total() <= 20_000_000);
+        assert!(eng.memory_report().unwrap().contains("memory limit 20MB: tt 16.8MB"));
+        assert!(eng.search(Epd::starting_pos(), TimeControl::Depth(3)).is_ok());
+
+        // too small for even a minimal tt, so refused
+        assert!(eng.set_option("MemoryLimitMB", "1").is_err());
+        assert_eq!(eng.memory.limit_mb, 20);
+
+        // a refused setting leaves the configuration as it was
+        let options = eng.options.len();
+        assert!(eng.set_option("Hash", "lots").is_err());
+        assert!(eng.set_option("Threads", "-1").is_err());
+        assert_eq!(eng.options.len(), options);
+        assert_eq!(eng.configured_memory(), configured);
+
+        eng.set_option("MemoryLimitMB", "0").unwrap();
+        assert_eq!(eng.search.tt.allocated_mb(), 64);
+        assert_eq!(eng.apply_memory_limit().unwrap(), configured);
+    }
+
+    #[test]
+    fn test_low_time_search() {
+        // a 500ms budget treated as low time, ample to search beyond the depth cap without it
+        let tc = TimeControl::SearchTime(Duration::from_millis(500));
+        let low_time_max_depth = 4;
+        let mut eng = ThreadedSearch::new();
+        eng.configure([("mte.low_time_ms".into(), "0".into())].into()).unwrap();
+        let res = eng.search(Epd::starting_pos(), tc.clone()).unwrap();
+        assert!(res.depth > low_time_max_depth, "depth {} uncapped", res.depth);
+
+        let settings: [(String, String); 2] = [
+            ("mte.low_time_ms".into(), "60000".into()),
+            ("mte.low_time_max_depth".into(), low_time_max_depth.to_string()),
+        ];
+        for epd in Catalog::bratko_kopec().iter().take(4) {
+            let mut eng = ThreadedSearch::new();
+            eng.configure(settings.clone().into()).unwrap();
+            let res = eng.search(epd.clone(), tc.clone()).unwrap();
+            let mv = res.supplied_move().unwrap();
+            assert!(epd.board().legal_moves().contains(&mv), "{mv} on {epd}");
+            assert!(res.depth <= low_time_max_depth, "depth {} on {epd}", res.depth);
+        }
+    }
+
+    #[test]
+    fn test_fast_return() {
+        let tc = TimeControl::parse_uci("wtime 60000 btime 60000").unwrap();
+        let mut eng = ThreadedSearch::new();
+
+        // Kxb2 is the only move
+        let epd = Epd::parse_epd("k7/8/8/8/8/8/1q6/K7 w - - 0 1").unwrap();
+        let res = eng.search(epd.clone(), tc.clone()).unwrap();
+        assert_eq!(res.supplied_move().unwrap().to_uci(), "a1b2");
+        assert_eq!(res.depth, 1);
+
+        // Bxc6 has to be recaptured, confirmed by a shallow search
+        let b = Catalog::starting_board();
+        let played = b.parse_uci_variation("e2e4 e7e5 g1f3 b8c6 f1b5 g8f6 b5c6").unwrap();
+        let res = eng.search(Epd::from_var(b.clone(), played.clone()), tc).unwrap();
+        assert_eq!(res.supplied_move().unwrap().to().uci(), "c6");
+        assert_eq!(res.depth, 6);
+
+        // not for fixed depth searches
+        let res = eng.search(Epd::from_var(b, played), TimeControl::Depth(8)).unwrap();
+        assert_eq!(res.depth, 8);
+        let res = eng.search(epd, TimeControl::Depth(3)).unwrap();
+        assert_eq!(res.depth, 3);
+    }
+
+    #[test]
+    fn test_shuffle_movegen() {
+        let settings = [
+            ("controller.shuffle_movegen".into(), "true".into()),
+            ("mte.deterministic".into(), "true".into()),
+        ]
+        .into();
+        let mut eng = ThreadedSearch::new();
+        eng.configure(settings).unwrap();
+        let search = |eng: &mut ThreadedSearch| {
+            eng.new_game();
+            eng.search(Catalog::test_position(), TimeControl::Depth(6)).unwrap()
+        };
+        let (res1, res2) = (search(&mut eng), search(&mut eng));
+        assert_eq!(res1.supplied_move(), res2.supplied_move());
+        assert_eq!(res1.nodes, res2.nodes, "deterministic mode uses a fixed seed");
+    }
+
+    #[test]
+    fn test_seldepth() {
+        let mut eng = ThreadedSearch::new();
+        let mut extended = 0;
+        for epd in Catalog::win_at_chess().into_iter().take(10) {
+            eng.new_game();
+            let res = eng.search(epd.clone(), TimeControl::Depth(5)).unwrap();
+            if res.score().is_some_and(|sc| sc.is_mate()) {
+                continue;
+            }
+            assert!(res.seldepth >= res.depth, "{res} for {epd}");
+            for info in res.infos.iter().filter(|i| i.kind == InfoKind::Pv) {
+                assert!(info.seldepth >= info.depth, "{info:?} for {epd}");
+            }
+            extended += (res.seldepth > res.depth) as i32;
+        }
+        // quiescence extends the tactical lines beyond the nominal depth
+        assert!(extended >= 5, "{extended}");
+    }
+
     #[test]
     fn test_threading() {
         for i in [1, 2, 3, 4, 8, 16, 32].into_iter() {
@@ -533,6 +822,24 @@ mod tests {
         assert!(moves.contains(&bm.as_str()), "{bm}");
     }
 
+    #[test]
+    fn test_show_wdl() {
+        let mut eng = ThreadedSearch::new();
+        let pv_infos = |eng: &mut ThreadedSearch| {
+            let sr = eng.search(Catalog::starting_position(), TimeControl::Depth(4)).unwrap();
+            sr.infos.into_iter().filter(|i| i.kind == InfoKind::Pv).collect_vec()
+        };
+        assert!(pv_infos(&mut eng).iter().all(|i| i.wdl.is_none() && i.tbhits.is_some()));
+        eng.set_option("UCI_ShowWDL", "true").unwrap();
+        let infos = pv_infos(&mut eng);
+        assert!(!infos.is_empty());
+        for info in &infos {
+            let wdl = info.wdl.unwrap();
+            assert_eq!(wdl.n(), 1000);
+            assert!(info.to_uci().contains(&format!("wdl {} {} {}", wdl.w, wdl.d, wdl.l)));
+        }
+    }
+
     #[test]
     fn jons_chess_problem() {
         let pos = Epd::parse_epd("2r2k2/5pp1/3p1b1p/2qPpP2/1p2B2P/pP3P2/2P1R3/2KRQ3 b - - 0 1").unwrap();
@@ -10,6 +10,18 @@ use serde::{Deserialize, Serialize};
 
 use super::algo::Search;
 
+/// The extensions along the search path to a ply: their total, and the consecutive check
+/// extensions of the side that moved into it
+#[derive(Copy, Clone, Debug, Default)]
+struct PathExtension {
+    total:  Ply,
+    checks: Ply,
+}
+
+/// Check extensions are capped at `check_max_in_row` for one side's successive checks, and
+/// all extensions at `max_path_extend` along any one line, so that long forced checking lines
+/// (perpetuals in particular) cannot spiral the search. A proven perpetual is scored as a draw
+/// by the repetition detector.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Extensions {
@@ -21,6 +33,8 @@ pub struct Extensions {
     check_see:            bool,
     check_see_threshold:  Score,
     check_only_captures:  bool,
+    check_max_in_row:     Ply,
+    max_path_extend:      Ply,
     promo_enabled:        bool,
     promo_max_depth:      Ply,
     near_promo_enabled:   bool,
@@ -33,6 +47,9 @@ pub struct Extensions {
     pawn_double_attacks:    bool,
 
     pv_enabled: bool,
+
+    #[serde(skip)]
+    path: Vec<PathExtension>,
 }
 
 impl Default for Extensions {
@@ -41,6 +58,8 @@ impl Default for Extensions {
             enabled:                true,
             check_max_depth:        2,
             check_only_captures:    false,
+            check_max_in_row:       6,
+            max_path_extend:        16,
             check_see_threshold:    Score::zero(),
             check_see:              false,
             gives_check_enabled:    true,
@@ -56,6 +75,7 @@ impl Default for Extensions {
             recapture_max_depth:    3,
             recapture_only_pv_node: false,
             recapture_same_square:  true,
+            path:                   Vec::new(),
         }
     }
 }
@@ -66,6 +86,8 @@ impl Configurable for Extensions {
         self.enabled.set(p.get("enabled"))?;
         self.check_max_depth.set(p.get("check_max_depth"))?;
         self.check_only_captures.set(p.get("check_only_captures"))?;
+        self.check_max_in_row.set(p.get("check_max_in_row"))?;
+        self.max_path_extend.set(p.get("max_path_extend"))?;
         self.check_see_threshold.set(p.get("check_see_threshold"))?;
         self.check_see.set(p.get("check_see"))?;
         self.gives_check_enabled.set(p.get("gives_check_enabled"))?;
@@ -115,6 +137,14 @@ impl Search {
         if !self.ext.enabled || n.is_qs() {
             return 0;
         }
+        let ply = n.ply as usize;
+        if self.ext.path.len() < ply + 2 {
+            self.ext.path.resize(ply + 2, PathExtension::default());
+        }
+        let parent = self.ext.path[ply];
+        // our own previous move led to the grandparent
+        let our_checks = ply.checked_sub(1).map_or(0, |gp| self.ext.path[gp].checks);
+        let mut check_ext = false;
         if self.ext.pv_enabled && n.depth == 1 && mv_num == 1 {
             ext += 1;
         }
@@ -125,9 +155,11 @@ impl Search {
             if n.depth <= self.ext.check_max_depth
                 && (!self.ext.check_only_captures || mv.is_capture())
                 && (!self.ext.check_see || self.eval.eval_move_see(mv, before) >= self.ext.check_see_threshold)
+                && our_checks < self.ext.check_max_in_row
             {
                 // algo.search_stats().inc_ext_check(n.ply);
                 ext += 1;
+                check_ext = true;
             }
         }
 
@@ -190,12 +222,124 @@ impl Search {
         //     // search_stats.inc_ext_check(node.ply);
         //     extend += self.ext.promo_extend;
         // }
-        std::cmp::min(ext, self.ext.max_extend)
+        let ext = ext
+            .min(self.ext.max_extend)
+            .min(self.ext.max_path_extend - parent.total)
+            .max(0);
+        self.ext.path[ply + 1] = PathExtension {
+            total:  parent.total + ext,
+            checks: if check_ext && ext > 0 { our_checks + 1 } else { 0 },
+        };
+        ext
+    }
+
+    /// A null move is never extended and ends the run of checks. Its child reads the path
+    /// extension, so it is written here rather than left over from an earlier sibling
+    #[inline]
+    pub fn extend_null_move(&mut self, n: &Node) {
+        let ply = n.ply as usize;
+        if self.ext.path.len() < ply + 2 {
+            self.ext.path.resize(ply + 2, PathExtension::default());
+        }
+        self.ext.path[ply + 1] = PathExtension {
+            total:  self.ext.path[ply].total,
+            checks: 0,
+        };
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use odonata_base::catalog::Catalog;
+    use odonata_base::domain::timecontrol::TimeControl;
+    use odonata_base::other::tags::EpdOps as _;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    /// the extensions along a perpetual check, with checks extended at any depth
+    #[test]
+    fn test_extension_caps() -> Result<()> {
+        let mut eng = ThreadedSearch::new();
+        let search = &mut eng.search;
+        search.ext.check_max_depth = 100;
+        let epd = Catalog::draws()[0].clone(); // DR.01
+        let mut b = epd.board();
+        let line = b.parse_san_variation(&"Qe8+ Kh7 Qg6+ Kh8 ".repeat(10))?;
+
+        let mut total = 0;
+        let mut in_row = 0;
+        for (ply, mv) in line.moves().enumerate() {
+            let n = Node {
+                ply: ply as Ply,
+                depth: 1,
+                ..Node::root(1)
+            };
+            let after = b.make_move(mv);
+            let ext = search.extend(&b, &after, mv, MoveType::Quiet, 1, &n, Move::new_null());
+            total += ext;
+            if ply % 2 == 0 {
+                in_row = if ext > 0 { in_row + 1 } else { 0 };
+                assert!(
+                    in_row <= search.ext.check_max_in_row,
+                    "{in_row} checks in a row at ply {ply}"
+                );
+            }
+            b = after;
+        }
+        assert_eq!(total, search.ext.max_path_extend);
+
+        // a null move's child reads a fresh path extension, not an earlier sibling's
+        let b = epd.board();
+        let mv = b.parse_san_move("Qe8+")?;
+        let n = Node::root(1);
+        assert_eq!(
+            search.extend(&b, &b.make_move(mv), mv, MoveType::Quiet, 1, &n, Move::new_null()),
+            1
+        );
+        assert_eq!(search.ext.path[1].checks, 1);
+        search.extend_null_move(&n);
+        assert_eq!(search.ext.path[1].checks, 0);
+        assert_eq!(search.ext.path[1].total, 0);
+        Ok(())
+    }
+
+    /// with both caps at zero, check extensions never apply
+    #[test]
+    fn test_extension_caps_in_search() -> Result<()> {
+        let epd = Catalog::draws()[0].clone(); // DR.01
+        let search_nodes = |settings: &[(&str, &str)]| -> Result<u64> {
+            let mut eng = ThreadedSearch::new();
+            eng.configure(settings.iter().map(|&(k, v)| (k.into(), v.into())).collect())?;
+            Ok(eng.search(epd.clone(), TimeControl::Depth(6))?.nodes)
+        };
+        let capped = search_nodes(&[
+            ("ext.check_max_depth", "100"),
+            ("ext.check_max_in_row", "0"),
+            ("ext.max_path_extend", "0"),
+        ])?;
+        let unextended = search_nodes(&[("ext.gives_check_enabled", "false")])?;
+        assert_eq!(capped, unextended);
+        Ok(())
+    }
+
+    /// perpetual checks, proven drawn with checks extended at any depth
+    #[test]
+    fn test_perpetual_checks() -> Result<()> {
+        for (epd, depth) in Catalog::draws().into_iter().zip([8, 12]) {
+            let mut eng = ThreadedSearch::new();
+            eng.configure([("ext.check_max_depth".into(), "100".into())].into())?;
+            let res = eng.search(epd.clone(), TimeControl::Depth(depth))?;
+            let score = res.score().unwrap();
+            assert!(epd.moves("bm").unwrap().contains(&res.supplied_move), "{epd}\n{res:#}");
+            assert!(
+                score > Score::from_cp(-50) && score < Score::from_cp(50),
+                "{score} {epd}\n{res:#}"
+            );
+        }
+        Ok(())
+    }
 
     // use crate::search::timecontrol::*;
 
@@ -9,6 +9,8 @@ use odonata_base::prelude::*;
 use strum_macros::EnumString;
 
 use super::algo::Search;
+use super::pruning_guard::{MaterialGuard, PruningGuard};
+use super::volatility::Volatility;
 
 // 5+0.04 => 165/1600
 // 10+0.08 => 82/1189 (3000 sims)
@@ -46,6 +48,7 @@ pub struct Futility {
     pub margin2:          i32,
     pub margin3:          i32,
     pub margin4:          i32,
+    guard:                PruningGuard,
 }
 
 impl Component for Futility {
@@ -83,6 +86,7 @@ impl Default for Futility {
             prune_extensions:     false,
             prune_fw_node:        true,
             prune_remaining:      false,
+            guard:                PruningGuard::default().with_soften(100),
         }
     }
 }
@@ -113,6 +117,7 @@ impl Configurable for Futility {
         self.prune_extensions.set(p.get("prune_extensions"))?;
         self.prune_fw_node.set(p.get("prune_fw_node"))?;
         self.prune_remaining.set(p.get("prune_remaining"))?;
+        self.guard.set(p.get("guard"))?;
         Ok(p.is_modified())
     }
 }
@@ -236,6 +241,7 @@ impl Search {
         before: &Board,
         after: &Board,
         eval: Score,
+        vol: i32,
         n: &Node,
         ext: Ply,
     ) -> Option<Score> {
@@ -297,15 +303,29 @@ impl Search {
             return None;
         }
 
-        // safety margin depends on how far away we are from leaf node
-        let margin = Score::from_cp(match n.depth {
+        // safety margin depends on how far away we are from leaf node, and how volatile the position
+        let margin = match n.depth {
             d if d <= 0 => self.futility.margin_qs,
             1 => self.futility.margin1,
             2 => self.futility.margin2,
             3 => self.futility.margin3,
             4 => self.futility.margin4,
             d => self.futility.margin4 + self.futility.margin_ply * (d - 4),
-        });
+        };
+        let mut margin = Score::from_cp(Volatility::scale(margin, vol));
+
+        // risky endings: widen the margin (or dont prune at all)
+        if let Some(guard) = self.futility.guard.check(before) {
+            Metrics::incr_node(n, match guard {
+                MaterialGuard::PawnEnding => Event::FutilityGuardPawnEnding,
+                MaterialGuard::KingPawns => Event::FutilityGuardKingPawns,
+                MaterialGuard::FewPieces => Event::FutilityGuardFewPieces,
+            });
+            if self.futility.guard.soften == 0 {
+                return None;
+            }
+            margin = margin + Score::from_cp(self.futility.guard.soften);
+        }
 
         // not a capture or promo => gain = 0
         // let gain = before.eval_move_material(&self.eval, mv);
@@ -1,18 +1,25 @@
 use std::fmt;
 
+use odonata_base::boards::movegen::MoveGenOrder;
 use odonata_base::boards::Position;
 use odonata_base::domain::info::{Info, InfoKind};
 use odonata_base::domain::node::{Counter, Event, Node};
+use odonata_base::domain::staticeval::StaticEval as _;
+use odonata_base::domain::wdl::ScoreWdl;
 use odonata_base::infra::component::{Component, State};
 use odonata_base::infra::metric::Metrics;
+use odonata_base::infra::paranoid;
 use odonata_base::infra::utils::calculate_branching_factor_by_nodes_and_depth;
 use odonata_base::other::outcome::Outcome;
 use odonata_base::piece::MAX_PLY;
 use odonata_base::prelude::*;
 use odonata_base::variation::MultiVariation;
+use rand::thread_rng;
 
 use super::algo::Search;
+use super::best_move_change::BestMoveChange;
 use super::search_results::Response;
+use super::summary::SearchSummary;
 use super::trail::Trail;
 
 #[derive(Clone, Debug)]
@@ -90,7 +97,7 @@ impl Search {
             // .map(|var| var.0.first().unwrap_or_default())
             // .unwrap_or_default(),
             outcome: Outcome::Unterminated,
-            tbhits: 0,
+            tbhits: search.tablebase.hits,
             nodes: search.clock.cumul_nodes_all_threads(),
             nodes_thread: search.clock.cumul_nodes_this_thread(),
             nps: search.clock.cumul_knps_all_threads() * 1000,
@@ -104,6 +111,7 @@ impl Search {
             emt: search.clock.elapsed_search().time,
             input: search.response.input.clone(),
             tc: search.mte.time_control().clone(),
+            bm_change: None,
             // tree: Some(trail.take_tree()),
             // metrics: None,
         }
@@ -119,6 +127,51 @@ impl Search {
         let mut book_move = false;
         let mut infos = vec![];
 
+        let avoided = self
+            .learning
+            .avoid(&self.board, &mut self.restrictions, &mut thread_rng());
+        if !avoided.is_empty() {
+            self.controller.invoke_callback(&Info {
+                string_text: Some(format!("learning avoids {}", avoided.to_uci())),
+                ..Info::default()
+            });
+        }
+        let avoided = self.experience.avoid(&self.board, &mut self.restrictions);
+        if !avoided.is_empty() {
+            self.controller.invoke_callback(&Info {
+                string_text: Some(format!("experience avoids {}", avoided.to_uci())),
+                ..Info::default()
+            });
+        }
+        let prepared = self.prep.bias(&self.board, &mut self.restrictions);
+        if !prepared.is_empty() {
+            self.controller.invoke_callback(&Info {
+                string_text: Some(format!("prep prefers {}", prepared.to_uci())),
+                ..Info::default()
+            });
+        }
+        if let Some((wdl, kept)) = self.tablebase.filter_root_moves(&self.board, &mut self.restrictions) {
+            self.controller.invoke_callback(&Info {
+                string_text: Some(format!("tablebase {wdl} keeps {}", kept.to_uci())),
+                ..Info::default()
+            });
+        }
+        self.seed_from_pv_cache(pos);
+        let forced = self.forced_root_moves(pos);
+        if self.controller.shuffle_movegen {
+            let seed = match self.mte.is_deterministic() {
+                true => Self::MOVEGEN_SHUFFLE_SEED,
+                false => rand::random(),
+            };
+            MoveGenOrder::set_shuffle_seed(Some(seed));
+            if self.clock.thread_index == 0 {
+                self.controller.invoke_callback(&Info {
+                    string_text: Some(format!("move generation shuffled with seed {seed}")),
+                    ..Info::default()
+                });
+            }
+        }
+
         'outer: loop {
             // Metrics::flush_thread_local();
             self.set_state(State::StartDepthIteration(ply));
@@ -126,14 +179,17 @@ impl Search {
             // self.stats.new_iteration();
             let mut multi_pv = MultiVariation::new();
             self.restrictions.excluded_moves.clear();
+            trail.set_selective_depth(0);
 
             // multi_pv.resize_with(self.controller.multi_pv, Default::default);
             // let mut exit = false;
-            for _i in 0..self.controller.multi_pv {
-                let pv = if let Some(mv) = (ply == self.ids.start_ply)
-                    .then(|| self.opening_book.lookup(&self.board, &self.restrictions))
-                    .flatten()
-                {
+            let mut raise = None;
+            for i in 0..self.controller.multi_pv {
+                let book = match ply == self.ids.start_ply {
+                    true => self.book_move(pos, trail),
+                    false => None,
+                };
+                let mut pv = if let Some(mv) = book {
                     score = Score::zero();
                     book_move = true;
                     Variation::new().append(mv)
@@ -143,14 +199,33 @@ impl Search {
                         Err(_evt) => Score::INFINITY,
                     };
                     self.mte.estimate_iteration(ply + 1, &mut self.clock);
+                    if i == 0 {
+                        raise = self.root_raise;
+                    }
                     book_move = false;
                     trail.root_pv().clone()
                 };
 
+                // runtime integrity check - an illegal tail (say from TT corruption) is truncated
+                paranoid::check_pv(&self.board, &pv);
+                if let Some(illegal) = pv.first_illegal_ply(&self.board) {
+                    warn!(
+                        "truncating illegal pv {san} at ply {illegal}",
+                        san = pv.display_san(&self.board)
+                    );
+                    pv = pv.take(illegal);
+                }
+                debug!("depth {ply} pv {san}", san = pv.display_san(&self.board));
+
                 self.tt.rewrite_pv(&self.board, &pv);
 
                 let info = if score.is_finite() {
-                    // let sel_depth = Some(trail.selective_depth());
+                    // lines ending early (mates, draws) can leave every branch short of the horizon
+                    debug_assert!(
+                        book_move || pv.len() < ply as usize || trail.selective_depth() >= ply,
+                        "seldepth {sd} < depth {ply} for pv {pv}",
+                        sd = trail.selective_depth()
+                    );
                     #[allow(clippy::cast_possible_truncation)]
                     Info {
                         kind: InfoKind::Pv,
@@ -162,8 +237,10 @@ impl Search {
                         multi_pv: Some(self.restrictions.excluded_moves.len() + 1),
                         pv: Some(pv.clone()),
                         score: Some(score),
+                        wdl: self.controller.show_wdl.then(|| ScoreWdl::from_score(score)),
                         depth: Some(ply),
                         seldepth: Some(trail.selective_depth()),
+                        tbhits: Some(self.tablebase.hits),
                         ..Info::default()
                     }
                 } else {
@@ -175,6 +252,7 @@ impl Search {
                         nps: Some(self.clock.cumul_knps_all_threads() * 1000),
                         time_millis: Some(self.clock.elapsed_search().time.as_millis() as u64),
                         hashfull_per_mille: Some(self.tt.hashfull_per_mille()),
+                        tbhits: Some(self.tablebase.hits),
                         ..Info::default()
                     }
                 };
@@ -206,8 +284,33 @@ impl Search {
             if self.time_up_or_cancelled(ply, false).0 {
                 break 'outer;
             }
+            let bm_change = BestMoveChange::between(ply, &last_results, &sr, raise);
             last_results = sr;
-            if book_move || self.mte.probable_timeout(ply) || ply >= self.ids.end_ply || ply >= MAX_PLY / 2 {
+            last_results.bm_change = bm_change;
+            if let (Some((moves, counter)), Ok(mv)) = (&forced, last_results.supplied_move()) {
+                let single_move = *counter == Counter::FastReturnSingleMove;
+                if self.mte.is_fast_return(ply, single_move) && moves.contains(&mv) {
+                    Metrics::incr(*counter);
+                    self.controller.invoke_callback(&Info {
+                        string_text: Some(format!("fast return ({counter}) at depth {ply}")),
+                        ..Info::default()
+                    });
+                    break 'outer;
+                }
+            }
+            if let Some(score) = last_results.score().filter(|&s| self.mte.is_proven_tb_win(ply, s)) {
+                self.controller.invoke_callback(&Info {
+                    string_text: Some(format!("tablebase win {score} at depth {ply}")),
+                    ..Info::default()
+                });
+                break 'outer;
+            }
+            if book_move
+                || self.mte.probable_timeout(ply)
+                || self.mte.is_depth_capped(ply)
+                || ply >= self.ids.end_ply
+                || ply >= MAX_PLY / 2
+            {
                 break 'outer;
             }
             ply += self.ids.step_size;
@@ -218,6 +321,29 @@ impl Search {
         //     .make_engine_move(results.clone(), Duration::from_millis(results.time_millis)); // *self.mte.time_control());
 
         self.response = last_results;
+        MoveGenOrder::set_shuffle_seed(None);
+        // enable with RUST_LOG=bm_change=info
+        if let (0, Some(change)) = (self.clock.thread_index, &self.response.bm_change) {
+            info!(target: "bm_change", "{change}");
+        }
+
+        // learn from our own mistakes (helper threads' copies are discarded)
+        if let (false, 0, Ok(mv), Some(score)) = (
+            book_move,
+            self.clock.thread_index,
+            self.response.supplied_move(),
+            self.response.score(),
+        ) {
+            self.learning.record(pos, mv, score);
+            self.experience.record(&self.board, mv, score);
+            self.pv_cache.record(&self.board, &self.response.pv());
+            self.analysis_tree
+                .record(&self.board, &self.response.pv(), score, self.response.depth);
+            if self.analysis_tree.enabled && self.analysis_tree.tt_ply > 0 {
+                let entries = self.tt.subtree(&self.board, self.analysis_tree.tt_ply, false);
+                self.analysis_tree.record_tt(&self.board, entries);
+            }
+        }
 
         // capture the piece that is the best move
         if Metrics::metrics_enabled() {
@@ -236,12 +362,35 @@ impl Search {
             }
         }
 
+        // quick console view of what pruned the (last iteration's) tree
+        if self.controller.debug_tree {
+            let table = trail.chess_tree.prune_heat_table().to_string();
+            for line in table.lines() {
+                self.controller.invoke_callback(&Info {
+                    string_text: Some(line.to_string()),
+                    ..Info::default()
+                });
+            }
+        }
+
         let info = Info {
             kind: InfoKind::BestMove,
             pv: Some(self.response.pv()),
             ..Info::default()
         };
         self.controller.invoke_callback(&info);
+
+        // "why this move", for the console
+        if self.controller.show_summary && self.clock.thread_index == 0 {
+            if let Some(summary) = SearchSummary::new(&self.response, self.mte.allotted()) {
+                for line in summary.to_string().lines() {
+                    self.controller.invoke_callback(&Info {
+                        string_text: Some(line.to_string()),
+                        ..Info::default()
+                    });
+                }
+            }
+        }
         // if self.max_depth > 0
         //     && !progress.outcome.is_game_over()
         //     && progress.bm().is_null()
@@ -250,6 +399,91 @@ impl Search {
         // }
     }
 
+    /// move generation shuffle seed used in deterministic mode
+    const MOVEGEN_SHUFFLE_SEED: u64 = 1;
+
+    /// the only root move, or the recaptures after the opponent started an even exchange.
+    /// With multi-pv all lines are wanted, so nothing is forced
+    /// The opening book move, if any. At fast time controls an unexpected static eval exits
+    /// the book, and at long time controls the book move is checked by a shallow search
+    fn book_move(&mut self, pos: &Position, trail: &mut Trail) -> Option<Move> {
+        let fast = self.opening_book.is_fast(self.mte.time_control());
+        let mv = self.opening_book.lookup(&self.board, &self.restrictions, fast)?;
+        if fast {
+            let eval = self.eval.static_eval(pos);
+            if self.opening_book.exit_on_eval(eval) {
+                self.controller.invoke_callback(&Info {
+                    string_text: Some(format!("book exited as eval {eval} is unexpected")),
+                    ..Info::default()
+                });
+                return None;
+            }
+            return Some(mv);
+        }
+        if self.opening_book.verify_nodes == 0 || !self.restrictions.is_none() {
+            return Some(mv);
+        }
+
+        // a timeout during verification leaves no time to search, so we trust the book
+        let budget = self.clock.cumul_nodes_this_thread() + self.opening_book.verify_nodes;
+        let (mut best, mut depth) = (Score::zero(), 0);
+        while self.clock.cumul_nodes_this_thread() < budget && depth < MAX_PLY / 2 {
+            depth += 1;
+            self.set_state(State::StartDepthIteration(depth));
+            best = match self.aspirated_search(trail, &mut pos.clone(), &mut Node::root(depth), best) {
+                Ok((score, _event)) => score,
+                Err(_event) => return Some(mv),
+            };
+        }
+        if trail.root_pv().first() == Some(mv) {
+            return Some(mv);
+        }
+        self.restrictions.search_moves.push(mv);
+        let book = self.aspirated_search(trail, &mut pos.clone(), &mut Node::root(depth), best);
+        self.restrictions.search_moves.clear();
+        let Ok((book_score, _event)) = book else {
+            return Some(mv);
+        };
+        if self.opening_book.is_verified(book_score, best) {
+            return Some(mv);
+        }
+        self.controller.invoke_callback(&Info {
+            string_text: Some(format!(
+                "book move {mv} rejected scoring {book_score} vs {best} at depth {depth}"
+            )),
+            ..Info::default()
+        });
+        None
+    }
+
+    fn forced_root_moves(&self, pos: &Position) -> Option<(MoveList, Counter)> {
+        if self.controller.multi_pv > 1 {
+            return None;
+        }
+        let bd = &self.board;
+        let moves: MoveList = bd
+            .legal_moves()
+            .iter()
+            .filter(|mv| self.restrictions.search_moves.is_empty() || self.restrictions.search_moves.contains(mv))
+            .copied()
+            .collect();
+        if moves.len() == 1 {
+            return Some((moves, Counter::FastReturnSingleMove));
+        }
+        let (prior, prior_bd) = (pos.prior_move()?, pos.prior_board()?);
+        let captured = prior.capture_piece(prior_bd)?;
+        if (captured.centipawns() - prior.mover().centipawns()).abs() > 100 {
+            return None;
+        }
+        let recaptures: MoveList = moves
+            .iter()
+            .filter(|mv| mv.is_capture() && mv.to() == prior.to())
+            .filter(|&&mv| self.eval.eval_move_see(mv, bd) >= Score::zero())
+            .copied()
+            .collect();
+        (!recaptures.is_empty()).then_some((recaptures, Counter::FastReturnRecapture))
+    }
+
     // pub fn exit_iteration(&mut self, ply: Ply, _s: Score) -> bool {
     //     self.time_up_or_cancelled(ply, false).0
     //         || self.mte.probable_timeout(ply)
@@ -11,6 +11,7 @@ use serde::{Deserialize, Serialize};
 use strum_macros::EnumString;
 
 use super::algo::Search;
+use super::continuation_history::ContinuationHistory;
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, from = "LmrConfig", into = "LmrConfig")]
@@ -58,6 +59,7 @@ pub struct LmrConfig {
     reduce_killer:         f32,
     reduce_bad_capture:    f32,
     reduce_hash:           f32,
+    reduce_history:        f32,
     min_remaining_depth:   i32,
     iir:                   f32,
 }
@@ -155,6 +157,7 @@ impl Default for LmrConfig {
             reduce_killer:         -0.85,
             reduce_bad_capture:    0.0,
             reduce_hash:           0.0,
+            reduce_history:        0.5,
             min_remaining_depth:   0,
             iir:                   5.0,
         }
@@ -243,6 +246,14 @@ impl Search {
             _ => 0.0,
         };
 
+        // reduce less (or more) by the continuation history, within +/- 2 * reduce_history.
+        // The move has been pushed onto the current variation, so is skipped.
+        if self.lmr.cfg.reduce_history != 0.0 {
+            let priors = ContinuationHistory::priors(&self.current_variation, 1);
+            let cmh = self.cont_history.score(before.color_us(), priors, mv);
+            reduce -= self.lmr.cfg.reduce_history * cmh as f32 / self.cont_history.max_value().max(1) as f32;
+        }
+
         if mv.mover_piece(before) == Piece::Pawn
             && mv.from().rank_number_as_white(before.color_us()) > self.lmr.cfg.max_pawn_rank as usize
         {
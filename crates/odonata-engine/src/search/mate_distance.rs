@@ -59,7 +59,8 @@ impl Search {
             n.alpha = std::cmp::max(n.alpha, Score::we_lose_in(n.ply));
         }
         if self.mate_dist.reduce_beta {
-            n.beta = std::cmp::min(n.beta, Score::we_win_in(n.ply));
+            // at best we mate with our next move
+            n.beta = std::cmp::min(n.beta, Score::we_win_in(n.ply + 1));
         }
 
         if n.alpha >= n.beta {
@@ -1,6 +1,10 @@
 pub mod algo;
 pub mod alphabeta;
+pub mod analysis_tree;
 pub mod aspiration;
+pub mod best_move_change;
+pub mod capture_history;
+pub mod continuation_history;
 pub mod controller;
 pub mod counter_move;
 pub mod engine;
@@ -15,10 +19,16 @@ pub mod mate_distance;
 pub mod move_orderer;
 pub mod move_time_estimator;
 pub mod nmp;
+pub mod pruning_guard;
+pub mod pruning_monitor;
+pub mod pv_cache;
 pub mod pvs;
 pub mod qs;
 pub mod razor;
 pub mod restrictions;
 pub mod reverse_futility;
 pub mod search_results;
+pub mod staged_moves;
+pub mod summary;
 pub mod trail;
+pub mod volatility;
@@ -10,6 +10,8 @@ use odonata_base::PreCalc;
 use serde::{Deserialize, Serialize};
 
 use super::algo::Search;
+use super::continuation_history::ContinuationHistory;
+use super::staged_moves::StagedMoves;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -33,6 +35,10 @@ pub struct MoveOrderer {
     pub castle_sort_bonus:           f32,
     pub pst_sort_factor:             f32,
     pub hh_sort_factor:              f32,
+    pub cmh_sort_factor:             f32,
+    pub capture_hist_sort_factor:    f32,
+    pub tt_prefetch:                 usize,
+    pub staged:                      bool,
     pub see_cutoff:                  Score,
     pub qsearch_see_cutoff:          Score,
     pub order:                       Vec<MoveType>,
@@ -67,6 +73,10 @@ impl Default for MoveOrderer {
             castle_sort_bonus:           0.0,
             pst_sort_factor:             0.0,
             hh_sort_factor:              1000.0,
+            cmh_sort_factor:             10.0,
+            capture_hist_sort_factor:    0.05,
+            tt_prefetch:                 2,
+            staged:                      true,
             // picker:                      Stack::<OrderedMoveList>::default(),
             order:                       MoveType::vec_from_string("SHIGKPqBE").unwrap(), // , SHICKPQE, SHIGKPQBE
             qorder:                      MoveType::vec_from_string("SIgE").unwrap(),      //
@@ -101,6 +111,10 @@ impl Configurable for MoveOrderer {
         self.castle_sort_bonus.set(p.get("castle_sort_bonus"))?;
         self.pst_sort_factor.set(p.get("pst_sort_factor"))?;
         self.hh_sort_factor.set(p.get("hh_sort_factor"))?;
+        self.cmh_sort_factor.set(p.get("cmh_sort_factor"))?;
+        self.capture_hist_sort_factor.set(p.get("capture_hist_sort_factor"))?;
+        self.tt_prefetch.set(p.get("tt_prefetch"))?;
+        self.staged.set(p.get("staged"))?;
         // self.order.set(p.get("order"))?;  // BUG
         // self.qorder.set(p.get("qorder"))?;
         // self.qorder_evasions.set(p.get("qorder_evasions"))?;
@@ -125,6 +139,7 @@ impl fmt::Display for MoveOrderer {
         writeln!(f, "mvv_lva          : {}", self.mvv_lva)?;
         writeln!(f, "see_cutoff       : {}", self.see_cutoff)?;
         writeln!(f, "qs_see_cutoff    : {}", self.qsearch_see_cutoff)?;
+        writeln!(f, "staged           : {}", self.staged)?;
         writeln!(f, "order            : {}", MoveType::slice_to_string(&self.order))?;
         writeln!(f, "qorder           : {}", MoveType::slice_to_string(&self.qorder))?;
         writeln!(f, "thread           : {}", self.thread)?;
@@ -252,6 +267,12 @@ impl MoveOrderer {
 
         score += self.hh_sort_factor * algo.history.history_heuristic_bonus(c, &mv, n, b) as f32;
 
+        // counter-move and follow-up history, indexed by the previous two moves
+        if self.cmh_sort_factor != 0.0 {
+            let priors = ContinuationHistory::priors(&algo.current_variation, 0);
+            score += self.cmh_sort_factor * algo.cont_history.score(c, priors, mv) as f32;
+        }
+
         let cm = algo.counter_move.counter_move_unchecked(c, parent, mv, n);
         if cm > 0 {
             Metrics::incr_node(n, Event::MoveSortCounterMove);
@@ -341,8 +362,18 @@ impl Search {
 }
 
 // uses Move Orderer and MoveGen to present a sequence of moves
+//
+// the staged generator is held inline in place of the stage lists, so that creating the moves
+// at a node never allocates
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum OrderedMoveList {
+    Ordered(OrderedMoves),
+    Staged(StagedMoves),
+}
+
 #[derive(Clone, Debug, Default)]
-pub struct OrderedMoveList {
+pub struct OrderedMoves {
     qsearch:      bool,
     is_in_check:  bool,
     stage:        usize,
@@ -357,7 +388,11 @@ pub struct OrderedMoveList {
 
 impl MoveOrderer {
     pub fn create_sorted_moves(&self, n: Node, b: &Board, tt: Move, last: Move) -> OrderedMoveList {
-        OrderedMoveList {
+        // the staged generator replaces the `order` stages outside of quiescence
+        if self.staged && !n.is_qs() {
+            return OrderedMoveList::Staged(StagedMoves::new(n, tt, last));
+        }
+        OrderedMoveList::Ordered(OrderedMoves {
             qsearch: n.is_qs(),
             is_in_check: b.is_in_check(b.color_us()),
             stage: 0,
@@ -369,11 +404,31 @@ impl MoveOrderer {
             n,
             tt,
             last,
-        }
+        })
     }
 }
 
 impl OrderedMoveList {
+    pub fn next_move(&mut self, b: &Board, algo: &mut Search) -> Option<(MoveType, Move)> {
+        let t = Metrics::timing_start();
+        let m = match self {
+            OrderedMoveList::Staged(staged) => staged.next_move(b, &*algo),
+            OrderedMoveList::Ordered(ordered) => ordered.calc_next_move_(b, algo),
+        };
+        Metrics::profile(t, Timing::TimingSortMoves);
+        m
+    }
+
+    /// mvv-lva blended with the capture history, so captures that often fail high sort
+    /// ahead of others of similar value
+    #[inline]
+    pub fn capture_points(mv: Move, bd: &Board, algo: &Search) -> i32 {
+        let factor = algo.move_orderer.capture_hist_sort_factor;
+        mv.mvv_lva_score(bd) + (factor * algo.capt_history.score(mv, bd) as f32) as i32
+    }
+}
+
+impl OrderedMoves {
     fn ordering<'a>(&self, algo: &'a Search) -> &'a Vec<MoveType> {
         if self.qsearch {
             if self.is_in_check {
@@ -386,13 +441,6 @@ impl OrderedMoveList {
         }
     }
 
-    pub fn next_move(&mut self, b: &Board, algo: &mut Search) -> Option<(MoveType, Move)> {
-        let t = Metrics::timing_start();
-        let m = self.calc_next_move_(b, algo);
-        Metrics::profile(t, Timing::TimingSortMoves);
-        m
-    }
-
     fn calc_next_move_(&mut self, b: &Board, algo: &mut Search) -> Option<(MoveType, Move)> {
         let move_type = self.ordering(algo)[self.stage];
         if self.index < self.moves.len() {
@@ -400,7 +448,7 @@ impl OrderedMoveList {
             // we dont sort killers
             // || move_type == 'b' as b is sorted by reverse anyway due to push and they are bad captures
             {
-                Self::sort_one_capture_move(self.index, &mut self.moves, self.last, b);
+                Self::sort_one_capture_move(self.index, &mut self.moves, self.last, b, algo);
             }
             if move_type == MoveType::GoodCaptureUpfrontSorted || move_type == MoveType::GoodCapture {
                 let mv = self.moves[self.index];
@@ -435,13 +483,13 @@ impl OrderedMoveList {
     }
 
     #[inline]
-    fn sort_one_capture_move(i: usize, moves: &mut MoveList, _last: Move, bd: &Board) {
+    fn sort_one_capture_move(i: usize, moves: &mut MoveList, _last: Move, bd: &Board, algo: &Search) {
         if let Some(j) = moves
             .iter()
             .enumerate()
             .skip(i)
             // .max_by_key(|(_n, &mv)| mv.mvv_lva_score(bd) - if mv.to() == last.to() { 0 } else { 0 })
-            .max_by_key(|(_n, &mv)| mv.mvv_lva_score(bd))
+            .max_by_key(|(_n, &mv)| OrderedMoveList::capture_points(mv, bd, algo))
             .map(|(n, _mv)| n)
         {
             moves.swap(i, j);
@@ -492,7 +540,9 @@ impl OrderedMoveList {
                     .iter()
                     .filter(|&m| Move::is_capture(m))
                     .for_each(|&m| moves.push(m));
-                moves.sort_by_cached_key(|m| Move::mvv_lva_score(m, b) + if m.to() == last.to() { 0 } else { 0 });
+                moves.sort_by_cached_key(|&m| {
+                    OrderedMoveList::capture_points(m, b, algo) + if m.to() == last.to() { 0 } else { 0 }
+                });
                 moves.reverse();
                 if algo.move_orderer.thread == 1 && moves.len() >= 2 {
                     moves.swap(0, 1);
@@ -606,7 +656,7 @@ impl OrderedMoveList {
                     .iter()
                     .filter(|m| Move::is_capture(m))
                     .for_each(|&m| moves.push(m));
-                moves.sort_unstable_by_key(|mv| mv.mvv_lva_score(b));
+                moves.sort_unstable_by_key(|&mv| OrderedMoveList::capture_points(mv, b, algo));
                 moves.reverse();
                 if algo.move_orderer.thread == 1 && moves.len() >= 2 {
                     moves.swap(0, 1);
@@ -625,6 +675,15 @@ impl OrderedMoveList {
             }
         };
 
+        // start loading the tt entries of the first few children (hashed without making the
+        // moves), so that memory latency overlaps with the search of the moves before them
+        let k = algo.move_orderer.tt_prefetch;
+        if k > 0 && !self.qsearch {
+            for &mv in self.moves.iter().take(k) {
+                algo.tt.prefetch(b.hash_after(mv));
+            }
+        }
+
         ///////////////////////////////////////
     }
 }
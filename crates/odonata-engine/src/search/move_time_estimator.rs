@@ -24,6 +24,13 @@ pub struct MoveTimeEstimator {
     check_every:            u64,
     use_last_2_iters:       bool,
     use_moves_to_go:        bool,
+    low_time_ms:            u64,
+    low_time_max_depth:     Ply,
+    low_time_headroom_perc: u32,
+    fast_return:            bool,
+    single_move_depth:      Ply,
+    recapture_depth:        Ply,
+    tb_win_depth:           Ply,
 
     #[serde(skip)]
     time_control: TimeControl,
@@ -63,6 +70,13 @@ impl Default for MoveTimeEstimator {
             perc_of_time_adv:       62,
             use_last_2_iters:       false,
             use_moves_to_go:        true,
+            low_time_ms:            100,
+            low_time_max_depth:     8,
+            low_time_headroom_perc: 60,
+            fast_return:            true,
+            single_move_depth:      1,
+            recapture_depth:        6,
+            tb_win_depth:           8,
 
             estimate_move_time: Duration::default(),
             elapsed_search:     Duration::default(),
@@ -89,6 +103,13 @@ impl Configurable for MoveTimeEstimator {
         self.perc_of_time_adv.set(p.get("perc_of_time_adv"))?;
         self.use_last_2_iters.set(p.get("use_last_2_iters"))?;
         self.use_moves_to_go.set(p.get("use_moves_to_go"))?;
+        self.low_time_ms.set(p.get("low_time_ms"))?;
+        self.low_time_max_depth.set(p.get("low_time_max_depth"))?;
+        self.low_time_headroom_perc.set(p.get("low_time_headroom_perc"))?;
+        self.fast_return.set(p.get("fast_return"))?;
+        self.single_move_depth.set(p.get("single_move_depth"))?;
+        self.recapture_depth.set(p.get("recapture_depth"))?;
+        self.tb_win_depth.set(p.get("tb_win_depth"))?;
         Ok(p.is_modified())
     }
 }
@@ -128,6 +149,10 @@ impl fmt::Display for MoveTimeEstimator {
         writeln!(f, "const moves rem. : {}", self.moves_rem)?;
         writeln!(f, "% of time adv    : {}", self.perc_of_time_adv)?;
         writeln!(f, "allotted for mv  : {}", Formatting::duration(self.allotted()))?;
+        writeln!(f, "low time ms      : {}", self.low_time_ms)?;
+        writeln!(f, "low time max dep : {}", self.low_time_max_depth)?;
+        writeln!(f, "low time headroom: {}%", self.low_time_headroom_perc)?;
+        writeln!(f, "is low time      : {}", self.is_low_time())?;
         writeln!(
             f,
             "est move time    : {}",
@@ -162,6 +187,10 @@ impl MoveTimeEstimator {
         &self.time_control
     }
 
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
     fn elapsed_with_margin(&self, clock: &mut Clock) -> Duration {
         let mut elapsed = clock.elapsed_search().time + Duration::from_millis(self.move_overhead_ms);
         // if in nodestime then convert nodes to time. nodestime is nodes per millisecond
@@ -184,14 +213,18 @@ impl MoveTimeEstimator {
         match self.time_control {
             TimeControl::DefaultTime => false,
             TimeControl::Depth(_max_ply) => false, // ply > max_ply,  // dont cause an abort on last iteration
-            TimeControl::SearchTime(duration) => 10 * self.elapsed_with_margin(c) > duration * 9 && !self.pondering(),
+            TimeControl::SearchTime(duration) => {
+                10 * self.elapsed_with_margin(c) > self.with_headroom(duration) * 9 && !self.pondering()
+            }
             TimeControl::NodeCount(n) => c.elapsed_search().nodes >= n,
             TimeControl::DepthNodeCount(_, n) => c.elapsed_search().nodes >= n,
             TimeControl::Instructions(n) => c.elapsed_search().instructions >= n,
             TimeControl::Cycles(n) => c.elapsed_search().cycles >= n,
             TimeControl::Infinite => false,
             TimeControl::MateIn(_) => false,
-            TimeControl::UciFischer { .. } => self.elapsed_with_margin(c) > self.allotted() && !self.pondering(),
+            TimeControl::UciFischer { .. } => {
+                self.elapsed_with_margin(c) > self.with_headroom(self.allotted()) && !self.pondering()
+            }
             TimeControl::FischerMulti { .. } => panic!("FischerMulti"),
         }
     }
@@ -248,12 +281,60 @@ impl MoveTimeEstimator {
                 let (_time, _inc) = rt.our_color.chooser_wb((rt.wtime, rt.winc), (rt.btime, rt.binc));
                 self.estimate_move_time > self.allotted()
                     && !self.pondering.load(atomic::Ordering::SeqCst)
-                    && ply >= self.min_ply_for_estimation
+                    && (ply >= self.min_ply_for_estimation || self.is_low_time())
             }
             _ => false,
         }
     }
 
+    /// an ultra-short budget (eg hyperbullet) where a regular search risks losing on time.
+    /// The search skips aspiration, caps depth growth and leaves headroom within the budget
+    pub fn is_low_time(&self) -> bool {
+        let low_time = Duration::from_millis(self.low_time_ms);
+        match &self.time_control {
+            TimeControl::SearchTime(duration) => *duration < low_time && !self.pondering(),
+            TimeControl::UciFischer(_) => self.allotted() < low_time && !self.pondering(),
+            _ => false,
+        }
+    }
+
+    /// under low time, only allow a proportion of the budget, as overheads
+    /// (thread start-up, gui latency) dominate
+    fn with_headroom(&self, budget: Duration) -> Duration {
+        match self.is_low_time() {
+            true => budget * self.low_time_headroom_perc / 100,
+            false => budget,
+        }
+    }
+
+    pub fn is_depth_capped(&self, ply: Ply) -> bool {
+        self.is_low_time() && ply >= self.low_time_max_depth
+    }
+
+    /// a forced move (the only legal move, or a recapture confirmed by a shallow search) need
+    /// not use the time allotted. Only for clock based time controls, and not whilst pondering
+    pub fn is_fast_return(&self, ply: Ply, single_move: bool) -> bool {
+        let min_depth = match single_move {
+            true => self.single_move_depth,
+            false => self.recapture_depth,
+        };
+        self.fast_return
+            && matches!(self.time_control, TimeControl::UciFischer(_))
+            && !self.pondering()
+            && ply >= min_depth
+    }
+
+    /// a tablebase win is proven, so once the search has found a way to progress (by
+    /// `tb_win_depth`) more time is wasted. As for a fast return, for clock based time controls
+    pub fn is_proven_tb_win(&self, ply: Ply, score: Score) -> bool {
+        self.fast_return
+            && matches!(self.time_control, TimeControl::UciFischer(_))
+            && !self.pondering()
+            && ply >= self.tb_win_depth
+            && score.is_tb()
+            && score > Score::zero()
+    }
+
     fn calc_from_remaining(&self, rt: &RemainingTime) -> Duration {
         let (time_us, inc) = rt.our_time_and_inc();
         let (time_them, _inc) = rt.their_time_and_inc();
@@ -295,7 +376,7 @@ impl MoveTimeEstimator {
         }
     }
 
-    fn allotted(&self) -> Duration {
+    pub fn allotted(&self) -> Duration {
         let zero = Duration::ZERO;
         match &self.time_control {
             TimeControl::DefaultTime => zero,
@@ -323,6 +404,8 @@ impl MoveTimeEstimator {
 
 #[cfg(test)]
 mod tests {
+    use odonata_base::infra::utils::Uci;
+
     use super::*;
 
     #[test]
@@ -332,4 +415,60 @@ mod tests {
         println!("{:?}", mte);
         println!("{:#?}", mte);
     }
+
+    #[test]
+    fn test_fast_return() {
+        let mut mte = MoveTimeEstimator::default();
+        mte.set_time_control(TimeControl::parse_uci("wtime 10000 btime 10000").unwrap());
+        assert!(mte.is_fast_return(1, true));
+        assert!(!mte.is_fast_return(1, false));
+        assert!(mte.is_fast_return(6, false));
+
+        mte.set_shared_ponder(true);
+        assert!(!mte.is_fast_return(6, true));
+        mte.set_shared_ponder(false);
+
+        mte.set_time_control(TimeControl::Depth(6));
+        assert!(!mte.is_fast_return(6, true));
+    }
+
+    #[test]
+    fn test_proven_tb_win() {
+        let mut mte = MoveTimeEstimator::default();
+        mte.set_time_control(TimeControl::parse_uci("wtime 10000 btime 10000").unwrap());
+        assert!(mte.is_proven_tb_win(8, Score::tb_win_in(3)));
+        assert!(!mte.is_proven_tb_win(7, Score::tb_win_in(3)));
+        assert!(!mte.is_proven_tb_win(8, Score::tb_lose_in(3)));
+        assert!(!mte.is_proven_tb_win(8, Score::from_cp(900)));
+        assert!(!mte.is_proven_tb_win(8, Score::we_win_in(5)));
+
+        mte.set_time_control(TimeControl::Depth(10));
+        assert!(!mte.is_proven_tb_win(8, Score::tb_win_in(3)));
+    }
+
+    #[test]
+    fn test_low_time() {
+        let mut mte = MoveTimeEstimator::default();
+        mte.set_time_control(TimeControl::SearchTime(Duration::from_millis(50)));
+        assert!(mte.is_low_time());
+        assert!(mte.is_depth_capped(8));
+        assert!(!mte.is_depth_capped(7));
+        assert_eq!(mte.with_headroom(Duration::from_millis(50)), Duration::from_millis(30));
+
+        mte.set_shared_ponder(true);
+        assert!(!mte.is_low_time());
+        mte.set_shared_ponder(false);
+
+        mte.set_time_control(TimeControl::SearchTime(Duration::from_millis(500)));
+        assert!(!mte.is_low_time());
+        assert_eq!(
+            mte.with_headroom(Duration::from_millis(500)),
+            Duration::from_millis(500)
+        );
+
+        mte.set_time_control(TimeControl::parse_uci("wtime 300 btime 300").unwrap());
+        assert!(mte.is_low_time());
+        mte.set_time_control(TimeControl::Depth(3));
+        assert!(!mte.is_low_time());
+    }
 }
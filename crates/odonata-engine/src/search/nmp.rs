@@ -13,6 +13,7 @@ use strum_macros::EnumString;
 use tracing::instrument;
 
 use super::algo::Search;
+use super::pruning_guard::{MaterialGuard, PruningGuard};
 use super::trail::Trail;
 use crate::cache::tt2::{EvalFromTt, TtNode, TtScore};
 
@@ -76,6 +77,7 @@ pub struct NullMovePruning {
     b:                     f32,
     c:                     f32,
     zugzwang:              Zugzwang,
+    guard:                 PruningGuard,
 }
 
 impl Configurable for NullMovePruning {
@@ -97,6 +99,7 @@ impl Configurable for NullMovePruning {
         self.a.set(p.get("a"))?;
         self.b.set(p.get("b"))?;
         self.c.set(p.get("c"))?;
+        self.guard.set(p.get("guard"))?;
         Ok(p.is_modified())
     }
 }
@@ -135,6 +138,7 @@ impl Default for NullMovePruning {
             b:                     0.198,
             c:                     0.00017,
             zugzwang:              Zugzwang::NonPawnNonPinned,
+            guard:                 PruningGuard::default().with_king_pawns(),
         }
     }
 }
@@ -179,6 +183,15 @@ impl NullMovePruning {
             return (false, "margin");
         }
 
+        if let Some(guard) = self.guard.check(b) {
+            Metrics::incr_node(n, match guard {
+                MaterialGuard::PawnEnding => Event::NmpGuardPawnEnding,
+                MaterialGuard::KingPawns => Event::NmpGuardKingPawns,
+                MaterialGuard::FewPieces => Event::NmpGuardFewPieces,
+            });
+            return (false, "material guard");
+        }
+
         if self.zugzwang_check && self.zugzwang.is_maybe_zugzwang(b) {
             Metrics::incr_node(n, Event::NmpDeclineZugzwang);
             return (false, "zugzwang");
@@ -316,9 +329,11 @@ impl Search {
         pos.push_move(mv);
         self.current_variation.push(mv);
         trail.push_move(n, mv);
+        self.extend_null_move(n);
         // self.explainer.start(n, &self.current_variation);
         Metrics::incr_node(n, Event::NmpAttempt);
         let reduced_depth = std::cmp::max(n.depth - r - 1, 0);
+        let path_draws = self.path_draws;
 
         // we increment ply so that history tables etc work correctly
 
@@ -359,6 +374,7 @@ impl Search {
                     bt: BoundType::LowerCut,
                     bm: Move::new_null().to_inner(),
                     eval,
+                    ghi: self.path_draws != path_draws,
                 };
                 // remember this is the child board hash with child score,
                 // but we store it as parent board and negative score and bound,
@@ -376,7 +392,10 @@ impl Search {
 
 #[cfg(test)]
 mod tests {
+    use odonata_base::catalog::Catalog;
+    use odonata_base::infra::component::State;
     use odonata_base::infra::profiler::PerfProfiler;
+    use odonata_base::other::tags::{EpdOps as _, Tags};
     use test_log::test;
 
     use super::*;
@@ -421,6 +440,22 @@ mod tests {
         // );
     }
 
+    /// guards against null move pruning regressions in zugzwang positions
+    #[test]
+    fn test_nmp_zugzwangs() {
+        let mut eng = ThreadedSearch::new();
+        assert!(eng.search.nmp.enabled);
+        for epd in Catalog::zugzwangs() {
+            let Some(nodes) = epd.int(Tags::ACN) else {
+                continue;
+            };
+            eng.set_state(State::NewGame);
+            let res = eng.search(epd.clone(), TimeControl::NodeCount(nodes as u64)).unwrap();
+            let bm = epd.moves(Tags::BM).unwrap();
+            assert!(bm.contains(&res.supplied_move().unwrap()), "{res}\n{epd}");
+        }
+    }
+
     #[test]
     fn test_nmp_metrics() {
         let pos = Epd::from_board(fixture());
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use odonata_base::prelude::*;
+
+/// Why a guard fired, from the material on the board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MaterialGuard {
+    PawnEnding, // only kings and pawns on the board
+    KingPawns,  // side to move has only king and pawns (zugzwang risk)
+    FewPieces,  // fewer than min_pieces men (kings and pawns included)
+}
+
+/// Disables (or softens) a speculative pruning technique in endings where zugzwang
+/// or an exact horizon matters more than the nodes saved. Each pruner owns one, so the
+/// guards can be tuned separately, and each records its own trigger counts.
+#[derive(Clone, Debug)]
+pub struct PruningGuard {
+    pub enabled: bool,
+    pawn_ending: bool,
+    king_pawns:  bool,
+    min_pieces:  i32,
+    pub soften:  i32, // extra margin (cp) rather than declining, 0 => decline
+}
+
+impl Default for PruningGuard {
+    fn default() -> Self {
+        Self {
+            enabled:     true,
+            pawn_ending: true,
+            king_pawns:  false,
+            min_pieces:  0,
+            soften:      0,
+        }
+    }
+}
+
+impl Configurable for PruningGuard {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.pawn_ending.set(p.get("pawn_ending"))?;
+        self.king_pawns.set(p.get("king_pawns"))?;
+        self.min_pieces.set(p.get("min_pieces"))?;
+        self.soften.set(p.get("soften"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl fmt::Display for PruningGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+impl PruningGuard {
+    pub fn with_king_pawns(mut self) -> Self {
+        self.king_pawns = true;
+        self
+    }
+
+    pub fn with_soften(mut self, soften: i32) -> Self {
+        self.soften = soften;
+        self
+    }
+
+    #[inline]
+    pub fn check(&self, bd: &Board) -> Option<MaterialGuard> {
+        if !self.enabled {
+            return None;
+        }
+        let mat = bd.material();
+        if self.pawn_ending && mat.minors_and_majors().total_count() == 0 {
+            return Some(MaterialGuard::PawnEnding);
+        }
+        if self.king_pawns && mat.color(bd.color_us()).minors_and_majors().total_count() == 0 {
+            return Some(MaterialGuard::KingPawns);
+        }
+        if self.min_pieces > 0 && mat.total_count() < self.min_pieces {
+            return Some(MaterialGuard::FewPieces);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_pruning_guard() {
+        let guard = PruningGuard::default().with_king_pawns();
+        let pawn_ending = Board::parse_fen("8/8/8/2p1K3/1kP5/8/8/8 w - - 0 1").unwrap();
+        let king_pawns = Board::parse_fen("3k4/8/3K4/2P5/8/8/8/7r w - - 0 1").unwrap();
+        let their_king_pawns = Board::parse_fen("3k4/8/3K4/2P5/8/8/8/7r b - - 0 1").unwrap();
+        assert_eq!(guard.check(&pawn_ending), Some(MaterialGuard::PawnEnding));
+        assert_eq!(guard.check(&king_pawns), Some(MaterialGuard::KingPawns));
+        assert_eq!(guard.check(&their_king_pawns), None);
+        assert_eq!(guard.check(&Board::starting_pos()), None);
+
+        let mut guard = PruningGuard::default();
+        guard.set(Param::new("min_pieces", "6")).unwrap();
+        assert_eq!(guard.check(&king_pawns), Some(MaterialGuard::FewPieces));
+        assert_eq!(guard.check(&Board::starting_pos()), None);
+
+        guard.enabled = false;
+        assert_eq!(guard.check(&pawn_ending), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use odonata_base::boards::Position;
+use odonata_base::domain::node::Node;
+use odonata_base::infra::component::Component;
+use odonata_base::infra::metric::Event;
+use odonata_base::prelude::*;
+use strum_macros::Display;
+use tabled::builder::Builder;
+
+use super::algo::Search;
+use super::trail::Trail;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum Pruning {
+    RevFut,
+    Razor,
+    Nmp,
+    Futility,
+    Lmp,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Verdicts {
+    pub sampled: u64,
+    pub wrong:   u64,
+}
+
+impl Verdicts {
+    pub fn wrong_perc(&self) -> f64 {
+        100.0 * self.wrong as f64 / self.sampled.max(1) as f64
+    }
+}
+
+/// Re-searches a small sample of pruned nodes and moves at full depth, counting how often
+/// the pruning verdict (fail high or fail low) turned out to be wrong, by technique and depth.
+///
+/// Sampling is by position hash, so is repeatable. Verification searches disturb node
+/// counts, move ordering and timing, so this is for diagnostics (eg after a bench run) only.
+/// Verdicts are shared by the search threads, and kept until `clear`.
+#[derive(Clone, Debug)]
+pub struct PruningMonitor {
+    pub enabled: bool,
+    sample_perc: f64,
+    max_depth:   Ply,
+    verifying:   Option<Ply>, // ply of the node being re-searched
+    verdicts:    Arc<Mutex<BTreeMap<(Pruning, Ply), Verdicts>>>,
+}
+
+impl Default for PruningMonitor {
+    fn default() -> Self {
+        Self {
+            enabled:     false,
+            sample_perc: 1.0,
+            max_depth:   6,
+            verifying:   None,
+            verdicts:    Arc::default(),
+        }
+    }
+}
+
+impl Configurable for PruningMonitor {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.sample_perc.set(p.get("sample_perc"))?;
+        self.max_depth.set(p.get("max_depth"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for PruningMonitor {
+    fn new_game(&mut self) {
+        self.verifying = None;
+    }
+
+    fn new_position(&mut self) {
+        self.verifying = None;
+    }
+}
+
+impl fmt::Display for PruningMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bu = Builder::new();
+        bu.push_record(["pruning", "depth", "sampled", "wrong", "%"]);
+        for ((pruning, depth), v) in self.verdicts().iter() {
+            bu.push_record([
+                pruning.to_string(),
+                depth.to_string(),
+                v.sampled.to_string(),
+                v.wrong.to_string(),
+                format!("{:.1}", v.wrong_perc()),
+            ]);
+        }
+        writeln!(f, "{}", bu.build())
+    }
+}
+
+impl PruningMonitor {
+    pub fn verdicts(&self) -> BTreeMap<(Pruning, Ply), Verdicts> {
+        self.verdicts.lock().unwrap().clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.verdicts.lock().unwrap().clear();
+    }
+
+    /// node pruning is skipped at the node being re-searched, else it would just prune again
+    pub fn is_verifying(&self, ply: Ply) -> bool {
+        self.verifying == Some(ply)
+    }
+
+    fn should_sample(&self, b: &Board, n: &Node) -> bool {
+        if !self.enabled || self.verifying.is_some() || n.depth > self.max_depth {
+            return false;
+        }
+        let r = (b.hash().wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 40) as f64 / (1_u64 << 24) as f64;
+        100.0 * r < self.sample_perc
+    }
+
+    fn record(&self, pruning: Pruning, depth: Ply, wrong: bool) {
+        let mut verdicts = self.verdicts.lock().unwrap();
+        let v = verdicts.entry((pruning, depth)).or_default();
+        v.sampled += 1;
+        v.wrong += wrong as u64;
+    }
+}
+
+impl Search {
+    /// the node was pruned with score `pruned` (a fail high if >= beta, else a fail low)
+    pub fn verify_node_pruning(
+        &mut self,
+        pruning: Pruning,
+        trail: &mut Trail,
+        pos: &mut Position,
+        n: &Node,
+        last_move: Move,
+        pruned: Score,
+    ) -> Result<(), Event> {
+        if !self.pruning_monitor.should_sample(pos.board(), n) {
+            return Ok(());
+        }
+        self.pruning_monitor.verifying = Some(n.ply);
+        let res = self.alphabeta("verify", trail, pos, *n, last_move);
+        self.pruning_monitor.verifying = None;
+        let (s, _) = res?;
+        let wrong = if pruned >= n.beta { s < n.beta } else { s > n.alpha };
+        self.pruning_monitor.record(pruning, n.depth, wrong);
+        Ok(())
+    }
+
+    /// the move `mv` (already made in `pos`) was pruned as unable to raise alpha
+    pub fn verify_move_pruning(
+        &mut self,
+        pruning: Pruning,
+        trail: &mut Trail,
+        pos: &mut Position,
+        n: &Node,
+        mv: Move,
+        ext: Ply,
+    ) -> Result<(), Event> {
+        if !self.pruning_monitor.should_sample(pos.board(), n) {
+            return Ok(());
+        }
+        let before = pos.prior_board().unwrap().clone();
+        self.pruning_monitor.verifying = Some(n.ply);
+        self.repetition.push_move(mv, &before);
+        self.current_variation.push(mv);
+        trail.push_move(n, mv);
+        let res = self.alphabeta("verify", trail, pos, n.new_child().ext(ext).zw(), mv);
+        trail.pop_move(n, mv);
+        self.current_variation.pop();
+        self.repetition.pop();
+        self.pruning_monitor.verifying = None;
+        let (s, _) = res?;
+        self.pruning_monitor.record(pruning, n.depth, -s > n.alpha);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::Epd;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    #[test]
+    fn test_pruning_monitor() {
+        let mut eng = ThreadedSearch::new();
+        eng.search.pruning_monitor.enabled = true;
+        eng.search.pruning_monitor.sample_perc = 20.0;
+        eng.search(Epd::starting_pos(), TimeControl::Depth(7)).unwrap();
+        let verdicts = eng.search.pruning_monitor.verdicts();
+        assert!(!verdicts.is_empty());
+        assert!(verdicts.values().all(|v| v.wrong <= v.sampled));
+        println!("{}", eng.search.pruning_monitor);
+
+        eng.search.pruning_monitor.clear();
+        assert!(eng.search.pruning_monitor.verdicts().is_empty());
+    }
+}
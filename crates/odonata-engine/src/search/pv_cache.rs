@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use odonata_base::boards::Position;
+use odonata_base::domain::staticeval::StaticEval as _;
+use odonata_base::domain::BoundType;
+use odonata_base::infra::component::{Component, State};
+use odonata_base::prelude::*;
+
+use super::algo::Search;
+use crate::cache::tt2::{TtNode, TtScore};
+
+/// The principal variations of our recent searches this game, each keyed by the position
+/// expected after the opponent's reply (so the pv shifted by two plies).
+///
+/// A search of an expected position re-seeds the tt along the rest of the pv and tries its
+/// first move first at the root, so that after a ponder miss or a "Clear Hash" the first
+/// iterations don't thrash re-discovering the line. Seeded entries are depth 0 with a
+/// mate upper bound, so give move ordering hints but never a cutoff.
+///
+/// With `pv_file` set the cache is also saved after every search (one "hash pv" per line)
+/// and read at first use, so that it survives the engine being restarted mid-game.
+#[derive(Clone, Debug)]
+pub struct PvCache {
+    pub enabled:     bool,
+    pub pv_file:     PathBuf,
+    pub max_entries: usize,
+
+    entries:   VecDeque<(Hash, String)>,
+    loaded:    bool,
+    root_move: Option<Move>,
+}
+
+impl Default for PvCache {
+    fn default() -> Self {
+        Self {
+            enabled:     true,
+            pv_file:     PathBuf::new(),
+            max_entries: 16,
+            entries:     VecDeque::new(),
+            loaded:      false,
+            root_move:   None,
+        }
+    }
+}
+
+impl Configurable for PvCache {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.pv_file.set(p.get("pv_file"))?;
+        self.max_entries.set(p.get("max_entries"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for PvCache {
+    fn set_state(&mut self, s: State) {
+        use State::*;
+        match s {
+            NewGame => self.entries.clear(),
+            SetPosition => self.root_move = None,
+            StartSearch => {}
+            EndSearch => {}
+            StartDepthIteration(_) => {}
+            Shutdown => {}
+        }
+    }
+
+    fn new_game(&mut self) {}
+
+    fn new_position(&mut self) {}
+}
+
+impl fmt::Display for PvCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+impl PvCache {
+    pub fn is_persistent(&self) -> bool {
+        !self.pv_file.as_os_str().is_empty()
+    }
+
+    /// the first move of the cached pv, as a root move ordering hint
+    pub fn root_move(&self) -> Option<Move> {
+        self.root_move
+    }
+
+    /// keeps the pv of a search of board `b`, keyed by the position two plies on
+    pub fn record(&mut self, b: &Board, pv: &Variation) {
+        if !self.enabled || pv.len() <= 2 {
+            return;
+        }
+        let hash = b.make_moves(&pv.take(2)).hash();
+        self.entries.retain(|(h, _)| *h != hash);
+        self.entries.push_back((hash, pv.skip(2).to_uci()));
+        while self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+        if let Err(e) = self.save() {
+            warn!("failed to save pv cache: {e:#}");
+        }
+    }
+
+    /// the cached pv for this position, if any (and still legal)
+    pub fn lookup(&mut self, b: &Board) -> Option<Variation> {
+        if !self.enabled {
+            return None;
+        }
+        if !self.loaded {
+            self.loaded = true;
+            if let Err(e) = self.reload() {
+                warn!("failed to read pv cache: {e:#}");
+            }
+        }
+        let (_, pv) = self.entries.iter().find(|(h, _)| *h == b.hash())?;
+        b.parse_uci_variation(pv).ok()
+    }
+
+    /// reads the pv file, a missing file is an empty one
+    fn reload(&mut self) -> Result<()> {
+        if !self.is_persistent() || !self.pv_file.exists() {
+            return Ok(());
+        }
+        let text = fs_err::read_to_string(&self.pv_file)?;
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let (hash, pv) = line
+                .split_once(' ')
+                .with_context(|| format!("expected 'hash pv' in '{line}'"))?;
+            self.entries
+                .push_back((Hash::from_str_radix(hash, 16)?, pv.to_string()));
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        if self.is_persistent() {
+            let mut text = String::new();
+            for (hash, pv) in &self.entries {
+                writeln!(text, "{hash:016x} {pv}")?;
+            }
+            fs_err::write(&self.pv_file, text)?;
+        }
+        Ok(())
+    }
+}
+
+impl Search {
+    /// seeds the tt with move hints along the cached pv for this position, or failing that
+    /// the analysis tree's line, if there is one
+    pub fn seed_from_pv_cache(&mut self, pos: &Position) {
+        let Some(pv) = self
+            .pv_cache
+            .lookup(pos.board())
+            .or_else(|| self.analysis_tree.lookup(pos.board()))
+        else {
+            return;
+        };
+        let mut pos = pos.clone();
+        for mv in pv.moves() {
+            if self.tt.probe_by_hash(pos.board().hash()).is_none() {
+                let entry = TtNode {
+                    score: TtScore::new(Score::we_win_in(0), 0),
+                    depth: 0,
+                    bt:    BoundType::UpperAll,
+                    bm:    mv.to_inner(),
+                    eval:  self.eval.static_eval(&pos).pov_score(),
+                    ghi:   false,
+                };
+                self.tt.store(pos.board().hash(), entry);
+            }
+            pos.push_move(mv);
+        }
+        self.pv_cache.root_move = pv.first();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::Epd;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    #[test]
+    fn test_pv_cache() {
+        let mut eng = ThreadedSearch::new();
+        let start = Epd::starting_pos();
+        let res = eng.search(start.clone(), TimeControl::Depth(6)).unwrap();
+        let pv = res.pv();
+        assert!(pv.len() > 2);
+
+        // the position after our move and the expected reply finds the rest of the pv
+        let b = start.board().make_moves(&pv.take(2));
+        let cached = eng.search.pv_cache.lookup(&b).unwrap();
+        assert_eq!(cached, pv.skip(2));
+        assert_eq!(eng.search.pv_cache.lookup(&start.board()), None);
+
+        // after clearing the hash, the tt is re-seeded with the pv moves
+        eng.search.tt.clear_hash();
+        eng.search.seed_from_pv_cache(&Position::from_board(b.clone()));
+        assert_eq!(eng.search.pv_cache.root_move(), cached.first());
+        let entry = eng.search.tt.probe_by_hash(b.hash()).unwrap();
+        assert_eq!(entry.depth, 0);
+        assert_eq!(entry.validate_move(&b), cached.first().unwrap());
+
+        let epd = Epd::from_var(start.board(), pv.take(2));
+        assert!(eng.search(epd, TimeControl::Depth(4)).is_ok());
+
+        // persisted to and read back from a file
+        let file = std::env::temp_dir().join(format!("odonata-pv-cache-{}.txt", std::process::id()));
+        let mut cache = PvCache {
+            pv_file: file.clone(),
+            ..PvCache::default()
+        };
+        cache.record(&start.board(), &pv);
+        let mut reread = PvCache {
+            pv_file: file.clone(),
+            ..PvCache::default()
+        };
+        assert_eq!(reread.lookup(&b), Some(pv.skip(2)));
+        reread.set_state(State::NewGame);
+        assert_eq!(reread.lookup(&b), None);
+        fs_err::remove_file(file).unwrap();
+    }
+}
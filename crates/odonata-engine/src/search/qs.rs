@@ -14,6 +14,7 @@ use odonata_base::Epd;
 use serde::{Deserialize, Serialize};
 
 use super::controller::Controller;
+use super::staged_moves::{MoveScorer, StagedMoves};
 use super::trail::Trail;
 use crate::cache::tt2::{EvalFromTt, TranspositionTable2};
 
@@ -32,6 +33,11 @@ pub struct Qs {
     pub see_prune_discovered_check:   bool,
     pub see_prune_gives_check:        bool,
     pub see_prune_near_promos:        bool,
+    pub see_prune_promos:             bool,
+    pub see_prune_checks:             bool,
+    pub see_prune_margin:             Score,
+    pub see_delta_prune:              bool,
+    pub see_delta_margin:             Score,
     pub even_exchange_max_ply:        Ply,
     pub max_ply:                      u16,
     pub delta_prune:                  bool,
@@ -40,6 +46,7 @@ pub struct Qs {
     pub delta_prune_node_margin:      Score,
     pub recapture_score:              i32,
     pub probe_tt:                     bool,
+    pub stalemate_max_pieces:         i32,
     pub use_hash_move:                bool,
 }
 
@@ -64,8 +71,14 @@ impl Default for Qs {
             promos:                       true,
             recapture_score:              0,
             see_prune_discovered_check:   false,
-            see_prune_gives_check:        true,
+            see_prune_gives_check:        false,
             see_prune_near_promos:        true,
+            see_prune_promos:             false,
+            see_prune_checks:             true,
+            see_prune_margin:             Score::from_cp(0),
+            see_delta_prune:              false,
+            see_delta_margin:             Score::from_cp(200),
+            stalemate_max_pieces:         4,
             use_hash_move:                true,
         }
     }
@@ -96,6 +109,12 @@ impl Configurable for Qs {
             .set(p.get("see_prune_discovered_check"))?;
         self.see_prune_gives_check.set(p.get("see_prune_gives_check"))?;
         self.see_prune_near_promos.set(p.get("see_prune_near_promos"))?;
+        self.see_prune_promos.set(p.get("see_prune_promos"))?;
+        self.see_prune_checks.set(p.get("see_prune_checks"))?;
+        self.see_prune_margin.set(p.get("see_prune_margin"))?;
+        self.see_delta_prune.set(p.get("see_delta_prune"))?;
+        self.see_delta_margin.set(p.get("see_delta_margin"))?;
+        self.stalemate_max_pieces.set(p.get("stalemate_max_pieces"))?;
         self.use_hash_move.set(p.get("use_hash_move"))?;
         Ok(p.is_modified())
     }
@@ -179,6 +198,13 @@ impl RunQs<'_> {
             return Err(Score::DRAW);
         }
 
+        let in_check = pos.board().is_in_check(pos.board().color_us());
+        if !in_check && self.is_stalemate(pos.board()) {
+            Metrics::incr_node(&n, Event::QsCatStalemate);
+            self.trail.terminal(&n, Score::DRAW, Event::QsCatStalemate);
+            return Err(Score::DRAW);
+        }
+
         Metrics::incr_node(&n, Event::QsEvalStatic);
         let t = Metrics::timing_start();
         // let mut pat = self.eval.static_evaldb).pov_score()eval);
@@ -187,8 +213,6 @@ impl RunQs<'_> {
 
         Metrics::profile(t, Timing::TimingQsEval);
 
-        let in_check = pos.board().is_in_check(pos.board().color_us());
-
         let mut pat = Score::zero();
         let hm = self.probe_tt(&mut n, pos, &mut pat)?;
 
@@ -251,8 +275,11 @@ impl RunQs<'_> {
         }
 
         Metrics::incr_node(&n, Event::QsMoveGen);
-        let mut moves = MoveList::new();
-        self.gen_sorted_moves(in_check, &n, pos.board(), lm, hm, &mut moves);
+        let mut moves = self.staged_moves(in_check, &n, hm);
+        let scorer = QsScorer {
+            lm,
+            recapture_score: self.config.recapture_score,
+        };
 
         // if in_check && moves.is_empty() {
         //     return Ok(Score::from_mate_in_moves(0).clamp_score());
@@ -260,7 +287,12 @@ impl RunQs<'_> {
 
         let mut unpruned_move_count = 0;
         let mut bs = None; // Some(pat);
-        for &mv in moves.iter() {
+        loop {
+            let t = Metrics::timing_start();
+            let Some((_mt, mv)) = moves.next_move(pos.board(), &scorer) else {
+                break;
+            };
+            Metrics::profile(t, Timing::TimingQsMoveSort);
             Metrics::incr_node(&n, Event::QsMoveCount);
             if !in_check && self.can_see_prune_move(mv, &n, pat, pos.board()) {
                 continue;
@@ -288,45 +320,28 @@ impl RunQs<'_> {
         Ok(bs.unwrap_or(n.alpha).clamp_score())
     }
 
-    // sort moves
-    //
-    fn gen_sorted_moves(&self, in_check: bool, n: &Node, bd: &Board, lm: Option<Move>, hm: Move, moves: &mut MoveList) {
-        let t = Metrics::timing_start();
-        match (in_check, self.config.promos, self.config.promo_piece) {
-            (true, ..) => bd.legal_moves_with(|mv| moves.push(mv)),
-            _ if -n.depth <= self.config.checks_max_ply => bd.legal_moves_with(|mv| {
-                if mv.is_capture() || (mv.is_promo() && self.config.promos) || bd.gives_check(mv) {
-                    moves.push(mv);
-                }
-            }),
-            (_, false, _) => bd.legal_moves_to_with(bd.them(), |mv| moves.push(mv)),
-            (_, true, None) => bd.legal_moves_to_with(bd.them() | Bitboard::RANKS_18, |mv| {
-                if mv.is_capture() || mv.is_promo() {
-                    moves.push(mv)
-                }
-            }),
-            (_, true, Some(_)) => bd.legal_moves_to_with(bd.them() | Bitboard::RANKS_18, |mv| {
-                if mv.is_capture() || mv.promo_piece() == self.config.promo_piece {
-                    moves.push(mv)
-                }
-            }),
-        };
+    /// standing pat misses a stalemate, as the stronger side captures the last mobile piece
+    /// near the horizon. Only checked for a side reduced to king and a few pawns, which has
+    /// few moves to generate
+    fn is_stalemate(&self, b: &Board) -> bool {
+        let us = b.us();
+        us.popcount() <= self.config.stalemate_max_pieces
+            && (us - b.kings() - b.pawns()).is_empty()
+            && !b.has_legal_moves()
+    }
 
-        moves.sort_by_cached_key(|m| {
-            Move::mvv_lva_score(m, bd)
-                + if let Some(lm) = lm {
-                    if m.to() == lm.to() {
-                        self.config.recapture_score
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                }
-                + if hm == *m { 10000 } else { 0 }
-        });
-        moves.reverse();
-        Metrics::profile(t, Timing::TimingQsMoveSort);
+    /// all moves when in check, otherwise captures, promos and (while within
+    /// `checks_max_ply`) quiet checks
+    fn staged_moves(&self, in_check: bool, n: &Node, hm: Move) -> StagedMoves {
+        if in_check {
+            return StagedMoves::new(*n, hm, Move::new_null());
+        }
+        let promos = match (self.config.promos, &self.config.promo_piece) {
+            (false, _) => &[][..],
+            (true, None) => &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight][..],
+            (true, Some(p)) => std::slice::from_ref(p),
+        };
+        StagedMoves::noisy(*n, hm, promos, -n.depth <= self.config.checks_max_ply)
     }
 
     // delta prune caters for non-captures during evasions
@@ -355,24 +370,37 @@ impl RunQs<'_> {
 
     // see prune
     //
-    fn can_see_prune_move(&self, mv: Move, n: &Node, _pat: Score, bd: &Board) -> bool {
-        if mv.is_capture()
-            && (self.config.see_prune_discovered_check || !bd.maybe_gives_discovered_check(mv))
+    // checking captures and capture promotions are exempt by default, as a losing exchange
+    // can still win by force. Captures losing more than the margin are pruned, as are (with
+    // see_delta_prune) those whose exchange gain cannot raise alpha.
+    //
+    // quiet checks (from checks_max_ply) that hand the piece over are pruned too
+    //
+    fn can_see_prune_move(&self, mv: Move, n: &Node, pat: Score, bd: &Board) -> bool {
+        if !mv.is_capture() {
+            return self.can_see_prune_check(mv, n, bd);
+        }
+        if (self.config.see_prune_discovered_check || !bd.maybe_gives_discovered_check(mv))
             && (self.config.see_prune_gives_check || !bd.gives_check(mv))
             && (self.config.see_prune_near_promos || !mv.is_near_promo(bd))
+            && (self.config.see_prune_promos || !mv.is_promo())
             && bd.occupied().popcount() >= self.config.delta_prune_min_pieces
         {
             let t = Metrics::timing_start();
             let score = self.eval.eval_move_see(mv, bd);
             Metrics::profile(t, Timing::TimingQsSee);
 
-            if score == 0.cp() && n.depth >= -self.config.even_exchange_max_ply || score < 0.cp() {
+            if score == 0.cp() && n.depth >= -self.config.even_exchange_max_ply || score < -self.config.see_prune_margin
+            {
                 Metrics::incr_node(n, Event::QsMovePruneSee);
                 true
-            // } else {
-            //     if pat + score + self.config.see_prune_margin <= n.alpha {
-            //         Metrics::incr_node(&n, Event::QsMovePruneSeeMargin);
-            //         true
+            } else if self.config.see_delta_prune
+                && pat.is_numeric()
+                && !mv.is_promo()
+                && pat + score + self.config.see_delta_margin <= n.alpha
+            {
+                Metrics::incr_node(n, Event::QsMovePruneSeeMargin);
+                true
             } else {
                 false
             }
@@ -381,6 +409,23 @@ impl RunQs<'_> {
         }
     }
 
+    fn can_see_prune_check(&self, mv: Move, n: &Node, bd: &Board) -> bool {
+        if self.config.see_prune_checks
+            && (self.config.see_prune_promos || !mv.is_promo())
+            && (self.config.see_prune_discovered_check || !bd.maybe_gives_discovered_check(mv))
+            && bd.gives_check(mv)
+        {
+            let t = Metrics::timing_start();
+            let score = self.eval.eval_move_swap(mv, bd);
+            Metrics::profile(t, Timing::TimingQsSee);
+            if score < -self.config.see_prune_margin {
+                Metrics::incr_node(n, Event::QsMovePruneSee);
+                return true;
+            }
+        }
+        false
+    }
+
     // Either:
     //   sets score, hash move, narrows window
     // Or:
@@ -436,7 +481,12 @@ impl RunQs<'_> {
             };
             let s = ttn.score.as_score(n.ply);
             debug_assert!(s.is_finite());
-            match ttn.bt {
+            // a score that depended on a path-specific draw may not hold for this path
+            let bt = match self.tt.is_path_independent(&ttn) {
+                true => ttn.bt,
+                false => BoundType::Unused,
+            };
+            match bt {
                 BoundType::ExactPv => {
                     if self.tt.allow_truncated_pv && !self.controller.analyse_mode {
                         // let mv = tt.validate_move(&pos);
@@ -458,7 +508,7 @@ impl RunQs<'_> {
                     };
                     *pat = Score::max(*pat, s);
                 }
-                BoundType::Unused => unreachable!(),
+                BoundType::Unused => {}
             }
             if self.config.use_hash_move {
                 return Ok(ttn.validate_move(pos.board()));
@@ -479,7 +529,8 @@ impl RunQs<'_> {
         bs: &mut Option<Score>,
         unpruned_move_count: u64,
     ) -> Result<(), Score> {
-        pos.push_move(mv);
+        // quiescence never needs the prior board, so avoids the board copy of push_move
+        pos.make_move(mv);
         self.trail.push_move(n, mv);
         // self.current_variation.push(mv);
         let qsn = Node {
@@ -491,10 +542,9 @@ impl RunQs<'_> {
         };
         let s = -self.qs(qsn, pos, Some(mv)).unwrap_or_else(|e| e);
         self.trail.pop_move(n, mv);
-        pos.pop_move();
+        pos.undo_move();
 
         // self.current_variation.pop();
-        // bd.undo_move(&mv);
         if bs.is_none() || s > bs.unwrap() {
             *bs = Some(s);
         }
@@ -517,6 +567,21 @@ impl RunQs<'_> {
     }
 }
 
+/// mvv-lva, with a bonus for recapturing on the square of the last move
+#[derive(Copy, Clone, Debug)]
+struct QsScorer {
+    lm:              Option<Move>,
+    recapture_score: i32,
+}
+
+impl MoveScorer for QsScorer {
+    #[inline]
+    fn capture_points(&self, _n: &Node, mv: Move, b: &Board) -> i32 {
+        let recapture = self.lm.is_some_and(|lm| lm.to() == mv.to());
+        mv.mvv_lva_score(b) + if recapture { self.recapture_score } else { 0 }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use odonata_base::catalog::Catalog;
@@ -524,6 +589,7 @@ mod tests {
     use odonata_base::other::tags::EpdOps as _;
 
     use super::*;
+    use crate::cache::tt2::{Ghi, TtNode, TtScore};
     use crate::search::engine::ThreadedSearch;
 
     #[test]
@@ -587,6 +653,167 @@ mod tests {
         // println!("{mets}", mets = res.metrics.unwrap().summary("Qs"));
     }
 
+    #[test]
+    fn test_qs_see_prune_predicate() {
+        let eng = ThreadedSearch::new();
+        let prunes_at = |config: &Qs, epd: &Epd, mv: Move, n: Node, pat: Score| {
+            let mut trail = Trail::new(epd.board());
+            let qs = RunQs {
+                controller: &eng.search.controller,
+                eval: &eng.search.eval,
+                clock: &eng.search.clock,
+                tt: &eng.search.tt,
+                config,
+                trail: &mut trail,
+            };
+            qs.can_see_prune_move(mv, &n, pat, &epd.board())
+        };
+        let prunes = |config: &Qs, epd: &Epd, mv: Move| prunes_at(config, epd, mv, Node::root(0), Score::zero());
+
+        // losing captures are pruned, as are even exchanges at depth 0, but not winning ones
+        let config = Qs {
+            see_prune_discovered_check: true,
+            ..Qs::default()
+        };
+        for epd in Catalog::see() {
+            let mv = epd.mv("sm").unwrap();
+            let ce = epd.int("ce").unwrap();
+            assert_eq!(prunes(&config, &epd, mv), ce <= 0, "{epd}");
+        }
+
+        // Qxa5+ loses the queen for a pawn, but is a check
+        let epd = Epd::parse_epd("k7/8/1p6/p7/8/8/8/K3Q3 w - - 0 1").unwrap();
+        let mv = epd.board().parse_uci_move("e1a5").unwrap();
+        assert!(!prunes(&Qs::default(), &epd, mv));
+        let config = Qs {
+            see_prune_gives_check: true,
+            ..Qs::default()
+        };
+        assert!(prunes(&config, &epd, mv));
+
+        // capture promotions (and under-promotions) are never pruned by default
+        let epd = Epd::parse_epd("n6r/1P4k1/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        for uci in ["b7a8q", "b7a8n"] {
+            let mv = epd.board().parse_uci_move(uci).unwrap();
+            assert!(!prunes(&Qs::default(), &epd, mv), "{uci}");
+        }
+
+        // a margin lets slightly losing captures through: RxN PxR loses 175
+        let epd = Epd::parse_epd("k7/8/1p6/2n5/8/8/8/K1R5 w - - 0 1").unwrap();
+        let mv = epd.board().parse_uci_move("c1c5").unwrap();
+        assert!(prunes(&Qs::default(), &epd, mv));
+        let config = Qs {
+            see_prune_margin: Score::from_cp(200),
+            ..Qs::default()
+        };
+        assert!(!prunes(&config, &epd, mv));
+
+        // quiet checks are pruned when the checking piece is simply lost
+        let epd = Epd::parse_epd("k7/8/1p6/8/8/8/8/K3Q3 w - - 0 1").unwrap();
+        for (uci, pruned) in [("e1a5", true), ("e1e8", false)] {
+            let mv = epd.board().parse_uci_move(uci).unwrap();
+            assert_eq!(prunes(&Qs::default(), &epd, mv), pruned, "{uci}");
+        }
+        let config = Qs {
+            see_prune_checks: false,
+            ..Qs::default()
+        };
+        assert!(!prunes(&config, &epd, epd.board().parse_uci_move("e1a5").unwrap()));
+
+        // winning a pawn cannot raise alpha when already a rook short of it
+        let epd = Epd::parse_epd("k7/8/8/7p/8/8/8/K2Q4 w - - 0 1").unwrap();
+        let mv = epd.board().parse_uci_move("d1h5").unwrap();
+        let config = Qs {
+            see_delta_prune: true,
+            ..Qs::default()
+        };
+        let node = |alpha: i32| Node {
+            alpha: Score::from_cp(alpha),
+            beta: Score::from_cp(alpha + 1),
+            ..Node::root(0)
+        };
+        assert!(prunes_at(&config, &epd, mv, node(500), Score::zero()));
+        assert!(!prunes_at(&config, &epd, mv, node(200), Score::zero()));
+        assert!(!prunes_at(&Qs::default(), &epd, mv, node(500), Score::zero()));
+    }
+
+    #[test]
+    fn test_qs_stalemate() {
+        // black is stalemated, though white is a knight and bishop up
+        let epd = Epd::parse_epd("k7/8/K1N5/8/8/8/6N1/7B b - - 0 1").unwrap();
+        let eng = ThreadedSearch::new();
+        let qs_score = |config: &Qs| {
+            let mut trail = Trail::new(epd.board());
+            let mut qs = RunQs {
+                controller: &eng.search.controller,
+                eval: &eng.search.eval,
+                clock: &eng.search.clock,
+                tt: &eng.search.tt,
+                config,
+                trail: &mut trail,
+            };
+            let mut pos = Position::from_board(epd.board());
+            qs.qsearch(&Node::root(0), &mut pos, None)
+        };
+        assert_eq!(qs_score(&Qs::default()), Err(Score::DRAW));
+        let config = Qs {
+            stalemate_max_pieces: 0,
+            ..Qs::default()
+        };
+        assert!(qs_score(&config).unwrap_or_else(|s| s) < Score::from_cp(-500));
+
+        // the stalemating captures are avoided even at depth 1
+        for epd in Catalog::recogs() {
+            if !["RECOG.03", "RECOG.06", "RECOG.07"].contains(&epd.tag("id").unwrap()) {
+                continue;
+            }
+            let mut eng = ThreadedSearch::new();
+            let res = eng.search(epd.clone(), TimeControl::Depth(1)).unwrap();
+            let mv = res.supplied_move().unwrap();
+            assert!(!epd.moves("am").unwrap().contains(&mv), "{mv} {epd}");
+        }
+    }
+
+    /// a draw by repetition found along one path, and stored as an upper bound of 0.00, is
+    /// not used to cut the same position in qs (unless Ghi::Ignore)
+    #[test]
+    fn test_qs_ghi() {
+        let mut eng = ThreadedSearch::new();
+        let mut pos = Position::from_board(Board::parse_fen("8/8/8/2k5/8/8/8/3QK3 w - - 0 1").unwrap());
+        let eval = eng.search.eval.static_eval(&pos).pov_score();
+        assert!(eval > Score::from_cp(300));
+        eng.search.tt.clear_hash();
+        eng.search.tt.store(pos.hash(), TtNode {
+            score: TtScore::new(Score::DRAW, 0),
+            depth: 0,
+            bt: BoundType::UpperAll,
+            bm: Move::new_null().to_inner(),
+            eval,
+            ghi: true,
+        });
+
+        let mut qs_score = |eng: &ThreadedSearch| {
+            let mut trail = Trail::new(pos.board().clone());
+            let mut qs = RunQs {
+                controller: &eng.search.controller,
+                eval:       &eng.search.eval,
+                clock:      &eng.search.clock,
+                tt:         &eng.search.tt,
+                config:     &eng.search.qs,
+                trail:      &mut trail,
+            };
+            let n = Node {
+                alpha: Score::from_cp(100),
+                ..Node::root(0)
+            };
+            let (Ok(score) | Err(score)) = qs.qs(n, &mut pos, None);
+            score
+        };
+        assert!(qs_score(&eng) > Score::from_cp(300));
+        eng.search.tt.ghi = Ghi::Ignore;
+        assert_eq!(qs_score(&eng), Score::DRAW);
+    }
+
     #[test]
     fn test_qs_catalog() -> Result<()> {
         let catalog = Catalog::quiesce();
@@ -10,7 +10,9 @@ use odonata_base::piece::MoveType;
 use odonata_base::prelude::*;
 
 use super::algo::Search;
+use super::pruning_guard::{MaterialGuard, PruningGuard};
 use super::trail::Trail;
+use super::volatility::Volatility;
 use crate::cache::tt2::{TtNode, TtScore};
 
 // CLOP - gamma
@@ -57,6 +59,7 @@ pub struct Razor {
     pub margin2:          i32,
     pub margin3:          i32,
     move_types_forbidden: MoveType,
+    guard:                PruningGuard,
 }
 
 impl Component for Razor {
@@ -89,6 +92,7 @@ impl Default for Razor {
                 | MoveType::GoodCaptureUpfrontSorted
                 | MoveType::CounterMove
                 | MoveType::QueenPromo,
+            guard:                PruningGuard::default(),
         }
     }
 }
@@ -107,6 +111,7 @@ impl Configurable for Razor {
         self.margin2.set(p.get("margin2"))?;
         self.margin3.set(p.get("margin3"))?;
         Configurable::set(&mut self.move_types_forbidden, p.get("move_types_forbidden"))?; // method name clash
+        self.guard.set(p.get("guard"))?;
         Ok(p.is_modified())
     }
 }
@@ -158,6 +163,14 @@ impl Razor {
             Metrics::incr_node(n, Event::RazorDeclineMinPieces);
             return false;
         }
+        if let Some(guard) = self.guard.check(bd) {
+            Metrics::incr_node(n, match guard {
+                MaterialGuard::PawnEnding => Event::RazorGuardPawnEnding,
+                MaterialGuard::KingPawns => Event::RazorGuardKingPawns,
+                MaterialGuard::FewPieces => Event::RazorGuardFewPieces,
+            });
+            return false;
+        }
         true
     }
 }
@@ -170,18 +183,20 @@ impl Search {
         last_move: Move,
         pos: &mut Position,
         eval: Score,
+        vol: i32,
         n: &Node,
     ) -> Result<Option<Score>, Event> {
         if !self.razor.can_razor(pos.board(), n) {
             return Ok(None);
         }
 
-        let margin = Score::from_cp(match n.depth {
+        let margin = match n.depth {
             1 => self.razor.margin1,
             2 => self.razor.margin2,
             3 => self.razor.margin3,
             _ => unreachable!(),
-        });
+        };
+        let margin = Score::from_cp(Volatility::scale(margin, vol));
 
         let event = match n.depth {
             1 => Event::RazorD1Success,
@@ -237,6 +252,7 @@ impl Search {
                         bt: BoundType::UpperAll,
                         bm: Move::new_null().to_inner(),
                         eval,
+                        ghi: false, // a quiescence search never draws by repetition
                     };
                     self.tt.store(pos.board().hash(), entry);
                 }
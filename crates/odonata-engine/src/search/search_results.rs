@@ -11,11 +11,13 @@ use odonata_base::other::outcome::Outcome;
 use odonata_base::prelude::*;
 use odonata_base::variation::{MultiVariation, ScoredVariation};
 use odonata_base::Epd;
+use serde::{Deserialize, Serialize};
 use tabled::builder::Builder;
 
+use super::best_move_change::BestMoveChange;
 use super::trail::ChessTree;
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct Response {
     pub tc:    TimeControl,
     pub input: Epd,
@@ -34,6 +36,7 @@ pub struct Response {
     pub emt:                Duration,
     pub multi_pv:           MultiVariation,
     pub infos:              Vec<Info>,
+    pub bm_change:          Option<BestMoveChange>, // best move change in the last completed iteration
 }
 
 impl fmt::Display for Response {
@@ -89,7 +92,7 @@ impl Response {
         if let Ok(mv) = self.supplied_move() {
             write!(f, "bestmove {mv}", mv = mv.to_uci())?;
             if let Some(ponder) = self.pv().second() {
-                write!(f, "ponder {}", ponder.to_uci())?;
+                write!(f, " ponder {}", ponder.to_uci())?;
             }
         } else {
             write!(f, "bestmove 0000")?;
@@ -149,7 +152,7 @@ impl Response {
             nodes = self.nodes,
             bm = self.supplied_move().unwrap_or_default().to_san(b),
             sc = self.score().unwrap_or_default(),
-            pv = self.pv().to_san(b),
+            pv = self.pv().display_san(b),
             mpv = self.multi_pv.to_san(b),
         )
     }
@@ -258,6 +261,7 @@ impl Response {
                 emt: Duration::ZERO,
                 input: epd,
                 tc,
+                bm_change: None,
             }
         } else {
             let mut sr = Response {
@@ -392,6 +396,16 @@ mod tests {
             parse_bestmove_uci("bestmove a2a3 ponder b7b6 extraneous", &b).is_err(),
             true
         );
+
+        let s = "info depth 3 score cp 20 pv e2e4 e7e5\nbestmove e2e4 ponder e7e5";
+        let sr = Response::parse_uci(s, Epd::starting_pos(), TimeControl::Depth(3)).unwrap();
+        assert_eq!(sr.to_uci(), "bestmove e2e4 ponder e7e5");
+        let json = serde_json::to_string(&sr).unwrap();
+        let sr2: Response = serde_json::from_str(&json).unwrap();
+        assert_eq!(sr2.to_uci(), sr.to_uci());
+        assert_eq!(sr2.multi_pv, sr.multi_pv);
+        assert_eq!(sr2.infos, sr.infos);
+        assert_eq!(sr2.input, sr.input);
     }
 
     #[test]
@@ -0,0 +1,350 @@
+use odonata_base::domain::node::{Event, Node};
+use odonata_base::infra::metric::Metrics;
+use odonata_base::piece::{MoveType, MAX_LEGAL_MOVES};
+use odonata_base::prelude::*;
+
+use super::algo::Search;
+use super::move_orderer::OrderedMoveList;
+
+/// Scores and classifies moves for [`StagedMoves`]. Only called for the moves of a stage
+/// once it is reached, so the quiet moves of a node cut by a capture are never scored.
+pub trait MoveScorer {
+    /// captures are tried highest first
+    fn capture_points(&self, n: &Node, mv: Move, b: &Board) -> i32;
+
+    /// checked as each capture is picked, the bad ones deferred until after the quiets
+    fn is_good_capture(&self, _n: &Node, _mv: Move, _b: &Board) -> bool {
+        true
+    }
+
+    /// legal killer moves for the node, pushed onto `moves`
+    fn killers(&self, _n: &Node, _b: &Board, _moves: &mut MoveList) {}
+
+    /// quiet moves are tried highest first
+    fn quiet_points(&self, _n: &Node, _mv: Move, _b: &Board, _last: Move) -> i32 {
+        0
+    }
+
+    /// the move expected to be tried next, called ahead of its search
+    fn prefetch(&self, _b: &Board, _mv: Move) {}
+}
+
+/// A staged move generator: hash move, good captures, killers, quiet promotions, quiets
+/// and bad captures, each stage generated (and scored) only when the one before it is
+/// exhausted, and sorted moves picked one at a time. Moves are generated pseudo-legal and
+/// checked as they are picked, so a node cut early skips the pin and king safety tests.
+///
+/// The caller holds the cursor and asks for `next_move` with the board and scorer, as
+/// alphabeta and quiescence make moves on the position between calls. Where the board
+/// can be borrowed throughout, `iter` gives the same sequence as an iterator.
+#[derive(Clone, Debug)]
+pub struct StagedMoves {
+    n:            Node,
+    tt:           Move,
+    last:         Move,
+    quiets:       bool,
+    quiet_promos: u8, // bit per promo piece, yielded with the captures when not generating quiets
+    checks:       bool,
+    stage:        usize,
+    index:        usize,
+    moves:        MoveList,
+    points:       [i32; MAX_LEGAL_MOVES],
+    killers:      MoveList,
+    bad_captures: MoveList,
+}
+
+impl StagedMoves {
+    const STAGES: [MoveType; 8] = [
+        MoveType::Start,
+        MoveType::Hash,
+        MoveType::GoodCapture,
+        MoveType::Killer,
+        MoveType::Promo,
+        MoveType::Quiet,
+        MoveType::BadCapture,
+        MoveType::End,
+    ];
+
+    /// every legal move, where `tt` (if not null) must be legal
+    pub fn new(n: Node, tt: Move, last: Move) -> Self {
+        Self {
+            n,
+            tt,
+            last,
+            quiets: true,
+            quiet_promos: 0,
+            checks: false,
+            stage: 0,
+            index: 0,
+            moves: MoveList::new(),
+            points: [0; MAX_LEGAL_MOVES],
+            killers: MoveList::new(),
+            bad_captures: MoveList::new(),
+        }
+    }
+
+    /// captures and quiet promotions to any of `promos` (and if `checks`, quiet checks),
+    /// as searched by quiescence when not in check. A `tt` move outside these is ignored
+    pub fn noisy(n: Node, tt: Move, promos: &[Piece], checks: bool) -> Self {
+        Self {
+            quiets: false,
+            quiet_promos: promos.iter().fold(0, |acc, p| acc | 1 << p.index()),
+            checks,
+            ..Self::new(n, tt, Move::new_null())
+        }
+    }
+
+    /// the stage of the last move returned
+    pub fn stage(&self) -> MoveType {
+        Self::STAGES[self.stage]
+    }
+
+    pub fn iter<'a, S: MoveScorer>(
+        &'a mut self,
+        b: &'a Board,
+        scorer: &'a S,
+    ) -> impl Iterator<Item = (MoveType, Move)> + 'a {
+        std::iter::from_fn(move || self.next_move(b, scorer))
+    }
+
+    pub fn next_move(&mut self, b: &Board, scorer: &impl MoveScorer) -> Option<(MoveType, Move)> {
+        loop {
+            let mt = Self::STAGES[self.stage];
+            if self.index < self.moves.len() {
+                let mv = self.moves[self.index];
+                self.index += 1;
+                if Self::is_sorted(mt) {
+                    self.select(self.index);
+                }
+                if Self::is_pseudo_legal(mt) && !b.is_legal(mv) {
+                    continue;
+                }
+                if mt == MoveType::GoodCapture && !scorer.is_good_capture(&self.n, mv, b) {
+                    self.bad_captures.push(mv);
+                    continue;
+                }
+                if self.index < self.moves.len() {
+                    scorer.prefetch(b, self.moves[self.index]);
+                }
+                return Some((mt, mv));
+            }
+            if mt == MoveType::End {
+                return None;
+            }
+            self.stage += 1;
+            self.index = 0;
+            self.gen(b, scorer);
+        }
+    }
+
+    fn is_sorted(mt: MoveType) -> bool {
+        mt == MoveType::GoodCapture || mt == MoveType::Quiet
+    }
+
+    /// the generated stages, as the hash move and killers are known legal, and bad captures
+    /// were checked before being deferred
+    fn is_pseudo_legal(mt: MoveType) -> bool {
+        matches!(mt, MoveType::GoodCapture | MoveType::Promo | MoveType::Quiet)
+    }
+
+    #[inline]
+    fn is_noisy(quiet_promos: u8, checks: bool, b: &Board, mv: Move) -> bool {
+        mv.is_capture()
+            || mv.promo_piece().is_some_and(|p| quiet_promos & (1 << p.index()) != 0)
+            || checks && b.gives_check(mv)
+    }
+
+    /// swaps the highest scoring of the remaining moves into position `i`
+    #[inline]
+    fn select(&mut self, i: usize) {
+        let len = self.moves.len();
+        if i + 1 >= len {
+            return;
+        }
+        let mut best = i;
+        for j in i + 1..len {
+            if self.points[j] >= self.points[best] {
+                best = j;
+            }
+        }
+        self.moves.swap(i, best);
+        self.points.swap(i, best);
+    }
+
+    fn gen(&mut self, b: &Board, scorer: &impl MoveScorer) {
+        self.moves.clear();
+        let (n, tt, last) = (self.n, self.tt, self.last);
+        let (quiet_promos, checks) = (self.quiet_promos, self.checks);
+        let mt = Self::STAGES[self.stage];
+        match mt {
+            MoveType::Hash => {
+                if !tt.is_null() && (self.quiets || Self::is_noisy(quiet_promos, checks, b, tt)) {
+                    self.moves.push(tt);
+                }
+            }
+            MoveType::GoodCapture => {
+                let to = match (checks, quiet_promos) {
+                    (true, _) => Bitboard::all(),
+                    (false, 0) => b.them(),
+                    (false, _) => b.them() | Bitboard::RANKS_18,
+                };
+                let moves = &mut self.moves;
+                b.pseudo_legal_moves_to_with(to, |mv| {
+                    if mv != tt && Self::is_noisy(quiet_promos, checks, b, mv) {
+                        moves.push(mv);
+                    }
+                });
+                for (i, &mv) in self.moves.iter().enumerate() {
+                    self.points[i] = scorer.capture_points(&n, mv, b);
+                }
+            }
+            MoveType::Killer if self.quiets => {
+                scorer.killers(&n, b, &mut self.killers);
+                self.killers.retain(|mv| *mv != tt && !mv.is_capture());
+                self.moves.extend(self.killers.iter().cloned());
+            }
+            MoveType::Promo if self.quiets => {
+                let (moves, killers) = (&mut self.moves, &self.killers);
+                b.pseudo_legal_moves_to_with(Bitboard::RANKS_18 - b.them(), |mv| {
+                    if mv.is_promo() && mv != tt && !killers.contains(&mv) {
+                        moves.push(mv);
+                    }
+                });
+            }
+            MoveType::Quiet if self.quiets => {
+                let (moves, killers) = (&mut self.moves, &self.killers);
+                b.pseudo_legal_moves_to_with(!b.them(), |mv| {
+                    if !mv.is_promo() && mv != tt && !killers.contains(&mv) {
+                        moves.push(mv);
+                    }
+                });
+                for (i, &mv) in self.moves.iter().enumerate() {
+                    self.points[i] = scorer.quiet_points(&n, mv, b, last);
+                }
+            }
+            MoveType::BadCapture => std::mem::swap(&mut self.moves, &mut self.bad_captures),
+            _ => {}
+        }
+        if Self::is_sorted(mt) {
+            self.select(0);
+        }
+    }
+}
+
+/// the main search's move ordering: capture history, SEE, killers and the quiet move
+/// heuristics of the move orderer
+impl MoveScorer for Search {
+    #[inline]
+    fn capture_points(&self, _n: &Node, mv: Move, b: &Board) -> i32 {
+        OrderedMoveList::capture_points(mv, b, self)
+    }
+
+    #[inline]
+    fn is_good_capture(&self, n: &Node, mv: Move, b: &Board) -> bool {
+        Metrics::incr_node(n, Event::EvalSee);
+        let see = self.eval.eval_move_see(mv, b);
+        see >= self.move_orderer.see_cutoff || self.move_orderer.discovered_checks && b.maybe_gives_discovered_check(mv)
+    }
+
+    #[inline]
+    fn killers(&self, n: &Node, b: &Board, moves: &mut MoveList) {
+        self.killers.legal_moves_for(n, b, moves);
+    }
+
+    #[inline]
+    fn quiet_points(&self, n: &Node, mv: Move, b: &Board, last: Move) -> i32 {
+        -self.move_orderer.quiet_points(n, mv, self, b.color_us(), b, last)
+    }
+
+    #[inline]
+    fn prefetch(&self, b: &Board, mv: Move) {
+        if self.move_orderer.tt_prefetch > 0 {
+            self.tt.prefetch(b.hash_after(mv));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use odonata_base::catalog::Catalog;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    /// mvv-lva captures, everything else unscored, counting the quiets scored
+    #[derive(Default)]
+    struct MvvLva {
+        quiets_scored: Cell<usize>,
+    }
+
+    impl MoveScorer for MvvLva {
+        fn capture_points(&self, _n: &Node, mv: Move, b: &Board) -> i32 {
+            mv.mvv_lva_score(b)
+        }
+
+        fn quiet_points(&self, _n: &Node, _mv: Move, _b: &Board, _last: Move) -> i32 {
+            self.quiets_scored.set(self.quiets_scored.get() + 1);
+            0
+        }
+    }
+
+    #[test]
+    fn test_staged_moves() {
+        let n = Node::root(3);
+        let scorer = MvvLva::default();
+        for pos in Catalog::win_at_chess() {
+            let b = pos.board();
+            let legal = b.legal_moves();
+            let tt = legal[legal.len() / 2];
+
+            // each legal move exactly once, hash move first
+            let mut staged = StagedMoves::new(n, tt, Move::new_null());
+            let moves: MoveList = staged.iter(&b, &scorer).map(|(_mt, mv)| mv).collect();
+            assert_eq!(moves[0], tt, "{pos}");
+            assert_eq!(moves.clone().sort(), legal.clone().sort(), "{pos}");
+
+            // captures before quiets, and in mvv-lva order
+            let mut staged = StagedMoves::new(n, Move::new_null(), Move::new_null());
+            let captures = staged
+                .iter(&b, &scorer)
+                .take_while(|&(mt, _mv)| mt == MoveType::GoodCapture)
+                .map(|(_mt, mv)| mv)
+                .collect_vec();
+            assert_eq!(captures.len(), legal.iter().filter(|mv| mv.is_capture()).count());
+            assert!(captures
+                .windows(2)
+                .all(|w| w[0].mvv_lva_score(&b) >= w[1].mvv_lva_score(&b)));
+
+            // only captures and queen promos when noisy
+            let mut staged = StagedMoves::noisy(n, Move::new_null(), &[Piece::Queen], false);
+            assert!(staged
+                .iter(&b, &scorer)
+                .all(|(_mt, mv)| mv.is_capture() || mv.promo_piece() == Some(Piece::Queen)));
+        }
+
+        // stopping after the first capture never scores the quiets
+        let b = Catalog::perft_kiwipete().0;
+        let scorer = MvvLva::default();
+        let mut staged = StagedMoves::new(n, Move::new_null(), Move::new_null());
+        assert_eq!(staged.next_move(&b, &scorer).unwrap().1.to_uci(), "e2a6");
+        assert_eq!(staged.stage(), MoveType::GoodCapture);
+        assert_eq!(scorer.quiets_scored.get(), 0);
+        assert_eq!(staged.iter(&b, &scorer).count() + 1, b.legal_moves().len());
+        assert!(scorer.quiets_scored.get() > 0);
+    }
+
+    #[test]
+    fn test_staged_moves_search_scorer() {
+        let eng = ThreadedSearch::new();
+        let n = Node::root(3);
+        for pos in Catalog::win_at_chess() {
+            let b = pos.board();
+            let mut staged = StagedMoves::new(n, Move::new_null(), Move::new_null());
+            let mut moves: MoveList = staged.iter(&b, &eng.search).map(|(_mt, mv)| mv).collect();
+            assert_eq!(moves.sort(), b.legal_moves().sort(), "{pos}");
+        }
+    }
+}
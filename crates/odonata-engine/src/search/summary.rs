@@ -0,0 +1,234 @@
+use std::fmt;
+use std::time::Duration;
+
+use odonata_base::domain::info::InfoKind;
+use odonata_base::infra::utils::Formatting;
+use odonata_base::prelude::*;
+
+use super::search_results::Response;
+
+/// the closest alternative to the best move, and why it was passed over
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alternative {
+    pub mv:         Move,
+    pub score:      Score,
+    pub refutation: Option<Move>, // the opponent's reply in the alternative's line
+    pub depth:      Option<Ply>,  // when preferred until (a best move change), rather than multi-pv
+}
+
+/// A short, plain account of a search for casual users: the score and its trend over the
+/// last few iterations, what the opponent is expected to do (and which of our pieces are
+/// left hanging) after the best move, the closest alternative and what was wrong with it,
+/// and the time taken against the time allotted.
+///
+/// Assembled from the search results alone: the per-depth pv infos give the trend, and a
+/// second multi-pv line (or failing that, the last best move change) the alternative.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchSummary {
+    pub board:       Board,
+    pub best_move:   Move,
+    pub score:       Score,
+    pub trend:       Option<(Ply, Score)>, // the score some iterations earlier
+    pub reply:       Option<Move>,
+    pub hanging:     Vec<(Piece, Square)>,
+    pub alternative: Option<Alternative>,
+    pub elapsed:     Duration,
+    pub allotted:    Duration, // zero if not a clock based time control
+}
+
+impl SearchSummary {
+    /// iterations back that the trend is measured from
+    const TREND_DEPTHS: Ply = 4;
+
+    /// a trend, or an alternative, within this is "about the same"
+    const MARGIN_CP: i32 = 30;
+
+    pub fn new(res: &Response, allotted: Duration) -> Option<Self> {
+        let board = res.input.board();
+        let best_move = res.supplied_move().ok()?;
+        let score = res.score()?;
+        let pv = res.pv();
+        let after = board.make_move(best_move);
+
+        // our pieces attacked and undefended once we've moved
+        let us = board.color_us();
+        let undefended = after.all_attacks_on(us) - after.all_attacks_on(us.flip_side());
+        let hanging = ((after.color(us) - after.kings()) & undefended)
+            .squares()
+            .filter_map(|sq| Some((after.piece(sq)?, sq)))
+            .collect_vec();
+
+        let alternative = match res.multi_pv.iter().nth(1) {
+            Some(sv) => sv.var.first().map(|mv| Alternative {
+                mv,
+                score: sv.score,
+                refutation: sv.var.second(),
+                depth: None,
+            }),
+            None => res.bm_change.as_ref().map(|c| Alternative {
+                mv:         c.old_move,
+                score:      c.old_score,
+                refutation: None,
+                depth:      Some(c.depth),
+            }),
+        };
+
+        Some(Self {
+            trend: Self::trend(res),
+            reply: pv.second(),
+            hanging,
+            alternative,
+            elapsed: Duration::from_millis(res.time_millis),
+            allotted,
+            board,
+            best_move,
+            score,
+        })
+    }
+
+    /// the first line's score at the depth `TREND_DEPTHS` before the last (or the earliest)
+    fn trend(res: &Response) -> Option<(Ply, Score)> {
+        let scores = res
+            .infos
+            .iter()
+            .filter(|i| i.kind == InfoKind::Pv && i.multi_pv.unwrap_or(1) == 1)
+            .filter_map(|i| Some((i.depth?, i.score?)))
+            .collect_vec();
+        let &(last, _) = scores.last()?;
+        scores
+            .iter()
+            .rev()
+            .find(|(d, _)| *d <= last - Self::TREND_DEPTHS)
+            .or(scores.first())
+            .filter(|(d, _)| *d < last)
+            .copied()
+    }
+}
+
+impl fmt::Display for SearchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.board;
+        let after = b.make_move(self.best_move);
+        write!(
+            f,
+            "I play {} with a score of {}",
+            self.best_move.to_san(b),
+            self.score.to_pgn()
+        )?;
+        match self.trend {
+            Some((depth, earlier)) if self.score.is_numeric() && earlier.is_numeric() => {
+                let change = self.score.as_i16() as i32 - earlier.as_i16() as i32;
+                let trend = match change {
+                    c if c > Self::MARGIN_CP => "rising",
+                    c if c < -Self::MARGIN_CP => "falling",
+                    _ => "steady",
+                };
+                writeln!(f, ", {trend} since depth {depth} ({})", earlier.to_pgn())?;
+            }
+            _ => writeln!(f)?,
+        }
+
+        if let Some(reply) = self.reply {
+            write!(f, "I expect {}", reply.to_san(&after))?;
+        } else {
+            write!(f, "I have no expected reply")?;
+        }
+        match self.hanging.as_slice() {
+            [] => writeln!(f)?,
+            hanging => writeln!(
+                f,
+                ", and the {} undefended",
+                hanging
+                    .iter()
+                    .map(|(p, sq)| format!("{} on {}", p.name(), sq.uci()))
+                    .join(", ")
+                    + if hanging.len() == 1 { " is" } else { " are" }
+            )?,
+        }
+
+        if let Some(alt) = &self.alternative {
+            let gap = match (self.score.is_numeric(), alt.score.is_numeric()) {
+                (true, true) => {
+                    let gap = self.score.as_i16() as i32 - alt.score.as_i16() as i32;
+                    format!("{:.2} worse", gap as f32 / 100.0)
+                }
+                _ => format!("scored {}", alt.score.to_pgn()),
+            };
+            write!(f, "{} is {gap}", alt.mv.to_san(b))?;
+            if let Some(depth) = alt.depth {
+                write!(f, ", and was my choice until depth {depth}")?;
+            }
+            match alt.refutation {
+                Some(r) => writeln!(f, ", refuted by {}", r.to_san(&b.make_move(alt.mv)))?,
+                None => writeln!(f)?,
+            }
+        }
+
+        write!(f, "I took {}", Formatting::duration(self.elapsed))?;
+        if self.allotted > Duration::ZERO {
+            write!(f, " of {} allotted", Formatting::duration(self.allotted))?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::domain::info::Info;
+    use odonata_base::epd::Epd;
+    use odonata_base::variation::{MultiVariation, ScoredVariation};
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_search_summary() {
+        let epd = Epd::parse_epd("4k3/8/8/8/8/2n5/3P4/R3K3 w - - 0 1").unwrap();
+        let b = epd.board();
+        let var = |s: &str| b.parse_uci_variation(s).unwrap();
+        let pv_info = |depth: Ply, cp: i32| Info {
+            kind: InfoKind::Pv,
+            depth: Some(depth),
+            score: Some(Score::from_cp(cp)),
+            ..Info::default()
+        };
+        let mut multi_pv = MultiVariation::new();
+        multi_pv.push(var("d2c3 e8d7"), Score::from_cp(500));
+        multi_pv.push(var("a1a8 e8d7"), Score::from_cp(200));
+        let res = Response {
+            input: epd,
+            supplied_move: b.parse_uci_move("d2c3").unwrap(),
+            multi_pv,
+            infos: (1..=6).map(|d| pv_info(d, 300 + 40 * d)).collect(),
+            time_millis: 1500,
+            ..Response::default()
+        };
+        let summary = SearchSummary::new(&res, Duration::from_secs(3)).unwrap();
+        assert_eq!(summary.trend, Some((2, Score::from_cp(380))));
+        assert_eq!(
+            summary.reply,
+            Some(b.make_move(summary.best_move).parse_uci_move("e8d7").unwrap())
+        );
+        assert_eq!(summary.hanging, vec![]);
+        let text = summary.to_string();
+        println!("{text}");
+        assert!(text.contains("I play dxc3 with a score of +5.00, rising since depth 2 (+3.80)"));
+        assert!(text.contains("Ra8+ is 3.00 worse, refuted by Kd7"));
+        assert!(text.contains(" allotted"));
+
+        // a hanging rook, and the alternative from a best move change
+        let res = Response {
+            multi_pv: [ScoredVariation {
+                var:   var("a1a4"),
+                score: Score::from_cp(-100),
+            }]
+            .into_iter()
+            .collect(),
+            supplied_move: b.parse_uci_move("a1a4").unwrap(),
+            ..res
+        };
+        let summary = SearchSummary::new(&res, Duration::ZERO).unwrap();
+        assert_eq!(summary.hanging, vec![(Piece::Rook, Square::A4)]);
+        assert!(summary.to_string().contains("rook on a4 is undefended"));
+    }
+}
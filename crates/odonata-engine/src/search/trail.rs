@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::{fmt, mem};
 
 use odonata_base::domain::node::Node;
@@ -7,6 +8,7 @@ use odonata_base::piece::LEN_PLY;
 use odonata_base::prelude::*;
 use odonata_base::Epd;
 use serde::{Deserialize, Serialize};
+use tabled::builder::Builder;
 
 #[derive(Clone, Default, PartialEq, Eq)]
 pub struct TreeNode {
@@ -32,7 +34,8 @@ pub struct NodeId(i32);
 
 #[derive(Clone, Default, PartialEq)]
 struct Tree {
-    nodes: Vec<TreeNode>,
+    nodes:    Vec<TreeNode>,
+    children: Vec<Vec<NodeId>>, // by node id, in the order added (so move order)
 }
 
 impl fmt::Debug for Tree {
@@ -59,7 +62,10 @@ const ROOT: TreeNode = TreeNode {
 
 impl Tree {
     fn new() -> Self {
-        Tree { nodes: vec![ROOT] }
+        Tree {
+            nodes:    vec![ROOT],
+            children: vec![vec![]],
+        }
     }
 
     fn add(&mut self, var: &Variation, index: usize) {
@@ -72,6 +78,8 @@ impl Tree {
         let id = NodeId(self.nodes.len() as i32);
         let tn = TreeNode { index, id, parent, mv };
         self.nodes.push(tn);
+        self.children.push(vec![]);
+        self.children[parent.0 as usize].push(id);
     }
 
     pub fn find_by_var(&self, var: &Variation) -> Option<&TreeNode> {
@@ -105,9 +113,10 @@ impl Tree {
     }
 
     fn children_of(&self, id: NodeId) -> Vec<&TreeNode> {
-        // root has -1 as parent
-        // self.nodes.iter().filter(|tn| tn.parent == id).collect_vec()
-        self.nodes.iter().filter(|tn| tn.parent == id).collect_vec()
+        self.children[id.0 as usize]
+            .iter()
+            .map(|&c| self.find_by_id(c))
+            .collect_vec()
     }
 }
 
@@ -203,9 +212,10 @@ impl TreeCrit {
 
 #[derive(Clone, Debug, PartialEq, Default)]
 pub struct ChessTree {
-    board: Board,
-    tree:  Tree,
-    arena: Vec<NodeDetails>,
+    board:     Board,
+    tree:      Tree,
+    arena:     Vec<NodeDetails>,
+    lmr_saved: BTreeMap<Ply, u64>, // by depth
 }
 
 impl ChessTree {
@@ -215,7 +225,29 @@ impl ChessTree {
             board,
             tree: Tree::new(),
             arena: vec![root],
+            lmr_saved: BTreeMap::new(),
+        }
+    }
+
+    pub fn prune_heat_table(&self) -> PruneHeatTable {
+        let mut table = PruneHeatTable::default();
+        for tn in self.tree.nodes.iter().filter(|tn| tn.id != ROOT.id) {
+            let nd = &self.arena[tn.index];
+            let row = table.by_depth.entry(nd.n.depth).or_default();
+            row.nodes += 1;
+            if let Some(i) = PRUNE_COLUMNS.iter().position(|(_, events)| events.contains(&nd.e)) {
+                row.pruned[i] += 1;
+            }
+            // the cutoff move is the last one searched (or pruned) at this node
+            if nd.e == Event::MoveScoreHigh {
+                row.cuts += 1;
+                row.cut_move_index_total += self.tree.children[tn.id.0 as usize].len() as u64;
+            }
         }
+        for (&depth, &count) in &self.lmr_saved {
+            table.by_depth.entry(depth).or_default().lmr_saved += count;
+        }
+        table
     }
 
     pub(crate) fn merge(&mut self, var: &Variation, details: NodeDetails) {
@@ -289,6 +321,82 @@ impl fmt::Display for ChessTree {
     }
 }
 
+/// (column heading, events recorded on the tree) for each pruning technique
+const PRUNE_COLUMNS: [(&str, &[Event]); 7] = [
+    ("tt", &[Event::TtCut, Event::TtAll]),
+    ("nmp", &[Event::NmpSuccess]),
+    ("razor", &[Event::RazorSuccess]),
+    ("rev fut", &[Event::RevFutSuccess]),
+    ("futility", &[Event::FutilitySuccessRemaining]),
+    ("lmp", &[Event::LmpSuccess]),
+    ("delta", &[Event::QsCatAllDeltaPruneNode]),
+];
+
+#[derive(Clone, Debug, Default)]
+struct DepthPruneCounts {
+    nodes:                u64,
+    pruned:               [u64; PRUNE_COLUMNS.len()],
+    lmr_saved:            u64,
+    cuts:                 u64,
+    cut_move_index_total: u64,
+}
+
+/// Per depth, how many tree nodes were cut by each pruning technique (with a text heat
+/// marker for the proportion of nodes at that depth), the reduced searches that avoided a
+/// re-search, and the average (1-based) index of the move causing a beta cutoff.
+///
+/// Pruned moves (futility, lmp) are counted at the depth of the unsearched child, and
+/// lmr savings at the depth of the node making the reduction.
+#[derive(Clone, Debug, Default)]
+pub struct PruneHeatTable {
+    by_depth: BTreeMap<Ply, DepthPruneCounts>,
+}
+
+impl PruneHeatTable {
+    pub fn nodes(&self) -> u64 {
+        self.by_depth.values().map(|row| row.nodes).sum()
+    }
+
+    pub fn pruned(&self, heading: &str) -> u64 {
+        let Some(i) = PRUNE_COLUMNS.iter().position(|(h, _)| *h == heading) else {
+            return 0;
+        };
+        self.by_depth.values().map(|row| row.pruned[i]).sum()
+    }
+
+    fn heat(count: u64, nodes: u64) -> char {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+        if count == 0 || nodes == 0 {
+            return ' ';
+        }
+        let i = (count * (RAMP.len() as u64 - 1)).div_ceil(nodes);
+        RAMP[i.min(RAMP.len() as u64 - 1) as usize] as char
+    }
+}
+
+impl fmt::Display for PruneHeatTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bu = Builder::new();
+        let mut header = vec!["depth", "nodes"];
+        header.extend(PRUNE_COLUMNS.iter().map(|(h, _)| *h));
+        header.extend(["lmr saved", "cuts", "avg cut idx"]);
+        bu.push_record(header);
+        for (depth, row) in self.by_depth.iter().rev() {
+            let cell = |count: u64| format!("{count} {}", Self::heat(count, row.nodes));
+            let mut record = vec![depth.to_string(), row.nodes.to_string()];
+            record.extend(row.pruned.iter().map(|&c| cell(c)));
+            record.push(cell(row.lmr_saved));
+            record.push(row.cuts.to_string());
+            record.push(match row.cuts {
+                0 => "-".to_string(),
+                cuts => format!("{:.2}", row.cut_move_index_total as f64 / cuts as f64),
+            });
+            bu.push_record(record);
+        }
+        write!(f, "{}", bu.build())
+    }
+}
+
 // line, branch or variation
 #[derive(Clone)]
 pub struct Trail {
@@ -296,6 +404,7 @@ pub struct Trail {
     path:          Variation,
     pv_for_ply:    Vec<Variation>,
     score_for_ply: Vec<Score>,
+    eval_for_ply:  Vec<Score>,
     root:          Board,
     positions:     Vec<Epd>,
 
@@ -319,6 +428,7 @@ impl Trail {
             path: Variation::new(),
             pv_for_ply: vec![Variation::new(); LEN_PLY],
             score_for_ply: vec![Score::zero(); LEN_PLY],
+            eval_for_ply: vec![Score::zero(); LEN_PLY],
             positions: vec![],
             refutations: vec![],
             refutation_scores: vec![],
@@ -329,7 +439,22 @@ impl Trail {
     }
 
     pub fn clear(&mut self) {
-        *self = Self::new(self.root.clone());
+        self.reset(self.root.clone());
+    }
+
+    /// as new, but keeps the per-ply buffers so that searching from here does not reallocate
+    pub fn reset(&mut self, root: Board) {
+        self.seldepth = 0;
+        self.path.clear();
+        self.pv_for_ply.iter_mut().for_each(Variation::clear);
+        self.score_for_ply.fill(Score::zero());
+        self.eval_for_ply.fill(Score::zero());
+        self.positions.clear();
+        self.refutations.clear();
+        self.refutation_scores.clear();
+        self.tree_crit = TreeCrit::default();
+        self.chess_tree = ChessTree::new(root.clone());
+        self.root = root;
     }
 
     pub fn set_tree_crit(&mut self, crit: TreeCrit) {
@@ -343,6 +468,11 @@ impl Trail {
         self.seldepth
     }
 
+    /// the max ply reached (including quiescence) is carried across re-searches of the root
+    pub fn set_selective_depth(&mut self, seldepth: Ply) {
+        self.seldepth = seldepth;
+    }
+
     pub fn root(&self) -> &Board {
         &self.root
     }
@@ -356,8 +486,11 @@ impl Trail {
     }
 
     fn board(&self, ply: i32) -> Board {
-        let ply = ply as usize;
-        self.root().make_moves_old(&self.path.take(ply))
+        let mut bd = self.root().clone();
+        for mv in self.path.moves().take(ply as usize) {
+            bd = bd.make_move(mv);
+        }
+        bd
     }
 
     pub fn pv(&self, n: &Node) -> Variation {
@@ -374,6 +507,28 @@ impl Trail {
         &self.pv_for_ply[0]
     }
 
+    /// static eval (pov side to move) of the node, for the eval swings along the path
+    pub fn set_eval(&mut self, n: &Node, eval: Score) {
+        self.eval_for_ply[n.ply as usize] = eval;
+    }
+
+    /// largest static eval swing (cp) from the last two plies, 0 at the root
+    pub fn eval_swing(&self, n: &Node) -> i32 {
+        let ply = n.ply as usize;
+        let Some(eval) = self.eval_for_ply[ply].cp() else {
+            return 0;
+        };
+        let swing = |back: usize| {
+            let prior = self.eval_for_ply[ply - back].cp()?;
+            // negamax: the parent's eval is from the opponent's pov
+            Some(match back {
+                1 => (eval as i32 + prior as i32).abs(),
+                _ => (eval as i32 - prior as i32).abs(),
+            })
+        };
+        (1..=ply.min(2)).filter_map(swing).max().unwrap_or_default()
+    }
+
     /// move goes from ply to ply+1
     /// null moves allowed
     pub fn push_move(&mut self, n: &Node, mv: Move) {
@@ -410,7 +565,7 @@ impl Trail {
     /// set pv to here (current pv)
     pub fn terminal(&mut self, n: &Node, sc: Score, e: Event) {
         let ply = n.ply as usize;
-        self.pv_for_ply[ply] = self.path.clone();
+        self.pv_for_ply[ply].clone_from(&self.path);
         trace!("set_pv:\n{self}");
         if self.tree_crit.accept(&self.path) {
             self.chess_tree.merge(&self.path, NodeDetails {
@@ -444,18 +599,19 @@ impl Trail {
         //     self.path.display_san(self.root())
         // );
 
-        let root = self.root();
-        let path = &self.path;
-        let mut var = path.clone();
+        // built in place, reusing the ply's buffer
+        let (pvs, pvs_above) = self.pv_for_ply.split_at_mut(ply + 1);
+        let var = &mut pvs[ply];
+        var.clone_from(&self.path);
         var.push(mv);
         // // debug_assert_eq!(var.last(), Some(&mv), "last moves don't match");
-        let pv_above = &self.pv_for_ply[ply + 1];
-        let extend = if !pv_above.is_empty() {
-            self.pv_for_ply[ply + 1].skip(ply + 1)
-        } else {
-            Variation::new()
-        };
-        var.extend(&extend);
+        let pv_above = &pvs_above[0];
+        if !pv_above.is_empty() {
+            var.extend_from_slice(&pv_above.as_slice()[ply + 1..]);
+        }
+
+        let var = &self.pv_for_ply[ply];
+        let (root, path) = (self.root(), &self.path);
         debug_assert!(
             var.len() <= self.selective_depth() as usize,
             "seldepth {sd} var : {var} {self:#}",
@@ -463,18 +619,17 @@ impl Trail {
         );
         debug_assert!(
             var.validate(root).is_ok(),
-            "update_pv: new pv at ply {ply}: {var} = cv ({path}) + mv ({mv}) + pv[{ply}+1][{ply}+1:] ({extend}) is invalid\nevent {e}\n{self:#}"
+            "update_pv: new pv at ply {ply}: {var} = cv ({path}) + mv ({mv}) + pv[{ply}+1][{ply}+1:] is invalid\nevent {e}\n{self:#}"
         );
         trace!("update_pv:\n{self}");
         if self.tree_crit.accept(&self.path) {
-            self.chess_tree.merge(&var, NodeDetails {
+            self.chess_tree.merge(var, NodeDetails {
                 n: *n,
                 e,
                 sc,
                 nt: BoundType::ExactPv,
             })
         }
-        self.pv_for_ply[ply] = var;
     }
 
     pub fn ignore_move(&mut self, n: &Node, sc: Score, _mv: Move, _e: Event) {
@@ -482,11 +637,13 @@ impl Trail {
     }
 
     /// we dont actually make the move - we futility prune it first
+    ///
+    /// recorded against the (unsearched) child node
     pub fn prune_move(&mut self, n: &Node, sc: Score, mv: Move, e: Event) {
         self.push_move(n, mv); // the move wont have been made
         if self.tree_crit.accept(&self.path) {
             self.chess_tree.merge(&self.path, NodeDetails {
-                n: *n,
+                n: n.new_child(),
                 e,
                 sc,
                 nt: BoundType::UpperAll,
@@ -536,6 +693,13 @@ impl Trail {
         }
     }
 
+    /// a reduced (lmr) search failed low, so no re-search at full depth was needed
+    pub fn lmr_saved(&mut self, n: &Node) {
+        if self.tree_crit.accept(&self.path) {
+            *self.chess_tree.lmr_saved.entry(n.depth).or_default() += 1;
+        }
+    }
+
     pub fn take_tree(&mut self) -> ChessTree {
         let board = self.root().clone();
         mem::replace(&mut self.chess_tree, ChessTree::new(board))
@@ -591,7 +755,10 @@ impl fmt::Display for Trail {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use odonata_base::catalog::Catalog;
+    use odonata_base::domain::info::Info;
     use odonata_base::infra::utils::ToStringOr;
     use test_log::test;
 
@@ -635,6 +802,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_prune_heat_table() {
+        let mut eng = ThreadedSearch::new();
+        eng.search.controller.debug_tree = true;
+        let lines = Arc::new(Mutex::new(vec![]));
+        let captured = Arc::clone(&lines);
+        eng.search.set_callback(move |info: &Info| {
+            captured.lock().unwrap().extend(info.string_text.clone());
+        });
+        let epd = Catalog::bratko_kopec()[0].clone();
+        eng.search(epd, TimeControl::Depth(4)).unwrap();
+        let lines = lines.lock().unwrap();
+        println!("{}", lines.join("\n"));
+        assert!(lines.iter().any(|l| l.contains("nmp") && l.contains("avg cut idx")));
+
+        let mut trail = Trail::new(Catalog::starting_board());
+        trail.set_tree_crit(TreeCrit {
+            enabled: true,
+            max_ply: 4,
+            ..TreeCrit::default()
+        });
+        // e4 is a cut node, with its cut on the 2nd move (after a6 was lmp pruned)
+        let (n, bd) = (Node::root(3), trail.root().clone());
+        let e4 = bd.parse_san_move("e4").unwrap();
+        let (n1, bd1) = (n.new_child(), bd.make_move(e4));
+        let (a6, e5) = (bd1.parse_san_move("a6").unwrap(), bd1.parse_san_move("e5").unwrap());
+        trail.push_move(&n, e4);
+        trail.prune_move(&n1, Score::zero(), a6, Event::LmpSuccess);
+        trail.push_move(&n1, e5);
+        trail.pop_move(&n1, e5);
+        trail.fail(&n1, Score::zero(), e5, Event::MoveScoreHigh);
+        trail.pop_move(&n, e4);
+        let table = trail.chess_tree.prune_heat_table();
+        println!("{table}");
+        assert_eq!(table.nodes(), 3);
+        assert_eq!(table.pruned("lmp"), 1);
+        assert!(table.to_string().contains("2.00"));
+    }
+
     #[test]
     fn tree_basics() -> anyhow::Result<()> {
         let mut tree = Tree::new();
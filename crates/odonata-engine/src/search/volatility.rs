@@ -0,0 +1,123 @@
+use std::fmt;
+
+use odonata_base::domain::node::Node;
+use odonata_base::infra::component::Component;
+use odonata_base::prelude::*;
+use odonata_base::PreCalc;
+
+/// Scales the futility and razor margins by an estimate of how volatile the position is -
+/// recent static eval swings along the search path, the pieces left to make tactics with,
+/// and the attacks on our king zone. Quiet positions get tighter margins (more pruning),
+/// sharp ones wider.
+///
+/// `enabled = false` is the ablation switch, restoring the constant margins for A/B testing.
+#[derive(Clone, Debug)]
+pub struct Volatility {
+    pub enabled:      bool,
+    max_depth:        Ply,
+    base_perc:        i32,
+    swing_perc:       i32, // % added per 100cp of eval swing
+    piece_perc:       i32, // % added per minor or major piece
+    king_danger_perc: i32, // % added per attacked square in our king zone
+    min_perc:         i32,
+    max_perc:         i32,
+}
+
+impl Default for Volatility {
+    fn default() -> Self {
+        Self {
+            enabled:          true,
+            max_depth:        4,
+            base_perc:        80,
+            swing_perc:       50,
+            piece_perc:       1,
+            king_danger_perc: 3,
+            min_perc:         60,
+            max_perc:         200,
+        }
+    }
+}
+
+impl Configurable for Volatility {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.max_depth.set(p.get("max_depth"))?;
+        self.base_perc.set(p.get("base_perc"))?;
+        self.swing_perc.set(p.get("swing_perc"))?;
+        self.piece_perc.set(p.get("piece_perc"))?;
+        self.king_danger_perc.set(p.get("king_danger_perc"))?;
+        self.min_perc.set(p.get("min_perc"))?;
+        self.max_perc.set(p.get("max_perc"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for Volatility {
+    fn new_game(&mut self) {
+        self.new_position();
+    }
+
+    fn new_position(&mut self) {}
+}
+
+impl fmt::Display for Volatility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{self:#?}")
+    }
+}
+
+impl Volatility {
+    /// margin scaling (as a percentage) for the node, 100 => the constant margins
+    #[inline]
+    pub fn scale_perc(&self, swing: i32, bd: &Board, n: &Node) -> i32 {
+        if !self.enabled || n.depth > self.max_depth {
+            return 100;
+        }
+        let us = bd.color_us();
+        let pieces = bd.material().minors_and_majors().total_count();
+        let king_zone = PreCalc::instance().king_attacks(bd.king(us));
+        let king_danger = (king_zone & bd.all_attacks_on(us)).popcount();
+        let perc = self.base_perc
+            + self.swing_perc * swing / 100
+            + self.piece_perc * pieces
+            + self.king_danger_perc * king_danger;
+        perc.clamp(self.min_perc, self.max_perc)
+    }
+
+    #[inline]
+    pub fn scale(margin: i32, perc: i32) -> i32 {
+        margin * perc / 100
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_log::test;
+
+    use super::*;
+
+    #[test]
+    fn test_volatility() {
+        let vol = Volatility::default();
+        let n = Node {
+            depth: 2,
+            ..Node::default()
+        };
+        let quiet = Board::parse_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let sharp = Board::parse_fen("r1b2rk1/ppp2ppp/8/3q4/8/2n5/P4PPP/R3K2R w KQ - 0 1").unwrap();
+        let p_quiet = vol.scale_perc(0, &quiet, &n);
+        let p_sharp = vol.scale_perc(0, &sharp, &n);
+        assert!(p_quiet < 100, "{p_quiet}");
+        assert!(p_sharp > p_quiet, "{p_sharp} {p_quiet}");
+        assert!(vol.scale_perc(300, &quiet, &n) > p_quiet);
+        assert_eq!(vol.scale_perc(10_000, &quiet, &n), vol.max_perc);
+        assert_eq!(Volatility::scale(200, 150), 300);
+
+        // too far from the horizon, or ablated
+        let deep = Node { depth: 8, ..n };
+        assert_eq!(vol.scale_perc(300, &sharp, &deep), 100);
+        let mut vol = vol;
+        vol.set(Param::new("enabled", "false")).unwrap();
+        assert_eq!(vol.scale_perc(300, &sharp, &n), 100);
+    }
+}
@@ -0,0 +1,378 @@
+use std::fmt;
+use std::sync::Arc;
+
+use odonata_base::domain::node::Node;
+use odonata_base::infra::component::{Component, State};
+use odonata_base::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::search::restrictions::Restrictions;
+
+/// Win, draw or loss from the point of view of the side to move. A cursed win (or blessed
+/// loss) is won (or lost) but for the fifty move rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    /// from a distance to zeroing (positive if the side to move wins) and the halfmove clock
+    pub fn from_dtz(dtz: i32, halfmove_clock: i32) -> Wdl {
+        match dtz {
+            0 => Wdl::Draw,
+            d if d > 0 && d + halfmove_clock <= 100 => Wdl::Win,
+            d if d > 0 => Wdl::CursedWin,
+            d if -d + halfmove_clock <= 100 => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        }
+    }
+
+    pub fn flip(self) -> Wdl {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+
+    /// cursed wins as wins and blessed losses as losses
+    pub fn ignoring_50_move_rule(self) -> Wdl {
+        match self {
+            Wdl::BlessedLoss => Wdl::Loss,
+            Wdl::CursedWin => Wdl::Win,
+            wdl => wdl,
+        }
+    }
+
+    /// wins found nearer the root score higher, and a cursed win is only just better than a draw
+    pub fn to_score(self, ply: Ply) -> Score {
+        match self {
+            Wdl::Loss => Score::tb_lose_in(ply),
+            Wdl::BlessedLoss => Score::from_cp(-1),
+            Wdl::Draw => Score::DRAW,
+            Wdl::CursedWin => Score::from_cp(1),
+            Wdl::Win => Score::tb_win_in(ply),
+        }
+    }
+}
+
+/// Probes Syzygy endgame tablebases, from the directories of `path` (separated as for the
+/// PATH environment variable, as is usual for the `SyzygyPath` uci option).
+///
+/// At the root, the moves that would throw away the tablebase result are excluded from the
+/// search. Within the search, positions reached by a capture or pawn move (where the WDL
+/// tables are exact) are scored from the tables without further search, at nodes with at
+/// least `probe_depth` remaining, or at any depth with fewer men than `probe_limit`.
+///
+/// Probing needs the "syzygy" feature, without which setting a path is an error.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tablebase {
+    pub enabled:          bool,
+    pub path:             String,
+    pub probe_depth:      Ply,
+    pub probe_limit:      i32, // most men probed, or 0 for the largest tables found
+    pub use_50_move_rule: bool,
+    pub root_filter:      bool,
+
+    #[serde(skip)]
+    pub hits: u64, // this search
+
+    #[serde(skip)]
+    tables: Option<Arc<syzygy::Tables>>,
+}
+
+impl Default for Tablebase {
+    fn default() -> Self {
+        Self {
+            enabled:          true,
+            path:             String::new(),
+            probe_depth:      1,
+            probe_limit:      0,
+            use_50_move_rule: true,
+            root_filter:      true,
+            hits:             0,
+            tables:           None,
+        }
+    }
+}
+
+impl fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tablebase")
+            .field("enabled", &self.enabled)
+            .field("path", &self.path)
+            .field("probe_depth", &self.probe_depth)
+            .field("probe_limit", &self.probe_limit)
+            .field("use_50_move_rule", &self.use_50_move_rule)
+            .field("root_filter", &self.root_filter)
+            .field("max_men", &self.max_men())
+            .finish()
+    }
+}
+
+impl Configurable for Tablebase {
+    fn set(&mut self, p: Param) -> Result<bool> {
+        self.enabled.set(p.get("enabled"))?;
+        self.path.set(p.get("path"))?;
+        self.probe_depth.set(p.get("probe_depth"))?;
+        self.probe_limit.set(p.get("probe_limit"))?;
+        self.use_50_move_rule.set(p.get("use_50_move_rule"))?;
+        self.root_filter.set(p.get("root_filter"))?;
+        Ok(p.is_modified())
+    }
+}
+
+impl Component for Tablebase {
+    fn set_state(&mut self, s: State) {
+        use State::*;
+        match s {
+            NewGame => {}
+            SetPosition => {}
+            StartSearch => self.hits = 0,
+            EndSearch => {}
+            StartDepthIteration(_) => {}
+            Shutdown => {}
+        }
+    }
+
+    fn new_game(&mut self) {}
+
+    fn new_position(&mut self) {}
+}
+
+impl Tablebase {
+    /// loads the tables of `path`, none if the path is empty (or "<empty>" from a gui)
+    pub fn reload(&mut self) -> Result<()> {
+        self.tables = None;
+        let path = self.path.trim();
+        if path.is_empty() || path == "<empty>" {
+            return Ok(());
+        }
+        let tables = syzygy::load(path).with_context(|| format!("loading syzygy tables from '{path}'"))?;
+        self.tables = Some(Arc::new(tables));
+        Ok(())
+    }
+
+    /// the most men in the tables loaded, 0 if none are
+    pub fn max_men(&self) -> i32 {
+        self.tables.as_deref().map_or(0, syzygy::max_men)
+    }
+
+    fn men_limit(&self) -> i32 {
+        match self.probe_limit {
+            0 => self.max_men(),
+            limit => limit.min(self.max_men()),
+        }
+    }
+
+    fn can_probe(&self, b: &Board) -> bool {
+        self.enabled
+            && self.tables.is_some()
+            && b.castling().is_empty()
+            && b.material().is_tb_position(self.men_limit())
+    }
+
+    fn adjust(&self, wdl: Wdl) -> Wdl {
+        match self.use_50_move_rule {
+            true => wdl,
+            false => wdl.ignoring_50_move_rule(),
+        }
+    }
+
+    /// the exact score of a node just after a capture or pawn move, if in the tables
+    pub fn probe_node(&mut self, b: &Board, n: &Node) -> Option<Score> {
+        if n.ply == 0 || b.halfmove_clock() != 0 || !self.can_probe(b) {
+            return None;
+        }
+        if n.depth < self.probe_depth && b.material().total_count() >= self.men_limit() {
+            return None;
+        }
+        let wdl = syzygy::probe_wdl_after_zeroing(self.tables.as_deref()?, b)?;
+        self.hits += 1;
+        Some(self.adjust(wdl).to_score(n.ply))
+    }
+
+    /// the result of each legal move, best first, or None if the position is not in the tables
+    pub fn root_wdls(&self, b: &Board) -> Option<Vec<(Move, Wdl)>> {
+        if !self.can_probe(b) {
+            return None;
+        }
+        let tables = self.tables.as_deref()?;
+        let mut wdls = Vec::new();
+        for &mv in b.legal_moves().iter() {
+            let child = b.make_move(mv);
+            let wdl = match child.legal_moves().is_empty() {
+                true if child.is_in_check(child.color_us()) => Wdl::Win,
+                true => Wdl::Draw,
+                false => Wdl::from_dtz(syzygy::probe_dtz(tables, &child)?, child.halfmove_clock()).flip(),
+            };
+            wdls.push((mv, self.adjust(wdl)));
+        }
+        wdls.sort_by_key(|&(_mv, wdl)| std::cmp::Reverse(wdl));
+        Some(wdls)
+    }
+
+    /// restricts the root moves to those keeping the best tablebase result, returning the
+    /// result and the moves kept (None if the position is not in the tables)
+    pub fn filter_root_moves(&mut self, b: &Board, res: &mut Restrictions) -> Option<(Wdl, MoveList)> {
+        if !self.root_filter {
+            return None;
+        }
+        let wdls = self
+            .root_wdls(b)?
+            .into_iter()
+            .filter(|(mv, _wdl)| res.search_moves.is_empty() || res.search_moves.contains(mv))
+            .collect_vec();
+        self.hits += wdls.len() as u64;
+        let best = wdls.first()?.1;
+        let kept: MoveList = wdls
+            .iter()
+            .filter(|&&(_mv, wdl)| wdl == best)
+            .map(|&(mv, _wdl)| mv)
+            .collect();
+        res.search_moves = kept.clone();
+        Some((best, kept))
+    }
+}
+
+#[cfg(feature = "syzygy")]
+mod syzygy {
+    use odonata_base::prelude::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::{CastlingMode, Chess};
+
+    use super::Wdl;
+
+    pub type Tables = shakmaty_syzygy::Tablebase<Chess>;
+
+    pub fn load(path: &str) -> Result<Tables> {
+        let mut tables = Tables::new();
+        for dir in std::env::split_paths(path) {
+            tables
+                .add_directory(&dir)
+                .with_context(|| format!("reading directory {}", dir.display()))?;
+        }
+        Ok(tables)
+    }
+
+    pub fn max_men(tables: &Tables) -> i32 {
+        tables.max_pieces() as i32
+    }
+
+    fn position(b: &Board) -> Option<Chess> {
+        let fen: Fen = b.to_fen().parse().ok()?;
+        fen.into_position(CastlingMode::Standard).ok()
+    }
+
+    pub fn probe_wdl_after_zeroing(tables: &Tables, b: &Board) -> Option<Wdl> {
+        let wdl = match tables.probe_wdl_after_zeroing(&position(b)?).ok()? {
+            shakmaty_syzygy::Wdl::Loss => Wdl::Loss,
+            shakmaty_syzygy::Wdl::BlessedLoss => Wdl::BlessedLoss,
+            shakmaty_syzygy::Wdl::Draw => Wdl::Draw,
+            shakmaty_syzygy::Wdl::CursedWin => Wdl::CursedWin,
+            shakmaty_syzygy::Wdl::Win => Wdl::Win,
+        };
+        Some(wdl)
+    }
+
+    pub fn probe_dtz(tables: &Tables, b: &Board) -> Option<i32> {
+        let dtz = tables.probe_dtz(&position(b)?).ok()?;
+        Some(dtz.ignore_rounding().0)
+    }
+}
+
+#[cfg(not(feature = "syzygy"))]
+mod syzygy {
+    use odonata_base::prelude::*;
+
+    use super::Wdl;
+
+    #[derive(Debug)]
+    pub enum Tables {}
+
+    pub fn load(_path: &str) -> Result<Tables> {
+        anyhow::bail!("syzygy tablebases need the engine built with the syzygy feature")
+    }
+
+    pub fn max_men(tables: &Tables) -> i32 {
+        match *tables {}
+    }
+
+    pub fn probe_wdl_after_zeroing(tables: &Tables, _b: &Board) -> Option<Wdl> {
+        match *tables {}
+    }
+
+    pub fn probe_dtz(tables: &Tables, _b: &Board) -> Option<i32> {
+        match *tables {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use odonata_base::epd::Epd;
+    use test_log::test;
+
+    use super::*;
+    use crate::search::engine::ThreadedSearch;
+
+    #[test]
+    fn test_wdl() {
+        assert_eq!(Wdl::from_dtz(0, 10), Wdl::Draw);
+        assert_eq!(Wdl::from_dtz(30, 70), Wdl::Win);
+        assert_eq!(Wdl::from_dtz(31, 70), Wdl::CursedWin);
+        assert_eq!(Wdl::from_dtz(-30, 70), Wdl::Loss);
+        assert_eq!(Wdl::from_dtz(-31, 70), Wdl::BlessedLoss);
+        assert_eq!(Wdl::CursedWin.flip(), Wdl::BlessedLoss);
+        assert_eq!(Wdl::CursedWin.ignoring_50_move_rule(), Wdl::Win);
+        assert!(Wdl::Win.to_score(3) > Wdl::Win.to_score(5));
+        assert!(Wdl::Win.to_score(5) < Score::we_win_in(100));
+        assert!(Wdl::CursedWin.to_score(5) > Wdl::Draw.to_score(5));
+        assert_eq!(Wdl::Loss.to_score(4), Wdl::Win.to_score(4).negate());
+        assert_eq!(Wdl::Win.to_score(4).tb_ply(), Some(4));
+        assert_eq!(Wdl::CursedWin.to_score(4).tb_ply(), None);
+    }
+
+    #[test]
+    fn test_tablebase_without_tables() {
+        let mut tb = Tablebase::default();
+        assert!(tb.reload().is_ok());
+        assert_eq!(tb.max_men(), 0);
+        let b = Epd::parse_epd("8/8/8/4k3/8/8/3QK3/8 w - - 0 1").unwrap().board();
+        assert_eq!(tb.root_wdls(&b), None);
+        assert_eq!(tb.filter_root_moves(&b, &mut Restrictions::none()), None);
+
+        tb.path = "/no/such/syzygy/dir".to_string();
+        assert!(tb.reload().is_err());
+        assert_eq!(tb.max_men(), 0);
+    }
+
+    /// with SYZYGY_PATH set to a directory of (at least) the 3-men tables
+    #[test]
+    fn test_tablebase_probes() {
+        let Ok(path) = std::env::var("SYZYGY_PATH") else {
+            return;
+        };
+        let mut eng = ThreadedSearch::new();
+        eng.set_option("SyzygyPath", &path).unwrap();
+        assert!(eng.search.tablebase.max_men() >= 3);
+
+        // only Qd2-d3+ and Qd2-d4+ hang the queen
+        let b = Epd::parse_epd("8/8/8/4k3/8/8/3QK3/8 w - - 0 1").unwrap().board();
+        let wdls = eng.search.tablebase.root_wdls(&b).unwrap();
+        assert_eq!(wdls[0].1, Wdl::Win);
+        assert!(wdls.iter().filter(|(_mv, wdl)| *wdl == Wdl::Draw).count() >= 1);
+
+        let res = eng.search(Epd::from_board(b.clone()), TimeControl::Depth(6)).unwrap();
+        let mv = res.supplied_move().unwrap();
+        assert!(wdls.contains(&(mv, Wdl::Win)), "{mv}");
+        assert!(res.tbhits > 0);
+    }
+}
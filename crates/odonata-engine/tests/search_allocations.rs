@@ -0,0 +1,75 @@
+//! A test binary of its own, as the counting allocator replaces the global allocator for
+//! every test in the binary. Run in release, single threaded:
+//!
+//! `cargo test --release -p odonata-engine --test search_allocations -- --test-threads=1`
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use odonata_base::boards::Position;
+use odonata_base::catalog::Catalog;
+use odonata_base::domain::node::Node;
+use odonata_base::domain::timecontrol::TimeControl;
+use odonata_base::mv::Move;
+use odonata_engine::search::algo::Search;
+use odonata_engine::search::engine::ThreadedSearch;
+use odonata_engine::search::trail::Trail;
+
+/// counts heap allocations made by the current thread, ignoring the test harness's threads
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[test]
+#[cfg_attr(debug_assertions, ignore = "debug assertions allocate, run with --release")]
+fn test_search_allocations() {
+    let epd = Catalog::bratko_kopec()[0].clone();
+    let mut eng = ThreadedSearch::new();
+    eng.search(epd.clone(), TimeControl::Depth(6)).unwrap();
+
+    let search = &mut eng.search;
+    let mut trail = Trail::new(epd.board());
+    let mut pos = Position::from_board(epd.board());
+    let mut search_once = |search: &mut Search| {
+        search.controller.set_running();
+        search.max_depth = 6;
+        trail.reset(pos.board().clone());
+        search
+            .alphabeta("begin", &mut trail, &mut pos, Node::root(6), Move::new_null())
+            .unwrap();
+    };
+
+    // warm up, growing the per-ply buffers (pvs, killers, position history) to size
+    search_once(search);
+
+    let nodes = search.clock.cumul_nodes_this_thread();
+    let before = ALLOCATIONS.with(Cell::get);
+    search_once(search);
+    let allocations = ALLOCATIONS.with(Cell::get) - before;
+    assert!(search.clock.cumul_nodes_this_thread() > nodes);
+    assert_eq!(allocations, 0, "{allocations} heap allocations during a depth 6 search");
+}